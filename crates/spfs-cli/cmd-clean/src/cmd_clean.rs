@@ -78,6 +78,39 @@ pub struct CmdClean {
     #[clap(long = "keep-proxies-with-no-links", group = "repo_data")]
     keep_proxies_with_no_links: bool,
 
+    /// Ignore the tag retention policies from the spfs config
+    ///
+    /// By default, any tag that matches one of the configured
+    /// retention policies is pruned according to that policy
+    /// instead of the pruning options given on the command line.
+    #[clap(long, group = "repo_data")]
+    ignore_retention_policies: bool,
+
+    /// Also keep any data that is tagged in this remote repository
+    ///
+    /// Use this when the cleaned repository shares its data with other
+    /// repositories, such as a payload store used by multiple users or
+    /// proxies. The tags in these repositories are never pruned. Can be
+    /// provided more than once.
+    #[clap(long = "root", value_name = "REMOTE", group = "repo_data")]
+    roots: Vec<String>,
+
+    /// Save the discovered set of attached objects to this file
+    ///
+    /// If the file already exists, the objects recorded in it are
+    /// assumed to still be attached and are not walked again, so that
+    /// an interrupted clean of a very large repository can be resumed.
+    /// The file is removed once the clean has been completed.
+    ///
+    /// This is always a local file, even when cleaning a remote repository,
+    /// and is not shared with the server or with any other clean.
+    #[clap(long, value_name = "PATH", group = "repo_data")]
+    mark_file: Option<std::path::PathBuf>,
+
+    /// Only discover and save the attached objects, without removing anything
+    #[clap(long, requires = "mark_file")]
+    mark_only: bool,
+
     // The number of concurrent tag stream scanning operations
     // that are buffered and allowed to run concurrently
     #[clap(
@@ -149,7 +182,27 @@ impl CmdClean {
             return Ok(0);
         }
 
-        let cleaner = spfs::Cleaner::new(&repo)
+        let mut roots = Vec::with_capacity(self.roots.len());
+        for remote in self.roots.iter() {
+            let mut root = config.get_remote(remote).await?;
+            // all of the tags in the root repositories must be
+            // considered, not just those in the current namespace
+            root.try_as_tag_mut()?.try_set_tag_namespace(None)?;
+            roots.push(root);
+        }
+
+        let retention = if self.ignore_retention_policies {
+            Vec::new()
+        } else {
+            config
+                .clean
+                .retention
+                .iter()
+                .map(spfs::RetentionPolicy::from_config)
+                .collect::<spfs::Result<Vec<_>>>()?
+        };
+
+        let mut cleaner = spfs::Cleaner::new(&repo)
             .with_reporter(spfs::clean::ConsoleCleanReporter::default())
             .with_dry_run(self.dry_run)
             .with_required_age(chrono::Duration::minutes(15))
@@ -161,10 +214,16 @@ impl CmdClean {
             .with_remove_proxies_with_no_links(!self.keep_proxies_with_no_links)
             .with_removal_concurrency(self.max_removal_concurrency)
             .with_discover_concurrency(self.max_discover_concurrency)
-            .with_tag_stream_concurrency(self.max_tag_stream_concurrency);
+            .with_tag_stream_concurrency(self.max_tag_stream_concurrency)
+            .with_mark_file(self.mark_file.clone())
+            .with_mark_only(self.mark_only)
+            .with_retention_policies(retention);
+        for root in roots.iter() {
+            cleaner = cleaner.with_root_repository(root);
+        }
 
         println!("{}", cleaner.format_plan());
-        if !self.dry_run && !self.yes {
+        if !self.dry_run && !self.yes && !self.mark_only {
             let answer = question::Question::new(
                 "This operation may remove data from the repository\n\
                  > Continue with the above plan?",
@@ -91,6 +91,10 @@ pub struct EnterArgs {
     #[clap(long)]
     metrics_in_env: bool,
 
+    /// Set up the runtime in a new, unprivileged user namespace
+    #[clap(long)]
+    rootless: bool,
+
     /// The command to run after initialization
     ///
     /// If not given, run an interactive shell environment
@@ -112,6 +116,13 @@ impl CommandName for CmdEnter {
 
 impl CmdEnter {
     pub fn run(&mut self, config: &spfs::Config) -> Result<i32> {
+        // a user namespace can only be entered by a single-threaded
+        // process, so this must happen before any async runtime exists
+        #[cfg(unix)]
+        if self.enter.rootless {
+            spfs::env::enter_user_namespace()?;
+        }
+
         // we need a single-threaded runtime in order to properly setup
         // and enter the namespace of the runtime
         let rt = tokio::runtime::Builder::new_current_thread()
@@ -324,6 +335,9 @@ impl CmdEnter {
                         copy_link_limit_count = %render_summary.copy_link_limit_count.load(Ordering::Relaxed),
                         copy_wrong_mode_count = %render_summary.copy_wrong_mode_count.load(Ordering::Relaxed),
                         copy_wrong_owner_count = %render_summary.copy_wrong_owner_count.load(Ordering::Relaxed),
+                        copy_compressed_count = %render_summary.copy_compressed_count.load(Ordering::Relaxed),
+                        copy_chunked_count = %render_summary.copy_chunked_count.load(Ordering::Relaxed),
+                        copy_xattrs_count = %render_summary.copy_xattrs_count.load(Ordering::Relaxed),
                         link_count = %render_summary.link_count.load(Ordering::Relaxed),
                         symlink_count = %render_summary.symlink_count.load(Ordering::Relaxed),
                         total_bytes_rendered = %render_summary.total_bytes_rendered.load(Ordering::Relaxed),
@@ -332,6 +346,9 @@ impl CmdEnter {
                         total_bytes_copied_link_limit = %render_summary.total_bytes_copied_link_limit.load(Ordering::Relaxed),
                         total_bytes_copied_wrong_mode = %render_summary.total_bytes_copied_wrong_mode.load(Ordering::Relaxed),
                         total_bytes_copied_wrong_owner = %render_summary.total_bytes_copied_wrong_owner.load(Ordering::Relaxed),
+                        total_bytes_copied_compressed = %render_summary.total_bytes_copied_compressed.load(Ordering::Relaxed),
+                        total_bytes_copied_chunked = %render_summary.total_bytes_copied_chunked.load(Ordering::Relaxed),
+                        total_bytes_copied_xattrs = %render_summary.total_bytes_copied_xattrs.load(Ordering::Relaxed),
                         total_bytes_linked = %render_summary.total_bytes_linked.load(Ordering::Relaxed),
                         sync_time = %sync_time,
                         render_time = %render_time,
@@ -82,6 +82,8 @@ pub struct CmdFuse {
     ///           as root/sudo.
     ///  remote - additional remote repository to read data from, can be given more
     ///           than once
+    ///  upperdir - a directory in which to store changes to the filesystem, which
+    ///           is required when mounting with 'rw'
     #[clap(long, short, value_delimiter = ',')]
     options: Vec<String>,
 
@@ -119,6 +121,7 @@ impl CmdFuse {
             uid: calling_uid,
             gid: calling_gid,
             remotes: Vec::new(),
+            upper_dir: None,
             mount_options: required_opts.into_iter().collect(),
         };
 
@@ -130,6 +133,9 @@ impl CmdFuse {
                         Some(("remote", name)) => {
                             opts.remotes.push(name.to_owned());
                         }
+                        Some(("upperdir", path)) => {
+                            opts.upper_dir = Some(path.into());
+                        }
                         Some(("uid", num)) if calling_uid.is_root() => {
                             opts.uid = num.parse::<u32>().map(nix::unistd::Uid::from_raw).map_err(
                                 |err| {
@@ -163,7 +169,15 @@ impl CmdFuse {
         tracing::debug!("FUSE Config: {opts:#?}");
 
         if opts.mount_options.contains(&MountOption::RW) {
-            bail!("rw mode is not supported, yet");
+            if opts.upper_dir.is_none() {
+                bail!("rw mode requires an upperdir to store changes");
+            }
+            // the read-only option is required by default, and
+            // must be removed when a writable mount was requested
+            opts.mount_options.remove(&MountOption::RO);
+        } else {
+            // changes cannot be made to a read-only mount
+            opts.upper_dir = None;
         }
 
         let mountpoint = self
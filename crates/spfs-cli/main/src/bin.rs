@@ -11,6 +11,8 @@ mod cmd_commit;
 mod cmd_config;
 mod cmd_diff;
 mod cmd_edit;
+mod cmd_export_oci;
+mod cmd_import_oci;
 mod cmd_info;
 mod cmd_init;
 mod cmd_layers;
@@ -18,6 +20,7 @@ mod cmd_log;
 mod cmd_ls;
 mod cmd_ls_tags;
 mod cmd_migrate;
+mod cmd_mirror;
 mod cmd_platforms;
 mod cmd_pull;
 mod cmd_push;
@@ -29,14 +32,18 @@ mod cmd_runtime_info;
 mod cmd_runtime_list;
 mod cmd_runtime_prune;
 mod cmd_runtime_remove;
+mod cmd_runtime_rollback;
+mod cmd_runtime_snapshot;
 mod cmd_search;
 #[cfg(feature = "server")]
 mod cmd_server;
 mod cmd_shell;
+mod cmd_sign;
 mod cmd_tag;
 mod cmd_tags;
 mod cmd_untag;
 mod cmd_version;
+mod cmd_watch_tags;
 mod cmd_write;
 
 use spfs_cli_common as cli;
@@ -73,6 +80,7 @@ pub enum Command {
     Run(cmd_run::CmdRun),
     Tag(cmd_tag::CmdTag),
     Untag(cmd_untag::CmdUntag),
+    Sign(cmd_sign::CmdSign),
     Shell(cmd_shell::CmdShell),
     Runtime(cmd_runtime::CmdRuntime),
     Layers(cmd_layers::CmdLayers),
@@ -81,7 +89,11 @@ pub enum Command {
     Info(cmd_info::CmdInfo),
     Pull(cmd_pull::CmdPull),
     Push(cmd_push::CmdPush),
+    Mirror(cmd_mirror::CmdMirror),
+    ExportOci(cmd_export_oci::CmdExportOci),
+    ImportOci(cmd_import_oci::CmdImportOci),
     Log(cmd_log::CmdLog),
+    WatchTags(cmd_watch_tags::CmdWatchTags),
     Search(cmd_search::CmdSearch),
     Diff(cmd_diff::CmdDiff),
     LsTags(cmd_ls_tags::CmdLsTags),
@@ -115,12 +127,14 @@ impl Opt {
             Command::Reset(cmd) => cmd.run(config).await,
             Command::Tag(cmd) => cmd.run(config).await,
             Command::Untag(cmd) => cmd.run(config).await,
+            Command::Sign(cmd) => cmd.run(config).await,
             Command::Runtime(cmd) => cmd.run(config).await,
             Command::Layers(cmd) => cmd.run(config).await,
             Command::Platforms(cmd) => cmd.run(config).await,
             Command::Tags(cmd) => cmd.run(config).await,
             Command::Info(cmd) => cmd.run(config).await,
             Command::Log(cmd) => cmd.run(config).await,
+            Command::WatchTags(cmd) => cmd.run(config).await,
             Command::Search(cmd) => cmd.run(config).await,
             Command::Diff(cmd) => cmd.run(config).await,
             Command::LsTags(cmd) => cmd.run(config).await,
@@ -133,6 +147,9 @@ impl Opt {
             Command::Shell(cmd) => cmd.run(config).await,
             Command::Pull(cmd) => cmd.run(config).await,
             Command::Push(cmd) => cmd.run(config).await,
+            Command::Mirror(cmd) => cmd.run(config).await,
+            Command::ExportOci(cmd) => cmd.run(config).await,
+            Command::ImportOci(cmd) => cmd.run(config).await,
             #[cfg(feature = "server")]
             Command::Server(cmd) => cmd.run(config).await,
             Command::External(args) => run_external_subcommand(args.clone()).await,
@@ -27,6 +27,11 @@ pub struct CmdCheck {
     #[clap(long)]
     pull: Option<Option<String>>,
 
+    /// Also verify that layers and platforms are signed by one of
+    /// the trusted keys in the spfs config
+    #[clap(long)]
+    verify_signatures: bool,
+
     /// Objects to recursively check, defaults to everything
     #[clap(name = "REF")]
     reference: Vec<String>,
@@ -64,6 +69,13 @@ impl CmdCheck {
         if let Some(pull_from) = &pull_from {
             checker = checker.with_repair_source(pull_from);
         }
+        if self.verify_signatures {
+            let trusted_keys = spfs::signing::load_trusted_keys(&config.signing)?;
+            if trusted_keys.is_empty() {
+                miette::bail!("No trusted keys configured, cannot --verify-signatures");
+            }
+            checker = checker.with_trusted_keys(trusted_keys);
+        }
         let mut summary = spfs::check::CheckSummary::default();
         let start = std::time::Instant::now();
         if self.reference.is_empty() {
@@ -96,9 +108,13 @@ impl CmdCheck {
             repaired_payloads,
             checked_payloads,
             checked_payload_bytes,
+            missing_signatures,
+            invalid_signatures,
         } = summary;
         let missing_objects = missing_objects.len();
         let missing_payloads = missing_payloads.len();
+        let missing_signatures = missing_signatures.len();
+        let invalid_signatures = invalid_signatures.len();
 
         println!("{} after {duration:.0?}:", "Finished".bold());
         let missing = "missing".red().italic();
@@ -115,6 +131,13 @@ impl CmdCheck {
             NumberPrefix::Prefixed(p, amt) => format!("{amt:.2} {}B", p.symbol()),
         };
         println!("{human_bytes:>12} total payload footprint");
+        if self.verify_signatures {
+            let invalid = "invalid".red().italic();
+            let untrusted = missing_signatures + invalid_signatures;
+            println!(
+                "{untrusted:>12} untrusted objects ({missing_signatures} {missing}, {invalid_signatures} {invalid})",
+            );
+        }
 
         if missing_objects + missing_payloads != 0 {
             if pull_from.is_none() {
@@ -122,6 +145,9 @@ impl CmdCheck {
             }
             return Ok(1);
         }
+        if missing_signatures + invalid_signatures != 0 {
+            return Ok(1);
+        }
         println!("No issues found");
         Ok(0)
     }
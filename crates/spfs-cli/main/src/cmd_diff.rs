@@ -3,29 +3,201 @@
 // https://github.com/spkenv/spk
 
 use clap::Args;
-use miette::Result;
+use miette::{Context, IntoDiagnostic, Result};
+use serde_json::json;
+use spfs::tracking::{Diff, DiffMode, Entry, MergeMode};
 
 /// Compare two spfs file system states
+///
+/// When a merge base is given, the two states are instead compared
+/// as separate sets of changes to that base, reporting any paths
+/// that were changed differently by each one. In this mode, the
+/// exit code is 1 if any conflicting changes are found.
 #[derive(Debug, Args)]
 pub struct CmdDiff {
+    /// Also show the changes to the contents of text files
+    #[clap(long, short = 'p', visible_alias = "patch")]
+    content: bool,
+
+    /// Show a summary of the changes to each file's size
+    #[clap(long, conflicts_with_all = &["content", "merge_base"])]
+    stat: bool,
+
+    /// Output the changes as json
+    #[clap(long, conflicts_with = "stat")]
+    json: bool,
+
+    /// Compare FROM and TO as two sets of changes made to this tag or id
+    #[clap(long = "base", value_name = "BASE")]
+    merge_base: Option<String>,
+
     /// The tag or id to use as the base of the computed diff, defaults to the current runtime
+    ///
+    /// In a three-way diff, these are 'our' changes.
     #[clap(value_name = "FROM")]
     base: Option<String>,
 
     /// The tag or id to diff the base against, defaults to the contents of the spfs filesystem
+    ///
+    /// In a three-way diff, these are 'their' changes.
     #[clap(value_name = "TO")]
     top: Option<String>,
 }
 
 impl CmdDiff {
-    pub async fn run(&mut self, _config: &spfs::Config) -> Result<i32> {
+    pub async fn run(&mut self, config: &spfs::Config) -> Result<i32> {
+        let repo = if self.content {
+            Some(config.get_local_repository_handle().await?)
+        } else {
+            None
+        };
+        if let Some(merge_base) = &self.merge_base {
+            return self.run_three_way(merge_base, repo.as_ref()).await;
+        }
+
         let diffs = spfs::diff(self.base.as_ref(), self.top.as_ref()).await?;
+        let changes = diffs.iter().filter(|d| !d.mode.is_unchanged());
+        if self.json {
+            let mut output = Vec::new();
+            for diff in changes {
+                let mut value = diff_json(&diff.mode);
+                value["path"] = json!(diff.path.as_str());
+                if let Some(repo) = &repo {
+                    let content =
+                        spfs::diff_content(repo, &diff.path, &diff.mode, self.top.is_none())
+                            .await?;
+                    value["content"] = json!(content);
+                }
+                output.push(value);
+            }
+            print_json(&output)?;
+            return Ok(0);
+        }
+
+        if self.stat {
+            println!("{}", spfs::io::format_diff_stat(changes));
+            return Ok(0);
+        }
+
         let out = spfs::io::format_changes(diffs.iter());
         if out.trim().is_empty() {
             tracing::info!("no changes");
+            return Ok(0);
+        }
+        println!("{out}");
+        if let Some(repo) = &repo {
+            for diff in changes {
+                print_content(repo, diff, self.top.is_none()).await?;
+            }
+        }
+        Ok(0)
+    }
+
+    async fn run_three_way(
+        &self,
+        merge_base: &String,
+        repo: Option<&spfs::storage::RepositoryHandle>,
+    ) -> Result<i32> {
+        let diffs = spfs::diff_three_way(merge_base, self.base.as_ref(), self.top.as_ref()).await?;
+        let conflicts = diffs.iter().filter(|d| d.mode.is_conflict()).count();
+
+        if self.json {
+            let mut output = Vec::new();
+            for diff in diffs.iter() {
+                let (ours, theirs) = match &diff.mode {
+                    MergeMode::Ours(mode) => (Some(mode), None),
+                    MergeMode::Theirs(mode) => (None, Some(mode)),
+                    MergeMode::Both(mode) => (Some(mode), Some(mode)),
+                    MergeMode::Conflict(ours, theirs) => (Some(ours), Some(theirs)),
+                };
+                let mut value = json!({
+                    "path": diff.path.as_str(),
+                    "mode": diff.mode.to_string(),
+                    "ours": ours.map(diff_json),
+                    "theirs": theirs.map(diff_json),
+                });
+                if let (Some(repo), MergeMode::Conflict(ours, theirs)) = (repo, &diff.mode) {
+                    value["ours"]["content"] =
+                        json!(spfs::diff_content(repo, &diff.path, ours, false).await?);
+                    value["theirs"]["content"] = json!(
+                        spfs::diff_content(repo, &diff.path, theirs, self.top.is_none()).await?
+                    );
+                }
+                output.push(value);
+            }
+            print_json(&output)?;
+        } else if diffs.is_empty() {
+            tracing::info!("no changes");
         } else {
-            println!("{out}");
+            println!("{}", spfs::io::format_merge_diffs(diffs.iter()));
+            if let Some(repo) = repo {
+                for diff in diffs.iter() {
+                    let MergeMode::Conflict(ours, theirs) = &diff.mode else {
+                        continue;
+                    };
+                    // only their changes are computed from /spfs by default
+                    for (mode, from_spfs) in [(ours, false), (theirs, self.top.is_none())] {
+                        print_content(
+                            repo,
+                            &Diff {
+                                mode: mode.clone(),
+                                path: diff.path.clone(),
+                            },
+                            from_spfs,
+                        )
+                        .await?;
+                    }
+                }
+            }
+        }
+
+        if conflicts > 0 {
+            tracing::warn!("found {conflicts} conflicting changes");
+            return Ok(1);
         }
         Ok(0)
     }
 }
+
+/// Print the changes to the contents of a file, if it is text
+async fn print_content(
+    repo: &spfs::storage::RepositoryHandle,
+    diff: &Diff,
+    from_spfs: bool,
+) -> Result<()> {
+    if let Some(content) = spfs::diff_content(repo, &diff.path, &diff.mode, from_spfs).await? {
+        print!("{content}");
+    }
+    Ok(())
+}
+
+fn print_json(value: &[serde_json::Value]) -> Result<()> {
+    serde_json::to_writer_pretty(std::io::stdout(), value)
+        .into_diagnostic()
+        .wrap_err("Failed to generate json output")?;
+    println!(); // the trailing new line is nice for interactive shells
+    Ok(())
+}
+
+fn diff_json(mode: &DiffMode) -> serde_json::Value {
+    let (kind, before, after) = match mode {
+        DiffMode::Unchanged(a) => ("unchanged", Some(a), Some(a)),
+        DiffMode::Changed(a, b) => ("changed", Some(a), Some(b)),
+        DiffMode::Added(b) => ("added", None, Some(b)),
+        DiffMode::Removed(a) => ("removed", Some(a), None),
+    };
+    json!({
+        "change": kind,
+        "before": before.map(entry_json),
+        "after": after.map(entry_json),
+    })
+}
+
+fn entry_json(entry: &Entry) -> serde_json::Value {
+    json!({
+        "kind": entry.kind.to_string(),
+        "mode": format!("{:06o}", entry.mode),
+        "size": entry.size(),
+        "digest": entry.object.to_string(),
+    })
+}
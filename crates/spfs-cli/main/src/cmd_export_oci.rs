@@ -0,0 +1,68 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use std::path::PathBuf;
+
+use clap::Args;
+use miette::Result;
+use relative_path::RelativePathBuf;
+
+/// Export an spfs environment as an OCI container image
+///
+/// Each spfs layer is written as a separate image layer, with
+/// removed files represented as OCI whiteouts
+#[derive(Debug, Args)]
+pub struct CmdExportOci {
+    /// Read the environment from a remote repository instead of the local one
+    #[clap(long, short)]
+    remote: Option<String>,
+
+    /// Write a single tar archive of the image layout instead of a directory
+    #[clap(long)]
+    archive: bool,
+
+    /// The name to give the image in the layout index (eg: 'latest')
+    #[clap(long, value_name = "NAME")]
+    tag: Option<String>,
+
+    /// The directory within the image where the environment is placed
+    #[clap(long, default_value = spfs::oci::DEFAULT_PREFIX)]
+    prefix: RelativePathBuf,
+
+    /// The tag or digest of what to export, use a '+' to join multiple layers
+    reference: String,
+
+    /// The image layout directory or archive file to write
+    output: PathBuf,
+}
+
+impl CmdExportOci {
+    pub async fn run(&mut self, config: &spfs::Config) -> Result<i32> {
+        let repo = spfs::config::open_repository_from_string(config, self.remote.as_ref()).await?;
+        let env_spec = spfs::tracking::EnvSpec::parse(&self.reference)?;
+        let mut stack = spfs::graph::Stack::default();
+        for item in env_spec.iter() {
+            if item.is_livelayerfile() {
+                tracing::warn!("live layers cannot be exported, skipping: {item}");
+                continue;
+            }
+            stack.push(item.resolve_digest(&repo).await?);
+        }
+
+        let exporter = spfs::oci::OciExporter::new(&repo)
+            .with_prefix(self.prefix.clone())
+            .with_ref_name(self.tag.clone());
+        let descriptor = if self.archive {
+            exporter.export_archive(&stack, &self.output).await?
+        } else {
+            exporter.export_layout(&stack, &self.output).await?
+        };
+        tracing::info!(
+            digest = %descriptor.digest,
+            output = %self.output.display(),
+            "exported image"
+        );
+        Ok(0)
+    }
+}
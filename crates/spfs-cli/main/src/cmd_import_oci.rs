@@ -0,0 +1,73 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use std::path::PathBuf;
+
+use clap::Args;
+use miette::Result;
+use relative_path::RelativePathBuf;
+use spfs::encoding::prelude::*;
+use spfs::prelude::*;
+
+/// Import an OCI or docker container image as an spfs platform
+///
+/// Each image layer becomes a separate spfs layer, with OCI
+/// whiteouts represented as masked files
+#[derive(Debug, Args)]
+pub struct CmdImportOci {
+    /// Import the image into a remote repository instead of the local one
+    #[clap(long, short)]
+    remote: Option<String>,
+
+    /// A human-readable tag for the generated platform
+    ///
+    /// Can be provided more than once.
+    #[clap(long = "tag", short)]
+    tags: Vec<String>,
+
+    /// The name of the image to import, when the input holds more than one
+    ///
+    /// This is the ref name of an image in an OCI layout (eg: 'latest'),
+    /// or one of the repo tags of an image in a docker archive.
+    #[clap(long, value_name = "NAME")]
+    image: Option<String>,
+
+    /// Only import this directory from within the image
+    ///
+    /// The contents of the directory are placed at the root of /spfs.
+    /// By default, the entire filesystem of the image is imported.
+    #[clap(long)]
+    prefix: Option<RelativePathBuf>,
+
+    /// An OCI image layout directory, or a tar archive of
+    /// an OCI image layout or the output of 'docker save'
+    input: PathBuf,
+}
+
+impl CmdImportOci {
+    pub async fn run(&mut self, config: &spfs::Config) -> Result<i32> {
+        let repo = spfs::config::open_repository_from_string(config, self.remote.as_ref()).await?;
+
+        let mut importer = spfs::oci::OciImporter::new(&repo).with_image_name(self.image.clone());
+        if let Some(prefix) = &self.prefix {
+            importer = importer.with_prefix(prefix.clone());
+        }
+        let platform = importer.import(&self.input).await?;
+        let digest = platform.digest()?;
+        tracing::info!(%digest, "created");
+
+        for tag in self.tags.iter() {
+            let tag_spec = match spfs::tracking::TagSpec::parse(tag) {
+                Ok(tag_spec) => tag_spec,
+                Err(err) => {
+                    tracing::warn!("cannot set invalid tag '{tag}': {err:?}");
+                    continue;
+                }
+            };
+            repo.push_tag(&tag_spec, &digest).await?;
+            tracing::info!(?tag, "created");
+        }
+        Ok(0)
+    }
+}
@@ -13,6 +13,13 @@ pub struct CmdMigrate {
     #[clap(long)]
     upgrade: bool,
 
+    /// Also rewrite all existing payloads using the given compression.
+    ///
+    /// The compression of newly written payloads is set separately,
+    /// in the configuration of each client that writes to the repository.
+    #[clap(long, value_name = "COMPRESSION")]
+    payload_compression: Option<spfs::storage::fs::PayloadCompression>,
+
     /// The path to the filesystem repository to migrate
     path: std::path::PathBuf,
 }
@@ -27,6 +34,11 @@ impl CmdMigrate {
             spfs::storage::fs::migrations::migrate_repo(repo_root).await?
         };
         tracing::info!(path = ?result, "migrated");
+        if let Some(compression) = self.payload_compression {
+            let count =
+                spfs::storage::fs::migrations::compress_payloads(&result, compression).await?;
+            tracing::info!(path = ?result, %compression, count, "rewrote payloads");
+        }
         Ok(0)
     }
 }
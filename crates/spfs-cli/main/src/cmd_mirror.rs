@@ -0,0 +1,83 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use std::path::PathBuf;
+use std::time::Duration;
+
+use clap::Args;
+use miette::Result;
+use relative_path::RelativePathBuf;
+use spfs::storage::TagNamespaceBuf;
+
+/// Continuously replicate the tags of one repository into another
+///
+/// The mirror remembers the newest entry of each tag stream that it
+/// has replicated, and only syncs tags that have changed since then
+#[derive(Debug, Args)]
+pub struct CmdMirror {
+    #[clap(short, long, action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// The name or address of the repository to mirror from
+    source: String,
+
+    /// The name or address of the repository to mirror into
+    dest: String,
+
+    /// Persist the position of the mirror in this file, so that
+    /// it can resume where it left off when restarted
+    #[clap(long, value_name = "FILE")]
+    cursor: Option<PathBuf>,
+
+    /// Only mirror tags under these paths (can be given more than once)
+    #[clap(long = "prefix", value_name = "PATH")]
+    prefixes: Vec<RelativePathBuf>,
+
+    /// Only mirror tags in this tag namespace
+    #[clap(long, value_name = "NAMESPACE")]
+    tag_namespace: Option<String>,
+
+    /// The number of seconds to wait between each mirror pass
+    #[clap(long, default_value_t = spfs::mirror::DEFAULT_MIRROR_INTERVAL.as_secs())]
+    interval: u64,
+
+    /// Run a single mirror pass and exit
+    #[clap(long)]
+    once: bool,
+}
+
+impl CmdMirror {
+    pub async fn run(&mut self, config: &spfs::Config) -> Result<i32> {
+        let (src, dest) = tokio::try_join!(
+            spfs::config::open_repository_from_string(config, Some(&self.source)),
+            spfs::config::open_repository_from_string(config, Some(&self.dest)),
+        )?;
+
+        let mut mirror = spfs::Mirror::new(&src, &dest)
+            .with_reporter(spfs::sync::ConsoleSyncReporter::default())
+            .with_tag_namespace(self.tag_namespace.as_ref().map(TagNamespaceBuf::new))
+            .with_prefixes(self.prefixes.iter().cloned());
+        if let Some(cursor) = &self.cursor {
+            mirror = mirror.with_cursor_file(cursor)?;
+        }
+
+        if !self.once {
+            mirror.run(Duration::from_secs(self.interval)).await?;
+            return Ok(0);
+        }
+
+        let summary = mirror.mirror_once().await?;
+        tracing::info!(
+            "{} tag streams checked, {} updated",
+            summary.checked_streams,
+            summary.updated_streams
+        );
+        tracing::info!("{}", spfs::io::format_sync_summary(&summary.sync));
+        if summary.failed_streams > 0 {
+            tracing::error!("{} tag streams failed to mirror", summary.failed_streams);
+            return Ok(1);
+        }
+        Ok(0)
+    }
+}
@@ -268,6 +268,7 @@ impl CmdRun {
             let start_time = Instant::now();
             runtime.config.mount_backend = config.filesystem.backend;
             runtime.config.secondary_repositories = config.get_secondary_runtime_repositories();
+            runtime.config.rootless = config.filesystem.rootless;
             if reference.is_empty() && !self.no_edit {
                 self.edit = true;
             } else if runtime.config.mount_backend.requires_localization() {
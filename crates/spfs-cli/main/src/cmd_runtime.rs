@@ -25,6 +25,8 @@ pub enum Command {
     List(super::cmd_runtime_list::CmdRuntimeList),
     Prune(super::cmd_runtime_prune::CmdRuntimePrune),
     Remove(super::cmd_runtime_remove::CmdRuntimeRemove),
+    Rollback(super::cmd_runtime_rollback::CmdRuntimeRollback),
+    Snapshot(super::cmd_runtime_snapshot::CmdRuntimeSnapshot),
 }
 
 impl Command {
@@ -34,6 +36,8 @@ impl Command {
             Self::List(cmd) => cmd.run(config).await,
             Self::Prune(cmd) => cmd.run(config).await,
             Self::Remove(cmd) => cmd.run(config).await,
+            Self::Rollback(cmd) => cmd.run(config).await,
+            Self::Snapshot(cmd) => cmd.run(config).await,
         }
     }
 }
@@ -0,0 +1,42 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use clap::Args;
+use miette::Result;
+
+/// Restore the edits of a durable runtime to a previous snapshot
+///
+/// All changes made since the snapshot was taken are discarded.
+/// The runtime must not be running.
+#[derive(Debug, Args)]
+pub struct CmdRuntimeRollback {
+    /// Use a runtime in a remote or alternate repository
+    #[clap(short, long)]
+    remote: Option<String>,
+
+    /// The name/id of the runtime to roll back
+    name: String,
+
+    /// The name of the snapshot to restore
+    snapshot: String,
+}
+
+impl CmdRuntimeRollback {
+    pub async fn run(&mut self, config: &spfs::Config) -> Result<i32> {
+        let runtime_storage = match &self.remote {
+            Some(remote) => {
+                let repo = config.get_remote(remote).await?;
+                spfs::runtime::Storage::new(repo)?
+            }
+            None => config.get_runtime_storage().await?,
+        };
+
+        let runtime = runtime_storage.read_runtime(&self.name).await?;
+        runtime_storage
+            .rollback_runtime(&runtime, &self.snapshot)
+            .await?;
+        tracing::info!(snapshot = %self.snapshot, "runtime rolled back");
+        Ok(0)
+    }
+}
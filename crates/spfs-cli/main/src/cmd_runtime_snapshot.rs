@@ -0,0 +1,55 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use clap::Args;
+use miette::Result;
+
+/// Save the current edits of a durable runtime, or list its snapshots
+///
+/// Snapshots can be restored using 'spfs runtime rollback'.
+#[derive(Debug, Args)]
+pub struct CmdRuntimeSnapshot {
+    /// Use a runtime in a remote or alternate repository
+    #[clap(short, long)]
+    remote: Option<String>,
+
+    /// The name/id of the runtime to snapshot
+    #[clap(env = "SPFS_RUNTIME")]
+    name: String,
+
+    /// The name of the snapshot to create, replacing any existing
+    /// snapshot with the same name
+    ///
+    /// When not given, the existing snapshots are listed instead.
+    snapshot: Option<String>,
+}
+
+impl CmdRuntimeSnapshot {
+    pub async fn run(&mut self, config: &spfs::Config) -> Result<i32> {
+        let runtime_storage = match &self.remote {
+            Some(remote) => {
+                let repo = config.get_remote(remote).await?;
+                spfs::runtime::Storage::new(repo)?
+            }
+            None => config.get_runtime_storage().await?,
+        };
+
+        let Some(snapshot) = &self.snapshot else {
+            for snapshot in runtime_storage.list_snapshots(&self.name).await? {
+                println!(
+                    "{:<20}\t{}\t{}",
+                    snapshot.name,
+                    snapshot.time.format("%Y-%m-%d %H:%M:%S"),
+                    snapshot.manifest
+                );
+            }
+            return Ok(0);
+        };
+
+        let runtime = runtime_storage.read_runtime(&self.name).await?;
+        let snapshot = runtime_storage.snapshot_runtime(&runtime, snapshot).await?;
+        tracing::info!(snapshot = %snapshot.name, manifest = %snapshot.manifest, "created");
+        Ok(0)
+    }
+}
@@ -3,7 +3,7 @@
 // https://github.com/spkenv/spk
 
 use clap::Args;
-use miette::Result;
+use miette::{Context, IntoDiagnostic, Result};
 use spfs_cli_common as cli;
 
 /// Start an spfs server
@@ -33,6 +33,25 @@ pub struct CmdServer {
     /// The address to listen on for http requests
     #[clap(default_value = "0.0.0.0:7787")]
     http_address: std::net::SocketAddr,
+
+    /// A PEM-encoded certificate chain used to serve both
+    /// gRPC and http requests over TLS
+    ///
+    /// The payloads root should then also be an https url
+    #[clap(long, requires = "tls_key")]
+    tls_cert: Option<std::path::PathBuf>,
+
+    /// The PEM-encoded private key of the TLS certificate
+    #[clap(long, requires = "tls_cert")]
+    tls_key: Option<std::path::PathBuf>,
+
+    /// A bundle of PEM-encoded certificate authorities used to
+    /// verify client certificates (mutual TLS)
+    ///
+    /// Client certificates are optional, but can be used to identify
+    /// clients as configured in the server.auth section of the config
+    #[clap(long, requires = "tls_cert")]
+    tls_client_ca: Option<std::path::PathBuf>,
 }
 
 impl CmdServer {
@@ -40,27 +59,58 @@ impl CmdServer {
         let repo = spfs::config::open_repository_from_string(config, self.remote.as_ref()).await?;
         let repo = std::sync::Arc::new(repo);
 
-        let payload_service =
-            spfs::server::PayloadService::new(repo.clone(), self.payloads_root.clone());
-        let http_server = {
-            let payload_service = payload_service.clone();
-            hyper::Server::bind(&self.http_address).serve(hyper::service::make_service_fn(
-                move |_| {
-                    let s = payload_service.clone();
-                    async move { Ok::<_, std::convert::Infallible>(s) }
-                },
-            ))
+        let auth = spfs::server::Auth::from_config(&config.server.auth);
+        if !auth.is_enabled() {
+            tracing::warn!("no client credentials are configured, all requests will be allowed");
+        }
+
+        let tls = match (&self.tls_cert, &self.tls_key) {
+            (Some(cert), Some(key)) => {
+                Some(spfs::server::TlsConfig::load(cert, key, self.tls_client_ca.as_deref()).await?)
+            }
+            _ => None,
         };
-        let http_future = http_server.with_graceful_shutdown(async {
-            if let Err(err) = tokio::signal::ctrl_c().await {
-                tracing::error!(?err, "Failed to setup graceful shutdown handler");
-            };
-            tracing::info!("shutting down http server...");
-        });
-        let grpc_future = tonic::transport::Server::builder()
+        if tls.is_some() && self.payloads_root.scheme() != "https" {
+            tracing::warn!(
+                "serving over TLS, but the payloads root is not an https url: {}",
+                self.payloads_root
+            );
+        }
+
+        let payload_service =
+            spfs::server::PayloadService::new(repo.clone(), self.payloads_root.clone())
+                .with_auth(auth.clone());
+        let http_listener = tokio::net::TcpListener::bind(self.http_address)
+            .await
+            .into_diagnostic()
+            .wrap_err("Failed to bind http address")?;
+        let http_future = payload_service
+            .clone()
+            .serve_http(http_listener, tls.as_ref(), async {
+                if let Err(err) = tokio::signal::ctrl_c().await {
+                    tracing::error!(?err, "Failed to setup graceful shutdown handler");
+                };
+                tracing::info!("shutting down http server...");
+            });
+        let mut grpc_server = tonic::transport::Server::builder();
+        if let Some(tls) = &tls {
+            grpc_server = grpc_server
+                .tls_config(tls.grpc_config())
+                .into_diagnostic()
+                .wrap_err("Invalid TLS configuration")?;
+        }
+        let grpc_future = grpc_server
             .add_service(spfs::server::Repository::new_srv())
-            .add_service(spfs::server::TagService::new_srv(repo.clone()))
-            .add_service(spfs::server::DatabaseService::new_srv(repo))
+            .add_service(
+                spfs::server::TagService::new(repo.clone())
+                    .with_auth(auth.clone())
+                    .into_srv(),
+            )
+            .add_service(
+                spfs::server::DatabaseService::new(repo)
+                    .with_auth(auth)
+                    .into_srv(),
+            )
             .add_service(payload_service.into_srv())
             .serve_with_shutdown(self.grpc_address, async {
                 if let Err(err) = tokio::signal::ctrl_c().await {
@@ -0,0 +1,55 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use std::path::PathBuf;
+
+use clap::Args;
+use miette::{Context, IntoDiagnostic, Result};
+use spfs::prelude::*;
+use spfs::signing::SigningKey;
+
+/// Sign layers and platforms with the configured signing key
+#[derive(Debug, Args)]
+pub struct CmdSign {
+    /// Store signatures in a remote repository instead of the local one
+    #[clap(long, short)]
+    remote: Option<String>,
+
+    /// Generate a new signing key into the given file and print
+    /// its public key, instead of signing anything
+    #[clap(long, value_name = "FILE", conflicts_with = "refs")]
+    generate_key: Option<PathBuf>,
+
+    /// The layers or platforms to sign
+    #[clap(value_name = "REF", required_unless_present = "generate_key")]
+    refs: Vec<String>,
+}
+
+impl CmdSign {
+    pub async fn run(&mut self, config: &spfs::Config) -> Result<i32> {
+        if let Some(path) = &self.generate_key {
+            let pkcs8 = SigningKey::generate_pkcs8()?;
+            std::fs::write(path, &pkcs8)
+                .into_diagnostic()
+                .wrap_err_with(|| format!("Failed to write {}", path.display()))?;
+            let name = config
+                .signing
+                .key_name
+                .as_deref()
+                .unwrap_or(spfs::config::DEFAULT_SIGNING_KEY_NAME);
+            let key = SigningKey::from_pkcs8(name, &pkcs8)?;
+            println!("{}", key.public_key().to_hex());
+            return Ok(0);
+        }
+
+        let key = spfs::signing::load_signing_key(&config.signing)?;
+        let repo = spfs::config::open_repository_from_string(config, self.remote.as_ref()).await?;
+        for reference in self.refs.iter() {
+            let digest = repo.read_ref(reference.as_str()).await?.digest()?;
+            let tag = spfs::signing::sign_object(&repo, digest, &key).await?;
+            tracing::info!(%digest, tag = %tag.path(), key = key.name(), "signed");
+        }
+        Ok(0)
+    }
+}
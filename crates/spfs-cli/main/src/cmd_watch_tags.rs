@@ -0,0 +1,67 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use chrono::Local;
+use clap::Args;
+use colored::*;
+use futures::TryStreamExt;
+use miette::Result;
+use relative_path::RelativePathBuf;
+use spfs::prelude::*;
+
+/// Print changes to tags as they are made
+///
+/// Each change is printed with a cursor that can be given to
+/// --since to resume watching from that point
+#[derive(Debug, Args)]
+pub struct CmdWatchTags {
+    /// Watch the tags of a remote repository instead of the local one
+    #[clap(long, short)]
+    remote: Option<String>,
+
+    /// Only print changes made after this cursor
+    #[clap(long, default_value_t = 0)]
+    since: u64,
+
+    /// Print the changes that have already been made and exit,
+    /// rather than waiting for new ones
+    #[clap(long)]
+    no_follow: bool,
+
+    /// Only print changes to tags under this path
+    #[clap(default_value = "")]
+    prefix: RelativePathBuf,
+}
+
+impl CmdWatchTags {
+    pub async fn run(&mut self, config: &spfs::Config) -> Result<i32> {
+        let repo = spfs::config::open_repository_from_string(config, self.remote.as_ref()).await?;
+
+        let mut changes: spfs::storage::TagChangeStream<'_> =
+            if self.no_follow {
+                let prefix = self.prefix.clone();
+                Box::pin(repo.read_tag_changes(self.since).try_filter(move |c| {
+                    futures::future::ready(c.spec.path().starts_with(&prefix))
+                }))
+            } else {
+                repo.watch_tag_changes(&self.prefix, self.since)
+            };
+        while let Some(change) = changes.try_next().await? {
+            let target = change
+                .tag
+                .as_ref()
+                .map(|t| t.target.to_string()[..10].to_string())
+                .unwrap_or_else(|| "-".repeat(10));
+            println!(
+                "{} {} {} {:<13} {}",
+                change.cursor.to_string().dimmed(),
+                change.time.with_timezone(&Local).to_string().green(),
+                target.yellow(),
+                change.kind.to_string(),
+                change.spec.to_string().bold(),
+            );
+        }
+        Ok(0)
+    }
+}
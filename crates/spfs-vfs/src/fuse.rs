@@ -8,6 +8,7 @@ use std::io::{Seek, SeekFrom};
 use std::mem::ManuallyDrop;
 use std::os::fd::{AsRawFd, FromRawFd};
 use std::os::unix::prelude::FileExt;
+use std::path::PathBuf;
 #[cfg(feature = "fuse-backend-abi-7-31")]
 use std::pin::Pin;
 use std::sync::atomic::{AtomicU64, Ordering};
@@ -20,12 +21,16 @@ use fuser::{
     FileAttr,
     FileType,
     MountOption,
+    ReplyCreate,
     ReplyData,
     ReplyDirectory,
     ReplyDirectoryPlus,
     ReplyEntry,
     ReplyOpen,
+    ReplyWrite,
+    ReplyXattr,
     Request,
+    TimeOrNow,
 };
 use spfs::prelude::*;
 #[cfg(feature = "fuse-backend-abi-7-31")]
@@ -34,6 +39,8 @@ use spfs::tracking::{Entry, EntryKind, EnvSpec, Manifest};
 use spfs::OsError;
 use tokio::io::AsyncReadExt;
 
+use self::upper::{Node, Upper};
+use crate::payload_cache::PayloadCache;
 use crate::Error;
 
 type Result<T> = std::result::Result<T, Error>;
@@ -55,12 +62,19 @@ pub struct Config {
     /// These are in addition to the local repository and
     /// are searched in order to find data.
     pub remotes: Vec<String>,
+    /// A directory in which to store changes made to the filesystem.
+    ///
+    /// The filesystem is only writable when this is set, and the
+    /// directory uses the same layout as an overlayfs upper directory.
+    pub upper_dir: Option<PathBuf>,
 }
 
 /// Handles the allocation of inodes, and async responses to all FUSE requests
 struct Filesystem {
     repos: Vec<Arc<spfs::storage::RepositoryHandle>>,
     opts: Config,
+    payload_cache: Option<Arc<PayloadCache>>,
+    upper: Option<Upper>,
 
     ttl: Duration,
     next_inode: AtomicU64,
@@ -81,11 +95,21 @@ impl Filesystem {
         repos: Vec<Arc<spfs::storage::RepositoryHandle>>,
         manifest: Manifest,
         opts: Config,
+        payload_cache: Option<Arc<PayloadCache>>,
     ) -> Self {
+        let upper = opts.upper_dir.clone().map(Upper::new);
+        let ttl = match upper {
+            // writable filesystems can change, so the kernel
+            // should not cache entries for very long
+            Some(_) => Duration::from_secs(1),
+            None => Duration::from_secs(u64::MAX),
+        };
         let fs = Self {
             repos,
             opts,
-            ttl: Duration::from_secs(u64::MAX),
+            payload_cache,
+            upper,
+            ttl,
             // the root inode must be 1, which we are about to allocate
             next_inode: AtomicU64::new(1),
             // we do not allocate handle 0, so skip it for now
@@ -102,7 +126,10 @@ impl Filesystem {
         // report this mode as a directory, the kernel will
         // not like our FUSE filesystem.
         root.mode = fs.opts.root_mode | libc::S_IFDIR;
-        fs.allocate_inodes(root);
+        let root = fs.allocate_inodes(root);
+        if let Some(upper) = &fs.upper {
+            upper.index(&root);
+        }
         fs
     }
 
@@ -122,6 +149,7 @@ impl Filesystem {
             entries,
             user_data: _,
             legacy_size,
+            xattrs,
         } = entry;
 
         let inode = self.allocate_inode();
@@ -136,6 +164,7 @@ impl Filesystem {
             entries,
             user_data: inode,
             legacy_size,
+            xattrs,
         });
         self.inodes.insert(inode, Arc::clone(&entry));
         entry
@@ -226,6 +255,9 @@ macro_rules! err {
     }};
 }
 
+// declared after the macros above so that they can be used within
+mod upper;
+
 // these functions mirror the actual fuse ones and
 // so we don't have much control over the shape
 #[allow(clippy::too_many_arguments)]
@@ -259,6 +291,10 @@ impl Filesystem {
             return;
         };
 
+        if let Some(upper) = &self.upper {
+            return self.lookup_upper(upper, parent, name, reply);
+        }
+
         let Some(parent) = self.inodes.get(&parent) else {
             reply.error(libc::ENOENT);
             return;
@@ -298,6 +334,10 @@ impl Filesystem {
     }
 
     async fn getattr(&self, ino: u64, reply: fuser::ReplyAttr) {
+        if let Some(upper) = &self.upper {
+            return self.getattr_upper(upper, ino, reply);
+        }
+
         let Some(inode) = self.inodes.get(&ino) else {
             reply.error(libc::ENOENT);
             return;
@@ -311,6 +351,12 @@ impl Filesystem {
     }
 
     async fn readlink(&self, ino: u64, reply: ReplyData) {
+        if let Some(path) = unwrap!(reply, self.upper_path(ino)) {
+            let link = unwrap!(reply, std::fs::read_link(path));
+            reply.data(link.as_os_str().as_encoded_bytes());
+            return;
+        }
+
         let Some(entry) = self.inodes.get(&ino).map(|kv| Arc::clone(kv.value())) else {
             reply.error(libc::ENOENT);
             return;
@@ -342,7 +388,78 @@ impl Filesystem {
         reply.data(data.as_slice());
     }
 
+    async fn getxattr(&self, ino: u64, name: OsString, size: u32, reply: ReplyXattr) {
+        if let Some(path) = unwrap!(reply, self.upper_path(ino)) {
+            match unwrap!(reply, xattr::get(path, name)) {
+                Some(value) => Self::reply_xattr(&value, size, reply),
+                None => reply.error(libc::ENODATA),
+            }
+            return;
+        }
+
+        let Some(entry) = self.inodes.get(&ino).map(|kv| Arc::clone(kv.value())) else {
+            reply.error(libc::ENOENT);
+            return;
+        };
+        let Some(value) = name.to_str().and_then(|name| entry.xattrs.get(name)) else {
+            reply.error(libc::ENODATA);
+            return;
+        };
+        Self::reply_xattr(value, size, reply);
+    }
+
+    async fn listxattr(&self, ino: u64, size: u32, reply: ReplyXattr) {
+        if let Some(path) = unwrap!(reply, self.upper_path(ino)) {
+            let mut names = Vec::new();
+            for name in unwrap!(reply, xattr::list(path)) {
+                names.extend_from_slice(name.as_encoded_bytes());
+                names.push(0);
+            }
+            Self::reply_xattr(&names, size, reply);
+            return;
+        }
+
+        let Some(entry) = self.inodes.get(&ino).map(|kv| Arc::clone(kv.value())) else {
+            reply.error(libc::ENOENT);
+            return;
+        };
+        // the list is a sequence of null-terminated names
+        let mut names = Vec::new();
+        for name in entry.xattrs.keys() {
+            names.extend_from_slice(name.as_bytes());
+            names.push(0);
+        }
+        Self::reply_xattr(&names, size, reply);
+    }
+
+    /// Reply to an xattr request, where a size of zero is
+    /// the caller asking how large of a buffer is needed
+    fn reply_xattr(data: &[u8], size: u32, reply: ReplyXattr) {
+        if size == 0 {
+            reply.size(data.len() as u32);
+        } else if data.len() > size as usize {
+            reply.error(libc::ERANGE);
+        } else {
+            reply.data(data);
+        }
+    }
+
     async fn open(&self, ino: u64, flags: i32, reply: ReplyOpen) {
+        if let Some(upper) = &self.upper {
+            let writing = flags & (libc::O_WRONLY | libc::O_RDWR | libc::O_TRUNC) != 0;
+            match unwrap!(reply, self.resolve(upper, ino)) {
+                None => {
+                    reply.error(libc::ENOENT);
+                    return;
+                }
+                // unmodified files are read from the repositories as usual
+                Some((_, Node::Lower(_))) if !writing => {}
+                Some((path, node)) => {
+                    return self.open_upper(upper, path, node, flags, reply).await
+                }
+            }
+        }
+
         let Some(entry) = self.inodes.get(&ino).map(|kv| Arc::clone(kv.value())) else {
             tracing::debug!("open {ino} = ENOENT");
             reply.error(libc::ENOENT);
@@ -350,7 +467,6 @@ impl Filesystem {
         };
 
         if flags & (libc::O_WRONLY | libc::O_RDWR) != 0 {
-            // TODO: support creating files?
             tracing::debug!("open {flags} = EROFS");
             reply.error(libc::EROFS);
             return;
@@ -374,8 +490,21 @@ impl Filesystem {
         #[allow(unused_mut)]
         let mut flags = FOPEN_KEEP_CACHE;
         for repo in self.repos.iter() {
+            // payloads that are compressed or chunked cannot be read directly
+            // from disk and are streamed like any remote payload
+            let must_stream = match &**repo {
+                spfs::storage::RepositoryHandle::FS(fs_repo) => match fs_repo.opened().await {
+                    Ok(fs_repo) => {
+                        !fs_repo.payloads.has_digest(digest)
+                            && (fs_repo.compressed_payloads.has_digest(digest)
+                                || fs_repo.chunks.has_digest(digest))
+                    }
+                    Err(_) => false,
+                },
+                _ => false,
+            };
             match &**repo {
-                spfs::storage::RepositoryHandle::FS(fs_repo) => {
+                spfs::storage::RepositoryHandle::FS(fs_repo) if !must_stream => {
                     let Ok(fs_repo) = fs_repo.opened().await else {
                         reply.error(libc::ENOENT);
                         return;
@@ -392,28 +521,62 @@ impl Filesystem {
                         Err(err) => err!(reply, err),
                     }
                 }
-                #[cfg(feature = "fuse-backend-abi-7-31")]
-                repo => match repo.open_payload(*digest).await {
-                    Ok((stream, _)) => {
-                        // TODO: try to leverage the returned file path?
-                        handle = Some(Handle::BlobStream {
-                            entry,
-                            stream: tokio::sync::Mutex::new(stream),
-                        });
-                        flags |= FOPEN_NONSEEKABLE | FOPEN_STREAM;
-                        break;
+                _ => {
+                    // payloads that cannot be read directly from disk are
+                    // copied into the local cache so that they can be seeked
+                    // and don't need to be read again the next time
+                    if let Some(cache) = &self.payload_cache {
+                        if let Some(file) = cache.open(digest) {
+                            handle = Some(Handle::BlobFile { entry, file });
+                            break;
+                        }
+                        // without streaming, the payload can only be
+                        // read once it has been copied into the cache
+                        #[cfg(not(feature = "fuse-backend-abi-7-31"))]
+                        match cache.open_or_populate(repo, *digest, entry.size()).await {
+                            Ok(Some(file)) => {
+                                handle = Some(Handle::BlobFile { entry, file });
+                                break;
+                            }
+                            Ok(None) => {}
+                            Err(spfs::Error::UnknownObject(_)) => continue,
+                            Err(err) => {
+                                tracing::warn!("Failed to cache payload {digest}: {err}");
+                            }
+                        }
+                    }
+                    #[cfg(feature = "fuse-backend-abi-7-31")]
+                    match repo.open_payload(*digest).await {
+                        Ok((stream, _)) => {
+                            // the payload is copied into the cache separately,
+                            // so that this open does not wait for all of it
+                            if let Some(cache) = &self.payload_cache {
+                                cache.populate_in_background(
+                                    Arc::clone(repo),
+                                    *digest,
+                                    entry.size(),
+                                );
+                            }
+                            // TODO: try to leverage the returned file path?
+                            handle = Some(Handle::BlobStream {
+                                entry,
+                                stream: tokio::sync::Mutex::new(stream),
+                            });
+                            flags |= FOPEN_NONSEEKABLE | FOPEN_STREAM;
+                            break;
+                        }
+                        Err(spfs::Error::UnknownObject(_)) => continue,
+                        Err(err) => err!(reply, err),
+                    }
+                    #[cfg(not(feature = "fuse-backend-abi-7-31"))]
+                    {
+                        tracing::error!(
+                            "Attempting to use unsupported repo type with fuse: {}",
+                            repo.address(),
+                        );
+                        reply.error(libc::ECONNREFUSED);
+                        return;
                     }
-                    Err(spfs::Error::UnknownObject(_)) => continue,
-                    Err(err) => err!(reply, err),
-                },
-                #[cfg(not(feature = "fuse-backend-abi-7-31"))]
-                repo => {
-                    tracing::error!(
-                        "Attempting to use unsupported repo type with fuse: {}",
-                        repo.address(),
-                    );
-                    reply.error(libc::ECONNREFUSED);
-                    return;
                 }
             }
         }
@@ -447,11 +610,11 @@ impl Filesystem {
         };
 
         match handle.value() {
-            Handle::Tree { .. } => {
+            Handle::Tree { .. } | Handle::UpperDir { .. } => {
                 tracing::debug!("read {fh} = EISDIR");
                 reply.error(libc::EISDIR);
             }
-            Handle::BlobFile { entry: _, file } => {
+            Handle::BlobFile { entry: _, file } | Handle::UpperFile { file } => {
                 // Safety: the fd must be valid and open, which we know. We also
                 // know that the file will live for the livetime of this function
                 // and so can create a copy of it safely for use before that rather
@@ -502,7 +665,7 @@ impl Filesystem {
         fh: u64,
         _flags: i32,
         _lock_owner: Option<u64>,
-        // ignore flush because we don't support write operations
+        // ignore flush because writes go directly to disk
         _flush: bool,
         reply: fuser::ReplyEmpty,
     ) {
@@ -515,6 +678,10 @@ impl Filesystem {
     }
 
     async fn opendir(&self, ino: u64, _flags: i32, reply: ReplyOpen) {
+        if let Some(upper) = &self.upper {
+            return self.opendir_upper(upper, ino, reply);
+        }
+
         let Some(entry) = self.inodes.get(&ino).map(|e| Arc::clone(e.value())) else {
             reply.error(libc::ENOENT);
             return;
@@ -545,7 +712,10 @@ impl Filesystem {
 
     async fn readdir(&self, _ino: u64, fh: u64, offset: i64, mut reply: ReplyDirectory) {
         tracing::trace!("readdir try_get_handle {fh} [{_ino}]");
-        let Some(entry) = self.handles.get(&fh).map(|h| h.value().entry_owned()) else {
+        if let Some(Handle::UpperDir { entries }) = self.handles.get(&fh).as_deref() {
+            return Self::readdir_upper(entries, offset, reply);
+        }
+        let Some(entry) = self.handles.get(&fh).and_then(|h| h.value().entry_owned()) else {
             reply.error(libc::EBADF);
             return;
         };
@@ -583,7 +753,10 @@ impl Filesystem {
 
     async fn readdirplus(&self, _ino: u64, fh: u64, offset: i64, mut reply: ReplyDirectoryPlus) {
         tracing::trace!("readdirplus try_get_handle {fh} @{offset}");
-        let Some(entry) = self.handles.get(&fh).map(|h| h.value().entry_owned()) else {
+        if let Some(Handle::UpperDir { entries }) = self.handles.get(&fh).as_deref() {
+            return self.readdirplus_upper(entries, offset, reply);
+        }
+        let Some(entry) = self.handles.get(&fh).and_then(|h| h.value().entry_owned()) else {
             reply.error(libc::EBADF);
             return;
         };
@@ -633,12 +806,12 @@ impl Filesystem {
         };
 
         let file = match handle.value() {
-            Handle::Tree { .. } => {
+            Handle::Tree { .. } | Handle::UpperDir { .. } => {
                 tracing::debug!("lseek {fh} = EISDIR");
                 reply.error(libc::EISDIR);
                 return;
             }
-            Handle::BlobFile { entry: _, file } => file,
+            Handle::BlobFile { entry: _, file } | Handle::UpperFile { file } => file,
             #[cfg(feature = "fuse-backend-abi-7-31")]
             Handle::BlobStream { .. } => {
                 tracing::warn!("FUSE should not allow seek calls on streams");
@@ -736,10 +909,28 @@ impl SessionInner {
                 };
 
                 let repos = repo.into_stack().into_iter().map(Arc::new).collect();
+                let payload_cache = match config.fuse.payload_cache_size_mb {
+                    0 => None,
+                    size_mb => {
+                        let root = config.fuse.payload_cache_root(&config.storage.root);
+                        match PayloadCache::new(root, size_mb * 1024 * 1024) {
+                            Ok(cache) => Some(Arc::new(cache)),
+                            Err(err) => {
+                                // the cache only makes reads faster, and
+                                // the filesystem works without it
+                                tracing::warn!(
+                                    "Failed to open payload cache, continuing without it: {err}"
+                                );
+                                None
+                            }
+                        }
+                    }
+                };
                 Ok(Arc::new(Filesystem::new(
                     repos,
                     manifest,
                     self.opts.clone(),
+                    payload_cache,
                 )))
             })
             .await
@@ -825,6 +1016,30 @@ impl fuser::Filesystem for Session {
         });
     }
 
+    fn getxattr(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        name: &OsStr,
+        size: u32,
+        reply: ReplyXattr,
+    ) {
+        let name = name.to_owned();
+        let session = Arc::clone(&self.inner);
+        tokio::task::spawn(async move {
+            let fs = unwrap!(reply, session.get_fs().await);
+            fs.getxattr(ino, name, size, reply).await
+        });
+    }
+
+    fn listxattr(&mut self, _req: &Request<'_>, ino: u64, size: u32, reply: ReplyXattr) {
+        let session = Arc::clone(&self.inner);
+        tokio::task::spawn(async move {
+            let fs = unwrap!(reply, session.get_fs().await);
+            fs.listxattr(ino, size, reply).await
+        });
+    }
+
     fn open(&mut self, _req: &Request<'_>, ino: u64, flags: i32, reply: ReplyOpen) {
         let session = Arc::clone(&self.inner);
         tokio::task::spawn(async move {
@@ -937,6 +1152,192 @@ impl fuser::Filesystem for Session {
             fs.lseek(ino, fh, offset, whence, reply).await
         });
     }
+
+    fn setattr(
+        &mut self,
+        _req: &Request<'_>,
+        ino: u64,
+        mode: Option<u32>,
+        uid: Option<u32>,
+        gid: Option<u32>,
+        size: Option<u64>,
+        atime: Option<TimeOrNow>,
+        mtime: Option<TimeOrNow>,
+        _ctime: Option<SystemTime>,
+        fh: Option<u64>,
+        _crtime: Option<SystemTime>,
+        _chgtime: Option<SystemTime>,
+        _bkuptime: Option<SystemTime>,
+        _flags: Option<u32>,
+        reply: fuser::ReplyAttr,
+    ) {
+        let session = Arc::clone(&self.inner);
+        tokio::task::spawn(async move {
+            let fs = unwrap!(reply, session.get_fs().await);
+            fs.setattr(ino, mode, uid, gid, size, atime, mtime, fh, reply)
+                .await
+        });
+    }
+
+    fn mkdir(
+        &mut self,
+        _req: &Request<'_>,
+        parent: u64,
+        name: &OsStr,
+        mode: u32,
+        umask: u32,
+        reply: ReplyEntry,
+    ) {
+        let Some(name) = name.to_str().map(String::from) else {
+            reply.error(libc::EINVAL);
+            return;
+        };
+        let session = Arc::clone(&self.inner);
+        tokio::task::spawn(async move {
+            let fs = unwrap!(reply, session.get_fs().await);
+            fs.mkdir(parent, name, mode, umask, reply).await
+        });
+    }
+
+    fn unlink(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: fuser::ReplyEmpty) {
+        let Some(name) = name.to_str().map(String::from) else {
+            reply.error(libc::EINVAL);
+            return;
+        };
+        let session = Arc::clone(&self.inner);
+        tokio::task::spawn(async move {
+            let fs = unwrap!(reply, session.get_fs().await);
+            fs.unlink(parent, name, reply).await
+        });
+    }
+
+    fn rmdir(&mut self, _req: &Request<'_>, parent: u64, name: &OsStr, reply: fuser::ReplyEmpty) {
+        let Some(name) = name.to_str().map(String::from) else {
+            reply.error(libc::EINVAL);
+            return;
+        };
+        let session = Arc::clone(&self.inner);
+        tokio::task::spawn(async move {
+            let fs = unwrap!(reply, session.get_fs().await);
+            fs.rmdir(parent, name, reply).await
+        });
+    }
+
+    fn symlink(
+        &mut self,
+        _req: &Request<'_>,
+        parent: u64,
+        link_name: &OsStr,
+        target: &std::path::Path,
+        reply: ReplyEntry,
+    ) {
+        let Some(name) = link_name.to_str().map(String::from) else {
+            reply.error(libc::EINVAL);
+            return;
+        };
+        let target = target.to_owned();
+        let session = Arc::clone(&self.inner);
+        tokio::task::spawn(async move {
+            let fs = unwrap!(reply, session.get_fs().await);
+            fs.symlink(parent, name, target, reply).await
+        });
+    }
+
+    fn rename(
+        &mut self,
+        _req: &Request<'_>,
+        parent: u64,
+        name: &OsStr,
+        newparent: u64,
+        newname: &OsStr,
+        flags: u32,
+        reply: fuser::ReplyEmpty,
+    ) {
+        let (Some(name), Some(newname)) = (
+            name.to_str().map(String::from),
+            newname.to_str().map(String::from),
+        ) else {
+            reply.error(libc::EINVAL);
+            return;
+        };
+        let session = Arc::clone(&self.inner);
+        tokio::task::spawn(async move {
+            let fs = unwrap!(reply, session.get_fs().await);
+            fs.rename(parent, name, newparent, newname, flags, reply)
+                .await
+        });
+    }
+
+    fn write(
+        &mut self,
+        _req: &Request<'_>,
+        _ino: u64,
+        fh: u64,
+        offset: i64,
+        data: &[u8],
+        _write_flags: u32,
+        _flags: i32,
+        _lock_owner: Option<u64>,
+        reply: ReplyWrite,
+    ) {
+        let data = data.to_vec();
+        let session = Arc::clone(&self.inner);
+        tokio::task::spawn(async move {
+            let fs = unwrap!(reply, session.get_fs().await);
+            fs.write(fh, offset, data, reply).await
+        });
+    }
+
+    fn flush(
+        &mut self,
+        _req: &Request<'_>,
+        _ino: u64,
+        fh: u64,
+        _lock_owner: u64,
+        reply: fuser::ReplyEmpty,
+    ) {
+        let session = Arc::clone(&self.inner);
+        tokio::task::spawn(async move {
+            let fs = unwrap!(reply, session.get_fs().await);
+            fs.flush(fh, reply).await
+        });
+    }
+
+    fn fsync(
+        &mut self,
+        _req: &Request<'_>,
+        _ino: u64,
+        fh: u64,
+        datasync: bool,
+        reply: fuser::ReplyEmpty,
+    ) {
+        let session = Arc::clone(&self.inner);
+        tokio::task::spawn(async move {
+            let fs = unwrap!(reply, session.get_fs().await);
+            fs.fsync(fh, datasync, reply).await
+        });
+    }
+
+    fn create(
+        &mut self,
+        _req: &Request<'_>,
+        parent: u64,
+        name: &OsStr,
+        mode: u32,
+        umask: u32,
+        flags: i32,
+        reply: ReplyCreate,
+    ) {
+        let Some(name) = name.to_str().map(String::from) else {
+            reply.error(libc::EINVAL);
+            return;
+        };
+        let session = Arc::clone(&self.inner);
+        tokio::task::spawn(async move {
+            let fs = unwrap!(reply, session.get_fs().await);
+            fs.create(parent, name, mode, umask, flags, reply).await
+        });
+    }
 }
 
 enum Handle {
@@ -957,15 +1358,25 @@ enum Handle {
     Tree {
         entry: Arc<Entry<u64>>,
     },
+    /// A handle to a file in the upper directory of a writable filesystem
+    UpperFile {
+        file: std::fs::File,
+    },
+    /// The merged contents of a directory in a writable filesystem,
+    /// as they were when the directory was opened
+    UpperDir {
+        entries: Vec<(String, FileAttr)>,
+    },
 }
 
 impl Handle {
-    fn entry_owned(&self) -> Arc<Entry<u64>> {
+    fn entry_owned(&self) -> Option<Arc<Entry<u64>>> {
         match self {
-            Self::BlobFile { entry, .. } => Arc::clone(entry),
+            Self::BlobFile { entry, .. } => Some(Arc::clone(entry)),
             #[cfg(feature = "fuse-backend-abi-7-31")]
-            Self::BlobStream { entry, .. } => Arc::clone(entry),
-            Self::Tree { entry } => Arc::clone(entry),
+            Self::BlobStream { entry, .. } => Some(Arc::clone(entry)),
+            Self::Tree { entry } => Some(Arc::clone(entry)),
+            Self::UpperFile { .. } | Self::UpperDir { .. } => None,
         }
     }
 }
@@ -0,0 +1,1017 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+//! Support for writable FUSE filesystems.
+//!
+//! Changes are stored in an upper directory on disk which uses the
+//! same layout as an overlayfs upper directory, so that runtimes can
+//! be committed and reset in the same way for every mount backend.
+//! Entries from the manifest are copied up into the upper directory
+//! before being modified, and removed entries are recorded as whiteout
+//! files (character devices with a 0/0 device number).
+//!
+//! Like overlayfs without the `redirect_dir` feature, directories that
+//! exist in the manifest cannot be renamed. This is reported as EXDEV,
+//! which causes most tools to fall back to copying the directory.
+
+use std::collections::HashSet;
+use std::ffi::OsStr;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{FileExt, MetadataExt, OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use dashmap::DashMap;
+use fuser::{
+    FileAttr,
+    FileType,
+    ReplyAttr,
+    ReplyCreate,
+    ReplyDirectory,
+    ReplyDirectoryPlus,
+    ReplyEmpty,
+    ReplyEntry,
+    ReplyOpen,
+    ReplyWrite,
+    TimeOrNow,
+    FUSE_ROOT_ID,
+};
+use spfs::prelude::*;
+use spfs::tracking::{BlobRead, Entry, EntryKind};
+use spfs::OsError;
+use tokio::io::AsyncWriteExt;
+
+use super::{Filesystem, Handle};
+
+#[cfg(test)]
+#[path = "./upper_test.rs"]
+mod upper_test;
+
+/// The location of an inode within the filesystem
+struct Location {
+    parent: u64,
+    name: String,
+}
+
+/// The upper directory of a writable filesystem, and the
+/// locations of all inodes so that they can be found within it
+pub(super) struct Upper {
+    root: PathBuf,
+    locations: DashMap<u64, Location>,
+    inodes: DashMap<(u64, String), u64>,
+    /// Held for each inode while it is copied up from the manifest,
+    /// so that concurrent requests only copy each one once
+    copy_up_locks: DashMap<u64, Arc<tokio::sync::Mutex<()>>>,
+}
+
+impl Upper {
+    pub fn new(root: PathBuf) -> Self {
+        Self {
+            root,
+            locations: Default::default(),
+            inodes: Default::default(),
+            copy_up_locks: Default::default(),
+        }
+    }
+
+    /// Record the location of every entry in the given manifest tree
+    pub fn index(&self, entry: &Entry<u64>) {
+        for (name, child) in entry.entries.iter() {
+            self.record(child.user_data, entry.user_data, name.clone());
+            self.index(child);
+        }
+    }
+
+    fn record(&self, ino: u64, parent: u64, name: String) {
+        self.inodes.insert((parent, name.clone()), ino);
+        self.locations.insert(ino, Location { parent, name });
+    }
+
+    /// Forget the inode at the given location, returning it
+    fn forget(&self, parent: u64, name: &str) -> Option<u64> {
+        let (_, ino) = self.inodes.remove(&(parent, name.to_owned()))?;
+        self.locations.remove(&ino);
+        Some(ino)
+    }
+
+    /// The path of an inode, relative to the root of the filesystem
+    fn relative_path(&self, ino: u64) -> Option<PathBuf> {
+        let mut names = Vec::new();
+        let mut current = ino;
+        while current != FUSE_ROOT_ID {
+            let location = self.locations.get(&current)?;
+            names.push(location.name.clone());
+            current = location.parent;
+        }
+        Some(names.iter().rev().collect())
+    }
+
+    /// The path of an entry in the given directory, relative
+    /// to the root of the filesystem
+    fn child_path(&self, parent: u64, name: &str) -> std::io::Result<PathBuf> {
+        self.relative_path(parent)
+            .map(|path| path.join(name))
+            .ok_or_else(|| errno(libc::ENOENT))
+    }
+}
+
+/// The current state of a path in a writable filesystem
+pub(super) enum Node {
+    /// The path has been added or modified in the upper directory
+    Upper(std::fs::Metadata),
+    /// The path is unmodified from the manifest
+    Lower(Arc<Entry<u64>>),
+}
+
+impl Node {
+    fn is_dir(&self) -> bool {
+        match self {
+            Self::Upper(meta) => meta.is_dir(),
+            Self::Lower(entry) => entry.is_dir(),
+        }
+    }
+}
+
+fn errno(code: i32) -> std::io::Error {
+    std::io::Error::from_raw_os_error(code)
+}
+
+/// Run blocking filesystem operations without holding up the async runtime
+async fn blocking<T, F>(op: F) -> std::io::Result<T>
+where
+    F: FnOnce() -> std::io::Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(op)
+        .await
+        .map_err(std::io::Error::other)?
+}
+
+/// Create each of the given directories under the root that
+/// does not already exist, with its given mode
+fn create_dirs(root: &Path, dirs: &[(PathBuf, u32)]) -> std::io::Result<()> {
+    for (dir, mode) in dirs {
+        let dir = root.join(dir);
+        if std::fs::symlink_metadata(&dir).is_ok() {
+            continue;
+        }
+        match std::fs::create_dir(&dir) {
+            Ok(()) => {}
+            Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(err),
+        }
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(*mode))?;
+    }
+    Ok(())
+}
+
+/// Create a whiteout file, marking the path as removed
+fn create_whiteout(path: &Path) -> std::io::Result<()> {
+    use nix::sys::stat::{mknod, Mode, SFlag};
+    mknod(path, SFlag::S_IFCHR, Mode::empty(), 0).map_err(std::io::Error::from)
+}
+
+fn open_options(flags: i32) -> std::fs::OpenOptions {
+    let mut options = std::fs::OpenOptions::new();
+    match flags & libc::O_ACCMODE {
+        libc::O_WRONLY => options.write(true),
+        libc::O_RDWR => options.read(true).write(true),
+        _ => options.read(true),
+    };
+    if flags & libc::O_ACCMODE != libc::O_RDONLY {
+        options
+            .append(flags & libc::O_APPEND != 0)
+            .truncate(flags & libc::O_TRUNC != 0);
+    }
+    options
+}
+
+fn time_or_now(time: TimeOrNow) -> SystemTime {
+    match time {
+        TimeOrNow::SpecificTime(time) => time,
+        TimeOrNow::Now => SystemTime::now(),
+    }
+}
+
+// these functions mirror the actual fuse ones and
+// so we don't have much control over the shape
+#[allow(clippy::too_many_arguments)]
+impl Filesystem {
+    /// Find the entry at the given path in the manifest
+    fn lower_entry(&self, path: &Path) -> Option<Arc<Entry<u64>>> {
+        let mut entry = self
+            .inodes
+            .get(&FUSE_ROOT_ID)
+            .map(|e| Arc::clone(e.value()))?;
+        for name in path.iter() {
+            let child = entry.entries.get(name.to_str()?)?;
+            if child.kind.is_mask() {
+                return None;
+            }
+            let ino = child.user_data;
+            entry = self.inodes.get(&ino).map(|e| Arc::clone(e.value()))?;
+        }
+        Some(entry)
+    }
+
+    /// Find the current state of the given path, if it exists
+    fn resolve_path(&self, upper: &Upper, path: &Path) -> std::io::Result<Option<Node>> {
+        if path.as_os_str().is_empty() {
+            // the root directory always reflects the manifest, and
+            // any changes are merged into it when it is listed
+            return Ok(self.lower_entry(path).map(Node::Lower));
+        }
+        match std::fs::symlink_metadata(upper.root.join(path)) {
+            Ok(meta) if spfs::runtime::is_removed_entry(&meta) => Ok(None),
+            Ok(meta) => Ok(Some(Node::Upper(meta))),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                Ok(self.lower_entry(path).map(Node::Lower))
+            }
+            // a parent of this path was replaced with a file or whiteout
+            Err(err) if err.raw_os_error() == Some(libc::ENOTDIR) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Find the path and current state of the given inode, if it exists
+    pub(super) fn resolve(
+        &self,
+        upper: &Upper,
+        ino: u64,
+    ) -> std::io::Result<Option<(PathBuf, Node)>> {
+        let Some(path) = upper.relative_path(ino) else {
+            return Ok(None);
+        };
+        Ok(self.resolve_path(upper, &path)?.map(|node| (path, node)))
+    }
+
+    /// The full path to the given inode on disk, if it is writable
+    /// and the inode has been added or modified in the upper directory
+    pub(super) fn upper_path(&self, ino: u64) -> std::io::Result<Option<PathBuf>> {
+        let Some(upper) = &self.upper else {
+            return Ok(None);
+        };
+        match self.resolve(upper, ino)? {
+            Some((path, Node::Upper(_))) => Ok(Some(upper.root.join(path))),
+            _ => Ok(None),
+        }
+    }
+
+    /// The inode at the given location, allocating one if needed
+    fn location_inode(&self, upper: &Upper, parent: u64, name: &str) -> u64 {
+        let ino = *upper
+            .inodes
+            .entry((parent, name.to_owned()))
+            .or_insert_with(|| self.allocate_inode());
+        upper.locations.entry(ino).or_insert_with(|| Location {
+            parent,
+            name: name.to_owned(),
+        });
+        ino
+    }
+
+    fn attr_from_metadata(&self, ino: u64, meta: &std::fs::Metadata) -> FileAttr {
+        let file_type = meta.file_type();
+        let kind = if file_type.is_dir() {
+            FileType::Directory
+        } else if file_type.is_symlink() {
+            FileType::Symlink
+        } else {
+            FileType::RegularFile
+        };
+        let mtime = meta.modified().unwrap_or(self.fs_creation_time);
+        FileAttr {
+            ino,
+            size: meta.len(),
+            blocks: meta.blocks(),
+            atime: meta.accessed().unwrap_or(mtime),
+            mtime,
+            ctime: mtime,
+            crtime: meta.created().unwrap_or(mtime),
+            kind,
+            perm: (meta.mode() & 0o7777) as u16,
+            nlink: meta.nlink() as u32,
+            uid: self.opts.uid.as_raw(),
+            gid: self.opts.gid.as_raw(),
+            rdev: 0,
+            blksize: Self::BLOCK_SIZE,
+            flags: 0,
+        }
+    }
+
+    fn attr_from_node(&self, ino: u64, node: &Node) -> Option<FileAttr> {
+        match node {
+            Node::Upper(meta) => Some(self.attr_from_metadata(ino, meta)),
+            Node::Lower(entry) => {
+                let mut attr = self.attr_from_entry(entry).ok()?;
+                attr.ino = ino;
+                Some(attr)
+            }
+        }
+    }
+
+    /// Open a payload from the first repository that has it
+    async fn open_lower_payload(
+        &self,
+        digest: spfs::encoding::Digest,
+    ) -> spfs::Result<Pin<Box<dyn BlobRead>>> {
+        for repo in self.repos.iter() {
+            match repo.open_payload(digest).await {
+                Ok((reader, _)) => return Ok(reader),
+                Err(spfs::Error::UnknownObject(_)) => continue,
+                Err(err) => return Err(err),
+            }
+        }
+        Err(spfs::Error::UnknownObject(digest))
+    }
+
+    /// The parent directories of the given path, each with the mode
+    /// that it should be created with in the upper directory
+    fn parent_dirs(&self, path: &Path) -> Vec<(PathBuf, u32)> {
+        let mut current = PathBuf::new();
+        let mut dirs = Vec::new();
+        for name in path.parent().into_iter().flat_map(Path::iter) {
+            current.push(name);
+            let mode = self
+                .lower_entry(&current)
+                .map(|entry| entry.mode & 0o7777)
+                .unwrap_or(0o777);
+            dirs.push((current.clone(), mode));
+        }
+        dirs
+    }
+
+    /// Ensure that all parent directories of the given path
+    /// exist in the upper directory
+    fn copy_up_parents(&self, upper: &Upper, path: &Path) -> std::io::Result<()> {
+        create_dirs(&upper.root, &self.parent_dirs(path))
+    }
+
+    /// Copy an entry from the manifest into the upper directory so
+    /// that it can be modified.
+    ///
+    /// The contents of files are only copied when `with_content` is
+    /// true, otherwise they are created empty.
+    async fn copy_up(
+        &self,
+        upper: &Upper,
+        path: &Path,
+        entry: &Entry<u64>,
+        with_content: bool,
+    ) -> spfs::Result<()> {
+        let ino = entry.user_data;
+        let lock = Arc::clone(upper.copy_up_locks.entry(ino).or_default().value());
+        let guard = lock.lock().await;
+        let result = self.copy_up_locked(upper, path, entry, with_content).await;
+        drop(guard);
+        drop(lock);
+        // locks are only kept around while other requests are waiting
+        upper
+            .copy_up_locks
+            .remove_if(&ino, |_, lock| Arc::strong_count(lock) == 1);
+        result
+    }
+
+    /// Copy up an entry while holding its copy up lock
+    async fn copy_up_locked(
+        &self,
+        upper: &Upper,
+        path: &Path,
+        entry: &Entry<u64>,
+        with_content: bool,
+    ) -> spfs::Result<()> {
+        let target = upper.root.join(path);
+        let write_err = |err| spfs::Error::RuntimeWriteError(target.clone(), err);
+        let exists = blocking({
+            let root = upper.root.clone();
+            let parents = self.parent_dirs(path);
+            let target = target.clone();
+            move || {
+                if std::fs::symlink_metadata(&target).is_ok() {
+                    return Ok(true);
+                }
+                create_dirs(&root, &parents)?;
+                Ok(false)
+            }
+        })
+        .await
+        .map_err(write_err)?;
+        if exists {
+            // copied up by another request while this one was waiting
+            return Ok(());
+        }
+
+        let mode = std::fs::Permissions::from_mode(entry.mode & 0o7777);
+        match entry.kind {
+            EntryKind::Mask => return Ok(()),
+            EntryKind::Tree => {
+                let target = target.clone();
+                blocking(move || {
+                    std::fs::create_dir(&target)?;
+                    std::fs::set_permissions(&target, mode)
+                })
+                .await
+                .map_err(write_err)?;
+            }
+            EntryKind::Blob(_) if entry.is_symlink() => {
+                let mut link = Vec::new();
+                let mut reader = self.open_lower_payload(entry.object).await?;
+                tokio::io::AsyncReadExt::read_to_end(&mut reader, &mut link)
+                    .await
+                    .map_err(write_err)?;
+                let target = target.clone();
+                // xattrs cannot be set on symlinks by most users
+                return blocking(move || {
+                    std::os::unix::fs::symlink(OsStr::from_bytes(&link), &target)
+                })
+                .await
+                .map_err(write_err);
+            }
+            EntryKind::Blob(_) => {
+                let mut file = tokio::fs::OpenOptions::new()
+                    .write(true)
+                    .create_new(true)
+                    .mode(0o600)
+                    .open(&target)
+                    .await
+                    .map_err(write_err)?;
+                if with_content {
+                    let mut reader = self.open_lower_payload(entry.object).await?;
+                    tokio::io::copy(&mut reader, &mut file)
+                        .await
+                        .map_err(write_err)?;
+                }
+                file.flush().await.map_err(write_err)?;
+                tokio::fs::set_permissions(&target, mode)
+                    .await
+                    .map_err(write_err)?;
+            }
+        }
+        let xattrs = entry.xattrs.clone();
+        let target = target.clone();
+        blocking(move || {
+            for (name, value) in xattrs.iter() {
+                // this is best effort, as some namespaces (eg: security and
+                // trusted) cannot be written without additional privileges
+                if let Err(err) = xattr::set(&target, name, value) {
+                    tracing::debug!(?target, "failed to copy up xattr {name}: {err}");
+                }
+            }
+            Ok(())
+        })
+        .await
+        .map_err(write_err)
+    }
+
+    /// Prepare the upper directory for a new entry at the given path,
+    /// returning true if it replaces an entry that was removed
+    fn prepare_new_entry(&self, upper: &Upper, path: &Path) -> std::io::Result<bool> {
+        self.copy_up_parents(upper, path)?;
+        let target = upper.root.join(path);
+        match std::fs::symlink_metadata(&target) {
+            Ok(meta) if spfs::runtime::is_removed_entry(&meta) => {
+                std::fs::remove_file(&target)?;
+                Ok(true)
+            }
+            Ok(_) => Err(errno(libc::EEXIST)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Remove an entry from the upper directory, leaving a whiteout
+    /// in its place if the path also exists in the manifest
+    fn remove_entry(&self, upper: &Upper, path: &Path) -> std::io::Result<()> {
+        let target = upper.root.join(path);
+        match std::fs::symlink_metadata(&target) {
+            Ok(meta) if meta.is_dir() => std::fs::remove_dir_all(&target)?,
+            Ok(_) => std::fs::remove_file(&target)?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err),
+        }
+        if self.lower_entry(path).is_some() {
+            self.copy_up_parents(upper, path)?;
+            create_whiteout(&target)?;
+        }
+        Ok(())
+    }
+
+    /// Open a file in the upper directory, copying it up from
+    /// the manifest first if needed
+    async fn open_upper_file(
+        &self,
+        upper: &Upper,
+        path: &Path,
+        node: &Node,
+        flags: i32,
+    ) -> spfs::Result<std::fs::File> {
+        match node {
+            Node::Upper(meta) if meta.is_dir() => {
+                return Err(spfs::Error::new_errno(
+                    libc::EISDIR,
+                    format!("cannot open directory as a file: {path:?}"),
+                ));
+            }
+            Node::Upper(_) => {}
+            Node::Lower(entry) => {
+                let with_content = flags & libc::O_TRUNC == 0;
+                self.copy_up(upper, path, entry, with_content).await?;
+            }
+        }
+        let target = upper.root.join(path);
+        open_options(flags)
+            .open(&target)
+            .map_err(|err| spfs::Error::RuntimeWriteError(target, err))
+    }
+
+    /// Create and open a new file with the given permissions
+    fn create_file(
+        &self,
+        upper: &Upper,
+        path: &Path,
+        mode: u32,
+        flags: i32,
+    ) -> std::io::Result<std::fs::File> {
+        self.prepare_new_entry(upper, path)?;
+        let target = upper.root.join(path);
+        // the file is created separately from being opened, because
+        // it may be created with flags that don't allow writing
+        std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(mode & 0o7777)
+            .open(&target)?;
+        open_options(flags).open(&target)
+    }
+
+    /// Create a new directory with the given permissions
+    fn make_dir(&self, upper: &Upper, path: &Path, mode: u32) -> std::io::Result<()> {
+        let replaced = self.prepare_new_entry(upper, path)?;
+        let target = upper.root.join(path);
+        std::fs::create_dir(&target)?;
+        if replaced {
+            // a directory that was removed may have had contents in the
+            // manifest, which must remain removed in the new directory
+            if let Some(lower) = self.lower_entry(path) {
+                for (name, child) in lower.entries.iter() {
+                    if !child.kind.is_mask() {
+                        create_whiteout(&target.join(name))?;
+                    }
+                }
+            }
+        }
+        let perms = std::fs::Permissions::from_mode(mode & 0o7777);
+        std::fs::set_permissions(&target, perms)
+    }
+
+    /// Create a new symlink pointing to the given location
+    fn make_symlink(&self, upper: &Upper, path: &Path, link: &Path) -> std::io::Result<()> {
+        self.prepare_new_entry(upper, path)?;
+        std::os::unix::fs::symlink(link, upper.root.join(path))
+    }
+
+    /// Remove a file or symlink from the filesystem
+    fn unlink_entry(&self, upper: &Upper, path: &Path) -> std::io::Result<()> {
+        match self.resolve_path(upper, path)? {
+            None => Err(errno(libc::ENOENT)),
+            Some(node) if node.is_dir() => Err(errno(libc::EISDIR)),
+            Some(_) => self.remove_entry(upper, path),
+        }
+    }
+
+    /// Remove an empty directory from the filesystem
+    fn remove_dir(&self, upper: &Upper, ino: u64, path: &Path) -> std::io::Result<()> {
+        match self.resolve_path(upper, path)? {
+            None => return Err(errno(libc::ENOENT)),
+            Some(node) if !node.is_dir() => return Err(errno(libc::ENOTDIR)),
+            Some(_) => {}
+        }
+        if !self.list_dir(upper, ino, path)?.is_empty() {
+            return Err(errno(libc::ENOTEMPTY));
+        }
+        self.remove_entry(upper, path)
+    }
+
+    /// Move an entry to a new location in the filesystem, replacing
+    /// any compatible entry at the destination unless `no_replace` is set
+    async fn rename_entry(
+        &self,
+        upper: &Upper,
+        src_path: &Path,
+        dst_path: &Path,
+        dst_ino: u64,
+        no_replace: bool,
+    ) -> spfs::Result<()> {
+        let src_target = upper.root.join(src_path);
+        let dst_target = upper.root.join(dst_path);
+        let src_err = |err| spfs::Error::RuntimeWriteError(src_target.clone(), err);
+        let dst_err = |err| spfs::Error::RuntimeWriteError(dst_target.clone(), err);
+        let Some(src) = self.resolve_path(upper, src_path).map_err(src_err)? else {
+            return Err(src_err(errno(libc::ENOENT)));
+        };
+        let dst = self.resolve_path(upper, dst_path).map_err(dst_err)?;
+        if let Some(dst) = &dst {
+            if no_replace {
+                return Err(dst_err(errno(libc::EEXIST)));
+            }
+            match (src.is_dir(), dst.is_dir()) {
+                (true, false) => return Err(dst_err(errno(libc::ENOTDIR))),
+                (false, true) => return Err(dst_err(errno(libc::EISDIR))),
+                (true, true) => {
+                    if !self
+                        .list_dir(upper, dst_ino, dst_path)
+                        .map_err(dst_err)?
+                        .is_empty()
+                    {
+                        return Err(dst_err(errno(libc::ENOTEMPTY)));
+                    }
+                }
+                (false, false) => {}
+            }
+        }
+        if src.is_dir()
+            && (self.lower_entry(src_path).is_some() || self.lower_entry(dst_path).is_some())
+        {
+            return Err(spfs::Error::new_errno(
+                libc::EXDEV,
+                format!("cannot rename directories from the manifest: {src_path:?}"),
+            ));
+        }
+
+        if let Node::Lower(entry) = &src {
+            self.copy_up(upper, src_path, entry, true).await?;
+        }
+        self.copy_up_parents(upper, dst_path).map_err(dst_err)?;
+        if matches!(&dst, Some(Node::Upper(meta)) if meta.is_dir()) {
+            // the directory is empty when merged, but may still hold whiteouts
+            std::fs::remove_dir_all(&dst_target).map_err(dst_err)?;
+        }
+        std::fs::rename(&src_target, &dst_target).map_err(src_err)?;
+        if self.lower_entry(src_path).is_some() {
+            create_whiteout(&src_target).map_err(src_err)?;
+        }
+        Ok(())
+    }
+
+    /// List the merged contents of a directory from the
+    /// upper directory and the manifest
+    fn list_dir(
+        &self,
+        upper: &Upper,
+        ino: u64,
+        path: &Path,
+    ) -> std::io::Result<Vec<(String, FileAttr)>> {
+        let mut entries = Vec::new();
+        // names in the upper directory replace or remove those in the manifest
+        let mut replaced = HashSet::new();
+        match std::fs::read_dir(upper.root.join(path)) {
+            Ok(read_dir) => {
+                for dirent in read_dir {
+                    let dirent = dirent?;
+                    let Some(name) = dirent.file_name().to_str().map(String::from) else {
+                        tracing::warn!("skipping non-unicode file name: {:?}", dirent.path());
+                        continue;
+                    };
+                    let meta = dirent.metadata()?;
+                    if !spfs::runtime::is_removed_entry(&meta) {
+                        let child = self.location_inode(upper, ino, &name);
+                        entries.push((name.clone(), self.attr_from_metadata(child, &meta)));
+                    }
+                    replaced.insert(name);
+                }
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => return Err(err),
+        }
+        if let Some(lower) = self.lower_entry(path) {
+            for (name, child) in lower.entries.iter() {
+                if replaced.contains(name) {
+                    continue;
+                }
+                let Ok(mut attr) = self.attr_from_entry(child) else {
+                    continue;
+                };
+                attr.ino = self.location_inode(upper, ino, name);
+                entries.push((name.clone(), attr));
+            }
+        }
+        Ok(entries)
+    }
+
+    pub(super) fn lookup_upper(&self, upper: &Upper, parent: u64, name: &str, reply: ReplyEntry) {
+        let Some(path) = upper.relative_path(parent).map(|p| p.join(name)) else {
+            reply.error(libc::ENOENT);
+            return;
+        };
+        let Some(node) = unwrap!(reply, self.resolve_path(upper, &path)) else {
+            reply.error(libc::ENOENT);
+            return;
+        };
+        let ino = self.location_inode(upper, parent, name);
+        let Some(attr) = self.attr_from_node(ino, &node) else {
+            reply.error(libc::ENOENT);
+            return;
+        };
+        reply.entry(&self.ttl, &attr, 0);
+    }
+
+    pub(super) fn getattr_upper(&self, upper: &Upper, ino: u64, reply: ReplyAttr) {
+        let Some((_, node)) = unwrap!(reply, self.resolve(upper, ino)) else {
+            reply.error(libc::ENOENT);
+            return;
+        };
+        let Some(attr) = self.attr_from_node(ino, &node) else {
+            reply.error(libc::ENOENT);
+            return;
+        };
+        reply.attr(&self.ttl, &attr);
+    }
+
+    pub(super) async fn open_upper(
+        &self,
+        upper: &Upper,
+        path: PathBuf,
+        node: Node,
+        flags: i32,
+        reply: ReplyOpen,
+    ) {
+        let file = unwrap!(
+            reply,
+            self.open_upper_file(upper, &path, &node, flags).await
+        );
+        let fh = self.allocate_handle(Handle::UpperFile { file });
+        tracing::trace!("open {path:?} = {fh} [UPPER]");
+        reply.opened(fh, 0);
+    }
+
+    pub(super) fn opendir_upper(&self, upper: &Upper, ino: u64, reply: ReplyOpen) {
+        let Some((path, node)) = unwrap!(reply, self.resolve(upper, ino)) else {
+            reply.error(libc::ENOENT);
+            return;
+        };
+        if !node.is_dir() {
+            reply.error(libc::ENOTDIR);
+            return;
+        }
+        let entries = unwrap!(reply, self.list_dir(upper, ino, &path));
+        let fh = self.allocate_handle(Handle::UpperDir { entries });
+        tracing::trace!("opendir {ino} = {fh} [UPPER]");
+        reply.opened(fh, 0);
+    }
+
+    pub(super) fn readdir_upper(
+        entries: &[(String, FileAttr)],
+        offset: i64,
+        mut reply: ReplyDirectory,
+    ) {
+        // offsets are the index of the next entry to be listed
+        for (i, (name, attr)) in entries.iter().enumerate().skip(offset as usize) {
+            if reply.add(attr.ino, i as i64 + 1, attr.kind, name) {
+                break;
+            }
+        }
+        reply.ok();
+    }
+
+    pub(super) fn readdirplus_upper(
+        &self,
+        entries: &[(String, FileAttr)],
+        offset: i64,
+        mut reply: ReplyDirectoryPlus,
+    ) {
+        for (i, (name, attr)) in entries.iter().enumerate().skip(offset as usize) {
+            if reply.add(attr.ino, i as i64 + 1, name, &self.ttl, attr, 0) {
+                break;
+            }
+        }
+        reply.ok();
+    }
+
+    pub(super) async fn setattr(
+        &self,
+        ino: u64,
+        mode: Option<u32>,
+        uid: Option<u32>,
+        gid: Option<u32>,
+        size: Option<u64>,
+        atime: Option<TimeOrNow>,
+        mtime: Option<TimeOrNow>,
+        fh: Option<u64>,
+        reply: ReplyAttr,
+    ) {
+        let Some(upper) = &self.upper else {
+            reply.error(libc::EROFS);
+            return;
+        };
+        let Some((path, node)) = unwrap!(reply, self.resolve(upper, ino)) else {
+            reply.error(libc::ENOENT);
+            return;
+        };
+        // all entries are owned by the same user, which cannot be changed
+        if uid.is_some_and(|uid| uid != self.opts.uid.as_raw())
+            || gid.is_some_and(|gid| gid != self.opts.gid.as_raw())
+        {
+            reply.error(libc::EPERM);
+            return;
+        }
+        if let Node::Lower(entry) = &node {
+            let with_content = size != Some(0);
+            unwrap!(reply, self.copy_up(upper, &path, entry, with_content).await);
+        }
+
+        let target = upper.root.join(&path);
+        let meta = unwrap!(reply, std::fs::symlink_metadata(&target));
+        // symlinks have no permissions or times of their own on linux,
+        // and the std functions below would follow them
+        if !meta.is_symlink() {
+            if let Some(mode) = mode {
+                let perms = std::fs::Permissions::from_mode(mode & 0o7777);
+                unwrap!(reply, std::fs::set_permissions(&target, perms));
+            }
+            if let Some(size) = size {
+                let handle = fh.and_then(|fh| self.handles.get(&fh));
+                match handle.as_deref() {
+                    Some(Handle::UpperFile { file }) => unwrap!(reply, file.set_len(size)),
+                    _ => {
+                        let file =
+                            unwrap!(reply, std::fs::OpenOptions::new().write(true).open(&target));
+                        unwrap!(reply, file.set_len(size));
+                    }
+                }
+            }
+            if atime.is_some() || mtime.is_some() {
+                let mut times = std::fs::FileTimes::new();
+                if let Some(atime) = atime {
+                    times = times.set_accessed(time_or_now(atime));
+                }
+                if let Some(mtime) = mtime {
+                    times = times.set_modified(time_or_now(mtime));
+                }
+                let file = unwrap!(reply, std::fs::File::open(&target));
+                unwrap!(reply, file.set_times(times));
+            }
+        }
+
+        let meta = unwrap!(reply, std::fs::symlink_metadata(&target));
+        reply.attr(&self.ttl, &self.attr_from_metadata(ino, &meta));
+    }
+
+    pub(super) async fn write(&self, fh: u64, offset: i64, data: Vec<u8>, reply: ReplyWrite) {
+        let Some(handle) = self.handles.get(&fh) else {
+            reply.error(libc::EBADF);
+            return;
+        };
+        let Handle::UpperFile { file } = handle.value() else {
+            reply.error(libc::EBADF);
+            return;
+        };
+        unwrap!(reply, file.write_all_at(&data, offset as u64));
+        tracing::trace!("write {fh} = {}", data.len());
+        reply.written(data.len() as u32);
+    }
+
+    pub(super) async fn flush(&self, fh: u64, reply: ReplyEmpty) {
+        if !self.handles.contains_key(&fh) {
+            reply.error(libc::EBADF);
+            return;
+        }
+        // writes go directly to the underlying file, so there
+        // is nothing that needs to be flushed here
+        reply.ok();
+    }
+
+    pub(super) async fn fsync(&self, fh: u64, datasync: bool, reply: ReplyEmpty) {
+        let Some(handle) = self.handles.get(&fh) else {
+            reply.error(libc::EBADF);
+            return;
+        };
+        if let Handle::UpperFile { file } = handle.value() {
+            match datasync {
+                true => unwrap!(reply, file.sync_data()),
+                false => unwrap!(reply, file.sync_all()),
+            }
+        }
+        reply.ok();
+    }
+
+    pub(super) async fn create(
+        &self,
+        parent: u64,
+        name: String,
+        mode: u32,
+        umask: u32,
+        flags: i32,
+        reply: ReplyCreate,
+    ) {
+        let Some(upper) = &self.upper else {
+            reply.error(libc::EROFS);
+            return;
+        };
+        let path = unwrap!(reply, upper.child_path(parent, &name));
+        let file = unwrap!(reply, self.create_file(upper, &path, mode & !umask, flags));
+        let meta = unwrap!(reply, file.metadata());
+        let ino = self.location_inode(upper, parent, &name);
+        let fh = self.allocate_handle(Handle::UpperFile { file });
+        tracing::trace!("create {path:?} = {fh}");
+        reply.created(&self.ttl, &self.attr_from_metadata(ino, &meta), 0, fh, 0);
+    }
+
+    pub(super) async fn mkdir(
+        &self,
+        parent: u64,
+        name: String,
+        mode: u32,
+        umask: u32,
+        reply: ReplyEntry,
+    ) {
+        let Some(upper) = &self.upper else {
+            reply.error(libc::EROFS);
+            return;
+        };
+        let path = unwrap!(reply, upper.child_path(parent, &name));
+        unwrap!(reply, self.make_dir(upper, &path, mode & !umask));
+        let meta = unwrap!(reply, std::fs::symlink_metadata(upper.root.join(&path)));
+        let ino = self.location_inode(upper, parent, &name);
+        reply.entry(&self.ttl, &self.attr_from_metadata(ino, &meta), 0);
+    }
+
+    pub(super) async fn symlink(
+        &self,
+        parent: u64,
+        name: String,
+        link: PathBuf,
+        reply: ReplyEntry,
+    ) {
+        let Some(upper) = &self.upper else {
+            reply.error(libc::EROFS);
+            return;
+        };
+        let path = unwrap!(reply, upper.child_path(parent, &name));
+        unwrap!(reply, self.make_symlink(upper, &path, &link));
+        let meta = unwrap!(reply, std::fs::symlink_metadata(upper.root.join(&path)));
+        let ino = self.location_inode(upper, parent, &name);
+        reply.entry(&self.ttl, &self.attr_from_metadata(ino, &meta), 0);
+    }
+
+    pub(super) async fn unlink(&self, parent: u64, name: String, reply: ReplyEmpty) {
+        let Some(upper) = &self.upper else {
+            reply.error(libc::EROFS);
+            return;
+        };
+        let path = unwrap!(reply, upper.child_path(parent, &name));
+        unwrap!(reply, self.unlink_entry(upper, &path));
+        upper.forget(parent, &name);
+        reply.ok();
+    }
+
+    pub(super) async fn rmdir(&self, parent: u64, name: String, reply: ReplyEmpty) {
+        let Some(upper) = &self.upper else {
+            reply.error(libc::EROFS);
+            return;
+        };
+        let path = unwrap!(reply, upper.child_path(parent, &name));
+        let ino = self.location_inode(upper, parent, &name);
+        unwrap!(reply, self.remove_dir(upper, ino, &path));
+        upper.forget(parent, &name);
+        reply.ok();
+    }
+
+    pub(super) async fn rename(
+        &self,
+        parent: u64,
+        name: String,
+        new_parent: u64,
+        new_name: String,
+        flags: u32,
+        reply: ReplyEmpty,
+    ) {
+        let Some(upper) = &self.upper else {
+            reply.error(libc::EROFS);
+            return;
+        };
+        if flags & !libc::RENAME_NOREPLACE != 0 {
+            // exchanging and creating whiteouts are not supported
+            reply.error(libc::EINVAL);
+            return;
+        }
+        let src_path = unwrap!(reply, upper.child_path(parent, &name));
+        let dst_path = unwrap!(reply, upper.child_path(new_parent, &new_name));
+        let dst_ino = self.location_inode(upper, new_parent, &new_name);
+        let no_replace = flags & libc::RENAME_NOREPLACE != 0;
+        unwrap!(
+            reply,
+            self.rename_entry(upper, &src_path, &dst_path, dst_ino, no_replace)
+                .await
+        );
+
+        // the moved entry keeps its inode at the new location
+        upper.forget(new_parent, &new_name);
+        if let Some(ino) = upper.forget(parent, &name) {
+            upper.record(ino, new_parent, new_name);
+        }
+        reply.ok();
+    }
+}
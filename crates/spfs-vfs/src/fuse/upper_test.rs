@@ -0,0 +1,303 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use std::os::unix::fs::FileExt;
+use std::path::Path;
+use std::sync::Arc;
+
+use fuser::FUSE_ROOT_ID;
+use rstest::{fixture, rstest};
+use spfs::storage::fs::FsRepository;
+use spfs::storage::RepositoryHandle;
+use spfs::OsError;
+
+use super::{Filesystem, Node};
+use crate::fuse::Config;
+
+#[fixture]
+fn tmpdir() -> tempfile::TempDir {
+    tempfile::Builder::new()
+        .prefix("spfs-vfs-test-")
+        .tempdir()
+        .expect("failed to create dir for test")
+}
+
+fn ensure(path: impl AsRef<Path>, data: &str) {
+    let path = path.as_ref();
+    std::fs::create_dir_all(path.parent().unwrap()).expect("failed to make dirs");
+    std::fs::write(path, data).expect("failed to write file");
+}
+
+fn is_whiteout(path: impl AsRef<Path>) -> bool {
+    std::fs::symlink_metadata(path)
+        .map(|meta| spfs::runtime::is_removed_entry(&meta))
+        .unwrap_or(false)
+}
+
+/// Create a writable filesystem over a manifest with
+/// `dir/file.txt` and `dir/sub/nested.txt` in it
+async fn writable_fs(tmpdir: &Path) -> Filesystem {
+    let lower = tmpdir.join("lower");
+    ensure(lower.join("dir/file.txt"), "hello");
+    ensure(lower.join("dir/sub/nested.txt"), "nested");
+
+    let repo: RepositoryHandle = FsRepository::create(tmpdir.join("repo"))
+        .await
+        .unwrap()
+        .into();
+    let manifest = spfs::Committer::new(&repo)
+        .commit_dir(&lower)
+        .await
+        .unwrap();
+    let upper_dir = tmpdir.join("upper");
+    std::fs::create_dir(&upper_dir).unwrap();
+    let opts = Config {
+        root_mode: 0o777,
+        uid: nix::unistd::getuid(),
+        gid: nix::unistd::getgid(),
+        mount_options: Default::default(),
+        remotes: Vec::new(),
+        upper_dir: Some(upper_dir),
+    };
+    Filesystem::new(vec![Arc::new(repo)], manifest, opts, None)
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_create_and_write(tmpdir: tempfile::TempDir) {
+    let fs = writable_fs(tmpdir.path()).await;
+    let upper = fs.upper.as_ref().unwrap();
+
+    let path = upper.child_path(FUSE_ROOT_ID, "new.txt").unwrap();
+    let file = fs
+        .create_file(upper, &path, 0o644, libc::O_RDWR)
+        .expect("should create a new file");
+    file.write_all_at(b"data", 0).unwrap();
+
+    let content = std::fs::read_to_string(upper.root.join("new.txt")).unwrap();
+    assert_eq!(content, "data");
+    assert!(matches!(
+        fs.resolve_path(upper, &path).unwrap(),
+        Some(Node::Upper(_))
+    ));
+    let err = fs
+        .create_file(upper, &path, 0o644, libc::O_RDWR)
+        .expect_err("should not replace an existing file");
+    assert_eq!(err.raw_os_error(), Some(libc::EEXIST));
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_open_copies_up_content(tmpdir: tempfile::TempDir) {
+    let fs = writable_fs(tmpdir.path()).await;
+    let upper = fs.upper.as_ref().unwrap();
+
+    let path = Path::new("dir/file.txt");
+    let node = fs.resolve_path(upper, path).unwrap().unwrap();
+    assert!(
+        matches!(node, Node::Lower(_)),
+        "should start in the manifest"
+    );
+    fs.open_upper_file(upper, path, &node, libc::O_RDWR)
+        .await
+        .expect("should open lower file for writing");
+
+    let content = std::fs::read_to_string(upper.root.join(path)).unwrap();
+    assert_eq!(content, "hello", "content should be copied up");
+    assert!(
+        upper.copy_up_locks.is_empty(),
+        "copy up locks should be released"
+    );
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_open_truncate_skips_content(tmpdir: tempfile::TempDir) {
+    let fs = writable_fs(tmpdir.path()).await;
+    let upper = fs.upper.as_ref().unwrap();
+
+    let path = Path::new("dir/file.txt");
+    let node = fs.resolve_path(upper, path).unwrap().unwrap();
+    fs.open_upper_file(upper, path, &node, libc::O_RDWR | libc::O_TRUNC)
+        .await
+        .expect("should open lower file for writing");
+
+    let content = std::fs::read_to_string(upper.root.join(path)).unwrap();
+    assert_eq!(content, "");
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_concurrent_copy_up(tmpdir: tempfile::TempDir) {
+    let fs = writable_fs(tmpdir.path()).await;
+    let upper = fs.upper.as_ref().unwrap();
+
+    let path = Path::new("dir/file.txt");
+    let Some(Node::Lower(entry)) = fs.resolve_path(upper, path).unwrap() else {
+        panic!("expected an entry from the manifest");
+    };
+    let (first, second) = tokio::join!(
+        fs.copy_up(upper, path, &entry, true),
+        fs.copy_up(upper, path, &entry, true),
+    );
+    first.expect("first copy up should succeed");
+    second.expect("second copy up should find the copied entry");
+
+    let content = std::fs::read_to_string(upper.root.join(path)).unwrap();
+    assert_eq!(content, "hello");
+    assert!(upper.copy_up_locks.is_empty());
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_unlink_lower_file(tmpdir: tempfile::TempDir) {
+    let fs = writable_fs(tmpdir.path()).await;
+    let upper = fs.upper.as_ref().unwrap();
+
+    let path = Path::new("dir/file.txt");
+    fs.unlink_entry(upper, path).expect("should unlink file");
+
+    assert!(
+        is_whiteout(upper.root.join(path)),
+        "removed lower file should leave a whiteout"
+    );
+    assert!(fs.resolve_path(upper, path).unwrap().is_none());
+    let ino = fs.location_inode(upper, FUSE_ROOT_ID, "dir");
+    let names = fs
+        .list_dir(upper, ino, Path::new("dir"))
+        .unwrap()
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec!["sub".to_string()]);
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_unlink_upper_file(tmpdir: tempfile::TempDir) {
+    let fs = writable_fs(tmpdir.path()).await;
+    let upper = fs.upper.as_ref().unwrap();
+
+    let path = Path::new("new.txt");
+    fs.create_file(upper, path, 0o644, libc::O_RDWR).unwrap();
+    fs.unlink_entry(upper, path).expect("should unlink file");
+
+    assert!(
+        std::fs::symlink_metadata(upper.root.join(path)).is_err(),
+        "new files should be removed without a whiteout"
+    );
+    let err = fs
+        .unlink_entry(upper, Path::new("dir"))
+        .expect_err("should not unlink a directory");
+    assert_eq!(err.raw_os_error(), Some(libc::EISDIR));
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_mkdir_replaces_removed_dir(tmpdir: tempfile::TempDir) {
+    let fs = writable_fs(tmpdir.path()).await;
+    let upper = fs.upper.as_ref().unwrap();
+
+    let dir = Path::new("dir/sub");
+    let ino = fs.location_inode(upper, FUSE_ROOT_ID, "dir");
+    let sub_ino = fs.location_inode(upper, ino, "sub");
+    let err = fs
+        .remove_dir(upper, sub_ino, dir)
+        .expect_err("should not remove a non-empty directory");
+    assert_eq!(err.raw_os_error(), Some(libc::ENOTEMPTY));
+
+    fs.unlink_entry(upper, &dir.join("nested.txt")).unwrap();
+    fs.remove_dir(upper, sub_ino, dir)
+        .expect("should remove an empty directory");
+    assert!(is_whiteout(upper.root.join(dir)));
+
+    fs.make_dir(upper, dir, 0o755)
+        .expect("should replace the removed directory");
+    assert!(
+        is_whiteout(upper.root.join(dir).join("nested.txt")),
+        "the new directory should hide the contents of the old one"
+    );
+    let entries = fs.list_dir(upper, sub_ino, dir).unwrap();
+    assert!(entries.is_empty(), "replaced directory should be empty");
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_make_symlink(tmpdir: tempfile::TempDir) {
+    let fs = writable_fs(tmpdir.path()).await;
+    let upper = fs.upper.as_ref().unwrap();
+
+    let path = Path::new("dir/link");
+    fs.make_symlink(upper, path, Path::new("file.txt"))
+        .expect("should create symlink");
+    let target = std::fs::read_link(upper.root.join(path)).unwrap();
+    assert_eq!(target, Path::new("file.txt"));
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_rename_lower_file(tmpdir: tempfile::TempDir) {
+    let fs = writable_fs(tmpdir.path()).await;
+    let upper = fs.upper.as_ref().unwrap();
+
+    let src = Path::new("dir/file.txt");
+    let dst = Path::new("moved/file.txt");
+    let moved_ino = fs.location_inode(upper, FUSE_ROOT_ID, "moved");
+    let dst_ino = fs.location_inode(upper, moved_ino, "file.txt");
+    fs.rename_entry(upper, src, dst, dst_ino, false)
+        .await
+        .expect("should rename lower file");
+
+    let content = std::fs::read_to_string(upper.root.join(dst)).unwrap();
+    assert_eq!(content, "hello", "renamed file should keep its content");
+    assert!(
+        is_whiteout(upper.root.join(src)),
+        "renamed lower file should leave a whiteout"
+    );
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_rename_no_replace(tmpdir: tempfile::TempDir) {
+    let fs = writable_fs(tmpdir.path()).await;
+    let upper = fs.upper.as_ref().unwrap();
+
+    let src = Path::new("new.txt");
+    let dst = Path::new("dir/file.txt");
+    fs.create_file(upper, src, 0o644, libc::O_RDWR).unwrap();
+    let dir_ino = fs.location_inode(upper, FUSE_ROOT_ID, "dir");
+    let dst_ino = fs.location_inode(upper, dir_ino, "file.txt");
+    let err = fs
+        .rename_entry(upper, src, dst, dst_ino, true)
+        .await
+        .expect_err("should not replace an existing file");
+    assert_eq!(err.os_error(), Some(libc::EEXIST));
+
+    fs.rename_entry(upper, src, dst, dst_ino, false)
+        .await
+        .expect("should replace an existing file");
+    let content = std::fs::read_to_string(upper.root.join(dst)).unwrap();
+    assert_eq!(content, "");
+    assert!(std::fs::symlink_metadata(upper.root.join(src)).is_err());
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_rename_lower_dir_is_exdev(tmpdir: tempfile::TempDir) {
+    let fs = writable_fs(tmpdir.path()).await;
+    let upper = fs.upper.as_ref().unwrap();
+
+    let dst_ino = fs.location_inode(upper, FUSE_ROOT_ID, "other");
+    let err = fs
+        .rename_entry(upper, Path::new("dir"), Path::new("other"), dst_ino, false)
+        .await
+        .expect_err("should not rename a directory from the manifest");
+    assert_eq!(err.os_error(), Some(libc::EXDEV));
+
+    fs.make_dir(upper, Path::new("new"), 0o755).unwrap();
+    fs.rename_entry(upper, Path::new("new"), Path::new("other"), dst_ino, false)
+        .await
+        .expect("should rename a new directory");
+    assert!(upper.root.join("other").is_dir());
+}
@@ -14,6 +14,8 @@ pub use error::Error;
 
 #[cfg(all(unix, feature = "fuse-backend"))]
 mod fuse;
+#[cfg(all(unix, feature = "fuse-backend"))]
+mod payload_cache;
 #[cfg(all(windows, feature = "winfsp-backend"))]
 pub mod proto;
 #[cfg(all(windows, feature = "winfsp-backend"))]
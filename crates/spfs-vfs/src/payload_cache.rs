@@ -0,0 +1,298 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use std::collections::HashMap;
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use spfs::encoding::{Digest, Hasher};
+use spfs::prelude::*;
+use spfs::tracking::BlobRead;
+use spfs::{Error, Result};
+use tokio::io::AsyncWriteExt;
+
+#[cfg(test)]
+#[path = "./payload_cache_test.rs"]
+mod payload_cache_test;
+
+/// A bounded, local copy of payloads that were read from
+/// repositories which cannot be accessed directly on disk.
+///
+/// Payloads are stored as plain files named by their digest so
+/// that they can be opened and seeked like any local payload.
+/// When the total size of the cache grows beyond its limit, the
+/// least recently used payloads are removed. Many filesystem
+/// processes can safely share the same cache directory, though
+/// each one only enforces the limit based on what it has seen.
+pub(crate) struct PayloadCache {
+    root: PathBuf,
+    max_size: u64,
+    next_working_file: AtomicU64,
+    index: Mutex<CacheIndex>,
+    /// Payloads that are currently being read into the cache
+    #[cfg(feature = "fuse-backend-abi-7-31")]
+    populating: Mutex<std::collections::HashSet<Digest>>,
+}
+
+#[derive(Default)]
+struct CacheIndex {
+    entries: HashMap<Digest, CachedPayload>,
+    total_size: u64,
+    clock: u64,
+}
+
+struct CachedPayload {
+    size: u64,
+    last_used: u64,
+}
+
+impl CacheIndex {
+    fn touch(&mut self, digest: Digest, size: u64) {
+        self.clock += 1;
+        let last_used = self.clock;
+        match self
+            .entries
+            .insert(digest, CachedPayload { size, last_used })
+        {
+            Some(previous) => self.total_size = self.total_size - previous.size + size,
+            None => self.total_size += size,
+        }
+    }
+
+    fn forget(&mut self, digest: &Digest) {
+        if let Some(previous) = self.entries.remove(digest) {
+            self.total_size -= previous.size;
+        }
+    }
+}
+
+impl PayloadCache {
+    /// Open the cache in the given directory, creating it if needed.
+    ///
+    /// Any payloads already in the directory are indexed, ordered
+    /// by when they were last used.
+    pub fn new(root: PathBuf, max_size: u64) -> Result<Self> {
+        spfs::runtime::makedirs_with_perms(&root, 0o777)
+            .map_err(|err| Error::StorageWriteError("create payload cache", root.clone(), err))?;
+
+        let read_dir = std::fs::read_dir(&root)
+            .map_err(|err| Error::StorageReadError("read payload cache", root.clone(), err))?;
+        let mut existing = Vec::new();
+        for entry in read_dir {
+            let entry = entry
+                .map_err(|err| Error::StorageReadError("read payload cache", root.clone(), err))?;
+            let name = entry.file_name();
+            let Some(Ok(digest)) = name.to_str().map(Digest::parse) else {
+                // working files and anything else that might
+                // be in the directory are not part of the cache
+                continue;
+            };
+            let Ok(meta) = entry.metadata() else {
+                continue;
+            };
+            let modified = meta.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+            existing.push((modified, digest, meta.len()));
+        }
+        existing.sort_by_key(|(modified, ..)| *modified);
+
+        let mut index = CacheIndex::default();
+        for (_, digest, size) in existing {
+            index.touch(digest, size);
+        }
+        let cache = Self {
+            root,
+            max_size,
+            next_working_file: AtomicU64::new(0),
+            index: Mutex::new(index),
+            #[cfg(feature = "fuse-backend-abi-7-31")]
+            populating: Mutex::new(Default::default()),
+        };
+        cache.evict(0);
+        Ok(cache)
+    }
+
+    fn payload_path(&self, digest: &Digest) -> PathBuf {
+        self.root.join(digest.to_string())
+    }
+
+    /// Open the identified payload from the cache, if it is there
+    pub fn open(&self, digest: &Digest) -> Option<std::fs::File> {
+        let path = self.payload_path(digest);
+        let file = match std::fs::File::open(&path) {
+            Ok(file) => file,
+            Err(err) => {
+                if err.kind() != std::io::ErrorKind::NotFound {
+                    tracing::warn!(?path, "failed to open cached payload: {err}");
+                }
+                // the payload may have been evicted by another process
+                self.index.lock().unwrap().forget(digest);
+                return None;
+            }
+        };
+        let size = match file.metadata() {
+            Ok(meta) => meta.len(),
+            Err(_) => return None,
+        };
+        // record the access on disk as well, so that a future process
+        // can tell which payloads were used most recently. This is best
+        // effort, as payloads can be cached by other users.
+        let _ = file.set_modified(SystemTime::now());
+        self.index.lock().unwrap().touch(*digest, size);
+        Some(file)
+    }
+
+    /// Read the identified payload into the cache from the given
+    /// repository without waiting for it to finish, so that it can
+    /// be opened from the cache later on.
+    ///
+    /// Does nothing if the payload is too large to be cached, or
+    /// is already being read into the cache.
+    #[cfg(feature = "fuse-backend-abi-7-31")]
+    pub fn populate_in_background(
+        self: &std::sync::Arc<Self>,
+        repo: std::sync::Arc<spfs::storage::RepositoryHandle>,
+        digest: Digest,
+        size: u64,
+    ) {
+        if size > self.max_size || !self.populating.lock().unwrap().insert(digest) {
+            return;
+        }
+        let cache = std::sync::Arc::clone(self);
+        tokio::spawn(async move {
+            let result = match repo.open_payload(digest).await {
+                Ok((reader, _)) => cache.populate(digest, size, reader).await.map(|_| ()),
+                Err(err) => Err(err),
+            };
+            if let Err(err) = result {
+                tracing::warn!("Failed to cache payload {digest}: {err}");
+            }
+            cache.populating.lock().unwrap().remove(&digest);
+        });
+    }
+
+    /// Open the identified payload from the cache, first reading it
+    /// into the cache from the given repository if needed.
+    ///
+    /// Returns `None` if the payload is too large to be cached.
+    #[cfg(not(feature = "fuse-backend-abi-7-31"))]
+    pub async fn open_or_populate(
+        &self,
+        repo: &spfs::storage::RepositoryHandle,
+        digest: Digest,
+        size: u64,
+    ) -> Result<Option<std::fs::File>> {
+        if let Some(file) = self.open(&digest) {
+            return Ok(Some(file));
+        }
+        if size > self.max_size {
+            return Ok(None);
+        }
+        let (reader, _) = repo.open_payload(digest).await?;
+        self.populate(digest, size, reader).await.map(Some)
+    }
+
+    async fn populate(
+        &self,
+        digest: Digest,
+        size: u64,
+        mut reader: Pin<Box<dyn BlobRead>>,
+    ) -> Result<std::fs::File> {
+        // make room first so that the cache does not
+        // grow beyond its limit while downloading
+        self.evict(size);
+
+        let working_file = self.root.join(format!(
+            ".{digest}.{}.{}",
+            std::process::id(),
+            self.next_working_file.fetch_add(1, Ordering::Relaxed)
+        ));
+        let result = self.write_working_file(&working_file, &mut reader).await;
+        let result = match result {
+            Ok(actual) if actual != digest => Err(Error::String(format!(
+                "Payload read from repository did not match its digest: {digest} != {actual}"
+            ))),
+            Ok(_) => {
+                let path = self.payload_path(&digest);
+                tokio::fs::rename(&working_file, &path)
+                    .await
+                    .map_err(|err| Error::StorageWriteError("rename cached payload", path, err))
+            }
+            Err(err) => Err(err),
+        };
+        if let Err(err) = result {
+            let _ = tokio::fs::remove_file(&working_file).await;
+            return Err(err);
+        }
+
+        self.index.lock().unwrap().touch(digest, size);
+        let path = self.payload_path(&digest);
+        std::fs::File::open(&path)
+            .map_err(|err| Error::StorageReadError("open cached payload", path, err))
+    }
+
+    /// Copy the reader into a new file, returning the digest of its contents
+    async fn write_working_file(
+        &self,
+        path: &std::path::Path,
+        reader: &mut Pin<Box<dyn BlobRead>>,
+    ) -> Result<Digest> {
+        let file = tokio::fs::File::create(path)
+            .await
+            .map_err(|err| Error::StorageWriteError("create cached payload", path.into(), err))?;
+        let mut hasher = Hasher::with_target(file);
+        tokio::io::copy(reader, &mut hasher)
+            .await
+            .map_err(|err| Error::StorageWriteError("write cached payload", path.into(), err))?;
+        hasher
+            .flush()
+            .await
+            .map_err(|err| Error::StorageWriteError("write cached payload", path.into(), err))?;
+        // cached payloads are shared by all users, and never modified
+        tokio::fs::set_permissions(path, std::fs::Permissions::from_mode(0o444))
+            .await
+            .map_err(|err| {
+                Error::StorageWriteError("set cached payload perms", path.into(), err)
+            })?;
+        Ok(hasher.digest())
+    }
+
+    /// Remove the least recently used payloads until there is
+    /// enough room for `reserve` additional bytes.
+    ///
+    /// Payloads that are still open remain readable through
+    /// their existing file handles after being removed.
+    fn evict(&self, reserve: u64) {
+        let mut index = self.index.lock().unwrap();
+        let limit = self.max_size.saturating_sub(reserve);
+        if index.total_size <= limit {
+            return;
+        }
+        let mut by_age = index
+            .entries
+            .iter()
+            .map(|(digest, payload)| (payload.last_used, *digest))
+            .collect::<Vec<_>>();
+        by_age.sort_unstable();
+        for (_, digest) in by_age {
+            if index.total_size <= limit {
+                break;
+            }
+            let path = self.payload_path(&digest);
+            match std::fs::remove_file(&path) {
+                Ok(()) => {}
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+                Err(err) => {
+                    tracing::warn!(?path, "failed to evict cached payload: {err}");
+                    continue;
+                }
+            }
+            tracing::trace!(%digest, "evicted cached payload");
+            index.forget(&digest);
+        }
+    }
+}
@@ -0,0 +1,158 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use rstest::{fixture, rstest};
+use spfs::encoding::{Digest, Hasher};
+
+use super::PayloadCache;
+
+#[fixture]
+fn tmpdir() -> tempfile::TempDir {
+    tempfile::Builder::new()
+        .prefix("spfs-vfs-test-")
+        .tempdir()
+        .expect("failed to create dir for test")
+}
+
+fn digest_of(data: &[u8]) -> Digest {
+    Hasher::hash_reader(data).unwrap()
+}
+
+/// Read the given data into the cache under its own digest
+async fn populate(cache: &PayloadCache, data: &'static [u8]) -> Digest {
+    let digest = digest_of(data);
+    cache
+        .populate(digest, data.len() as u64, Box::pin(data))
+        .await
+        .expect("failed to populate cache");
+    digest
+}
+
+fn is_cached(root: &Path, digest: &Digest) -> bool {
+    root.join(digest.to_string()).exists()
+}
+
+/// Write a payload straight into the cache directory, as if
+/// it had been cached by an earlier process
+fn write_payload(root: &Path, data: &[u8], modified: SystemTime) -> Digest {
+    let digest = digest_of(data);
+    let file = std::fs::File::create(root.join(digest.to_string())).unwrap();
+    std::io::Write::write_all(&mut &file, data).unwrap();
+    file.set_modified(modified).unwrap();
+    digest
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_payload_cache_evicts_least_recently_used(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path().join("cache");
+    let cache = PayloadCache::new(root.clone(), 10).unwrap();
+
+    let first = populate(&cache, b"first").await;
+    let second = populate(&cache, b"other").await;
+    assert_eq!(cache.index.lock().unwrap().total_size, 10);
+
+    // using the first payload makes the second one the oldest
+    assert!(cache.open(&first).is_some());
+    let third = populate(&cache, b"third").await;
+
+    assert!(
+        is_cached(&root, &first),
+        "recently used payload should stay"
+    );
+    assert!(
+        !is_cached(&root, &second),
+        "oldest payload should be evicted"
+    );
+    assert!(is_cached(&root, &third));
+    assert!(cache.open(&second).is_none());
+
+    let index = cache.index.lock().unwrap();
+    assert_eq!(index.total_size, 10, "cache should stay within its limit");
+    assert_eq!(index.entries.len(), 2);
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_payload_cache_evicts_until_payload_fits(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path().join("cache");
+    let cache = PayloadCache::new(root.clone(), 10).unwrap();
+
+    let small = [
+        populate(&cache, b"aaa").await,
+        populate(&cache, b"bbb").await,
+        populate(&cache, b"ccc").await,
+    ];
+    let large = populate(&cache, b"ninebytes").await;
+
+    for digest in &small {
+        assert!(!is_cached(&root, digest), "all older payloads are evicted");
+    }
+    assert!(is_cached(&root, &large));
+    assert_eq!(cache.index.lock().unwrap().total_size, 9);
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_payload_cache_rejects_digest_mismatch(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path().join("cache");
+    let cache = PayloadCache::new(root.clone(), 100).unwrap();
+
+    let expected = digest_of(b"expected");
+    cache
+        .populate(expected, 8, Box::pin(&b"modified"[..]))
+        .await
+        .expect_err("should fail when the payload does not match its digest");
+
+    assert!(!is_cached(&root, &expected));
+    assert!(cache.open(&expected).is_none());
+    let remaining = std::fs::read_dir(&root).unwrap().count();
+    assert_eq!(remaining, 0, "working file should be removed");
+    assert_eq!(cache.index.lock().unwrap().total_size, 0);
+}
+
+#[rstest]
+fn test_payload_cache_indexes_existing_payloads(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path().join("cache");
+    std::fs::create_dir(&root).unwrap();
+    let now = SystemTime::now();
+    let oldest = write_payload(&root, b"oldest", now - Duration::from_secs(60));
+    let newest = write_payload(&root, b"newest", now);
+    // an unfinished working file from another process
+    std::fs::write(root.join(format!(".{newest}.1234.0")), "partial").unwrap();
+
+    let cache = PayloadCache::new(root.clone(), 100).unwrap();
+    {
+        let index = cache.index.lock().unwrap();
+        assert_eq!(index.total_size, 12, "working files should not be indexed");
+        assert!(index.entries[&oldest].last_used < index.entries[&newest].last_used);
+    }
+    drop(cache);
+
+    // reopening with a smaller limit evicts the least recently used payload
+    let cache = PayloadCache::new(root.clone(), 8).unwrap();
+    assert!(!is_cached(&root, &oldest));
+    assert!(is_cached(&root, &newest));
+    assert_eq!(cache.index.lock().unwrap().total_size, 6);
+    assert!(root.join(format!(".{newest}.1234.0")).exists());
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_payload_cache_open_forgets_removed_payload(tmpdir: tempfile::TempDir) {
+    let root = tmpdir.path().join("cache");
+    let cache = PayloadCache::new(root.clone(), 100).unwrap();
+    let digest = populate(&cache, b"payload").await;
+
+    // another process sharing the cache evicts the payload
+    std::fs::remove_file(root.join(digest.to_string())).unwrap();
+
+    assert!(cache.open(&digest).is_none());
+    let index = cache.index.lock().unwrap();
+    assert!(!index.entries.contains_key(&digest));
+    assert_eq!(index.total_size, 0);
+}
@@ -131,6 +131,7 @@ impl Mount {
             entries,
             user_data: _,
             legacy_size,
+            xattrs,
         } = entry;
 
         let inode = self.allocate_inode();
@@ -145,6 +146,7 @@ impl Mount {
             entries,
             user_data: inode,
             legacy_size,
+            xattrs,
         });
         self.inodes.insert(inode, Arc::clone(&entry));
         entry
@@ -288,8 +290,21 @@ impl winfsp::filesystem::FileSystemContext for Mount {
         let digest = entry.object;
         self.rt.spawn(async move {
             for repo in repos.into_iter() {
+                // payloads that are compressed or chunked cannot be read directly
+                // from disk and are streamed like any remote payload
+                let must_stream = match &*repo {
+                    spfs::storage::RepositoryHandle::FS(fs_repo) => match fs_repo.opened().await {
+                        Ok(fs_repo) => {
+                            !fs_repo.payloads.has_digest(&digest)
+                                && (fs_repo.compressed_payloads.has_digest(&digest)
+                                    || fs_repo.chunks.has_digest(&digest))
+                        }
+                        Err(_) => false,
+                    },
+                    _ => false,
+                };
                 match &*repo {
-                    spfs::storage::RepositoryHandle::FS(fs_repo) => {
+                    spfs::storage::RepositoryHandle::FS(fs_repo) if !must_stream => {
                         let Ok(fs_repo) = fs_repo.opened().await else {
                             let _ =
                                 send.send(Err(winfsp::FspError::IO(std::io::ErrorKind::NotFound)));
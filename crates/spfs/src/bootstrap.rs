@@ -362,6 +362,12 @@ where
         enter_args.extend(["--tmpdir".into(), tmpdir_value_for_child_process]);
     }
 
+    if rt.config.rootless {
+        // the user namespace must be created by spfs-enter itself
+        // before it starts any additional threads
+        enter_args.push("--rootless".into());
+    }
+
     enter_args.extend([
         "--runtime-storage".into(),
         rt.storage().address().to_string().into(),
@@ -15,7 +15,7 @@ use tokio::sync::Semaphore;
 use crate::graph::AnnotationValue;
 use crate::prelude::*;
 use crate::sync::{SyncObjectResult, SyncPayloadResult, SyncPolicy};
-use crate::{encoding, graph, storage, tracking, Error, Result};
+use crate::{encoding, graph, signing, storage, tracking, Error, Result};
 
 #[cfg(test)]
 #[path = "./check_test.rs"]
@@ -31,6 +31,7 @@ pub struct Checker<'repo, 'sync, Reporter: CheckReporter = SilentCheckReporter>
     processed_digests: Arc<dashmap::DashMap<encoding::Digest, CheckProgress>>,
     tag_stream_semaphore: Semaphore,
     object_semaphore: Semaphore,
+    trusted_keys: Option<Vec<signing::TrustedKey>>,
 }
 
 impl<'repo> Checker<'repo, 'static> {
@@ -47,6 +48,7 @@ impl<'repo> Checker<'repo, 'static> {
             processed_digests: Arc::new(Default::default()),
             tag_stream_semaphore: Semaphore::new(Self::DEFAULT_MAX_TAG_STREAM_CONCURRENCY),
             object_semaphore: Semaphore::new(Self::DEFAULT_MAX_OBJECT_CONCURRENCY),
+            trusted_keys: None,
         }
     }
 }
@@ -68,6 +70,7 @@ where
             processed_digests: self.processed_digests,
             tag_stream_semaphore: self.tag_stream_semaphore,
             object_semaphore: self.object_semaphore,
+            trusted_keys: self.trusted_keys,
         }
     }
 
@@ -90,6 +93,7 @@ where
             processed_digests: self.processed_digests,
             tag_stream_semaphore: self.tag_stream_semaphore,
             object_semaphore: self.object_semaphore,
+            trusted_keys: self.trusted_keys,
         }
     }
 
@@ -105,6 +109,15 @@ where
         self
     }
 
+    /// Also verify that every layer and platform is signed by one of these keys.
+    ///
+    /// Missing and invalid signatures are reported in the results of
+    /// the check, but do not otherwise stop the check from proceeding.
+    pub fn with_trusted_keys(mut self, trusted_keys: Vec<signing::TrustedKey>) -> Self {
+        self.trusted_keys = Some(trusted_keys);
+        self
+    }
+
     /// Validate that all of the targets and their children exist for all
     /// of the tags in the repository, including tag history.
     pub async fn check_all_tags(&self) -> Result<Vec<CheckTagStreamResult>> {
@@ -322,9 +335,11 @@ where
             .map(|d| self.check_digest(*d))
             .collect();
         let results = futures.try_collect().await?;
+        let signature = self.check_signature(platform.digest()?).await?;
         let res = CheckPlatformResult {
             platform,
             results,
+            signature,
             repaired: false,
         };
         Ok(res)
@@ -357,15 +372,31 @@ where
 
         let mut results = vec![manifest_result];
         results.extend(annotation_results);
+        let signature = self.check_signature(layer.digest()?).await?;
 
         let res = CheckLayerResult {
             layer,
             results,
+            signature,
             repaired: false,
         };
         Ok(res)
     }
 
+    /// Verify the signature of the identified object, if the
+    /// checker has been given any trusted keys.
+    async fn check_signature(
+        &self,
+        digest: encoding::Digest,
+    ) -> Result<Option<signing::SignatureStatus>> {
+        let Some(trusted_keys) = &self.trusted_keys else {
+            return Ok(None);
+        };
+        let status = signing::verify_object(self.repo, digest, trusted_keys).await?;
+        self.reporter.checked_signature(&digest, &status);
+        Ok(Some(status))
+    }
+
     /// Validate that the identified manifest's children all exist.
     ///
     /// To also check if the manifest object exists, use [`Self::check_digest`]
@@ -452,9 +483,11 @@ where
             self.check_payload_with_perms_opt(*blob.payload(), perms)
                 .await?
         };
+        let chunks = self.check_payload_chunks(*blob.payload()).await?;
         let res = CheckBlobResult::Checked {
             blob: blob.to_owned(),
             result,
+            chunks,
             repaired: result == CheckPayloadResult::Repaired,
         };
         self.reporter.checked_blob(&res);
@@ -505,6 +538,32 @@ where
         Ok(result)
     }
 
+    /// Check each chunk of a payload that is stored as a sequence of
+    /// chunks, returning no results for payloads that are stored whole.
+    async fn check_payload_chunks(
+        &self,
+        digest: encoding::Digest,
+    ) -> Result<Vec<CheckPayloadResult>> {
+        if !self.repo.supports_chunked_payloads() {
+            return Ok(Vec::new());
+        }
+        let Some(index) = self.repo.read_chunk_index(digest).await? else {
+            return Ok(Vec::new());
+        };
+        let mut results = Vec::with_capacity(index.chunks().len());
+        for chunk in index.chunks() {
+            self.reporter.visit_payload(chunk.digest);
+            let result = if self.repo.has_payload(chunk.digest).await {
+                CheckPayloadResult::Ok
+            } else {
+                CheckPayloadResult::Missing(chunk.digest)
+            };
+            self.reporter.checked_payload(&result);
+            results.push(result);
+        }
+        Ok(results)
+    }
+
     /// Returns the object, and whether or not it was repaired
     async fn read_object_with_fallback(
         &self,
@@ -597,6 +656,9 @@ pub trait CheckReporter: Send + Sync {
 
     /// Called when a payload was found to be missing and successfully repaired
     fn repaired_payload(&self, _result: &SyncPayloadResult) {}
+
+    /// Called when the signature of a layer or platform has been verified
+    fn checked_signature(&self, _digest: &encoding::Digest, _status: &signing::SignatureStatus) {}
 }
 
 #[derive(Default)]
@@ -655,11 +717,23 @@ impl CheckReporter for ConsoleCheckReporter {
         }
     }
 
+    fn checked_signature(&self, digest: &encoding::Digest, status: &signing::SignatureStatus) {
+        if status.is_trusted() {
+            return;
+        }
+        let bars = self.get_bars();
+        bars.missing
+            .println(format!("{}: {digest} ({status})", "Untrusted".red()));
+        bars.missing.inc_length(1);
+    }
+
     fn repaired_payload(&self, result: &SyncPayloadResult) {
         let bars = self.get_bars();
         bars.missing.inc_length(1);
         bars.missing.inc(1);
-        bars.bytes.inc(result.summary().synced_payload_bytes);
+        let summary = result.summary();
+        bars.bytes
+            .inc(summary.synced_payload_bytes + summary.deduplicated_payload_bytes);
     }
 }
 
@@ -702,6 +776,10 @@ pub struct CheckSummary {
     pub checked_payloads: usize,
     /// The total number of payload bytes checked
     pub checked_payload_bytes: u64,
+    /// The layers and platforms found to have no trusted signature
+    pub missing_signatures: HashSet<encoding::Digest>,
+    /// The layers and platforms found to have only invalid signatures
+    pub invalid_signatures: HashSet<encoding::Digest>,
 }
 
 impl CheckSummary {
@@ -711,6 +789,20 @@ impl CheckSummary {
             ..Default::default()
         }
     }
+
+    fn checked_one_signature(digest: encoding::Digest, status: &signing::SignatureStatus) -> Self {
+        let mut summary = Self::default();
+        match status {
+            signing::SignatureStatus::Trusted { .. } => {}
+            signing::SignatureStatus::Invalid { .. } => {
+                summary.invalid_signatures.insert(digest);
+            }
+            signing::SignatureStatus::Missing => {
+                summary.missing_signatures.insert(digest);
+            }
+        }
+        summary
+    }
 }
 
 impl std::ops::AddAssign for CheckSummary {
@@ -727,6 +819,8 @@ impl std::ops::AddAssign for CheckSummary {
             checked_payload_bytes,
             repaired_objects,
             repaired_payloads,
+            missing_signatures,
+            invalid_signatures,
         } = rhs;
         self.missing_tags += missing_tags;
         self.checked_tags += checked_tags;
@@ -737,6 +831,8 @@ impl std::ops::AddAssign for CheckSummary {
         self.checked_payload_bytes += checked_payload_bytes;
         self.repaired_objects += repaired_objects;
         self.repaired_payloads += repaired_payloads;
+        self.missing_signatures.extend(missing_signatures);
+        self.invalid_signatures.extend(invalid_signatures);
     }
 }
 
@@ -887,6 +983,8 @@ pub struct CheckPlatformResult {
     pub repaired: bool,
     pub platform: graph::Platform,
     pub results: Vec<CheckObjectResult>,
+    /// The signature status of the platform, if signatures were checked
+    pub signature: Option<signing::SignatureStatus>,
 }
 
 impl CheckPlatformResult {
@@ -898,6 +996,10 @@ impl CheckPlatformResult {
     pub fn summary(&self) -> CheckSummary {
         let mut summary: CheckSummary = self.results.iter().map(|r| r.summary()).sum();
         summary += CheckSummary::checked_one_object();
+        if let Some(signature) = &self.signature {
+            let digest = self.platform.digest().expect("Object has valid digest");
+            summary += CheckSummary::checked_one_signature(digest, signature);
+        }
         if self.repaired {
             summary.repaired_objects += 1;
         }
@@ -910,6 +1012,8 @@ pub struct CheckLayerResult {
     pub repaired: bool,
     pub layer: graph::Layer,
     pub results: Vec<CheckObjectResult>,
+    /// The signature status of the layer, if signatures were checked
+    pub signature: Option<signing::SignatureStatus>,
 }
 
 impl CheckLayerResult {
@@ -921,6 +1025,10 @@ impl CheckLayerResult {
     pub fn summary(&self) -> CheckSummary {
         let mut summary: CheckSummary = self.results.iter().map(|r| r.summary()).sum();
         summary += CheckSummary::checked_one_object();
+        if let Some(signature) = &self.signature {
+            let digest = self.layer.digest().expect("Object has valid digest");
+            summary += CheckSummary::checked_one_signature(digest, signature);
+        }
         if self.repaired {
             summary.repaired_objects += 1;
         }
@@ -1007,6 +1115,9 @@ pub enum CheckBlobResult {
         repaired: bool,
         blob: graph::Blob,
         result: CheckPayloadResult,
+        /// The results for each chunk, if the payload is
+        /// stored as a sequence of chunks
+        chunks: Vec<CheckPayloadResult>,
     },
 }
 
@@ -1028,9 +1139,13 @@ impl CheckBlobResult {
             Self::Checked {
                 repaired,
                 result,
+                chunks,
                 blob,
             } => {
                 let mut summary = result.summary();
+                for chunk in chunks {
+                    summary += chunk.summary();
+                }
                 summary += CheckSummary {
                     checked_objects: 1,
                     checked_payload_bytes: blob.size(),
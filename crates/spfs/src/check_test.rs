@@ -8,7 +8,10 @@ use spfs_encoding::prelude::*;
 use super::{CheckSummary, Checker};
 use crate::fixtures::*;
 use crate::graph::Database;
-use crate::storage::PayloadStorage;
+use crate::prelude::*;
+use crate::storage::chunking::MAX_CHUNK_SIZE;
+use crate::storage::fs::{FsRepository, OpenFsRepository};
+use crate::storage::{PayloadStorage, RepositoryHandle};
 
 #[rstest]
 #[tokio::test]
@@ -230,3 +233,96 @@ async fn test_check_missing_object_recover(#[future] tmprepo: TempRepo) {
         "should see no missing payloads",
     );
 }
+
+#[rstest]
+#[tokio::test]
+async fn test_check_missing_payload_chunk(tmpdir: tempfile::TempDir) {
+    init_logging();
+    let mut repo = OpenFsRepository::create(tmpdir.path()).await.unwrap();
+    repo.set_payload_chunking_threshold(Some(MAX_CHUNK_SIZE as u64));
+    let digest = repo
+        .commit_blob(Box::pin(std::io::Cursor::new(random_data(
+            3 * MAX_CHUNK_SIZE as usize,
+        ))))
+        .await
+        .unwrap();
+    let index = repo
+        .read_chunk_index(digest)
+        .await
+        .unwrap()
+        .expect("payload should be chunked");
+    let removed = index.chunks()[0].digest;
+    tokio::fs::remove_file(repo.payloads.build_digest_path(&removed))
+        .await
+        .unwrap();
+
+    let repo = RepositoryHandle::from(FsRepository::from(repo));
+    let summary: CheckSummary = Checker::new(&repo)
+        .check_all_objects()
+        .await
+        .unwrap()
+        .iter()
+        .map(|r| r.summary())
+        .sum();
+    tracing::info!("{summary:#?}");
+    assert!(
+        summary.missing_payloads.contains(&removed),
+        "should report the missing chunk"
+    );
+    assert_eq!(
+        summary.checked_payloads,
+        index.chunks().len() - 1,
+        "expected all chunks to be visited except the missing one"
+    );
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_check_signatures(#[future] tmprepo: TempRepo) {
+    init_logging();
+    let tmprepo = tmprepo.await;
+
+    let pkcs8 = crate::signing::SigningKey::generate_pkcs8().unwrap();
+    let key = crate::signing::SigningKey::from_pkcs8("farm", &pkcs8).unwrap();
+
+    let manifest = generate_tree(&tmprepo).await.to_graph_manifest();
+    let signed = tmprepo.create_layer(&manifest).await.unwrap();
+    let signed = signed.digest().unwrap();
+    crate::signing::sign_object(&*tmprepo, signed, &key)
+        .await
+        .unwrap();
+    let manifest = generate_tree(&tmprepo).await.to_graph_manifest();
+    let unsigned = tmprepo.create_layer(&manifest).await.unwrap();
+    let unsigned = unsigned.digest().unwrap();
+
+    let summary: CheckSummary = Checker::new(&tmprepo.repo())
+        .with_trusted_keys(vec![key.public_key()])
+        .check_all_objects()
+        .await
+        .unwrap()
+        .iter()
+        .map(|r| r.summary())
+        .sum();
+    tracing::info!("{summary:#?}");
+    assert!(
+        summary.missing_signatures.contains(&unsigned),
+        "should report the unsigned layer"
+    );
+    assert!(
+        !summary.missing_signatures.contains(&signed),
+        "should not report the signed layer"
+    );
+    assert!(summary.invalid_signatures.is_empty());
+
+    let summary: CheckSummary = Checker::new(&tmprepo.repo())
+        .check_all_objects()
+        .await
+        .unwrap()
+        .iter()
+        .map(|r| r.summary())
+        .sum();
+    assert!(
+        summary.missing_signatures.is_empty(),
+        "signatures should not be checked without trusted keys"
+    );
+}
@@ -7,6 +7,7 @@ use std::fmt::Write;
 use std::future::ready;
 #[cfg(unix)]
 use std::os::linux::fs::MetadataExt;
+use std::path::PathBuf;
 
 use chrono::{DateTime, Duration, Local, Utc};
 use colored::Colorize;
@@ -14,8 +15,9 @@ use dashmap::DashSet;
 use futures::{FutureExt, StreamExt, TryFutureExt, TryStreamExt};
 use once_cell::sync::OnceCell;
 use progress_bar_derive_macro::ProgressBar;
+use relative_path::{RelativePath, RelativePathBuf};
 
-use super::prune::PruneParameters;
+use super::prune::{PruneParameters, RetentionPolicy};
 use crate::prelude::*;
 use crate::runtime::makedirs_with_perms;
 use crate::storage::fs::OpenFsRepository;
@@ -45,7 +47,13 @@ where
     must_be_older_than: DateTime<Utc>,
     prune_repeated_tags: bool,
     prune_params: PruneParameters,
+    retention: Vec<RetentionPolicy>,
     remove_proxies_with_no_links: bool,
+    root_repos: Vec<&'repo storage::RepositoryHandle>,
+    mark_file: Option<PathBuf>,
+    mark_only: bool,
+    /// Attached digests that have not yet been saved to the mark file
+    pending_marks: std::sync::Mutex<Vec<encoding::Digest>>,
 }
 
 impl<'repo> Cleaner<'repo, SilentCleanReporter> {
@@ -68,7 +76,12 @@ impl<'repo> Cleaner<'repo, SilentCleanReporter> {
             must_be_older_than: Utc::now(),
             prune_repeated_tags: false,
             prune_params: Default::default(),
+            retention: Vec::new(),
             remove_proxies_with_no_links: true,
+            root_repos: Vec::new(),
+            mark_file: None,
+            mark_only: false,
+            pending_marks: Default::default(),
         }
     }
 }
@@ -88,10 +101,15 @@ where
             must_be_older_than: self.must_be_older_than,
             prune_repeated_tags: self.prune_repeated_tags,
             prune_params: self.prune_params,
+            retention: self.retention,
             removal_concurrency: self.removal_concurrency,
             discover_concurrency: self.discover_concurrency,
             tag_stream_concurrency: self.tag_stream_concurrency,
             remove_proxies_with_no_links: self.remove_proxies_with_no_links,
+            root_repos: self.root_repos,
+            mark_file: self.mark_file,
+            mark_only: self.mark_only,
+            pending_marks: self.pending_marks,
         }
     }
 
@@ -196,6 +214,19 @@ where
         self
     }
 
+    /// Prune the tags that match any of these policies using the
+    /// first matching policy, instead of the other pruning options.
+    ///
+    /// This allows different retention to be used for different sets
+    /// of tags within the same repository.
+    pub fn with_retention_policies<I>(mut self, policies: I) -> Self
+    where
+        I: IntoIterator<Item = RetentionPolicy>,
+    {
+        self.retention.extend(policies);
+        self
+    }
+
     /// When set, also remove any proxies that do not have any hard links
     /// regardless of if they are still attached in the repository.
     ///
@@ -209,6 +240,44 @@ where
         self
     }
 
+    /// Also treat the tags in this repository as keeping data alive.
+    ///
+    /// This allows a repository to be cleaned safely when its data
+    /// is shared with other repositories, such as a payload store
+    /// that is used by multiple users or proxies. The tags of these
+    /// additional repositories are never pruned, and the objects that
+    /// they reference are read from them before the cleaned repository.
+    pub fn with_root_repository(mut self, repo: &'repo storage::RepositoryHandle) -> Self {
+        self.root_repos.push(repo);
+        self
+    }
+
+    /// Save the set of attached objects to this file as they are discovered.
+    ///
+    /// Any objects already recorded in the file are considered to be
+    /// attached along with everything that they reference, and are not
+    /// walked again. This allows the discovery of attached data in very
+    /// large repositories to be resumed after an interruption. The file
+    /// is removed once a clean has been completed, so that any data which
+    /// has since become detached can be removed by the next clean.
+    ///
+    /// The file is only ever read and written by this process, even when
+    /// cleaning a remote repository, and so a clean can only be resumed
+    /// from a machine that can see the same file.
+    pub fn with_mark_file(mut self, mark_file: Option<PathBuf>) -> Self {
+        self.mark_file = mark_file;
+        self
+    }
+
+    /// Only discover the attached objects, without removing any data.
+    ///
+    /// This is only useful along with [`Self::with_mark_file`], so that
+    /// the removal can be completed later by another clean.
+    pub fn with_mark_only(mut self, mark_only: bool) -> Self {
+        self.mark_only = mark_only;
+        self
+    }
+
     /// Provide a human-readable summary of the current
     /// configuration for this cleaner.
     ///
@@ -223,13 +292,41 @@ where
         let identify = "IDENTIFY".cyan();
 
         let mut out = format!("{}:\n", "Cleaning Plan".bold());
-        let _ = writeln!(&mut out, "First, {scan} all of the tags in the repository.",);
+        if let Some(mark_file) = &self.mark_file {
+            let _ = writeln!(
+                &mut out,
+                "First, {identify} the objects recorded as attached in {}",
+                mark_file.display()
+            );
+            let _ = writeln!(
+                &mut out,
+                "Then, {scan} all of the tags in the repository, in every namespace.",
+            );
+        } else {
+            let _ = writeln!(
+                &mut out,
+                "First, {scan} all of the tags in the repository, in every namespace.",
+            );
+        }
         let _ = writeln!(
             &mut out,
             " - {} each item in the tag's history, and for each one:",
             "VISIT".cyan()
         );
-        if self.prune_repeated_tags || !self.prune_params.is_empty() {
+        if !self.retention.is_empty() {
+            let _ = writeln!(
+                &mut out,
+                " - {identify} the first retention policy that matches the tag:",
+            );
+            for policy in self.retention.iter() {
+                let _ = writeln!(&mut out, "   - {policy}");
+            }
+            let _ = writeln!(
+                &mut out,
+                " - {prune} entries using the matching policy, if any, instead of the below",
+            );
+        }
+        if self.prune_repeated_tags || !self.prune_params.is_empty() || !self.retention.is_empty() {
             if self.prune_repeated_tags {
                 let _ = writeln!(
                     &mut out,
@@ -275,6 +372,24 @@ where
                 " - {find} all the objects and payloads connected to it",
             );
         }
+        if !self.root_repos.is_empty() {
+            let _ = writeln!(
+                &mut out,
+                "Then, {scan} all of the tags in {} other repositories",
+                self.root_repos.len()
+            );
+            let _ = writeln!(
+                &mut out,
+                " - {find} all the objects and payloads connected to each item in the tag's history",
+            );
+        }
+        if self.mark_only {
+            let _ = writeln!(
+                &mut out,
+                "Then, stop without removing any objects or payloads"
+            );
+            return out;
+        }
 
         let _ = writeln!(
             &mut out,
@@ -315,9 +430,23 @@ where
     /// partially complete depending on the nature of the errors.
     pub async fn prune_all_tags_and_clean(&self) -> Result<CleanResult> {
         let mut result = CleanResult::default();
-        let mut stream = self.repo.iter_tag_streams().boxed();
+        self.load_marks().await?;
+
+        let mut tag_streams = Vec::new();
+        for namespace in find_tag_namespaces(self.repo).await? {
+            for tag_spec in find_tag_specs(self.repo, namespace.as_deref()).await? {
+                tag_streams.push((None, namespace.clone(), tag_spec));
+            }
+        }
+        for root in self.root_repos.iter().copied() {
+            for namespace in find_tag_namespaces(root).await? {
+                for tag_spec in find_tag_specs(root, namespace.as_deref()).await? {
+                    tag_streams.push((Some(root), namespace.clone(), tag_spec));
+                }
+            }
+        }
         let mut futures = futures::stream::FuturesUnordered::new();
-        while let Some((tag_spec, _stream)) = stream.try_next().await? {
+        for (root, namespace, tag_spec) in tag_streams {
             if futures.len() > self.tag_stream_concurrency {
                 // if we've reached the limit, let the fastest half finish
                 // before adding additional futures. This is a crude way to
@@ -325,12 +454,19 @@ where
                 // completed futures for too long or needing to wait for the
                 // slowest ones too often
                 while futures.len() > self.tag_stream_concurrency / 2 {
-                    futures.try_next().await?;
+                    if let Some(r) = futures.try_next().await? {
+                        result += r;
+                    }
+                }
+                if result.errors.is_empty() {
+                    self.save_marks()?;
                 }
             }
-            futures.push(self.prune_tag_stream_and_walk(tag_spec));
+            futures.push(match root {
+                None => self.prune_tag_stream_and_walk(namespace, tag_spec).boxed(),
+                Some(root) => self.walk_root_tag_stream(root, namespace, tag_spec).boxed(),
+            });
         }
-        drop(stream);
         while let Some(r) = futures.try_next().await? {
             result += r;
         }
@@ -344,6 +480,10 @@ where
             // is not lost.
             return Ok(result);
         }
+        self.save_marks()?;
+        if self.mark_only {
+            return Ok(result);
+        }
 
         // Safety: both of these functions require that the repository is fully
         // walked and all attached objects discovered. See the above block which
@@ -354,27 +494,157 @@ where
             result += self.remove_unvisited_objects_and_payloads().await?;
             result += self.remove_unvisited_renders_and_proxies().await?;
         }
+        if !self.dry_run {
+            self.remove_marks()?;
+        }
+        Ok(result)
+    }
+
+    /// Discover the objects attached to every entry in a tag stream
+    /// from one of the root repositories, which are never pruned
+    async fn walk_root_tag_stream(
+        &self,
+        root: &'repo storage::RepositoryHandle,
+        namespace: Option<storage::TagNamespaceBuf>,
+        tag_spec: tracking::TagSpec,
+    ) -> Result<CleanResult> {
+        let history = root
+            .read_tag_in_namespace(namespace.as_deref(), &tag_spec)
+            .await?
+            .try_collect::<Vec<_>>()
+            .await?;
+        let mut result = CleanResult {
+            visited_tags: history.len() as u64,
+            ..CleanResult::default()
+        };
+        let mut walk_stream = futures::stream::iter(history.iter())
+            .then(|tag| {
+                self.reporter.visit_tag(tag);
+                ready(
+                    self.discover_attached_objects_from(Some(root), tag.target)
+                        .boxed(),
+                )
+            })
+            .buffer_unordered(self.discover_concurrency)
+            .boxed();
+        while let Some(res) = walk_stream.try_next().await? {
+            result += res;
+        }
         Ok(result)
     }
 
-    async fn prune_tag_stream_and_walk(&self, tag_spec: tracking::TagSpec) -> Result<CleanResult> {
+    /// Load the attached objects recorded by a previous clean
+    async fn load_marks(&self) -> Result<()> {
+        let Some(mark_file) = &self.mark_file else {
+            return Ok(());
+        };
+        let data = match tokio::fs::read_to_string(mark_file).await {
+            Ok(data) => data,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            Err(err) => {
+                return Err(Error::StorageReadError(
+                    "read_to_string on mark file",
+                    mark_file.clone(),
+                    err,
+                ))
+            }
+        };
+        for line in data.lines() {
+            // a line that was only partially written by an interrupted
+            // clean is skipped, since it's always safe to walk it again
+            if let Ok(digest) = encoding::Digest::parse(line) {
+                self.attached.insert(digest);
+            }
+        }
+        Ok(())
+    }
+
+    /// Remember a newly attached digest so that it can be saved to the mark file
+    fn record_mark(&self, digest: encoding::Digest) {
+        if self.mark_file.is_some() {
+            self.pending_marks
+                .lock()
+                .expect("mark lock should not be poisoned")
+                .push(digest);
+        }
+    }
+
+    /// Append all pending marks to the mark file
+    fn save_marks(&self) -> Result<()> {
+        let Some(mark_file) = &self.mark_file else {
+            return Ok(());
+        };
+        let marks = std::mem::take(
+            &mut *self
+                .pending_marks
+                .lock()
+                .expect("mark lock should not be poisoned"),
+        );
+        if marks.is_empty() {
+            return Ok(());
+        }
+        let mut data = String::new();
+        for digest in marks {
+            let _ = writeln!(&mut data, "{digest}");
+        }
+        let mut file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(mark_file)
+            .map_err(|err| Error::StorageWriteError("open on mark file", mark_file.clone(), err))?;
+        std::io::Write::write_all(&mut file, data.as_bytes())
+            .and_then(|_| file.sync_data())
+            .map_err(|err| Error::StorageWriteError("write on mark file", mark_file.clone(), err))
+    }
+
+    /// Remove the mark file once it is no longer needed
+    fn remove_marks(&self) -> Result<()> {
+        let Some(mark_file) = &self.mark_file else {
+            return Ok(());
+        };
+        match std::fs::remove_file(mark_file) {
+            Ok(()) => Ok(()),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(err) => Err(Error::StorageWriteError(
+                "remove_file on mark file",
+                mark_file.clone(),
+                err,
+            )),
+        }
+    }
+
+    async fn prune_tag_stream_and_walk(
+        &self,
+        namespace: Option<storage::TagNamespaceBuf>,
+        tag_spec: tracking::TagSpec,
+    ) -> Result<CleanResult> {
+        let namespace = namespace.as_deref();
         let history = self
             .repo
-            .read_tag(&tag_spec)
+            .read_tag_in_namespace(namespace, &tag_spec)
             .await?
             .try_collect::<Vec<_>>()
             .await?;
         let mut to_prune = Vec::with_capacity(history.len() / 2);
         let mut to_keep = Vec::with_capacity(history.len() / 2);
         let mut seen_targets = std::collections::HashSet::new();
+        let policy = self
+            .retention
+            .iter()
+            .find(|p| p.matches(namespace, &tag_spec));
+        let never_prune = policy.is_some_and(RetentionPolicy::never_prune);
         for (i, tag) in history.into_iter().enumerate() {
             let spec = tag.to_spec(i as u64);
             self.reporter.visit_tag(&tag);
-            if !seen_targets.insert(tag.target) && self.prune_repeated_tags {
+            if !seen_targets.insert(tag.target) && self.prune_repeated_tags && !never_prune {
                 to_prune.push(tag);
                 continue;
             }
-            if self.prune_params.should_prune(&spec, &tag) {
+            let should_prune = match policy {
+                Some(policy) => policy.should_prune(&spec, &tag),
+                None => self.prune_params.should_prune(&spec, &tag),
+            };
+            if should_prune {
                 to_prune.push(tag);
             } else {
                 to_keep.push(tag);
@@ -388,12 +658,17 @@ where
 
         for tag in to_prune.iter() {
             if !self.dry_run {
-                self.repo.remove_tag(tag).await?;
+                self.repo.remove_tag_in_namespace(namespace, tag).await?;
             }
             self.reporter.tag_removed(tag);
         }
 
-        result.pruned_tags.insert(tag_spec, to_prune);
+        // the same tag spec can exist in more than one namespace
+        result
+            .pruned_tags
+            .entry(tag_spec)
+            .or_default()
+            .extend(to_prune);
 
         let mut walk_stream = futures::stream::iter(to_keep.iter())
             .then(|tag| ready(self.discover_attached_objects(tag.target).boxed()))
@@ -406,18 +681,36 @@ where
         Ok(result)
     }
 
-    #[async_recursion::async_recursion]
     async fn discover_attached_objects(&self, digest: encoding::Digest) -> Result<CleanResult> {
+        self.discover_attached_objects_from(None, digest).await
+    }
+
+    /// Discover the objects attached to the given digest, reading them
+    /// from the provided root repository first, if one is given
+    #[async_recursion::async_recursion]
+    async fn discover_attached_objects_from(
+        &self,
+        root: Option<&'repo storage::RepositoryHandle>,
+        digest: encoding::Digest,
+    ) -> Result<CleanResult> {
         let mut result = CleanResult::default();
         if !self.attached.insert(digest) {
             return Ok(result);
         }
 
-        let obj = match self.repo.read_object(digest).await {
+        let obj = match root {
+            Some(root) => match root.read_object(digest).await {
+                Err(Error::UnknownObject(_)) => self.repo.read_object(digest).await,
+                res => res,
+            },
+            None => self.repo.read_object(digest).await,
+        };
+        let obj = match obj {
             Ok(obj) => obj,
             Err(Error::UnknownObject(_)) => {
                 // TODO: it would be nice to have an option to prune
                 // broken tags that cause this error
+                self.record_mark(digest);
                 return Ok(result);
             }
             Err(err) => {
@@ -431,17 +724,42 @@ where
         if let graph::object::Enum::Blob(b) = obj.to_enum() {
             result.visited_payloads += 1;
             self.reporter.visit_payload(&b);
+            if let Err(err) = self.attach_payload_chunks(*b.payload()).await {
+                self.reporter.error_encountered(&err);
+                result.errors.push(err);
+            }
         }
         let mut walk_stream = futures::stream::iter(obj.child_objects())
-            .then(|child| ready(self.discover_attached_objects(child).boxed()))
+            .then(|child| ready(self.discover_attached_objects_from(root, child).boxed()))
             .buffer_unordered(self.discover_concurrency)
             .boxed();
         while let Some(res) = walk_stream.try_next().await? {
             result += res;
         }
+        // objects are only marked once all of their children have been, so
+        // that saved marks never refer to an object whose walk is incomplete.
+        // Any child that was skipped here is still being walked from one of
+        // the unmarked tags in progress, which would be walked again if
+        // this clean is resumed.
+        if result.errors.is_empty() {
+            self.record_mark(digest);
+        }
         Ok(result)
     }
 
+    /// Mark the chunks of a chunked payload as attached, since they
+    /// are not otherwise reachable from any object
+    async fn attach_payload_chunks(&self, payload: encoding::Digest) -> Result<()> {
+        if let Some(index) = self.repo.read_chunk_index(payload).await? {
+            for chunk in index.chunks() {
+                if self.attached.insert(chunk.digest) {
+                    self.record_mark(chunk.digest);
+                }
+            }
+        }
+        Ok(())
+    }
+
     /// # Safety
     /// This function should only be called once the discovery of all attached
     /// objects has completed successfully and with no errors. Otherwise, it may
@@ -477,14 +795,18 @@ where
                 ready(Ok(future.boxed()))
             })
             .try_buffer_unordered(self.removal_concurrency)
-            .try_filter_map(|(digest, obj, removed)| {
+            .and_then(|(digest, obj, removed)| async move {
                 if !removed {
                     // objects that are too new to be removed become
-                    // implicitly attached
+                    // implicitly attached, along with any payload chunks
                     self.attached.insert(digest);
+                    if let graph::object::Enum::Blob(blob) = obj.to_enum() {
+                        self.attach_payload_chunks(*blob.payload()).await?;
+                    }
                 }
-                ready(Ok(removed.then_some(obj)))
+                Ok(removed.then_some(obj))
             })
+            .try_filter_map(|obj| ready(Ok(obj)))
             .and_then(|obj| {
                 self.reporter.object_removed(&obj);
                 ready(Ok(obj))
@@ -735,6 +1057,56 @@ where
     }
 }
 
+/// Find every tag namespace in a repository, starting
+/// with the root namespace, which is represented as `None`
+async fn find_tag_namespaces(
+    repo: &storage::RepositoryHandle,
+) -> Result<Vec<Option<storage::TagNamespaceBuf>>> {
+    let mut namespaces = vec![None];
+    let mut next = 0;
+    while let Some(parent) = namespaces.get(next).cloned() {
+        next += 1;
+        let mut entries = repo.ls_tags_in_namespace(parent.as_deref(), RelativePath::new("/"));
+        while let Some(entry) = entries.try_next().await? {
+            let storage::EntryType::Namespace(name) = entry else {
+                continue;
+            };
+            let path = match &parent {
+                Some(parent) => parent.as_rel_path().join(name),
+                None => RelativePathBuf::from(name),
+            };
+            namespaces.push(Some(storage::TagNamespaceBuf::new(path)));
+        }
+    }
+    Ok(namespaces)
+}
+
+/// Find all of the tag streams in a single namespace of the repository,
+/// without descending into any of the namespaces nested within it
+async fn find_tag_specs(
+    repo: &storage::RepositoryHandle,
+    namespace: Option<&storage::TagNamespace>,
+) -> Result<Vec<tracking::TagSpec>> {
+    let mut specs = Vec::new();
+    let mut folders = vec![RelativePathBuf::from("/")];
+    while let Some(folder) = folders.pop() {
+        let mut entries = repo.ls_tags_in_namespace(namespace, &folder);
+        while let Some(entry) = entries.try_next().await? {
+            match entry {
+                storage::EntryType::Folder(name) => folders.push(folder.join(name)),
+                storage::EntryType::Tag(name) => {
+                    let path = folder.join(name);
+                    specs.push(tracking::TagSpec::parse(
+                        path.as_str().trim_start_matches('/'),
+                    )?);
+                }
+                storage::EntryType::Namespace(_) => {}
+            }
+        }
+    }
+    Ok(specs)
+}
+
 #[derive(Debug, Default)]
 pub struct CleanResult {
     /// The number of tags visited when walking the database
@@ -289,3 +289,187 @@ fn list_files<P: AsRef<std::path::Path>>(dirname: P) -> Vec<String> {
     }
     all_files
 }
+
+#[rstest]
+#[tokio::test]
+async fn test_clean_keeps_attached_payload_chunks(#[future] tmprepo: TempRepo) {
+    init_logging();
+    let tmprepo = tmprepo.await;
+    let storage::RepositoryHandle::FS(fs_repo) = &*tmprepo else {
+        panic!("expected an fs repository");
+    };
+    let mut writer = (*fs_repo.opened().await.unwrap()).clone();
+    writer.set_payload_chunking_threshold(Some(0));
+
+    let data = random_data(12 * 1024 * 1024);
+    let digest = writer
+        .commit_blob(Box::pin(std::io::Cursor::new(data.clone())))
+        .await
+        .unwrap();
+    let unattached = writer
+        .commit_blob(Box::pin(std::io::Cursor::new(random_data(
+            12 * 1024 * 1024,
+        ))))
+        .await
+        .unwrap();
+
+    let index = tmprepo
+        .read_chunk_index(digest)
+        .await
+        .unwrap()
+        .expect("payload should be chunked");
+    let tag = tracking::TagSpec::parse("my_tag").unwrap();
+    tmprepo.push_tag(&tag, &digest).await.unwrap();
+
+    let cleaner = Cleaner::new(&tmprepo).with_reporter(TracingCleanReporter);
+    cleaner
+        .prune_all_tags_and_clean()
+        .await
+        .expect("failed to clean repo");
+
+    for chunk in index.chunks() {
+        assert!(
+            tmprepo.has_payload(chunk.digest).await,
+            "attached payload chunks should not be removed"
+        );
+    }
+    assert!(tmprepo.has_payload(digest).await);
+    assert!(!tmprepo.has_payload(unattached).await);
+    assert!(tmprepo
+        .read_chunk_index(unattached)
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_clean_keeps_data_tagged_in_root_repository(
+    #[future] tmprepo: TempRepo,
+    tmpdir: tempfile::TempDir,
+) {
+    init_logging();
+    let tmprepo = tmprepo.await;
+    let root_repo = crate::fixtures::tmprepo("fs").await;
+
+    let data_dir = tmpdir.path().join("data");
+    ensure(data_dir.join("file.txt"), "shared data");
+    let manifest = crate::Committer::new(&tmprepo)
+        .commit_dir(data_dir.as_path())
+        .await
+        .unwrap();
+    let layer = tmprepo
+        .create_layer(&manifest.to_graph_manifest())
+        .await
+        .unwrap();
+    let blob_digest = manifest.root().entries.get("file.txt").unwrap().object;
+    // only the root repository knows that this data is still in use
+    let tag = tracking::TagSpec::parse("shared").unwrap();
+    root_repo
+        .push_tag(&tag, &layer.digest().unwrap())
+        .await
+        .unwrap();
+
+    let result = Cleaner::new(&tmprepo)
+        .with_reporter(TracingCleanReporter)
+        .with_dry_run(true)
+        .prune_all_tags_and_clean()
+        .await
+        .unwrap();
+    assert!(
+        result.removed_payloads.contains(&blob_digest),
+        "data should be removed when the root repository is not considered"
+    );
+
+    Cleaner::new(&tmprepo)
+        .with_reporter(TracingCleanReporter)
+        .with_root_repository(&root_repo)
+        .prune_all_tags_and_clean()
+        .await
+        .expect("failed to clean repo");
+    tmprepo
+        .open_payload(blob_digest)
+        .await
+        .expect("data tagged in a root repository should not be removed");
+    assert!(
+        root_repo.resolve_tag(&tag).await.is_ok(),
+        "tags in root repositories should never be pruned"
+    );
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_clean_resumes_from_mark_file(#[future] tmprepo: TempRepo, tmpdir: tempfile::TempDir) {
+    init_logging();
+    let tmprepo = tmprepo.await;
+
+    let data_dir = tmpdir.path().join("data");
+    ensure(data_dir.join("file.txt"), "marked data");
+    let manifest = crate::Committer::new(&tmprepo)
+        .commit_dir(data_dir.as_path())
+        .await
+        .unwrap();
+    let layer = tmprepo
+        .create_layer(&manifest.to_graph_manifest())
+        .await
+        .unwrap();
+    let blob_digest = manifest.root().entries.get("file.txt").unwrap().object;
+    let tag = tracking::TagSpec::parse("marked").unwrap();
+    tmprepo
+        .push_tag(&tag, &layer.digest().unwrap())
+        .await
+        .unwrap();
+
+    let mark_file = tmpdir.path().join("marks");
+    Cleaner::new(&tmprepo)
+        .with_reporter(TracingCleanReporter)
+        .with_mark_file(Some(mark_file.clone()))
+        .with_mark_only(true)
+        .prune_all_tags_and_clean()
+        .await
+        .expect("failed to mark repo");
+    let marks = std::fs::read_to_string(&mark_file).expect("mark file should be written");
+    let marks = marks.lines().collect::<Vec<_>>();
+    let layer_digest = layer.digest().unwrap().to_string();
+    let blob_mark = blob_digest.to_string();
+    let layer_pos = marks.iter().position(|m| *m == layer_digest);
+    let blob_pos = marks.iter().position(|m| *m == blob_mark);
+    assert!(layer_pos.is_some(), "layer should be marked");
+    assert!(blob_pos.is_some(), "blob should be marked");
+    assert!(
+        blob_pos < layer_pos,
+        "objects should only be marked after everything they reference"
+    );
+
+    // the recorded marks are trusted when resuming, so the
+    // data is kept even though it is no longer tagged
+    tmprepo.remove_tag_stream(&tag).await.unwrap();
+    Cleaner::new(&tmprepo)
+        .with_reporter(TracingCleanReporter)
+        .with_mark_file(Some(mark_file.clone()))
+        .prune_all_tags_and_clean()
+        .await
+        .expect("failed to clean repo");
+    tmprepo
+        .open_payload(blob_digest)
+        .await
+        .expect("marked data should not be removed");
+    assert!(
+        !mark_file.exists(),
+        "mark file should be removed after a completed clean"
+    );
+
+    Cleaner::new(&tmprepo)
+        .with_reporter(TracingCleanReporter)
+        .with_mark_file(Some(mark_file.clone()))
+        .prune_all_tags_and_clean()
+        .await
+        .expect("failed to clean repo");
+    assert!(
+        matches!(
+            tmprepo.open_payload(blob_digest).await,
+            Err(Error::UnknownObject(_))
+        ),
+        "detached data should be removed once the marks are cleared"
+    );
+}
@@ -73,13 +73,23 @@ pub struct Committer<
     builder: ManifestBuilder<H, F, Arc<Reporter>>,
     max_concurrent_blobs: usize,
     allow_empty: bool,
+    drop_xattrs: bool,
 }
 
 impl<'repo> Committer<'repo, InMemoryBlobHasher, (), SilentCommitReporter> {
     /// Create a new committer, with the default [`InMemoryBlobHasher`].
     pub fn new(repo: &'repo storage::RepositoryHandle) -> Self {
         let reporter = Arc::new(SilentCommitReporter);
+        // objects in the legacy encoding format have no place to store
+        // extended attributes, so any that are captured must be dropped
+        let drop_xattrs = matches!(
+            crate::get_config()
+                .map(|config| config.storage.encoding_format)
+                .unwrap_or(graph::object::EncodingFormat::Legacy),
+            graph::object::EncodingFormat::Legacy
+        );
         let builder = ManifestBuilder::new()
+            .with_xattrs(true)
             .with_blob_hasher(InMemoryBlobHasher)
             .with_reporter(Arc::clone(&reporter));
         Self {
@@ -88,6 +98,7 @@ impl<'repo> Committer<'repo, InMemoryBlobHasher, (), SilentCommitReporter> {
             builder,
             max_concurrent_blobs: tracking::DEFAULT_MAX_CONCURRENT_BLOBS,
             allow_empty: false,
+            drop_xattrs,
         }
     }
 }
@@ -131,6 +142,17 @@ where
         self
     }
 
+    /// Set whether extended attributes, including posix acls and
+    /// file capabilities, are captured into the committed manifest.
+    ///
+    /// Defaults to true. When the legacy encoding format is configured
+    /// for new objects, which has no place to store them, any captured
+    /// attributes are dropped with a warning instead.
+    pub fn with_xattrs(mut self, capture_xattrs: bool) -> Self {
+        self.builder = self.builder.with_xattrs(capture_xattrs);
+        self
+    }
+
     /// Use the given [`BlobHasher`] when building the manifest.
     ///
     /// See [`InMemoryBlobHasher`] and [`WriteToRepositoryBlobHasher`] for
@@ -145,6 +167,7 @@ where
             reporter: self.reporter,
             max_concurrent_blobs: self.max_concurrent_blobs,
             allow_empty: self.allow_empty,
+            drop_xattrs: self.drop_xattrs,
         }
     }
 
@@ -160,6 +183,7 @@ where
             reporter,
             max_concurrent_blobs: self.max_concurrent_blobs,
             allow_empty: self.allow_empty,
+            drop_xattrs: self.drop_xattrs,
         }
     }
 
@@ -180,6 +204,7 @@ where
             reporter: self.reporter,
             max_concurrent_blobs: self.max_concurrent_blobs,
             allow_empty: self.allow_empty,
+            drop_xattrs: self.drop_xattrs,
         }
     }
 
@@ -194,9 +219,10 @@ where
     /// This will add the layer to the current runtime and then remount it.
     pub async fn commit_manifest(
         &self,
-        manifest: tracking::Manifest,
+        mut manifest: tracking::Manifest,
         runtime: &mut runtime::Runtime,
     ) -> Result<graph::Layer> {
+        self.drop_unsupported_xattrs(&mut manifest);
         if manifest.is_empty() && !self.allow_empty {
             return Err(Error::NothingToCommit);
         }
@@ -244,10 +270,25 @@ where
     {
         let path = dunce::canonicalize(&path)
             .map_err(|err| Error::InvalidPath(path.as_ref().to_owned(), err))?;
-        let manifest = self.builder.compute_manifest(&path).await?;
+        let mut manifest = self.builder.compute_manifest(&path).await?;
+        self.drop_unsupported_xattrs(&mut manifest);
         Ok((path, manifest))
     }
 
+    /// Remove any extended attributes that cannot be stored in the
+    /// configured encoding format, warning that they were dropped
+    fn drop_unsupported_xattrs(&self, manifest: &mut tracking::Manifest) {
+        if !self.drop_xattrs {
+            return;
+        }
+        let dropped = manifest.clear_xattrs();
+        if dropped > 0 {
+            tracing::warn!(
+                "Dropped the extended attributes of {dropped} files, they cannot be stored in the legacy encoding format"
+            );
+        }
+    }
+
     /// Commit a local file system directory to this storage.
     ///
     /// This collects all files to store as blobs and maintains a
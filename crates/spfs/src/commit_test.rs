@@ -6,7 +6,9 @@ use rstest::rstest;
 
 use super::Committer;
 use crate::fixtures::*;
-use crate::Error;
+use crate::graph::object::EncodingFormat;
+use crate::storage::prelude::Database;
+use crate::{Config, Error};
 
 #[rstest]
 #[tokio::test]
@@ -36,3 +38,42 @@ async fn test_commit_empty(tmpdir: tempfile::TempDir) {
         res => panic!("expected nothing to commit, got {res:?}"),
     }
 }
+
+#[cfg(unix)]
+#[rstest(encoding_format => [EncodingFormat::Legacy, EncodingFormat::FlatBuffers])]
+#[tokio::test]
+#[serial_test::serial(config)]
+async fn test_commit_xattrs_for_encoding(
+    tmpdir: tempfile::TempDir,
+    encoding_format: EncodingFormat,
+) {
+    let mut config = Config::default();
+    config.storage.encoding_format = encoding_format;
+    config.make_current().unwrap();
+
+    let dir = tmpdir.path().join("dir");
+    ensure(dir.join("bin/runme"), "somedata");
+    if let Err(err) = xattr::set(dir.join("bin/runme"), "user.spfs.test", b"value") {
+        tracing::warn!("skipping test, xattrs not supported in tmpdir: {err}");
+        return;
+    }
+    let repo = crate::storage::RepositoryHandle::from(
+        crate::storage::fs::FsRepository::create(tmpdir.path().join("repo"))
+            .await
+            .unwrap(),
+    );
+
+    let manifest = Committer::new(&repo)
+        .commit_dir(&dir)
+        .await
+        .expect("xattrs should not fail the commit");
+    let captured = !manifest.get_path("bin/runme").unwrap().xattrs.is_empty();
+    assert_eq!(
+        captured,
+        !matches!(encoding_format, EncodingFormat::Legacy),
+        "xattrs should only be captured when they can be encoded"
+    );
+    repo.write_object(&manifest.to_graph_manifest())
+        .await
+        .expect("manifest should be written in the configured encoding");
+}
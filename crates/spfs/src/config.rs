@@ -24,6 +24,8 @@ mod config_test;
 const DEFAULT_USER_STORAGE: &str = "spfs";
 const FALLBACK_STORAGE_ROOT: &str = "/tmp/spfs";
 const LOCAL_STORAGE_NAME: &str = "<local storage>";
+/// The name given to the configured signing key when none is specified
+pub const DEFAULT_SIGNING_KEY_NAME: &str = "default";
 
 fn default_fuse_worker_threads() -> NonZeroUsize {
     let num_cpu = num_cpus::get();
@@ -40,6 +42,11 @@ const fn default_fuse_max_blocking_threads() -> NonZeroUsize {
     unsafe { NonZeroUsize::new_unchecked(512) }
 }
 
+const fn default_fuse_payload_cache_size_mb() -> u64 {
+    // disabled unless configured
+    0
+}
+
 fn default_monitor_worker_threads() -> NonZeroUsize {
     let num_cpu = num_cpus::get();
     // typically fuse does not need a huge number of threads
@@ -121,6 +128,17 @@ pub struct Storage {
     /// All available formats are still supported for reading.
     #[serde(default)]
     pub encoding_format: graph::object::EncodingFormat,
+    /// Payloads of at least this many bytes are stored as a sequence of
+    /// content-defined chunks when written to the local repository.
+    ///
+    /// Similar payloads are then able to share most of their data, both
+    /// on disk and when syncing between repositories. Chunking is
+    /// disabled when this is not set.
+    pub payload_chunking_threshold: Option<u64>,
+    /// The compression to apply to payloads that are written to
+    /// the local repository.
+    #[serde(default)]
+    pub payload_compression: storage::fs::PayloadCompression,
 }
 
 impl Storage {
@@ -143,12 +161,15 @@ impl Default for Storage {
             tag_namespace: None,
             digest_strategy: graph::object::DigestStrategy::default(),
             encoding_format: graph::object::EncodingFormat::default(),
+            payload_chunking_threshold: None,
+            payload_compression: Default::default(),
         }
     }
 }
 
 #[derive(Clone, Debug, Serialize)]
 #[serde(untagged)]
+#[allow(clippy::large_enum_variant)]
 pub enum Remote {
     Address(RemoteAddress),
     Config(RemoteConfig),
@@ -163,6 +184,25 @@ impl ToAddress for Remote {
     }
 }
 
+impl Remote {
+    /// True if layers and platforms from this remote must
+    /// be signed by a trusted key before they can be used.
+    pub fn requires_signatures(&self) -> bool {
+        match self {
+            Self::Address(addr) => address_requires_signatures(&addr.address),
+            Self::Config(conf) => conf.require_signatures,
+        }
+    }
+}
+
+/// True if the given repository address has the `require_signatures`
+/// query parameter set, see [`RemoteConfig::require_signatures`]
+pub fn address_requires_signatures(address: &url::Url) -> bool {
+    address
+        .query_pairs()
+        .any(|(k, v)| k == "require_signatures" && (v == "true" || v == "1"))
+}
+
 impl<'de> serde::de::Deserialize<'de> for Remote {
     fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
     where
@@ -196,6 +236,11 @@ pub struct RemoteConfig {
     #[builder(setter(strip_option), default)]
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub tag_namespace: Option<TagNamespaceBuf>,
+    /// Refuse to use layers and platforms from this repository
+    /// unless they have a signature from one of the trusted keys
+    #[builder(default)]
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub require_signatures: bool,
     #[serde(flatten)]
     pub inner: RepositoryConfig,
 }
@@ -205,6 +250,7 @@ impl ToAddress for RemoteConfig {
         let Self {
             when,
             tag_namespace,
+            require_signatures,
             inner,
         } = self;
         let mut inner = inner.to_address()?;
@@ -222,6 +268,13 @@ impl ToAddress for RemoteConfig {
                 Some(q) => inner.set_query(Some(&format!("{q}&{query}"))),
             }
         }
+        if *require_signatures {
+            let query = "require_signatures=true";
+            match inner.query() {
+                None | Some("") => inner.set_query(Some(query)),
+                Some(q) => inner.set_query(Some(&format!("{q}&{query}"))),
+            }
+        }
         Ok(inner)
     }
 }
@@ -230,9 +283,13 @@ impl ToAddress for RemoteConfig {
 #[serde(tag = "scheme", rename_all = "lowercase")]
 pub enum RepositoryConfig {
     Fs(storage::fs::Config),
+    #[serde(alias = "grpcs")]
     Grpc(storage::rpc::Config),
     Tar(storage::tar::Config),
     Proxy(storage::proxy::Config),
+    S3(storage::s3::Config),
+    #[serde(alias = "https")]
+    Http(storage::http::Config),
 }
 
 impl ToAddress for RepositoryConfig {
@@ -242,6 +299,8 @@ impl ToAddress for RepositoryConfig {
             Self::Grpc(c) => c.to_address(),
             Self::Tar(c) => c.to_address(),
             Self::Proxy(c) => c.to_address(),
+            Self::S3(c) => c.to_address(),
+            Self::Http(c) => c.to_address(),
         }
     }
 }
@@ -258,6 +317,9 @@ impl RemoteConfig {
                 "tag_namespace" => {
                     builder.tag_namespace(TagNamespaceBuf::new(RelativePath::new(&v)));
                 }
+                "require_signatures" => {
+                    builder.require_signatures(address_requires_signatures(&url));
+                }
                 _ => (),
             }
         }
@@ -268,12 +330,18 @@ impl RemoteConfig {
             "file" | "" => storage::fs::Config::from_url(&url)
                 .await
                 .map(RepositoryConfig::Fs),
-            "http2" | "grpc" => storage::rpc::Config::from_url(&url)
+            "http2" | "grpc" | "grpcs" => storage::rpc::Config::from_url(&url)
                 .await
                 .map(RepositoryConfig::Grpc),
             "proxy" => storage::proxy::Config::from_url(&url)
                 .await
                 .map(RepositoryConfig::Proxy),
+            "s3" => storage::s3::Config::from_url(&url)
+                .await
+                .map(RepositoryConfig::S3),
+            "http" | "https" => storage::http::Config::from_url(&url)
+                .await
+                .map(RepositoryConfig::Http),
             scheme => return Err(format!("Unsupported repository scheme: '{scheme}'").into()),
         };
         builder.inner(result.map_err(|source| Error::FailedToOpenRepository {
@@ -298,6 +366,7 @@ impl RemoteConfig {
         let Self {
             when,
             tag_namespace,
+            require_signatures: _,
             inner,
         } = self;
         let mut handle: storage::RepositoryHandle = match inner.clone() {
@@ -313,6 +382,12 @@ impl RemoteConfig {
             RepositoryConfig::Proxy(config) => storage::proxy::ProxyRepository::from_config(config)
                 .await?
                 .into(),
+            RepositoryConfig::S3(config) => {
+                storage::s3::S3Repository::from_config(config).await?.into()
+            }
+            RepositoryConfig::Http(config) => storage::http::HttpRepository::from_config(config)
+                .await?
+                .into(),
         };
         // Set tag namespace first before pinning, because it is not possible
         // to set the tag namespace on a pinned handle.
@@ -357,6 +432,14 @@ pub struct Filesystem {
     /// directly in the annotation layer.
     #[serde(default = "Filesystem::default_annotation_size_limit")]
     pub annotation_size_limit: usize,
+
+    /// Set up new runtimes in an unprivileged user namespace, rather
+    /// than relying on the capabilities of the spfs-enter binary.
+    ///
+    /// This requires a system that allows unprivileged user namespaces
+    /// and, for the overlayfs backends, a kernel that supports mounting
+    /// overlayfs from within them (linux 5.11+).
+    pub rootless: bool,
 }
 
 impl Filesystem {
@@ -382,6 +465,13 @@ pub struct Fuse {
     pub worker_threads: NonZeroUsize,
     #[serde(default = "default_fuse_max_blocking_threads")]
     pub max_blocking_threads: NonZeroUsize,
+    /// The maximum size, in megabytes, of the local cache of payloads
+    /// read from remote repositories. Zero disables the cache.
+    #[serde(default = "default_fuse_payload_cache_size_mb")]
+    pub payload_cache_size_mb: u64,
+    /// Where to store the local cache of payloads read from remote
+    /// repositories, defaults to a directory in the local storage root
+    pub payload_cache_root: Option<PathBuf>,
 }
 
 impl Default for Fuse {
@@ -389,10 +479,26 @@ impl Default for Fuse {
         Self {
             worker_threads: default_fuse_worker_threads(),
             max_blocking_threads: default_fuse_max_blocking_threads(),
+            payload_cache_size_mb: default_fuse_payload_cache_size_mb(),
+            payload_cache_root: None,
         }
     }
 }
 
+impl Fuse {
+    /// The name of the payload cache directory in the local
+    /// storage root, when no other location is configured
+    pub const DEFAULT_PAYLOAD_CACHE_DIR: &'static str = "fuse_cache";
+
+    /// The directory used to cache remote payloads, given the
+    /// root of the local storage
+    pub fn payload_cache_root(&self, storage_root: &std::path::Path) -> PathBuf {
+        self.payload_cache_root
+            .clone()
+            .unwrap_or_else(|| storage_root.join(Self::DEFAULT_PAYLOAD_CACHE_DIR))
+    }
+}
+
 /// Configuration options for the monitor process
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(default)]
@@ -401,6 +507,8 @@ pub struct Monitor {
     pub worker_threads: NonZeroUsize,
     #[serde(default = "default_monitor_max_blocking_threads")]
     pub max_blocking_threads: NonZeroUsize,
+    /// Record the resources consumed by each runtime in its status
+    pub track_resource_usage: bool,
 }
 
 impl Default for Monitor {
@@ -408,10 +516,184 @@ impl Default for Monitor {
         Self {
             worker_threads: default_monitor_worker_threads(),
             max_blocking_threads: default_monitor_max_blocking_threads(),
+            track_resource_usage: false,
         }
     }
 }
 
+/// Configuration options for cleaning repositories
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Clean {
+    /// Policies that control how long tags are kept in a repository
+    ///
+    /// Each tag stream is pruned using the first policy that matches it,
+    /// and tags that match no policy are pruned using the options given
+    /// to the clean command
+    pub retention: Vec<TagRetention>,
+}
+
+/// Conditions for pruning the history of the tags that match a pattern
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct TagRetention {
+    /// A glob pattern matched against the path of each tag (eg: `ci/**`),
+    /// which matches all tags when not given
+    pub tags: Option<String>,
+    /// Only match the tags in this tag namespace, in which case the
+    /// pattern is matched against the path of the tag within it
+    pub namespace: Option<TagNamespaceBuf>,
+    /// Never prune the matching tags, regardless of any other settings
+    pub never_prune: bool,
+    /// Prune tags older than this age (eg: 1y, 8w, 10d, 3h, 4m, 8s)
+    pub prune_if_older_than: Option<String>,
+    /// Always keep tags newer than this age
+    pub keep_if_newer_than: Option<String>,
+    /// Prune tags if there are more than this number in a stream
+    pub prune_if_version_more_than: Option<u64>,
+    /// Always keep at least this number of tags in a stream
+    pub keep_if_version_less_than: Option<u64>,
+}
+
+/// Configuration options for the spfs server
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Server {
+    /// Identifies clients of the server, and what they are allowed to do
+    pub auth: ServerAuth,
+}
+
+/// The clients that are allowed to connect to the spfs server.
+///
+/// When no tokens or certificates are configured, all requests
+/// to the server are allowed.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct ServerAuth {
+    /// Bearer tokens that clients can present in their requests
+    pub tokens: Vec<AuthGrant>,
+    /// Client certificates that can be presented when connecting
+    /// to the server over mutual TLS
+    pub client_certificates: Vec<AuthGrant>,
+}
+
+impl ServerAuth {
+    /// True if any client credentials have been configured
+    pub fn is_enabled(&self) -> bool {
+        !self.tokens.is_empty() || !self.client_certificates.is_empty()
+    }
+}
+
+/// A set of permissions given to the holder of a credential
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct AuthGrant {
+    /// A name for the client, used when logging requests
+    pub name: String,
+    /// The hex-encoded sha256 digest of the credential.
+    ///
+    /// For tokens, this is the digest of the token itself so that
+    /// it does not need to be stored in the configuration. For
+    /// certificates, it is the fingerprint of the DER-encoded certificate.
+    pub sha256: String,
+    /// The operations that this client is allowed to perform
+    #[serde(default)]
+    pub permissions: Vec<Permission>,
+}
+
+/// An operation that a client may be allowed to perform
+/// on a repository served by the spfs server.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Permission {
+    /// Read any object, payload or tag
+    Read,
+    /// Read everything, and write objects and payloads along with
+    /// tags that are within the given namespace
+    WriteTags(TagNamespaceBuf),
+    /// Read and write any object, payload or tag
+    Write,
+    /// Perform any operation, including the removal of objects
+    /// and payloads (eg: as needed to clean the repository)
+    Admin,
+}
+
+impl Permission {
+    const WRITE_TAGS_PREFIX: &'static str = "write-tags:";
+}
+
+impl std::str::FromStr for Permission {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "read" => Ok(Self::Read),
+            "write" => Ok(Self::Write),
+            "admin" => Ok(Self::Admin),
+            _ => match s.strip_prefix(Self::WRITE_TAGS_PREFIX) {
+                Some(namespace) if !namespace.is_empty() => {
+                    Ok(Self::WriteTags(TagNamespaceBuf::new(namespace)))
+                }
+                _ => Err(Error::String(format!(
+                    "Invalid permission '{s}', expected one of: read, write, admin, {}<namespace>",
+                    Self::WRITE_TAGS_PREFIX
+                ))),
+            },
+        }
+    }
+}
+
+impl TryFrom<String> for Permission {
+    type Error = Error;
+
+    fn try_from(value: String) -> Result<Self> {
+        value.parse()
+    }
+}
+
+impl std::fmt::Display for Permission {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Read => f.write_str("read"),
+            Self::WriteTags(namespace) => write!(f, "{}{namespace}", Self::WRITE_TAGS_PREFIX),
+            Self::Write => f.write_str("write"),
+            Self::Admin => f.write_str("admin"),
+        }
+    }
+}
+
+impl From<Permission> for String {
+    fn from(value: Permission) -> Self {
+        value.to_string()
+    }
+}
+
+/// Configuration for the signing and verification of objects
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct Signing {
+    /// A PKCS#8 file (DER or PEM) holding the ed25519 key
+    /// used when signing objects
+    pub key_file: Option<PathBuf>,
+    /// The name under which signatures made with the signing key are
+    /// stored, defaults to [`DEFAULT_SIGNING_KEY_NAME`]
+    pub key_name: Option<String>,
+    /// The public keys whose signatures are trusted
+    pub trusted_keys: Vec<TrustedKey>,
+    /// Refuse to render any layer into a runtime unless it has
+    /// a signature from one of the trusted keys
+    pub require_signatures: bool,
+}
+
+/// A public key whose signatures are trusted
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct TrustedKey {
+    /// The name of the key, which must match the name used
+    /// when the signatures were made
+    pub name: String,
+    /// The hex-encoded ed25519 public key
+    pub public_key: String,
+}
+
 #[derive(Clone, Default, Debug, Deserialize, Serialize)]
 #[serde(default)]
 pub struct Sentry {
@@ -442,6 +724,9 @@ pub struct Config {
     pub fuse: Fuse,
     pub monitor: Monitor,
     pub sentry: Sentry,
+    pub server: Server,
+    pub signing: Signing,
+    pub clean: Clean,
 }
 
 impl Config {
@@ -524,6 +809,8 @@ impl Config {
         })?;
 
         local_repo.set_tag_namespace(self.storage.tag_namespace.clone());
+        local_repo.set_payload_compression(self.storage.payload_compression);
+        local_repo.set_payload_chunking_threshold(self.storage.payload_chunking_threshold);
 
         Ok(local_repo)
     }
@@ -579,6 +866,15 @@ impl Config {
         }
     }
 
+    /// True if the named remote requires trusted signatures on its
+    /// layers and platforms, see [`RemoteConfig::require_signatures`]
+    pub fn remote_requires_signatures<S: AsRef<str>>(&self, remote_name: S) -> bool {
+        self.remote
+            .get(remote_name.as_ref())
+            .map(Remote::requires_signatures)
+            .unwrap_or_default()
+    }
+
     /// Get a remote repository by name.
     pub async fn get_remote<S: AsRef<str>>(
         &self,
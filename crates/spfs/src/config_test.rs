@@ -73,6 +73,17 @@ fn test_remote_config_or_address(#[case] source: &str) {
     let _config: Config = serde_json::from_str(source).expect("config should have loaded properly");
 }
 
+#[rstest]
+fn test_grpcs_remote_config() {
+    let config: RepositoryConfig =
+        serde_json::from_str(r#"{"scheme": "grpcs", "address": "grpcs://my-domain.com:1234"}"#)
+            .expect("grpcs should be accepted as a scheme");
+    let RepositoryConfig::Grpc(config) = config else {
+        panic!("expected a grpc repository config, got {config:?}");
+    };
+    assert_eq!(config.address.scheme(), "grpcs");
+}
+
 #[rstest]
 fn test_config_expands_tilde_in_paths() {
     let source = r#"
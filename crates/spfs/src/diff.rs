@@ -4,9 +4,17 @@
 
 use std::sync::Arc;
 
+use relative_path::RelativePath;
+use tokio::io::AsyncReadExt;
+
 use super::resolve::compute_manifest;
 use super::status::{active_runtime, compute_runtime_manifest};
-use crate::{tracking, Result};
+use crate::env::SPFS_DIR;
+use crate::prelude::*;
+use crate::{encoding, storage, tracking, Error, Result};
+
+/// The largest file that will be compared line by line in [`diff_content`]
+pub const MAX_CONTENT_DIFF_SIZE: u64 = 1024 * 1024;
 
 ///  Return the changes going from 'base' to 'top'.
 ///
@@ -19,31 +27,161 @@ pub async fn diff(
     base: Option<&String>,
     top: Option<&String>,
 ) -> Result<Vec<tracking::Diff<(), ()>>> {
-    let base_manifest = match base {
+    let base_manifest = compute_base_manifest(base).await?;
+    let top_manifest = compute_top_manifest(top).await?;
+
+    tracing::debug!("computing diffs");
+    Ok(tracking::compute_diff(&base_manifest, &top_manifest))
+}
+
+/// Return the changes made by 'ours' and 'theirs' to a common 'base'.
+///
+/// Args:
+/// - **base**: The tag or id that both sets of changes were made to
+/// - **ours**: The tag or id with the first set of changes
+///         (defaults to the current runtime)
+/// - **theirs**: The tag or id with the second set of changes
+///         (defaults to the contents of /spfs)
+pub async fn diff_three_way(
+    base: &String,
+    ours: Option<&String>,
+    theirs: Option<&String>,
+) -> Result<Vec<tracking::MergeDiff>> {
+    tracing::debug!(reference = %base, "computing merge base manifest");
+    let base_manifest = compute_manifest(base).await?;
+    let ours_manifest = compute_base_manifest(ours).await?;
+    let theirs_manifest = compute_top_manifest(theirs).await?;
+
+    tracing::debug!("computing three-way diff");
+    Ok(tracking::compute_three_way_diff(
+        &base_manifest,
+        &ours_manifest,
+        &theirs_manifest,
+    ))
+}
+
+/// Compute the manifest of the given reference, or of the current runtime
+async fn compute_base_manifest(base: Option<&String>) -> Result<tracking::Manifest> {
+    match base {
         None => {
             tracing::debug!("computing runtime manifest as base");
             let runtime = active_runtime().await?;
-            compute_runtime_manifest(&runtime).await?
+            compute_runtime_manifest(&runtime).await
         }
         Some(base) => {
             tracing::debug!(reference = %base, "computing base manifest");
-            compute_manifest(base).await?
+            compute_manifest(base).await
         }
-    };
+    }
+}
 
-    let top_manifest = match top {
+/// Compute the manifest of the given reference, or of the contents of /spfs
+async fn compute_top_manifest(top: Option<&String>) -> Result<tracking::Manifest> {
+    match top {
         None => {
             tracing::debug!("computing manifest for /spfs");
-            tracking::compute_manifest("/spfs").await?
+            tracking::compute_manifest(SPFS_DIR).await
         }
         Some(top) => {
             tracing::debug!(reference = ?top, "computing top manifest");
-            compute_manifest(top).await?
+            compute_manifest(top).await
         }
+    }
+}
+
+/// Render the changes to the contents of a text file as a unified diff.
+///
+/// Returns None if the path is not a regular file on both sides of the
+/// change, or if either version is too large or not valid text.
+///
+/// When `new_from_spfs` is true, the new side of the change was computed
+/// from the contents of /spfs, and so any of its content that is not in
+/// the repository is read from there instead. Content missing from the
+/// repository for any other side is reported as an error.
+pub async fn diff_content<U1, U2>(
+    repo: &storage::RepositoryHandle,
+    path: &RelativePath,
+    mode: &tracking::DiffMode<U1, U2>,
+    new_from_spfs: bool,
+) -> Result<Option<String>> {
+    let (old, new) = match mode {
+        tracking::DiffMode::Unchanged(_) => return Ok(None),
+        tracking::DiffMode::Changed(a, b) => (text_payload(a), text_payload(b)),
+        tracking::DiffMode::Added(b) => (Some(None), text_payload(b)),
+        tracking::DiffMode::Removed(a) => (text_payload(a), Some(None)),
+    };
+    let (Some(old), Some(new)) = (old, new) else {
+        return Ok(None);
+    };
+    let old_text = match old {
+        Some(digest) => read_text(repo, path, digest, false).await?,
+        None => Some(String::new()),
+    };
+    let new_text = match new {
+        Some(digest) => read_text(repo, path, digest, new_from_spfs).await?,
+        None => Some(String::new()),
     };
+    let (Some(old_text), Some(new_text)) = (old_text, new_text) else {
+        return Ok(None);
+    };
+    let old_name = match old {
+        Some(_) => format!("a{path}"),
+        None => "/dev/null".to_string(),
+    };
+    let new_name = match new {
+        Some(_) => format!("b{path}"),
+        None => "/dev/null".to_string(),
+    };
+    let diff = tracking::unified_diff(&old_text, &new_text, &old_name, &new_name);
+    // an empty diff means that only the metadata of the file was changed
+    Ok((!diff.is_empty()).then_some(diff))
+}
 
-    tracing::debug!("computing diffs");
-    Ok(tracking::compute_diff(&base_manifest, &top_manifest))
+/// The payload of an entry that can be compared line by line,
+/// where Some(None) is used for entries that have no content
+fn text_payload<U>(entry: &tracking::Entry<U>) -> Option<Option<encoding::Digest>> {
+    if entry.kind.is_mask() {
+        return Some(None);
+    }
+    if !entry.is_regular_file() || entry.size() > MAX_CONTENT_DIFF_SIZE {
+        return None;
+    }
+    Some(Some(entry.object))
+}
+
+/// Read the identified payload as text, returning None if it is not valid utf-8.
+///
+/// Payloads that are not in the repository are read from the
+/// file at the same path in /spfs when `from_spfs` is true.
+async fn read_text(
+    repo: &storage::RepositoryHandle,
+    path: &RelativePath,
+    digest: encoding::Digest,
+    from_spfs: bool,
+) -> Result<Option<String>> {
+    let data = match repo.open_payload(digest).await {
+        Ok((mut reader, filename)) => {
+            let mut data = Vec::new();
+            reader
+                .read_to_end(&mut data)
+                .await
+                .map_err(|err| Error::StorageReadError("read payload", filename, err))?;
+            data
+        }
+        Err(Error::UnknownObject(_) | Error::ObjectMissingPayload(..)) if from_spfs => {
+            let local = path.to_path(SPFS_DIR);
+            tokio::fs::read(&local)
+                .await
+                .map_err(|err| Error::StorageReadError("read file for diff", local, err))?
+        }
+        Err(err) => return Err(err),
+    };
+    if data.contains(&0) {
+        // null bytes are a good indication of binary data,
+        // even when it happens to also be valid utf-8
+        return Ok(None);
+    }
+    Ok(String::from_utf8(data).ok())
 }
 
 /// Build a manifest of the current set of changes
@@ -10,6 +10,10 @@ use std::path::{Path, PathBuf};
 use super::runtime;
 use crate::{which, Error, Result};
 
+#[cfg(test)]
+#[path = "./env_test.rs"]
+mod env_test;
+
 pub const SPFS_DIR: &str = "/spfs";
 pub const SPFS_DIR_PREFIX: &str = "/spfs/";
 
@@ -175,6 +179,41 @@ impl<MountNamespace> RuntimeConfigurator<NoUserChanges, MountNamespace> {
     }
 }
 
+/// Move this process into a new user namespace, mapping the current
+/// user and group to root within it.
+///
+/// This allows a runtime to be set up without any special privileges,
+/// since the process holds all capabilities within the new namespace.
+/// Becoming root and entering a mount namespace afterwards will operate
+/// within the new user namespace, and any runtime that is created will
+/// only be accessible to the current user.
+///
+/// This function will fail if called from a process with multiple threads.
+pub fn enter_user_namespace() -> Result<()> {
+    check_single_threaded("enter a user namespace")?;
+
+    tracing::debug!("entering user namespace...");
+    let uid = nix::unistd::getuid();
+    let gid = nix::unistd::getgid();
+    if let Err(err) = nix::sched::unshare(nix::sched::CloneFlags::CLONE_NEWUSER) {
+        return Err(Error::wrap_nix(
+            err,
+            "Failed to enter user namespace, unprivileged user namespaces may be disabled on this system",
+        ));
+    }
+
+    // setgroups must be denied before an unprivileged
+    // process is allowed to write the gid map
+    for (path, content) in [
+        ("/proc/self/setgroups", "deny".to_string()),
+        ("/proc/self/uid_map", format!("0 {uid} 1")),
+        ("/proc/self/gid_map", format!("0 {gid} 1")),
+    ] {
+        std::fs::write(path, content).map_err(|err| Error::RuntimeWriteError(path.into(), err))?;
+    }
+    Ok(())
+}
+
 impl<User> RuntimeConfigurator<User, NoMountNamespace> {
     /// Enter a new mount namespace and return a guard that represents the thread
     /// that is in the new namespace.
@@ -223,31 +262,40 @@ impl<User> RuntimeConfigurator<User, NoMountNamespace> {
         self,
         rt: &runtime::Runtime,
     ) -> Result<RuntimeConfigurator<User, ThreadIsInMountNamespace>> {
-        check_can_join()?;
+        check_can_join(rt)?;
 
         let pid = match rt.status.owner {
             None => return Err(Error::RuntimeNotInitialized(rt.name().into())),
             Some(pid) => pid,
         };
 
-        let ns_path = std::path::Path::new("/proc")
-            .join(pid.to_string())
-            .join("ns/mnt");
-
-        tracing::debug!(?ns_path, "Getting process namespace");
-        let file = match std::fs::File::open(&ns_path) {
-            Ok(file) => file,
-            Err(err) => {
-                return match err.kind() {
-                    std::io::ErrorKind::NotFound => Err(Error::UnknownRuntime {
-                        runtime: rt.name().into(),
-                        source: Box::new(err),
-                    }),
-                    _ => Err(Error::RuntimeReadError(ns_path, err)),
-                }
-            }
+        let proc_dir = std::path::Path::new("/proc").join(pid.to_string());
+        let open_namespace = |name: &str| {
+            let ns_path = proc_dir.join("ns").join(name);
+            tracing::debug!(?ns_path, "Getting process namespace");
+            std::fs::File::open(&ns_path).map_err(|err| match err.kind() {
+                std::io::ErrorKind::NotFound => Error::UnknownRuntime {
+                    runtime: rt.name().into(),
+                    source: Box::new(err),
+                },
+                _ => Error::RuntimeReadError(ns_path, err),
+            })
         };
 
+        if rt.config.rootless {
+            // the mount namespace of a rootless runtime is owned by its
+            // user namespace, which must be joined first in order to
+            // have the capabilities needed to join the mount namespace
+            let file = open_namespace("user")?;
+            if let Err(err) = nix::sched::setns(file, nix::sched::CloneFlags::CLONE_NEWUSER) {
+                return Err(Error::wrap_nix(
+                    err,
+                    "Failed to join runtime user namespace",
+                ));
+            }
+        }
+
+        let file = open_namespace("mnt")?;
         if let Err(err) = nix::sched::setns(file, nix::sched::CloneFlags::empty()) {
             return Err(match err {
                 nix::errno::Errno::EPERM => Error::new_errno(
@@ -384,8 +432,17 @@ where
     /// Check or create the necessary directories for mounting the provided runtime
     pub fn ensure_mount_targets_exist(&self, config: &runtime::Config) -> Result<()> {
         tracing::debug!("ensuring mount targets exist...");
-        runtime::makedirs_with_perms(SPFS_DIR, 0o777)
-            .map_err(|source| Error::CouldNotCreateSpfsRoot { source })?;
+        if config.rootless {
+            // root within an unprivileged user namespace has no
+            // permission to create directories at the root of the
+            // host filesystem, so the mount point must already exist
+            if !Path::new(SPFS_DIR).is_dir() {
+                return Err(Error::MissingRootlessSpfsRoot);
+            }
+        } else {
+            runtime::makedirs_with_perms(SPFS_DIR, 0o777)
+                .map_err(|source| Error::CouldNotCreateSpfsRoot { source })?;
+        }
 
         if let Some(dir) = &config.runtime_dir {
             runtime::makedirs_with_perms(dir, 0o777)
@@ -564,17 +621,26 @@ where
 
     #[cfg(feature = "fuse-backend")]
     pub(crate) async fn mount_fuse_lower_dir(&self, rt: &runtime::Runtime) -> Result<()> {
-        self.mount_fuse_onto(rt, &rt.config.lower_dir).await
+        // edits are made in the overlayfs that is mounted over the lower dir
+        const READ_ONLY: bool = true;
+        self.mount_fuse_onto(rt, &rt.config.lower_dir, READ_ONLY)
+            .await
     }
 
     #[cfg(feature = "fuse-backend")]
     pub(crate) async fn mount_env_fuse(&self, rt: &runtime::Runtime) -> Result<()> {
-        self.mount_fuse_onto(rt, SPFS_DIR).await?;
+        self.mount_fuse_onto(rt, SPFS_DIR, !rt.status.editable)
+            .await?;
         self.mount_live_layers(rt).await
     }
 
     #[cfg(feature = "fuse-backend")]
-    async fn mount_fuse_onto<P>(&self, rt: &runtime::Runtime, path: P) -> Result<()>
+    async fn mount_fuse_onto<P>(
+        &self,
+        rt: &runtime::Runtime,
+        path: P,
+        read_only: bool,
+    ) -> Result<()>
     where
         P: AsRef<std::ffi::OsStr>,
     {
@@ -582,7 +648,7 @@ where
 
         let path = path.as_ref().to_owned();
         let platform = rt.to_platform().digest()?.to_string();
-        let opts = get_fuse_args(&rt.config, &self.user, true);
+        let opts = get_fuse_args(&rt.config, &self.user, read_only);
 
         // A new thread created in mount namespace will be inside the same
         // mount namespace...
@@ -998,7 +1064,20 @@ impl<MountNamespace> RuntimeConfigurator<IsRootUser, MountNamespace> {
 }
 
 // Checks if the current process will be able to join an existing runtime
-fn check_can_join() -> Result<()> {
+fn check_can_join(rt: &runtime::Runtime) -> Result<()> {
+    check_single_threaded("join an existing runtime")?;
+
+    // a rootless runtime is joined through its user namespace,
+    // which grants the capabilities needed for the rest
+    if !rt.config.rootless && !have_required_join_capabilities()? {
+        return Err("Missing required capabilities to join an existing runtime".into());
+    }
+    Ok(())
+}
+
+// Checks that the current process has only one thread, which is
+// required in order to move the process into other namespaces
+fn check_single_threaded(action: &str) -> Result<()> {
     match procfs::process::Process::myself()
         .map_err(|err| Error::String(err.to_string()))?
         .stat()
@@ -1006,21 +1085,11 @@ fn check_can_join() -> Result<()> {
         .num_threads
     {
         count @ 2.. => {
-            return Err(format!(
-                "Program must be single-threaded to join an existing runtime (has {count} threads)"
-            )
-            .into());
+            Err(format!("Program must be single-threaded to {action} (has {count} threads)").into())
         }
-        1 => (),
-        i => {
-            return Err(format!("Unexpected negative thread count: {i}").into());
-        }
-    }
-
-    if !have_required_join_capabilities()? {
-        return Err("Missing required capabilities to join an existing runtime".into());
+        1 => Ok(()),
+        i => Err(format!("Unexpected negative thread count: {i}").into()),
     }
-    Ok(())
 }
 
 // Checks if the current process has the capabilities required
@@ -1074,7 +1143,9 @@ impl OverlayMountOptions {
         Self {
             read_only: !rt.status.editable,
             break_hardlinks: true,
-            metadata_copy_up: true,
+            // overlayfs does not allow metadata-only copy up
+            // when mounted from within a user namespace
+            metadata_copy_up: !rt.config.rootless,
         }
     }
 
@@ -1169,7 +1240,16 @@ fn get_fuse_args(config: &runtime::Config, owner: &IsRootUser, read_only: bool)
         CUSTOM(format!("uid={}", owner.original_uid)),
         CUSTOM(format!("gid={}", nix::unistd::getgid())),
     ];
-    opts.push(if read_only { RO } else { RW });
+    if read_only {
+        opts.push(RO);
+    } else {
+        // changes are stored in the upper dir in the same way as overlayfs,
+        // and permissions must be checked by the kernel now that files can
+        // be modified
+        opts.push(RW);
+        opts.push(DefaultPermissions);
+        opts.push(CUSTOM(format!("upperdir={}", config.upper_dir.display())));
+    }
     opts.extend(
         config
             .secondary_repositories
@@ -0,0 +1,242 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use std::os::fd::RawFd;
+use std::path::Path;
+
+use rstest::rstest;
+
+use super::{enter_user_namespace, OverlayMountOptions, RuntimeConfigurator, SPFS_DIR};
+use crate::fixtures::*;
+use crate::runtime::{Runtime, Storage};
+use crate::{Error, Result};
+
+/// Run the given function in a forked child process, returning
+/// the id of the child.
+///
+/// Namespaces can only be entered by single-threaded processes, which
+/// the test harness is not, but the forked child only has the one thread.
+fn fork_child<F>(func: F) -> libc::pid_t
+where
+    F: FnOnce() -> Result<()>,
+{
+    // Safety: the child only runs the given function and exits
+    // without returning to the test harness
+    let pid = unsafe { libc::fork() };
+    assert!(pid >= 0, "failed to fork test process");
+    if pid == 0 {
+        let code = match func() {
+            Ok(()) => 0,
+            Err(err) => {
+                eprintln!("{err:?}");
+                1
+            }
+        };
+        // Safety: exit without running any of the handlers or
+        // destructors that belong to the parent process
+        unsafe { libc::_exit(code) }
+    }
+    pid
+}
+
+/// Wait for a forked child process, returning its exit code
+fn wait_for_child(pid: libc::pid_t) -> i32 {
+    let mut status = 0;
+    // Safety: pid is a child of this process
+    let res = unsafe { libc::waitpid(pid, &mut status, 0) };
+    assert_eq!(res, pid, "failed to wait for child process");
+    if libc::WIFEXITED(status) {
+        libc::WEXITSTATUS(status)
+    } else {
+        -1
+    }
+}
+
+fn run_in_child<F>(func: F) -> i32
+where
+    F: FnOnce() -> Result<()>,
+{
+    wait_for_child(fork_child(func))
+}
+
+/// True if this system allows unprivileged user namespaces
+fn user_namespaces_available() -> bool {
+    run_in_child(enter_user_namespace) == 0
+}
+
+fn pipe() -> (RawFd, RawFd) {
+    let mut fds = [0; 2];
+    // Safety: fds has room for the two descriptors
+    let res = unsafe { libc::pipe(fds.as_mut_ptr()) };
+    assert_eq!(res, 0, "failed to create pipe");
+    (fds[0], fds[1])
+}
+
+fn send(fd: RawFd) {
+    // Safety: writes a single byte from a valid buffer
+    unsafe { libc::write(fd, [1u8].as_ptr().cast(), 1) };
+}
+
+fn receive(fd: RawFd) -> bool {
+    let mut buf = [0u8];
+    // Safety: reads a single byte into a valid buffer
+    unsafe { libc::read(fd, buf.as_mut_ptr().cast(), 1) == 1 }
+}
+
+async fn rootless_runtime(tmpdir: &Path) -> Runtime {
+    let repo = crate::storage::RepositoryHandle::from(
+        crate::storage::fs::FsRepository::create(tmpdir.join("repo"))
+            .await
+            .unwrap(),
+    );
+    let storage = Storage::new(repo).unwrap();
+    let mut rt = storage
+        .create_transient_runtime()
+        .await
+        .expect("failed to create runtime in storage");
+    rt.config.rootless = true;
+    rt.config.runtime_dir = Some(tmpdir.join("runtime"));
+    std::fs::create_dir(tmpdir.join("runtime")).unwrap();
+    rt
+}
+
+#[rstest]
+fn test_enter_user_namespace_requires_single_thread() {
+    // the test harness runs each test in its own thread
+    let err = enter_user_namespace().expect_err("should not enter from many threads");
+    assert!(err.to_string().contains("single-threaded"), "{err}");
+}
+
+#[rstest]
+fn test_enter_user_namespace() {
+    if !user_namespaces_available() {
+        eprintln!("skipping: unprivileged user namespaces are not available");
+        return;
+    }
+
+    let uid = nix::unistd::getuid().to_string();
+    let code = run_in_child(|| {
+        enter_user_namespace()?;
+        if !nix::unistd::getuid().is_root() {
+            return Err("should be root in the user namespace".into());
+        }
+        let uid_map = std::fs::read_to_string("/proc/self/uid_map")
+            .map_err(|err| Error::RuntimeReadError("/proc/self/uid_map".into(), err))?;
+        let mapping = uid_map.split_whitespace().collect::<Vec<_>>();
+        if mapping != ["0", uid.as_str(), "1"] {
+            return Err(format!("unexpected uid map: {uid_map}").into());
+        }
+        Ok(())
+    });
+    assert_eq!(code, 0, "child should enter the user namespace as root");
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_rootless_runtime_needs_spfs_dir(tmpdir: tempfile::TempDir) {
+    if !user_namespaces_available() {
+        eprintln!("skipping: unprivileged user namespaces are not available");
+        return;
+    }
+    if Path::new(SPFS_DIR).exists() {
+        eprintln!("skipping: {SPFS_DIR} already exists on this system");
+        return;
+    }
+
+    let rt = rootless_runtime(tmpdir.path()).await;
+    let code = run_in_child(|| {
+        enter_user_namespace()?;
+        let with_root = RuntimeConfigurator::default()
+            .enter_mount_namespace()?
+            .become_root()?;
+        match with_root.ensure_mount_targets_exist(&rt.config) {
+            Err(Error::MissingRootlessSpfsRoot) => Ok(()),
+            Err(err) => Err(err),
+            Ok(()) => Err(format!("{SPFS_DIR} should not be created when rootless").into()),
+        }
+    });
+    assert_eq!(
+        code, 0,
+        "rootless runtime should require an existing {SPFS_DIR}"
+    );
+    assert!(!Path::new(SPFS_DIR).exists());
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_rootless_runtime_create_and_join(tmpdir: tempfile::TempDir) {
+    if !user_namespaces_available() {
+        eprintln!("skipping: unprivileged user namespaces are not available");
+        return;
+    }
+
+    let mut rt = rootless_runtime(tmpdir.path()).await;
+    let runtime_dir = rt.config.runtime_dir.clone().unwrap();
+    let marker = runtime_dir.join("marker");
+    let (ready_read, ready_write) = pipe();
+    let (done_read, done_write) = pipe();
+
+    // the first process creates the runtime, and stays alive
+    // until the second one has joined it
+    let owner = fork_child(|| {
+        enter_user_namespace()?;
+        let with_root = RuntimeConfigurator::default()
+            .enter_mount_namespace()?
+            .become_root()?;
+        with_root.mount_runtime(&rt.config)?;
+        std::fs::write(&marker, "rootless")
+            .map_err(|err| Error::RuntimeWriteError(marker.clone(), err))?;
+        send(ready_write);
+        receive(done_read);
+        Ok(())
+    });
+    // Safety: these ends now belong to the child alone, so that
+    // reads see the end of the pipe if the child exits early
+    unsafe {
+        libc::close(ready_write);
+        libc::close(done_read);
+    }
+    assert!(
+        receive(ready_read),
+        "runtime owner failed before it was ready"
+    );
+    assert!(
+        !marker.exists(),
+        "runtime mounts should only be visible within the runtime"
+    );
+
+    rt.status.owner = Some(owner as u32);
+    let joined = run_in_child(|| {
+        RuntimeConfigurator::default().join_runtime(&rt)?;
+        if !nix::unistd::getuid().is_root() {
+            return Err("should be root after joining a rootless runtime".into());
+        }
+        match std::fs::read_to_string(&marker) {
+            Ok(content) if content == "rootless" => Ok(()),
+            Ok(content) => Err(format!("unexpected marker content: {content}").into()),
+            Err(err) => Err(Error::RuntimeReadError(marker.clone(), err)),
+        }
+    });
+    send(done_write);
+    assert_eq!(
+        wait_for_child(owner),
+        0,
+        "child should create a rootless runtime"
+    );
+    assert_eq!(joined, 0, "second process should join the rootless runtime");
+
+    for fd in [ready_read, done_write] {
+        // Safety: each descriptor was opened above and is closed once
+        unsafe { libc::close(fd) };
+    }
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_rootless_overlay_options(tmpdir: tempfile::TempDir) {
+    let mut rt = rootless_runtime(tmpdir.path()).await;
+    assert!(!OverlayMountOptions::new(&rt).metadata_copy_up);
+    rt.config.rootless = false;
+    assert!(OverlayMountOptions::new(&rt).metadata_copy_up);
+}
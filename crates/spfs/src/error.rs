@@ -61,6 +61,12 @@ pub enum Error {
     Utf8Error(#[from] Utf8Error),
     #[error("Error communicating with the server: {0:?}")]
     Tonic(#[from] tonic::Status),
+    #[error("Error communicating with the object store: {0}")]
+    ObjectStore(#[from] object_store::Error),
+    #[error("Error communicating with the http server: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("Invalid TLS configuration: {0}")]
+    InvalidTlsConfig(String),
     #[error(transparent)]
     TokioJoinError(#[from] tokio::task::JoinError),
     #[error("Failed to spawn {0}")]
@@ -88,8 +94,19 @@ pub enum Error {
         desired: graph::ObjectKind,
         digest: encoding::Digest,
     },
+    #[error("Object {digest} does not have a trusted signature: {status}")]
+    #[diagnostic(
+        code("spfs::untrusted_object"),
+        help("Objects can be signed with 'spfs sign', and trusted keys are listed in the spfs config")
+    )]
+    UntrustedObject {
+        digest: encoding::Digest,
+        status: crate::signing::SignatureStatus,
+    },
     #[error("Cannot write to a repository which has been pinned in time")]
     RepositoryIsPinned,
+    #[error("Cannot write to a read-only repository: {0}")]
+    RepositoryIsReadOnly(url::Url),
 
     #[error("Failed to open repository: {repository}")]
     #[diagnostic(code("spfs::failed_to_open_repo"))]
@@ -141,6 +158,12 @@ pub enum Error {
         help("If you have sudo/admin privileges, you can try creating it yourself")
     )]
     CouldNotCreateSpfsRoot { source: std::io::Error },
+    #[error("{} directory does not exist and cannot be created by a rootless runtime", crate::env::SPFS_DIR)]
+    #[diagnostic(
+        code("spfs::missing_spfs_dir"),
+        help("An administrator must create it ahead of time for rootless runtimes to work")
+    )]
+    MissingRootlessSpfsRoot,
     #[error("Unable to make the runtime durable: {0}")]
     RuntimeChangeToDurableError(String),
     #[error("Storage read error from {0} at {1}: {2}")]
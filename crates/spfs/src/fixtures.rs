@@ -14,6 +14,10 @@ use crate as spfs;
 pub enum TempRepo {
     FS(Arc<spfs::storage::RepositoryHandle>, Arc<TempDir>),
     Tar(Arc<spfs::storage::RepositoryHandle>, TempDir),
+    S3(
+        Arc<spfs::storage::RepositoryHandle>,
+        Arc<dyn object_store::ObjectStore>,
+    ),
     Rpc {
         repo: Arc<spfs::storage::RepositoryHandle>,
         grpc_join_handle: Option<tokio::task::JoinHandle<()>>,
@@ -29,6 +33,7 @@ impl TempRepo {
         match self {
             Self::FS(r, _) => Arc::clone(r),
             Self::Tar(r, _) => Arc::clone(r),
+            Self::S3(r, _) => Arc::clone(r),
             Self::Rpc { repo, .. } => Arc::clone(repo),
         }
     }
@@ -47,7 +52,15 @@ impl TempRepo {
                 )));
                 TempRepo::FS(Arc::new(repo.into()), Arc::clone(tempdir))
             }
-            _ => panic!("only TempRepo::FS type supports setting tag namespaces"),
+            TempRepo::S3(_, store) => {
+                let mut repo =
+                    spfs::storage::s3::S3Repository::with_store(Arc::clone(store), s3_config());
+                repo.set_tag_namespace(Some(spfs::storage::TagNamespaceBuf::new(
+                    namespace.as_ref(),
+                )));
+                TempRepo::S3(Arc::new(repo.into()), Arc::clone(store))
+            }
+            _ => panic!("only TempRepo::FS and TempRepo::S3 types support setting tag namespaces"),
         }
     }
 }
@@ -58,6 +71,7 @@ impl std::ops::Deref for TempRepo {
         match self {
             Self::FS(r, _) => r,
             Self::Tar(r, _) => r,
+            Self::S3(r, _) => r,
             Self::Rpc { repo, .. } => repo,
         }
     }
@@ -149,85 +163,132 @@ pub async fn tmprepo(kind: &str) -> TempRepo {
                 .into();
             TempRepo::Tar(Arc::new(repo), tmpdir)
         }
-        #[cfg(feature = "server")]
-        "rpc" => {
-            use crate::storage::prelude::*;
-            let repo = std::sync::Arc::new(spfs::storage::RepositoryHandle::FS(
-                spfs::storage::fs::FsRepository::create(tmpdir.path().join("repo"))
-                    .await
-                    .unwrap(),
-            ));
-            let listen: std::net::SocketAddr = "127.0.0.1:0".parse().unwrap();
-            let http_listener = std::net::TcpListener::bind(listen).unwrap();
-            let local_http_addr = http_listener.local_addr().unwrap();
-            let payload_service = spfs::server::PayloadService::new(
-                repo.clone(),
-                format!("http://{local_http_addr}").parse().unwrap(),
-            );
-            let (grpc_shutdown, grpc_shutdown_recv) = std::sync::mpsc::channel::<()>();
-            let (http_shutdown, http_shutdown_recv) = std::sync::mpsc::channel::<()>();
-            let grpc_listener = tokio::net::TcpListener::bind(listen).await.unwrap();
-            let local_grpc_addr = grpc_listener.local_addr().unwrap();
-            let incoming = tokio_stream::wrappers::TcpListenerStream::new(grpc_listener);
-            let grpc_future = tonic::transport::Server::builder()
-                .add_service(spfs::server::Repository::new_srv())
-                .add_service(spfs::server::TagService::new_srv(repo.clone()))
-                .add_service(spfs::server::DatabaseService::new_srv(repo))
-                .add_service(payload_service.clone().into_srv())
-                .serve_with_incoming_shutdown(incoming, async move {
-                    // use a blocking task to avoid locking up the whole server
-                    // with this very synchronous channel recv process
-                    tokio::task::spawn_blocking(move || {
-                        grpc_shutdown_recv
-                            .recv()
-                            .expect("failed to get server shutdown signal");
-                    })
-                    .await
-                    .unwrap()
-                });
-            tracing::debug!("test rpc server listening: {local_grpc_addr}");
-            let grpc_join_handle =
-                tokio::task::spawn(async move { grpc_future.await.expect("test server failed") });
-            let http_server = {
-                hyper::Server::from_tcp(http_listener).unwrap().serve(
-                    hyper::service::make_service_fn(move |_| {
-                        let s = payload_service.clone();
-                        async move { Ok::<_, std::convert::Infallible>(s) }
-                    }),
-                )
-            };
-            let http_future = http_server.with_graceful_shutdown(async {
-                // use a blocking task to avoid locking up the whole server
-                // with this very synchronous channel recv process
-                tokio::task::spawn_blocking(move || {
-                    http_shutdown_recv
-                        .recv()
-                        .expect("failed to get http server shutdown signal");
-                })
-                .await
-                .unwrap()
-            });
-            let http_join_handle =
-                tokio::task::spawn(async move { http_future.await.expect("http server failed") });
-            let url = format!("http2://{local_grpc_addr}").parse().unwrap();
-            tracing::debug!("Connected to rpc test repo: {url}");
-            let repo = spfs::storage::rpc::RpcRepository::from_url(&url)
-                .await
-                .unwrap()
-                .into();
-            TempRepo::Rpc {
-                repo: Arc::new(repo),
-                grpc_join_handle: Some(grpc_join_handle),
-                http_join_handle: Some(http_join_handle),
-                grpc_shutdown,
-                http_shutdown,
-                tmpdir,
-            }
+        "s3" => {
+            let store: Arc<dyn object_store::ObjectStore> =
+                Arc::new(object_store::memory::InMemory::new());
+            let repo = spfs::storage::s3::S3Repository::with_store(Arc::clone(&store), s3_config());
+            TempRepo::S3(Arc::new(repo.into()), store)
         }
+        #[cfg(feature = "server")]
+        "rpc" => serve_rpc_repo(tmpdir, Default::default(), None, Default::default()).await,
         _ => panic!("unknown repo kind '{kind}'"),
     }
 }
 
+/// Serve a new repository over grpc, connecting to it with
+/// the given client parameters.
+///
+/// When serving over TLS, the server certificate must be valid for 127.0.0.1.
+#[cfg(feature = "server")]
+pub async fn serve_rpc_repo(
+    tmpdir: TempDir,
+    auth: spfs::server::Auth,
+    tls: Option<spfs::server::TlsConfig>,
+    params: spfs::storage::rpc::Params,
+) -> TempRepo {
+    let repo = std::sync::Arc::new(spfs::storage::RepositoryHandle::FS(
+        spfs::storage::fs::FsRepository::create(tmpdir.path().join("repo"))
+            .await
+            .unwrap(),
+    ));
+    let listen: std::net::SocketAddr = "127.0.0.1:0".parse().unwrap();
+    let (grpc_scheme, http_scheme) = match tls {
+        Some(_) => ("grpcs", "https"),
+        None => ("http2", "http"),
+    };
+    let http_listener = tokio::net::TcpListener::bind(listen).await.unwrap();
+    let local_http_addr = http_listener.local_addr().unwrap();
+    let payload_service = spfs::server::PayloadService::new(
+        repo.clone(),
+        format!("{http_scheme}://{local_http_addr}")
+            .parse()
+            .unwrap(),
+    )
+    .with_auth(auth.clone());
+    let (grpc_shutdown, grpc_shutdown_recv) = std::sync::mpsc::channel::<()>();
+    let (http_shutdown, http_shutdown_recv) = std::sync::mpsc::channel::<()>();
+    let grpc_listener = tokio::net::TcpListener::bind(listen).await.unwrap();
+    let local_grpc_addr = grpc_listener.local_addr().unwrap();
+    let incoming = tokio_stream::wrappers::TcpListenerStream::new(grpc_listener);
+    let mut grpc_server = tonic::transport::Server::builder();
+    if let Some(tls) = &tls {
+        grpc_server = grpc_server.tls_config(tls.grpc_config()).unwrap();
+    }
+    let grpc_future = grpc_server
+        .add_service(spfs::server::Repository::new_srv())
+        .add_service(
+            spfs::server::TagService::new(repo.clone())
+                .with_auth(auth.clone())
+                .into_srv(),
+        )
+        .add_service(
+            spfs::server::DatabaseService::new(repo)
+                .with_auth(auth)
+                .into_srv(),
+        )
+        .add_service(payload_service.clone().into_srv())
+        .serve_with_incoming_shutdown(incoming, async move {
+            // use a blocking task to avoid locking up the whole server
+            // with this very synchronous channel recv process
+            tokio::task::spawn_blocking(move || {
+                grpc_shutdown_recv
+                    .recv()
+                    .expect("failed to get server shutdown signal");
+            })
+            .await
+            .unwrap()
+        });
+    tracing::debug!("test rpc server listening: {local_grpc_addr}");
+    let grpc_join_handle =
+        tokio::task::spawn(async move { grpc_future.await.expect("test server failed") });
+    let http_future = async move {
+        let shutdown = async {
+            // use a blocking task to avoid locking up the whole server
+            // with this very synchronous channel recv process
+            tokio::task::spawn_blocking(move || {
+                http_shutdown_recv
+                    .recv()
+                    .expect("failed to get http server shutdown signal");
+            })
+            .await
+            .unwrap()
+        };
+        payload_service
+            .serve_http(http_listener, tls.as_ref(), shutdown)
+            .await
+    };
+    let http_join_handle =
+        tokio::task::spawn(async move { http_future.await.expect("http server failed") });
+    let url = format!("{grpc_scheme}://{local_grpc_addr}")
+        .parse()
+        .unwrap();
+    tracing::debug!("Connected to rpc test repo: {url}");
+    let repo = spfs::storage::rpc::RpcRepository::new(spfs::storage::rpc::Config {
+        address: url,
+        params,
+    })
+    .await
+    .unwrap()
+    .into();
+    TempRepo::Rpc {
+        repo: Arc::new(repo),
+        grpc_join_handle: Some(grpc_join_handle),
+        http_join_handle: Some(http_join_handle),
+        grpc_shutdown,
+        http_shutdown,
+        tmpdir,
+    }
+}
+
+/// The configuration used for in-memory s3 test repositories
+fn s3_config() -> spfs::storage::s3::Config {
+    spfs::storage::s3::Config {
+        bucket: "spfs-test".to_string(),
+        prefix: "repo".to_string(),
+        params: Default::default(),
+    }
+}
+
 pub fn ensure(path: std::path::PathBuf, data: &str) {
     std::fs::create_dir_all(path.parent().unwrap()).expect("failed to make dirs");
     let mut file = std::fs::OpenOptions::new()
@@ -278,6 +339,13 @@ fn generate_subtree(root: &std::path::Path, max_depth: i32) {
     }
 }
 
+pub fn random_data(len: usize) -> Vec<u8> {
+    let mut rng = rand::thread_rng();
+    let mut buf = vec![0; len];
+    rng.fill(buf.as_mut_slice());
+    buf
+}
+
 pub fn random_digest() -> crate::encoding::Digest {
     let mut hasher = crate::encoding::Hasher::new_sync();
     let mut rng = rand::thread_rng();
@@ -7,7 +7,7 @@ use std::io::BufRead;
 use encoding::prelude::*;
 use spfs_proto::EntryArgs;
 
-use crate::{encoding, tracking, Result};
+use crate::{encoding, tracking, Error, Result};
 
 #[cfg(test)]
 #[path = "./entry_test.rs"]
@@ -26,6 +26,10 @@ impl<'buf> std::fmt::Debug for Entry<'buf> {
             .field("mode", &self.mode())
             .field("size", &self.size())
             .field("object", self.object())
+            .field(
+                "xattrs",
+                &self.xattrs().map(|(name, _)| name).collect::<Vec<_>>(),
+            )
             .finish()
     }
 }
@@ -46,8 +50,10 @@ impl<'buf> Entry<'buf> {
         mode: u32,
         size: u64,
         object: &encoding::Digest,
+        xattrs: &tracking::Xattrs,
     ) -> flatbuffers::WIPOffset<spfs_proto::Entry<'fbb>> {
         let name = builder.create_string(name);
+        let xattrs = Self::build_xattrs(builder, xattrs);
         spfs_proto::Entry::create(
             builder,
             &EntryArgs {
@@ -56,10 +62,44 @@ impl<'buf> Entry<'buf> {
                 mode,
                 size_: size,
                 object: Some(object),
+                xattrs,
             },
         )
     }
 
+    /// Build the table of extended attributes for an entry, which
+    /// is left out entirely when there are none to keep the
+    /// encoding identical to entries written before it existed.
+    fn build_xattrs<'fbb>(
+        builder: &mut flatbuffers::FlatBufferBuilder<'fbb>,
+        xattrs: &tracking::Xattrs,
+    ) -> Option<
+        flatbuffers::WIPOffset<
+            flatbuffers::Vector<
+                'fbb,
+                flatbuffers::ForwardsUOffset<spfs_proto::ExtendedAttribute<'fbb>>,
+            >,
+        >,
+    > {
+        if xattrs.is_empty() {
+            return None;
+        }
+        let mut offsets = Vec::with_capacity(xattrs.len());
+        // the map is already sorted by name, which is what readers expect
+        for (name, value) in xattrs.iter() {
+            let name = builder.create_string(name);
+            let value = builder.create_vector(value.as_slice());
+            offsets.push(spfs_proto::ExtendedAttribute::create(
+                builder,
+                &spfs_proto::ExtendedAttributeArgs {
+                    name: Some(name),
+                    value: Some(value),
+                },
+            ));
+        }
+        Some(builder.create_vector(&offsets))
+    }
+
     pub fn from<'fbb, T>(
         builder: &mut flatbuffers::FlatBufferBuilder<'fbb>,
         name: &str,
@@ -72,6 +112,7 @@ impl<'buf> Entry<'buf> {
             entry.mode,
             entry.size_for_legacy_encode(),
             &entry.object,
+            &entry.xattrs,
         )
     }
 
@@ -107,6 +148,28 @@ impl<'buf> Entry<'buf> {
         self.0.object()
     }
 
+    /// Iterate the extended attributes of this entry, sorted by name.
+    pub fn xattrs(&self) -> impl Iterator<Item = (&'buf str, &'buf [u8])> {
+        self.0
+            .xattrs()
+            .into_iter()
+            .flat_map(|xattrs| xattrs.iter())
+            .map(|xattr| (xattr.name(), xattr.value().bytes()))
+    }
+
+    /// True if this entry has any extended attributes.
+    #[inline]
+    pub fn has_xattrs(&self) -> bool {
+        self.0.xattrs().is_some_and(|xattrs| !xattrs.is_empty())
+    }
+
+    /// Collect the extended attributes of this entry into an owned map.
+    pub fn to_xattrs(&self) -> tracking::Xattrs {
+        self.xattrs()
+            .map(|(name, value)| (name.to_owned(), value.to_vec()))
+            .collect()
+    }
+
     #[inline]
     pub fn is_symlink(&self) -> bool {
         unix_mode::is_symlink(self.mode())
@@ -174,22 +237,44 @@ impl<'buf> encoding::Digestible for Entry<'buf> {
 
     fn digest(&self) -> std::result::Result<spfs_proto::Digest, Self::Error> {
         let mut hasher = encoding::Hasher::new_sync();
-        self.legacy_encode(&mut hasher)?;
+        self.digest_encode(&mut hasher)?;
         Ok(hasher.digest())
     }
 }
 
 impl<'buf> Entry<'buf> {
     pub(super) fn digest_encode(&self, writer: &mut impl std::io::Write) -> Result<()> {
-        encoding::write_digest(&mut *writer, self.object())?;
-        self.kind().encode(&mut *writer)?;
-        encoding::write_uint64(&mut *writer, self.mode() as u64)?;
-        encoding::write_uint64(&mut *writer, self.size_for_legacy_encode())?;
-        encoding::write_string(writer, self.name())?;
+        self.encode_common_fields(&mut *writer)?;
+        // Entries without any extended attributes must continue
+        // to produce the same digest as they always have
+        if self.has_xattrs() {
+            let xattrs = self.xattrs().collect::<Vec<_>>();
+            encoding::write_uint64(&mut *writer, xattrs.len() as u64)?;
+            for (name, value) in xattrs {
+                encoding::write_string(&mut *writer, name)?;
+                encoding::write_uint64(&mut *writer, value.len() as u64)?;
+                writer
+                    .write_all(value)
+                    .map_err(encoding::Error::FailedWrite)?;
+            }
+        }
         Ok(())
     }
 
     pub(super) fn legacy_encode(&self, writer: &mut impl std::io::Write) -> Result<()> {
+        // Legacy encoded entries have no place to store extended attributes
+        if self.has_xattrs() {
+            return Err(Error::String(format!(
+                "Invalid Entry object for legacy encoding, '{}' has extended attributes. Extended attributes are not supported with legacy encoding",
+                self.name()
+            )));
+        }
+        self.encode_common_fields(writer)
+    }
+
+    /// Write the fields that are shared by the legacy encoding
+    /// and the digest of this entry
+    fn encode_common_fields(&self, writer: &mut impl std::io::Write) -> Result<()> {
         encoding::write_digest(&mut *writer, self.object())?;
         self.kind().encode(&mut *writer)?;
         encoding::write_uint64(&mut *writer, self.mode() as u64)?;
@@ -211,7 +296,15 @@ impl<'buf> Entry<'buf> {
         if kind.is_blob() {
             kind = tracking::EntryKind::Blob(size);
         }
-        Ok(Self::build(builder, &name, kind, mode, size, &object))
+        Ok(Self::build(
+            builder,
+            &name,
+            kind,
+            mode,
+            size,
+            &object,
+            &Default::default(),
+        ))
     }
 }
 
@@ -246,6 +339,7 @@ impl EntryBuf {
                         }
                     },
                     name: Some(name),
+                    xattrs: None,
                 },
             );
             builder.finish_minimal(e);
@@ -277,6 +371,7 @@ impl EntryBuf {
                         }
                     },
                     name: Some(name),
+                    xattrs: None,
                 },
             );
             builder.finish_minimal(e);
@@ -286,6 +381,26 @@ impl EntryBuf {
         })
     }
 
+    pub fn build_with_xattrs(
+        name: &str,
+        kind: tracking::EntryKind,
+        mode: u32,
+        object: &encoding::Digest,
+        xattrs: &tracking::Xattrs,
+    ) -> Self {
+        crate::graph::BUILDER.with_borrow_mut(|builder| {
+            let size = match kind {
+                tracking::EntryKind::Blob(size) => size,
+                _ => 0,
+            };
+            let e = Entry::build(builder, name, kind, mode, size, object, xattrs);
+            builder.finish_minimal(e);
+            let bytes = builder.finished_data().into();
+            builder.reset();
+            Self(bytes)
+        })
+    }
+
     pub fn as_entry(&self) -> Entry<'_> {
         let e =
             flatbuffers::root::<spfs_proto::Entry<'_>>(&self.0[..]).expect("valid internal buffer");
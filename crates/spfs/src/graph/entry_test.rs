@@ -129,3 +129,45 @@ fn test_entry_compare() {
     );
     assert!(root_dir.as_entry() > root_file.as_entry());
 }
+
+#[rstest]
+fn test_entry_xattrs_change_digest() {
+    init_logging();
+
+    let object = "ZD25L3AN5E3LTZ6MDQOIZUV6KRV5Y4SSXRE4YMYZJJ3PXCQ3FMQA===="
+        .parse()
+        .unwrap();
+    let plain = EntryBuf::build("runme", EntryKind::Blob(10), 0o100755, &object);
+    let no_xattrs = EntryBuf::build_with_xattrs(
+        "runme",
+        EntryKind::Blob(10),
+        0o100755,
+        &object,
+        &Default::default(),
+    );
+    let xattrs = tracking::Xattrs::from_iter([(
+        "security.capability".to_string(),
+        vec![1, 0, 0, 2, 0, 4, 0, 0],
+    )]);
+    let with_xattrs =
+        EntryBuf::build_with_xattrs("runme", EntryKind::Blob(10), 0o100755, &object, &xattrs);
+
+    assert_eq!(
+        plain.as_entry().digest().unwrap(),
+        no_xattrs.as_entry().digest().unwrap(),
+        "an empty xattr table should not change the digest"
+    );
+    assert_ne!(
+        plain.as_entry().digest().unwrap(),
+        with_xattrs.as_entry().digest().unwrap(),
+        "xattrs should be included in the digest"
+    );
+    assert_eq!(with_xattrs.as_entry().to_xattrs(), xattrs);
+    assert!(
+        with_xattrs
+            .as_entry()
+            .legacy_encode(&mut Vec::new())
+            .is_err(),
+        "xattrs cannot be written with the legacy encoding"
+    );
+}
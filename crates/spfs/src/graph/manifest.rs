@@ -128,6 +128,7 @@ impl Manifest {
                     object: *entry.object(),
                     user_data: (),
                     legacy_size: entry.size_for_legacy_encode(),
+                    xattrs: entry.to_xattrs(),
                 };
                 if entry.kind().is_tree() {
                     new_entry.object = encoding::NULL_DIGEST.into();
@@ -322,6 +323,7 @@ impl ManifestBuilder {
                         node.entry.mode,
                         node.entry.size_for_legacy_encode(),
                         &sub_root_digest,
+                        &node.entry.xattrs,
                     )
                 }
                 _ => Entry::from(builder, node.path.as_str(), node.entry),
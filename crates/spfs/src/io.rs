@@ -120,6 +120,82 @@ pub fn format_changes<'a, U1: 'a, U2: 'a>(
     format_diffs(diffs.filter(|x| !x.mode.is_unchanged()))
 }
 
+/// Return a summary of how the size of each changed file was affected.
+///
+/// Ignores any additional entry user data.
+pub fn format_diff_stat<'a, U1: 'a, U2: 'a>(
+    diffs: impl Iterator<Item = &'a tracking::Diff<U1, U2>>,
+) -> String {
+    let mut outputs = Vec::new();
+    let (mut added, mut removed, mut changed) = (0, 0, 0);
+    let (mut grown, mut shrunk) = (0, 0);
+    for diff in diffs {
+        let (before, after) = match &diff.mode {
+            tracking::DiffMode::Unchanged(..) => continue,
+            _ if diff.mode.is_dir() => continue,
+            tracking::DiffMode::Added(b) => {
+                added += 1;
+                (0, b.size())
+            }
+            tracking::DiffMode::Removed(a) => {
+                removed += 1;
+                (a.size(), 0)
+            }
+            tracking::DiffMode::Changed(a, b) => {
+                changed += 1;
+                (a.size(), b.size())
+            }
+        };
+        let delta = if after >= before {
+            grown += after - before;
+            format!("+{}", format_size(after - before)).green()
+        } else {
+            shrunk += before - after;
+            format!("-{}", format_size(before - after)).red()
+        };
+        outputs.push(format!(
+            "{} /spfs{} | {delta}",
+            format!("{:>8}", diff.mode).bold(),
+            diff.path
+        ));
+    }
+    outputs.push(format!(
+        "{} files changed ({added} added, {removed} removed, {changed} changed), {}, {}",
+        added + removed + changed,
+        format!("+{}", format_size(grown)).green(),
+        format!("-{}", format_size(shrunk)).red(),
+    ));
+    outputs.join("\n")
+}
+
+/// Return a human readable string rendering of the given three-way diffs.
+///
+/// Ignores any additional entry user data.
+pub fn format_merge_diffs<'a, U: 'a>(
+    diffs: impl Iterator<Item = &'a tracking::MergeDiff<U>>,
+) -> String {
+    let mut outputs = Vec::new();
+    for diff in diffs {
+        let about = match &diff.mode {
+            tracking::MergeMode::Ours(mode)
+            | tracking::MergeMode::Theirs(mode)
+            | tracking::MergeMode::Both(mode) => format!(" [{mode}]"),
+            tracking::MergeMode::Conflict(ours, theirs) => format!(" [{ours} / {theirs}]"),
+        };
+        let mut out = String::new();
+        out += format!("{:>8}", diff.mode.to_string()).bold().as_ref();
+        out += format!(" /spfs{}{}", diff.path, about.dimmed()).as_ref();
+        let out = match diff.mode {
+            tracking::MergeMode::Conflict(..) => out.red(),
+            tracking::MergeMode::Both(..) => out.dimmed(),
+            _ => out.bright_blue(),
+        };
+        outputs.push(out.to_string())
+    }
+
+    outputs.join("\n")
+}
+
 /// Return a human-readable representation of the sync summary data.
 pub fn format_sync_summary(summary: &super::sync::SyncSummary) -> String {
     let super::sync::SyncSummary {
@@ -130,15 +206,23 @@ pub fn format_sync_summary(summary: &super::sync::SyncSummary) -> String {
         skipped_payloads,
         synced_payloads,
         synced_payload_bytes,
+        deduplicated_payload_bytes,
     } = summary;
 
-    format!(
+    let mut summary = format!(
         "synced:\n\t{synced_tags} tags ({})\n\t{synced_objects} objects ({})\n\t{synced_payloads} payloads ({}) ({})",
         format!("{skipped_tags} skipped").dimmed(),
         format!("{skipped_objects} skipped").dimmed(),
         format!("{skipped_payloads} skipped").dimmed(),
         format_size(*synced_payload_bytes)
-    )
+    );
+    if *deduplicated_payload_bytes > 0 {
+        summary.push_str(&format!(
+            " ({})",
+            format!("{} deduplicated", format_size(*deduplicated_payload_bytes)).dimmed()
+        ));
+    }
+    summary
 }
 
 /// Return a human-readable file size in bytes.
@@ -21,8 +21,10 @@ mod error;
 pub mod find_path;
 pub mod graph;
 pub mod io;
+pub mod mirror;
 #[cfg_attr(windows, path = "./monitor_win.rs")]
 pub mod monitor;
+pub mod oci;
 pub mod prelude;
 pub mod proto;
 mod prune;
@@ -31,6 +33,7 @@ mod resolve;
 pub mod runtime;
 #[cfg(feature = "server")]
 pub mod server;
+pub mod signing;
 mod status;
 pub mod storage;
 pub mod sync;
@@ -48,9 +51,18 @@ pub use bootstrap::{
 pub use check::Checker;
 pub use clean::Cleaner;
 pub use commit::Committer;
-pub use diff::{diff, diff_runtime_changes, runtime_active_changes};
+pub use diff::{
+    diff,
+    diff_content,
+    diff_runtime_changes,
+    diff_three_way,
+    runtime_active_changes,
+    MAX_CONTENT_DIFF_SIZE,
+};
 pub use encoding::Digest;
 pub use error::{Error, OsError, OsErrorExt, Result};
+pub use mirror::Mirror;
+pub use prune::RetentionPolicy;
 pub use resolve::{
     compute_environment_manifest,
     compute_manifest,
@@ -0,0 +1,275 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+//! Continuous replication of one repository into another.
+//!
+//! Unlike a regular [`Syncer`], the mirror remembers the newest entry that
+//! it has seen in each tag stream of the source repository. Each pass only
+//! syncs the tag entries that have appeared since then, along with any
+//! of their object data that is missing from the destination.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures::TryStreamExt;
+use relative_path::{RelativePath, RelativePathBuf};
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::*;
+use crate::storage::TagNamespaceBuf;
+use crate::sync::{SilentSyncReporter, SyncPolicy, SyncReporter, SyncSummary};
+use crate::{encoding, storage, tracking, Error, Result, Syncer};
+
+#[cfg(test)]
+#[path = "./mirror_test.rs"]
+mod mirror_test;
+
+/// The default amount of time to wait between mirror passes
+pub const DEFAULT_MIRROR_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Records how far each tag stream has been mirrored.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MirrorCursor {
+    /// The digest of the newest tag that was mirrored, by tag path
+    #[serde(default)]
+    pub streams: BTreeMap<String, encoding::Digest>,
+}
+
+impl MirrorCursor {
+    /// Load a cursor from the given file.
+    ///
+    /// An empty cursor is returned if the file does not exist.
+    pub fn load(path: &Path) -> Result<Self> {
+        let data = match std::fs::read(path) {
+            Ok(data) => data,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(err) => {
+                return Err(Error::StorageReadError(
+                    "read mirror cursor",
+                    path.into(),
+                    err,
+                ))
+            }
+        };
+        serde_json::from_slice(&data).map_err(|err| {
+            Error::String(format!(
+                "Invalid mirror cursor file {}: {err}",
+                path.display()
+            ))
+        })
+    }
+
+    /// Save this cursor to the given file, replacing it atomically.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let data = serde_json::to_vec_pretty(self)
+            .map_err(|err| Error::String(format!("Failed to serialize mirror cursor: {err}")))?;
+        let mut working_file = path.as_os_str().to_owned();
+        working_file.push(".tmp");
+        let working_file = PathBuf::from(working_file);
+        std::fs::write(&working_file, data).map_err(|err| {
+            Error::StorageWriteError("write mirror cursor", working_file.clone(), err)
+        })?;
+        std::fs::rename(&working_file, path)
+            .map_err(|err| Error::StorageWriteError("save mirror cursor", path.into(), err))
+    }
+}
+
+/// Keeps a destination repository up to date with the tags of a source.
+pub struct Mirror<'src, 'dst, Reporter: SyncReporter = SilentSyncReporter> {
+    src: &'src storage::RepositoryHandle,
+    dest: &'dst storage::RepositoryHandle,
+    reporter: Arc<Reporter>,
+    tag_namespace: Option<TagNamespaceBuf>,
+    prefixes: Vec<RelativePathBuf>,
+    cursor: MirrorCursor,
+    cursor_file: Option<PathBuf>,
+}
+
+impl<'src, 'dst> Mirror<'src, 'dst> {
+    pub fn new(
+        src: &'src storage::RepositoryHandle,
+        dest: &'dst storage::RepositoryHandle,
+    ) -> Self {
+        Self {
+            src,
+            dest,
+            reporter: Arc::new(SilentSyncReporter::default()),
+            tag_namespace: None,
+            prefixes: Vec::new(),
+            cursor: MirrorCursor::default(),
+            cursor_file: None,
+        }
+    }
+}
+
+impl<'src, 'dst, Reporter> Mirror<'src, 'dst, Reporter>
+where
+    Reporter: SyncReporter,
+{
+    /// Report sync progress to the given instance, replacing any existing one
+    pub fn with_reporter<T, R>(self, reporter: T) -> Mirror<'src, 'dst, R>
+    where
+        T: Into<Arc<R>>,
+        R: SyncReporter,
+    {
+        Mirror {
+            src: self.src,
+            dest: self.dest,
+            reporter: reporter.into(),
+            tag_namespace: self.tag_namespace,
+            prefixes: self.prefixes,
+            cursor: self.cursor,
+            cursor_file: self.cursor_file,
+        }
+    }
+
+    /// Only mirror the tags in this namespace.
+    ///
+    /// Tags are read from and written to the same namespace
+    /// in both repositories.
+    pub fn with_tag_namespace(mut self, tag_namespace: Option<TagNamespaceBuf>) -> Self {
+        self.tag_namespace = tag_namespace;
+        self
+    }
+
+    /// Only mirror tags whose path starts with one of these prefixes.
+    ///
+    /// All tags are mirrored when no prefixes are given.
+    pub fn with_prefixes<I>(mut self, prefixes: I) -> Self
+    where
+        I: IntoIterator<Item = RelativePathBuf>,
+    {
+        self.prefixes = prefixes.into_iter().collect();
+        self
+    }
+
+    /// Load and persist the mirror cursor to this file.
+    ///
+    /// The cursor is saved at the end of each pass so that
+    /// the mirror can resume where it left off if restarted.
+    pub fn with_cursor_file<P: Into<PathBuf>>(mut self, path: P) -> Result<Self> {
+        let path = path.into();
+        self.cursor = MirrorCursor::load(&path)?;
+        self.cursor_file = Some(path);
+        Ok(self)
+    }
+
+    /// The current position of the mirror in each tag stream
+    pub fn cursor(&self) -> &MirrorCursor {
+        &self.cursor
+    }
+
+    /// Run mirror passes forever, waiting for the given interval between each.
+    ///
+    /// Errors in a single pass are logged and do not stop the mirror.
+    pub async fn run(&mut self, interval: Duration) -> Result<()> {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            ticker.tick().await;
+            match self.mirror_once().await {
+                Ok(summary) => tracing::info!(
+                    streams = summary.checked_streams,
+                    updated = summary.updated_streams,
+                    tags = summary.sync.synced_tags,
+                    objects = summary.sync.synced_objects,
+                    payloads = summary.sync.synced_payloads,
+                    "mirror pass completed"
+                ),
+                Err(err) => tracing::error!("mirror pass failed: {err}"),
+            }
+        }
+    }
+
+    /// Perform a single pass, syncing any tags that have changed
+    /// since the last pass.
+    pub async fn mirror_once(&mut self) -> Result<MirrorSummary> {
+        let syncer = Syncer::new(self.src, self.dest)
+            .with_policy(SyncPolicy::MissingDataOnly)
+            .with_reporter(Arc::clone(&self.reporter));
+        let mut summary = MirrorSummary::default();
+        let mut streams = self
+            .src
+            .iter_tag_streams_in_namespace(self.tag_namespace.as_deref());
+        while let Some((spec, mut stream)) = streams.try_next().await? {
+            let path = spec.path();
+            if !self.is_included(&path) {
+                continue;
+            }
+            summary.checked_streams += 1;
+            let key = path.to_string();
+            let last_seen = self.cursor.streams.get(&key).copied();
+
+            // streams are ordered from newest to oldest, so collect
+            // entries until reaching the last one that was mirrored
+            let mut new_tags = Vec::new();
+            while let Some(tag) = stream.try_next().await? {
+                if Some(tag.digest()?) == last_seen {
+                    break;
+                }
+                new_tags.push(tag);
+            }
+            let Some(newest) = new_tags.first() else {
+                continue;
+            };
+            let newest = newest.digest()?;
+
+            match self.mirror_tags(&syncer, new_tags).await {
+                Ok(sync) => {
+                    summary.updated_streams += 1;
+                    summary.sync += sync;
+                    self.cursor.streams.insert(key, newest);
+                }
+                Err(err) => {
+                    // the cursor is left as-is so that
+                    // this stream is retried next time
+                    tracing::warn!(tag = %key, "failed to mirror tag stream: {err}");
+                    summary.failed_streams += 1;
+                }
+            }
+        }
+        if let Some(path) = &self.cursor_file {
+            self.cursor.save(path)?;
+        }
+        Ok(summary)
+    }
+
+    /// Sync the given tags, newest first, and their targets to the destination
+    async fn mirror_tags<R: SyncReporter>(
+        &self,
+        syncer: &Syncer<'_, '_, R>,
+        tags: Vec<tracking::Tag>,
+    ) -> Result<SyncSummary> {
+        let mut summary = SyncSummary::default();
+        // insert the oldest entries first, so that the destination is never
+        // left with a newer tag than the ones before it, if interrupted
+        for tag in tags.into_iter().rev() {
+            summary += syncer.sync_digest(tag.target).await?.summary();
+            self.dest
+                .insert_tag_in_namespace(self.tag_namespace.as_deref(), &tag)
+                .await?;
+            summary.synced_tags += 1;
+        }
+        Ok(summary)
+    }
+
+    fn is_included(&self, path: &RelativePath) -> bool {
+        self.prefixes.is_empty() || self.prefixes.iter().any(|p| path.starts_with(p))
+    }
+}
+
+/// The outcome of a single mirror pass
+#[derive(Debug, Default)]
+pub struct MirrorSummary {
+    /// The number of tag streams that were considered
+    pub checked_streams: usize,
+    /// The number of tag streams that had new entries to mirror
+    pub updated_streams: usize,
+    /// The number of tag streams that could not be mirrored
+    pub failed_streams: usize,
+    /// The tags and data that were synced
+    pub sync: SyncSummary,
+}
@@ -0,0 +1,134 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use futures::TryStreamExt;
+use relative_path::RelativePathBuf;
+use rstest::rstest;
+
+use super::{Mirror, MirrorCursor};
+use crate::fixtures::*;
+use crate::prelude::*;
+use crate::tracking;
+
+async fn push_data(repo: &TempRepo, tag: &str, data: &str) -> crate::encoding::Digest {
+    let digest = repo
+        .commit_blob(Box::pin(std::io::Cursor::new(data.as_bytes().to_vec())))
+        .await
+        .unwrap();
+    let tag = tracking::TagSpec::parse(tag).unwrap();
+    repo.push_tag(&tag, &digest).await.unwrap();
+    digest
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_mirror_only_new_tags(
+    #[future]
+    #[from(tmprepo)]
+    src: TempRepo,
+    #[future]
+    #[from(tmprepo)]
+    dest: TempRepo,
+) {
+    init_logging();
+    let src = src.await;
+    let dest = dest.await;
+
+    push_data(&src, "testing/first", "one").await;
+    push_data(&src, "testing/first", "two").await;
+    push_data(&src, "testing/second", "three").await;
+
+    let mut mirror = Mirror::new(&src, &dest);
+    let summary = mirror.mirror_once().await.unwrap();
+    assert_eq!(summary.checked_streams, 2);
+    assert_eq!(summary.updated_streams, 2);
+    assert_eq!(summary.sync.synced_tags, 3, "should sync all tag history");
+
+    let first = tracking::TagSpec::parse("testing/first").unwrap();
+    let history: Vec<_> = dest
+        .read_tag(&first)
+        .await
+        .unwrap()
+        .try_collect()
+        .await
+        .unwrap();
+    assert_eq!(history.len(), 2, "should mirror the entire tag stream");
+
+    let summary = mirror.mirror_once().await.unwrap();
+    assert_eq!(summary.updated_streams, 0, "nothing has changed");
+    assert_eq!(summary.sync.synced_tags, 0, "nothing has changed");
+
+    let latest = push_data(&src, "testing/first", "four").await;
+    let summary = mirror.mirror_once().await.unwrap();
+    assert_eq!(summary.updated_streams, 1);
+    assert_eq!(summary.sync.synced_tags, 1, "should only sync the new tag");
+    assert_eq!(dest.resolve_tag(&first).await.unwrap().target, latest);
+    assert!(dest.has_payload(latest).await);
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_mirror_prefix_filter(
+    #[future]
+    #[from(tmprepo)]
+    src: TempRepo,
+    #[future]
+    #[from(tmprepo)]
+    dest: TempRepo,
+) {
+    init_logging();
+    let src = src.await;
+    let dest = dest.await;
+
+    push_data(&src, "included/tag", "one").await;
+    push_data(&src, "excluded/tag", "two").await;
+
+    let summary = Mirror::new(&src, &dest)
+        .with_prefixes([RelativePathBuf::from("included")])
+        .mirror_once()
+        .await
+        .unwrap();
+    assert_eq!(summary.checked_streams, 1);
+
+    let included = tracking::TagSpec::parse("included/tag").unwrap();
+    let excluded = tracking::TagSpec::parse("excluded/tag").unwrap();
+    assert!(dest.resolve_tag(&included).await.is_ok());
+    assert!(dest.resolve_tag(&excluded).await.is_err());
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_mirror_cursor_is_persisted(
+    tmpdir: tempfile::TempDir,
+    #[future]
+    #[from(tmprepo)]
+    src: TempRepo,
+    #[future]
+    #[from(tmprepo)]
+    dest: TempRepo,
+) {
+    init_logging();
+    let src = src.await;
+    let dest = dest.await;
+    let cursor_file = tmpdir.path().join("cursor.json");
+
+    push_data(&src, "testing/tag", "one").await;
+
+    let mut mirror = Mirror::new(&src, &dest)
+        .with_cursor_file(&cursor_file)
+        .unwrap();
+    mirror.mirror_once().await.unwrap();
+    let cursor = MirrorCursor::load(&cursor_file).unwrap();
+    assert_eq!(&cursor, mirror.cursor());
+    assert!(cursor.streams.contains_key("testing/tag"));
+
+    // a new mirror should resume from the saved cursor
+    let summary = Mirror::new(&src, &dest)
+        .with_cursor_file(&cursor_file)
+        .unwrap()
+        .mirror_once()
+        .await
+        .unwrap();
+    assert_eq!(summary.sync.synced_tags, 0);
+}
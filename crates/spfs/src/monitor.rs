@@ -18,6 +18,10 @@ use super::runtime;
 use crate::repeating_timeout::RepeatingTimeout;
 use crate::{Error, OsError, Result};
 
+#[cfg(test)]
+#[path = "./monitor_test.rs"]
+mod monitor_test;
+
 pub const PROC_DIR: &str = "/proc";
 
 pub const SPFS_MONITOR_FOREGROUND_LOGGING_VAR: &str = "SPFS_MONITOR_FOREGROUND_LOGGING";
@@ -29,6 +33,123 @@ enum PidEvent {
     Exit(i32),
 }
 
+/// Accumulates the resources consumed by the processes in a runtime
+///
+/// Clones of a tracker share the same accumulated usage, so that
+/// it can still be read once the monitoring has stopped.
+#[derive(Debug, Clone)]
+pub struct ResourceTracker {
+    state: Arc<std::sync::Mutex<ResourceTrackerState>>,
+}
+
+#[derive(Debug)]
+struct ResourceTrackerState {
+    started: std::time::Instant,
+    /// The latest cpu time seen for each process, keyed by pid
+    /// and start time so that reused pids are not confused
+    cpu_ticks: HashMap<(u32, u64), ProcessTicks>,
+    process_count: u64,
+    peak_rss_bytes: u64,
+}
+
+/// The cpu time of a single process, in clock ticks
+#[derive(Debug, Clone, Copy)]
+struct ProcessTicks {
+    parent: u32,
+    /// The user and system time of the process itself
+    own: u64,
+    /// The user and system time of all children that the
+    /// process has waited for, including those never sampled
+    children: u64,
+}
+
+impl ProcessTicks {
+    fn total(&self) -> u64 {
+        self.own + self.children
+    }
+}
+
+impl Default for ResourceTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ResourceTracker {
+    /// Create a tracker that measures the runtime duration from now
+    pub fn new() -> Self {
+        Self {
+            state: Arc::new(std::sync::Mutex::new(ResourceTrackerState {
+                started: std::time::Instant::now(),
+                cpu_ticks: HashMap::new(),
+                process_count: 0,
+                peak_rss_bytes: 0,
+            })),
+        }
+    }
+
+    /// Record the current resource usage of the given processes
+    ///
+    /// The cpu time of processes that exit between samples is still
+    /// counted once they are waited for by another sampled process.
+    pub fn sample(&self, pids: &HashSet<u32>) {
+        let page_size = procfs::page_size();
+        let mut rss_bytes = 0;
+        let mut sampled = HashMap::with_capacity(pids.len());
+        for pid in pids {
+            // processes can exit at any time, and any that
+            // cannot be read are simply left out of this sample
+            let Ok(stat) = procfs::process::Process::new(*pid as i32).and_then(|p| p.stat()) else {
+                continue;
+            };
+            rss_bytes += stat.rss * page_size;
+            let ticks = ProcessTicks {
+                parent: stat.ppid as u32,
+                own: stat.utime + stat.stime,
+                children: (stat.cutime + stat.cstime).max(0) as u64,
+            };
+            sampled.insert(*pid, (stat.starttime, ticks));
+        }
+        let mut state = self
+            .state
+            .lock()
+            .expect("resource tracker lock should not be poisoned");
+        // A process that has gone away while its parent is still running
+        // has been waited for by that parent, whose children time now
+        // includes all of its cpu time, so it must not be counted twice
+        state.cpu_ticks.retain(|(pid, starttime), ticks| {
+            if sampled.contains_key(pid) {
+                return true;
+            }
+            !matches!(
+                sampled.get(&ticks.parent),
+                Some((parent_start, _)) if parent_start <= starttime
+            )
+        });
+        for (pid, (starttime, ticks)) in sampled {
+            if state.cpu_ticks.insert((pid, starttime), ticks).is_none() {
+                state.process_count += 1;
+            }
+        }
+        state.peak_rss_bytes = state.peak_rss_bytes.max(rss_bytes);
+    }
+
+    /// The resources consumed so far by all sampled processes
+    pub fn usage(&self) -> runtime::ResourceUsage {
+        let state = self
+            .state
+            .lock()
+            .expect("resource tracker lock should not be poisoned");
+        let cpu_ticks: u64 = state.cpu_ticks.values().map(ProcessTicks::total).sum();
+        runtime::ResourceUsage {
+            cpu_time_ms: cpu_ticks * 1000 / procfs::ticks_per_second(),
+            peak_rss_bytes: state.peak_rss_bytes,
+            process_count: state.process_count,
+            duration_ms: state.started.elapsed().as_millis() as u64,
+        }
+    }
+}
+
 /// Run an spfs monitor for the provided runtime
 ///
 /// The monitor command will spawn but immediately fail
@@ -81,9 +202,15 @@ pub fn spawn_monitor_for_runtime(rt: &runtime::Runtime) -> Result<tokio::process
 
 /// When provided an active runtime, wait until all contained processes exit
 ///
+/// If a tracker is provided, the resource usage of the runtime's
+/// processes is sampled into it for as long as they are monitored.
+///
 /// This is a privileged operation that may fail with a permission
 /// issue if the calling process is not root or CAP_NET_ADMIN
-pub async fn wait_for_empty_runtime(rt: &runtime::Runtime) -> Result<()> {
+pub async fn wait_for_empty_runtime(
+    rt: &runtime::Runtime,
+    tracker: Option<ResourceTracker>,
+) -> Result<()> {
     let pid = match rt.status.owner {
         None => return Err(Error::RuntimeNotInitialized(rt.name().into())),
         Some(pid) => pid,
@@ -165,6 +292,9 @@ pub async fn wait_for_empty_runtime(rt: &runtime::Runtime) -> Result<()> {
         None => HashSet::new(),
     };
     tracked_processes.extend(current_pids);
+    if let Some(tracker) = &tracker {
+        tracker.sample(&tracked_processes);
+    }
 
     // it's possible that the runtime process(es)
     // completed before we were even able to see them
@@ -199,6 +329,9 @@ pub async fn wait_for_empty_runtime(rt: &runtime::Runtime) -> Result<()> {
                         }
                         Ok(pids) => pids,
                     };
+                    if let Some(tracker) = &tracker {
+                        tracker.sample(&current_pids);
+                    }
 
                     // Grab one of the existing pids to play the role of
                     // parent for any new pid.
@@ -0,0 +1,51 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use std::collections::HashSet;
+
+use rstest::rstest;
+
+use super::ResourceTracker;
+
+#[rstest]
+fn test_resource_tracker_sample() {
+    let tracker = ResourceTracker::new();
+    assert_eq!(tracker.usage().process_count, 0);
+
+    let pid = nix::unistd::getpid().as_raw() as u32;
+    // a pid that is larger than the kernel allows, and so cannot exist
+    let missing = u32::MAX / 2;
+    tracker.sample(&HashSet::from([pid, missing]));
+    tracker.sample(&HashSet::from([pid]));
+
+    let usage = tracker.usage();
+    assert_eq!(
+        usage.process_count, 1,
+        "repeated samples of the same process should only be counted once"
+    );
+    assert!(usage.peak_rss_bytes > 0);
+}
+
+#[rstest]
+fn test_resource_tracker_waited_children() {
+    let tracker = ResourceTracker::new();
+    let pid = nix::unistd::getpid().as_raw() as u32;
+    let mut child = std::process::Command::new("sleep")
+        .arg("10")
+        .spawn()
+        .expect("failed to spawn child process");
+    tracker.sample(&HashSet::from([pid, child.id()]));
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+    tracker.sample(&HashSet::from([pid, child.id()]));
+
+    let state = tracker.state.lock().unwrap();
+    assert_eq!(state.process_count, 2);
+    assert_eq!(
+        state.cpu_ticks.len(),
+        1,
+        "a child that was waited for is counted in its parent's cpu time"
+    );
+}
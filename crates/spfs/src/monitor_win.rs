@@ -12,6 +12,24 @@ use crate::Result;
 
 pub const SPFS_MONITOR_FOREGROUND_LOGGING_VAR: &str = "SPFS_MONITOR_FOREGROUND_LOGGING";
 
+/// Accumulates the resources consumed by the processes in a runtime
+#[derive(Debug, Clone, Default)]
+pub struct ResourceTracker {}
+
+impl ResourceTracker {
+    /// Create a tracker that measures the runtime duration from now
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    /// The resources consumed so far by all sampled processes
+    ///
+    /// Resources are not yet tracked on windows, and so this is always empty.
+    pub fn usage(&self) -> runtime::ResourceUsage {
+        runtime::ResourceUsage::default()
+    }
+}
+
 /// Run an spfs monitor for the provided runtime
 ///
 /// The monitor command will spawn but immediately fail
@@ -24,7 +42,10 @@ pub fn spawn_monitor_for_runtime(_rt: &runtime::Runtime) -> Result<tokio::proces
 ///
 /// This is a privileged operation that may fail with a permission
 /// issue if the calling process is not root or CAP_NET_ADMIN
-pub async fn wait_for_empty_runtime(_rt: &runtime::Runtime) -> Result<()> {
+pub async fn wait_for_empty_runtime(
+    _rt: &runtime::Runtime,
+    _tracker: Option<ResourceTracker>,
+) -> Result<()> {
     todo!()
 }
 
@@ -0,0 +1,424 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::Path;
+
+use relative_path::{RelativePath, RelativePathBuf};
+use tokio::io::AsyncSeekExt;
+
+use super::{
+    blob_path,
+    read_json,
+    Descriptor,
+    History,
+    ImageConfig,
+    ImageIndex,
+    ImageManifest,
+    RootFs,
+    ANNOTATION_REF_NAME,
+    ANNOTATION_SPFS_LAYER,
+    BLOBS_DIR,
+    INDEX_FILE,
+    MEDIA_TYPE_IMAGE_CONFIG,
+    MEDIA_TYPE_IMAGE_LAYER,
+    MEDIA_TYPE_IMAGE_MANIFEST,
+    OCI_LAYOUT_FILE,
+    OCI_LAYOUT_VERSION,
+    SHA256,
+    WHITEOUT_PREFIX,
+};
+use crate::prelude::*;
+use crate::{graph, storage, tracking, Error, Result};
+
+#[cfg(test)]
+#[path = "./export_test.rs"]
+mod export_test;
+
+/// Writes spfs environments as OCI images.
+///
+/// Each layer of the environment becomes one layer in the image. Layers
+/// are written in a reproducible way, so that the same spfs layer always
+/// produces the same OCI layer and can be shared between images.
+pub struct OciExporter<'repo> {
+    repo: &'repo storage::RepositoryHandle,
+    prefix: RelativePathBuf,
+    ref_name: Option<String>,
+    architecture: String,
+}
+
+impl<'repo> OciExporter<'repo> {
+    /// Create an exporter that reads layer data from the given repository
+    pub fn new(repo: &'repo storage::RepositoryHandle) -> Self {
+        Self {
+            repo,
+            prefix: RelativePathBuf::from(super::DEFAULT_PREFIX),
+            ref_name: None,
+            architecture: super::default_architecture().to_string(),
+        }
+    }
+
+    /// The directory within the image where layer contents are placed
+    pub fn with_prefix<P: Into<RelativePathBuf>>(mut self, prefix: P) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// The name that the exported image is given in the layout index
+    pub fn with_ref_name(mut self, ref_name: Option<String>) -> Self {
+        self.ref_name = ref_name;
+        self
+    }
+
+    /// The architecture recorded in the image configuration
+    pub fn with_architecture<S: Into<String>>(mut self, architecture: S) -> Self {
+        self.architecture = architecture.into();
+        self
+    }
+
+    /// Export the given stack of layers into an OCI image layout directory.
+    ///
+    /// If the directory already contains an image layout, the new image
+    /// is added to it, replacing any existing image of the same name.
+    /// Returns the descriptor of the new image manifest.
+    pub async fn export_layout(&self, stack: &graph::Stack, layout: &Path) -> Result<Descriptor> {
+        let layers = crate::resolve_stack_to_layers(stack, Some(self.repo)).await?;
+
+        std::fs::create_dir_all(layout.join(BLOBS_DIR).join(SHA256))
+            .map_err(|err| Error::StorageWriteError("create oci layout", layout.into(), err))?;
+        let layout_file = layout.join(OCI_LAYOUT_FILE);
+        let layout_data = serde_json::json!({ "imageLayoutVersion": OCI_LAYOUT_VERSION });
+        std::fs::write(&layout_file, layout_data.to_string())
+            .map_err(|err| Error::StorageWriteError("write oci layout", layout_file, err))?;
+
+        let mut layer_descriptors = Vec::with_capacity(layers.len());
+        let mut diff_ids = Vec::with_capacity(layers.len());
+        let mut history = Vec::with_capacity(layers.len());
+        for layer in layers.iter() {
+            let Some(manifest_digest) = layer.manifest() else {
+                // layers that only hold annotations have no filesystem
+                // contents to be represented in the image
+                continue;
+            };
+            let layer_digest = layer.digest()?;
+            tracing::debug!(%layer_digest, "exporting layer");
+            let manifest = self
+                .repo
+                .read_manifest(*manifest_digest)
+                .await?
+                .to_tracking_manifest();
+            let mut descriptor = self.write_layer(&manifest, layout).await?;
+            descriptor
+                .annotations
+                .insert(ANNOTATION_SPFS_LAYER.to_string(), layer_digest.to_string());
+            // layers are not compressed, so the digest of their
+            // contents is the same as the digest of the blob
+            diff_ids.push(descriptor.digest.clone());
+            history.push(History {
+                created_by: Some(format!("spfs layer {layer_digest}")),
+                comment: None,
+            });
+            layer_descriptors.push(descriptor);
+        }
+
+        let config = ImageConfig {
+            architecture: self.architecture.clone(),
+            os: "linux".to_string(),
+            rootfs: RootFs {
+                kind: "layers".to_string(),
+                diff_ids,
+            },
+            history,
+        };
+        let config = write_json_blob(layout, MEDIA_TYPE_IMAGE_CONFIG, &config)?;
+        let manifest = ImageManifest {
+            schema_version: 2,
+            media_type: Some(MEDIA_TYPE_IMAGE_MANIFEST.to_string()),
+            config,
+            layers: layer_descriptors,
+            annotations: BTreeMap::new(),
+        };
+        let mut descriptor = write_json_blob(layout, MEDIA_TYPE_IMAGE_MANIFEST, &manifest)?;
+        if let Some(ref_name) = &self.ref_name {
+            descriptor
+                .annotations
+                .insert(ANNOTATION_REF_NAME.to_string(), ref_name.clone());
+        }
+
+        let index_file = layout.join(INDEX_FILE);
+        let mut index = if index_file.exists() {
+            read_json::<ImageIndex>(&index_file)?
+        } else {
+            ImageIndex::default()
+        };
+        if let Some(ref_name) = &self.ref_name {
+            index
+                .manifests
+                .retain(|m| m.annotations.get(ANNOTATION_REF_NAME) != Some(ref_name));
+        }
+        index.manifests.push(descriptor.clone());
+        let index_data = serde_json::to_vec_pretty(&index)
+            .map_err(|err| Error::String(format!("Failed to serialize OCI index: {err}")))?;
+        std::fs::write(&index_file, index_data)
+            .map_err(|err| Error::StorageWriteError("write oci index", index_file, err))?;
+        Ok(descriptor)
+    }
+
+    /// Export the given stack of layers as a tar archive of an OCI image layout.
+    ///
+    /// Returns the descriptor of the new image manifest.
+    pub async fn export_archive(&self, stack: &graph::Stack, archive: &Path) -> Result<Descriptor> {
+        let parent = match archive.parent() {
+            Some(p) if !p.as_os_str().is_empty() => p,
+            _ => Path::new("."),
+        };
+        let working_dir = tempfile::Builder::new()
+            .prefix(".spfs-oci-")
+            .tempdir_in(parent)
+            .map_err(|err| {
+                Error::StorageWriteError("create oci working dir", parent.into(), err)
+            })?;
+        let descriptor = self.export_layout(stack, working_dir.path()).await?;
+
+        let file = std::fs::File::create(archive)
+            .map_err(|err| Error::StorageWriteError("create oci archive", archive.into(), err))?;
+        let mut builder = tar::Builder::new(std::io::BufWriter::new(file));
+        let write_err = |err| Error::StorageWriteError("write oci archive", archive.into(), err);
+        for name in [OCI_LAYOUT_FILE, INDEX_FILE] {
+            builder
+                .append_path_with_name(working_dir.path().join(name), name)
+                .map_err(write_err)?;
+        }
+        builder
+            .append_dir_all(BLOBS_DIR, working_dir.path().join(BLOBS_DIR))
+            .map_err(write_err)?;
+        builder
+            .into_inner()
+            .and_then(|mut writer| writer.flush())
+            .map_err(write_err)?;
+        Ok(descriptor)
+    }
+
+    /// Write the contents of a manifest as a layer blob in the given layout
+    async fn write_layer(
+        &self,
+        manifest: &tracking::Manifest,
+        layout: &Path,
+    ) -> Result<Descriptor> {
+        let blobs_dir = layout.join(BLOBS_DIR).join(SHA256);
+        let working_file = blobs_dir.join(format!(".layer-{}", uuid::Uuid::new_v4()));
+        let result = self.write_layer_to(manifest, &working_file).await;
+        let (digest, size) = match result {
+            Ok(result) => result,
+            Err(err) => {
+                let _ = std::fs::remove_file(&working_file);
+                return Err(err);
+            }
+        };
+        let path = blobs_dir.join(&digest);
+        std::fs::rename(&working_file, &path)
+            .map_err(|err| Error::StorageWriteError("save oci layer", path, err))?;
+        Ok(Descriptor {
+            media_type: MEDIA_TYPE_IMAGE_LAYER.to_string(),
+            digest: format!("{SHA256}:{digest}"),
+            size,
+            annotations: BTreeMap::new(),
+            platform: None,
+        })
+    }
+
+    /// Write the contents of a manifest as a tar file, returning
+    /// the encoded sha256 digest and size of the result
+    async fn write_layer_to(
+        &self,
+        manifest: &tracking::Manifest,
+        path: &Path,
+    ) -> Result<(String, u64)> {
+        let write_err = |err| Error::StorageWriteError("write oci layer", path.into(), err);
+        let file = std::fs::File::create(path).map_err(write_err)?;
+        let mut builder = tar::Builder::new(HashingWriter::new(std::io::BufWriter::new(file)));
+
+        // every parent of the prefix must exist in the layer
+        // for the image to be extracted consistently
+        let mut parent = RelativePathBuf::new();
+        for component in self.prefix.components() {
+            parent.push(component.as_str());
+            let mut header = new_header(tar::EntryType::Directory, 0o755);
+            builder
+                .append_data(&mut header, parent.as_str(), std::io::empty())
+                .map_err(write_err)?;
+        }
+
+        let mut nodes = Vec::new();
+        collect_sorted_nodes(manifest.root(), RelativePath::new(""), &mut nodes);
+        for (node_path, entry) in nodes {
+            let path = self.prefix.join(&node_path);
+            if !entry.xattrs.is_empty() {
+                let xattrs = entry
+                    .xattrs
+                    .iter()
+                    .map(|(name, value)| (format!("SCHILY.xattr.{name}"), value.as_slice()))
+                    .collect::<Vec<_>>();
+                builder
+                    .append_pax_extensions(xattrs.iter().map(|(k, v)| (k.as_str(), *v)))
+                    .map_err(write_err)?;
+            }
+            match entry.kind {
+                tracking::EntryKind::Tree => {
+                    let mut header = new_header(tar::EntryType::Directory, entry.mode);
+                    builder
+                        .append_data(&mut header, path.as_str(), std::io::empty())
+                        .map_err(write_err)?;
+                }
+                tracking::EntryKind::Mask => {
+                    let whiteout = path.with_file_name(format!(
+                        "{WHITEOUT_PREFIX}{}",
+                        path.file_name().unwrap_or_default()
+                    ));
+                    let mut header = new_header(tar::EntryType::Regular, 0o644);
+                    builder
+                        .append_data(&mut header, whiteout.as_str(), std::io::empty())
+                        .map_err(write_err)?;
+                }
+                tracking::EntryKind::Blob(size) if entry.is_symlink() => {
+                    let (mut reader, _) = self.repo.open_payload(entry.object).await?;
+                    let mut target = String::new();
+                    tokio::io::AsyncReadExt::read_to_string(&mut reader, &mut target)
+                        .await
+                        .map_err(|err| {
+                            Error::StorageReadError(
+                                "read symlink payload",
+                                path.as_str().into(),
+                                err,
+                            )
+                        })?;
+                    debug_assert_eq!(target.len() as u64, size);
+                    let mut header = new_header(tar::EntryType::Symlink, entry.mode);
+                    builder
+                        .append_link(&mut header, path.as_str(), target)
+                        .map_err(write_err)?;
+                }
+                tracking::EntryKind::Blob(size) => {
+                    let contents = self.spool_payload(entry.object).await?;
+                    let mut header = new_header(tar::EntryType::Regular, entry.mode);
+                    header.set_size(size);
+                    builder
+                        .append_data(&mut header, path.as_str(), contents)
+                        .map_err(write_err)?;
+                }
+            }
+        }
+
+        let mut writer = builder.into_inner().map_err(write_err)?;
+        writer.flush().map_err(write_err)?;
+        Ok((writer.encoded_digest(), writer.size))
+    }
+
+    /// Copy the identified payload into a temporary file, so that
+    /// it can be read synchronously while writing the layer
+    async fn spool_payload(&self, digest: crate::encoding::Digest) -> Result<std::fs::File> {
+        let spool_err = |err| Error::String(format!("Failed to spool payload {digest}: {err}"));
+        let (mut reader, _) = self.repo.open_payload(digest).await?;
+        let mut spool = tokio::fs::File::from_std(tempfile::tempfile().map_err(spool_err)?);
+        tokio::io::copy(&mut reader, &mut spool)
+            .await
+            .map_err(spool_err)?;
+        spool
+            .seek(std::io::SeekFrom::Start(0))
+            .await
+            .map_err(spool_err)?;
+        Ok(spool.into_std().await)
+    }
+}
+
+/// Collect all of the entries under `entry`, ordered by path
+/// so that layers are always written in the same way
+fn collect_sorted_nodes<'m>(
+    entry: &'m tracking::Entry,
+    prefix: &RelativePath,
+    nodes: &mut Vec<(RelativePathBuf, &'m tracking::Entry)>,
+) {
+    let mut names = entry.entries.keys().collect::<Vec<_>>();
+    names.sort();
+    for name in names {
+        let child = &entry.entries[name];
+        let path = prefix.join(name);
+        nodes.push((path.clone(), child));
+        if child.kind.is_tree() {
+            collect_sorted_nodes(child, &path, nodes);
+        }
+    }
+}
+
+/// Create a tar header with no ownership or timestamps, which
+/// would otherwise change the digest of identical layers
+fn new_header(kind: tar::EntryType, mode: u32) -> tar::Header {
+    let mut header = tar::Header::new_gnu();
+    header.set_entry_type(kind);
+    header.set_mode(mode & 0o7777);
+    header.set_uid(0);
+    header.set_gid(0);
+    header.set_mtime(0);
+    header.set_size(0);
+    header
+}
+
+/// Serialize a value as a json blob in the given layout
+fn write_json_blob<T: serde::Serialize>(
+    layout: &Path,
+    media_type: &str,
+    value: &T,
+) -> Result<Descriptor> {
+    let data = serde_json::to_vec(value)
+        .map_err(|err| Error::String(format!("Failed to serialize {media_type}: {err}")))?;
+    let digest = format!(
+        "{SHA256}:{}",
+        data_encoding::HEXLOWER.encode(ring::digest::digest(&ring::digest::SHA256, &data).as_ref())
+    );
+    let path = blob_path(layout, &digest)?;
+    std::fs::write(&path, &data)
+        .map_err(|err| Error::StorageWriteError("write oci blob", path, err))?;
+    Ok(Descriptor {
+        media_type: media_type.to_string(),
+        digest,
+        size: data.len() as u64,
+        annotations: BTreeMap::new(),
+        platform: None,
+    })
+}
+
+/// Computes the sha256 digest of all data written through it
+struct HashingWriter<W> {
+    inner: W,
+    context: ring::digest::Context,
+    size: u64,
+}
+
+impl<W> HashingWriter<W> {
+    fn new(inner: W) -> Self {
+        Self {
+            inner,
+            context: ring::digest::Context::new(&ring::digest::SHA256),
+            size: 0,
+        }
+    }
+
+    fn encoded_digest(&self) -> String {
+        data_encoding::HEXLOWER.encode(self.context.clone().finish().as_ref())
+    }
+}
+
+impl<W: Write> Write for HashingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let count = self.inner.write(buf)?;
+        self.context.update(&buf[..count]);
+        self.size += count as u64;
+        Ok(count)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
@@ -0,0 +1,125 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use std::path::Path;
+
+use rstest::rstest;
+
+use super::OciExporter;
+use crate::fixtures::*;
+use crate::graph;
+use crate::oci::{blob_path, read_json, ImageIndex, ImageManifest, ANNOTATION_REF_NAME};
+use crate::prelude::*;
+use crate::tracking;
+
+/// Commit the contents of a directory as a layer, also
+/// masking any of the given paths
+async fn commit_layer(repo: &TempRepo, dir: &Path, masks: &[&str]) -> graph::Layer {
+    let mut manifest = crate::Committer::new(repo).commit_dir(dir).await.unwrap();
+    for mask in masks {
+        manifest.mknod(mask, tracking::Entry::mask()).unwrap();
+    }
+    repo.create_layer_from_manifest(&manifest).await.unwrap()
+}
+
+/// List the paths in the identified layer blob of an image layout
+fn list_layer(layout: &Path, digest: &str) -> Vec<String> {
+    let file = std::fs::File::open(blob_path(layout, digest).unwrap()).unwrap();
+    tar::Archive::new(file)
+        .entries()
+        .unwrap()
+        .map(|e| e.unwrap().path().unwrap().to_string_lossy().to_string())
+        .collect()
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_export_layout_whiteouts(#[future] tmprepo: TempRepo, tmpdir: tempfile::TempDir) {
+    init_logging();
+    let tmprepo = tmprepo.await;
+
+    let bottom_dir = tmpdir.path().join("bottom");
+    ensure(bottom_dir.join("bin/tool"), "tool");
+    ensure(bottom_dir.join("data.txt"), "data");
+    let bottom = commit_layer(&tmprepo, &bottom_dir, &[]).await;
+
+    let top_dir = tmpdir.path().join("top");
+    ensure(top_dir.join("bin/other"), "other");
+    let top = commit_layer(&tmprepo, &top_dir, &["data.txt"]).await;
+
+    let stack = graph::Stack::from_iter([bottom.digest().unwrap(), top.digest().unwrap()]);
+    let layout = tmpdir.path().join("layout");
+    let descriptor = OciExporter::new(&tmprepo)
+        .with_ref_name(Some("latest".into()))
+        .export_layout(&stack, &layout)
+        .await
+        .expect("should export the stack");
+
+    let index: ImageIndex = read_json(&layout.join("index.json")).unwrap();
+    assert_eq!(index.manifests, vec![descriptor.clone()]);
+    assert_eq!(
+        descriptor.annotations.get(ANNOTATION_REF_NAME),
+        Some(&"latest".to_string())
+    );
+
+    let manifest: ImageManifest =
+        read_json(&blob_path(&layout, &descriptor.digest).unwrap()).unwrap();
+    assert_eq!(
+        manifest.layers.len(),
+        2,
+        "should create one oci layer per spfs layer"
+    );
+    assert_eq!(
+        list_layer(&layout, &manifest.layers[0].digest),
+        vec!["spfs", "spfs/bin", "spfs/bin/tool", "spfs/data.txt"]
+    );
+    assert_eq!(
+        list_layer(&layout, &manifest.layers[1].digest),
+        vec!["spfs", "spfs/bin", "spfs/bin/other", "spfs/.wh.data.txt"],
+        "masked entries should be written as whiteouts"
+    );
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_export_layout_reuses_layers(#[future] tmprepo: TempRepo, tmpdir: tempfile::TempDir) {
+    init_logging();
+    let tmprepo = tmprepo.await;
+
+    let shared_dir = tmpdir.path().join("shared");
+    ensure(shared_dir.join("lib/shared.so"), "shared");
+    let shared = commit_layer(&tmprepo, &shared_dir, &[]).await;
+    let app_dir = tmpdir.path().join("app");
+    ensure(app_dir.join("bin/app"), "app");
+    let app = commit_layer(&tmprepo, &app_dir, &[]).await;
+
+    let layout = tmpdir.path().join("layout");
+    let first = OciExporter::new(&tmprepo)
+        .with_ref_name(Some("first".into()))
+        .export_layout(&graph::Stack::from(shared.digest().unwrap()), &layout)
+        .await
+        .unwrap();
+    let second = OciExporter::new(&tmprepo)
+        .with_ref_name(Some("second".into()))
+        .export_layout(
+            &graph::Stack::from_iter([shared.digest().unwrap(), app.digest().unwrap()]),
+            &layout,
+        )
+        .await
+        .unwrap();
+
+    let first: ImageManifest = read_json(&blob_path(&layout, &first.digest).unwrap()).unwrap();
+    let second: ImageManifest = read_json(&blob_path(&layout, &second.digest).unwrap()).unwrap();
+    assert_eq!(
+        first.layers[0], second.layers[0],
+        "the same spfs layer should always produce the same oci layer"
+    );
+
+    let index: ImageIndex = read_json(&layout.join("index.json")).unwrap();
+    assert_eq!(
+        index.manifests.len(),
+        2,
+        "both images should be in the layout"
+    );
+}
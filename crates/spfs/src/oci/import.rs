@@ -0,0 +1,547 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use std::io::{BufRead, Read};
+use std::path::{Component, Path, PathBuf};
+
+use relative_path::RelativePathBuf;
+use serde::Deserialize;
+
+use super::{
+    blob_path,
+    default_architecture,
+    read_json,
+    Descriptor,
+    ImageIndex,
+    ImageManifest,
+    ANNOTATION_REF_NAME,
+    DOCKER_MANIFEST_FILE,
+    INDEX_FILE,
+    MEDIA_TYPE_DOCKER_MANIFEST_LIST,
+    MEDIA_TYPE_IMAGE_INDEX,
+    WHITEOUT_OPAQUE,
+    WHITEOUT_PREFIX,
+};
+use crate::prelude::*;
+use crate::{graph, storage, tracking, Error, Result};
+
+#[cfg(test)]
+#[path = "./import_test.rs"]
+mod import_test;
+
+const MODE_DIR: u32 = 0o040000;
+const MODE_FILE: u32 = 0o100000;
+const MODE_SYMLINK: u32 = 0o120000;
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// Creates spfs platforms from OCI and docker images.
+///
+/// Each layer of the image becomes one spfs layer, with whiteouts
+/// in the image represented as masked entries in the layer.
+pub struct OciImporter<'repo> {
+    repo: &'repo storage::RepositoryHandle,
+    prefix: RelativePathBuf,
+    image_name: Option<String>,
+}
+
+impl<'repo> OciImporter<'repo> {
+    /// Create an importer that writes layer data into the given repository
+    pub fn new(repo: &'repo storage::RepositoryHandle) -> Self {
+        Self {
+            repo,
+            prefix: RelativePathBuf::new(),
+            image_name: None,
+        }
+    }
+
+    /// Only import the contents of this directory within the image.
+    ///
+    /// The default is to import the entire filesystem of the image.
+    pub fn with_prefix<P: Into<RelativePathBuf>>(mut self, prefix: P) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    /// The name of the image to import, when there are many to choose from.
+    ///
+    /// This is matched against the ref name of images in an OCI layout
+    /// and the repo tags of images in a docker archive. The default is
+    /// to import the first image.
+    pub fn with_image_name(mut self, image_name: Option<String>) -> Self {
+        self.image_name = image_name;
+        self
+    }
+
+    /// Import an image from an OCI image layout directory or from
+    /// a tar archive of either an OCI layout or a `docker save`.
+    pub async fn import(&self, path: &Path) -> Result<graph::Platform> {
+        if path.is_dir() {
+            return self.import_layout(path).await;
+        }
+        let working_dir = tempfile::Builder::new()
+            .prefix("spfs-oci-")
+            .tempdir()
+            .map_err(|err| Error::String(format!("Failed to create oci working dir: {err}")))?;
+        let archive = path.to_owned();
+        let target = working_dir.path().to_owned();
+        tokio::task::spawn_blocking(move || {
+            let file = std::fs::File::open(&archive)
+                .map_err(|err| Error::StorageReadError("open oci archive", archive.clone(), err))?;
+            tar::Archive::new(std::io::BufReader::new(file))
+                .unpack(&target)
+                .map_err(|err| Error::StorageReadError("unpack oci archive", archive, err))
+        })
+        .await
+        .map_err(|err| Error::String(format!("Failed to unpack oci archive: {err}")))??;
+        self.import_layout(working_dir.path()).await
+    }
+
+    /// Import an image from an unpacked OCI image layout or `docker save`.
+    pub async fn import_layout(&self, layout: &Path) -> Result<graph::Platform> {
+        let layer_files = if layout.join(INDEX_FILE).exists() {
+            self.find_oci_layers(layout)?
+        } else if layout.join(DOCKER_MANIFEST_FILE).exists() {
+            self.find_docker_layers(layout)?
+        } else {
+            return Err(Error::String(format!(
+                "Not an OCI image layout or docker archive: {}",
+                layout.display()
+            )));
+        };
+
+        let prefix = self
+            .prefix
+            .components()
+            .map(|c| c.as_str().to_string())
+            .filter(|c| !c.is_empty() && c != ".")
+            .collect::<Vec<_>>();
+        let mut merged = tracking::Manifest::default();
+        let mut stack = graph::Stack::default();
+        for layer_file in layer_files {
+            tracing::debug!(layer = %layer_file.display(), "importing layer");
+            let prefix = prefix.clone();
+            let pending = tokio::task::spawn_blocking(move || read_layer(&layer_file, &prefix))
+                .await
+                .map_err(|err| Error::String(format!("Failed to read oci layer: {err}")))??;
+            let manifest = self.commit_layer(pending, &merged).await?;
+            if manifest.is_empty() {
+                // many images include layers that only change
+                // metadata, which are not useful as spfs layers
+                tracing::debug!("skipping empty layer");
+                continue;
+            }
+            let layer = self.repo.create_layer_from_manifest(&manifest).await?;
+            stack.push(layer.digest()?);
+            merged.update(&manifest);
+        }
+        self.repo.create_platform(stack).await
+    }
+
+    /// Identify the layer files of the selected image in an OCI layout
+    fn find_oci_layers(&self, layout: &Path) -> Result<Vec<PathBuf>> {
+        let index: ImageIndex = read_json(&layout.join(INDEX_FILE))?;
+        let descriptor = match &self.image_name {
+            Some(name) => index
+                .manifests
+                .iter()
+                .find(|m| m.annotations.get(ANNOTATION_REF_NAME) == Some(name))
+                .ok_or_else(|| Error::String(format!("No image named '{name}' in OCI layout")))?,
+            None => index
+                .manifests
+                .first()
+                .ok_or_else(|| Error::String("No images in OCI layout".to_string()))?,
+        };
+        let manifest = read_image_manifest(layout, descriptor)?;
+        manifest
+            .layers
+            .iter()
+            .map(|layer| blob_path(layout, &layer.digest))
+            .collect()
+    }
+
+    /// Identify the layer files of the selected image in a `docker save`
+    fn find_docker_layers(&self, layout: &Path) -> Result<Vec<PathBuf>> {
+        let images: Vec<DockerImage> = read_json(&layout.join(DOCKER_MANIFEST_FILE))?;
+        let image = match &self.image_name {
+            Some(name) => images
+                .iter()
+                .find(|i| i.repo_tags.contains(name))
+                .ok_or_else(|| Error::String(format!("No image named '{name}' in archive")))?,
+            None => images
+                .first()
+                .ok_or_else(|| Error::String("No images in docker archive".to_string()))?,
+        };
+        image
+            .layers
+            .iter()
+            .map(|layer| {
+                let path = Path::new(layer);
+                if path
+                    .components()
+                    .any(|c| !matches!(c, Component::Normal(_)))
+                {
+                    return Err(Error::String(format!("Invalid docker layer path: {layer}")));
+                }
+                Ok(layout.join(path))
+            })
+            .collect()
+    }
+
+    /// Commit the contents of a layer that was read from an image,
+    /// returning the completed manifest.
+    ///
+    /// The merged manifest of all lower layers is used to mask
+    /// the entries hidden by any opaque directories in this layer.
+    async fn commit_layer(
+        &self,
+        pending: PendingLayer,
+        lower: &tracking::Manifest,
+    ) -> Result<tracking::Manifest> {
+        let PendingLayer {
+            manifest,
+            blobs,
+            opaque_dirs,
+            spool: _spool,
+        } = pending;
+
+        let mut digests = Vec::with_capacity(blobs.len());
+        for blob in blobs {
+            let digest = match blob {
+                PendingBlob::File(path, mode) => {
+                    let file = tokio::fs::File::open(&path).await.map_err(|err| {
+                        Error::StorageReadError("open spooled layer file", path.clone(), err)
+                    })?;
+                    let reader = tokio::io::BufReader::new(file).with_permissions(mode);
+                    self.repo.commit_blob(Box::pin(reader)).await?
+                }
+                PendingBlob::Link(target) => {
+                    let reader = tokio::io::BufReader::new(std::io::Cursor::new(target));
+                    self.repo.commit_blob(Box::pin(reader)).await?
+                }
+            };
+            digests.push(digest);
+        }
+
+        let mut root = resolve_objects(manifest.take_root(), &digests);
+        for parents in opaque_dirs {
+            let Some(lower_dir) = get_entry(lower.root(), &parents) else {
+                continue;
+            };
+            let dir = dir_mut(&mut root, &parents);
+            for (name, entry) in lower_dir.entries.iter() {
+                if entry.kind.is_mask() || dir.entries.contains_key(name) {
+                    continue;
+                }
+                dir.entries.insert(name.clone(), tracking::Entry::mask());
+            }
+        }
+        Ok(tracking::Manifest::new(root))
+    }
+}
+
+/// An image listed in the manifest of a `docker save` archive
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct DockerImage {
+    #[serde(default)]
+    repo_tags: Vec<String>,
+    layers: Vec<String>,
+}
+
+/// Content that must be committed to the repository
+/// before an imported entry can be saved
+enum PendingBlob {
+    /// A regular file that was spooled to disk, and its mode
+    File(PathBuf, u32),
+    /// The target of a symlink
+    Link(Vec<u8>),
+}
+
+/// A layer that was read from an image but not yet committed.
+///
+/// Each entry with a payload holds the index of its blob.
+struct PendingLayer {
+    manifest: tracking::Manifest<Option<usize>>,
+    blobs: Vec<PendingBlob>,
+    /// Directories in this layer that hide the contents of lower layers
+    opaque_dirs: Vec<Vec<String>>,
+    /// Holds the spooled file contents until they are committed
+    spool: tempfile::TempDir,
+}
+
+/// Read the manifest of an image, selecting the image
+/// for the current platform from any nested index
+fn read_image_manifest(layout: &Path, descriptor: &Descriptor) -> Result<ImageManifest> {
+    let path = blob_path(layout, &descriptor.digest)?;
+    let is_index = [MEDIA_TYPE_IMAGE_INDEX, MEDIA_TYPE_DOCKER_MANIFEST_LIST]
+        .contains(&descriptor.media_type.as_str());
+    if !is_index {
+        return read_json(&path);
+    }
+    let index: ImageIndex = read_json(&path)?;
+    let architecture = default_architecture();
+    let selected = index
+        .manifests
+        .iter()
+        .find(|m| {
+            m.platform
+                .as_ref()
+                .map(|p| p.os == "linux" && p.architecture == architecture)
+                .unwrap_or(false)
+        })
+        .ok_or_else(|| {
+            Error::String(format!(
+                "No image for linux/{architecture} in index {}",
+                descriptor.digest
+            ))
+        })?;
+    read_image_manifest(layout, selected)
+}
+
+/// Read the contents of a layer file into a new manifest,
+/// keeping only the entries under the given prefix
+fn read_layer(path: &Path, prefix: &[String]) -> Result<PendingLayer> {
+    let read_err = |err| Error::StorageReadError("read oci layer", path.to_owned(), err);
+    let spool = tempfile::Builder::new()
+        .prefix("spfs-oci-layer-")
+        .tempdir()
+        .map_err(|err| Error::String(format!("Failed to create layer spool dir: {err}")))?;
+    let mut root = tracking::Entry::empty_dir_with_open_perms_with_data(None);
+    let mut blobs = Vec::new();
+    let mut opaque_dirs = Vec::new();
+
+    let mut archive = tar::Archive::new(open_layer(path)?);
+    for entry in archive.entries().map_err(read_err)? {
+        let mut entry = entry.map_err(read_err)?;
+        let entry_path = entry.path().map_err(read_err)?.into_owned();
+        let Some(components) = split_path(&entry_path, prefix)? else {
+            continue;
+        };
+        let Some((name, parents)) = components.split_last() else {
+            // the root of the layer, or the prefix itself
+            continue;
+        };
+
+        if name == WHITEOUT_OPAQUE {
+            opaque_dirs.push(parents.to_vec());
+            continue;
+        }
+        if let Some(masked) = name.strip_prefix(WHITEOUT_PREFIX) {
+            dir_mut(&mut root, parents)
+                .entries
+                .insert(masked.to_string(), tracking::Entry::mask_with_data(None));
+            continue;
+        }
+
+        let perms = entry.header().mode().map_err(read_err)? & 0o7777;
+        let mut xattrs = tracking::Xattrs::new();
+        if let Some(extensions) = entry.pax_extensions().map_err(read_err)? {
+            for extension in extensions {
+                let extension = extension.map_err(read_err)?;
+                if let Some(name) = extension
+                    .key()
+                    .ok()
+                    .and_then(|key| key.strip_prefix("SCHILY.xattr."))
+                {
+                    xattrs.insert(name.to_string(), extension.value_bytes().to_vec());
+                }
+            }
+        }
+
+        let mut node = match entry.header().entry_type() {
+            tar::EntryType::Directory => {
+                let dir = dir_mut(&mut root, &components);
+                dir.mode = MODE_DIR | perms;
+                dir.xattrs = xattrs;
+                continue;
+            }
+            tar::EntryType::Regular | tar::EntryType::Continuous => {
+                let spool_path = spool.path().join(blobs.len().to_string());
+                let mut file = std::fs::File::create(&spool_path).map_err(|err| {
+                    Error::StorageWriteError("spool layer file", spool_path.clone(), err)
+                })?;
+                let size = std::io::copy(&mut entry, &mut file).map_err(read_err)?;
+                let mode = MODE_FILE | perms;
+                blobs.push(PendingBlob::File(spool_path, mode));
+                let mut node =
+                    tracking::Entry::empty_file_with_open_perms_with_data(Some(blobs.len() - 1));
+                node.kind = tracking::EntryKind::Blob(size);
+                node.mode = mode;
+                node
+            }
+            tar::EntryType::Symlink => {
+                let target = entry
+                    .link_name_bytes()
+                    .map(|t| t.into_owned())
+                    .unwrap_or_default();
+                let size = target.len() as u64;
+                blobs.push(PendingBlob::Link(target));
+                let mut node = tracking::Entry::empty_symlink_with_data(Some(blobs.len() - 1));
+                node.kind = tracking::EntryKind::Blob(size);
+                node.mode = MODE_SYMLINK | perms;
+                node
+            }
+            tar::EntryType::Link => {
+                let target = entry
+                    .link_name()
+                    .map_err(read_err)?
+                    .map(|t| t.into_owned())
+                    .unwrap_or_default();
+                let existing = split_path(&target, prefix)?
+                    .and_then(|target| get_entry(&root, &target).cloned());
+                match existing {
+                    Some(existing) if !existing.kind.is_tree() => existing,
+                    _ => {
+                        tracing::warn!(
+                            "skipping hard link to an entry outside of the layer: {}",
+                            entry_path.display()
+                        );
+                        continue;
+                    }
+                }
+            }
+            kind => {
+                tracing::warn!(
+                    "skipping unsupported {kind:?} entry: {}",
+                    entry_path.display()
+                );
+                continue;
+            }
+        };
+        if !xattrs.is_empty() {
+            node.xattrs = xattrs;
+        }
+        dir_mut(&mut root, parents)
+            .entries
+            .insert(name.clone(), node);
+    }
+
+    Ok(PendingLayer {
+        manifest: tracking::Manifest::new(root),
+        blobs,
+        opaque_dirs,
+        spool,
+    })
+}
+
+/// Open a layer file, decompressing it if needed
+fn open_layer(path: &Path) -> Result<Box<dyn Read + Send>> {
+    let file = std::fs::File::open(path)
+        .map_err(|err| Error::StorageReadError("open oci layer", path.to_owned(), err))?;
+    let mut reader = std::io::BufReader::new(file);
+    let magic = reader
+        .fill_buf()
+        .map_err(|err| Error::StorageReadError("read oci layer", path.to_owned(), err))?;
+    if magic.starts_with(GZIP_MAGIC) {
+        return Ok(Box::new(flate2::bufread::MultiGzDecoder::new(reader)));
+    }
+    if magic.starts_with(ZSTD_MAGIC) {
+        return Err(Error::String(format!(
+            "Zstd compressed OCI layers are not supported: {}",
+            path.display()
+        )));
+    }
+    Ok(Box::new(reader))
+}
+
+/// Split a path from a layer into its components, returning
+/// the components under the given prefix, or None if the path
+/// is not within the prefix
+fn split_path(path: &Path, prefix: &[String]) -> Result<Option<Vec<String>>> {
+    let mut components = Vec::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(name) => {
+                let Some(name) = name.to_str() else {
+                    return Err(Error::String(format!(
+                        "OCI layer paths must be valid utf-8: {}",
+                        path.display()
+                    )));
+                };
+                components.push(name.to_string());
+            }
+            Component::RootDir | Component::CurDir => continue,
+            Component::ParentDir | Component::Prefix(_) => {
+                return Err(Error::String(format!(
+                    "Invalid path in OCI layer: {}",
+                    path.display()
+                )));
+            }
+        }
+    }
+    if !components.starts_with(prefix) {
+        return Ok(None);
+    }
+    Ok(Some(components.split_off(prefix.len())))
+}
+
+/// Find the directory at the given path, creating
+/// it and any missing parents as needed.
+///
+/// Unlike [`tracking::Manifest::mkdirs`], names are
+/// used as-is, so that dotfiles are preserved.
+fn dir_mut<'e, T: Default>(
+    root: &'e mut tracking::Entry<T>,
+    path: &[String],
+) -> &'e mut tracking::Entry<T> {
+    let mut dir = root;
+    for name in path {
+        let child = dir
+            .entries
+            .entry(name.clone())
+            .or_insert_with(|| new_dir(0o755));
+        if !child.kind.is_tree() {
+            *child = new_dir(0o755);
+        }
+        dir = child;
+    }
+    dir
+}
+
+/// Find the entry at the given path, if it exists
+fn get_entry<'e, T>(
+    root: &'e tracking::Entry<T>,
+    path: &[String],
+) -> Option<&'e tracking::Entry<T>> {
+    let mut entry = root;
+    for name in path {
+        entry = entry.entries.get(name)?;
+    }
+    Some(entry)
+}
+
+fn new_dir<T: Default>(perms: u32) -> tracking::Entry<T> {
+    let mut dir = tracking::Entry::empty_dir_with_open_perms();
+    dir.mode = MODE_DIR | perms;
+    dir
+}
+
+/// Fill in the committed payload of each entry from its pending blob
+fn resolve_objects(
+    entry: tracking::Entry<Option<usize>>,
+    digests: &[crate::encoding::Digest],
+) -> tracking::Entry {
+    let tracking::Entry {
+        kind,
+        object,
+        mode,
+        entries,
+        user_data,
+        legacy_size,
+        xattrs,
+    } = entry;
+    tracking::Entry {
+        kind,
+        object: user_data.map(|i| digests[i]).unwrap_or(object),
+        mode,
+        entries: entries
+            .into_iter()
+            .map(|(name, child)| (name, resolve_objects(child, digests)))
+            .collect(),
+        user_data: (),
+        legacy_size,
+        xattrs,
+    }
+}
@@ -0,0 +1,173 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use std::io::Write;
+
+use rstest::rstest;
+
+use super::OciImporter;
+use crate::fixtures::*;
+use crate::graph;
+use crate::oci::OciExporter;
+use crate::prelude::*;
+use crate::tracking;
+
+/// Load the manifest of each layer in a platform, from bottom to top
+async fn read_layers(repo: &TempRepo, platform: &graph::Platform) -> Vec<tracking::Manifest> {
+    let layers = crate::resolve_stack_to_layers(&platform.to_stack(), Some(repo))
+        .await
+        .unwrap();
+    let mut manifests = Vec::new();
+    for layer in layers {
+        let digest = layer
+            .manifest()
+            .expect("imported layers should have a manifest");
+        manifests.push(
+            repo.read_manifest(*digest)
+                .await
+                .unwrap()
+                .to_tracking_manifest(),
+        );
+    }
+    manifests
+}
+
+/// Create a tar file with the given regular files and entries
+fn write_layer_tar<W: Write>(writer: W, build: impl FnOnce(&mut tar::Builder<W>)) {
+    let mut builder = tar::Builder::new(writer);
+    build(&mut builder);
+    builder.into_inner().unwrap().flush().unwrap();
+}
+
+fn append_file<W: Write>(builder: &mut tar::Builder<W>, path: &str, data: &str) {
+    let mut header = tar::Header::new_gnu();
+    header.set_entry_type(tar::EntryType::Regular);
+    header.set_mode(0o644);
+    header.set_size(data.len() as u64);
+    builder
+        .append_data(&mut header, path, data.as_bytes())
+        .unwrap();
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_import_exported_layout(#[future] tmprepo: TempRepo, tmpdir: tempfile::TempDir) {
+    init_logging();
+    let tmprepo = tmprepo.await;
+
+    let bottom_dir = tmpdir.path().join("bottom");
+    ensure(bottom_dir.join("bin/tool"), "tool");
+    ensure(bottom_dir.join("data.txt"), "data");
+    ensure(bottom_dir.join(".hidden"), "hidden");
+    std::os::unix::fs::symlink("tool", bottom_dir.join("bin/link")).unwrap();
+    let bottom = crate::Committer::new(&tmprepo)
+        .commit_dir(&bottom_dir)
+        .await
+        .unwrap();
+    let bottom = tmprepo.create_layer_from_manifest(&bottom).await.unwrap();
+    let mut top = tracking::Manifest::default();
+    top.mknod("data.txt", tracking::Entry::mask()).unwrap();
+    let top = tmprepo.create_layer_from_manifest(&top).await.unwrap();
+
+    let layout = tmpdir.path().join("layout");
+    OciExporter::new(&tmprepo)
+        .export_layout(
+            &graph::Stack::from_iter([bottom.digest().unwrap(), top.digest().unwrap()]),
+            &layout,
+        )
+        .await
+        .unwrap();
+
+    let platform = OciImporter::new(&tmprepo)
+        .with_prefix(crate::oci::DEFAULT_PREFIX)
+        .import(&layout)
+        .await
+        .expect("should import the exported image");
+    assert_eq!(
+        platform.to_stack(),
+        graph::Stack::from_iter([bottom.digest().unwrap(), top.digest().unwrap()]),
+        "importing an exported image should reproduce the original layers"
+    );
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_import_docker_archive(#[future] tmprepo: TempRepo, tmpdir: tempfile::TempDir) {
+    init_logging();
+    let tmprepo = tmprepo.await;
+
+    let archive_dir = tmpdir.path().join("archive");
+    std::fs::create_dir_all(archive_dir.join("bottom")).unwrap();
+    std::fs::create_dir_all(archive_dir.join("top")).unwrap();
+    let bottom = std::fs::File::create(archive_dir.join("bottom/layer.tar")).unwrap();
+    write_layer_tar(
+        flate2::write::GzEncoder::new(bottom, flate2::Compression::default()),
+        |builder| {
+            append_file(builder, "etc/config", "config");
+            append_file(builder, "etc/old/removed", "removed");
+            append_file(builder, "usr/bin/tool", "tool");
+        },
+    );
+    let top = std::fs::File::create(archive_dir.join("top/layer.tar")).unwrap();
+    write_layer_tar(top, |builder| {
+        append_file(builder, "etc/.wh..wh..opq", "");
+        append_file(builder, "etc/new", "new");
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Link);
+        header.set_size(0);
+        builder
+            .append_link(&mut header, "usr/bin/alias", "etc/new")
+            .unwrap();
+    });
+    let docker_manifest = serde_json::json!([{
+        "Config": "config.json",
+        "RepoTags": ["vendor/base:latest"],
+        "Layers": ["bottom/layer.tar", "top/layer.tar"],
+    }]);
+    std::fs::write(
+        archive_dir.join("manifest.json"),
+        docker_manifest.to_string(),
+    )
+    .unwrap();
+
+    let platform = OciImporter::new(&tmprepo)
+        .with_image_name(Some("vendor/base:latest".into()))
+        .import(&archive_dir)
+        .await
+        .expect("should import the docker archive");
+    let layers = read_layers(&tmprepo, &platform).await;
+    assert_eq!(layers.len(), 2);
+
+    let top = &layers[1];
+    assert!(
+        top.get_path("etc/config").unwrap().kind.is_mask(),
+        "an opaque directory should mask the lower contents"
+    );
+    assert!(top.get_path("etc/old").unwrap().kind.is_mask());
+    let new = top.get_path("etc/new").unwrap();
+    assert!(new.kind.is_blob());
+    assert_eq!(
+        top.get_path("usr/bin/alias").unwrap().object,
+        new.object,
+        "hard links should share the payload of their target"
+    );
+    assert!(
+        top.get_path("usr/bin/tool").is_none(),
+        "entries outside of the opaque directory are not masked"
+    );
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_import_missing_image(#[future] tmprepo: TempRepo, tmpdir: tempfile::TempDir) {
+    init_logging();
+    let tmprepo = tmprepo.await;
+
+    std::fs::write(tmpdir.path().join("manifest.json"), "[]").unwrap();
+    let result = OciImporter::new(&tmprepo)
+        .with_image_name(Some("missing".into()))
+        .import(tmpdir.path())
+        .await;
+    assert!(result.is_err(), "should fail for an unknown image name");
+}
@@ -0,0 +1,173 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+//! Conversion between spfs layers and OCI container images.
+//!
+//! Each spfs layer is represented by a single OCI layer, so that layers
+//! which are shared between spfs environments are also shared between
+//! the images that are created from them.
+
+mod export;
+mod import;
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+pub use export::OciExporter;
+pub use import::OciImporter;
+
+use crate::{Error, Result};
+
+/// The media type of an OCI image index
+pub const MEDIA_TYPE_IMAGE_INDEX: &str = "application/vnd.oci.image.index.v1+json";
+/// The media type of an OCI image manifest
+pub const MEDIA_TYPE_IMAGE_MANIFEST: &str = "application/vnd.oci.image.manifest.v1+json";
+/// The media type of an OCI image configuration
+pub const MEDIA_TYPE_IMAGE_CONFIG: &str = "application/vnd.oci.image.config.v1+json";
+/// The media type of an uncompressed OCI layer
+pub const MEDIA_TYPE_IMAGE_LAYER: &str = "application/vnd.oci.image.layer.v1.tar";
+/// The media type of a docker image index, which
+/// is otherwise compatible with [`MEDIA_TYPE_IMAGE_INDEX`]
+pub const MEDIA_TYPE_DOCKER_MANIFEST_LIST: &str =
+    "application/vnd.docker.distribution.manifest.list.v2+json";
+
+/// The annotation that holds the name of an image in an index
+pub const ANNOTATION_REF_NAME: &str = "org.opencontainers.image.ref.name";
+/// The annotation that identifies the spfs layer that an OCI layer
+/// was created from
+pub const ANNOTATION_SPFS_LAYER: &str = "io.spkenv.spfs.layer";
+
+/// The file name prefix that marks a removed entry in an OCI layer
+pub const WHITEOUT_PREFIX: &str = ".wh.";
+/// The file name that marks a directory as replacing
+/// all of the contents of lower layers in an OCI layer
+pub const WHITEOUT_OPAQUE: &str = ".wh..wh..opq";
+
+/// The directory, relative to the root of the image, where
+/// spfs layers are placed by default
+pub const DEFAULT_PREFIX: &str = "spfs";
+
+const OCI_LAYOUT_FILE: &str = "oci-layout";
+const OCI_LAYOUT_VERSION: &str = "1.0.0";
+const INDEX_FILE: &str = "index.json";
+const DOCKER_MANIFEST_FILE: &str = "manifest.json";
+const BLOBS_DIR: &str = "blobs";
+const SHA256: &str = "sha256";
+
+/// Identifies a piece of content in an OCI image layout
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Descriptor {
+    pub media_type: String,
+    pub digest: String,
+    pub size: u64,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub annotations: BTreeMap<String, String>,
+    /// The platform of the image that this descriptor identifies,
+    /// for descriptors within an index
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub platform: Option<ImagePlatform>,
+}
+
+/// The operating system and architecture that an image runs on
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImagePlatform {
+    pub architecture: String,
+    pub os: String,
+}
+
+/// The entry point of an OCI image layout, listing the images within it
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageIndex {
+    pub schema_version: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub media_type: Option<String>,
+    #[serde(default)]
+    pub manifests: Vec<Descriptor>,
+}
+
+impl Default for ImageIndex {
+    fn default() -> Self {
+        Self {
+            schema_version: 2,
+            media_type: Some(MEDIA_TYPE_IMAGE_INDEX.to_string()),
+            manifests: Vec::new(),
+        }
+    }
+}
+
+/// Describes the configuration and layers of a single image
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageManifest {
+    pub schema_version: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub media_type: Option<String>,
+    pub config: Descriptor,
+    pub layers: Vec<Descriptor>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub annotations: BTreeMap<String, String>,
+}
+
+/// The runtime configuration and filesystem history of an image
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImageConfig {
+    pub architecture: String,
+    pub os: String,
+    pub rootfs: RootFs,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub history: Vec<History>,
+}
+
+/// The uncompressed digests of each layer in an image, from bottom to top
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RootFs {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub diff_ids: Vec<String>,
+}
+
+/// Describes how a single layer of an image was created
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct History {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_by: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+/// The name of the OCI architecture for the current machine
+pub fn default_architecture() -> &'static str {
+    match std::env::consts::ARCH {
+        "x86_64" => "amd64",
+        "aarch64" => "arm64",
+        "powerpc64" => "ppc64le",
+        arch => arch,
+    }
+}
+
+/// The path of a blob within an OCI image layout
+pub(crate) fn blob_path(layout: &Path, digest: &str) -> Result<std::path::PathBuf> {
+    let Some((algorithm, encoded)) = digest.split_once(':') else {
+        return Err(Error::String(format!("Invalid OCI digest: {digest}")));
+    };
+    if algorithm.contains(['/', '.']) || encoded.contains(['/', '.']) {
+        return Err(Error::String(format!("Invalid OCI digest: {digest}")));
+    }
+    Ok(layout.join(BLOBS_DIR).join(algorithm).join(encoded))
+}
+
+/// Read and parse a json document from an OCI image layout
+pub(crate) fn read_json<T>(path: &Path) -> Result<T>
+where
+    T: serde::de::DeserializeOwned,
+{
+    let data = std::fs::read(path)
+        .map_err(|err| Error::StorageReadError("read oci json", path.to_owned(), err))?;
+    serde_json::from_slice(&data)
+        .map_err(|err| Error::String(format!("Invalid OCI json {}: {err}", path.display())))
+}
@@ -99,6 +99,42 @@ impl From<&tracking::Tag> for super::Tag {
     }
 }
 
+impl TryFrom<super::TagChange> for storage::TagChange {
+    type Error = Error;
+    fn try_from(source: super::TagChange) -> Result<Self> {
+        let kind = match super::tag_change::Kind::try_from(source.kind) {
+            Ok(super::tag_change::Kind::Insert) => storage::TagChangeKind::Insert,
+            Ok(super::tag_change::Kind::Remove) => storage::TagChangeKind::Remove,
+            Ok(super::tag_change::Kind::RemoveStream) => storage::TagChangeKind::RemoveStream,
+            Err(_) => return Err("Received unknown tag change kind in rpc data".into()),
+        };
+        Ok(Self {
+            cursor: source.cursor,
+            time: convert_to_datetime(source.time)?,
+            kind,
+            spec: tracking::TagSpec::parse(&source.tag_spec)?,
+            tag: source.tag.map(TryInto::try_into).transpose()?,
+        })
+    }
+}
+
+impl From<&storage::TagChange> for super::TagChange {
+    fn from(source: &storage::TagChange) -> Self {
+        let kind = match source.kind {
+            storage::TagChangeKind::Insert => super::tag_change::Kind::Insert,
+            storage::TagChangeKind::Remove => super::tag_change::Kind::Remove,
+            storage::TagChangeKind::RemoveStream => super::tag_change::Kind::RemoveStream,
+        };
+        Self {
+            cursor: source.cursor,
+            time: Some(convert_from_datetime(&source.time)),
+            kind: kind as i32,
+            tag_spec: source.spec.to_string(),
+            tag: source.tag.as_ref().map(Into::into),
+        }
+    }
+}
+
 impl From<Error> for super::Error {
     fn from(err: Error) -> Self {
         let kind = Some(match err {
@@ -306,6 +342,26 @@ impl TryFrom<super::Manifest> for graph::Manifest {
                         Err(_) => return Err("Received unknown entry kind in rpc data".into()),
                     };
                     let name = builder.create_string(&entry.name);
+                    let xattrs = if entry.xattrs.is_empty() {
+                        None
+                    } else {
+                        let xattrs = entry
+                            .xattrs
+                            .iter()
+                            .map(|xattr| {
+                                let name = builder.create_string(&xattr.name);
+                                let value = builder.create_vector(xattr.value.as_slice());
+                                spfs_proto::ExtendedAttribute::create(
+                                    &mut builder,
+                                    &spfs_proto::ExtendedAttributeArgs {
+                                        name: Some(name),
+                                        value: Some(value),
+                                    },
+                                )
+                            })
+                            .collect::<Vec<_>>();
+                        Some(builder.create_vector(&xattrs))
+                    };
                     Ok(spfs_proto::Entry::create(
                         &mut builder,
                         &spfs_proto::EntryArgs {
@@ -314,6 +370,7 @@ impl TryFrom<super::Manifest> for graph::Manifest {
                             mode: entry.mode,
                             size_: entry.size,
                             name: Some(name),
+                            xattrs,
                         },
                     ))
                 })
@@ -406,6 +463,13 @@ impl<'buf> From<&graph::Entry<'buf>> for super::Entry {
             mode: source.mode(),
             size: source.size(),
             name: source.name().to_owned(),
+            xattrs: source
+                .xattrs()
+                .map(|(name, value)| super::ExtendedAttribute {
+                    name: name.to_owned(),
+                    value: value.to_vec(),
+                })
+                .collect(),
         }
     }
 }
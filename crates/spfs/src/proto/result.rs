@@ -93,6 +93,11 @@ rpc_result!(
     gen::remove_tag_stream_response::Result
 );
 rpc_result!(gen::RemoveTagResponse, gen::remove_tag_response::Result);
+rpc_result!(
+    gen::WatchTagChangesResponse,
+    gen::watch_tag_changes_response::Result,
+    gen::TagChange
+);
 
 rpc_result!(
     gen::ReadObjectResponse,
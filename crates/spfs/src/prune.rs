@@ -4,14 +4,15 @@
 
 use chrono::prelude::*;
 
-use crate::tracking;
+use crate::storage::{TagNamespace, TagNamespaceBuf};
+use crate::{config, tracking, Error, Result};
 
 #[cfg(test)]
 #[path = "./prune_test.rs"]
 mod prune_test;
 
 /// Specifies a range of conditions for pruning tags out of a repository.
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub(crate) struct PruneParameters {
     pub prune_if_older_than: Option<DateTime<Utc>>,
     pub keep_if_newer_than: Option<DateTime<Utc>>,
@@ -58,3 +59,113 @@ impl PruneParameters {
         false
     }
 }
+
+/// Conditions for pruning only the tags that match a pattern
+#[derive(Debug, Clone)]
+pub struct RetentionPolicy {
+    namespace: Option<TagNamespaceBuf>,
+    pattern: glob::Pattern,
+    never_prune: bool,
+    params: PruneParameters,
+}
+
+impl RetentionPolicy {
+    /// Load a policy from its configuration, where
+    /// any ages are relative to the current time
+    pub fn from_config(config: &config::TagRetention) -> Result<Self> {
+        let now = Utc::now();
+        let cutoff = |age: &Option<String>| -> Result<Option<DateTime<Utc>>> {
+            let Some(age) = age else {
+                return Ok(None);
+            };
+            let duration = tracking::parse_duration(age)?;
+            let duration = chrono::Duration::from_std(duration).map_err(|err| {
+                Error::String(format!("Invalid age in retention policy '{age}': {err}"))
+            })?;
+            Ok(Some(now - duration))
+        };
+        let pattern = config.tags.as_deref().unwrap_or("**");
+        let pattern = glob::Pattern::new(pattern).map_err(|err| {
+            Error::String(format!(
+                "Invalid tag pattern in retention policy '{pattern}': {err}"
+            ))
+        })?;
+        Ok(Self {
+            namespace: config.namespace.clone(),
+            pattern,
+            never_prune: config.never_prune,
+            params: PruneParameters {
+                prune_if_older_than: cutoff(&config.prune_if_older_than)?,
+                keep_if_newer_than: cutoff(&config.keep_if_newer_than)?,
+                prune_if_version_more_than: config.prune_if_version_more_than,
+                keep_if_version_less_than: config.keep_if_version_less_than,
+            },
+        })
+    }
+
+    /// True if this policy applies to the given tag stream
+    ///
+    /// Policies without a namespace apply to tags in any namespace.
+    pub fn matches(&self, namespace: Option<&TagNamespace>, spec: &tracking::TagSpec) -> bool {
+        if let Some(expected) = &self.namespace {
+            let Some(namespace) = namespace else {
+                return false;
+            };
+            if namespace.as_rel_path() != expected.as_rel_path() {
+                return false;
+            }
+        }
+        let options = glob::MatchOptions {
+            require_literal_separator: true,
+            ..Default::default()
+        };
+        self.pattern.matches_with(spec.path().as_str(), options)
+    }
+
+    /// True if the matching tags are never pruned
+    pub fn never_prune(&self) -> bool {
+        self.never_prune
+    }
+
+    pub(crate) fn should_prune(&self, spec: &tracking::TagSpec, tag: &tracking::Tag) -> bool {
+        !self.never_prune && self.params.should_prune(spec, tag)
+    }
+}
+
+impl std::fmt::Display for RetentionPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "tags matching '{}'", self.pattern)?;
+        if let Some(namespace) = &self.namespace {
+            write!(f, " in namespace '{namespace}'")?;
+        }
+        if self.never_prune {
+            return f.write_str(": never prune");
+        }
+        let PruneParameters {
+            prune_if_older_than,
+            keep_if_newer_than,
+            prune_if_version_more_than,
+            keep_if_version_less_than,
+        } = &self.params;
+        let mut conditions = Vec::new();
+        if let Some(dt) = prune_if_older_than {
+            conditions.push(format!("prune if older than {}", dt.with_timezone(&Local)));
+        }
+        if let Some(v) = prune_if_version_more_than {
+            conditions.push(format!("prune if greater than version {v}"));
+        }
+        if let Some(dt) = keep_if_newer_than {
+            conditions.push(format!(
+                "keep if created after {}",
+                dt.with_timezone(&Local)
+            ));
+        }
+        if let Some(v) = keep_if_version_less_than {
+            conditions.push(format!("keep if less than version {v}"));
+        }
+        if conditions.is_empty() {
+            return f.write_str(": keep all");
+        }
+        write!(f, ": {}", conditions.join(", "))
+    }
+}
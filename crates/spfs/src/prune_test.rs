@@ -8,10 +8,11 @@ use chrono::{TimeZone, Utc};
 use rstest::rstest;
 use tokio_stream::StreamExt;
 
+use super::RetentionPolicy;
 use crate::clean::TracingCleanReporter;
 use crate::fixtures::*;
 use crate::prelude::*;
-use crate::{encoding, storage, tracking, Cleaner, Error};
+use crate::{config, encoding, storage, tracking, Cleaner, Error};
 
 #[rstest]
 #[tokio::test]
@@ -192,3 +193,120 @@ async fn test_prune_tags(#[future] tmprepo: TempRepo) {
         panic!("should not have any pruned tag left")
     }
 }
+
+#[rstest]
+#[case(None, "ci/**", None, "ci/build", true)]
+#[case(None, "ci/**", None, "ci/nested/build", true)]
+#[case(None, "ci/*", None, "ci/nested/build", false)]
+#[case(None, "ci/**", None, "release/build", false)]
+#[case(None, "ci/**", Some("team"), "ci/build", true)]
+#[case(Some("team"), "ci/**", Some("team"), "ci/build", true)]
+#[case(Some("team"), "ci/**", None, "ci/build", false)]
+#[case(Some("team/sub"), "**", Some("team/sub"), "build", true)]
+#[case(Some("team"), "**", Some("other"), "build", false)]
+fn test_retention_policy_matches(
+    #[case] namespace: Option<&str>,
+    #[case] tags: &str,
+    #[case] tag_namespace: Option<&str>,
+    #[case] tag: &str,
+    #[case] expected: bool,
+) {
+    let policy = RetentionPolicy::from_config(&config::TagRetention {
+        tags: Some(tags.to_string()),
+        namespace: namespace.map(storage::TagNamespaceBuf::new),
+        ..Default::default()
+    })
+    .unwrap();
+    let tag_namespace = tag_namespace.map(storage::TagNamespaceBuf::new);
+    let tag = tracking::TagSpec::parse(tag).unwrap();
+    assert_eq!(
+        policy.matches(tag_namespace.as_deref(), &tag),
+        expected,
+        "{policy} vs {tag}"
+    );
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_prune_tags_with_retention_policies(#[future] tmprepo: TempRepo) {
+    init_logging();
+    let tmprepo = tmprepo.await;
+
+    let streams = ["ci/build", "release/v1", "spk/pkg/my-pkg", "other/tag"];
+    for stream in streams {
+        let spec = tracking::TagSpec::parse(stream).unwrap();
+        for year in 2020..=2025 {
+            let mut tag = tracking::Tag::new(spec.org(), spec.name(), random_digest()).unwrap();
+            tag.time = Utc.with_ymd_and_hms(year, 1, 1, 0, 0, 0).unwrap();
+            tmprepo.insert_tag(&tag).await.unwrap();
+        }
+    }
+    let team_repo = tmprepo.with_tag_namespace("team").await;
+    let spec = tracking::TagSpec::parse("ci/build").unwrap();
+    for year in 2020..=2025 {
+        let mut tag = tracking::Tag::new(spec.org(), spec.name(), random_digest()).unwrap();
+        tag.time = Utc.with_ymd_and_hms(year, 1, 1, 0, 0, 0).unwrap();
+        team_repo.insert_tag(&tag).await.unwrap();
+    }
+
+    let policies = [
+        config::TagRetention {
+            namespace: Some(storage::TagNamespaceBuf::new("team")),
+            never_prune: true,
+            ..Default::default()
+        },
+        config::TagRetention {
+            tags: Some("ci/**".into()),
+            prune_if_older_than: Some("1d".into()),
+            keep_if_version_less_than: Some(1),
+            ..Default::default()
+        },
+        config::TagRetention {
+            tags: Some("release/**".into()),
+            never_prune: true,
+            ..Default::default()
+        },
+        config::TagRetention {
+            tags: Some("spk/pkg/**".into()),
+            prune_if_version_more_than: Some(2),
+            ..Default::default()
+        },
+    ]
+    .iter()
+    .map(RetentionPolicy::from_config)
+    .collect::<crate::Result<Vec<_>>>()
+    .unwrap();
+    let cleaner = Cleaner::new(&tmprepo)
+        .with_reporter(TracingCleanReporter)
+        .with_retention_policies(policies)
+        .with_prune_repeated_tags(true)
+        .with_prune_tags_older_than(Some(Utc.with_ymd_and_hms(2030, 1, 1, 0, 0, 0).unwrap()));
+    println!("{}", cleaner.format_plan());
+    cleaner.prune_all_tags_and_clean().await.unwrap();
+
+    async fn count(tmprepo: &storage::RepositoryHandle, tag: &str) -> usize {
+        let tag = tracking::TagSpec::parse(tag).unwrap();
+        match tmprepo.read_tag(&tag).await {
+            Ok(stream) => stream.collect::<Vec<_>>().await.len(),
+            Err(Error::UnknownReference(_)) => 0,
+            Err(err) => panic!("{err:?}"),
+        }
+    }
+    assert_eq!(
+        count(&tmprepo, "ci/build").await,
+        1,
+        "should keep the latest"
+    );
+    assert_eq!(count(&tmprepo, "release/v1").await, 6, "should never prune");
+    assert_eq!(count(&tmprepo, "spk/pkg/my-pkg").await, 3);
+    assert_eq!(
+        count(&tmprepo, "other/tag").await,
+        0,
+        "unmatched tags should use the cleaner options"
+    );
+    assert_eq!(
+        count(&team_repo, "ci/build").await,
+        6,
+        "should apply policies by namespace"
+    );
+}
@@ -14,7 +14,7 @@ use super::config::get_config;
 use crate::prelude::*;
 use crate::storage::fallback::FallbackProxy;
 use crate::storage::fs::{ManifestRenderPath, RenderSummary};
-use crate::{graph, runtime, storage, tracking, Error, Result};
+use crate::{encoding, graph, runtime, storage, tracking, Error, Result};
 
 #[cfg(test)]
 #[path = "./resolve_test.rs"]
@@ -346,6 +346,80 @@ where
     Ok(resolved_manifests)
 }
 
+/// Ensure that every layer in the runtime's stack has a signature from
+/// one of the trusted keys, if signatures are required by the spfs config.
+///
+/// Signatures are required for every layer when `signing.require_signatures`
+/// is set, and otherwise only for the layers that came from a remote which
+/// requires them (see [`layer_requires_signature`]). Signatures are accepted
+/// from the local repository or any configured remote.
+pub(crate) async fn ensure_stack_is_signed(runtime: &runtime::Runtime) -> Result<()> {
+    let config = get_config()?;
+    let remote_names = config.list_remote_names();
+    let remote_policies = remote_names
+        .iter()
+        .map(|name| config.remote_requires_signatures(name))
+        .collect::<Vec<_>>();
+    if !config.signing.require_signatures && !remote_policies.contains(&true) {
+        return Ok(());
+    }
+    let trusted_keys = crate::signing::load_trusted_keys(&config.signing)?;
+    // remotes are opened in the same order as their names, so
+    // that each one can be matched with its signing policy
+    let (repo, remotes) = tokio::try_join!(
+        config.get_opened_local_repository(),
+        futures::future::try_join_all(remote_names.iter().map(|name| config.get_remote(name))),
+    )?;
+    let fallback_repo = FallbackProxy::new(repo, remotes);
+
+    let layers = resolve_stack_to_layers_with_repo(&runtime.status.stack, &fallback_repo).await?;
+    for layer in layers.iter() {
+        let digest = layer.digest()?;
+        if !config.signing.require_signatures
+            && !layer_requires_signature(digest, fallback_repo.secondary(), &remote_policies).await
+        {
+            continue;
+        }
+        let mut status =
+            crate::signing::verify_object(&fallback_repo, digest, &trusted_keys).await?;
+        for remote in fallback_repo.secondary() {
+            if status.is_trusted() {
+                break;
+            }
+            let found = crate::signing::verify_object(remote, digest, &trusted_keys).await?;
+            status = status.combine(found);
+        }
+        if !status.is_trusted() {
+            return Err(Error::UntrustedObject { digest, status });
+        }
+    }
+    Ok(())
+}
+
+/// True if a layer came from one of the remotes that require signatures.
+///
+/// A layer is considered to have come from such a remote when it is
+/// only available from remotes that require signatures. Layers that are
+/// available from a remote without that requirement could have come from
+/// there instead, and layers that are not in any remote were created locally.
+async fn layer_requires_signature(
+    digest: encoding::Digest,
+    remotes: &[storage::RepositoryHandle],
+    remote_policies: &[bool],
+) -> bool {
+    let mut requires_signature = false;
+    for (remote, requires) in remotes.iter().zip(remote_policies) {
+        if !remote.has_object(digest).await {
+            continue;
+        }
+        if !*requires {
+            return false;
+        }
+        requires_signature = true;
+    }
+    requires_signature
+}
+
 /// Compile the set of directories to be overlaid for a runtime, and
 /// render them.
 ///
@@ -400,6 +474,8 @@ pub async fn resolve_stack_to_layers<'repo>(
         storage::RepositoryHandle::FS(r) => resolve_stack_to_layers_with_repo(stack, r).await,
         storage::RepositoryHandle::Tar(r) => resolve_stack_to_layers_with_repo(stack, r).await,
         storage::RepositoryHandle::Rpc(r) => resolve_stack_to_layers_with_repo(stack, r).await,
+        storage::RepositoryHandle::S3(r) => resolve_stack_to_layers_with_repo(stack, r).await,
+        storage::RepositoryHandle::Http(r) => resolve_stack_to_layers_with_repo(stack, r).await,
         storage::RepositoryHandle::FallbackProxy(r) => {
             resolve_stack_to_layers_with_repo(stack, &**r).await
         }
@@ -6,11 +6,12 @@ use std::sync::Arc;
 
 use rstest::rstest;
 
-use super::resolve_stack_to_layers;
+use super::{layer_requires_signature, resolve_stack_to_layers};
 use crate::fixtures::*;
 use crate::io::DigestFormat;
 use crate::prelude::*;
-use crate::{encoding, graph, io};
+use crate::storage::fs::OpenFsRepository;
+use crate::{encoding, graph, io, storage};
 
 #[rstest]
 #[tokio::test]
@@ -26,6 +27,39 @@ async fn test_stack_to_layers_dedupe(#[future] tmprepo: TempRepo) {
     assert_eq!(resolved.len(), 1, "should deduplicate layers in resolve");
 }
 
+#[rstest]
+#[tokio::test]
+async fn test_layer_requires_signature_from_remote(tmpdir: tempfile::TempDir) {
+    init_logging();
+    let mut remotes: Vec<storage::RepositoryHandle> = Vec::new();
+    for name in ["unsigned", "signed"] {
+        let repo = OpenFsRepository::create(tmpdir.path().join(name))
+            .await
+            .unwrap();
+        remotes.push(repo.into());
+    }
+    let policies = [false, true];
+    let only_signed = graph::Layer::new(encoding::EMPTY_DIGEST.into());
+    let both = graph::Layer::new(encoding::NULL_DIGEST.into());
+    let neither = graph::Layer::new(encoding::Digest::from_bytes(&[1; 32]).unwrap());
+    remotes[1].write_object(&only_signed).await.unwrap();
+    remotes[0].write_object(&both).await.unwrap();
+    remotes[1].write_object(&both).await.unwrap();
+
+    assert!(
+        layer_requires_signature(only_signed.digest().unwrap(), &remotes, &policies).await,
+        "a layer only found in a remote that requires signatures came from there"
+    );
+    assert!(
+        !layer_requires_signature(both.digest().unwrap(), &remotes, &policies).await,
+        "a layer that is also in a remote without the requirement could have come from there"
+    );
+    assert!(
+        !layer_requires_signature(neither.digest().unwrap(), &remotes, &policies).await,
+        "a layer that is in no remote was created locally"
+    );
+}
+
 /// Test that if there are too many layers to fit on a single mount
 /// that enough layers are merged together so the mount will succeed.
 #[rstest]
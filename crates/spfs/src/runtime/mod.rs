@@ -30,7 +30,9 @@ pub use storage::{
     LiveLayerFile,
     MountBackend,
     OwnedRuntime,
+    ResourceUsage,
     Runtime,
+    Snapshot,
     Status,
     Storage,
     STARTUP_FILES_LOCATION,
@@ -114,6 +114,30 @@ pub struct Status {
     /// An empty command signifies that this runtime is being
     /// used to launch an interactive shell environment
     pub command: Vec<String>,
+    /// The resources consumed by this runtime's processes
+    ///
+    /// This is only recorded by the monitor when resource tracking
+    /// is enabled, and is updated once the runtime has exited
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resource_usage: Option<ResourceUsage>,
+}
+
+/// The resources consumed by the processes in a runtime
+///
+/// These values are sampled periodically by the runtime monitor.
+/// The cpu time of processes that were too short-lived to be seen
+/// between samples is still included once they are waited for by a
+/// sampled parent, but they are not otherwise counted or measured.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ResourceUsage {
+    /// The total user and system cpu time of all processes, in milliseconds
+    pub cpu_time_ms: u64,
+    /// The largest combined resident memory of all processes, in bytes
+    pub peak_rss_bytes: u64,
+    /// The number of distinct processes that were seen in the runtime
+    pub process_count: u64,
+    /// How long the runtime was monitored for, in milliseconds
+    pub duration_ms: u64,
 }
 
 /// Data needed to bind mount a path onto an /spfs backend that uses
@@ -404,6 +428,14 @@ pub struct Config {
     /// List of live layers to add on top of the runtime's overlayfs
     #[serde(default, skip_serializing_if = "Vec::is_empty")]
     pub live_layers: Vec<LiveLayer>,
+    /// Whether this runtime is set up in an unprivileged user namespace
+    ///
+    /// Rootless runtimes map the current user to root within a new
+    /// user namespace, and do not require any special privileges to
+    /// create or join. Processes in the runtime will see themselves
+    /// as the root user.
+    #[serde(default)]
+    pub rootless: bool,
 }
 
 impl Default for Config {
@@ -447,6 +479,7 @@ impl Config {
             secondary_repositories: Vec::new(),
             durable: false,
             live_layers: Vec::new(),
+            rootless: false,
         }
     }
 
@@ -485,7 +518,8 @@ pub enum MountBackend {
     /// Mounts a fuse filesystem as the lower directory to
     /// overlayfs, using the overlayfs upper directory for edits
     OverlayFsWithFuse,
-    /// Mounts a fuse filesystem directly
+    /// Mounts a fuse filesystem directly, which stores edits
+    /// in an upper directory in the same way as overlayfs
     FuseOnly,
     /// Leverages the win file system protocol system to present
     /// dynamic file system entries to runtime processes
@@ -958,7 +992,9 @@ impl Runtime {
     /// Return true if the upper dir of this runtime has changes.
     pub fn is_dirty(&self) -> bool {
         match self.config.mount_backend {
-            MountBackend::OverlayFsWithFuse | MountBackend::OverlayFsWithRenders => {
+            MountBackend::OverlayFsWithFuse
+            | MountBackend::OverlayFsWithRenders
+            | MountBackend::FuseOnly => {
                 match std::fs::metadata(&self.config.upper_dir) {
                     #[cfg(unix)]
                     Ok(meta) => meta.size() != 0,
@@ -972,7 +1008,6 @@ impl Runtime {
                     }
                 }
             }
-            MountBackend::FuseOnly => false,
             MountBackend::WinFsp => false,
         }
     }
@@ -1104,6 +1139,17 @@ impl Runtime {
     }
 }
 
+/// A saved state of the edits made in a durable runtime
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    /// The name of this snapshot, unique within its runtime
+    pub name: String,
+    /// The manifest of the runtime's upper dir when the snapshot was taken
+    pub manifest: Digest,
+    /// When the snapshot was taken
+    pub time: chrono::DateTime<chrono::Utc>,
+}
+
 /// Manages the on-disk storage of many runtimes.
 #[derive(Debug, Clone)]
 pub struct Storage {
@@ -1189,6 +1235,15 @@ impl Storage {
             }
         }
 
+        for snapshot in self.list_snapshots(name.as_ref()).await? {
+            let tag = snapshot_tag(name.as_ref(), &snapshot.name)?;
+            match self.inner.remove_tag_stream(&tag).await {
+                Ok(_) => {}
+                Err(Error::UnknownReference(_)) => {}
+                err => return err,
+            }
+        }
+
         Ok(())
     }
 
@@ -1487,6 +1542,131 @@ impl Storage {
                 }),
         )
     }
+
+    /// Save the current edits of a durable runtime as a named snapshot.
+    ///
+    /// The contents of the runtime's upper dir are committed to the
+    /// repository as a manifest, without creating a layer, so that
+    /// they can be restored later using [`Self::rollback_runtime`].
+    /// An existing snapshot with the same name is replaced.
+    pub async fn snapshot_runtime<S: AsRef<str>>(
+        &self,
+        rt: &Runtime,
+        snapshot: S,
+    ) -> Result<Snapshot> {
+        if !rt.is_durable() {
+            return Err(Error::String(format!(
+                "Only durable runtimes can be snapshotted: {}",
+                rt.name()
+            )));
+        }
+        let tag = snapshot_tag(rt.name(), snapshot.as_ref())?;
+        rt.ensure_upper_dirs().await?;
+        let manifest = crate::Committer::new(&self.inner)
+            .commit_dir(rt.upper_dir())
+            .await?;
+        let digest = manifest.to_graph_manifest().digest()?;
+        self.inner.push_tag(&tag, &digest).await?;
+        self.read_snapshot(rt.name(), snapshot).await
+    }
+
+    /// Load a single snapshot of the named runtime
+    ///
+    /// # Errors:
+    /// - [`Error::UnknownReference`] if the snapshot does not exist
+    pub async fn read_snapshot<R, S>(&self, runtime: R, snapshot: S) -> Result<Snapshot>
+    where
+        R: AsRef<str>,
+        S: AsRef<str>,
+    {
+        let tag = snapshot_tag(runtime.as_ref(), snapshot.as_ref())?;
+        let tag = self.inner.resolve_tag(&tag).await?;
+        Ok(Snapshot {
+            name: snapshot.as_ref().to_string(),
+            manifest: tag.target,
+            time: tag.time,
+        })
+    }
+
+    /// List the snapshots of the named runtime, oldest first
+    pub async fn list_snapshots<R: AsRef<str>>(&self, runtime: R) -> Result<Vec<Snapshot>> {
+        let path = format!(
+            "spfs/runtimes/{}/{}",
+            RuntimeDataType::Snapshot,
+            runtime.as_ref()
+        );
+        let names: Vec<_> = self
+            .inner
+            .ls_tags(relative_path::RelativePath::new(&path))
+            .try_filter_map(|entry| {
+                futures::future::ready(Ok(match entry {
+                    storage::EntryType::Tag(name) => Some(name),
+                    _ => None,
+                }))
+            })
+            .try_collect()
+            .await?;
+        let mut snapshots = Vec::with_capacity(names.len());
+        for name in names {
+            snapshots.push(self.read_snapshot(runtime.as_ref(), name).await?);
+        }
+        snapshots.sort_by(|a, b| a.time.cmp(&b.time).then_with(|| a.name.cmp(&b.name)));
+        Ok(snapshots)
+    }
+
+    /// Restore the edits of a durable runtime to a previous snapshot.
+    ///
+    /// Any changes made in the runtime's upper dir since the
+    /// snapshot was taken are discarded. The runtime cannot be
+    /// running, since its filesystem would be modified out from
+    /// under the processes using it.
+    pub async fn rollback_runtime<S: AsRef<str>>(&self, rt: &Runtime, snapshot: S) -> Result<()> {
+        if !rt.is_durable() {
+            return Err(Error::String(format!(
+                "Only durable runtimes can be rolled back: {}",
+                rt.name()
+            )));
+        }
+        if rt.status.running {
+            return Err(Error::String(format!(
+                "Cannot roll back a runtime that is currently running: {}",
+                rt.name()
+            )));
+        }
+        let RepositoryHandle::FS(repo) = &*self.inner else {
+            return Err(Error::DoesNotSupportDurableRuntimePath);
+        };
+        let repo = repo.opened().await?;
+        let snapshot = self.read_snapshot(rt.name(), snapshot).await?;
+        let manifest = self.inner.read_manifest(snapshot.manifest).await?;
+
+        rt.ensure_upper_dirs().await?;
+        rt.reset_all()?;
+        let upper_dir = rt.upper_dir();
+        storage::fs::Renderer::new(&*repo)
+            .render_manifest_into_dir(&manifest, upper_dir, storage::fs::RenderType::Copy)
+            .await?;
+
+        // masked entries are not rendered, and must be
+        // recreated as the whiteout files that overlayfs expects
+        #[cfg(unix)]
+        for node in manifest.to_tracking_manifest().walk() {
+            if !node.entry.kind.is_mask() {
+                continue;
+            }
+            let fullpath = node.path.to_path(upper_dir);
+            nix::sys::stat::mknod(
+                &fullpath,
+                nix::sys::stat::SFlag::S_IFCHR,
+                nix::sys::stat::Mode::empty(),
+                0,
+            )
+            .map_err(|err| {
+                Error::wrap_nix(err, format!("Failed to create file mask: {}", node.path))
+            })?;
+        }
+        Ok(())
+    }
 }
 
 /// Specifies a type of runtime data being stored
@@ -1496,6 +1676,8 @@ enum RuntimeDataType {
     Metadata,
     /// Runtime payload data identifies the spfs file data being used
     Payload,
+    /// Snapshots identify saved states of a durable runtime's edits
+    Snapshot,
 }
 
 impl std::fmt::Display for RuntimeDataType {
@@ -1503,6 +1685,7 @@ impl std::fmt::Display for RuntimeDataType {
         match self {
             Self::Metadata => "meta".fmt(f),
             Self::Payload => "data".fmt(f),
+            Self::Snapshot => "snapshots".fmt(f),
         }
     }
 }
@@ -1514,6 +1697,17 @@ fn runtime_tag<S: std::fmt::Display>(
     tracking::TagSpec::parse(format!("spfs/runtimes/{data_type}/{name}"))
 }
 
+fn snapshot_tag(runtime: &str, snapshot: &str) -> Result<tracking::TagSpec> {
+    // snapshots are stored in a folder per runtime, and
+    // so cannot be nested any further than that
+    if snapshot.contains('/') {
+        return Err(Error::String(format!(
+            "Invalid snapshot name, cannot contain '/': {snapshot}"
+        )));
+    }
+    runtime_tag(RuntimeDataType::Snapshot, format!("{runtime}/{snapshot}"))
+}
+
 /// Recursively create the given directory with the appropriate permissions.
 ///
 /// Returns EINVAL if the path contains any parent dir components, ie '..')
@@ -667,6 +667,71 @@ async fn test_runtime_reset(tmpdir: tempfile::TempDir) {
     assert_eq!(listdir(upper_dir), Vec::<String>::new());
 }
 
+#[rstest]
+#[tokio::test]
+async fn test_runtime_snapshot_and_rollback(tmpdir: tempfile::TempDir) {
+    init_logging();
+    let root = tmpdir.path().to_string_lossy().to_string();
+    let repo = crate::storage::RepositoryHandle::from(
+        crate::storage::fs::FsRepository::create(root)
+            .await
+            .unwrap(),
+    );
+    let storage = Storage::new(repo).unwrap();
+
+    let transient = storage.create_transient_runtime().await.unwrap();
+    assert!(
+        storage.snapshot_runtime(&transient, "first").await.is_err(),
+        "only durable runtimes should support snapshots"
+    );
+
+    let durable = true;
+    let runtime = storage
+        .create_named_runtime("snapshots", durable, Vec::new())
+        .await
+        .expect("failed to create durable runtime");
+    let upper_dir = runtime.upper_dir().clone();
+    ensure(upper_dir.join("file"), "original");
+    ensure(upper_dir.join("dir/file"), "nested");
+
+    let snapshot = storage
+        .snapshot_runtime(&runtime, "first")
+        .await
+        .expect("failed to snapshot runtime");
+    assert_eq!(snapshot.name, "first");
+
+    ensure(upper_dir.join("file"), "modified");
+    ensure(upper_dir.join("added"), "added");
+    std::fs::remove_dir_all(upper_dir.join("dir")).unwrap();
+    storage
+        .snapshot_runtime(&runtime, "second")
+        .await
+        .expect("failed to snapshot runtime");
+
+    let snapshots = storage.list_snapshots(runtime.name()).await.unwrap();
+    let names: Vec<_> = snapshots.iter().map(|s| s.name.as_str()).collect();
+    assert_eq!(names, vec!["first", "second"]);
+
+    storage
+        .rollback_runtime(&runtime, "first")
+        .await
+        .expect("failed to roll back runtime");
+    assert_eq!(
+        std::fs::read_to_string(upper_dir.join("file")).unwrap(),
+        "original"
+    );
+    assert_eq!(
+        std::fs::read_to_string(upper_dir.join("dir/file")).unwrap(),
+        "nested"
+    );
+    assert!(
+        !upper_dir.join("added").exists(),
+        "changes made after the snapshot should be removed"
+    );
+
+    assert!(storage.rollback_runtime(&runtime, "missing").await.is_err());
+}
+
 #[rstest]
 #[tokio::test]
 async fn test_runtime_ensure_extra_bind_mount_locations_exist(tmpdir: tempfile::TempDir) {
@@ -0,0 +1,290 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+//! Authentication and authorization of requests made to the spfs server
+
+use std::sync::Arc;
+
+use tonic::Status;
+
+use crate::config::{self, AuthGrant, Permission};
+use crate::storage::TagNamespace;
+
+#[cfg(test)]
+#[path = "./auth_test.rs"]
+mod auth_test;
+
+/// The authorization header expected in both gRPC and http requests
+const AUTHORIZATION: &str = "authorization";
+const BEARER_PREFIX: &str = "Bearer ";
+
+/// An operation that a client is attempting to perform.
+#[derive(Clone, Copy, Debug)]
+pub enum Access<'a> {
+    /// Read an object, payload or tag
+    Read,
+    /// Write an object or payload
+    Write,
+    /// Create or remove tags in the given namespace
+    WriteTags(Option<&'a TagNamespace>),
+    /// Remove an object or payload from the repository
+    Remove,
+}
+
+impl std::fmt::Display for Access<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Read => f.write_str("read"),
+            Self::Write => f.write_str("write"),
+            Self::WriteTags(None) => f.write_str("write tags"),
+            Self::WriteTags(Some(namespace)) => write!(f, "write tags in namespace {namespace}"),
+            Self::Remove => f.write_str("remove"),
+        }
+    }
+}
+
+/// Checks if this permission grants the given access
+fn permission_allows(permission: &Permission, access: Access<'_>) -> bool {
+    match (permission, access) {
+        (Permission::Admin, _) => true,
+        (_, Access::Remove) => false,
+        (Permission::Write, _) => true,
+        (Permission::Read, access) => matches!(access, Access::Read),
+        (Permission::WriteTags(_), Access::Read | Access::Write) => true,
+        (Permission::WriteTags(allowed), Access::WriteTags(namespace)) => {
+            namespace.is_some_and(|ns| {
+                ns.as_rel_path()
+                    .normalize()
+                    .starts_with(allowed.as_rel_path().normalize())
+            })
+        }
+    }
+}
+
+/// The credentials presented by a client along with its request.
+#[derive(Clone, Debug, Default)]
+pub struct Credentials {
+    /// A bearer token, from the authorization header of the request
+    pub bearer_token: Option<String>,
+    /// The DER-encoded certificate chain of the client, when
+    /// connected over mutual TLS
+    pub client_certificates: Vec<Vec<u8>>,
+}
+
+impl Credentials {
+    /// Collect the credentials that were sent with a gRPC request
+    pub fn from_grpc_request<T>(request: &tonic::Request<T>) -> Self {
+        let bearer_token = request
+            .metadata()
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(parse_bearer_token);
+        let client_certificates = request
+            .peer_certs()
+            .map(|certs| certs.iter().map(|c| c.get_ref().to_vec()).collect())
+            .unwrap_or_default();
+        Self {
+            bearer_token,
+            client_certificates,
+        }
+    }
+
+    /// Collect the credentials that were sent with an http request
+    pub fn from_http_request<B>(request: &hyper::http::Request<B>) -> Self {
+        let bearer_token = request
+            .headers()
+            .get(hyper::http::header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(parse_bearer_token);
+        Self {
+            bearer_token,
+            client_certificates: Vec::new(),
+        }
+    }
+}
+
+fn parse_bearer_token(value: &str) -> Option<String> {
+    value
+        .strip_prefix(BEARER_PREFIX)
+        .map(|token| token.trim().to_owned())
+        .filter(|token| !token.is_empty())
+}
+
+/// A client that has been identified from its credentials
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Identity {
+    /// The name of the client, for logging purposes
+    pub name: String,
+    /// The operations that this client is allowed to perform
+    pub permissions: Vec<Permission>,
+}
+
+impl Identity {
+    /// Checks if this client is allowed to perform the given operation
+    pub fn allows(&self, access: Access<'_>) -> bool {
+        self.permissions
+            .iter()
+            .any(|permission| permission_allows(permission, access))
+    }
+}
+
+/// Identifies clients of the server based on their credentials.
+///
+/// Any number of authenticators can be added to an [`Auth`] policy,
+/// and the first one that is able to identify a client is used.
+pub trait Authenticator: std::fmt::Debug + Send + Sync {
+    /// Identify the client that presented the given credentials, if possible
+    fn authenticate(&self, credentials: &Credentials) -> Option<Identity>;
+}
+
+/// Identifies clients by the sha256 digest of a static bearer token
+#[derive(Debug, Default)]
+pub struct TokenAuthenticator {
+    grants: Vec<AuthGrant>,
+}
+
+impl TokenAuthenticator {
+    pub fn new(grants: Vec<AuthGrant>) -> Self {
+        Self { grants }
+    }
+}
+
+impl Authenticator for TokenAuthenticator {
+    fn authenticate(&self, credentials: &Credentials) -> Option<Identity> {
+        let token = credentials.bearer_token.as_ref()?;
+        find_grant(&self.grants, token.as_bytes())
+    }
+}
+
+/// Identifies clients by the sha256 fingerprint of the
+/// certificate that they presented over mutual TLS
+#[derive(Debug, Default)]
+pub struct ClientCertificateAuthenticator {
+    grants: Vec<AuthGrant>,
+}
+
+impl ClientCertificateAuthenticator {
+    pub fn new(grants: Vec<AuthGrant>) -> Self {
+        Self { grants }
+    }
+}
+
+impl Authenticator for ClientCertificateAuthenticator {
+    fn authenticate(&self, credentials: &Credentials) -> Option<Identity> {
+        // the first certificate is the client's own, any
+        // others are intermediates that were used to sign it
+        let certificate = credentials.client_certificates.first()?;
+        find_grant(&self.grants, certificate)
+    }
+}
+
+/// Find the grant whose digest matches the given credential data
+fn find_grant(grants: &[AuthGrant], credential: &[u8]) -> Option<Identity> {
+    let digest = ring::digest::digest(&ring::digest::SHA256, credential);
+    let digest = data_encoding::HEXLOWER.encode(digest.as_ref());
+    grants
+        .iter()
+        .find(|grant| grant.sha256.trim().eq_ignore_ascii_case(&digest))
+        .map(|grant| Identity {
+            name: grant.name.clone(),
+            permissions: grant.permissions.clone(),
+        })
+}
+
+/// Decides which requests are allowed to be processed by the server.
+///
+/// The default policy has no authenticators, and allows
+/// any request to be made without credentials.
+#[derive(Clone, Debug, Default)]
+pub struct Auth {
+    authenticators: Vec<Arc<dyn Authenticator>>,
+}
+
+impl Auth {
+    /// Create the authentication policy described by the given config
+    pub fn from_config(config: &config::ServerAuth) -> Self {
+        let mut auth = Self::default();
+        if !config.tokens.is_empty() {
+            auth = auth.with_authenticator(TokenAuthenticator::new(config.tokens.clone()));
+        }
+        if !config.client_certificates.is_empty() {
+            auth = auth.with_authenticator(ClientCertificateAuthenticator::new(
+                config.client_certificates.clone(),
+            ));
+        }
+        auth
+    }
+
+    /// Add an additional way to identify clients of the server
+    pub fn with_authenticator(mut self, authenticator: impl Authenticator + 'static) -> Self {
+        self.authenticators.push(Arc::new(authenticator));
+        self
+    }
+
+    /// True if clients must be identified in order to make requests
+    pub fn is_enabled(&self) -> bool {
+        !self.authenticators.is_empty()
+    }
+
+    /// Identify the client that presented the given credentials, if possible
+    pub fn authenticate(&self, credentials: &Credentials) -> Option<Identity> {
+        self.authenticators
+            .iter()
+            .find_map(|authenticator| authenticator.authenticate(credentials))
+    }
+
+    /// Check that the holder of the given credentials is allowed
+    /// to perform the requested operation.
+    pub fn authorize(
+        &self,
+        credentials: &Credentials,
+        access: Access<'_>,
+    ) -> Result<(), AuthError> {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+        let Some(identity) = self.authenticate(credentials) else {
+            return Err(AuthError::Unauthenticated);
+        };
+        if !identity.allows(access) {
+            tracing::debug!(client = %identity.name, %access, "permission denied");
+            return Err(AuthError::PermissionDenied {
+                client: identity.name,
+                access: access.to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Check that the sender of the given gRPC request is allowed
+    /// to perform the requested operation.
+    pub fn authorize_request<T>(
+        &self,
+        request: &tonic::Request<T>,
+        access: Access<'_>,
+    ) -> Result<(), AuthError> {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+        self.authorize(&Credentials::from_grpc_request(request), access)
+    }
+}
+
+/// The reason that a request to the server was rejected
+#[derive(Debug, thiserror::Error)]
+pub enum AuthError {
+    #[error("valid credentials are required by this server")]
+    Unauthenticated,
+    #[error("{client} is not allowed to {access}")]
+    PermissionDenied { client: String, access: String },
+}
+
+impl From<AuthError> for Status {
+    fn from(err: AuthError) -> Self {
+        match err {
+            AuthError::Unauthenticated => Status::unauthenticated(err.to_string()),
+            AuthError::PermissionDenied { .. } => Status::permission_denied(err.to_string()),
+        }
+    }
+}
@@ -0,0 +1,235 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use relative_path::RelativePath;
+use rstest::rstest;
+
+use super::{Access, Auth, AuthError, Credentials, Identity};
+use crate::config::{AuthGrant, Permission, ServerAuth};
+use crate::fixtures::*;
+use crate::prelude::*;
+use crate::storage::{TagNamespace, TagNamespaceBuf};
+use crate::{tracking, Error};
+
+fn grant(name: &str, token: &str, permissions: &[&str]) -> AuthGrant {
+    let digest = ring::digest::digest(&ring::digest::SHA256, token.as_bytes());
+    AuthGrant {
+        name: name.to_string(),
+        sha256: data_encoding::HEXLOWER.encode(digest.as_ref()),
+        permissions: permissions.iter().map(|p| p.parse().unwrap()).collect(),
+    }
+}
+
+fn token(token: &str) -> Credentials {
+    Credentials {
+        bearer_token: Some(token.to_string()),
+        ..Default::default()
+    }
+}
+
+fn test_auth() -> Auth {
+    Auth::from_config(&ServerAuth {
+        tokens: vec![
+            grant("reader", "read-token", &["read"]),
+            grant("team", "team-token", &["write-tags:team"]),
+            grant("admin", "admin-token", &["admin"]),
+        ],
+        client_certificates: Vec::new(),
+    })
+}
+
+#[rstest]
+#[case("read", Permission::Read)]
+#[case("write", Permission::Write)]
+#[case("admin", Permission::Admin)]
+#[case(
+    "write-tags:team/sub",
+    Permission::WriteTags(TagNamespaceBuf::new("team/sub"))
+)]
+fn test_permission_round_trip(#[case] source: &str, #[case] expected: Permission) {
+    let permission: Permission = source.parse().unwrap();
+    assert_eq!(permission, expected);
+    assert_eq!(permission.to_string(), source);
+}
+
+#[rstest]
+#[case("")]
+#[case("remove")]
+#[case("write-tags:")]
+fn test_permission_invalid(#[case] source: &str) {
+    source
+        .parse::<Permission>()
+        .expect_err("should fail to parse an invalid permission");
+}
+
+#[rstest]
+fn test_identity_allows() {
+    let team = TagNamespace::new(RelativePath::new("team"));
+    let nested = TagNamespace::new(RelativePath::new("team/nested"));
+    let other = TagNamespace::new(RelativePath::new("other"));
+    let identity = |permission: &str| Identity {
+        name: "test".into(),
+        permissions: vec![permission.parse().unwrap()],
+    };
+
+    let reader = identity("read");
+    assert!(reader.allows(Access::Read));
+    assert!(!reader.allows(Access::Write));
+    assert!(!reader.allows(Access::WriteTags(None)));
+
+    let writer = identity("write-tags:team");
+    assert!(writer.allows(Access::Read));
+    assert!(writer.allows(Access::Write));
+    assert!(writer.allows(Access::WriteTags(Some(team))));
+    assert!(writer.allows(Access::WriteTags(Some(nested))));
+    assert!(!writer.allows(Access::WriteTags(Some(other))));
+    assert!(!writer.allows(Access::WriteTags(None)));
+    assert!(!writer.allows(Access::Remove));
+
+    let writer = identity("write");
+    assert!(writer.allows(Access::WriteTags(None)));
+    assert!(!writer.allows(Access::Remove));
+
+    assert!(identity("admin").allows(Access::Remove));
+}
+
+#[rstest]
+fn test_auth_authorize() {
+    let auth = Auth::default();
+    assert!(
+        auth.authorize(&Credentials::default(), Access::Remove)
+            .is_ok(),
+        "default auth should allow all requests"
+    );
+
+    let auth = test_auth();
+    let err = auth
+        .authorize(&Credentials::default(), Access::Read)
+        .unwrap_err();
+    assert!(matches!(err, AuthError::Unauthenticated), "{err:?}");
+    let err = auth
+        .authorize(&token("wrong-token"), Access::Read)
+        .unwrap_err();
+    assert!(matches!(err, AuthError::Unauthenticated), "{err:?}");
+    let err = auth
+        .authorize(&token("read-token"), Access::Write)
+        .unwrap_err();
+    assert!(matches!(err, AuthError::PermissionDenied { .. }), "{err:?}");
+    assert!(auth.authorize(&token("read-token"), Access::Read).is_ok());
+    assert!(auth
+        .authorize(&token("admin-token"), Access::Remove)
+        .is_ok());
+}
+
+#[rstest]
+fn test_client_certificate_authenticator() {
+    let certificate = b"not really a certificate".to_vec();
+    let digest = ring::digest::digest(&ring::digest::SHA256, &certificate);
+    let auth = Auth::from_config(&ServerAuth {
+        tokens: Vec::new(),
+        client_certificates: vec![AuthGrant {
+            name: "studio".into(),
+            sha256: data_encoding::HEXUPPER.encode(digest.as_ref()),
+            permissions: vec![Permission::Read],
+        }],
+    });
+    let credentials = Credentials {
+        bearer_token: None,
+        client_certificates: vec![certificate],
+    };
+    let identity = auth
+        .authenticate(&credentials)
+        .expect("should identify client");
+    assert_eq!(identity.name, "studio");
+    assert!(auth.authenticate(&token("read-token")).is_none());
+}
+
+fn is_status(err: &Error, code: tonic::Code) -> bool {
+    matches!(err, Error::Tonic(status) if status.code() == code)
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_server_requires_authorization(tmpdir: tempfile::TempDir) {
+    init_logging();
+    let anonymous = serve_rpc_repo(tmpdir, test_auth(), None, Default::default()).await;
+    let TempRepo::Rpc { repo, .. } = &anonymous else {
+        unreachable!();
+    };
+    let address = repo.address();
+    let connect = |token: &str, namespace: Option<&str>| {
+        let address = address.clone();
+        let params = crate::storage::rpc::Params {
+            token: Some(token.to_string()),
+            tag_namespace: namespace.map(TagNamespaceBuf::new),
+            ..Default::default()
+        };
+        async move {
+            let mut address = address;
+            address.set_query(None);
+            let repo = crate::storage::rpc::RpcRepository::new(crate::storage::rpc::Config {
+                address,
+                params,
+            })
+            .await
+            .unwrap();
+            crate::storage::RepositoryHandle::from(repo)
+        }
+    };
+
+    let tag = tracking::TagSpec::parse("testing/data").unwrap();
+    let err = anonymous
+        .resolve_tag(&tag)
+        .await
+        .expect_err("should not be able to read without credentials");
+    assert!(is_status(&err, tonic::Code::Unauthenticated), "{err:?}");
+
+    let team = connect("team-token", Some("team")).await;
+    let digest = team
+        .commit_blob(Box::pin(b"some data".as_slice()))
+        .await
+        .expect("should be able to write data with write permission");
+    team.push_tag(&tag, &digest)
+        .await
+        .expect("should be able to tag within the allowed namespace");
+    let err = team
+        .remove_payload(digest)
+        .await
+        .expect_err("should not be able to remove data without admin");
+    assert!(is_status(&err, tonic::Code::PermissionDenied), "{err:?}");
+
+    let outside = connect("team-token", None).await;
+    let err = outside
+        .push_tag(&tag, &digest)
+        .await
+        .expect_err("should not be able to tag outside of the allowed namespace");
+    assert!(is_status(&err, tonic::Code::PermissionDenied), "{err:?}");
+
+    let reader = connect("read-token", Some("team")).await;
+    assert_eq!(reader.resolve_tag(&tag).await.unwrap().target, digest);
+    let (mut payload, _) = reader
+        .open_payload(digest)
+        .await
+        .expect("should be able to download payloads with read permission");
+    let mut data = Vec::new();
+    tokio::io::AsyncReadExt::read_to_end(&mut payload, &mut data)
+        .await
+        .unwrap();
+    assert_eq!(data, b"some data");
+    let err = reader
+        .commit_blob(Box::pin(b"other data".as_slice()))
+        .await
+        .expect_err("should not be able to write data with read permission");
+    assert!(is_status(&err, tonic::Code::PermissionDenied), "{err:?}");
+
+    let admin = connect("admin-token", Some("team")).await;
+    admin
+        .remove_tag_stream(&tag)
+        .await
+        .expect("admin should be able to remove tags");
+    admin
+        .remove_payload(digest)
+        .await
+        .expect("admin should be able to remove payloads");
+}
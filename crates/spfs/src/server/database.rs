@@ -10,6 +10,7 @@ use chrono::{DateTime, Utc};
 use futures::{Stream, StreamExt};
 use tonic::{Request, Response, Status};
 
+use super::auth::{Access, Auth};
 use crate::prelude::*;
 use crate::proto::database_service_server::DatabaseServiceServer;
 use crate::proto::{self, convert_digest, convert_to_datetime, RpcResult};
@@ -18,6 +19,7 @@ use crate::storage;
 #[derive(Debug, Clone)]
 pub struct DatabaseService {
     repo: Arc<storage::RepositoryHandle>,
+    auth: Auth,
 }
 
 #[tonic::async_trait]
@@ -33,6 +35,7 @@ impl proto::database_service_server::DatabaseService for DatabaseService {
         &self,
         request: Request<proto::HasObjectRequest>,
     ) -> Result<Response<proto::HasObjectResponse>, Status> {
+        self.auth.authorize_request(&request, Access::Read)?;
         let request = request.into_inner();
         let digest = convert_digest(request.digest)
             .map_err(|err| Status::invalid_argument(err.to_string()))?;
@@ -45,6 +48,7 @@ impl proto::database_service_server::DatabaseService for DatabaseService {
         &self,
         request: Request<proto::ReadObjectRequest>,
     ) -> Result<Response<proto::ReadObjectResponse>, Status> {
+        self.auth.authorize_request(&request, Access::Read)?;
         let request = request.into_inner();
         let digest = proto::handle_error!(convert_digest(request.digest));
         let object = { proto::handle_error!(self.repo.read_object(digest).await) };
@@ -56,6 +60,7 @@ impl proto::database_service_server::DatabaseService for DatabaseService {
         &self,
         request: Request<proto::FindDigestsRequest>,
     ) -> Result<Response<Self::FindDigestsStream>, Status> {
+        self.auth.authorize_request(&request, Access::Read)?;
         let request = request.into_inner();
         let search_criteria = request
             .search_criteria
@@ -73,8 +78,9 @@ impl proto::database_service_server::DatabaseService for DatabaseService {
 
     async fn iter_objects(
         &self,
-        _request: Request<proto::IterObjectsRequest>,
+        request: Request<proto::IterObjectsRequest>,
     ) -> Result<Response<Self::IterObjectsStream>, Status> {
+        self.auth.authorize_request(&request, Access::Read)?;
         Err(Status::unimplemented(
             "object iteration is no yet supported directly over gRPC",
         ))
@@ -82,8 +88,9 @@ impl proto::database_service_server::DatabaseService for DatabaseService {
 
     async fn walk_objects(
         &self,
-        _request: Request<proto::WalkObjectsRequest>,
+        request: Request<proto::WalkObjectsRequest>,
     ) -> Result<Response<Self::WalkObjectsStream>, Status> {
+        self.auth.authorize_request(&request, Access::Read)?;
         Err(Status::unimplemented(
             "object walking is no yet supported directly over gRPC",
         ))
@@ -93,6 +100,7 @@ impl proto::database_service_server::DatabaseService for DatabaseService {
         &self,
         request: Request<proto::WriteObjectRequest>,
     ) -> Result<Response<proto::WriteObjectResponse>, Status> {
+        self.auth.authorize_request(&request, Access::Write)?;
         let request = request.into_inner();
         let object = proto::handle_error!(request.object.try_into());
         {
@@ -106,6 +114,7 @@ impl proto::database_service_server::DatabaseService for DatabaseService {
         &self,
         request: Request<proto::RemoveObjectRequest>,
     ) -> Result<Response<proto::RemoveObjectResponse>, Status> {
+        self.auth.authorize_request(&request, Access::Remove)?;
         let request = request.into_inner();
         let digest: crate::encoding::Digest = proto::handle_error!(convert_digest(request.digest));
         proto::handle_error!(self.repo.remove_object(digest).await);
@@ -117,6 +126,7 @@ impl proto::database_service_server::DatabaseService for DatabaseService {
         &self,
         request: Request<proto::RemoveObjectIfOlderThanRequest>,
     ) -> Result<Response<proto::RemoveObjectIfOlderThanResponse>, Status> {
+        self.auth.authorize_request(&request, Access::Remove)?;
         let request = request.into_inner();
         let older_than: DateTime<Utc> =
             proto::handle_error!(convert_to_datetime(request.older_than));
@@ -133,10 +143,23 @@ impl proto::database_service_server::DatabaseService for DatabaseService {
 
 impl DatabaseService {
     pub fn new(repo: Arc<storage::RepositoryHandle>) -> Self {
-        Self { repo }
+        Self {
+            repo,
+            auth: Auth::default(),
+        }
     }
 
     pub fn new_srv(repo: Arc<storage::RepositoryHandle>) -> DatabaseServiceServer<Self> {
-        DatabaseServiceServer::new(Self::new(repo))
+        Self::new(repo).into_srv()
+    }
+
+    /// Require that clients are authorized by the given policy
+    pub fn with_auth(mut self, auth: Auth) -> Self {
+        self.auth = auth;
+        self
+    }
+
+    pub fn into_srv(self) -> DatabaseServiceServer<Self> {
+        DatabaseServiceServer::new(self)
     }
 }
@@ -9,6 +9,7 @@ use futures::{Stream, StreamExt};
 use prost::Message;
 use tonic::{Request, Response, Status};
 
+use super::auth::{Access, Auth, AuthError, Credentials};
 use crate::prelude::*;
 use crate::proto::payload_service_server::PayloadServiceServer;
 use crate::proto::{self, convert_digest, RpcResult};
@@ -26,6 +27,10 @@ use crate::storage;
 pub struct PayloadService {
     repo: Arc<storage::RepositoryHandle>,
     external_root: url::Url,
+    auth: Auth,
+    /// The certificates presented by the client of the
+    /// current http connection, if any
+    client_certificates: Arc<Vec<Vec<u8>>>,
 }
 
 #[tonic::async_trait]
@@ -35,8 +40,9 @@ impl proto::payload_service_server::PayloadService for PayloadService {
 
     async fn iter_digests(
         &self,
-        _request: Request<proto::IterDigestsRequest>,
+        request: Request<proto::IterDigestsRequest>,
     ) -> Result<Response<Self::IterDigestsStream>, Status> {
+        self.auth.authorize_request(&request, Access::Read)?;
         let stream = self
             .repo
             .iter_payload_digests()
@@ -49,8 +55,9 @@ impl proto::payload_service_server::PayloadService for PayloadService {
 
     async fn write_payload(
         &self,
-        _request: Request<proto::WritePayloadRequest>,
+        request: Request<proto::WritePayloadRequest>,
     ) -> Result<Response<proto::WritePayloadResponse>, Status> {
+        self.auth.authorize_request(&request, Access::Write)?;
         let data = proto::write_payload_response::UploadOption {
             url: self.external_root.to_string(),
         };
@@ -62,6 +69,7 @@ impl proto::payload_service_server::PayloadService for PayloadService {
         &self,
         request: Request<proto::HasPayloadRequest>,
     ) -> Result<Response<proto::HasPayloadResponse>, Status> {
+        self.auth.authorize_request(&request, Access::Read)?;
         let request = request.into_inner();
         let digest = convert_digest(request.digest)
             .map_err(|err| Status::invalid_argument(err.to_string()))?;
@@ -74,6 +82,7 @@ impl proto::payload_service_server::PayloadService for PayloadService {
         &self,
         request: Request<proto::OpenPayloadRequest>,
     ) -> Result<Response<proto::OpenPayloadResponse>, Status> {
+        self.auth.authorize_request(&request, Access::Read)?;
         let request = request.into_inner();
         let digest: crate::encoding::Digest = proto::handle_error!(convert_digest(request.digest));
         // do a little effort to determine if we can actually serve the
@@ -93,6 +102,7 @@ impl proto::payload_service_server::PayloadService for PayloadService {
         &self,
         request: Request<proto::RemovePayloadRequest>,
     ) -> Result<Response<proto::RemovePayloadResponse>, Status> {
+        self.auth.authorize_request(&request, Access::Remove)?;
         let request = request.into_inner();
         let digest: crate::encoding::Digest = proto::handle_error!(convert_digest(request.digest));
         proto::handle_error!(self.repo.remove_payload(digest).await);
@@ -115,6 +125,15 @@ impl hyper::service::Service<hyper::http::Request<hyper::Body>> for PayloadServi
     }
 
     fn call(&mut self, req: hyper::http::Request<hyper::Body>) -> Self::Future {
+        let access = match *req.method() {
+            hyper::Method::POST => Access::Write,
+            _ => Access::Read,
+        };
+        let mut credentials = Credentials::from_http_request(&req);
+        credentials.client_certificates = self.client_certificates.to_vec();
+        if let Err(err) = self.auth.authorize(&credentials, access) {
+            return Box::pin(futures::future::ready(Ok(unauthorized_response(&err))));
+        }
         match *req.method() {
             hyper::Method::POST => Box::pin(handle_upload(self.repo.clone(), req)),
             hyper::Method::GET => Box::pin(handle_download(self.repo.clone(), req)),
@@ -133,6 +152,8 @@ impl PayloadService {
         Self {
             repo,
             external_root,
+            auth: Auth::default(),
+            client_certificates: Arc::default(),
         }
     }
 
@@ -143,9 +164,77 @@ impl PayloadService {
         Self::new(repo, external_root).into_srv()
     }
 
+    /// Require that clients are authorized by the given policy
+    ///
+    /// This applies to both the gRPC service and the http server.
+    pub fn with_auth(mut self, auth: Auth) -> Self {
+        self.auth = auth;
+        self
+    }
+
     pub fn into_srv(self) -> PayloadServiceServer<Self> {
         PayloadServiceServer::new(self)
     }
+
+    /// Serve the http portion of this service from the given listener,
+    /// until the shutdown future completes.
+    ///
+    /// Connections are made over TLS when a config is provided, in
+    /// which case the external root of this service should be an
+    /// https url.
+    pub async fn serve_http<F>(
+        self,
+        listener: tokio::net::TcpListener,
+        tls: Option<&super::TlsConfig>,
+        shutdown: F,
+    ) -> crate::Result<()>
+    where
+        F: std::future::Future<Output = ()>,
+    {
+        let result = match tls {
+            None => {
+                let incoming = tokio_stream::wrappers::TcpListenerStream::new(listener);
+                hyper::Server::builder(hyper::server::accept::from_stream(incoming))
+                    .serve(hyper::service::make_service_fn(move |_| {
+                        let s = self.clone();
+                        async move { Ok::<_, std::convert::Infallible>(s) }
+                    }))
+                    .with_graceful_shutdown(shutdown)
+                    .await
+            }
+            Some(tls) => {
+                let incoming = tls.incoming(listener);
+                hyper::Server::builder(hyper::server::accept::from_stream(incoming))
+                    .serve(hyper::service::make_service_fn(
+                        move |conn: &tokio_rustls::server::TlsStream<tokio::net::TcpStream>| {
+                            let mut s = self.clone();
+                            s.client_certificates = Arc::new(super::tls::peer_certificates(conn));
+                            async move { Ok::<_, std::convert::Infallible>(s) }
+                        },
+                    ))
+                    .with_graceful_shutdown(shutdown)
+                    .await
+            }
+        };
+        result.map_err(|err| crate::Error::String(format!("http server failed: {err}")))
+    }
+}
+
+fn unauthorized_response(err: &AuthError) -> hyper::http::Response<hyper::Body> {
+    let mut response = hyper::Response::new(hyper::Body::from(err.to_string()));
+    match err {
+        AuthError::Unauthenticated => {
+            *response.status_mut() = hyper::http::StatusCode::UNAUTHORIZED;
+            response.headers_mut().insert(
+                hyper::http::header::WWW_AUTHENTICATE,
+                hyper::http::HeaderValue::from_static("Bearer"),
+            );
+        }
+        AuthError::PermissionDenied { .. } => {
+            *response.status_mut() = hyper::http::StatusCode::FORBIDDEN;
+        }
+    }
+    response
 }
 
 async fn handle_upload(
@@ -3,13 +3,16 @@
 // https://github.com/spkenv/spk
 
 use std::convert::TryInto;
+use std::future::ready;
+use std::pin::Pin;
 use std::sync::Arc;
 
-use futures::TryStreamExt;
-use relative_path::RelativePath;
+use futures::{Stream, TryStreamExt};
+use relative_path::{RelativePath, RelativePathBuf};
 use tokio_stream::StreamExt;
 use tonic::{Request, Response, Status};
 
+use super::auth::{Access, Auth};
 use crate::prelude::*;
 use crate::proto::tag_service_server::TagServiceServer;
 use crate::proto::{self, convert_digest, RpcResult};
@@ -26,15 +29,20 @@ fn string_to_namespace(namespace: &String) -> Option<&TagNamespace> {
 #[derive(Debug, Clone)]
 pub struct TagService {
     repo: Arc<storage::RepositoryHandle>,
+    auth: Auth,
 }
 
 #[tonic::async_trait]
 impl proto::tag_service_server::TagService for TagService {
+    type WatchTagChangesStream =
+        Pin<Box<dyn Stream<Item = Result<proto::WatchTagChangesResponse, Status>> + Send>>;
+
     async fn ls_tags(
         &self,
         request: Request<proto::LsTagsRequest>,
     ) -> std::result::Result<Response<proto::LsTagsResponse>, Status> {
         tracing::trace!("receive request");
+        self.auth.authorize_request(&request, Access::Read)?;
         let request = request.into_inner();
         let path = relative_path::RelativePath::new(&request.path);
         let entries: crate::Result<Vec<_>> = {
@@ -54,6 +62,7 @@ impl proto::tag_service_server::TagService for TagService {
         &self,
         request: tonic::Request<proto::ResolveTagRequest>,
     ) -> Result<tonic::Response<proto::ResolveTagResponse>, tonic::Status> {
+        self.auth.authorize_request(&request, Access::Read)?;
         let request = request.into_inner();
         let tag_spec = proto::handle_error!(request.tag_spec.parse());
         let tag = proto::handle_error!(
@@ -69,6 +78,7 @@ impl proto::tag_service_server::TagService for TagService {
         &self,
         request: tonic::Request<proto::FindTagsRequest>,
     ) -> Result<tonic::Response<proto::FindTagsResponse>, tonic::Status> {
+        self.auth.authorize_request(&request, Access::Read)?;
         let request = request.into_inner();
         let digest = proto::handle_error!(convert_digest(request.digest));
         let mut results = self
@@ -87,6 +97,7 @@ impl proto::tag_service_server::TagService for TagService {
         &self,
         request: tonic::Request<proto::IterTagSpecsRequest>,
     ) -> Result<tonic::Response<proto::IterTagSpecsResponse>, tonic::Status> {
+        self.auth.authorize_request(&request, Access::Read)?;
         let request = request.into_inner();
         let mut streams = self
             .repo
@@ -106,6 +117,7 @@ impl proto::tag_service_server::TagService for TagService {
         &self,
         request: tonic::Request<proto::ReadTagRequest>,
     ) -> Result<tonic::Response<proto::ReadTagResponse>, tonic::Status> {
+        self.auth.authorize_request(&request, Access::Read)?;
         let request = request.into_inner();
         let tag_spec = proto::handle_error!(request.tag_spec.parse());
         let stream = proto::handle_error!(
@@ -124,6 +136,10 @@ impl proto::tag_service_server::TagService for TagService {
         &self,
         request: tonic::Request<proto::InsertTagRequest>,
     ) -> Result<tonic::Response<proto::InsertTagResponse>, tonic::Status> {
+        self.auth.authorize_request(
+            &request,
+            Access::WriteTags(string_to_namespace(&request.get_ref().namespace)),
+        )?;
         let request = request.into_inner();
         let tag = proto::handle_error!(request.tag.try_into());
         proto::handle_error!(
@@ -139,6 +155,10 @@ impl proto::tag_service_server::TagService for TagService {
         &self,
         request: tonic::Request<proto::RemoveTagStreamRequest>,
     ) -> Result<tonic::Response<proto::RemoveTagStreamResponse>, tonic::Status> {
+        self.auth.authorize_request(
+            &request,
+            Access::WriteTags(string_to_namespace(&request.get_ref().namespace)),
+        )?;
         let request = request.into_inner();
         let tag_spec = proto::handle_error!(request.tag_spec.parse());
         proto::handle_error!(
@@ -155,6 +175,10 @@ impl proto::tag_service_server::TagService for TagService {
         &self,
         request: tonic::Request<proto::RemoveTagRequest>,
     ) -> Result<tonic::Response<proto::RemoveTagResponse>, tonic::Status> {
+        self.auth.authorize_request(
+            &request,
+            Access::WriteTags(string_to_namespace(&request.get_ref().namespace)),
+        )?;
         let request = request.into_inner();
         let tag = proto::handle_error!(request.tag.try_into());
         proto::handle_error!(
@@ -166,14 +190,56 @@ impl proto::tag_service_server::TagService for TagService {
         let data = proto::RemoveTagResponse::ok(proto::Ok {});
         Ok(Response::new(data))
     }
+
+    async fn watch_tag_changes(
+        &self,
+        request: tonic::Request<proto::WatchTagChangesRequest>,
+    ) -> Result<tonic::Response<Self::WatchTagChangesStream>, tonic::Status> {
+        self.auth.authorize_request(&request, Access::Read)?;
+        let request = request.into_inner();
+        let namespace = string_to_namespace(&request.namespace).map(ToOwned::to_owned);
+        let prefix = RelativePathBuf::from(request.prefix);
+        let changes: storage::TagChangeStream<'static> = if request.follow {
+            storage::follow_tag_changes(Arc::clone(&self.repo), namespace, prefix, request.since)
+        } else {
+            let changes = self
+                .repo
+                .read_tag_changes_in_namespace(namespace.as_deref(), request.since);
+            Box::pin(
+                changes.try_filter(move |change| ready(change.spec.path().starts_with(&prefix))),
+            )
+        };
+        let stream = changes
+            .map(|result| {
+                proto::WatchTagChangesResponse::from_result(
+                    result.map(|change| proto::TagChange::from(&change)),
+                )
+            })
+            .map(Ok);
+        let stream: Self::WatchTagChangesStream = Box::pin(stream);
+        Ok(Response::new(stream))
+    }
 }
 
 impl TagService {
     pub fn new(repo: Arc<storage::RepositoryHandle>) -> Self {
-        Self { repo }
+        Self {
+            repo,
+            auth: Auth::default(),
+        }
     }
 
     pub fn new_srv(repo: Arc<storage::RepositoryHandle>) -> TagServiceServer<Self> {
-        TagServiceServer::new(Self::new(repo))
+        Self::new(repo).into_srv()
+    }
+
+    /// Require that clients are authorized by the given policy
+    pub fn with_auth(mut self, auth: Auth) -> Self {
+        self.auth = auth;
+        self
+    }
+
+    pub fn into_srv(self) -> TagServiceServer<Self> {
+        TagServiceServer::new(self)
     }
 }
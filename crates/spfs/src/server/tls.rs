@@ -0,0 +1,145 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+//! Serving the gRPC and http payload protocols over TLS
+
+use std::path::Path;
+use std::sync::Arc;
+
+use futures::{Stream, StreamExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_rustls::server::TlsStream;
+use tokio_rustls::TlsAcceptor;
+use tokio_stream::wrappers::TcpListenerStream;
+
+use crate::{Error, Result};
+
+#[cfg(test)]
+#[path = "./tls_test.rs"]
+mod tls_test;
+
+/// The maximum number of TLS handshakes that are processed
+/// at the same time for new http connections
+const MAX_CONCURRENT_HANDSHAKES: usize = 64;
+
+/// The certificates and keys used to serve requests over TLS.
+///
+/// When a client certificate authority is given, clients are asked
+/// to present a certificate signed by it (mutual TLS). The certificate
+/// is still optional so that clients can identify themselves in other
+/// ways, see [`super::auth`].
+#[derive(Clone)]
+pub struct TlsConfig {
+    grpc: tonic::transport::ServerTlsConfig,
+    acceptor: TlsAcceptor,
+}
+
+impl TlsConfig {
+    /// Load the PEM-encoded certificate chain and private key of the server,
+    /// and optionally a bundle of authorities used to verify client certificates
+    pub async fn load(
+        certificate: &Path,
+        private_key: &Path,
+        client_ca: Option<&Path>,
+    ) -> Result<Self> {
+        let certificate = read_pem_file(certificate).await?;
+        let private_key = read_pem_file(private_key).await?;
+        let client_ca = match client_ca {
+            Some(path) => Some(read_pem_file(path).await?),
+            None => None,
+        };
+
+        let mut grpc = tonic::transport::ServerTlsConfig::new().identity(
+            tonic::transport::Identity::from_pem(&certificate, &private_key),
+        );
+        if let Some(client_ca) = &client_ca {
+            grpc = grpc
+                .client_ca_root(tonic::transport::Certificate::from_pem(client_ca))
+                .client_auth_optional(true);
+        }
+
+        let cert_chain = rustls_pemfile::certs(&mut certificate.as_slice())
+            .collect::<std::io::Result<Vec<_>>>()
+            .map_err(|err| Error::InvalidTlsConfig(format!("invalid certificate: {err}")))?;
+        let key = rustls_pemfile::private_key(&mut private_key.as_slice())
+            .map_err(|err| Error::InvalidTlsConfig(format!("invalid private key: {err}")))?
+            .ok_or_else(|| Error::InvalidTlsConfig("no private key found".into()))?;
+        let builder = rustls::ServerConfig::builder();
+        let builder = match &client_ca {
+            None => builder.with_no_client_auth(),
+            Some(client_ca) => {
+                let mut roots = rustls::RootCertStore::empty();
+                for cert in rustls_pemfile::certs(&mut client_ca.as_slice()) {
+                    let cert = cert.map_err(|err| {
+                        Error::InvalidTlsConfig(format!("invalid client ca certificate: {err}"))
+                    })?;
+                    roots.add(cert).map_err(|err| {
+                        Error::InvalidTlsConfig(format!("invalid client ca certificate: {err}"))
+                    })?;
+                }
+                let verifier = rustls::server::WebPkiClientVerifier::builder(Arc::new(roots))
+                    .allow_unauthenticated()
+                    .build()
+                    .map_err(|err| Error::InvalidTlsConfig(err.to_string()))?;
+                builder.with_client_cert_verifier(verifier)
+            }
+        };
+        let server_config = builder
+            .with_single_cert(cert_chain, key)
+            .map_err(|err| Error::InvalidTlsConfig(err.to_string()))?;
+
+        Ok(Self {
+            grpc,
+            acceptor: TlsAcceptor::from(Arc::new(server_config)),
+        })
+    }
+
+    /// The configuration to use for a gRPC server
+    pub fn grpc_config(&self) -> tonic::transport::ServerTlsConfig {
+        self.grpc.clone()
+    }
+
+    /// Accept TLS connections from clients of the given listener.
+    ///
+    /// Connections that fail to complete the TLS handshake
+    /// are logged and dropped.
+    pub fn incoming(
+        &self,
+        listener: TcpListener,
+    ) -> impl Stream<Item = std::io::Result<TlsStream<TcpStream>>> {
+        let acceptor = self.acceptor.clone();
+        TcpListenerStream::new(listener)
+            .filter_map(|res| async move {
+                res.map_err(|err| tracing::warn!(?err, "failed to accept connection"))
+                    .ok()
+            })
+            .map(move |stream| acceptor.accept(stream))
+            .buffer_unordered(MAX_CONCURRENT_HANDSHAKES)
+            .filter_map(|res| async move {
+                match res {
+                    Ok(stream) => Some(Ok(stream)),
+                    Err(err) => {
+                        tracing::debug!(?err, "TLS handshake failed");
+                        None
+                    }
+                }
+            })
+    }
+}
+
+/// The DER-encoded certificates presented by the client of a connection
+pub(super) fn peer_certificates(stream: &TlsStream<TcpStream>) -> Vec<Vec<u8>> {
+    stream
+        .get_ref()
+        .1
+        .peer_certificates()
+        .map(|certs| certs.iter().map(|cert| cert.to_vec()).collect())
+        .unwrap_or_default()
+}
+
+async fn read_pem_file(path: &Path) -> Result<Vec<u8>> {
+    tokio::fs::read(path)
+        .await
+        .map_err(|err| Error::InvalidTlsConfig(format!("failed to read {}: {err}", path.display())))
+}
@@ -0,0 +1,184 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use std::path::{Path, PathBuf};
+
+use rstest::rstest;
+use tokio::io::AsyncReadExt;
+
+use super::TlsConfig;
+use crate::config::{AuthGrant, Permission, ServerAuth};
+use crate::fixtures::*;
+use crate::prelude::*;
+use crate::server::Auth;
+use crate::storage::rpc::{Config, Params, RpcRepository};
+use crate::storage::RepositoryHandle;
+use crate::Error;
+
+/// The PEM files of a certificate and its private key
+struct Pem {
+    cert: PathBuf,
+    key: PathBuf,
+}
+
+fn write_pem(dir: &Path, name: &str, cert: &str, key: &str) -> Pem {
+    let pem = Pem {
+        cert: dir.join(format!("{name}.crt")),
+        key: dir.join(format!("{name}.key")),
+    };
+    std::fs::write(&pem.cert, cert).unwrap();
+    std::fs::write(&pem.key, key).unwrap();
+    pem
+}
+
+/// Create a certificate authority along with a server and
+/// client certificate that are signed by it.
+fn generate_certificates(dir: &Path) -> (Pem, Pem, Pem) {
+    let mut params = rcgen::CertificateParams::new(Vec::new());
+    params.is_ca = rcgen::IsCa::Ca(rcgen::BasicConstraints::Unconstrained);
+    params.key_usages = vec![
+        rcgen::KeyUsagePurpose::KeyCertSign,
+        rcgen::KeyUsagePurpose::DigitalSignature,
+    ];
+    let ca = rcgen::Certificate::from_params(params).unwrap();
+    let ca_pem = write_pem(
+        dir,
+        "ca",
+        &ca.serialize_pem().unwrap(),
+        &ca.serialize_private_key_pem(),
+    );
+
+    let mut params = rcgen::CertificateParams::new(Vec::new());
+    params.subject_alt_names = vec![rcgen::SanType::IpAddress([127, 0, 0, 1].into())];
+    params.extended_key_usages = vec![rcgen::ExtendedKeyUsagePurpose::ServerAuth];
+    let server = rcgen::Certificate::from_params(params).unwrap();
+    let server_pem = write_pem(
+        dir,
+        "server",
+        &server.serialize_pem_with_signer(&ca).unwrap(),
+        &server.serialize_private_key_pem(),
+    );
+
+    let mut params = rcgen::CertificateParams::new(Vec::new());
+    params.extended_key_usages = vec![rcgen::ExtendedKeyUsagePurpose::ClientAuth];
+    let client = rcgen::Certificate::from_params(params).unwrap();
+    let client_pem = write_pem(
+        dir,
+        "client",
+        &client.serialize_pem_with_signer(&ca).unwrap(),
+        &client.serialize_private_key_pem(),
+    );
+    (ca_pem, server_pem, client_pem)
+}
+
+/// The sha256 fingerprint of the first certificate in a PEM file
+fn fingerprint(path: &Path) -> String {
+    let pem = std::fs::read(path).unwrap();
+    let cert = rustls_pemfile::certs(&mut pem.as_slice())
+        .next()
+        .unwrap()
+        .unwrap();
+    let digest = ring::digest::digest(&ring::digest::SHA256, &cert);
+    data_encoding::HEXLOWER.encode(digest.as_ref())
+}
+
+async fn connect(address: &url::Url, params: Params) -> crate::Result<RepositoryHandle> {
+    let mut address = address.clone();
+    address.set_query(None);
+    let repo = RpcRepository::new(Config { address, params })
+        .await
+        .map_err(|source| Error::FailedToOpenRepository {
+            repository: "test".into(),
+            source,
+        })?;
+    Ok(repo.into())
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_serve_over_mutual_tls(tmpdir: tempfile::TempDir) {
+    init_logging();
+    let certs = tempfile::tempdir().unwrap();
+    let (ca, server, client) = generate_certificates(certs.path());
+    let tls = TlsConfig::load(&server.cert, &server.key, Some(&ca.cert))
+        .await
+        .expect("should load the generated certificates");
+    let auth = Auth::from_config(&ServerAuth {
+        tokens: Vec::new(),
+        client_certificates: vec![AuthGrant {
+            name: "client".into(),
+            sha256: fingerprint(&client.cert),
+            permissions: vec![Permission::Write],
+        }],
+    });
+    let params = Params {
+        ca_bundle: Some(ca.cert.clone()),
+        client_cert: Some(client.cert.clone()),
+        client_key: Some(client.key.clone()),
+        ..Default::default()
+    };
+    let served = serve_rpc_repo(tmpdir, auth, Some(tls), params).await;
+    let TempRepo::Rpc { repo, .. } = &served else {
+        unreachable!();
+    };
+    let address = repo.address();
+    assert_eq!(address.scheme(), "grpcs");
+
+    let digest = repo
+        .commit_blob(Box::pin(b"some data".as_slice()))
+        .await
+        .expect("should be able to upload over mutual TLS");
+    let (mut payload, _) = repo
+        .open_payload(digest)
+        .await
+        .expect("should be able to download over mutual TLS");
+    let mut data = Vec::new();
+    payload.read_to_end(&mut data).await.unwrap();
+    assert_eq!(data, b"some data");
+
+    // without a client certificate, the connection is still
+    // allowed but the client cannot be identified
+    let anonymous = connect(
+        &address,
+        Params {
+            ca_bundle: Some(ca.cert.clone()),
+            ..Default::default()
+        },
+    )
+    .await
+    .unwrap();
+    let err = anonymous
+        .open_payload(digest)
+        .await
+        .map(|_| ())
+        .expect_err("should not be able to read without a client certificate");
+    assert!(
+        matches!(&err, Error::Tonic(status) if status.code() == tonic::Code::Unauthenticated),
+        "{err:?}"
+    );
+
+    // the server is not trusted without the certificate authority
+    let untrusted = match connect(&address, Default::default()).await {
+        Ok(repo) => repo.open_payload(digest).await.map(|_| ()),
+        Err(err) => Err(err),
+    };
+    untrusted.expect_err("should not trust an unknown server certificate");
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_load_invalid_tls_config() {
+    let certs = tempfile::tempdir().unwrap();
+    let (ca, server, _) = generate_certificates(certs.path());
+    let result = TlsConfig::load(&server.cert, &ca.cert, None).await;
+    assert!(
+        matches!(result, Err(Error::InvalidTlsConfig(_))),
+        "should fail to load a certificate as the private key"
+    );
+    let result = TlsConfig::load(&server.cert, &certs.path().join("missing"), None).await;
+    assert!(
+        matches!(result, Err(Error::InvalidTlsConfig(_))),
+        "should fail to load a missing file"
+    );
+}
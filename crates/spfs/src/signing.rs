@@ -0,0 +1,342 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+//! Signing and verification of layers and platforms.
+//!
+//! Signatures are made with ed25519 keys over the digest of an object.
+//! Each signature is stored as a blob in the same repository as the
+//! object, and is found through a tag that is derived from the signed
+//! digest and the name of the key, eg:
+//!
+//! ```text
+//! spfs-signatures/<digest>/<key-name>
+//! ```
+//!
+//! Storing signatures this way means that objects do not need to change
+//! in order to be signed, and signatures can be added or revoked without
+//! rewriting any existing data.
+
+use std::path::Path;
+
+use ring::signature::{Ed25519KeyPair, KeyPair, UnparsedPublicKey, ED25519};
+use tokio::io::AsyncReadExt;
+
+use crate::prelude::*;
+use crate::{config, encoding, graph, tracking, Error, Result};
+
+#[cfg(test)]
+#[path = "./signing_test.rs"]
+mod signing_test;
+
+/// The tag org under which all signatures are stored
+pub const SIGNATURE_TAG_ORG: &str = "spfs-signatures";
+
+/// Prepended to the digest of an object before it is signed, so that
+/// these signatures cannot be confused with ones made for another purpose
+const SIGNATURE_CONTEXT: &[u8] = b"spfs-object-signature-v1:";
+
+/// A private key that can be used to sign objects
+pub struct SigningKey {
+    name: String,
+    key_pair: Ed25519KeyPair,
+}
+
+impl std::fmt::Debug for SigningKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SigningKey")
+            .field("name", &self.name)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SigningKey {
+    /// Generate a new PKCS#8 document containing a random ed25519 key.
+    ///
+    /// The result can be written to disk and loaded later
+    /// with [`SigningKey::load`].
+    pub fn generate_pkcs8() -> Result<Vec<u8>> {
+        let rng = ring::rand::SystemRandom::new();
+        Ed25519KeyPair::generate_pkcs8(&rng)
+            .map(|doc| doc.as_ref().to_vec())
+            .map_err(|err| Error::String(format!("Failed to generate signing key: {err}")))
+    }
+
+    /// Create a signing key from a DER-encoded PKCS#8 document
+    pub fn from_pkcs8<S: Into<String>>(name: S, pkcs8: &[u8]) -> Result<Self> {
+        let name = name.into();
+        validate_key_name(&name)?;
+        let key_pair = Ed25519KeyPair::from_pkcs8_maybe_unchecked(pkcs8)
+            .map_err(|err| Error::String(format!("Invalid ed25519 signing key: {err}")))?;
+        Ok(Self { name, key_pair })
+    }
+
+    /// Load a signing key from a PKCS#8 file, in either DER or PEM format
+    pub fn load<S: Into<String>>(name: S, path: &Path) -> Result<Self> {
+        let data = std::fs::read(path)
+            .map_err(|err| Error::String(format!("Failed to read {}: {err}", path.display())))?;
+        let der = match std::str::from_utf8(&data) {
+            Ok(text) if text.contains("-----BEGIN") => decode_pem(text)?,
+            _ => data,
+        };
+        Self::from_pkcs8(name, &der)
+    }
+
+    /// The name of this key, as used in signature tags
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The public portion of this key, which can be used to verify its signatures
+    pub fn public_key(&self) -> TrustedKey {
+        TrustedKey {
+            name: self.name.clone(),
+            public_key: self.key_pair.public_key().as_ref().to_vec(),
+        }
+    }
+
+    /// Sign the given object digest
+    pub fn sign(&self, digest: &encoding::Digest) -> Vec<u8> {
+        self.key_pair
+            .sign(&signed_message(digest))
+            .as_ref()
+            .to_vec()
+    }
+}
+
+/// A public key whose signatures are trusted
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TrustedKey {
+    /// The name of the key, as used in signature tags
+    pub name: String,
+    /// The raw ed25519 public key
+    pub public_key: Vec<u8>,
+}
+
+impl TrustedKey {
+    /// Create a trusted key from its hex-encoded public key
+    pub fn from_hex<S: Into<String>>(name: S, public_key: &str) -> Result<Self> {
+        let name = name.into();
+        validate_key_name(&name)?;
+        let public_key = data_encoding::HEXLOWER_PERMISSIVE
+            .decode(public_key.trim().as_bytes())
+            .map_err(|err| {
+                Error::String(format!(
+                    "Invalid public key for trusted key '{name}': {err}"
+                ))
+            })?;
+        Ok(Self { name, public_key })
+    }
+
+    /// The public key of this key as a hex string
+    pub fn to_hex(&self) -> String {
+        data_encoding::HEXLOWER.encode(&self.public_key)
+    }
+
+    /// True if the given signature was made for this digest by this key
+    pub fn verify(&self, digest: &encoding::Digest, signature: &[u8]) -> bool {
+        UnparsedPublicKey::new(&ED25519, &self.public_key)
+            .verify(&signed_message(digest), signature)
+            .is_ok()
+    }
+}
+
+/// The outcome of verifying the signatures of an object
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SignatureStatus {
+    /// The object has a valid signature from the named trusted key
+    Trusted { key_name: String },
+    /// The object has signatures for the named trusted keys, but
+    /// none of them are valid
+    Invalid { key_names: Vec<String> },
+    /// The object has no signature from any trusted key
+    Missing,
+}
+
+impl SignatureStatus {
+    /// True if the object has a valid signature from a trusted key
+    pub fn is_trusted(&self) -> bool {
+        matches!(self, Self::Trusted { .. })
+    }
+
+    /// Combine this status with one found for the same object elsewhere,
+    /// preferring a trusted signature over an invalid one over none at all
+    pub fn combine(self, other: Self) -> Self {
+        match (self, other) {
+            (trusted @ Self::Trusted { .. }, _) | (_, trusted @ Self::Trusted { .. }) => trusted,
+            (Self::Invalid { mut key_names }, Self::Invalid { key_names: more }) => {
+                key_names.extend(more);
+                key_names.sort();
+                key_names.dedup();
+                Self::Invalid { key_names }
+            }
+            (invalid @ Self::Invalid { .. }, _) | (_, invalid @ Self::Invalid { .. }) => invalid,
+            (Self::Missing, Self::Missing) => Self::Missing,
+        }
+    }
+}
+
+impl std::fmt::Display for SignatureStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Trusted { key_name } => write!(f, "signed by {key_name}"),
+            Self::Invalid { key_names } => {
+                write!(f, "invalid signature from {}", key_names.join(", "))
+            }
+            Self::Missing => f.write_str("no trusted signature"),
+        }
+    }
+}
+
+/// Load the signing key identified in the given configuration
+pub fn load_signing_key(config: &config::Signing) -> Result<SigningKey> {
+    let Some(key_file) = &config.key_file else {
+        return Err(Error::String(
+            "No signing key configured, see signing.key_file in the spfs config".into(),
+        ));
+    };
+    let name = config
+        .key_name
+        .as_deref()
+        .unwrap_or(config::DEFAULT_SIGNING_KEY_NAME);
+    SigningKey::load(name, key_file)
+}
+
+/// Load the trusted public keys from the given configuration
+pub fn load_trusted_keys(config: &config::Signing) -> Result<Vec<TrustedKey>> {
+    config
+        .trusted_keys
+        .iter()
+        .map(|key| TrustedKey::from_hex(&key.name, &key.public_key))
+        .collect()
+}
+
+/// The tag used to store the signature of `digest` made by the named key
+pub fn signature_tag(digest: &encoding::Digest, key_name: &str) -> Result<tracking::TagSpec> {
+    // the padding characters of a digest are not allowed in tags
+    let digest = digest.to_string();
+    let digest = digest.trim_end_matches('=');
+    tracking::build_tag_spec(
+        Some(format!("{SIGNATURE_TAG_ORG}/{digest}")),
+        key_name.into(),
+        0,
+    )
+}
+
+/// Sign the identified layer or platform, storing the signature in `repo`.
+pub async fn sign_object<R>(
+    repo: &R,
+    digest: encoding::Digest,
+    key: &SigningKey,
+) -> Result<tracking::Tag>
+where
+    R: Repository + ?Sized,
+{
+    let object = repo.read_object(digest).await?;
+    match object.to_enum() {
+        graph::object::Enum::Layer(_) | graph::object::Enum::Platform(_) => {}
+        _ => {
+            return Err(Error::String(format!(
+                "Only layers and platforms can be signed, {digest} is a {:?}",
+                object.kind()
+            )))
+        }
+    }
+    let signature = key.sign(&digest);
+    let blob = repo
+        .commit_blob(Box::pin(std::io::Cursor::new(signature)))
+        .await?;
+    let tag = signature_tag(&digest, key.name())?;
+    repo.push_tag(&tag, &blob).await
+}
+
+/// Find a valid signature for the identified object in `repo`, made by
+/// any one of the trusted keys.
+pub async fn verify_object<R>(
+    repo: &R,
+    digest: encoding::Digest,
+    trusted_keys: &[TrustedKey],
+) -> Result<SignatureStatus>
+where
+    R: Repository + ?Sized,
+{
+    let mut invalid = Vec::new();
+    for key in trusted_keys {
+        let tag = signature_tag(&digest, &key.name)?;
+        let signature = match repo.resolve_tag(&tag).await {
+            Ok(tag) => tag.target,
+            Err(Error::UnknownReference(_)) => continue,
+            Err(err) => return Err(err),
+        };
+        let (mut reader, _) = match repo.open_payload(signature).await {
+            Ok(payload) => payload,
+            Err(Error::UnknownObject(_)) => {
+                invalid.push(key.name.clone());
+                continue;
+            }
+            Err(err) => return Err(err),
+        };
+        let mut data = Vec::new();
+        reader
+            .read_to_end(&mut data)
+            .await
+            .map_err(|err| Error::String(format!("Failed to read signature: {err}")))?;
+        if key.verify(&digest, &data) {
+            return Ok(SignatureStatus::Trusted {
+                key_name: key.name.clone(),
+            });
+        }
+        invalid.push(key.name.clone());
+    }
+    if invalid.is_empty() {
+        Ok(SignatureStatus::Missing)
+    } else {
+        Ok(SignatureStatus::Invalid { key_names: invalid })
+    }
+}
+
+/// Ensure that the identified object has a trusted signature in `repo`.
+pub async fn ensure_trusted<R>(
+    repo: &R,
+    digest: encoding::Digest,
+    trusted_keys: &[TrustedKey],
+) -> Result<()>
+where
+    R: Repository + ?Sized,
+{
+    match verify_object(repo, digest, trusted_keys).await? {
+        SignatureStatus::Trusted { .. } => Ok(()),
+        status => Err(Error::UntrustedObject { digest, status }),
+    }
+}
+
+fn signed_message(digest: &encoding::Digest) -> Vec<u8> {
+    let mut message = Vec::with_capacity(SIGNATURE_CONTEXT.len() + encoding::DIGEST_SIZE);
+    message.extend_from_slice(SIGNATURE_CONTEXT);
+    message.extend_from_slice(digest.as_bytes());
+    message
+}
+
+fn validate_key_name(name: &str) -> Result<()> {
+    // key names are used as tag names, and so must follow the same rules
+    tracking::TagSpec::parse(name)
+        .ok()
+        .filter(|spec| spec.org().is_none() && spec.version() == 0)
+        .map(|_| ())
+        .ok_or_else(|| {
+            Error::String(format!(
+                "Invalid signing key name '{name}': must only contain alphanumerics, '-', '_' and '.'"
+            ))
+        })
+}
+
+fn decode_pem(text: &str) -> Result<Vec<u8>> {
+    let body: String = text
+        .lines()
+        .filter(|line| !line.starts_with("-----"))
+        .map(str::trim)
+        .collect();
+    data_encoding::BASE64
+        .decode(body.as_bytes())
+        .map_err(|err| Error::String(format!("Invalid PEM encoded signing key: {err}")))
+}
@@ -0,0 +1,145 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use rstest::rstest;
+
+use super::{
+    ensure_trusted,
+    sign_object,
+    signature_tag,
+    verify_object,
+    SignatureStatus,
+    SigningKey,
+    TrustedKey,
+};
+use crate::fixtures::*;
+use crate::prelude::*;
+use crate::Error;
+
+fn generate_key(name: &str) -> SigningKey {
+    let pkcs8 = SigningKey::generate_pkcs8().expect("failed to generate key");
+    SigningKey::from_pkcs8(name, &pkcs8).expect("generated key should be valid")
+}
+
+#[rstest]
+fn test_signature_tag_is_valid() {
+    let digest = random_digest();
+    let tag = signature_tag(&digest, "build-farm").expect("signature tag should be valid");
+    assert_eq!(tag.name(), "build-farm");
+    assert!(tag.org().unwrap().starts_with(super::SIGNATURE_TAG_ORG));
+}
+
+#[rstest]
+fn test_invalid_key_names() {
+    let pkcs8 = SigningKey::generate_pkcs8().unwrap();
+    SigningKey::from_pkcs8("build/farm", &pkcs8).expect_err("key names cannot have an org");
+    SigningKey::from_pkcs8("farm~1", &pkcs8).expect_err("key names cannot have a version");
+    TrustedKey::from_hex("", "00").expect_err("key names cannot be empty");
+}
+
+#[rstest]
+fn test_trusted_key_hex_round_trip() {
+    let key = generate_key("farm").public_key();
+    let parsed = TrustedKey::from_hex("farm", &key.to_hex()).unwrap();
+    assert_eq!(key, parsed);
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_sign_and_verify_layer(#[future] tmprepo: TempRepo) {
+    let tmprepo = tmprepo.await;
+    let manifest = generate_tree(&tmprepo).await.to_graph_manifest();
+    let layer = tmprepo.create_layer(&manifest).await.unwrap();
+    let digest = layer.digest().unwrap();
+
+    let key = generate_key("farm");
+    let trusted = vec![key.public_key()];
+
+    let status = verify_object(&*tmprepo, digest, &trusted).await.unwrap();
+    assert_eq!(status, SignatureStatus::Missing);
+    let err = ensure_trusted(&*tmprepo, digest, &trusted)
+        .await
+        .expect_err("unsigned layer should not be trusted");
+    assert!(matches!(err, Error::UntrustedObject { .. }), "{err:?}");
+
+    sign_object(&*tmprepo, digest, &key).await.unwrap();
+    let status = verify_object(&*tmprepo, digest, &trusted).await.unwrap();
+    assert_eq!(
+        status,
+        SignatureStatus::Trusted {
+            key_name: "farm".into()
+        }
+    );
+    ensure_trusted(&*tmprepo, digest, &trusted)
+        .await
+        .expect("signed layer should be trusted");
+
+    // a different key with the same name must not be able to
+    // validate the signature
+    let imposter = vec![generate_key("farm").public_key()];
+    let status = verify_object(&*tmprepo, digest, &imposter).await.unwrap();
+    assert_eq!(
+        status,
+        SignatureStatus::Invalid {
+            key_names: vec!["farm".into()]
+        }
+    );
+
+    // signatures from other keys are not considered at all
+    let other = vec![generate_key("other").public_key()];
+    let status = verify_object(&*tmprepo, digest, &other).await.unwrap();
+    assert_eq!(status, SignatureStatus::Missing);
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_sign_platform(#[future] tmprepo: TempRepo) {
+    let tmprepo = tmprepo.await;
+    let manifest = generate_tree(&tmprepo).await.to_graph_manifest();
+    let layer = tmprepo.create_layer(&manifest).await.unwrap();
+    let platform = tmprepo
+        .create_platform(crate::graph::Stack::from(layer.digest().unwrap()))
+        .await
+        .unwrap();
+    let digest = platform.digest().unwrap();
+
+    let key = generate_key("farm");
+    sign_object(&*tmprepo, digest, &key).await.unwrap();
+    let status = verify_object(&*tmprepo, digest, &[key.public_key()])
+        .await
+        .unwrap();
+    assert!(status.is_trusted());
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_sign_only_layers_and_platforms(#[future] tmprepo: TempRepo) {
+    let tmprepo = tmprepo.await;
+    let manifest = generate_tree(&tmprepo).await.to_graph_manifest();
+
+    let key = generate_key("farm");
+    sign_object(&*tmprepo, manifest.digest().unwrap(), &key)
+        .await
+        .expect_err("should not be able to sign a manifest");
+}
+
+#[rstest]
+fn test_status_combine() {
+    let trusted = SignatureStatus::Trusted {
+        key_name: "a".into(),
+    };
+    let invalid = SignatureStatus::Invalid {
+        key_names: vec!["b".into()],
+    };
+    assert_eq!(
+        SignatureStatus::Missing.combine(trusted.clone()),
+        trusted.clone()
+    );
+    assert_eq!(invalid.clone().combine(trusted.clone()), trusted);
+    assert_eq!(SignatureStatus::Missing.combine(invalid.clone()), invalid);
+    assert_eq!(
+        SignatureStatus::Missing.combine(SignatureStatus::Missing),
+        SignatureStatus::Missing
+    );
+}
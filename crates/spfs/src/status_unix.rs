@@ -2,7 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0
 // https://github.com/spkenv/spk
 
-use crate::resolve::{resolve_and_render_overlay_dirs, RenderResult};
+use crate::resolve::{ensure_stack_is_signed, resolve_and_render_overlay_dirs, RenderResult};
 use crate::storage::fs::RenderSummary;
 use crate::{bootstrap, env, runtime, Error, Result};
 
@@ -126,6 +126,8 @@ pub async fn change_to_durable_runtime(rt: &mut runtime::Runtime) -> Result<Rend
 /// This function will run blocking IO on the current thread. Although this is not ideal,
 /// the mount namespacing operated per-thread and so restricts our ability to move execution.
 pub async fn reinitialize_runtime(rt: &mut runtime::Runtime) -> Result<RenderSummary> {
+    ensure_stack_is_signed(rt).await?;
+
     let render_result = match rt.config.mount_backend {
         runtime::MountBackend::OverlayFsWithRenders => {
             resolve_and_render_overlay_dirs(rt, false).await?
@@ -195,6 +197,8 @@ pub async fn initialize_runtime(rt: &mut runtime::Runtime) -> Result<RenderSumma
     // the namespace.
     rt.prepare_live_layers().await?;
 
+    ensure_stack_is_signed(rt).await?;
+
     let render_result = match rt.config.mount_backend {
         runtime::MountBackend::OverlayFsWithRenders => {
             resolve_and_render_overlay_dirs(
@@ -243,6 +247,10 @@ pub async fn initialize_runtime(rt: &mut runtime::Runtime) -> Result<RenderSumma
         }
         #[cfg(feature = "fuse-backend")]
         runtime::MountBackend::FuseOnly => {
+            // the runtime dir holds the upper dir, where
+            // edits are stored by the fuse filesystem
+            with_root.mount_runtime(&rt.config)?;
+            with_root.setup_runtime(rt).await?;
             with_root.mount_env_fuse(rt).await?;
         }
         #[allow(unreachable_patterns)]
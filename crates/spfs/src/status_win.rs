@@ -2,6 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0
 // https://github.com/spkenv/spk
 
+use crate::resolve::ensure_stack_is_signed;
 use crate::storage::fs::RenderSummary;
 use crate::{env, runtime, Error, Result};
 
@@ -39,6 +40,8 @@ pub async fn reinitialize_runtime(_rt: &mut runtime::Runtime) -> Result<RenderSu
 /// This function will run blocking IO on the current thread. Although this is not ideal,
 /// the mount namespacing operated per-thread and so restricts our ability to move execution.
 pub async fn initialize_runtime(rt: &mut runtime::Runtime) -> Result<RenderSummary> {
+    ensure_stack_is_signed(rt).await?;
+
     tracing::debug!("computing runtime manifest");
     let manifest = super::compute_runtime_manifest(rt).await?;
 
@@ -0,0 +1,162 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+//! Content-defined chunking of large payloads.
+//!
+//! A large payload can be stored as a sequence of smaller chunks, where
+//! each chunk is itself stored as a payload. The boundaries between chunks
+//! are chosen based on the content of the data, so that payloads which
+//! differ by only a few bytes share most of their chunks. The
+//! [`ChunkIndex`] for a payload records the chunks that make up its
+//! full content and is identified by the digest of that full content,
+//! meaning that blobs refer to chunked and whole payloads in the same way.
+
+use std::future::Future;
+use std::pin::Pin;
+
+use futures::{Stream, TryStreamExt};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use crate::encoding::prelude::*;
+use crate::tracking::BlobRead;
+use crate::{encoding, Error, Result};
+
+#[cfg(test)]
+#[path = "./chunking_test.rs"]
+mod chunking_test;
+
+/// The smallest chunk that will be cut from a payload, except at its end
+pub const MIN_CHUNK_SIZE: u32 = 256 * 1024;
+/// The desired average size of the chunks cut from a payload
+pub const AVG_CHUNK_SIZE: u32 = 1024 * 1024;
+/// The largest chunk that will be cut from a payload
+pub const MAX_CHUNK_SIZE: u32 = 4 * 1024 * 1024;
+
+const CHUNK_INDEX_HEADER: &[u8] = b"--SPFS-CHUNKS--";
+
+/// One contiguous piece of a chunked payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Chunk {
+    /// The digest of the chunk data, which is stored as its own payload
+    pub digest: encoding::Digest,
+    /// The size of the chunk data in bytes
+    pub size: u64,
+}
+
+/// Describes how the full content of a payload is assembled from chunks.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkIndex {
+    digest: encoding::Digest,
+    chunks: Vec<Chunk>,
+}
+
+impl ChunkIndex {
+    pub fn new(digest: encoding::Digest, chunks: Vec<Chunk>) -> Self {
+        Self { digest, chunks }
+    }
+
+    /// The digest of the full payload content.
+    pub fn digest(&self) -> &encoding::Digest {
+        &self.digest
+    }
+
+    /// The chunks of the payload, in order.
+    pub fn chunks(&self) -> &[Chunk] {
+        &self.chunks
+    }
+
+    /// The size of the full payload content in bytes.
+    pub fn size(&self) -> u64 {
+        self.chunks.iter().map(|c| c.size).sum()
+    }
+}
+
+impl Encodable for ChunkIndex {
+    type Error = Error;
+
+    fn encode(&self, writer: &mut impl std::io::Write) -> Result<()> {
+        encoding::write_header(&mut *writer, CHUNK_INDEX_HEADER)?;
+        encoding::write_digest(&mut *writer, &self.digest)?;
+        encoding::write_uint64(&mut *writer, self.chunks.len() as u64)?;
+        for chunk in self.chunks.iter() {
+            encoding::write_digest(&mut *writer, &chunk.digest)?;
+            encoding::write_uint64(&mut *writer, chunk.size)?;
+        }
+        Ok(())
+    }
+}
+
+impl Decodable for ChunkIndex {
+    fn decode(reader: &mut impl std::io::BufRead) -> Result<Self> {
+        encoding::consume_header(&mut *reader, CHUNK_INDEX_HEADER)?;
+        let digest = encoding::read_digest(&mut *reader)?;
+        let count = encoding::read_uint64(&mut *reader)?;
+        let mut chunks = Vec::new();
+        for _ in 0..count {
+            chunks.push(Chunk {
+                digest: encoding::read_digest(&mut *reader)?,
+                size: encoding::read_uint64(&mut *reader)?,
+            });
+        }
+        Ok(Self { digest, chunks })
+    }
+}
+
+/// Split the data from the given reader into content-defined chunks.
+///
+/// Each item is the digest and data of the next chunk in sequence.
+pub fn split_into_chunks<R>(
+    reader: R,
+) -> impl Stream<Item = Result<(encoding::Digest, Vec<u8>)>> + Send
+where
+    R: AsyncRead + Unpin + Send,
+{
+    async_stream::try_stream! {
+        let mut chunker = fastcdc::v2020::AsyncStreamCDC::new(
+            reader,
+            MIN_CHUNK_SIZE,
+            AVG_CHUNK_SIZE,
+            MAX_CHUNK_SIZE,
+        );
+        let mut chunks = Box::pin(chunker.as_stream().map_err(|err| match err {
+            fastcdc::v2020::Error::IoError(err) => Error::Encoding(encoding::Error::FailedRead(err)),
+            err => Error::String(err.to_string()),
+        }));
+        while let Some(chunk) = chunks.try_next().await? {
+            let mut hasher = encoding::Hasher::<std::io::Sink>::default();
+            hasher.update(&chunk.data);
+            yield (hasher.digest(), chunk.data);
+        }
+    }
+}
+
+/// Read the full content of a chunked payload, one chunk at a time.
+///
+/// Each chunk is opened with the given function, which should return
+/// None if the chunk's payload does not exist.
+pub(crate) fn read_chunks<F, Fut>(
+    index: ChunkIndex,
+    open_chunk: F,
+) -> impl Stream<Item = std::io::Result<bytes::Bytes>> + Send + Sync + 'static
+where
+    F: Fn(encoding::Digest) -> Fut + Send + 'static,
+    Fut: Future<Output = Result<Option<Pin<Box<dyn BlobRead>>>>> + Send,
+{
+    sync_wrapper::SyncStream::new(async_stream::try_stream! {
+        for chunk in index.chunks() {
+            let mut reader = open_chunk(chunk.digest)
+                .await
+                .map_err(std::io::Error::other)?
+                .ok_or_else(|| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::NotFound,
+                        format!("missing payload chunk {}", chunk.digest),
+                    )
+                })?;
+            let mut data = Vec::with_capacity(chunk.size as usize);
+            reader.read_to_end(&mut data).await?;
+            yield bytes::Bytes::from(data);
+        }
+    })
+}
@@ -0,0 +1,182 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use futures::TryStreamExt;
+use rstest::rstest;
+use tokio::io::AsyncReadExt;
+
+use super::{split_into_chunks, Chunk, ChunkIndex, MAX_CHUNK_SIZE};
+use crate::fixtures::*;
+use crate::prelude::*;
+use crate::storage::fs::OpenFsRepository;
+use crate::{encoding, tracking};
+
+const DATA_SIZE: usize = 12 * 1024 * 1024;
+
+fn digest_of(data: &[u8]) -> encoding::Digest {
+    let mut hasher = encoding::Hasher::<std::io::Sink>::default();
+    hasher.update(data);
+    hasher.digest()
+}
+
+async fn split(data: &[u8]) -> Vec<(encoding::Digest, Vec<u8>)> {
+    split_into_chunks(data).try_collect().await.unwrap()
+}
+
+#[rstest]
+fn test_chunk_index_encoding_round_trip() {
+    let index = ChunkIndex::new(
+        random_digest(),
+        vec![
+            Chunk {
+                digest: random_digest(),
+                size: 10,
+            },
+            Chunk {
+                digest: random_digest(),
+                size: 20,
+            },
+        ],
+    );
+    let data = index.encode_to_bytes().unwrap();
+    let decoded = ChunkIndex::decode(&mut data.as_slice()).unwrap();
+    assert_eq!(decoded, index);
+    assert_eq!(decoded.size(), 30);
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_split_into_chunks_is_deterministic() {
+    let data = random_data(DATA_SIZE);
+    let chunks = split(&data).await;
+    assert!(chunks.len() > 1, "large data should be split");
+    assert!(chunks
+        .iter()
+        .all(|(_, chunk)| chunk.len() <= MAX_CHUNK_SIZE as usize));
+    let joined: Vec<u8> = chunks.iter().flat_map(|(_, c)| c.iter().copied()).collect();
+    assert!(joined == data, "chunks should reassemble the data");
+    for (digest, chunk) in chunks.iter() {
+        assert_eq!(*digest, digest_of(chunk));
+    }
+
+    let again = split(&data).await;
+    let digests =
+        |chunks: &[(encoding::Digest, Vec<u8>)]| chunks.iter().map(|(d, _)| *d).collect::<Vec<_>>();
+    assert_eq!(digests(&chunks), digests(&again));
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_split_into_chunks_shares_unchanged_content() {
+    let data = random_data(DATA_SIZE);
+    let mut modified = data.clone();
+    modified.splice(DATA_SIZE / 2..DATA_SIZE / 2, b"inserted".iter().copied());
+
+    let original = split(&data).await;
+    let changed = split(&modified).await;
+    let shared = changed
+        .iter()
+        .filter(|(d, _)| original.iter().any(|(o, _)| o == d))
+        .count();
+    assert!(
+        shared >= original.len() - 2,
+        "only the chunks around the change should differ"
+    );
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_write_chunked_payload(tmpdir: tempfile::TempDir) {
+    init_logging();
+    let mut repo = OpenFsRepository::create(tmpdir.path()).await.unwrap();
+    repo.set_payload_chunking_threshold(Some(MAX_CHUNK_SIZE as u64));
+    let data = random_data(DATA_SIZE);
+    let digest = repo
+        .commit_blob(Box::pin(std::io::Cursor::new(data.clone())))
+        .await
+        .unwrap();
+
+    assert_eq!(digest, digest_of(&data), "blob digest should not change");
+    assert!(
+        !repo.payloads.has_digest(&digest),
+        "whole payload should be replaced by its chunks"
+    );
+    let index = repo
+        .read_chunk_index(digest)
+        .await
+        .unwrap()
+        .expect("payload should have a chunk index");
+    assert_eq!(index.size(), DATA_SIZE as u64);
+    assert!(repo.has_payload(digest).await);
+
+    let (mut reader, _) = repo.open_payload(digest).await.unwrap();
+    let mut read = Vec::new();
+    reader.read_to_end(&mut read).await.unwrap();
+    assert!(
+        read == data,
+        "chunked payload should read back as the original"
+    );
+
+    let digests: Vec<_> = repo.iter_payload_digests().try_collect().await.unwrap();
+    assert!(digests.contains(&digest));
+    for chunk in index.chunks() {
+        assert!(digests.contains(&chunk.digest));
+    }
+
+    repo.remove_payload(digest).await.unwrap();
+    assert!(repo.read_chunk_index(digest).await.unwrap().is_none());
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_small_payload_not_chunked(tmpdir: tempfile::TempDir) {
+    init_logging();
+    let mut repo = OpenFsRepository::create(tmpdir.path()).await.unwrap();
+    repo.set_payload_chunking_threshold(Some(0));
+    let digest = repo
+        .commit_blob(Box::pin(b"small data".as_slice()))
+        .await
+        .unwrap();
+
+    assert!(repo.payloads.has_digest(&digest));
+    assert!(repo.read_chunk_index(digest).await.unwrap().is_none());
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_render_chunked_manifest(tmpdir: tempfile::TempDir) {
+    init_logging();
+    let mut storage = OpenFsRepository::create(tmpdir.path().join("storage"))
+        .await
+        .unwrap();
+    storage.set_payload_chunking_threshold(Some(0));
+    let src_dir = tmpdir.path().join("source");
+    std::fs::create_dir_all(&src_dir).unwrap();
+    std::fs::write(src_dir.join("large.bin"), random_data(DATA_SIZE)).unwrap();
+    ensure(src_dir.join("small.txt"), "smalldata");
+
+    let manifest = tracking::compute_manifest(&src_dir).await.unwrap();
+    for node in manifest.walk_abs(src_dir.to_str().unwrap()) {
+        if node.entry.kind.is_blob() {
+            let data = tokio::fs::File::open(&node.path.to_path("/"))
+                .await
+                .unwrap();
+            storage
+                .commit_blob(Box::pin(tokio::io::BufReader::new(data)))
+                .await
+                .unwrap();
+        }
+    }
+
+    let expected = manifest.to_graph_manifest();
+    let rendered_path = crate::storage::fs::Renderer::new(&storage)
+        .render_manifest(&expected, None)
+        .await
+        .expect("should successfully render manifest");
+    let actual = tracking::compute_manifest(rendered_path)
+        .await
+        .unwrap()
+        .to_graph_manifest();
+    assert_eq!(actual.digest().unwrap(), expected.digest().unwrap());
+}
@@ -11,6 +11,7 @@ use crate::{encoding, graph};
 #[rstest]
 #[case::fs(tmprepo("fs"))]
 #[case::tar(tmprepo("tar"))]
+#[case::s3(tmprepo("s3"))]
 #[cfg_attr(feature = "server", case::rpc(tmprepo("rpc")))]
 #[tokio::test]
 async fn test_object_existence(
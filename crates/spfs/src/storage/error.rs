@@ -90,6 +90,20 @@ pub enum OpenRepositoryError {
         #[from]
         source: tonic::transport::Error,
     },
+    #[error("Failed to configure the object store client")]
+    FailedToConfigureObjectStore {
+        #[from]
+        source: object_store::Error,
+    },
+    #[error("Failed to configure the http client")]
+    FailedToConfigureHttpClient {
+        #[from]
+        source: reqwest::Error,
+    },
+    #[error("Invalid repository credentials: {0}")]
+    InvalidCredentials(String),
+    #[error("Invalid TLS configuration: {0}")]
+    InvalidTlsConfig(String),
     #[error("Pinned repository is read only")]
     RepositoryIsPinned,
 
@@ -15,7 +15,15 @@ use crate::graph::ObjectProto;
 use crate::prelude::*;
 use crate::storage::fs::{FsHashStore, ManifestRenderPath, OpenFsRepository, RenderStore};
 use crate::storage::tag::TagSpecAndTagStream;
-use crate::storage::{EntryType, LocalRepository, TagNamespace, TagNamespaceBuf, TagStorageMut};
+use crate::storage::{
+    ChunkIndex,
+    EntryType,
+    LocalRepository,
+    TagChangeStream,
+    TagNamespace,
+    TagNamespaceBuf,
+    TagStorageMut,
+};
 use crate::tracking::BlobRead;
 use crate::{encoding, graph, storage, tracking, Error, Result};
 
@@ -83,6 +91,11 @@ impl FallbackProxy {
             secondary,
         }
     }
+
+    /// The repositories that are used to repair missing data
+    pub fn secondary(&self) -> &[crate::storage::RepositoryHandle] {
+        &self.secondary
+    }
 }
 
 #[async_trait::async_trait]
@@ -282,6 +295,20 @@ impl PayloadStorage for FallbackProxy {
         self.primary.remove_payload(digest).await?;
         Ok(())
     }
+
+    fn supports_chunked_payloads(&self) -> bool {
+        self.primary.supports_chunked_payloads()
+    }
+
+    async fn read_chunk_index(&self, digest: encoding::Digest) -> Result<Option<ChunkIndex>> {
+        self.primary.read_chunk_index(digest).await
+    }
+
+    async unsafe fn write_chunk_index(&self, index: &ChunkIndex) -> Result<()> {
+        // Safety: we are wrapping the same underlying unsafe function and
+        // so the same safety holds for our callers
+        unsafe { self.primary.write_chunk_index(index).await }
+    }
 }
 
 #[async_trait::async_trait]
@@ -350,6 +377,24 @@ impl TagStorage for FallbackProxy {
         self.primary.remove_tag_in_namespace(namespace, tag).await?;
         Ok(())
     }
+
+    fn read_tag_changes_in_namespace(
+        &self,
+        namespace: Option<&TagNamespace>,
+        since: u64,
+    ) -> TagChangeStream<'static> {
+        self.primary.read_tag_changes_in_namespace(namespace, since)
+    }
+
+    fn watch_tag_changes_in_namespace(
+        &self,
+        namespace: Option<&TagNamespace>,
+        prefix: &RelativePath,
+        since: u64,
+    ) -> TagChangeStream<'_> {
+        self.primary
+            .watch_tag_changes_in_namespace(namespace, prefix, since)
+    }
 }
 
 impl TagStorageMut for FallbackProxy {
@@ -387,6 +432,11 @@ impl LocalRepository for FallbackProxy {
         self.primary.payloads()
     }
 
+    #[inline]
+    fn compressed_payloads(&self) -> &FsHashStore {
+        self.primary.compressed_payloads()
+    }
+
     #[inline]
     fn render_store(&self) -> Result<&RenderStore> {
         self.primary.render_store()
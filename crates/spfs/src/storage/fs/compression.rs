@@ -0,0 +1,75 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use std::pin::Pin;
+
+use serde::{Deserialize, Serialize};
+
+use crate::tracking::BlobRead;
+
+#[cfg(test)]
+#[path = "./compression_test.rs"]
+mod compression_test;
+
+/// The directory in the root of a repository that holds payloads
+/// which are stored as zstd frames.
+///
+/// Compressed payloads are kept apart from the `payloads` directory so
+/// that the compression of each payload is known from where it is
+/// stored. Versions of spfs that predate compression do not look in
+/// this directory, and report these payloads as missing rather than
+/// reading the compressed data.
+pub(crate) const ZSTD_PAYLOADS_DIRNAME: &str = "payloads-zstd";
+
+/// Identifies how payload data is compressed when stored on disk.
+///
+/// Payload compression is transparent to the rest of spfs. The digest
+/// of a payload is always computed from its uncompressed contents.
+#[derive(
+    Default,
+    Clone,
+    Copy,
+    Debug,
+    Eq,
+    PartialEq,
+    strum::Display,
+    strum::EnumString,
+    strum::VariantNames,
+    Serialize,
+    Deserialize,
+)]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum PayloadCompression {
+    /// Payloads are stored as-is
+    #[default]
+    None,
+    /// Payloads are stored as a single zstd frame
+    Zstd,
+}
+
+impl PayloadCompression {
+    /// True if payloads written with this mode are not stored as-is
+    pub fn is_compressed(&self) -> bool {
+        !matches!(self, Self::None)
+    }
+}
+
+/// Wrap a reader over stored payload data so that it yields the
+/// original, uncompressed contents.
+pub(crate) fn decompress_payload<R>(
+    reader: tokio::io::BufReader<R>,
+    compression: PayloadCompression,
+) -> Pin<Box<dyn BlobRead>>
+where
+    R: tokio::io::AsyncRead + Send + Sync + Unpin + 'static,
+{
+    match compression {
+        PayloadCompression::None => Box::pin(reader),
+        PayloadCompression::Zstd => {
+            let decoder = async_compression::tokio::bufread::ZstdDecoder::new(reader);
+            Box::pin(tokio::io::BufReader::new(decoder))
+        }
+    }
+}
@@ -0,0 +1,186 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use std::io::Write;
+
+use rstest::rstest;
+use tokio::io::AsyncReadExt;
+
+use super::PayloadCompression;
+use crate::fixtures::*;
+use crate::prelude::*;
+use crate::storage::fs::migrations::compress_payloads;
+use crate::storage::fs::{Config, OpenFsRepository, Params};
+use crate::storage::FromConfig;
+use crate::tracking;
+
+/// Every zstd frame begins with these bytes
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+const DATA: &[u8] = b"some data that is compressed, some data that is compressed";
+
+fn expected_digest() -> crate::encoding::Digest {
+    let mut hasher = crate::encoding::Hasher::<std::io::Sink>::default();
+    hasher.write_all(DATA).unwrap();
+    hasher.digest()
+}
+
+async fn read_payload(repo: &OpenFsRepository, digest: crate::encoding::Digest) -> Vec<u8> {
+    let (mut reader, _) = repo.open_payload(digest).await.unwrap();
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data).await.unwrap();
+    data
+}
+
+async fn payload_is_compressed(repo: &OpenFsRepository, digest: crate::encoding::Digest) -> bool {
+    if repo.payloads.has_digest(&digest) {
+        return false;
+    }
+    let path = repo.compressed_payloads.build_digest_path(&digest);
+    tokio::fs::read(path)
+        .await
+        .unwrap()
+        .starts_with(&ZSTD_MAGIC)
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_compression_defaults_to_none(tmpdir: tempfile::TempDir) {
+    init_logging();
+    let repo = OpenFsRepository::create(tmpdir.path()).await.unwrap();
+    assert_eq!(repo.payload_compression(), PayloadCompression::None);
+
+    let digest = repo.commit_blob(Box::pin(DATA)).await.unwrap();
+    assert!(repo.payloads.has_digest(&digest));
+    assert!(!repo.compressed_payloads.root().exists());
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_compressed_payload_round_trip(tmpdir: tempfile::TempDir) {
+    init_logging();
+    let repo = OpenFsRepository::from_config(Config {
+        path: tmpdir.path().to_owned(),
+        params: Params {
+            create: true,
+            compression: Some(PayloadCompression::Zstd),
+            ..Default::default()
+        },
+    })
+    .await
+    .unwrap();
+    assert_eq!(repo.payload_compression(), PayloadCompression::Zstd);
+
+    let digest = repo.commit_blob(Box::pin(DATA)).await.unwrap();
+    assert_eq!(
+        digest,
+        expected_digest(),
+        "digest should be computed from the uncompressed data"
+    );
+    assert!(payload_is_compressed(&repo, digest).await);
+    assert_eq!(read_payload(&repo, digest).await, DATA);
+
+    // compressed payloads can be read by any client, no matter
+    // which compression it writes new payloads with
+    let reopened = OpenFsRepository::open(tmpdir.path()).await.unwrap();
+    assert_eq!(reopened.payload_compression(), PayloadCompression::None);
+    assert_eq!(read_payload(&reopened, digest).await, DATA);
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_uncompressed_payload_readable_after_enabling(tmpdir: tempfile::TempDir) {
+    init_logging();
+    let mut repo = OpenFsRepository::create(tmpdir.path()).await.unwrap();
+    let digest = repo.commit_blob(Box::pin(DATA)).await.unwrap();
+
+    repo.set_payload_compression(PayloadCompression::Zstd);
+    assert!(!payload_is_compressed(&repo, digest).await);
+    assert_eq!(read_payload(&repo, digest).await, DATA);
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_uncompressed_payload_with_zstd_magic(tmpdir: tempfile::TempDir) {
+    init_logging();
+    let mut repo = OpenFsRepository::create(tmpdir.path()).await.unwrap();
+    repo.set_payload_compression(PayloadCompression::Zstd);
+    let mut data = ZSTD_MAGIC.to_vec();
+    data.extend_from_slice(b"not actually a zstd frame");
+    let (digest, _) = repo
+        .payloads
+        .write_data(Box::pin(data.as_slice()))
+        .await
+        .unwrap();
+    assert_eq!(
+        read_payload(&repo, digest).await,
+        data,
+        "uncompressed payloads should be read as-is, whatever they contain"
+    );
+}
+
+#[rstest]
+#[tokio::test(flavor = "multi_thread")]
+async fn test_compress_payloads_migration(tmpdir: tempfile::TempDir) {
+    init_logging();
+    let repo = OpenFsRepository::create(tmpdir.path()).await.unwrap();
+    let digest = repo.commit_blob(Box::pin(DATA)).await.unwrap();
+
+    let count = compress_payloads(tmpdir.path(), PayloadCompression::Zstd)
+        .await
+        .unwrap();
+    assert_eq!(count, 1);
+    let repo = OpenFsRepository::open(tmpdir.path()).await.unwrap();
+    assert!(payload_is_compressed(&repo, digest).await);
+    assert_eq!(read_payload(&repo, digest).await, DATA);
+
+    let count = compress_payloads(tmpdir.path(), PayloadCompression::Zstd)
+        .await
+        .unwrap();
+    assert_eq!(count, 0, "already compressed payloads should be skipped");
+
+    compress_payloads(tmpdir.path(), PayloadCompression::None)
+        .await
+        .unwrap();
+    let repo = OpenFsRepository::open(tmpdir.path()).await.unwrap();
+    assert!(!payload_is_compressed(&repo, digest).await);
+    assert_eq!(read_payload(&repo, digest).await, DATA);
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_render_compressed_manifest(tmpdir: tempfile::TempDir) {
+    init_logging();
+    let mut storage = OpenFsRepository::create(tmpdir.path().join("storage"))
+        .await
+        .unwrap();
+    storage.set_payload_compression(PayloadCompression::Zstd);
+
+    let src_dir = tmpdir.path().join("source");
+    ensure(src_dir.join("dir1.0/file.txt"), "somedata");
+    ensure(src_dir.join("file.txt"), "rootdata");
+    let manifest = tracking::compute_manifest(&src_dir).await.unwrap();
+    for node in manifest.walk_abs(src_dir.to_str().unwrap()) {
+        if node.entry.kind.is_blob() {
+            let data = tokio::fs::File::open(&node.path.to_path("/"))
+                .await
+                .unwrap();
+            storage
+                .commit_blob(Box::pin(tokio::io::BufReader::new(data)))
+                .await
+                .unwrap();
+        }
+    }
+
+    let expected = manifest.to_graph_manifest();
+    let rendered_path = crate::storage::fs::Renderer::new(&storage)
+        .render_manifest(&expected, None)
+        .await
+        .expect("should successfully render manifest");
+    let actual = tracking::compute_manifest(rendered_path)
+        .await
+        .unwrap()
+        .to_graph_manifest();
+    assert_eq!(actual.digest().unwrap(), expected.digest().unwrap());
+}
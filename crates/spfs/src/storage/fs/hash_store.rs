@@ -14,6 +14,7 @@ use futures::{Stream, TryStreamExt};
 use tokio::fs::DirEntry;
 use tokio::io::AsyncWriteExt;
 
+use super::compression::{decompress_payload, PayloadCompression};
 use crate::runtime::makedirs_with_perms;
 use crate::storage::{OpenRepositoryError, OpenRepositoryResult};
 use crate::tracking::BlobRead;
@@ -42,6 +43,8 @@ pub struct FsHashStore {
     pub directory_permissions: u32,
     /// permissions used when creating new files
     pub file_permissions: u32,
+    /// compression applied to the data of every file in this storage
+    pub compression: PayloadCompression,
 }
 
 impl FsHashStore {
@@ -60,9 +63,16 @@ impl FsHashStore {
             root: root.as_ref().to_path_buf(),
             directory_permissions: 0o777, // this is a shared store for all users
             file_permissions: 0o666,      // read+write is required to make hard links
+            compression: PayloadCompression::None,
         }
     }
 
+    /// Set the compression applied to the files in this storage.
+    pub fn with_compression(mut self, compression: PayloadCompression) -> Self {
+        self.compression = compression;
+        self
+    }
+
     /// The folder where payloads are copied to have the expected ownership
     /// and permissions suitable for hard-linking into a render.
     pub fn proxydir(&self) -> PathBuf {
@@ -239,13 +249,67 @@ impl FsHashStore {
                     )
                 })?,
         );
-        let mut hasher = encoding::Hasher::with_target(&mut writer);
-        let copied = match tokio::io::copy(&mut reader, &mut hasher).await {
+        // The digest is always computed over the original data, so
+        // the hasher sits in front of any compression.
+        let (copied, digest) = match self.compression {
+            PayloadCompression::None => {
+                let hasher = encoding::Hasher::with_target(&mut writer);
+                Self::copy_and_hash(&mut reader, hasher, &working_file).await?
+            }
+            PayloadCompression::Zstd => {
+                let mut encoder = async_compression::tokio::write::ZstdEncoder::new(&mut writer);
+                let result = Self::copy_and_hash(
+                    &mut reader,
+                    encoding::Hasher::with_target(&mut encoder),
+                    &working_file,
+                )
+                .await?;
+                if let Err(err) = encoder.shutdown().await {
+                    let _ = tokio::fs::remove_file(&working_file).await;
+                    return Err(Error::StorageWriteError(
+                        "finish compression of hash store object file",
+                        working_file,
+                        err,
+                    ));
+                }
+                result
+            }
+        };
+        if let Err(err) = writer.into_inner().into_std().await.close() {
+            return Err(Error::StorageWriteError(
+                "close on hash store object file",
+                working_file,
+                err,
+            ));
+        }
+
+        self.persist_object_with_digest(
+            PersistableObject::WorkingFile {
+                working_file,
+                copied,
+                object_permissions,
+            },
+            digest,
+        )
+        .await
+    }
+
+    /// Copy all data from reader into the hasher, returning the number of
+    /// bytes copied and the resulting digest.
+    async fn copy_and_hash<W>(
+        reader: &mut Pin<Box<dyn BlobRead>>,
+        mut hasher: encoding::Hasher<W>,
+        working_file: &Path,
+    ) -> Result<(u64, encoding::Digest)>
+    where
+        W: tokio::io::AsyncWrite + Unpin,
+    {
+        let copied = match tokio::io::copy(reader, &mut hasher).await {
             Err(err) => {
-                let _ = tokio::fs::remove_file(&working_file).await;
+                let _ = tokio::fs::remove_file(working_file).await;
                 return Err(Error::StorageWriteError(
                     "copy on hash store object file",
-                    working_file,
+                    working_file.to_owned(),
                     err,
                 ));
             }
@@ -255,28 +319,55 @@ impl FsHashStore {
         if let Err(err) = hasher.flush().await {
             return Err(Error::StorageWriteError(
                 "flush on hash store object file",
-                working_file,
+                working_file.to_owned(),
                 err,
             ));
         }
-        let digest = hasher.digest();
-        if let Err(err) = writer.into_inner().into_std().await.close() {
+        Ok((copied, hasher.digest()))
+    }
+
+    /// Store the given data under the provided digest, which is not
+    /// required to be the digest of the data itself.
+    pub(crate) async fn write_data_with_digest(
+        &self,
+        data: &[u8],
+        digest: encoding::Digest,
+    ) -> Result<()> {
+        let uuid = uuid::Uuid::new_v4().to_string();
+        let working_file = self.workdir().join(uuid);
+        self.ensure_base_dir(&working_file)?;
+        if let Err(err) = tokio::fs::write(&working_file, data).await {
+            let _ = tokio::fs::remove_file(&working_file).await;
             return Err(Error::StorageWriteError(
-                "close on hash store object file",
+                "write on hash store object file",
                 working_file,
                 err,
             ));
         }
-
         self.persist_object_with_digest(
             PersistableObject::WorkingFile {
                 working_file,
-                copied,
-                object_permissions,
+                copied: data.len() as u64,
+                object_permissions: None,
             },
             digest,
         )
-        .await
+        .await?;
+        Ok(())
+    }
+
+    /// Open the data stored for the given digest, decompressing it
+    /// as needed.
+    pub async fn open_digest(
+        &self,
+        digest: &encoding::Digest,
+    ) -> std::io::Result<Pin<Box<dyn BlobRead>>> {
+        let path = self.build_digest_path(digest);
+        let file = tokio::fs::File::open(&path).await?;
+        Ok(decompress_payload(
+            tokio::io::BufReader::new(file),
+            self.compression,
+        ))
     }
 
     pub(crate) async fn persist_object_with_digest(
@@ -27,4 +27,7 @@ pub enum MigrationError {
         version: String,
         source: semver::Error,
     },
+
+    #[error("Failed to migrate payload data")]
+    PayloadError(#[source] Box<crate::Error>),
 }
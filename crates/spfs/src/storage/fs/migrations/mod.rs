@@ -3,10 +3,12 @@
 // https://github.com/spkenv/spk
 
 mod error;
+mod payload_compression;
 
 use std::path::{Path, PathBuf};
 
 pub use error::{MigrationError, MigrationResult};
+pub use payload_compression::compress_payloads;
 
 use super::read_last_migration_version;
 
@@ -0,0 +1,97 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use std::path::Path;
+
+use futures::TryStreamExt;
+
+use super::{MigrationError, MigrationResult};
+use crate::storage::fs::{FsHashStore, PayloadCompression, ZSTD_PAYLOADS_DIRNAME};
+use crate::tracking::BlobReadExt;
+
+/// Convert all existing payloads in the repository at the given path
+/// to the given compression.
+///
+/// Each payload is written into the storage for the new compression
+/// before it is removed from the old one, so that the repository
+/// remains readable throughout. This migration is safe to resume if
+/// interrupted. Renders that already link to existing payloads are not
+/// affected, but compressed payloads are reported as missing by versions
+/// of spfs that predate payload compression.
+///
+/// The compression of payloads written after this migration is
+/// decided by the configuration of each client, see
+/// [`crate::storage::fs::Params::compression`].
+///
+/// # Returns:
+///    - the number of payloads that were rewritten
+pub async fn compress_payloads<P: AsRef<Path>>(
+    root: P,
+    compression: PayloadCompression,
+) -> MigrationResult<usize> {
+    let root = tokio::task::block_in_place(|| dunce::canonicalize(&root))
+        .map_err(|err| MigrationError::InvalidRoot(root.as_ref().to_owned(), err))?;
+
+    let uncompressed = FsHashStore::open_unchecked(root.join("payloads"));
+    let compressed = FsHashStore::open_unchecked(root.join(ZSTD_PAYLOADS_DIRNAME))
+        .with_compression(PayloadCompression::Zstd);
+    let (source, target) = match compression {
+        PayloadCompression::None => (compressed, uncompressed),
+        PayloadCompression::Zstd => (uncompressed, compressed),
+    };
+    if !source.root().exists() {
+        return Ok(0);
+    }
+
+    let mut rewritten = 0;
+    let mut digests = Box::pin(source.iter());
+    while let Some(digest) = digests
+        .try_next()
+        .await
+        .map_err(|err| MigrationError::PayloadError(Box::new(err)))?
+    {
+        let path = source.build_digest_path(&digest);
+        if !target.has_digest(&digest) {
+            let metadata = tokio::fs::symlink_metadata(&path).await.map_err(|err| {
+                MigrationError::ReadError("symlink_metadata on payload", path.clone(), err)
+            })?;
+            let reader = source.open_digest(&digest).await.map_err(|err| {
+                MigrationError::ReadError("open_digest on payload", path.clone(), err)
+            })?;
+            #[cfg(unix)]
+            let reader = {
+                use std::os::unix::fs::PermissionsExt;
+                Box::pin(reader.with_permissions(metadata.permissions().mode()))
+            };
+            #[cfg(windows)]
+            let _ = metadata;
+
+            tracing::debug!(%digest, %compression, "rewriting payload");
+            let (written, _) = target
+                .write_data(reader)
+                .await
+                .map_err(|err| MigrationError::PayloadError(Box::new(err)))?;
+            if written != digest {
+                return Err(MigrationError::PayloadError(Box::new(
+                    crate::Error::String(format!(
+                        "payload data does not match its digest, expected {digest} but found {written}"
+                    )),
+                )));
+            }
+        }
+        match tokio::fs::remove_file(&path).await {
+            Ok(()) => {}
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+            Err(err) => {
+                return Err(MigrationError::WriteError(
+                    "remove_file on rewritten payload",
+                    path,
+                    err,
+                ))
+            }
+        }
+        rewritten += 1;
+    }
+    Ok(rewritten)
+}
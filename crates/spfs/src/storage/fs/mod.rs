@@ -4,6 +4,7 @@
 
 //! Uses a local directory on disk to store the spfs repository.
 
+mod compression;
 mod database;
 mod hash_store;
 mod manifest_render_path;
@@ -16,6 +17,8 @@ mod tag;
 pub mod migrations;
 mod render_reporter;
 
+pub(crate) use compression::{decompress_payload, ZSTD_PAYLOADS_DIRNAME};
+pub use compression::PayloadCompression;
 pub use hash_store::FsHashStore;
 pub use manifest_render_path::ManifestRenderPath;
 pub use render_reporter::{
@@ -6,10 +6,13 @@ use std::io::ErrorKind;
 use std::pin::Pin;
 
 use futures::future::ready;
-use futures::{Stream, StreamExt, TryFutureExt};
+use futures::{Stream, StreamExt, TryFutureExt, TryStreamExt};
 
-use super::{FsRepository, OpenFsRepository};
+use super::{FsHashStore, FsRepository, OpenFsRepository, PayloadCompression};
+use crate::encoding::prelude::*;
+use crate::storage::chunking::{read_chunks, split_into_chunks, MAX_CHUNK_SIZE};
 use crate::storage::prelude::*;
+use crate::storage::{Chunk, ChunkIndex};
 use crate::tracking::BlobRead;
 use crate::{encoding, graph, Error, Result};
 
@@ -49,24 +52,63 @@ impl crate::storage::PayloadStorage for FsRepository {
     async fn remove_payload(&self, digest: encoding::Digest) -> Result<()> {
         self.opened().await?.remove_payload(digest).await
     }
+
+    fn supports_chunked_payloads(&self) -> bool {
+        true
+    }
+
+    async fn read_chunk_index(&self, digest: encoding::Digest) -> Result<Option<ChunkIndex>> {
+        self.opened().await?.read_chunk_index(digest).await
+    }
+
+    async unsafe fn write_chunk_index(&self, index: &ChunkIndex) -> Result<()> {
+        let opened = self.opened().await?;
+        // Safety: we are simply deferring this function to the inner
+        // one and so the same safety rules apply to our caller
+        unsafe { opened.write_chunk_index(index).await }
+    }
 }
 
 #[async_trait::async_trait]
 impl crate::storage::PayloadStorage for OpenFsRepository {
     async fn has_payload(&self, digest: encoding::Digest) -> bool {
-        let path = self.payloads.build_digest_path(&digest);
-        tokio::fs::symlink_metadata(path).await.is_ok()
+        if self.has_whole_payload(&digest).await {
+            return true;
+        }
+        // a chunked payload can only be read when all of its chunks exist
+        let Ok(Some(index)) = self.read_chunk_index(digest).await else {
+            return false;
+        };
+        for chunk in index.chunks() {
+            if !self.has_whole_payload(&chunk.digest).await {
+                return false;
+            }
+        }
+        true
     }
 
     fn iter_payload_digests(&self) -> Pin<Box<dyn Stream<Item = Result<encoding::Digest>> + Send>> {
-        Box::pin(self.payloads.iter())
+        let mut digests: Pin<Box<dyn Stream<Item = Result<encoding::Digest>> + Send>> =
+            Box::pin(self.payloads.iter());
+        for store in [&self.compressed_payloads, &self.chunks] {
+            if store.root().exists() {
+                digests = Box::pin(digests.chain(store.iter()));
+            }
+        }
+        digests
     }
 
     async unsafe fn write_data(
         &self,
         reader: Pin<Box<dyn BlobRead>>,
     ) -> Result<(encoding::Digest, u64)> {
-        self.payloads.write_data(reader).await
+        let (digest, size) = self.payload_store().write_data(reader).await?;
+        if let Some(threshold) = self.payload_chunking_threshold() {
+            if size >= threshold && size > MAX_CHUNK_SIZE as u64 {
+                self.chunk_payload(digest).await?;
+            }
+        }
+        Ok((digest, size))
     }
 
     async fn open_payload(
@@ -74,10 +116,14 @@ impl crate::storage::PayloadStorage for OpenFsRepository {
         digest: encoding::Digest,
     ) -> Result<(Pin<Box<dyn BlobRead>>, std::path::PathBuf)> {
         let path = self.payloads.build_digest_path(&digest);
-        match tokio::fs::File::open(&path).await {
-            Ok(file) => Ok((Box::pin(tokio::io::BufReader::new(file)), path)),
+        match self.open_whole_payload(&digest).await {
+            Ok(opened) => Ok(opened),
             Err(err) => match err.kind() {
                 ErrorKind::NotFound => {
+                    if let Some(index) = self.read_chunk_index(digest).await? {
+                        let path = self.chunks.build_digest_path(&digest);
+                        return Ok((self.open_chunked_payload(index), path));
+                    }
                     // Return an error specific to this situation, whether the
                     // blob is really unknown or just the payload is missing.
                     match self.read_blob(digest).await {
@@ -97,17 +143,165 @@ impl crate::storage::PayloadStorage for OpenFsRepository {
     }
 
     async fn remove_payload(&self, digest: encoding::Digest) -> Result<()> {
-        let path = self.payloads.build_digest_path(&digest);
+        let mut removed = false;
+        // a payload may be stored whole, as chunks, or both
+        for (store, context) in [
+            (&self.payloads, "remove_file on payload"),
+            (
+                &self.compressed_payloads,
+                "remove_file on compressed payload",
+            ),
+            (&self.chunks, "remove_file on payload chunk index"),
+        ] {
+            let path = store.build_digest_path(&digest);
+            match tokio::fs::remove_file(&path).await {
+                Ok(()) => removed = true,
+                Err(err) if err.kind() == ErrorKind::NotFound => {}
+                Err(err) => return Err(Error::StorageWriteError(context, path, err)),
+            }
+        }
+        if !removed {
+            return Err(Error::UnknownObject(digest));
+        }
+        Ok(())
+    }
+
+    fn supports_chunked_payloads(&self) -> bool {
+        true
+    }
+
+    async fn read_chunk_index(&self, digest: encoding::Digest) -> Result<Option<ChunkIndex>> {
+        let path = self.chunks.build_digest_path(&digest);
+        let data = match tokio::fs::read(&path).await {
+            Ok(data) => data,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(err) => {
+                return Err(Error::StorageReadError(
+                    "read on payload chunk index",
+                    path,
+                    err,
+                ))
+            }
+        };
+        Ok(Some(ChunkIndex::decode(&mut data.as_slice())?))
+    }
+
+    async unsafe fn write_chunk_index(&self, index: &ChunkIndex) -> Result<()> {
+        for chunk in index.chunks() {
+            if !self.has_whole_payload(&chunk.digest).await {
+                return Err(Error::UnknownObject(chunk.digest));
+            }
+        }
+        self.chunks
+            .write_data_with_digest(&index.encode_to_bytes()?, *index.digest())
+            .await
+    }
+}
+
+impl OpenFsRepository {
+    /// The storage that newly written payloads are placed into
+    fn payload_store(&self) -> &FsHashStore {
+        match self.payload_compression() {
+            PayloadCompression::None => &self.payloads,
+            PayloadCompression::Zstd => &self.compressed_payloads,
+        }
+    }
+
+    /// True if the payload is stored whole, with or without compression
+    async fn has_whole_payload(&self, digest: &encoding::Digest) -> bool {
+        for store in [&self.payloads, &self.compressed_payloads] {
+            let path = store.build_digest_path(digest);
+            if tokio::fs::symlink_metadata(path).await.is_ok() {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Open a payload that is stored whole, with or without compression,
+    /// returning the path that it was read from.
+    async fn open_whole_payload(
+        &self,
+        digest: &encoding::Digest,
+    ) -> std::io::Result<(Pin<Box<dyn BlobRead>>, std::path::PathBuf)> {
+        match self.payloads.open_digest(digest).await {
+            Ok(reader) => Ok((reader, self.payloads.build_digest_path(digest))),
+            Err(err) if err.kind() == ErrorKind::NotFound => {
+                let reader = self.compressed_payloads.open_digest(digest).await?;
+                Ok((reader, self.compressed_payloads.build_digest_path(digest)))
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Convert a payload that is stored whole into a sequence of chunks.
+    ///
+    /// Payloads that fit into a single chunk are left as-is and
+    /// have no index.
+    pub async fn chunk_payload(&self, digest: encoding::Digest) -> Result<Option<ChunkIndex>> {
+        let (reader, path) = self.open_whole_payload(&digest).await.map_err(|err| {
+            Error::StorageReadError(
+                "open payload for chunking",
+                self.payloads.build_digest_path(&digest),
+                err,
+            )
+        })?;
+        let mut chunks = Vec::new();
+        let mut stream = Box::pin(split_into_chunks(reader));
+        while let Some((chunk_digest, data)) = stream.try_next().await? {
+            let size = data.len() as u64;
+            if chunk_digest != digest && !self.has_whole_payload(&chunk_digest).await {
+                self.payload_store()
+                    .write_data(Box::pin(std::io::Cursor::new(data)))
+                    .await?;
+            }
+            chunks.push(Chunk {
+                digest: chunk_digest,
+                size,
+            });
+        }
+        drop(stream);
+        if chunks.len() < 2 {
+            return Ok(None);
+        }
+
+        let index = ChunkIndex::new(digest, chunks);
+        // Safety: the chunks were all written above and the
+        // payload that they replace is already tracked
+        unsafe { self.write_chunk_index(&index).await? };
         match tokio::fs::remove_file(&path).await {
-            Ok(()) => Ok(()),
-            Err(err) => match err.kind() {
-                ErrorKind::NotFound => Err(Error::UnknownObject(digest)),
-                _ => Err(Error::StorageWriteError(
-                    "remove_file on payload",
+            Ok(()) => {}
+            Err(err) if err.kind() == ErrorKind::NotFound => {}
+            Err(err) => {
+                return Err(Error::StorageWriteError(
+                    "remove_file on chunked payload",
                     path,
                     err,
-                )),
-            },
+                ))
+            }
         }
+        Ok(Some(index))
+    }
+
+    /// Read the full content of a chunked payload, one chunk at a time.
+    fn open_chunked_payload(&self, index: ChunkIndex) -> Pin<Box<dyn BlobRead>> {
+        let repo = self.clone();
+        let stream = read_chunks(index, move |digest| {
+            let repo = repo.clone();
+            async move {
+                match repo.open_whole_payload(&digest).await {
+                    Ok((reader, _)) => Ok(Some(reader)),
+                    Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+                    Err(err) => Err(Error::StorageReadError(
+                        "open_whole_payload on payload chunk",
+                        repo.payloads.build_digest_path(&digest),
+                        err,
+                    )),
+                }
+            }
+        });
+        Box::pin(tokio::io::BufReader::new(
+            tokio_util::io::StreamReader::new(stream),
+        ))
     }
 }
@@ -19,6 +19,12 @@ pub enum RenderBlobResult {
     PayloadCopiedWrongMode,
     /// Was not possible to hard link because of different file ownership.
     PayloadCopiedWrongOwner,
+    /// Was not possible to hard link because the payload is stored compressed.
+    PayloadCopiedCompressed,
+    /// Was not possible to hard link because the payload is stored as chunks.
+    PayloadCopiedChunked,
+    /// Was not possible to hard link because the entry has extended attributes.
+    PayloadCopiedXattrs,
     /// Payload was able to be hard linked.
     PayloadHardLinked,
     /// Payload was a symlink and already existed.
@@ -18,6 +18,9 @@ pub struct RenderSummary {
     pub copy_link_limit_count: AtomicUsize,
     pub copy_wrong_mode_count: AtomicUsize,
     pub copy_wrong_owner_count: AtomicUsize,
+    pub copy_compressed_count: AtomicUsize,
+    pub copy_chunked_count: AtomicUsize,
+    pub copy_xattrs_count: AtomicUsize,
     pub link_count: AtomicUsize,
     pub symlink_count: AtomicUsize,
 
@@ -27,6 +30,9 @@ pub struct RenderSummary {
     pub total_bytes_copied_link_limit: AtomicUsize,
     pub total_bytes_copied_wrong_mode: AtomicUsize,
     pub total_bytes_copied_wrong_owner: AtomicUsize,
+    pub total_bytes_copied_compressed: AtomicUsize,
+    pub total_bytes_copied_chunked: AtomicUsize,
+    pub total_bytes_copied_xattrs: AtomicUsize,
     pub total_bytes_linked: AtomicUsize,
 }
 
@@ -94,6 +100,39 @@ impl RenderSummary {
                 self.total_bytes_copied_wrong_owner
                     .fetch_add(entry_size, Ordering::Relaxed);
             }
+            RenderBlobResult::PayloadCopiedCompressed => {
+                self.copy_count.fetch_add(1, Ordering::Relaxed);
+                self.copy_compressed_count.fetch_add(1, Ordering::Relaxed);
+
+                self.total_bytes_rendered
+                    .fetch_add(entry_size, Ordering::Relaxed);
+                self.total_bytes_copied
+                    .fetch_add(entry_size, Ordering::Relaxed);
+                self.total_bytes_copied_compressed
+                    .fetch_add(entry_size, Ordering::Relaxed);
+            }
+            RenderBlobResult::PayloadCopiedChunked => {
+                self.copy_count.fetch_add(1, Ordering::Relaxed);
+                self.copy_chunked_count.fetch_add(1, Ordering::Relaxed);
+
+                self.total_bytes_rendered
+                    .fetch_add(entry_size, Ordering::Relaxed);
+                self.total_bytes_copied
+                    .fetch_add(entry_size, Ordering::Relaxed);
+                self.total_bytes_copied_chunked
+                    .fetch_add(entry_size, Ordering::Relaxed);
+            }
+            RenderBlobResult::PayloadCopiedXattrs => {
+                self.copy_count.fetch_add(1, Ordering::Relaxed);
+                self.copy_xattrs_count.fetch_add(1, Ordering::Relaxed);
+
+                self.total_bytes_rendered
+                    .fetch_add(entry_size, Ordering::Relaxed);
+                self.total_bytes_copied
+                    .fetch_add(entry_size, Ordering::Relaxed);
+                self.total_bytes_copied_xattrs
+                    .fetch_add(entry_size, Ordering::Relaxed);
+            }
             RenderBlobResult::PayloadHardLinked => {
                 self.link_count.fetch_add(1, Ordering::Relaxed);
 
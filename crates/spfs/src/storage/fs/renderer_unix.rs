@@ -478,6 +478,22 @@ where
                                     render_type,
                                 )
                                 .await;
+                            if res.is_ok() && entry.has_xattrs() {
+                                let xattrs = entry.to_xattrs();
+                                let fd = child_dir.as_raw_fd();
+                                res = tokio::task::spawn_blocking(move || {
+                                    apply_xattrs(fd, &xattrs)
+                                })
+                                .await
+                                .expect("syscall should not panic")
+                                .map_err(|err| {
+                                    Error::StorageWriteError(
+                                        "set xattrs on rendered dir",
+                                        PathBuf::new(),
+                                        err,
+                                    )
+                                });
+                            }
                             if res.is_ok() {
                                 let mode = Mode::from_bits_truncate(entry.mode());
                                 res = tokio::task::spawn_blocking(move || {
@@ -596,6 +612,36 @@ where
         drop(reader);
 
         let mut committed_path = self.repo.payloads().build_digest_path(entry.object());
+
+        // Only payloads that are stored whole and uncompressed can be
+        // linked into a render as-is. Compressed and chunked payloads
+        // must be expanded into a copy (or proxy) first.
+        let payload_linkable = self.repo.payloads().has_digest(entry.object());
+        let payload_compressed =
+            !payload_linkable && self.repo.compressed_payloads().has_digest(entry.object());
+        let payload_chunked = !payload_linkable
+            && !payload_compressed
+            && self.repo.read_chunk_index(*entry.object()).await?.is_some();
+        if entry.has_xattrs() && !matches!(render_type, RenderType::Copy) {
+            // extended attributes belong to the inode, and so cannot
+            // be shared through hard links with other rendered files
+            self.render_blob_with_permit(dir_fd, entry, RenderType::Copy, permit)
+                .await?;
+            return Ok(RenderBlobResult::PayloadCopiedXattrs);
+        }
+        if matches!(render_type, RenderType::HardLinkNoProxy) {
+            if payload_compressed {
+                self.render_blob_with_permit(dir_fd, entry, RenderType::Copy, permit)
+                    .await?;
+                return Ok(RenderBlobResult::PayloadCopiedCompressed);
+            }
+            if payload_chunked {
+                self.render_blob_with_permit(dir_fd, entry, RenderType::Copy, permit)
+                    .await?;
+                return Ok(RenderBlobResult::PayloadCopiedChunked);
+            }
+        }
+
         Ok(match render_type {
             RenderType::HardLink | RenderType::HardLinkNoProxy => {
                 let mut retry_count = 0;
@@ -634,19 +680,29 @@ where
                             // proxy can be a hard link instead of a copy.
                             // This assumes that the payload's owner and
                             // permissions are never changed.
-                            let metadata = match tokio::fs::symlink_metadata(&payload_path).await {
-                                Err(err) => {
-                                    return Err(Error::StorageReadError(
-                                        "symlink_metadata on payload path",
-                                        payload_path.clone(),
-                                        err,
-                                    ))
+                            // A compressed or chunked payload has no file
+                            // that can be linked to.
+                            let metadata = if !payload_linkable {
+                                None
+                            } else {
+                                match tokio::fs::symlink_metadata(&payload_path).await {
+                                    Err(err) => {
+                                        return Err(Error::StorageReadError(
+                                            "symlink_metadata on payload path",
+                                            payload_path.clone(),
+                                            err,
+                                        ))
+                                    }
+                                    Ok(metadata) => Some(metadata),
                                 }
-                                Ok(metadata) => metadata,
                             };
 
-                            let has_correct_mode = metadata.permissions().mode() == entry.mode();
-                            let mut has_correct_owner = metadata.uid() == geteuid().as_raw();
+                            let has_correct_mode = metadata
+                                .as_ref()
+                                .is_some_and(|m| m.permissions().mode() == entry.mode());
+                            let mut has_correct_owner = metadata
+                                .as_ref()
+                                .is_some_and(|m| m.uid() == geteuid().as_raw());
 
                             // Can we still share this payload if it doesn't
                             // have the correct owner?
@@ -721,10 +777,22 @@ where
                                     RenderBlobResult::PayloadHardLinked
                                 }
                             } else {
-                                if !has_correct_mode {
-                                    tracing::debug!(actual_mode = ?metadata.permissions().mode(), expected_mode = ?entry.mode(), ?payload_path, "couldn't skip proxy copy; payload had wrong mode");
-                                } else if !has_correct_owner {
-                                    tracing::debug!(actual_uid = ?metadata.uid(), expected_uid = ?geteuid().as_raw(), ?payload_path, "couldn't skip proxy copy; payload had wrong uid");
+                                if payload_compressed {
+                                    tracing::trace!(
+                                        ?payload_path,
+                                        "couldn't skip proxy copy; payload is compressed"
+                                    );
+                                } else if payload_chunked {
+                                    tracing::trace!(
+                                        ?payload_path,
+                                        "couldn't skip proxy copy; payload is chunked"
+                                    );
+                                } else if let Some(metadata) = &metadata {
+                                    if !has_correct_mode {
+                                        tracing::debug!(actual_mode = ?metadata.permissions().mode(), expected_mode = ?entry.mode(), ?payload_path, "couldn't skip proxy copy; payload had wrong mode");
+                                    } else if !has_correct_owner {
+                                        tracing::debug!(actual_uid = ?metadata.uid(), expected_uid = ?geteuid().as_raw(), ?payload_path, "couldn't skip proxy copy; payload had wrong uid");
+                                    }
                                 }
 
                                 // Write to a temporary file so that some other render
@@ -740,19 +808,8 @@ where
                                         err,
                                     )
                                 })?;
-                                let mut payload_file =
-                                    tokio::fs::File::open(&payload_path).await.map_err(|err| {
-                                        if err.kind() == std::io::ErrorKind::NotFound {
-                                            // in the case of a corrupt repository, this is a more appropriate error
-                                            Error::UnknownObject(*entry.object())
-                                        } else {
-                                            Error::StorageReadError(
-                                                "open payload for proxying",
-                                                payload_path.clone(),
-                                                err,
-                                            )
-                                        }
-                                    })?;
+                                let (mut payload_file, _) =
+                                    self.repo.open_payload(*entry.object()).await?;
                                 let proxy_file_fd =
                                     nix::unistd::dup(temp_proxy_file.as_file().as_raw_fd())?;
                                 // Safety: from_raw_fd takes ownership of this fd which is what we want
@@ -792,6 +849,10 @@ where
                                             ))
                                         }
                                     }
+                                } else if payload_compressed {
+                                    RenderBlobResult::PayloadCopiedCompressed
+                                } else if payload_chunked {
+                                    RenderBlobResult::PayloadCopiedChunked
                                 } else if !has_correct_mode {
                                     RenderBlobResult::PayloadCopiedWrongMode
                                 } else {
@@ -884,16 +945,7 @@ where
             }
             RenderType::Copy => {
                 let name = entry.name().to_owned();
-                let mut payload_file =
-                    tokio::fs::File::open(&committed_path)
-                        .await
-                        .map_err(|err| {
-                            Error::StorageReadError(
-                                "open of payload source file",
-                                committed_path,
-                                err,
-                            )
-                        })?;
+                let (mut payload_file, _) = self.repo.open_payload(*entry.object()).await?;
                 let mut rendered_file =
                     tokio::task::spawn_blocking(move || -> std::io::Result<tokio::fs::File> {
                         // create with open permissions, as they will be set to the proper mode in the future
@@ -924,6 +976,20 @@ where
                             err,
                         )
                     })?;
+                if entry.has_xattrs() {
+                    let xattrs = entry.to_xattrs();
+                    let fd = rendered_file.as_raw_fd();
+                    tokio::task::spawn_blocking(move || apply_xattrs(fd, &xattrs))
+                        .await
+                        .expect("syscall should not panic")
+                        .map_err(|err| {
+                            Error::StorageWriteError(
+                                "set xattrs on copied payload",
+                                PathBuf::from(entry.name()),
+                                err,
+                            )
+                        })?;
+                }
                 let mode = entry.mode();
                 return tokio::task::spawn_blocking(move || {
                     nix::sys::stat::fchmod(
@@ -946,6 +1012,32 @@ where
     }
 }
 
+/// Restore extended attributes onto an open file descriptor.
+///
+/// Attributes that the current user is not permitted to set, such
+/// as file capabilities when rendering without privileges, or that
+/// the filesystem does not support, are logged and skipped.
+fn apply_xattrs(fd: std::os::fd::RawFd, xattrs: &tracking::Xattrs) -> std::io::Result<()> {
+    for (name, value) in xattrs.iter() {
+        let c_name = std::ffi::CString::new(name.as_str())?;
+        // Safety: the name is a valid null-terminated string and
+        // the value pointer and length come from the same slice
+        let res =
+            unsafe { libc::fsetxattr(fd, c_name.as_ptr(), value.as_ptr().cast(), value.len(), 0) };
+        if res == 0 {
+            continue;
+        }
+        let err = std::io::Error::last_os_error();
+        match err.raw_os_error() {
+            Some(libc::EPERM) | Some(libc::ENOTSUP) => {
+                tracing::warn!(%name, "unable to restore extended attribute: {err}");
+            }
+            _ => return Err(err),
+        }
+    }
+    Ok(())
+}
+
 async fn create_and_open_dir_at<A>(dir_fd: A, name: String) -> std::io::Result<tokio::fs::File>
 where
     A: AsRawFd + Send + 'static,
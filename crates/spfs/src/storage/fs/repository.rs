@@ -13,9 +13,10 @@ use std::sync::Arc;
 
 use arc_swap::ArcSwap;
 
+use super::compression::ZSTD_PAYLOADS_DIRNAME;
 use super::hash_store::PROXY_DIRNAME;
 use super::migrations::{MigrationError, MigrationResult};
-use super::FsHashStore;
+use super::{FsHashStore, PayloadCompression};
 use crate::config::{pathbuf_deserialize_with_tilde_expansion, ToAddress};
 use crate::runtime::makedirs_with_perms;
 use crate::storage::prelude::*;
@@ -63,6 +64,16 @@ pub struct Params {
     #[serde(default)]
     pub lazy: bool,
     pub tag_namespace: Option<TagNamespaceBuf>,
+    /// The compression to apply to newly written payloads.
+    ///
+    /// Payloads are always readable, no matter which compression
+    /// they were written with.
+    pub compression: Option<PayloadCompression>,
+    /// Payloads of at least this many bytes are stored as a sequence
+    /// of content-defined chunks when written to the repository.
+    ///
+    /// Chunking is disabled when this is not set.
+    pub chunking_threshold: Option<u64>,
 }
 
 #[async_trait::async_trait]
@@ -282,10 +293,18 @@ pub struct OpenFsRepository {
     tag_namespace: Option<TagNamespaceBuf>,
     /// stores the actual file data/payloads of this repo
     pub payloads: FsHashStore,
+    /// stores the payloads of this repo that were written with compression
+    pub compressed_payloads: FsHashStore,
     /// stores all digraph object data for this repo
     pub objects: FsHashStore,
+    /// stores the index of each payload that is kept as separate chunks
+    pub chunks: FsHashStore,
     /// stores rendered file system layers for use in overlayfs
     pub renders: Option<RenderStore>,
+    /// the compression applied to newly written payloads
+    payload_compression: PayloadCompression,
+    /// newly written payloads of at least this size are chunked
+    payload_chunking_threshold: Option<u64>,
 }
 
 #[async_trait::async_trait]
@@ -300,6 +319,8 @@ impl FromConfig for OpenFsRepository {
         };
         repo.map(|mut repo| {
             repo.set_tag_namespace(config.params.tag_namespace);
+            repo.set_payload_compression(config.params.compression.unwrap_or_default());
+            repo.set_payload_chunking_threshold(config.params.chunking_threshold);
             repo
         })
     }
@@ -311,9 +332,14 @@ impl Clone for OpenFsRepository {
         Self {
             objects: FsHashStore::open_unchecked(root.join("objects")),
             payloads: FsHashStore::open_unchecked(root.join("payloads")),
+            compressed_payloads: FsHashStore::open_unchecked(root.join(ZSTD_PAYLOADS_DIRNAME))
+                .with_compression(PayloadCompression::Zstd),
+            chunks: FsHashStore::open_unchecked(root.join("chunks")),
             renders: self.renders.clone(),
             root,
             tag_namespace: self.tag_namespace.clone(),
+            payload_compression: self.payload_compression,
+            payload_chunking_threshold: self.payload_chunking_threshold,
         }
     }
 }
@@ -324,6 +350,11 @@ impl LocalRepository for OpenFsRepository {
         &self.payloads
     }
 
+    #[inline]
+    fn compressed_payloads(&self) -> &FsHashStore {
+        &self.compressed_payloads
+    }
+
     #[inline]
     fn render_store(&self) -> Result<&RenderStore> {
         self.renders
@@ -341,6 +372,11 @@ impl OpenFsRepository {
                 create: false,
                 lazy: false,
                 tag_namespace: self.tag_namespace.clone(),
+                compression: self
+                    .payload_compression
+                    .is_compressed()
+                    .then_some(self.payload_compression),
+                chunking_threshold: self.payload_chunking_threshold,
             },
         }
         .to_address()
@@ -375,6 +411,7 @@ impl OpenFsRepository {
             root.join("tags"),
             root.join("objects"),
             root.join("payloads"),
+            root.join("chunks"),
             root.join("renders").join(username).join(PROXY_DIRNAME),
             root.join(DURABLE_EDITS_DIR),
         ] {
@@ -390,6 +427,34 @@ impl OpenFsRepository {
         unsafe { Self::open_unchecked(root) }
     }
 
+    /// The compression applied to newly written payloads in this repository.
+    pub fn payload_compression(&self) -> PayloadCompression {
+        self.payload_compression
+    }
+
+    /// Set the compression to apply to newly written payloads.
+    ///
+    /// Existing payloads are not modified, but remain readable. See
+    /// [`super::migrations::compress_payloads`] to also convert the
+    /// existing data.
+    pub fn set_payload_compression(&mut self, compression: PayloadCompression) {
+        self.payload_compression = compression;
+    }
+
+    /// The size at which newly written payloads are stored as chunks,
+    /// if chunking is enabled for this repository.
+    pub fn payload_chunking_threshold(&self) -> Option<u64> {
+        self.payload_chunking_threshold
+    }
+
+    /// Set the size at which newly written payloads are stored as
+    /// chunks, or None to disable chunking.
+    ///
+    /// Existing payloads are not modified.
+    pub fn set_payload_chunking_threshold(&mut self, threshold: Option<u64>) {
+        self.payload_chunking_threshold = threshold;
+    }
+
     /// Return the configured tag namespace, if any.
     #[inline]
     pub fn get_tag_namespace(&self) -> Option<Cow<'_, TagNamespace>> {
@@ -452,9 +517,18 @@ impl OpenFsRepository {
         Ok(Self {
             objects: FsHashStore::open(root.join("objects"))?,
             payloads: FsHashStore::open(root.join("payloads"))?,
+            // this directory is only created once the first
+            // compressed payload is written
+            compressed_payloads: FsHashStore::open_unchecked(root.join(ZSTD_PAYLOADS_DIRNAME))
+                .with_compression(PayloadCompression::Zstd),
+            // repositories created before payload chunking will not
+            // have this directory until the first chunked payload is written
+            chunks: FsHashStore::open_unchecked(root.join("chunks")),
             renders: RenderStore::for_user(root, username).ok(),
             root: root.to_owned(),
             tag_namespace: None,
+            payload_compression: PayloadCompression::None,
+            payload_chunking_threshold: None,
         })
     }
 
@@ -521,12 +595,19 @@ impl OpenFsRepository {
                     Self {
                         objects: FsHashStore::open_unchecked(self.root.join("objects")),
                         payloads: FsHashStore::open_unchecked(self.root.join("payloads")),
+                        compressed_payloads: FsHashStore::open_unchecked(
+                            self.root.join(ZSTD_PAYLOADS_DIRNAME),
+                        )
+                        .with_compression(PayloadCompression::Zstd),
+                        chunks: FsHashStore::open_unchecked(self.root.join("chunks")),
                         renders: self
                             .renders
                             .as_ref()
                             .and_then(|_| RenderStore::for_user(self.root.as_ref(), dir).ok()),
                         root: self.root.clone(),
                         tag_namespace: self.tag_namespace.clone(),
+                        payload_compression: self.payload_compression,
+                        payload_chunking_threshold: self.payload_chunking_threshold,
                     },
                 )
             })
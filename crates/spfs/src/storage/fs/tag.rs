@@ -17,11 +17,13 @@ use encoding::{Decodable, Encodable};
 use futures::future::ready;
 use futures::{Future, Stream, StreamExt, TryFutureExt};
 use relative_path::RelativePath;
-use tokio::io::{AsyncRead, AsyncSeek, AsyncWriteExt, ReadBuf};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeek, AsyncSeekExt, AsyncWriteExt, ReadBuf};
 
 use super::{FsRepository, OpenFsRepository};
 use crate::storage::tag::{EntryType, TagSpecAndTagStream, TagStream};
 use crate::storage::{
+    TagChange,
+    TagChangeStream,
     TagNamespace,
     TagNamespaceBuf,
     TagStorage,
@@ -31,6 +33,16 @@ use crate::storage::{
 use crate::{encoding, tracking, Error, OsError, OsErrorExt, Result};
 
 const TAG_EXT: &str = "tag";
+/// The name of the tag change log file in the root of each tag namespace
+const TAG_CHANGES_FILE: &str = "changes.log";
+/// The header at the start of each tag change log, which is
+/// followed by the cursor that the entries in the log start from
+const TAG_CHANGES_HEADER: &[u8] = b"--SPFS-TAG-CHANGES--";
+/// The size of the complete header of a tag change log, in bytes
+const TAG_CHANGES_HEADER_SIZE: usize = TAG_CHANGES_HEADER.len() + 1 + size_of::<u64>();
+/// A tag change log that grows beyond this size is compacted by
+/// dropping its oldest entries, see [`compact_tag_changes_file`]
+const TAG_CHANGES_MAX_SIZE: u64 = 16 * 1024 * 1024;
 
 #[async_trait::async_trait]
 impl TagStorage for FsRepository {
@@ -128,6 +140,18 @@ impl TagStorage for FsRepository {
             .remove_tag_in_namespace(namespace, tag)
             .await
     }
+
+    fn read_tag_changes_in_namespace(
+        &self,
+        namespace: Option<&TagNamespace>,
+        since: u64,
+    ) -> TagChangeStream<'static> {
+        let namespace = namespace.map(ToOwned::to_owned);
+        self.opened()
+            .map_ok(move |opened| opened.read_tag_changes_in_namespace(namespace.as_deref(), since))
+            .try_flatten_stream()
+            .boxed()
+    }
 }
 
 impl OpenFsRepository {
@@ -148,6 +172,70 @@ impl OpenFsRepository {
         }
         tags_root
     }
+
+    fn tag_changes_file_in_namespace(&self, namespace: Option<&TagNamespace>) -> PathBuf {
+        self.tags_root_in_namespace(namespace)
+            .join(TAG_CHANGES_FILE)
+    }
+
+    /// Append an entry to the tag change log of the given namespace.
+    ///
+    /// This should be called while still holding the lock for the
+    /// modified tag so that changes to one tag stream are logged in
+    /// the same order that they were made.
+    async fn append_tag_change(
+        &self,
+        namespace: Option<&TagNamespace>,
+        change: &TagChange,
+    ) -> Result<()> {
+        let filepath = self.tag_changes_file_in_namespace(namespace);
+        let entry = change.encode_to_bytes()?;
+        let mut buf = Vec::with_capacity(TAG_CHANGES_HEADER_SIZE + size_of::<u64>() + entry.len());
+
+        // appending is not atomic on all filesystems (eg: NFS), so
+        // entries are only ever written while holding the log's lock
+        let _lock = TagLock::at(filepath.with_extension("log.lock")).await?;
+        let mut file = tokio::fs::OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(&filepath)
+            .await
+            .map_err(|err| {
+                Error::StorageWriteError("open tag change log for append", filepath.clone(), err)
+            })?;
+        let len = file
+            .metadata()
+            .await
+            .map_err(|err| {
+                Error::StorageReadError("metadata on tag change log", filepath.clone(), err)
+            })?
+            .len();
+        let is_new = len == 0;
+        if is_new {
+            write_tag_changes_header(&mut buf, 0)?;
+        }
+        encoding::write_uint64(&mut buf, entry.len() as u64)?;
+        buf.extend_from_slice(&entry);
+        file.write_all(&buf).await.map_err(|err| {
+            Error::StorageWriteError("write_all on tag change log", filepath.clone(), err)
+        })?;
+        file.flush().await.map_err(|err| {
+            Error::StorageWriteError("flush on tag change log", filepath.clone(), err)
+        })?;
+        drop(file);
+
+        #[cfg(unix)]
+        if is_new {
+            let perms = std::fs::Permissions::from_mode(0o666);
+            if let Err(err) = tokio::fs::set_permissions(&filepath, perms).await {
+                tracing::warn!(?err, ?filepath, "Failed to set tag change log permissions");
+            }
+        }
+        if len + buf.len() as u64 > TAG_CHANGES_MAX_SIZE {
+            compact_tag_changes_file(&filepath).await?;
+        }
+        Ok(())
+    }
 }
 
 #[async_trait::async_trait]
@@ -313,7 +401,9 @@ impl TagStorage for OpenFsRepository {
             Err(err) => Err(err),
         }?;
 
-        working_file.write_tags(&tags).await
+        working_file.write_tags(&tags).await?;
+        self.append_tag_change(namespace, &TagChange::insert(tag))
+            .await
     }
 
     async fn remove_tag_stream_in_namespace(
@@ -346,6 +436,8 @@ impl TagStorage for OpenFsRepository {
                 }
             }
         }
+        self.append_tag_change(namespace, &TagChange::remove_stream(tag))
+            .await?;
         // the lock file needs to be removed if the directory has any hope of being empty
         drop(lock);
 
@@ -403,7 +495,18 @@ impl TagStorage for OpenFsRepository {
             Err(err) => Err(err),
         }?;
 
-        working_file.write_tags(&tags).await
+        working_file.write_tags(&tags).await?;
+        self.append_tag_change(namespace, &TagChange::remove(tag))
+            .await
+    }
+
+    fn read_tag_changes_in_namespace(
+        &self,
+        namespace: Option<&TagNamespace>,
+        since: u64,
+    ) -> TagChangeStream<'static> {
+        let filepath = self.tag_changes_file_in_namespace(namespace);
+        read_tag_changes_file(filepath, since)
     }
 }
 
@@ -498,6 +601,140 @@ impl Stream for TagStreamIter {
     }
 }
 
+fn write_tag_changes_header(buf: &mut Vec<u8>, start: u64) -> Result<()> {
+    encoding::write_header(&mut *buf, TAG_CHANGES_HEADER)?;
+    encoding::write_uint64(&mut *buf, start)?;
+    Ok(())
+}
+
+/// Read the complete entries in a tag change log after the given cursor.
+///
+/// Entries are read from the file as the stream is polled. Entries that
+/// cannot be decoded are reported and skipped. When the log has been
+/// compacted since the given cursor, reading starts from its oldest entry.
+fn read_tag_changes_file(filepath: PathBuf, since: u64) -> TagChangeStream<'static> {
+    Box::pin(async_stream::try_stream! {
+        let file = match tokio::fs::File::open(&filepath).await {
+            Ok(file) => file,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return,
+            Err(err) => Err(Error::StorageReadError(
+                "open of tag change log",
+                filepath.clone(),
+                err,
+            ))?,
+        };
+        let mut reader = tokio::io::BufReader::new(file);
+        let mut header = [0; TAG_CHANGES_HEADER_SIZE];
+        if !read_tag_changes_exact(&mut reader, &mut header, &filepath).await? {
+            // the log is still being created by someone else
+            return;
+        }
+        let mut header = header.as_slice();
+        encoding::consume_header(&mut header, TAG_CHANGES_HEADER)?;
+        let start = encoding::read_uint64(&mut header)?;
+
+        let mut cursor = since.max(start);
+        reader
+            .seek(std::io::SeekFrom::Start(
+                TAG_CHANGES_HEADER_SIZE as u64 + cursor - start,
+            ))
+            .await
+            .map_err(|err| {
+                Error::StorageReadError("seek in tag change log", filepath.clone(), err)
+            })?;
+        loop {
+            let mut size = [0; size_of::<u64>()];
+            if !read_tag_changes_exact(&mut reader, &mut size, &filepath).await? {
+                break;
+            }
+            let size = u64::from_be_bytes(size);
+            if size > TAG_CHANGES_MAX_SIZE {
+                tracing::warn!(?filepath, cursor, "Tag change log is corrupt after this cursor");
+                break;
+            }
+            let mut entry = vec![0; size as usize];
+            if !read_tag_changes_exact(&mut reader, &mut entry, &filepath).await? {
+                // the last entry is still being written by someone else
+                break;
+            }
+            cursor += (size_of::<u64>() + entry.len()) as u64;
+            match TagChange::decode(&mut entry.as_slice()) {
+                Ok(change) => yield change.with_cursor(cursor),
+                Err(err) => {
+                    tracing::warn!(%err, ?filepath, cursor, "Skipping unreadable tag change");
+                }
+            }
+        }
+    })
+}
+
+/// Fill the given buffer from a tag change log, returning
+/// false if the end of the file is reached first
+async fn read_tag_changes_exact(
+    reader: &mut (impl AsyncRead + Unpin),
+    buf: &mut [u8],
+    filepath: &Path,
+) -> Result<bool> {
+    match reader.read_exact(buf).await {
+        Ok(_) => Ok(true),
+        Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => Ok(false),
+        Err(err) => Err(Error::StorageReadError(
+            "read of tag change log",
+            filepath.to_owned(),
+            err,
+        )),
+    }
+}
+
+/// Drop the oldest entries from a tag change log, leaving no more
+/// than half of [`TAG_CHANGES_MAX_SIZE`].
+///
+/// The remaining entries keep their cursors. The caller must hold
+/// the lock for the log.
+async fn compact_tag_changes_file(filepath: &Path) -> Result<()> {
+    let data = tokio::fs::read(filepath).await.map_err(|err| {
+        Error::StorageReadError("read of tag change log", filepath.to_owned(), err)
+    })?;
+    let mut header = data.as_slice();
+    encoding::consume_header(&mut header, TAG_CHANGES_HEADER)?;
+    let start = encoding::read_uint64(&mut header)?;
+
+    let entries = &data[TAG_CHANGES_HEADER_SIZE.min(data.len())..];
+    let mut remaining = entries;
+    while remaining.len() as u64 > TAG_CHANGES_MAX_SIZE / 2 {
+        let size = encoding::read_uint64(&mut &remaining[..])? as usize;
+        let Some(rest) = remaining.get(size_of::<u64>() + size..) else {
+            break;
+        };
+        remaining = rest;
+    }
+    let dropped = (entries.len() - remaining.len()) as u64;
+
+    let mut compacted = Vec::with_capacity(TAG_CHANGES_HEADER_SIZE + remaining.len());
+    write_tag_changes_header(&mut compacted, start + dropped)?;
+    compacted.extend_from_slice(remaining);
+    let working = filepath.with_extension("log.work");
+    tokio::fs::write(&working, &compacted)
+        .await
+        .map_err(|err| {
+            Error::StorageWriteError("write of compacted tag change log", working.clone(), err)
+        })?;
+    #[cfg(unix)]
+    {
+        let perms = std::fs::Permissions::from_mode(0o666);
+        if let Err(err) = tokio::fs::set_permissions(&working, perms).await {
+            tracing::warn!(?err, ?working, "Failed to set tag change log permissions");
+        }
+    }
+    tokio::fs::rename(&working, filepath).await.map_err(|err| {
+        Error::StorageWriteError(
+            "rename of compacted tag change log",
+            filepath.to_owned(),
+            err,
+        )
+    })
+}
+
 trait TagReader: AsyncRead + AsyncSeek + Send + Unpin {}
 
 impl TagReader for tokio::io::BufReader<tokio::fs::File> {}
@@ -852,7 +1089,11 @@ impl TagLock {
     pub async fn new<P: AsRef<Path>>(tag_file: P) -> Result<TagLock> {
         let mut lock_file = tag_file.as_ref().to_path_buf();
         lock_file.set_extension("tag.lock");
+        Self::at(lock_file).await
+    }
 
+    /// Acquire the lock held by the given lock file
+    pub async fn at(lock_file: PathBuf) -> Result<TagLock> {
         let timeout = std::time::Instant::now() + std::time::Duration::from_secs(5);
         loop {
             match tokio::fs::OpenOptions::new()
@@ -917,11 +1158,15 @@ impl TagWorkingFile {
     ///
     /// Writing 0 tags will result in the original file being removed
     /// rather than actually replacing it with an empty file.
-    pub async fn write_tags(self, tags: &[tracking::Tag]) -> Result<()> {
+    pub async fn write_tags(&self, tags: &[tracking::Tag]) -> Result<()> {
         let working = self.original.with_extension("tag.work");
         if tags.is_empty() {
             return tokio::fs::remove_file(&self.original).await.map_err(|err| {
-                Error::StorageWriteError("remove_file on tag stream file", self.original, err)
+                Error::StorageWriteError(
+                    "remove_file on tag stream file",
+                    self.original.clone(),
+                    err,
+                )
             });
         }
         if let Err(err) = write_tags_to_path(&working, tags).await {
@@ -936,7 +1181,7 @@ impl TagWorkingFile {
             }
             return Err(Error::StorageWriteError(
                 "rename of tag stream file",
-                self.original,
+                self.original.clone(),
                 err,
             ));
         }
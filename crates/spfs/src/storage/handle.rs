@@ -15,7 +15,14 @@ use spfs_encoding as encoding;
 use super::prelude::*;
 use super::repository::Ref;
 use super::tag::TagSpecAndTagStream;
-use super::{RepositoryHandle, TagNamespace, TagNamespaceBuf, TagStorageMut};
+use super::{
+    ChunkIndex,
+    RepositoryHandle,
+    TagChangeStream,
+    TagNamespace,
+    TagNamespaceBuf,
+    TagStorageMut,
+};
 use crate::graph::ObjectProto;
 use crate::tracking::{self, BlobRead};
 use crate::{graph, Error, Result};
@@ -29,6 +36,8 @@ macro_rules! each_variant {
             RepositoryHandle::FS($inner) => $ops,
             RepositoryHandle::Tar($inner) => $ops,
             RepositoryHandle::Rpc($inner) => $ops,
+            RepositoryHandle::S3($inner) => $ops,
+            RepositoryHandle::Http($inner) => $ops,
             RepositoryHandle::FallbackProxy($inner) => $ops,
             RepositoryHandle::Proxy($inner) => $ops,
             RepositoryHandle::Pinned($inner) => $ops,
@@ -146,6 +155,27 @@ impl TagStorage for RepositoryHandle {
             repo.remove_tag_in_namespace(namespace, tag).await
         })
     }
+
+    fn read_tag_changes_in_namespace(
+        &self,
+        namespace: Option<&TagNamespace>,
+        since: u64,
+    ) -> TagChangeStream<'static> {
+        each_variant!(self, repo, {
+            repo.read_tag_changes_in_namespace(namespace, since)
+        })
+    }
+
+    fn watch_tag_changes_in_namespace(
+        &self,
+        namespace: Option<&TagNamespace>,
+        prefix: &RelativePath,
+        since: u64,
+    ) -> TagChangeStream<'_> {
+        each_variant!(self, repo, {
+            repo.watch_tag_changes_in_namespace(namespace, prefix, since)
+        })
+    }
 }
 
 impl TagStorageMut for RepositoryHandle {
@@ -157,6 +187,8 @@ impl TagStorageMut for RepositoryHandle {
             RepositoryHandle::FS(repo) => repo.try_set_tag_namespace(tag_namespace),
             RepositoryHandle::Tar(repo) => repo.try_set_tag_namespace(tag_namespace),
             RepositoryHandle::Rpc(repo) => repo.try_set_tag_namespace(tag_namespace),
+            RepositoryHandle::S3(repo) => repo.try_set_tag_namespace(tag_namespace),
+            RepositoryHandle::Http(repo) => repo.try_set_tag_namespace(tag_namespace),
             RepositoryHandle::FallbackProxy(repo) => repo.try_set_tag_namespace(tag_namespace),
             RepositoryHandle::Proxy(repo) => repo.try_set_tag_namespace(tag_namespace),
             RepositoryHandle::Pinned(_) => Err(Error::RepositoryIsPinned),
@@ -193,6 +225,20 @@ impl PayloadStorage for RepositoryHandle {
     async fn remove_payload(&self, digest: encoding::Digest) -> Result<()> {
         each_variant!(self, repo, { repo.remove_payload(digest).await })
     }
+
+    fn supports_chunked_payloads(&self) -> bool {
+        each_variant!(self, repo, { repo.supports_chunked_payloads() })
+    }
+
+    async fn read_chunk_index(&self, digest: encoding::Digest) -> Result<Option<ChunkIndex>> {
+        each_variant!(self, repo, { repo.read_chunk_index(digest).await })
+    }
+
+    async unsafe fn write_chunk_index(&self, index: &ChunkIndex) -> Result<()> {
+        // Safety: we are wrapping the same underlying unsafe function and
+        // so the same safety holds for our callers
+        unsafe { each_variant!(self, repo, { repo.write_chunk_index(index).await }) }
+    }
 }
 
 impl BlobStorage for RepositoryHandle {}
@@ -365,6 +411,27 @@ impl TagStorage for Arc<RepositoryHandle> {
             repo.remove_tag_in_namespace(namespace, tag).await
         })
     }
+
+    fn read_tag_changes_in_namespace(
+        &self,
+        namespace: Option<&TagNamespace>,
+        since: u64,
+    ) -> TagChangeStream<'static> {
+        each_variant!(&**self, repo, {
+            repo.read_tag_changes_in_namespace(namespace, since)
+        })
+    }
+
+    fn watch_tag_changes_in_namespace(
+        &self,
+        namespace: Option<&TagNamespace>,
+        prefix: &RelativePath,
+        since: u64,
+    ) -> TagChangeStream<'_> {
+        each_variant!(&**self, repo, {
+            repo.watch_tag_changes_in_namespace(namespace, prefix, since)
+        })
+    }
 }
 
 #[async_trait::async_trait]
@@ -396,6 +463,20 @@ impl PayloadStorage for Arc<RepositoryHandle> {
     async fn remove_payload(&self, digest: encoding::Digest) -> Result<()> {
         each_variant!(&**self, repo, { repo.remove_payload(digest).await })
     }
+
+    fn supports_chunked_payloads(&self) -> bool {
+        each_variant!(&**self, repo, { repo.supports_chunked_payloads() })
+    }
+
+    async fn read_chunk_index(&self, digest: encoding::Digest) -> Result<Option<ChunkIndex>> {
+        each_variant!(&**self, repo, { repo.read_chunk_index(digest).await })
+    }
+
+    async unsafe fn write_chunk_index(&self, index: &ChunkIndex) -> Result<()> {
+        // Safety: we are wrapping the same underlying unsafe function and
+        // so the same safety holds for our callers
+        unsafe { each_variant!(&**self, repo, { repo.write_chunk_index(index).await }) }
+    }
 }
 
 impl BlobStorage for Arc<RepositoryHandle> {}
@@ -0,0 +1,85 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use std::pin::Pin;
+
+use chrono::{DateTime, Utc};
+use futures::{Stream, TryStreamExt};
+
+use super::HttpRepository;
+use crate::graph::{self, ObjectProto};
+use crate::{encoding, Error, Result};
+
+const OBJECTS: &str = "objects";
+
+#[async_trait::async_trait]
+impl graph::DatabaseView for HttpRepository {
+    async fn has_object(&self, digest: encoding::Digest) -> bool {
+        self.server
+            .exists(self.server.digest_url(OBJECTS, &digest))
+            .await
+    }
+
+    async fn read_object(&self, digest: encoding::Digest) -> Result<graph::Object> {
+        let url = self.server.digest_url(OBJECTS, &digest);
+        let Some(resp) = self.server.get(url).await? else {
+            return Err(Error::UnknownObject(digest));
+        };
+        graph::Object::new(resp.bytes().await?)
+    }
+
+    fn find_digests(
+        &self,
+        search_criteria: graph::DigestSearchCriteria,
+    ) -> Pin<Box<dyn Stream<Item = Result<encoding::Digest>> + Send>> {
+        let mut prefix = None;
+        if let graph::DigestSearchCriteria::StartsWith(partial) = &search_criteria {
+            // the encoded partial digest can only be trusted to identify
+            // a single prefix when it encodes to more characters than that
+            // prefix, because base 32 may encode partial data to the final
+            // character
+            let encoded = partial.to_string();
+            if encoded.len() > 2 {
+                prefix = Some(encoded[..2].to_owned());
+            }
+        }
+        let digests = self.server.clone().iter_digests(OBJECTS, prefix);
+        Box::pin(digests.try_filter(move |digest| {
+            let matches = match &search_criteria {
+                graph::DigestSearchCriteria::All => true,
+                graph::DigestSearchCriteria::StartsWith(partial) => {
+                    digest.as_bytes().starts_with(partial.as_slice())
+                }
+            };
+            std::future::ready(matches)
+        }))
+    }
+
+    fn iter_objects(&self) -> graph::DatabaseIterator<'_> {
+        graph::DatabaseIterator::new(self)
+    }
+
+    fn walk_objects<'db>(&'db self, root: &encoding::Digest) -> graph::DatabaseWalker<'db> {
+        graph::DatabaseWalker::new(self, *root)
+    }
+}
+
+#[async_trait::async_trait]
+impl graph::Database for HttpRepository {
+    async fn write_object<T: ObjectProto>(&self, _obj: &graph::FlatObject<T>) -> Result<()> {
+        Err(self.read_only_error())
+    }
+
+    async fn remove_object(&self, _digest: encoding::Digest) -> Result<()> {
+        Err(self.read_only_error())
+    }
+
+    async fn remove_object_if_older_than(
+        &self,
+        _older_than: DateTime<Utc>,
+        _digest: encoding::Digest,
+    ) -> Result<bool> {
+        Err(self.read_only_error())
+    }
+}
@@ -0,0 +1,19 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+//! Reads an spfs repository from a static http file server.
+//!
+//! The server is expected to expose the on-disk layout of an
+//! [`super::fs::FsRepository`] as-is, for example by serving the
+//! repository root directory with nginx. Operations that need to
+//! enumerate objects or tags rely on the server providing html
+//! directory listings (eg: nginx `autoindex`). All write operations
+//! fail with [`crate::Error::RepositoryIsReadOnly`].
+
+mod database;
+mod payload;
+mod repository;
+mod tag;
+
+pub use repository::{Config, HttpRepository, Params};
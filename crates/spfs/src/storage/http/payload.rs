@@ -0,0 +1,157 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use std::path::PathBuf;
+use std::pin::Pin;
+
+use futures::{Stream, StreamExt, TryStreamExt};
+
+use super::repository::FileServer;
+use super::HttpRepository;
+use crate::encoding::prelude::*;
+use crate::storage::chunking::read_chunks;
+use crate::storage::fs::{decompress_payload, PayloadCompression, ZSTD_PAYLOADS_DIRNAME};
+use crate::storage::prelude::*;
+use crate::storage::ChunkIndex;
+use crate::tracking::BlobRead;
+use crate::{encoding, graph, Error, Result};
+
+const PAYLOADS: &str = "payloads";
+const CHUNKS: &str = "chunks";
+
+#[async_trait::async_trait]
+impl PayloadStorage for HttpRepository {
+    async fn has_payload(&self, digest: encoding::Digest) -> bool {
+        if has_whole_payload(&self.server, &digest).await {
+            return true;
+        }
+        // a chunked payload can only be read when all of its chunks exist
+        let Ok(Some(index)) = self.read_chunk_index(digest).await else {
+            return false;
+        };
+        for chunk in index.chunks() {
+            if !has_whole_payload(&self.server, &chunk.digest).await {
+                return false;
+            }
+        }
+        true
+    }
+
+    fn iter_payload_digests(&self) -> Pin<Box<dyn Stream<Item = Result<encoding::Digest>> + Send>> {
+        Box::pin(
+            self.server
+                .clone()
+                .iter_digests(PAYLOADS, None)
+                .chain(
+                    self.server
+                        .clone()
+                        .iter_digests(ZSTD_PAYLOADS_DIRNAME, None),
+                )
+                .chain(self.server.clone().iter_digests(CHUNKS, None)),
+        )
+    }
+
+    async unsafe fn write_data(
+        &self,
+        _reader: Pin<Box<dyn BlobRead>>,
+    ) -> Result<(encoding::Digest, u64)> {
+        Err(self.read_only_error())
+    }
+
+    async fn open_payload(
+        &self,
+        digest: encoding::Digest,
+    ) -> Result<(Pin<Box<dyn BlobRead>>, PathBuf)> {
+        if let Some(opened) = open_whole_payload(&self.server, &digest).await? {
+            return Ok(opened);
+        }
+        if let Some(index) = self.read_chunk_index(digest).await? {
+            let url = self.server.digest_url(CHUNKS, &digest);
+            let server = self.server.clone();
+            let stream = read_chunks(index, move |digest| {
+                let server = server.clone();
+                async move {
+                    Ok(open_whole_payload(&server, &digest)
+                        .await?
+                        .map(|(reader, _)| reader))
+                }
+            });
+            let reader = Box::pin(tokio::io::BufReader::new(
+                tokio_util::io::StreamReader::new(stream),
+            ));
+            return Ok((reader, PathBuf::from(url.to_string())));
+        }
+        // Return an error specific to this situation, whether the
+        // blob is really unknown or just the payload is missing.
+        match self.read_blob(digest).await {
+            Ok(blob) => Err(Error::ObjectMissingPayload(blob.into(), digest)),
+            Err(
+                err @ Error::NotCorrectKind {
+                    desired: graph::ObjectKind::Blob,
+                    ..
+                },
+            ) => Err(err),
+            Err(_) => Err(Error::UnknownObject(digest)),
+        }
+    }
+
+    async fn remove_payload(&self, _digest: encoding::Digest) -> Result<()> {
+        Err(self.read_only_error())
+    }
+
+    async fn read_chunk_index(&self, digest: encoding::Digest) -> Result<Option<ChunkIndex>> {
+        let url = self.server.digest_url(CHUNKS, &digest);
+        let Some(resp) = self.server.get(url).await? else {
+            return Ok(None);
+        };
+        let data = resp.bytes().await?;
+        Ok(Some(ChunkIndex::decode(&mut data.as_ref())?))
+    }
+}
+
+/// True if the payload is stored whole, with or without compression
+async fn has_whole_payload(server: &FileServer, digest: &encoding::Digest) -> bool {
+    for dir in [PAYLOADS, ZSTD_PAYLOADS_DIRNAME] {
+        if server.exists(server.digest_url(dir, digest)).await {
+            return true;
+        }
+    }
+    false
+}
+
+/// Open a payload that is stored whole, with or without compression,
+/// if it exists on the server
+async fn open_whole_payload(
+    server: &FileServer,
+    digest: &encoding::Digest,
+) -> Result<Option<(Pin<Box<dyn BlobRead>>, PathBuf)>> {
+    for (dir, compression) in [
+        (PAYLOADS, PayloadCompression::None),
+        (ZSTD_PAYLOADS_DIRNAME, PayloadCompression::Zstd),
+    ] {
+        let url = server.digest_url(dir, digest);
+        if let Some(reader) = open_file(server, url.clone(), compression).await? {
+            return Ok(Some((reader, PathBuf::from(url.to_string()))));
+        }
+    }
+    Ok(None)
+}
+
+/// Open a payload file for reading, if it exists on the server
+async fn open_file(
+    server: &FileServer,
+    url: url::Url,
+    compression: PayloadCompression,
+) -> Result<Option<Pin<Box<dyn BlobRead>>>> {
+    let Some(resp) = server.get(url.clone()).await? else {
+        return Ok(None);
+    };
+    // the stream must return io errors in order to be converted to a reader
+    let stream = resp.bytes_stream().map_err(std::io::Error::other);
+    let reader = tokio_util::io::StreamReader::new(sync_wrapper::SyncStream::new(stream));
+    Ok(Some(decompress_payload(
+        tokio::io::BufReader::new(reader),
+        compression,
+    )))
+}
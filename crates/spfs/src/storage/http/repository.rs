@@ -0,0 +1,285 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use futures::Stream;
+
+use crate::config::ToAddress;
+use crate::storage::{
+    BlobStorage,
+    LayerStorage,
+    ManifestStorage,
+    OpenRepositoryError,
+    OpenRepositoryResult,
+    PlatformStorage,
+    TagNamespace,
+    TagNamespaceBuf,
+    TagStorageMut,
+};
+use crate::{encoding, storage, Error, Result};
+
+#[cfg(test)]
+#[path = "./repository_test.rs"]
+mod repository_test;
+
+/// Configures a read-only repository served over http(s)
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct Config {
+    pub address: url::Url,
+    #[serde(flatten)]
+    pub params: Params,
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, Default)]
+pub struct Params {
+    /// The global timeout for all requests made in this client
+    ///
+    /// Default is no timeout
+    pub timeout_ms: Option<u64>,
+
+    /// optional tag namespace to use when querying tags
+    pub tag_namespace: Option<TagNamespaceBuf>,
+}
+
+#[async_trait::async_trait]
+impl storage::FromUrl for Config {
+    async fn from_url(url: &url::Url) -> OpenRepositoryResult<Self> {
+        let mut address = url.clone();
+        let params = if let Some(qs) = address.query() {
+            serde_qs::from_str(qs)
+                .map_err(|source| OpenRepositoryError::invalid_query(url, source))?
+        } else {
+            Params::default()
+        };
+        address.set_query(None);
+        Ok(Self { address, params })
+    }
+}
+
+impl ToAddress for Config {
+    fn to_address(&self) -> Result<url::Url> {
+        let query = serde_qs::to_string(&self.params).map_err(|err| {
+            crate::Error::String(format!(
+                "Http repo parameters do not create a valid url: {err:?}"
+            ))
+        })?;
+        let mut address = self.address.clone();
+        if !query.is_empty() {
+            address.set_query(Some(&query));
+        }
+        Ok(address)
+    }
+}
+
+/// A read-only spfs repository served by a static http file server.
+pub struct HttpRepository {
+    address: url::Url,
+    pub(super) server: FileServer,
+    /// the namespace to use for tag resolution. If set, then this is treated
+    /// as "chroot" of the real tag root.
+    tag_namespace: Option<TagNamespaceBuf>,
+}
+
+#[async_trait::async_trait]
+impl storage::FromConfig for HttpRepository {
+    type Config = Config;
+
+    async fn from_config(config: Self::Config) -> OpenRepositoryResult<Self> {
+        Self::new(config)
+    }
+}
+
+impl std::fmt::Debug for HttpRepository {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("HttpRepository<{}>", &self.address))
+    }
+}
+
+impl HttpRepository {
+    /// Create a new client for the repository served at the configured address
+    pub fn new(config: Config) -> OpenRepositoryResult<Self> {
+        let mut builder = reqwest::Client::builder();
+        if let Some(ms) = config.params.timeout_ms {
+            builder = builder.timeout(std::time::Duration::from_millis(ms));
+        }
+        let mut base = config.address.clone();
+        base.set_query(None);
+        base.set_fragment(None);
+        if !base.path().ends_with('/') {
+            base.set_path(&format!("{}/", base.path()));
+        }
+        Ok(Self {
+            address: config.to_address().expect("an internally valid config"),
+            server: FileServer {
+                client: builder.build()?,
+                base,
+            },
+            tag_namespace: config.params.tag_namespace,
+        })
+    }
+
+    /// The namespace to use for tag resolution.
+    pub fn tag_namespace(&self) -> Option<&TagNamespace> {
+        self.tag_namespace.as_deref()
+    }
+
+    /// Set the namespace to use for tag resolution.
+    ///
+    /// Returns the previous namespace, if any.
+    pub fn set_tag_namespace(
+        &mut self,
+        tag_namespace: Option<TagNamespaceBuf>,
+    ) -> Option<TagNamespaceBuf> {
+        std::mem::replace(&mut self.tag_namespace, tag_namespace)
+    }
+
+    /// The error returned by all operations that would modify the repository
+    pub(super) fn read_only_error(&self) -> Error {
+        Error::RepositoryIsReadOnly(self.address.clone())
+    }
+}
+
+/// Fetches the files of a repository from an http server
+#[derive(Clone)]
+pub(super) struct FileServer {
+    client: reqwest::Client,
+    /// the root url of the repository, always ending with a slash
+    base: url::Url,
+}
+
+impl FileServer {
+    /// The url of a file in the repository, given its path segments
+    pub(super) fn file_url<S: AsRef<str>>(&self, segments: &[S]) -> url::Url {
+        let mut url = self.base.clone();
+        url.path_segments_mut()
+            .expect("http urls can always be a base")
+            .pop_if_empty()
+            .extend(segments);
+        url
+    }
+
+    /// The url of the given digest in a directory of content-addressed files
+    pub(super) fn digest_url(&self, kind: &str, digest: &encoding::Digest) -> url::Url {
+        let digest_str = digest.to_string();
+        self.file_url(&[kind, &digest_str[..2], &digest_str[2..]])
+    }
+
+    /// Fetch the contents of a file from the server.
+    ///
+    /// Returns `None` if the server reports that the file does not exist.
+    pub(super) async fn get(&self, url: url::Url) -> Result<Option<reqwest::Response>> {
+        let resp = self.client.get(url).send().await?;
+        if resp.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        Ok(Some(resp.error_for_status()?))
+    }
+
+    /// True if the server reports that the given file exists
+    pub(super) async fn exists(&self, url: url::Url) -> bool {
+        match self.client.head(url).send().await {
+            Ok(resp) => resp.status().is_success(),
+            Err(_) => false,
+        }
+    }
+
+    /// List the names of the entries in a directory of the repository.
+    ///
+    /// This relies on the server generating an html index page for
+    /// the directory, and reports a missing directory as empty. The
+    /// names of subdirectories end with a slash.
+    pub(super) async fn list_dir<S: AsRef<str>>(&self, segments: &[S]) -> Result<Vec<String>> {
+        let mut url = self.file_url(segments);
+        url.path_segments_mut()
+            .expect("http urls can always be a base")
+            .push("");
+        let Some(resp) = self.get(url.clone()).await? else {
+            return Ok(Vec::new());
+        };
+        let page = resp.text().await?;
+        Ok(parse_index_page(&url, &page))
+    }
+
+    /// Iterate the digests of all files in a directory of content-addressed
+    /// files, optionally limited to the given two character prefix.
+    pub(super) fn iter_digests(
+        self,
+        kind: &'static str,
+        prefix: Option<String>,
+    ) -> impl Stream<Item = Result<encoding::Digest>> + Send + 'static {
+        async_stream::try_stream! {
+            let prefixes = match prefix {
+                Some(prefix) => vec![prefix],
+                None => self.list_dir(&[kind]).await?,
+            };
+            for prefix in prefixes {
+                let prefix = prefix.trim_end_matches('/');
+                for name in self.list_dir(&[kind, prefix]).await? {
+                    if let Some(digest) = digest_from_parts(prefix, &name) {
+                        yield digest;
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl storage::Repository for HttpRepository {
+    fn address(&self) -> url::Url {
+        self.address.clone()
+    }
+}
+
+impl TagStorageMut for HttpRepository {
+    fn try_set_tag_namespace(
+        &mut self,
+        tag_namespace: Option<TagNamespaceBuf>,
+    ) -> Result<Option<TagNamespaceBuf>> {
+        Ok(self.set_tag_namespace(tag_namespace))
+    }
+}
+
+impl BlobStorage for HttpRepository {}
+impl ManifestStorage for HttpRepository {}
+impl LayerStorage for HttpRepository {}
+impl PlatformStorage for HttpRepository {}
+
+/// Collect the names of the entries linked from a directory index page.
+///
+/// Only links that point directly to a child of the directory
+/// are considered entries. Sorting links, parent directory links
+/// and links to other locations are ignored.
+fn parse_index_page(dir: &url::Url, page: &str) -> Vec<String> {
+    let mut entries = Vec::new();
+    for (start, attr) in page.match_indices("href=\"") {
+        let Some((href, _)) = page[start + attr.len()..].split_once('"') else {
+            continue;
+        };
+        let Ok(target) = dir.join(href) else {
+            continue;
+        };
+        if target.origin() != dir.origin() || target.query().is_some() {
+            continue;
+        }
+        let Some(name) = target.path().strip_prefix(dir.path()) else {
+            continue;
+        };
+        let trimmed = name.strip_suffix('/').unwrap_or(name);
+        if trimmed.is_empty() || trimmed.contains('/') {
+            continue;
+        }
+        let name = percent_encoding::percent_decode_str(name)
+            .decode_utf8_lossy()
+            .into_owned();
+        if !entries.contains(&name) {
+            entries.push(name);
+        }
+    }
+    entries
+}
+
+/// Parse the digest of a content-addressed file, given the
+/// name of its two character prefix directory and the file name.
+fn digest_from_parts(prefix: &str, rest: &str) -> Option<encoding::Digest> {
+    encoding::Digest::parse(&format!("{prefix}{rest}")).ok()
+}
@@ -0,0 +1,199 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use std::path::{Path, PathBuf};
+
+use futures::TryStreamExt;
+use rstest::rstest;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt};
+
+use super::{parse_index_page, HttpRepository};
+use crate::fixtures::*;
+use crate::prelude::*;
+use crate::storage::{EntryType, FromUrl, RepositoryHandle};
+use crate::{encoding, tracking, Error, Syncer};
+
+/// Serve the contents of a directory over http, like a static file
+/// server with directory listings enabled.
+async fn serve_dir(root: PathBuf) -> url::Url {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        while let Ok((stream, _)) = listener.accept().await {
+            let root = root.clone();
+            tokio::spawn(async move {
+                let mut stream = tokio::io::BufReader::new(stream);
+                let mut request = String::new();
+                stream.read_line(&mut request).await.unwrap();
+                loop {
+                    let mut header = String::new();
+                    stream.read_line(&mut header).await.unwrap();
+                    if header.trim().is_empty() {
+                        break;
+                    }
+                }
+                let mut parts = request.split_whitespace();
+                let method = parts.next().unwrap_or_default().to_owned();
+                let path = parts.next().unwrap_or_default();
+                let path = percent_encoding::percent_decode_str(path)
+                    .decode_utf8_lossy()
+                    .into_owned();
+                let (status, body) = respond(&root.join(path.trim_start_matches('/')), &path);
+                let mut stream = stream.into_inner();
+                let head = format!(
+                    "HTTP/1.1 {status}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    body.len()
+                );
+                stream.write_all(head.as_bytes()).await.unwrap();
+                if method != "HEAD" {
+                    stream.write_all(&body).await.unwrap();
+                }
+                stream.shutdown().await.unwrap();
+            });
+        }
+    });
+    url::Url::parse(&format!("http://{address}/repo/")).unwrap()
+}
+
+fn respond(path: &Path, request_path: &str) -> (&'static str, Vec<u8>) {
+    if path.is_file() {
+        return ("200 OK", std::fs::read(path).unwrap());
+    }
+    if !path.is_dir() || !request_path.ends_with('/') {
+        return ("404 Not Found", Vec::new());
+    }
+    let mut page = String::from("<html><body><a href=\"../\">../</a>\n");
+    for entry in std::fs::read_dir(path).unwrap() {
+        let entry = entry.unwrap();
+        let mut name = percent_encoding::utf8_percent_encode(
+            &entry.file_name().to_string_lossy(),
+            percent_encoding::NON_ALPHANUMERIC,
+        )
+        .to_string();
+        if entry.path().is_dir() {
+            name.push('/');
+        }
+        page.push_str(&format!("<a href=\"{name}\">{name}</a>\n"));
+    }
+    page.push_str("</body></html>");
+    ("200 OK", page.into_bytes())
+}
+
+#[rstest]
+fn test_parse_index_page() {
+    let dir = url::Url::parse("http://localhost/repo/tags/").unwrap();
+    let page = r#"
+        <a href="?C=N;O=D">Name</a>
+        <a href="/repo/">Parent Directory</a>
+        <a href="../">../</a>
+        <a href="spi/">spi/</a>
+        <a href="test.tag">test.tag</a>
+        <a href="ns%23ns/">ns#ns/</a>
+        <a href="http://elsewhere/repo/tags/other.tag">other.tag</a>
+        <a href="/repo/tags/absolute.tag">absolute.tag</a>
+    "#;
+    assert_eq!(
+        parse_index_page(&dir, page),
+        vec!["spi/", "test.tag", "ns#ns/", "absolute.tag"]
+    );
+}
+
+#[rstest]
+#[tokio::test(flavor = "multi_thread")]
+async fn test_read_served_fs_repository(tmpdir: tempfile::TempDir, #[future] tmprepo: TempRepo) {
+    init_logging();
+    let served: RepositoryHandle =
+        crate::storage::fs::FsRepository::create(tmpdir.path().join("repo"))
+            .await
+            .unwrap()
+            .into();
+
+    let src_dir = tmpdir.path().join("source");
+    ensure(src_dir.join("dir/file.txt"), "hello");
+    ensure(src_dir.join("dir2/otherfile.txt"), "hello2");
+    let manifest = crate::Committer::new(&served)
+        .commit_dir(src_dir.as_path())
+        .await
+        .unwrap();
+    let layer = served
+        .create_layer(&manifest.to_graph_manifest())
+        .await
+        .unwrap();
+    let platform = served
+        .create_platform(layer.digest().unwrap().into())
+        .await
+        .unwrap();
+    let platform_digest = platform.digest().unwrap();
+    let tag = tracking::TagSpec::parse("testing/platform").unwrap();
+    served.push_tag(&tag, &platform_digest).await.unwrap();
+    served
+        .push_tag(&tag, &layer.digest().unwrap())
+        .await
+        .unwrap();
+
+    let address = serve_dir(tmpdir.path().to_owned()).await;
+    let repo = HttpRepository::from_url(&address).await.unwrap();
+
+    let versions: Vec<_> = repo
+        .read_tag(&tag)
+        .await
+        .unwrap()
+        .try_collect()
+        .await
+        .unwrap();
+    assert_eq!(versions.len(), 2);
+    assert_eq!(
+        versions[0].target,
+        layer.digest().unwrap(),
+        "tags should be read newest first"
+    );
+    let entries: Vec<_> = repo
+        .ls_tags(relative_path::RelativePath::new("/"))
+        .try_collect()
+        .await
+        .unwrap();
+    assert_eq!(entries, vec![EntryType::Folder("testing".into())]);
+    let found: Vec<_> = repo
+        .find_tags(&platform_digest)
+        .try_collect()
+        .await
+        .unwrap();
+    assert_eq!(found, vec![tag.with_version(1)]);
+
+    assert!(repo.has_object(platform_digest).await);
+    let partial = encoding::PartialDigest::from(&platform_digest.as_bytes()[..10]);
+    assert_eq!(
+        repo.resolve_full_digest(&partial).await.unwrap(),
+        platform_digest
+    );
+    let file = manifest
+        .walk()
+        .find(|node| node.entry.kind.is_blob())
+        .map(|node| node.entry.object)
+        .unwrap();
+    let (mut payload, _) = repo.open_payload(file).await.unwrap();
+    let mut data = String::new();
+    payload.read_to_string(&mut data).await.unwrap();
+    assert!(data == "hello" || data == "hello2");
+
+    let err = repo
+        .push_tag(&tag, &platform_digest)
+        .await
+        .expect_err("http repositories should be read-only");
+    assert!(matches!(err, Error::RepositoryIsReadOnly(_)), "{err:?}");
+    let err = repo
+        .remove_payload(file)
+        .await
+        .expect_err("http repositories should be read-only");
+    assert!(matches!(err, Error::RepositoryIsReadOnly(_)), "{err:?}");
+
+    let dest = tmprepo.await;
+    let repo = RepositoryHandle::from(repo);
+    Syncer::new(&repo, &dest)
+        .sync_ref(tag.to_string())
+        .await
+        .expect("should be able to pull from an http repository");
+    assert!(dest.has_object(layer.digest().unwrap()).await);
+    assert!(dest.has_payload(file).await);
+}
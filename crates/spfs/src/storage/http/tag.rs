@@ -0,0 +1,211 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use std::borrow::Cow;
+use std::mem::size_of;
+use std::pin::Pin;
+
+use futures::{Stream, StreamExt};
+use relative_path::RelativePath;
+
+use super::repository::FileServer;
+use super::HttpRepository;
+use crate::encoding::prelude::*;
+use crate::storage::tag::{EntryType, TagSpecAndTagStream, TagStream};
+use crate::storage::{TagNamespace, TagStorage, TAG_NAMESPACE_MARKER};
+use crate::{encoding, tracking, Error, Result};
+
+const TAG_EXT: &str = ".tag";
+
+impl HttpRepository {
+    /// The path segments of the tags directory for the given namespace
+    fn tags_root_in_namespace(&self, namespace: Option<&TagNamespace>) -> Vec<String> {
+        let mut segments = vec!["tags".to_string()];
+        if let Some(tag_namespace) = namespace {
+            for component in tag_namespace.as_rel_path().components() {
+                // Assuming the tag namespace is only made up of `Normal`
+                // elements (validated elsewhere).
+                let relative_path::Component::Normal(component) = component else {
+                    continue;
+                };
+                segments.push(format!("{component}{TAG_NAMESPACE_MARKER}"));
+            }
+        }
+        segments
+    }
+}
+
+#[async_trait::async_trait]
+impl TagStorage for HttpRepository {
+    #[inline]
+    fn get_tag_namespace(&self) -> Option<Cow<'_, TagNamespace>> {
+        self.tag_namespace().map(Cow::Borrowed)
+    }
+
+    fn ls_tags_in_namespace(
+        &self,
+        namespace: Option<&TagNamespace>,
+        path: &RelativePath,
+    ) -> Pin<Box<dyn Stream<Item = Result<EntryType>> + Send>> {
+        let mut segments = self.tags_root_in_namespace(namespace);
+        for component in path.components() {
+            if let relative_path::Component::Normal(component) = component {
+                segments.push(component.to_string());
+            }
+        }
+        let server = self.server.clone();
+        Box::pin(async_stream::try_stream! {
+            for name in server.list_dir(&segments).await? {
+                if let Some(stem) = name.strip_suffix(TAG_EXT) {
+                    yield EntryType::Tag(stem.to_owned());
+                } else if let Some(dir) = name.strip_suffix('/') {
+                    match dir.split_once(TAG_NAMESPACE_MARKER) {
+                        Some((name, _)) => yield EntryType::Namespace(name.to_owned()),
+                        None => yield EntryType::Folder(dir.to_owned()),
+                    }
+                }
+            }
+        })
+    }
+
+    /// Find tags that point to the given digest.
+    ///
+    /// This is an O(n) operation based on the number of all
+    /// tag versions in each tag stream.
+    fn find_tags_in_namespace(
+        &self,
+        namespace: Option<&TagNamespace>,
+        digest: &encoding::Digest,
+    ) -> Pin<Box<dyn Stream<Item = Result<tracking::TagSpec>> + Send>> {
+        let digest = *digest;
+        let stream = self.iter_tag_streams_in_namespace(namespace);
+        let mapped = futures::StreamExt::filter_map(stream, move |res| async move {
+            let (spec, stream) = match res {
+                Ok(res) => res,
+                Err(err) => return Some(Err(err)),
+            };
+            let mut stream = futures::StreamExt::enumerate(stream);
+            while let Some((i, tag)) = stream.next().await {
+                match tag {
+                    Ok(tag) if tag.target == digest => {
+                        return Some(Ok(spec.with_version(i as u64)));
+                    }
+                    Ok(_) => continue,
+                    Err(err) => return Some(Err(err)),
+                }
+            }
+            None
+        });
+        Box::pin(mapped)
+    }
+
+    /// Iterate through the available tags in this storage.
+    fn iter_tag_streams_in_namespace(
+        &self,
+        namespace: Option<&TagNamespace>,
+    ) -> Pin<Box<dyn Stream<Item = Result<TagSpecAndTagStream>> + Send>> {
+        let root = self.tags_root_in_namespace(namespace);
+        let server = self.server.clone();
+        Box::pin(async_stream::try_stream! {
+            // each entry is the path of a directory relative to the tags root
+            let mut to_visit = vec![Vec::<String>::new()];
+            while let Some(dir) = to_visit.pop() {
+                let segments: Vec<_> = root.iter().chain(dir.iter()).collect();
+                for name in server.list_dir(&segments).await? {
+                    if let Some(stem) = name.strip_suffix(TAG_EXT) {
+                        let mut path = dir.clone();
+                        path.push(stem.to_owned());
+                        let spec = tracking::TagSpec::parse(path.join("/"))?;
+                        let tags = read_tag_file(&server, &root, &spec).await?.unwrap_or_default();
+                        let stream: TagStream = Box::pin(futures::stream::iter(tags.into_iter().map(Ok)));
+                        yield (spec, stream);
+                    } else if let Some(subdir) = name.strip_suffix('/') {
+                        if subdir.contains(TAG_NAMESPACE_MARKER) {
+                            // tags in nested namespaces are not part of this one
+                            continue;
+                        }
+                        let mut path = dir.clone();
+                        path.push(subdir.to_owned());
+                        to_visit.push(path);
+                    }
+                }
+            }
+        })
+    }
+
+    async fn read_tag_in_namespace(
+        &self,
+        namespace: Option<&TagNamespace>,
+        tag: &tracking::TagSpec,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<tracking::Tag>> + Send>>> {
+        let root = self.tags_root_in_namespace(namespace);
+        match read_tag_file(&self.server, &root, tag).await? {
+            Some(tags) => Ok(Box::pin(futures::stream::iter(tags.into_iter().map(Ok)))),
+            None => Err(Error::UnknownReference(tag.to_string())),
+        }
+    }
+
+    async fn insert_tag_in_namespace(
+        &self,
+        _namespace: Option<&TagNamespace>,
+        _tag: &tracking::Tag,
+    ) -> Result<()> {
+        Err(self.read_only_error())
+    }
+
+    async fn remove_tag_stream_in_namespace(
+        &self,
+        _namespace: Option<&TagNamespace>,
+        _tag: &tracking::TagSpec,
+    ) -> Result<()> {
+        Err(self.read_only_error())
+    }
+
+    async fn remove_tag_in_namespace(
+        &self,
+        _namespace: Option<&TagNamespace>,
+        _tag: &tracking::Tag,
+    ) -> Result<()> {
+        Err(self.read_only_error())
+    }
+}
+
+/// Load all of the tags in the identified tag file, if it exists.
+///
+/// The tags are returned from latest to earliest.
+async fn read_tag_file(
+    server: &FileServer,
+    root: &[String],
+    spec: &tracking::TagSpec,
+) -> Result<Option<Vec<tracking::Tag>>> {
+    let mut segments = root.to_vec();
+    if let Some(org) = spec.org() {
+        segments.extend(org.split('/').map(ToOwned::to_owned));
+    }
+    segments.push(format!("{}{TAG_EXT}", spec.name()));
+    let Some(resp) = server.get(server.file_url(&segments)).await? else {
+        return Ok(None);
+    };
+    let data = resp.bytes().await?;
+
+    // Tag files are a sequence of encoded tags, from earliest to latest,
+    // each prefixed by its size as a big-endian 64 bit integer.
+    let mut tags = Vec::new();
+    let mut data = data.as_ref();
+    while !data.is_empty() {
+        let Some((size, rest)) = data.split_first_chunk::<{ size_of::<i64>() }>() else {
+            return Err(Error::String("tag file is truncated".into()));
+        };
+        let size = usize::try_from(i64::from_be_bytes(*size))
+            .map_err(|err| Error::String(format!("tag file contains invalid size index: {err}")))?;
+        if rest.len() < size {
+            return Err(Error::String("tag file is truncated".into()));
+        }
+        let (mut tag, rest) = rest.split_at(size);
+        tags.push(tracking::Tag::decode(&mut tag)?);
+        data = rest;
+    }
+    tags.reverse();
+    Ok(Some(tags))
+}
@@ -12,6 +12,7 @@ use crate::tracking;
 #[rstest]
 #[case::fs(tmprepo("fs"))]
 #[case::tar(tmprepo("tar"))]
+#[case::s3(tmprepo("s3"))]
 #[cfg_attr(feature = "server", case::rpc(tmprepo("rpc")))]
 #[tokio::test]
 async fn test_read_write_manifest(
@@ -50,6 +51,7 @@ async fn test_read_write_manifest(
 #[rstest]
 #[case::fs(tmprepo("fs"))]
 #[case::tar(tmprepo("tar"))]
+#[case::s3(tmprepo("s3"))]
 #[cfg_attr(feature = "server", case::rpc(tmprepo("rpc")))]
 #[tokio::test]
 async fn test_manifest_parity(
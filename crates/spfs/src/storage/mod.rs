@@ -3,6 +3,7 @@
 // https://github.com/spkenv/spk
 
 mod blob;
+pub mod chunking;
 mod error;
 mod layer;
 mod manifest;
@@ -10,22 +11,26 @@ pub mod payload;
 mod platform;
 mod repository;
 mod tag;
+mod tag_change;
 mod tag_namespace;
 
 mod config;
 pub mod fallback;
 pub mod fs;
 mod handle;
+pub mod http;
 pub mod pinned;
 pub mod prelude;
 pub mod proxy;
 pub mod rpc;
+pub mod s3;
 pub mod tar;
 
 use std::sync::Arc;
 
 pub use blob::BlobStorage;
 use chrono::{DateTime, Utc};
+pub use chunking::{Chunk, ChunkIndex};
 pub use error::OpenRepositoryError;
 pub use layer::LayerStorage;
 pub use manifest::ManifestStorage;
@@ -34,6 +39,13 @@ pub use platform::PlatformStorage;
 pub use proxy::{Config, ProxyRepository};
 pub use repository::{LocalRepository, Repository};
 pub use tag::{EntryType, TagStorage, TagStorageMut};
+pub use tag_change::{
+    follow_tag_changes,
+    TagChange,
+    TagChangeKind,
+    TagChangeStream,
+    TAG_CHANGE_POLL_INTERVAL,
+};
 pub use tag_namespace::{TagNamespace, TagNamespaceBuf, TAG_NAMESPACE_MARKER};
 
 pub use self::config::{FromConfig, FromUrl, OpenRepositoryResult};
@@ -45,6 +57,8 @@ pub enum RepositoryHandle {
     FS(fs::FsRepository),
     Tar(tar::TarRepository),
     Rpc(rpc::RpcRepository),
+    S3(s3::S3Repository),
+    Http(http::HttpRepository),
     FallbackProxy(Box<fallback::FallbackProxy>),
     Proxy(Box<proxy::ProxyRepository>),
     Pinned(Box<pinned::PinnedRepository<RepositoryHandle>>),
@@ -94,6 +108,8 @@ impl RepositoryHandle {
             RepositoryHandle::FS(repo) => Ok(repo),
             RepositoryHandle::Tar(repo) => Ok(repo),
             RepositoryHandle::Rpc(repo) => Ok(repo),
+            RepositoryHandle::S3(repo) => Ok(repo),
+            RepositoryHandle::Http(repo) => Ok(repo),
             RepositoryHandle::FallbackProxy(repo) => Ok(&mut **repo),
             RepositoryHandle::Proxy(repo) => Ok(&mut **repo),
             RepositoryHandle::Pinned(_) => Err(Error::RepositoryIsPinned),
@@ -131,6 +147,18 @@ impl From<rpc::RpcRepository> for RepositoryHandle {
     }
 }
 
+impl From<s3::S3Repository> for RepositoryHandle {
+    fn from(repo: s3::S3Repository) -> Self {
+        RepositoryHandle::S3(repo)
+    }
+}
+
+impl From<http::HttpRepository> for RepositoryHandle {
+    fn from(repo: http::HttpRepository) -> Self {
+        RepositoryHandle::Http(repo)
+    }
+}
+
 impl From<fallback::FallbackProxy> for RepositoryHandle {
     fn from(repo: fallback::FallbackProxy) -> Self {
         RepositoryHandle::FallbackProxy(Box::new(repo))
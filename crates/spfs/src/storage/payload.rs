@@ -6,8 +6,9 @@ use std::pin::Pin;
 
 use futures::Stream;
 
+use super::ChunkIndex;
 use crate::tracking::BlobRead;
-use crate::{encoding, Result};
+use crate::{encoding, Error, Result};
 
 #[cfg(test)]
 #[path = "payload_test.rs"]
@@ -48,6 +49,35 @@ pub trait PayloadStorage: Sync + Send {
     /// Errors:
     /// - [`crate::Error::UnknownObject`]: if the payload does not exist in this storage
     async fn remove_payload(&self, digest: encoding::Digest) -> Result<()>;
+
+    /// True if this storage can store payloads as a sequence of
+    /// chunks, see [`Self::write_chunk_index`].
+    fn supports_chunked_payloads(&self) -> bool {
+        false
+    }
+
+    /// Return the chunk index of the identified payload, if it is
+    /// stored as a sequence of chunks.
+    ///
+    /// Payloads that are stored whole, or that do not exist, have no index.
+    async fn read_chunk_index(&self, _digest: encoding::Digest) -> Result<Option<ChunkIndex>> {
+        Ok(None)
+    }
+
+    /// Store a payload as the sequence of chunks described by the given index.
+    ///
+    /// Every chunk in the index must already exist in this storage as
+    /// a payload of its own.
+    ///
+    /// # Safety
+    ///
+    /// It is unsafe to write payload data without also creating a blob
+    /// to track that payload in the database.
+    async unsafe fn write_chunk_index(&self, _index: &ChunkIndex) -> Result<()> {
+        Err(Error::String(
+            "This repository does not support chunked payloads".to_string(),
+        ))
+    }
 }
 
 #[async_trait::async_trait]
@@ -79,4 +109,18 @@ impl<T: PayloadStorage> PayloadStorage for &T {
     async fn remove_payload(&self, digest: encoding::Digest) -> Result<()> {
         PayloadStorage::remove_payload(&**self, digest).await
     }
+
+    fn supports_chunked_payloads(&self) -> bool {
+        PayloadStorage::supports_chunked_payloads(&**self)
+    }
+
+    async fn read_chunk_index(&self, digest: encoding::Digest) -> Result<Option<ChunkIndex>> {
+        PayloadStorage::read_chunk_index(&**self, digest).await
+    }
+
+    async unsafe fn write_chunk_index(&self, index: &ChunkIndex) -> Result<()> {
+        // Safety: we are wrapping the same underlying unsafe function and
+        // so the same safety holds for our callers
+        unsafe { PayloadStorage::write_chunk_index(&**self, index).await }
+    }
 }
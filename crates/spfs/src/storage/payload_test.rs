@@ -12,6 +12,7 @@ use crate::prelude::*;
 #[rstest]
 #[case::fs(tmprepo("fs"))]
 #[case::tar(tmprepo("tar"))]
+#[case::s3(tmprepo("s3"))]
 #[cfg_attr(feature = "server", case::rpc(tmprepo("rpc")))]
 #[tokio::test]
 async fn test_payload_io(
@@ -47,6 +48,7 @@ async fn test_payload_io(
 #[rstest]
 #[case::fs(tmprepo("fs"))]
 #[case::tar(tmprepo("tar"))]
+#[case::s3(tmprepo("s3"))]
 #[cfg_attr(feature = "server", case::rpc(tmprepo("rpc")))]
 #[tokio::test]
 async fn test_payload_existence(
@@ -79,6 +81,7 @@ async fn test_payload_existence(
 #[rstest]
 #[case::fs(tmprepo("fs"))]
 #[case::tar(tmprepo("tar"))]
+#[case::s3(tmprepo("s3"))]
 #[cfg_attr(feature = "server", case::rpc(tmprepo("rpc")))]
 #[tokio::test]
 async fn test_payloads_iter(
@@ -11,6 +11,7 @@ use spfs_encoding as encoding;
 
 use crate::graph::ObjectProto;
 use crate::storage::prelude::*;
+use crate::storage::ChunkIndex;
 use crate::tracking::BlobRead;
 use crate::{graph, Error, Result};
 
@@ -147,6 +148,22 @@ where
     async fn remove_payload(&self, _digest: encoding::Digest) -> Result<()> {
         Err(Error::RepositoryIsPinned)
     }
+
+    fn supports_chunked_payloads(&self) -> bool {
+        self.inner.supports_chunked_payloads()
+    }
+
+    async fn read_chunk_index(&self, digest: encoding::Digest) -> Result<Option<ChunkIndex>> {
+        self.inner.read_chunk_index(digest).await
+    }
+
+    async unsafe fn write_chunk_index(&self, index: &ChunkIndex) -> Result<()> {
+        // chunk indices are stored by digest, and so are treated
+        // the same as payload data, see `write_data`
+
+        // Safety: we are simply calling the same inner unsafe function
+        unsafe { self.inner.write_chunk_index(index).await }
+    }
 }
 
 impl<T> BlobStorage for PinnedRepository<T> where T: BlobStorage + 'static {}
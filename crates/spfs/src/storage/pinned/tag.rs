@@ -12,7 +12,7 @@ use relative_path::RelativePath;
 
 use super::PinnedRepository;
 use crate::storage::tag::{EntryType, TagSpecAndTagStream, TagStream};
-use crate::storage::{TagNamespace, TagStorage};
+use crate::storage::{TagChangeStream, TagNamespace, TagStorage};
 use crate::{encoding, tracking, Error, Result};
 
 #[cfg(test)]
@@ -215,6 +215,19 @@ where
     ) -> Result<()> {
         Err(Error::RepositoryIsPinned)
     }
+
+    fn read_tag_changes_in_namespace(
+        &self,
+        namespace: Option<&TagNamespace>,
+        since: u64,
+    ) -> TagChangeStream<'static> {
+        let pin = self.pin;
+        Box::pin(
+            self.inner
+                .read_tag_changes_in_namespace(namespace, since)
+                .try_filter(move |change| ready(change.time <= pin)),
+        )
+    }
 }
 
 impl<T> PinnedRepository<T>
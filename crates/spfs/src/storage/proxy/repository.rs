@@ -14,9 +14,11 @@ use crate::graph::ObjectProto;
 use crate::prelude::*;
 use crate::storage::tag::TagSpecAndTagStream;
 use crate::storage::{
+    ChunkIndex,
     EntryType,
     OpenRepositoryError,
     OpenRepositoryResult,
+    TagChangeStream,
     TagNamespace,
     TagNamespaceBuf,
     TagStorageMut,
@@ -238,6 +240,28 @@ impl PayloadStorage for ProxyRepository {
         self.primary.remove_payload(digest).await?;
         Ok(())
     }
+
+    fn supports_chunked_payloads(&self) -> bool {
+        self.primary.supports_chunked_payloads()
+    }
+
+    async fn read_chunk_index(&self, digest: encoding::Digest) -> Result<Option<ChunkIndex>> {
+        if let Some(index) = self.primary.read_chunk_index(digest).await? {
+            return Ok(Some(index));
+        }
+        for repo in self.secondary.iter() {
+            if let Some(index) = repo.read_chunk_index(digest).await? {
+                return Ok(Some(index));
+            }
+        }
+        Ok(None)
+    }
+
+    async unsafe fn write_chunk_index(&self, index: &ChunkIndex) -> Result<()> {
+        // Safety: we are wrapping the same underlying unsafe function and
+        // so the same safety holds for our callers
+        unsafe { self.primary.write_chunk_index(index).await }
+    }
 }
 
 #[async_trait::async_trait]
@@ -318,6 +342,24 @@ impl TagStorage for ProxyRepository {
         self.primary.remove_tag_in_namespace(namespace, tag).await?;
         Ok(())
     }
+
+    fn read_tag_changes_in_namespace(
+        &self,
+        namespace: Option<&TagNamespace>,
+        since: u64,
+    ) -> TagChangeStream<'static> {
+        self.primary.read_tag_changes_in_namespace(namespace, since)
+    }
+
+    fn watch_tag_changes_in_namespace(
+        &self,
+        namespace: Option<&TagNamespace>,
+        prefix: &RelativePath,
+        since: u64,
+    ) -> TagChangeStream<'_> {
+        self.primary
+            .watch_tag_changes_in_namespace(namespace, prefix, since)
+    }
 }
 
 impl TagStorageMut for ProxyRepository {
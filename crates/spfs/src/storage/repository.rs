@@ -146,6 +146,10 @@ pub trait LocalRepository {
     /// Return the payload storage type
     fn payloads(&self) -> &FsHashStore;
 
+    /// Return the storage for payloads that are kept compressed, which
+    /// cannot be linked into a render as-is
+    fn compressed_payloads(&self) -> &FsHashStore;
+
     /// If supported, returns the type responsible for locally rendered manifests
     ///
     /// # Errors:
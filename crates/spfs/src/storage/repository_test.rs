@@ -19,6 +19,7 @@ use crate::tracking::TagSpec;
 #[rstest]
 #[case::fs(tmprepo("fs"))]
 #[case::tar(tmprepo("tar"))]
+#[case::s3(tmprepo("s3"))]
 #[cfg_attr(feature = "server", case::rpc(tmprepo("rpc")))]
 #[tokio::test]
 async fn test_find_aliases(
@@ -112,6 +113,7 @@ async fn test_commit_mode_fs(tmpdir: tempfile::TempDir) {
 #[rstest]
 #[case::fs(tmprepo("fs"))]
 #[case::tar(tmprepo("tar"))]
+#[case::s3(tmprepo("s3"))]
 #[cfg_attr(feature = "server", case::rpc(tmprepo("rpc")))]
 #[tokio::test]
 async fn test_commit_broken_link(
@@ -139,6 +141,7 @@ async fn test_commit_broken_link(
 #[rstest]
 #[case::fs(tmprepo("fs"))]
 #[case::tar(tmprepo("tar"))]
+#[case::s3(tmprepo("s3"))]
 #[cfg_attr(feature = "server", case::rpc(tmprepo("rpc")))]
 #[tokio::test]
 // This test just needs the config to not change while it is running.
@@ -8,5 +8,6 @@ mod database;
 mod payload;
 mod repository;
 mod tag;
+mod tls;
 
 pub use repository::{Config, Params, RpcRepository};
@@ -56,7 +56,7 @@ impl storage::PayloadStorage for super::RpcRepository {
             compressed_reader,
             tokio_util::codec::BytesCodec::new(),
         );
-        let request = hyper::Request::builder()
+        let mut request = hyper::Request::builder()
             .method(hyper::Method::POST)
             .header(hyper::http::header::CONTENT_TYPE, "application/x-bzip2")
             .uri(&option.url)
@@ -64,6 +64,7 @@ impl storage::PayloadStorage for super::RpcRepository {
             .map_err(|err| {
                 crate::Error::String(format!("Failed to build upload request: {err:?}"))
             })?;
+        self.add_authorization(&mut request);
         let resp = self.http_client.request(request).await.map_err(|err| {
             crate::Error::String(format!("Failed to send upload request: {err:?}"))
         })?;
@@ -102,7 +103,7 @@ impl storage::PayloadStorage for super::RpcRepository {
             .locations
             .first()
             .ok_or_else(|| crate::Error::String("upload option gave no locations to try".into()))?;
-        let req = hyper::Request::builder()
+        let mut req = hyper::Request::builder()
             .uri(url_str)
             .method(hyper::http::Method::GET)
             .header(hyper::http::header::ACCEPT, "application/x-bzip2")
@@ -111,6 +112,7 @@ impl storage::PayloadStorage for super::RpcRepository {
             .map_err(|err| {
                 crate::Error::String(format!("Failed to build download request: {err:?}"))
             })?;
+        self.add_authorization(&mut req);
         let resp = self.http_client.request(req).await.map_err(|err| {
             crate::Error::String(format!("Failed to send download request: {err:?}"))
         })?;
@@ -140,6 +142,17 @@ impl storage::PayloadStorage for super::RpcRepository {
     }
 }
 
+impl super::RpcRepository {
+    /// Include the configured credentials in a request to the payload server
+    fn add_authorization(&self, request: &mut hyper::http::Request<hyper::Body>) {
+        if let Some(authorization) = &self.authorization {
+            request
+                .headers_mut()
+                .insert(hyper::http::header::AUTHORIZATION, authorization.clone());
+        }
+    }
+}
+
 fn open_download_stream(
     mut resp: hyper::http::Response<hyper::Body>,
 ) -> Result<Pin<Box<dyn BlobRead>>> {
@@ -2,8 +2,12 @@
 // SPDX-License-Identifier: Apache-2.0
 // https://github.com/spkenv/spk
 
+use std::path::PathBuf;
+
 use storage::FromUrl;
+use tonic::service::interceptor::InterceptedService;
 
+use super::tls::ClientTls;
 use crate::config::ToAddress;
 use crate::proto::database_service_client::DatabaseServiceClient;
 use crate::proto::payload_service_client::PayloadServiceClient;
@@ -12,6 +16,9 @@ use crate::proto::tag_service_client::TagServiceClient;
 use crate::storage::{OpenRepositoryError, OpenRepositoryResult, TagNamespace, TagNamespaceBuf};
 use crate::{proto, storage, Result};
 
+/// The address scheme of servers that are connected to over TLS
+const TLS_SCHEME: &str = "grpcs";
+
 /// Configures an rpc repository connection
 #[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
 pub struct Config {
@@ -43,6 +50,40 @@ pub struct Params {
 
     /// optional tag namespace to use when querying tags
     pub tag_namespace: Option<TagNamespaceBuf>,
+
+    /// A bearer token used to authenticate with the server
+    ///
+    /// The token is never included in the address of this repository,
+    /// so `token_env` should be preferred when the same credentials
+    /// are needed by other spfs processes (eg: runtime file systems)
+    #[serde(default, skip_serializing)]
+    pub token: Option<String>,
+
+    /// The name of an environment variable that holds the bearer
+    /// token used to authenticate with the server
+    pub token_env: Option<String>,
+
+    /// A bundle of PEM-encoded certificate authorities to trust when
+    /// connecting to the server over TLS, in addition to those of the system
+    pub ca_bundle: Option<PathBuf>,
+
+    /// A PEM-encoded certificate to present to the server, for
+    /// servers that identify their clients using mutual TLS
+    pub client_cert: Option<PathBuf>,
+
+    /// The PEM-encoded private key of the client certificate
+    pub client_key: Option<PathBuf>,
+}
+
+impl Params {
+    /// The bearer token to send to the server, if any
+    pub fn bearer_token(&self) -> Option<String> {
+        self.token.clone().or_else(|| {
+            self.token_env
+                .as_ref()
+                .and_then(|name| std::env::var(name).ok())
+        })
+    }
 }
 
 #[async_trait::async_trait]
@@ -73,14 +114,42 @@ impl ToAddress for Config {
     }
 }
 
+/// Adds the configured credentials to each outgoing gRPC request
+#[derive(Clone, Debug, Default)]
+pub(super) struct CredentialInterceptor {
+    authorization: Option<hyper::http::HeaderValue>,
+}
+
+impl tonic::service::Interceptor for CredentialInterceptor {
+    fn call(
+        &mut self,
+        mut request: tonic::Request<()>,
+    ) -> std::result::Result<tonic::Request<()>, tonic::Status> {
+        if let Some(authorization) = &self.authorization {
+            let value = tonic::metadata::MetadataValue::try_from(authorization.as_bytes())
+                .map_err(|err| tonic::Status::internal(err.to_string()))?;
+            request
+                .metadata_mut()
+                .insert(hyper::http::header::AUTHORIZATION.as_str(), value);
+        }
+        Ok(request)
+    }
+}
+
+/// A grpc connection that includes any configured credentials
+pub(super) type Channel = InterceptedService<tonic::transport::Channel, CredentialInterceptor>;
+
 #[derive(Clone, Debug)]
 pub struct RpcRepository {
     address: url::Url,
-    pub(super) repo_client: RepositoryClient<tonic::transport::Channel>,
-    pub(super) tag_client: TagServiceClient<tonic::transport::Channel>,
-    pub(super) db_client: DatabaseServiceClient<tonic::transport::Channel>,
-    pub(super) payload_client: PayloadServiceClient<tonic::transport::Channel>,
-    pub(super) http_client: hyper::Client<hyper::client::HttpConnector, hyper::Body>,
+    pub(super) repo_client: RepositoryClient<Channel>,
+    pub(super) tag_client: TagServiceClient<Channel>,
+    pub(super) db_client: DatabaseServiceClient<Channel>,
+    pub(super) payload_client: PayloadServiceClient<Channel>,
+    pub(super) http_client:
+        hyper::Client<hyper_rustls::HttpsConnector<hyper::client::HttpConnector>, hyper::Body>,
+    /// the authorization header to include in requests made to the server
+    pub(super) authorization: Option<hyper::http::HeaderValue>,
     /// the namespace to use for tag resolution. If set, then this is treated
     /// as "chroot" of the real tag root.
     tag_namespace: Option<TagNamespaceBuf>,
@@ -106,22 +175,68 @@ impl RpcRepository {
 
     /// Create a new rpc repository client for the given configuration
     pub async fn new(config: Config) -> OpenRepositoryResult<Self> {
-        let mut endpoint = tonic::transport::Endpoint::from_shared(config.address.to_string())
-            .map_err(|source| OpenRepositoryError::InvalidTransportAddress {
-                address: config.address.to_string(),
-                source,
+        let domain = config.address.host_str().unwrap_or_default();
+        let use_tls = config.address.scheme() == TLS_SCHEME;
+        let tls = ClientTls::load(&config.params, domain, use_tls).await?;
+        // the grpc client only uses tls for https addresses
+        let endpoint_address = match use_tls {
+            true => format!("https://{}", &config.address[url::Position::BeforeHost..]),
+            false => config.address.to_string(),
+        };
+        let mut endpoint =
+            tonic::transport::Endpoint::from_shared(endpoint_address).map_err(|source| {
+                OpenRepositoryError::InvalidTransportAddress {
+                    address: config.address.to_string(),
+                    source,
+                }
+            })?;
+        if use_tls {
+            endpoint = endpoint.tls_config(tls.grpc).map_err(|source| {
+                OpenRepositoryError::InvalidTransportAddress {
+                    address: config.address.to_string(),
+                    source,
+                }
             })?;
+        }
         if let Some(ms) = config.params.timeout_ms {
             endpoint = endpoint.timeout(std::time::Duration::from_millis(ms));
         }
+        // payloads may be served over https even when the grpc
+        // connection is not, depending on the server configuration
+        let http_client = hyper::Client::builder().build(
+            hyper_rustls::HttpsConnectorBuilder::new()
+                .with_tls_config(tls.http)
+                .https_or_http()
+                .enable_http1()
+                .build(),
+        );
         let channel = match config.params.lazy {
             true => endpoint.connect_lazy(),
             false => endpoint.connect().await?,
         };
-        let mut repo_client = RepositoryClient::new(channel.clone());
-        let mut tag_client = TagServiceClient::new(channel.clone());
-        let mut db_client = DatabaseServiceClient::new(channel.clone());
-        let mut payload_client = PayloadServiceClient::new(channel);
+        let authorization = match config.params.bearer_token() {
+            None => None,
+            Some(token) => {
+                let mut value = hyper::http::HeaderValue::try_from(format!("Bearer {token}"))
+                    .map_err(|_| {
+                        OpenRepositoryError::InvalidCredentials(
+                            "bearer token contains invalid characters".into(),
+                        )
+                    })?;
+                value.set_sensitive(true);
+                Some(value)
+            }
+        };
+        let interceptor = CredentialInterceptor {
+            authorization: authorization.clone(),
+        };
+        let mut repo_client =
+            RepositoryClient::with_interceptor(channel.clone(), interceptor.clone());
+        let mut tag_client =
+            TagServiceClient::with_interceptor(channel.clone(), interceptor.clone());
+        let mut db_client =
+            DatabaseServiceClient::with_interceptor(channel.clone(), interceptor.clone());
+        let mut payload_client = PayloadServiceClient::with_interceptor(channel, interceptor);
         if let Some(max) = config.params.max_decode_message_size_bytes {
             repo_client = repo_client.max_decoding_message_size(max);
             tag_client = tag_client.max_decoding_message_size(max);
@@ -140,7 +255,8 @@ impl RpcRepository {
             tag_client,
             db_client,
             payload_client,
-            http_client: hyper::Client::new(),
+            http_client,
+            authorization,
             tag_namespace: config.params.tag_namespace,
         })
     }
@@ -13,7 +13,7 @@ use relative_path::RelativePath;
 use crate::proto::tag_service_client::TagServiceClient;
 use crate::proto::{self, RpcResult};
 use crate::storage::tag::TagSpecAndTagStream;
-use crate::storage::{self, EntryType, TagNamespace, TagNamespaceBuf};
+use crate::storage::{self, EntryType, TagChangeStream, TagNamespace, TagNamespaceBuf};
 use crate::{encoding, tracking, Result};
 
 #[async_trait::async_trait]
@@ -183,6 +183,33 @@ impl storage::TagStorage for super::RpcRepository {
             .to_result()?;
         Ok(())
     }
+
+    fn read_tag_changes_in_namespace(
+        &self,
+        namespace: Option<&TagNamespace>,
+        since: u64,
+    ) -> TagChangeStream<'static> {
+        watch_tag_changes(
+            self.tag_client.clone(),
+            namespace,
+            RelativePath::new(""),
+            since,
+            false,
+        )
+    }
+
+    /// Follow the tag change log of the remote repository.
+    ///
+    /// New changes are pushed by the server as they are made,
+    /// rather than by polling the remote log.
+    fn watch_tag_changes_in_namespace(
+        &self,
+        namespace: Option<&TagNamespace>,
+        prefix: &RelativePath,
+        since: u64,
+    ) -> TagChangeStream<'_> {
+        watch_tag_changes(self.tag_client.clone(), namespace, prefix, since, true)
+    }
 }
 
 impl storage::TagStorageMut for super::RpcRepository {
@@ -195,7 +222,7 @@ impl storage::TagStorageMut for super::RpcRepository {
 }
 
 async fn read_tag(
-    mut client: TagServiceClient<tonic::transport::Channel>,
+    mut client: TagServiceClient<super::repository::Channel>,
     tag_namespace: Option<&TagNamespace>,
     tag: &tracking::TagSpec,
 ) -> Result<Pin<Box<dyn Stream<Item = Result<tracking::Tag>> + Send>>> {
@@ -211,3 +238,25 @@ async fn read_tag(
         .collect();
     Ok(Box::pin(futures::stream::iter(items?.into_iter().map(Ok))))
 }
+
+fn watch_tag_changes(
+    mut client: TagServiceClient<super::repository::Channel>,
+    tag_namespace: Option<&TagNamespace>,
+    prefix: &RelativePath,
+    since: u64,
+    follow: bool,
+) -> TagChangeStream<'static> {
+    let request = proto::WatchTagChangesRequest {
+        namespace: tag_namespace.map(|p| p.to_string()).unwrap_or_default(),
+        prefix: prefix.to_string(),
+        since,
+        follow,
+    };
+    let stream = futures::stream::once(async move { client.watch_tag_changes(request).await })
+        .map_err(crate::Error::from)
+        .map_ok(|r| r.into_inner().map_err(crate::Error::from))
+        .try_flatten()
+        .and_then(|r| async { r.to_result() })
+        .and_then(|change| async { change.try_into() });
+    Box::pin(stream)
+}
@@ -0,0 +1,81 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use std::path::Path;
+
+use super::Params;
+use crate::storage::{OpenRepositoryError, OpenRepositoryResult};
+
+/// The TLS settings used to connect to both the gRPC
+/// and http payload services of an spfs server
+pub(super) struct ClientTls {
+    pub grpc: tonic::transport::ClientTlsConfig,
+    pub http: rustls::ClientConfig,
+}
+
+impl ClientTls {
+    /// Load any certificates named in the given parameters, for
+    /// connecting to the server at the given domain.
+    ///
+    /// When `use_tls` is set, the certificates of the system are also
+    /// trusted, which matches the behavior of the grpc client. They are
+    /// not loaded for plaintext connections, which is costly to do for
+    /// every repository that is opened.
+    pub async fn load(params: &Params, domain: &str, use_tls: bool) -> OpenRepositoryResult<Self> {
+        let mut grpc = tonic::transport::ClientTlsConfig::new().domain_name(domain);
+
+        let mut roots = rustls::RootCertStore::empty();
+        if use_tls {
+            match rustls_native_certs::load_native_certs() {
+                Ok(certs) => {
+                    roots.add_parsable_certificates(certs);
+                }
+                Err(err) => tracing::warn!(?err, "failed to load system certificates"),
+            }
+        }
+        if let Some(path) = &params.ca_bundle {
+            let pem = read_pem_file(path).await?;
+            for cert in rustls_pemfile::certs(&mut pem.as_slice()) {
+                let cert = cert.map_err(|err| invalid_file(path, err))?;
+                roots.add(cert).map_err(|err| invalid_file(path, err))?;
+            }
+            grpc = grpc.ca_certificate(tonic::transport::Certificate::from_pem(pem));
+        }
+        let builder = rustls::ClientConfig::builder().with_root_certificates(roots);
+
+        let http = match (&params.client_cert, &params.client_key) {
+            (None, None) => builder.with_no_client_auth(),
+            (Some(cert_path), Some(key_path)) => {
+                let cert = read_pem_file(cert_path).await?;
+                let key = read_pem_file(key_path).await?;
+                let cert_chain = rustls_pemfile::certs(&mut cert.as_slice())
+                    .collect::<std::io::Result<Vec<_>>>()
+                    .map_err(|err| invalid_file(cert_path, err))?;
+                let private_key = rustls_pemfile::private_key(&mut key.as_slice())
+                    .map_err(|err| invalid_file(key_path, err))?
+                    .ok_or_else(|| invalid_file(key_path, "no private key found"))?;
+                grpc = grpc.identity(tonic::transport::Identity::from_pem(cert, key));
+                builder
+                    .with_client_auth_cert(cert_chain, private_key)
+                    .map_err(|err| OpenRepositoryError::InvalidTlsConfig(err.to_string()))?
+            }
+            _ => {
+                return Err(OpenRepositoryError::InvalidTlsConfig(
+                    "both a client certificate and key must be given".into(),
+                ))
+            }
+        };
+        Ok(Self { grpc, http })
+    }
+}
+
+async fn read_pem_file(path: &Path) -> OpenRepositoryResult<Vec<u8>> {
+    tokio::fs::read(path)
+        .await
+        .map_err(|err| invalid_file(path, err))
+}
+
+fn invalid_file(path: &Path, err: impl std::fmt::Display) -> OpenRepositoryError {
+    OpenRepositoryError::InvalidTlsConfig(format!("{}: {err}", path.display()))
+}
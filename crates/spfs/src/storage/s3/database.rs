@@ -0,0 +1,121 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use std::pin::Pin;
+
+use chrono::{DateTime, Utc};
+use encoding::prelude::*;
+use futures::{Stream, TryStreamExt};
+
+use super::repository::digest_from_path;
+use super::S3Repository;
+use crate::graph::{self, ObjectProto};
+use crate::{encoding, Error, Result};
+
+const OBJECTS: &str = "objects";
+
+#[async_trait::async_trait]
+impl graph::DatabaseView for S3Repository {
+    async fn has_object(&self, digest: encoding::Digest) -> bool {
+        let path = self.build_digest_path(OBJECTS, &digest);
+        self.store.head(&path).await.is_ok()
+    }
+
+    async fn read_object(&self, digest: encoding::Digest) -> Result<graph::Object> {
+        let path = self.build_digest_path(OBJECTS, &digest);
+        let data = match self.store.get(&path).await {
+            Ok(result) => result.bytes().await?,
+            Err(object_store::Error::NotFound { .. }) => return Err(Error::UnknownObject(digest)),
+            Err(err) => return Err(err.into()),
+        };
+        graph::Object::new(data)
+    }
+
+    fn find_digests(
+        &self,
+        search_criteria: graph::DigestSearchCriteria,
+    ) -> Pin<Box<dyn Stream<Item = Result<encoding::Digest>> + Send>> {
+        let mut prefix = self.root_for(OBJECTS);
+        if let graph::DigestSearchCriteria::StartsWith(partial) = &search_criteria {
+            // the encoded partial digest can only be trusted to identify
+            // a single prefix when it encodes to more characters than that
+            // prefix, because base 32 may encode partial data to the final
+            // character
+            let encoded = partial.to_string();
+            if encoded.len() > 2 {
+                prefix = prefix.child(&encoded[..2]);
+            }
+        }
+        let store = self.store.clone();
+        Box::pin(async_stream::try_stream! {
+            let mut listing = store.list(Some(&prefix));
+            while let Some(meta) = listing.try_next().await? {
+                let Some(digest) = digest_from_path(&meta.location) else {
+                    continue;
+                };
+                if let graph::DigestSearchCriteria::StartsWith(partial) = &search_criteria {
+                    if !digest.as_bytes().starts_with(partial.as_slice()) {
+                        continue;
+                    }
+                }
+                yield digest;
+            }
+        })
+    }
+
+    fn iter_objects(&self) -> graph::DatabaseIterator<'_> {
+        graph::DatabaseIterator::new(self)
+    }
+
+    fn walk_objects<'db>(&'db self, root: &encoding::Digest) -> graph::DatabaseWalker<'db> {
+        graph::DatabaseWalker::new(self, *root)
+    }
+}
+
+#[async_trait::async_trait]
+impl graph::Database for S3Repository {
+    async fn write_object<T: ObjectProto>(&self, obj: &graph::FlatObject<T>) -> Result<()> {
+        let digest = obj.digest()?;
+        let path = self.build_digest_path(OBJECTS, &digest);
+        if self.store.head(&path).await.is_ok() {
+            tracing::trace!(%digest, kind=%std::any::type_name::<T>(), "object already exists");
+            return Ok(());
+        }
+        tracing::trace!(%digest, kind=%std::any::type_name::<T>(), "writing object to db");
+        let mut encoded = Vec::new();
+        obj.encode(&mut encoded)?;
+        // objects are identified by their content, so a concurrent
+        // write of the same object can safely replace this one
+        self.store.put(&path, encoded.into()).await?;
+        Ok(())
+    }
+
+    async fn remove_object(&self, digest: encoding::Digest) -> Result<()> {
+        let path = self.build_digest_path(OBJECTS, &digest);
+        match self.store.delete(&path).await {
+            Ok(()) | Err(object_store::Error::NotFound { .. }) => Ok(()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    async fn remove_object_if_older_than(
+        &self,
+        older_than: DateTime<Utc>,
+        digest: encoding::Digest,
+    ) -> Result<bool> {
+        let path = self.build_digest_path(OBJECTS, &digest);
+        let meta = match self.store.head(&path).await {
+            Ok(meta) => meta,
+            Err(object_store::Error::NotFound { .. }) => return Err(Error::UnknownObject(digest)),
+            Err(err) => return Err(err.into()),
+        };
+        if meta.last_modified >= older_than {
+            return Ok(false);
+        }
+        match self.store.delete(&path).await {
+            Ok(()) | Err(object_store::Error::NotFound { .. }) => Ok(true),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
@@ -0,0 +1,17 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+//! Storage implementation backed by an S3-compatible object store
+//!
+//! Every object, payload and tag version is stored under its own key so
+//! that no write ever needs to read, modify and replace existing data.
+//! This allows multiple writers to use the same bucket concurrently
+//! without any additional locking.
+
+mod database;
+mod payload;
+mod repository;
+mod tag;
+
+pub use repository::{Config, Params, S3Repository};
@@ -0,0 +1,111 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use std::path::PathBuf;
+use std::pin::Pin;
+
+use futures::{Stream, StreamExt, TryStreamExt};
+use tokio::io::AsyncWriteExt;
+
+use super::repository::digest_from_path;
+use super::S3Repository;
+use crate::storage::PayloadStorage;
+use crate::tracking::BlobRead;
+use crate::{encoding, Error, Result};
+
+const PAYLOADS: &str = "payloads";
+const WORK: &str = "work";
+
+#[async_trait::async_trait]
+impl PayloadStorage for S3Repository {
+    async fn has_payload(&self, digest: encoding::Digest) -> bool {
+        let path = self.build_digest_path(PAYLOADS, &digest);
+        self.store.head(&path).await.is_ok()
+    }
+
+    fn iter_payload_digests(&self) -> Pin<Box<dyn Stream<Item = Result<encoding::Digest>> + Send>> {
+        let prefix = self.root_for(PAYLOADS);
+        let store = self.store.clone();
+        Box::pin(async_stream::try_stream! {
+            let mut listing = store.list(Some(&prefix));
+            while let Some(meta) = listing.try_next().await? {
+                if let Some(digest) = digest_from_path(&meta.location) {
+                    yield digest;
+                }
+            }
+        })
+    }
+
+    async unsafe fn write_data(
+        &self,
+        mut reader: Pin<Box<dyn BlobRead>>,
+    ) -> Result<(encoding::Digest, u64)> {
+        // the digest is not known until all the data has been
+        // read, so it is uploaded to a unique working key first
+        let working_path = self.root_for(WORK).child(uuid::Uuid::new_v4().to_string());
+        let mut writer =
+            object_store::buffered::BufWriter::new(self.store.clone(), working_path.clone());
+        let mut hasher = encoding::Hasher::with_target(&mut writer);
+        let copied = match tokio::io::copy(&mut reader, &mut hasher).await {
+            Ok(copied) => copied,
+            Err(err) => {
+                let _ = writer.abort().await;
+                return Err(Error::StorageWriteError(
+                    "copy of payload to object store",
+                    PathBuf::from(working_path.to_string()),
+                    err,
+                ));
+            }
+        };
+        let digest = hasher.digest();
+        writer.shutdown().await.map_err(|err| {
+            Error::StorageWriteError(
+                "upload of payload to object store",
+                PathBuf::from(working_path.to_string()),
+                err,
+            )
+        })?;
+
+        let path = self.build_digest_path(PAYLOADS, &digest);
+        if self.store.head(&path).await.is_ok() {
+            self.store.delete(&working_path).await?;
+        } else {
+            self.store.rename(&working_path, &path).await?;
+        }
+        Ok((digest, copied))
+    }
+
+    async fn open_payload(
+        &self,
+        digest: encoding::Digest,
+    ) -> Result<(Pin<Box<dyn BlobRead>>, PathBuf)> {
+        let path = self.build_digest_path(PAYLOADS, &digest);
+        let result = match self.store.get(&path).await {
+            Ok(result) => result,
+            Err(object_store::Error::NotFound { .. }) => return Err(Error::UnknownObject(digest)),
+            Err(err) => return Err(err.into()),
+        };
+        // the stream must return io errors in order to be converted to a reader
+        let stream = result
+            .into_stream()
+            .map(|chunk| chunk.map_err(std::io::Error::other));
+        let stream_reader =
+            tokio_util::io::StreamReader::new(sync_wrapper::SyncStream::new(stream));
+        Ok((
+            Box::pin(tokio::io::BufReader::new(stream_reader)),
+            PathBuf::from(path.to_string()),
+        ))
+    }
+
+    async fn remove_payload(&self, digest: encoding::Digest) -> Result<()> {
+        let path = self.build_digest_path(PAYLOADS, &digest);
+        if let Err(object_store::Error::NotFound { .. }) = self.store.head(&path).await {
+            return Err(Error::UnknownObject(digest));
+        }
+        match self.store.delete(&path).await {
+            Ok(()) | Err(object_store::Error::NotFound { .. }) => Ok(()),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
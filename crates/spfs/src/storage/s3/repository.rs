@@ -0,0 +1,206 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use std::sync::Arc;
+
+use object_store::path::Path as ObjectPath;
+use object_store::ObjectStore;
+
+use crate::config::ToAddress;
+use crate::storage::{
+    BlobStorage,
+    LayerStorage,
+    ManifestStorage,
+    OpenRepositoryError,
+    OpenRepositoryResult,
+    PlatformStorage,
+    TagNamespace,
+    TagNamespaceBuf,
+    TagStorageMut,
+};
+use crate::{encoding, storage, Result};
+
+#[cfg(test)]
+#[path = "./repository_test.rs"]
+mod repository_test;
+
+/// Configures a repository in an S3-compatible object store
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize)]
+pub struct Config {
+    /// The name of the bucket that holds the repository
+    pub bucket: String,
+    /// The key prefix under which all repository data is stored
+    #[serde(default)]
+    pub prefix: String,
+    #[serde(flatten)]
+    pub params: Params,
+}
+
+#[derive(Clone, Debug, serde::Deserialize, serde::Serialize, Default)]
+pub struct Params {
+    /// A custom endpoint to connect to, for services other than AWS
+    /// (eg: http://localhost:9000 for a local MinIO server)
+    pub endpoint: Option<String>,
+
+    /// The region that the bucket is in
+    ///
+    /// Defaults to the region from the environment, if any
+    pub region: Option<String>,
+
+    /// if true, allow connecting to the endpoint over plain http
+    #[serde(default)]
+    pub allow_http: bool,
+
+    /// optional tag namespace to use when querying tags
+    pub tag_namespace: Option<TagNamespaceBuf>,
+}
+
+#[async_trait::async_trait]
+impl storage::FromUrl for Config {
+    async fn from_url(url: &url::Url) -> OpenRepositoryResult<Self> {
+        let params = if let Some(qs) = url.query() {
+            serde_qs::from_str(qs)
+                .map_err(|source| OpenRepositoryError::invalid_query(url, source))?
+        } else {
+            Params::default()
+        };
+        Ok(Self {
+            bucket: url.host_str().unwrap_or_default().to_owned(),
+            prefix: url.path().trim_matches('/').to_owned(),
+            params,
+        })
+    }
+}
+
+impl ToAddress for Config {
+    fn to_address(&self) -> Result<url::Url> {
+        let query = serde_qs::to_string(&self.params).map_err(|err| {
+            crate::Error::String(format!(
+                "S3 repo parameters do not create a valid url: {err:?}"
+            ))
+        })?;
+        let mut address = url::Url::parse(&format!("s3://{}/{}", self.bucket, self.prefix))?;
+        if !query.is_empty() {
+            address.set_query(Some(&query));
+        }
+        Ok(address)
+    }
+}
+
+/// An spfs repository stored in an S3-compatible object store.
+///
+/// Credentials and other client settings that are not part of the
+/// repository configuration are loaded from the standard `AWS_*`
+/// environment variables.
+pub struct S3Repository {
+    address: url::Url,
+    pub(super) store: Arc<dyn ObjectStore>,
+    root: ObjectPath,
+    /// the namespace to use for tag resolution. If set, then this is treated
+    /// as "chroot" of the real tag root.
+    tag_namespace: Option<TagNamespaceBuf>,
+}
+
+#[async_trait::async_trait]
+impl storage::FromConfig for S3Repository {
+    type Config = Config;
+
+    async fn from_config(config: Self::Config) -> OpenRepositoryResult<Self> {
+        Self::new(config)
+    }
+}
+
+impl std::fmt::Debug for S3Repository {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("S3Repository<{}>", &self.address))
+    }
+}
+
+impl S3Repository {
+    /// Create a new client for the repository in the configured bucket
+    pub fn new(config: Config) -> OpenRepositoryResult<Self> {
+        let mut builder = object_store::aws::AmazonS3Builder::from_env()
+            .with_bucket_name(&config.bucket)
+            .with_allow_http(config.params.allow_http);
+        if let Some(endpoint) = &config.params.endpoint {
+            builder = builder.with_endpoint(endpoint);
+        }
+        if let Some(region) = &config.params.region {
+            builder = builder.with_region(region);
+        }
+        let store = builder.build()?;
+        Ok(Self::with_store(Arc::new(store), config))
+    }
+
+    /// Create a repository over an existing object store client.
+    ///
+    /// The bucket and connection parameters of the given config are
+    /// assumed to have already been applied to the store.
+    pub fn with_store(store: Arc<dyn ObjectStore>, config: Config) -> Self {
+        Self {
+            address: config.to_address().expect("an internally valid config"),
+            root: ObjectPath::from(config.prefix.as_str()),
+            tag_namespace: config.params.tag_namespace,
+            store,
+        }
+    }
+
+    /// The namespace to use for tag resolution.
+    pub fn tag_namespace(&self) -> Option<&TagNamespace> {
+        self.tag_namespace.as_deref()
+    }
+
+    /// Set the namespace to use for tag resolution.
+    ///
+    /// Returns the previous namespace, if any.
+    pub fn set_tag_namespace(
+        &mut self,
+        tag_namespace: Option<TagNamespaceBuf>,
+    ) -> Option<TagNamespaceBuf> {
+        std::mem::replace(&mut self.tag_namespace, tag_namespace)
+    }
+
+    /// The key prefix under which the given kind of data is stored
+    pub(super) fn root_for(&self, kind: &str) -> ObjectPath {
+        self.root.child(kind)
+    }
+
+    /// The key of the given digest in a store of content-addressed data
+    pub(super) fn build_digest_path(&self, kind: &str, digest: &encoding::Digest) -> ObjectPath {
+        let digest_str = digest.to_string();
+        self.root_for(kind)
+            .child(&digest_str[..2])
+            .child(&digest_str[2..])
+    }
+}
+
+impl storage::Repository for S3Repository {
+    fn address(&self) -> url::Url {
+        self.address.clone()
+    }
+}
+
+impl TagStorageMut for S3Repository {
+    fn try_set_tag_namespace(
+        &mut self,
+        tag_namespace: Option<TagNamespaceBuf>,
+    ) -> Result<Option<TagNamespaceBuf>> {
+        Ok(self.set_tag_namespace(tag_namespace))
+    }
+}
+
+impl BlobStorage for S3Repository {}
+impl ManifestStorage for S3Repository {}
+impl LayerStorage for S3Repository {}
+impl PlatformStorage for S3Repository {}
+
+/// Parse the digest of a content-addressed key, which is
+/// split into a two character prefix and the remainder.
+pub(super) fn digest_from_path(path: &ObjectPath) -> Option<encoding::Digest> {
+    let parts: Vec<_> = path.parts().collect();
+    let [.., prefix, rest] = parts.as_slice() else {
+        return None;
+    };
+    encoding::Digest::parse(&format!("{}{}", prefix.as_ref(), rest.as_ref())).ok()
+}
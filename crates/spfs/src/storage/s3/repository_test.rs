@@ -0,0 +1,86 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use std::sync::Arc;
+
+use futures::TryStreamExt;
+use rstest::rstest;
+
+use super::{Config, S3Repository};
+use crate::config::ToAddress;
+use crate::fixtures::*;
+use crate::prelude::*;
+use crate::storage::FromUrl;
+use crate::{encoding, tracking};
+
+#[rstest]
+#[tokio::test]
+async fn test_config_address_round_trip() {
+    let address = url::Url::parse(
+        "s3://spfs-bucket/repos/main?endpoint=http%3A%2F%2Flocalhost%3A9000&allow_http=true",
+    )
+    .unwrap();
+    let config = Config::from_url(&address).await.unwrap();
+    assert_eq!(config.bucket, "spfs-bucket");
+    assert_eq!(config.prefix, "repos/main");
+    assert_eq!(
+        config.params.endpoint.as_deref(),
+        Some("http://localhost:9000")
+    );
+    assert!(config.params.allow_http);
+
+    let reparsed = Config::from_url(&config.to_address().unwrap())
+        .await
+        .unwrap();
+    assert_eq!(reparsed.bucket, config.bucket);
+    assert_eq!(reparsed.prefix, config.prefix);
+    assert_eq!(reparsed.params.endpoint, config.params.endpoint);
+    assert_eq!(reparsed.params.allow_http, config.params.allow_http);
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_concurrent_tag_writers() {
+    init_logging();
+    let store: Arc<dyn object_store::ObjectStore> = Arc::new(object_store::memory::InMemory::new());
+    let config = || Config {
+        bucket: "spfs-test".to_string(),
+        prefix: "repo".to_string(),
+        params: Default::default(),
+    };
+    let first = S3Repository::with_store(Arc::clone(&store), config());
+    let second = S3Repository::with_store(store, config());
+
+    let spec = tracking::TagSpec::parse("shared/tag").unwrap();
+    let mut first_tag = tracking::Tag::new(
+        spec.org(),
+        spec.name(),
+        encoding::Digest::from_bytes(&[1; encoding::DIGEST_SIZE]).unwrap(),
+    )
+    .unwrap();
+    first_tag.time = chrono::Utc::now() - chrono::Duration::seconds(5);
+    let second_tag = tracking::Tag::new(
+        spec.org(),
+        spec.name(),
+        encoding::Digest::from_bytes(&[2; encoding::DIGEST_SIZE]).unwrap(),
+    )
+    .unwrap();
+
+    let (res1, res2) = tokio::join!(first.insert_tag(&first_tag), second.insert_tag(&second_tag));
+    res1.unwrap();
+    res2.unwrap();
+
+    let versions: Vec<_> = first
+        .read_tag(&spec)
+        .await
+        .unwrap()
+        .try_collect()
+        .await
+        .unwrap();
+    assert_eq!(
+        versions,
+        vec![second_tag, first_tag],
+        "both writes should be kept, newest first"
+    );
+}
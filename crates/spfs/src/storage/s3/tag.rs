@@ -0,0 +1,269 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use encoding::prelude::*;
+use futures::{Stream, StreamExt, TryStreamExt};
+use object_store::path::{Path as ObjectPath, PathPart};
+use object_store::ObjectStore;
+use relative_path::RelativePath;
+
+use super::S3Repository;
+use crate::storage::tag::{EntryType, TagSpecAndTagStream, TagStream};
+use crate::storage::{TagNamespace, TagStorage, TAG_NAMESPACE_MARKER};
+use crate::{encoding, tracking, Error, Result};
+
+const TAGS: &str = "tags";
+const TAG_EXT: &str = ".tag";
+
+impl S3Repository {
+    fn tags_root_in_namespace(&self, namespace: Option<&TagNamespace>) -> ObjectPath {
+        let mut tags_root = self.root_for(TAGS);
+        if let Some(tag_namespace) = namespace {
+            for component in tag_namespace.as_rel_path().components() {
+                // Assuming the tag namespace is only made up of `Normal`
+                // elements (validated elsewhere).
+                let relative_path::Component::Normal(component) = component else {
+                    continue;
+                };
+                // Add the same suffix as the fs repository to distinguish
+                // tag namespace prefixes from normal tag prefixes.
+                tags_root = tags_root.child(format!("{component}{TAG_NAMESPACE_MARKER}"));
+            }
+        }
+        tags_root
+    }
+
+    /// The prefix under which each version of a tag stream is stored
+    fn tag_stream_prefix(
+        &self,
+        namespace: Option<&TagNamespace>,
+        spec: &tracking::TagSpec,
+    ) -> ObjectPath {
+        let mut prefix = self.tags_root_in_namespace(namespace);
+        if let Some(org) = spec.org() {
+            for part in org.split('/') {
+                prefix = prefix.child(part);
+            }
+        }
+        prefix.child(format!("{}{TAG_EXT}", spec.name()))
+    }
+
+    /// The key of a single tag version.
+    ///
+    /// Every version is stored under its own key, so that writers
+    /// never need to read and replace the existing tag stream.
+    fn tag_version_path(
+        &self,
+        namespace: Option<&TagNamespace>,
+        spec: &tracking::TagSpec,
+        digest: &encoding::Digest,
+    ) -> ObjectPath {
+        self.tag_stream_prefix(namespace, spec)
+            .child(digest.to_string())
+    }
+}
+
+#[async_trait::async_trait]
+impl TagStorage for S3Repository {
+    #[inline]
+    fn get_tag_namespace(&self) -> Option<Cow<'_, TagNamespace>> {
+        self.tag_namespace().map(Cow::Borrowed)
+    }
+
+    fn ls_tags_in_namespace(
+        &self,
+        namespace: Option<&TagNamespace>,
+        path: &RelativePath,
+    ) -> Pin<Box<dyn Stream<Item = Result<EntryType>> + Send>> {
+        let mut prefix = self.tags_root_in_namespace(namespace);
+        for component in path.components() {
+            if let relative_path::Component::Normal(component) = component {
+                prefix = prefix.child(component);
+            }
+        }
+        let store = self.store.clone();
+        Box::pin(async_stream::try_stream! {
+            let listing = store.list_with_delimiter(Some(&prefix)).await?;
+            let marker = PathPart::from(TAG_NAMESPACE_MARKER);
+            for common in listing.common_prefixes {
+                let Some(name) = common.filename() else {
+                    continue;
+                };
+                if let Some(stem) = name.strip_suffix(TAG_EXT) {
+                    yield EntryType::Tag(stem.to_owned());
+                } else if let Some((name, _)) = name.split_once(marker.as_ref()) {
+                    yield EntryType::Namespace(name.to_owned());
+                } else {
+                    yield EntryType::Folder(name.to_owned());
+                }
+            }
+        })
+    }
+
+    /// Find tags that point to the given digest.
+    ///
+    /// This is an O(n) operation based on the number of all
+    /// tag versions in each tag stream.
+    fn find_tags_in_namespace(
+        &self,
+        namespace: Option<&TagNamespace>,
+        digest: &encoding::Digest,
+    ) -> Pin<Box<dyn Stream<Item = Result<tracking::TagSpec>> + Send>> {
+        let digest = *digest;
+        let stream = self.iter_tag_streams_in_namespace(namespace);
+        let mapped = futures::StreamExt::filter_map(stream, move |res| async move {
+            let (spec, stream) = match res {
+                Ok(res) => res,
+                Err(err) => return Some(Err(err)),
+            };
+            let mut stream = futures::StreamExt::enumerate(stream);
+            while let Some((i, tag)) = stream.next().await {
+                match tag {
+                    Ok(tag) if tag.target == digest => {
+                        return Some(Ok(spec.with_version(i as u64)));
+                    }
+                    Ok(_) => continue,
+                    Err(err) => return Some(Err(err)),
+                }
+            }
+            None
+        });
+        Box::pin(mapped)
+    }
+
+    /// Iterate through the available tags in this storage.
+    fn iter_tag_streams_in_namespace(
+        &self,
+        namespace: Option<&TagNamespace>,
+    ) -> Pin<Box<dyn Stream<Item = Result<TagSpecAndTagStream>> + Send>> {
+        let root = self.tags_root_in_namespace(namespace);
+        let store = self.store.clone();
+        Box::pin(async_stream::try_stream! {
+            let marker = PathPart::from(TAG_NAMESPACE_MARKER);
+            let root_depth = root.parts().count();
+            let mut streams = BTreeMap::<String, Vec<ObjectPath>>::new();
+            let mut listing = store.list(Some(&root));
+            while let Some(meta) = listing.try_next().await? {
+                let parts: Vec<_> = meta.location.parts().skip(root_depth).collect();
+                let [folders @ .., stream, _version] = parts.as_slice() else {
+                    continue;
+                };
+                let Some(name) = stream.as_ref().strip_suffix(TAG_EXT) else {
+                    continue;
+                };
+                if folders.iter().any(|f| f.as_ref().contains(marker.as_ref())) {
+                    // tags in nested namespaces are not part of this one
+                    continue;
+                }
+                let mut spec = folders
+                    .iter()
+                    .map(|f| f.as_ref().to_owned())
+                    .collect::<Vec<_>>();
+                spec.push(name.to_owned());
+                streams.entry(spec.join("/")).or_default().push(meta.location);
+            }
+            for (spec, versions) in streams {
+                let spec = tracking::TagSpec::parse(spec)?;
+                let stream: TagStream = Box::pin(futures::stream::iter(
+                    read_tag_versions(&store, versions).await?.into_iter().map(Ok),
+                ));
+                yield (spec, stream);
+            }
+        })
+    }
+
+    async fn read_tag_in_namespace(
+        &self,
+        namespace: Option<&TagNamespace>,
+        tag: &tracking::TagSpec,
+    ) -> Result<Pin<Box<dyn Stream<Item = Result<tracking::Tag>> + Send>>> {
+        let prefix = self.tag_stream_prefix(namespace, tag);
+        let versions: Vec<_> = self
+            .store
+            .list(Some(&prefix))
+            .map_ok(|meta| meta.location)
+            .try_collect()
+            .await?;
+        if versions.is_empty() {
+            return Err(Error::UnknownReference(tag.to_string()));
+        }
+        let tags = read_tag_versions(&self.store, versions).await?;
+        Ok(Box::pin(futures::stream::iter(tags.into_iter().map(Ok))))
+    }
+
+    async fn insert_tag_in_namespace(
+        &self,
+        namespace: Option<&TagNamespace>,
+        tag: &tracking::Tag,
+    ) -> Result<()> {
+        let spec = tracking::build_tag_spec(tag.org(), tag.name(), 0)?;
+        let path = self.tag_version_path(namespace, &spec, &tag.digest()?);
+        // the key is derived from the tag content, so writing
+        // the same tag more than once is harmless
+        self.store.put(&path, tag.encode_to_bytes()?.into()).await?;
+        Ok(())
+    }
+
+    async fn remove_tag_stream_in_namespace(
+        &self,
+        namespace: Option<&TagNamespace>,
+        tag: &tracking::TagSpec,
+    ) -> Result<()> {
+        let spec = tracking::build_tag_spec(tag.org(), tag.name(), 0)?;
+        let prefix = self.tag_stream_prefix(namespace, &spec);
+        let versions: Vec<_> = self
+            .store
+            .list(Some(&prefix))
+            .map_ok(|meta| meta.location)
+            .try_collect()
+            .await?;
+        if versions.is_empty() {
+            return Err(Error::UnknownReference(tag.to_string()));
+        }
+        for path in versions {
+            match self.store.delete(&path).await {
+                Ok(()) | Err(object_store::Error::NotFound { .. }) => continue,
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Ok(())
+    }
+
+    async fn remove_tag_in_namespace(
+        &self,
+        namespace: Option<&TagNamespace>,
+        tag: &tracking::Tag,
+    ) -> Result<()> {
+        let spec = tracking::build_tag_spec(tag.org(), tag.name(), 0)?;
+        let path = self.tag_version_path(namespace, &spec, &tag.digest()?);
+        match self.store.delete(&path).await {
+            Ok(()) | Err(object_store::Error::NotFound { .. }) => Ok(()),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+/// Load all of the given tag versions, ordered from latest to earliest
+async fn read_tag_versions(
+    store: &Arc<dyn ObjectStore>,
+    versions: Vec<ObjectPath>,
+) -> Result<Vec<tracking::Tag>> {
+    let mut tags = Vec::with_capacity(versions.len());
+    for path in versions {
+        let data = match store.get(&path).await {
+            Ok(result) => result.bytes().await?,
+            // removed by another writer since it was listed
+            Err(object_store::Error::NotFound { .. }) => continue,
+            Err(err) => return Err(err.into()),
+        };
+        tags.push(tracking::Tag::decode(&mut data.as_ref())?);
+    }
+    tags.sort_by(|a, b| b.cmp(a));
+    Ok(tags)
+}
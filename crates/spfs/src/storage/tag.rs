@@ -10,6 +10,7 @@ use futures::Stream;
 use relative_path::RelativePath;
 use tokio_stream::StreamExt;
 
+use super::tag_change::{follow_tag_changes, TagChangeStream};
 use super::{TagNamespace, TagNamespaceBuf, TAG_NAMESPACE_MARKER};
 use crate::prelude::*;
 use crate::{encoding, tracking, Error, Result};
@@ -278,6 +279,54 @@ pub trait TagStorage: Send + Sync {
         namespace: Option<&TagNamespace>,
         tag: &tracking::Tag,
     ) -> Result<()>;
+
+    /// Read the log of changes made to tags in this storage, oldest first.
+    ///
+    /// Only changes made after the given cursor are returned, and a
+    /// cursor of zero reads the entire log. The cursor of the last
+    /// change that was seen can be used to resume reading later on.
+    fn read_tag_changes(&self, since: u64) -> TagChangeStream<'static> {
+        self.read_tag_changes_in_namespace(self.get_tag_namespace().as_deref(), since)
+    }
+
+    /// Read the log of changes made to tags in the given namespace, oldest first.
+    ///
+    /// Storage that does not keep a change log yields a single error.
+    fn read_tag_changes_in_namespace(
+        &self,
+        _namespace: Option<&TagNamespace>,
+        _since: u64,
+    ) -> TagChangeStream<'static> {
+        Box::pin(futures::stream::once(async {
+            Err(Error::String(
+                "This repository does not keep a tag change log".into(),
+            ))
+        }))
+    }
+
+    /// Follow the log of changes made to tags under the given path prefix,
+    /// starting after the given cursor.
+    ///
+    /// Unlike [`TagStorage::read_tag_changes`], the returned stream waits
+    /// for new changes to be made and does not end on its own.
+    fn watch_tag_changes(&self, prefix: &RelativePath, since: u64) -> TagChangeStream<'_> {
+        self.watch_tag_changes_in_namespace(self.get_tag_namespace().as_deref(), prefix, since)
+    }
+
+    /// Follow the log of changes made to tags in the given namespace.
+    fn watch_tag_changes_in_namespace(
+        &self,
+        namespace: Option<&TagNamespace>,
+        prefix: &RelativePath,
+        since: u64,
+    ) -> TagChangeStream<'_> {
+        follow_tag_changes(
+            self,
+            namespace.map(ToOwned::to_owned),
+            prefix.to_owned(),
+            since,
+        )
+    }
 }
 
 #[async_trait::async_trait]
@@ -341,6 +390,23 @@ impl<T: TagStorage> TagStorage for &T {
     ) -> Result<()> {
         TagStorage::remove_tag_in_namespace(&**self, namespace, tag).await
     }
+
+    fn read_tag_changes_in_namespace(
+        &self,
+        namespace: Option<&TagNamespace>,
+        since: u64,
+    ) -> TagChangeStream<'static> {
+        TagStorage::read_tag_changes_in_namespace(&**self, namespace, since)
+    }
+
+    fn watch_tag_changes_in_namespace(
+        &self,
+        namespace: Option<&TagNamespace>,
+        prefix: &RelativePath,
+        since: u64,
+    ) -> TagChangeStream<'_> {
+        TagStorage::watch_tag_changes_in_namespace(&**self, namespace, prefix, since)
+    }
 }
 
 pub trait TagStorageMut {
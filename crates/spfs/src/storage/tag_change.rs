@@ -0,0 +1,186 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use std::io::BufRead;
+use std::pin::Pin;
+use std::time::Duration;
+
+use chrono::prelude::*;
+use futures::{Stream, TryStreamExt};
+use relative_path::RelativePathBuf;
+
+use super::{TagNamespaceBuf, TagStorage};
+use crate::encoding::prelude::*;
+use crate::{encoding, tracking, Error, Result};
+
+/// A stream of changes read from the tag change log of a repository
+pub type TagChangeStream<'a> = Pin<Box<dyn Stream<Item = Result<TagChange>> + Send + 'a>>;
+
+/// How long to wait before checking for new entries when
+/// following a tag change log that does not push them
+pub const TAG_CHANGE_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// The kind of modification that was made to a tag stream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, strum::Display)]
+#[strum(serialize_all = "snake_case")]
+pub enum TagChangeKind {
+    /// A tag was inserted into the stream
+    Insert,
+    /// A single tag was removed from the stream
+    Remove,
+    /// The entire tag stream was removed
+    RemoveStream,
+}
+
+impl TagChangeKind {
+    fn to_u8(self) -> u8 {
+        match self {
+            Self::Insert => 0,
+            Self::Remove => 1,
+            Self::RemoveStream => 2,
+        }
+    }
+
+    fn from_u8(value: u8) -> Result<Self> {
+        match value {
+            0 => Ok(Self::Insert),
+            1 => Ok(Self::Remove),
+            2 => Ok(Self::RemoveStream),
+            _ => Err(Error::String(format!("Unknown tag change kind: {value}"))),
+        }
+    }
+}
+
+/// A single entry in the tag change log of a repository.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TagChange {
+    /// The position in the log just after this change.
+    ///
+    /// Reading the log from this cursor will only return
+    /// changes that were made after this one. Cursors are
+    /// opaque and only meaningful to the repository that
+    /// produced them.
+    pub cursor: u64,
+    /// When the change was made
+    pub time: DateTime<Utc>,
+    pub kind: TagChangeKind,
+    /// The tag stream that was modified
+    pub spec: tracking::TagSpec,
+    /// The tag that was inserted or removed, if any
+    pub tag: Option<tracking::Tag>,
+}
+
+impl TagChange {
+    /// A change that inserted the given tag, made just now
+    pub fn insert(tag: &tracking::Tag) -> Self {
+        Self::for_tag(TagChangeKind::Insert, tag)
+    }
+
+    /// A change that removed the given tag, made just now
+    pub fn remove(tag: &tracking::Tag) -> Self {
+        Self::for_tag(TagChangeKind::Remove, tag)
+    }
+
+    /// A change that removed the entire tag stream, made just now
+    pub fn remove_stream(spec: &tracking::TagSpec) -> Self {
+        Self {
+            cursor: 0,
+            time: Utc::now().trunc_subsecs(6),
+            kind: TagChangeKind::RemoveStream,
+            spec: spec.with_version(0),
+            tag: None,
+        }
+    }
+
+    fn for_tag(kind: TagChangeKind, tag: &tracking::Tag) -> Self {
+        Self {
+            cursor: 0,
+            time: Utc::now().trunc_subsecs(6),
+            kind,
+            spec: tag.to_spec(0),
+            tag: Some(tag.clone()),
+        }
+    }
+
+    /// Return this change with the given cursor
+    pub fn with_cursor(mut self, cursor: u64) -> Self {
+        self.cursor = cursor;
+        self
+    }
+}
+
+impl Encodable for TagChange {
+    type Error = Error;
+
+    /// Encode this change for storage in a log.
+    ///
+    /// The cursor is not encoded, as it is defined by
+    /// the position of the change within the log.
+    fn encode(&self, writer: &mut impl std::io::Write) -> Result<()> {
+        encoding::write_uint8(&mut *writer, self.kind.to_u8())?;
+        encoding::write_string(&mut *writer, &self.time.to_rfc3339())?;
+        encoding::write_string(&mut *writer, &self.spec.to_string())?;
+        match &self.tag {
+            Some(tag) => {
+                encoding::write_uint8(&mut *writer, 1)?;
+                tag.encode(writer)?;
+            }
+            None => encoding::write_uint8(&mut *writer, 0)?,
+        }
+        Ok(())
+    }
+}
+
+impl encoding::Decodable for TagChange {
+    fn decode(mut reader: &mut impl BufRead) -> Result<Self> {
+        let kind = TagChangeKind::from_u8(encoding::read_uint8(&mut *reader)?)?;
+        let time = DateTime::parse_from_rfc3339(&encoding::read_string(&mut *reader)?)?.into();
+        let spec = tracking::TagSpec::parse(encoding::read_string(&mut *reader)?)?;
+        let tag = match encoding::read_uint8(&mut *reader)? {
+            0 => None,
+            _ => Some(tracking::Tag::decode(reader)?),
+        };
+        Ok(Self {
+            cursor: 0,
+            time,
+            kind,
+            spec,
+            tag,
+        })
+    }
+}
+
+/// Follow the tag change log of a repository, starting after the given cursor.
+///
+/// The log is checked for new entries periodically, and only changes
+/// to tags under the given path prefix are yielded. The returned
+/// stream never ends unless reading the log fails.
+pub fn follow_tag_changes<'a, R>(
+    repo: R,
+    namespace: Option<TagNamespaceBuf>,
+    prefix: RelativePathBuf,
+    since: u64,
+) -> TagChangeStream<'a>
+where
+    R: std::ops::Deref + Send + Sync + 'a,
+    R::Target: TagStorage,
+{
+    Box::pin(async_stream::try_stream! {
+        let mut cursor = since;
+        loop {
+            let mut changes = repo.read_tag_changes_in_namespace(namespace.as_deref(), cursor);
+            let mut found = false;
+            while let Some(change) = changes.try_next().await? {
+                found = true;
+                cursor = change.cursor;
+                if change.spec.path().starts_with(&prefix) {
+                    yield change;
+                }
+            }
+            if !found {
+                tokio::time::sleep(TAG_CHANGE_POLL_INTERVAL).await;
+            }
+        }
+    })
+}
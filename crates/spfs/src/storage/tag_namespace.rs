@@ -42,7 +42,7 @@ impl std::fmt::Display for TagNamespace {
 }
 
 /// An owned tag namespace name
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
 pub struct TagNamespaceBuf(RelativePathBuf);
 
 impl TagNamespaceBuf {
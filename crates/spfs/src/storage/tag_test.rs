@@ -6,18 +6,20 @@ use std::os::unix::fs::MetadataExt;
 
 use chrono::prelude::*;
 use futures::TryStreamExt;
-use relative_path::RelativePathBuf;
+use relative_path::{RelativePath, RelativePathBuf};
 use rstest::rstest;
 use tokio_stream::StreamExt;
 
+use crate::encoding::prelude::*;
 use crate::fixtures::*;
-use crate::storage::fs::FsRepository;
-use crate::storage::{EntryType, TagStorage};
+use crate::storage::fs::{FsRepository, OpenFsRepository};
+use crate::storage::{EntryType, TagChange, TagChangeKind, TagNamespaceBuf, TagStorage};
 use crate::{encoding, tracking, Result};
 
 #[rstest]
 #[case::fs(tmprepo("fs"))]
 #[case::tar(tmprepo("tar"))]
+#[case::s3(tmprepo("s3"))]
 #[cfg_attr(feature = "server", case::rpc(tmprepo("rpc")))]
 #[tokio::test]
 async fn test_tag_stream(
@@ -70,6 +72,7 @@ async fn test_tag_stream(
 #[rstest]
 #[case::fs(tmprepo("fs"))]
 #[case::tar(tmprepo("tar"))]
+#[case::s3(tmprepo("s3"))]
 #[cfg_attr(feature = "server", case::rpc(tmprepo("rpc")))]
 #[tokio::test]
 async fn test_tag_no_duplication(
@@ -133,6 +136,7 @@ async fn test_tag_permissions(tmpdir: tempfile::TempDir) {
 #[rstest]
 #[case::fs(tmprepo("fs"))]
 #[case::tar(tmprepo("tar"))]
+#[case::s3(tmprepo("s3"))]
 #[cfg_attr(feature = "server", case::rpc(tmprepo("rpc")))]
 #[tokio::test]
 async fn test_ls_tags(
@@ -194,6 +198,7 @@ async fn test_ls_tags(
 #[rstest]
 #[case::fs(tmprepo("fs"))]
 #[case::tar(tmprepo("tar"))]
+#[case::s3(tmprepo("s3"))]
 #[cfg_attr(feature = "server", case::rpc(tmprepo("rpc")))]
 #[tokio::test]
 async fn test_tag_ordering(
@@ -253,6 +258,7 @@ async fn test_tag_ordering(
 #[rstest]
 #[case::fs(tmprepo("fs"))]
 #[case::tar(tmprepo("tar"))]
+#[case::s3(tmprepo("s3"))]
 #[cfg_attr(feature = "server", case::rpc(tmprepo("rpc")))]
 #[tokio::test]
 async fn test_rm_tags(
@@ -411,3 +417,178 @@ async fn test_tag_in_namespace_name_collision(
     let tag = tmprepo.resolve_tag(&spec_foo_bar_baz).await.unwrap();
     assert_eq!(tag.target, foo_bar_baz);
 }
+
+#[rstest]
+#[case::fs(tmprepo("fs"))]
+#[cfg_attr(feature = "server", case::rpc(tmprepo("rpc")))]
+#[tokio::test]
+async fn test_tag_change_log(
+    #[case]
+    #[future]
+    tmprepo: TempRepo,
+) {
+    init_logging();
+    let tmprepo = tmprepo.await;
+
+    let first = tracking::TagSpec::parse("spi/first").unwrap();
+    let second = tracking::TagSpec::parse("spi/second").unwrap();
+    let tag1 = tmprepo.push_tag(&first, &random_digest()).await.unwrap();
+    tmprepo.push_tag(&first, &random_digest()).await.unwrap();
+    tmprepo.push_tag(&second, &random_digest()).await.unwrap();
+    // inserting a tag that already exists is not a change
+    tmprepo.insert_tag(&tag1).await.unwrap();
+    tmprepo.remove_tag(&tag1).await.unwrap();
+    tmprepo.remove_tag_stream(&second).await.unwrap();
+
+    let changes: Vec<_> = tmprepo.read_tag_changes(0).try_collect().await.unwrap();
+    let summary: Vec<_> = changes
+        .iter()
+        .map(|c| (c.kind, c.spec.to_string()))
+        .collect();
+    assert_eq!(
+        summary,
+        vec![
+            (TagChangeKind::Insert, "spi/first".to_string()),
+            (TagChangeKind::Insert, "spi/first".to_string()),
+            (TagChangeKind::Insert, "spi/second".to_string()),
+            (TagChangeKind::Remove, "spi/first".to_string()),
+            (TagChangeKind::RemoveStream, "spi/second".to_string()),
+        ]
+    );
+    assert_eq!(changes[0].tag.as_ref(), Some(&tag1));
+    assert_eq!(changes[4].tag, None);
+
+    // reading from a cursor only returns later changes
+    let later: Vec<_> = tmprepo
+        .read_tag_changes(changes[2].cursor)
+        .try_collect()
+        .await
+        .unwrap();
+    assert_eq!(later, changes[3..]);
+    let none: Vec<_> = tmprepo
+        .read_tag_changes(changes[4].cursor)
+        .try_collect()
+        .await
+        .unwrap();
+    assert!(none.is_empty(), "no changes after the last cursor");
+
+    // changes in other namespaces are logged separately
+    let namespace = TagNamespaceBuf::new("test-namespace");
+    let tag = tracking::Tag::new(first.org(), first.name(), random_digest()).unwrap();
+    tmprepo
+        .insert_tag_in_namespace(Some(&*namespace), &tag)
+        .await
+        .unwrap();
+    let changes: Vec<_> = tmprepo
+        .read_tag_changes_in_namespace(Some(&*namespace), 0)
+        .try_collect()
+        .await
+        .unwrap();
+    assert_eq!(changes.len(), 1);
+    let unchanged: Vec<_> = tmprepo.read_tag_changes(0).try_collect().await.unwrap();
+    assert_eq!(unchanged.len(), 5);
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_tag_change_log_skips_bad_entries(tmpdir: tempfile::TempDir) {
+    init_logging();
+    let repo = OpenFsRepository::create(tmpdir.path()).await.unwrap();
+    let spec = tracking::TagSpec::parse("spi/stable").unwrap();
+    repo.push_tag(&spec, &random_digest()).await.unwrap();
+
+    // a complete entry that cannot be decoded
+    let log = repo.root().join("tags").join("changes.log");
+    let mut data = std::fs::read(&log).unwrap();
+    encoding::write_uint64(&mut data, 4).unwrap();
+    data.extend_from_slice(b"junk");
+    std::fs::write(&log, data).unwrap();
+    let tag = repo.push_tag(&spec, &random_digest()).await.unwrap();
+
+    let changes: Vec<_> = repo.read_tag_changes(0).try_collect().await.unwrap();
+    assert_eq!(changes.len(), 2, "the bad entry should be skipped");
+    assert_eq!(changes[1].tag, Some(tag));
+    let later: Vec<_> = repo
+        .read_tag_changes(changes[0].cursor)
+        .try_collect()
+        .await
+        .unwrap();
+    assert_eq!(later, changes[1..]);
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_tag_change_log_compaction(tmpdir: tempfile::TempDir) {
+    init_logging();
+    let repo = OpenFsRepository::create(tmpdir.path()).await.unwrap();
+    let spec = tracking::TagSpec::parse("spi/stable").unwrap();
+    repo.push_tag(&spec, &random_digest()).await.unwrap();
+
+    // fill the log up to its limit without making each change through the repo
+    let log = repo.root().join("tags").join("changes.log");
+    let mut data = std::fs::read(&log).unwrap();
+    while data.len() < 16 * 1024 * 1024 {
+        let tag = tracking::Tag::new(spec.org(), spec.name(), random_digest()).unwrap();
+        let entry = TagChange::insert(&tag).encode_to_bytes().unwrap();
+        encoding::write_uint64(&mut data, entry.len() as u64).unwrap();
+        data.extend_from_slice(&entry);
+    }
+    std::fs::write(&log, &data).unwrap();
+    let before: Vec<_> = repo.read_tag_changes(0).try_collect().await.unwrap();
+
+    let tag = repo.push_tag(&spec, &random_digest()).await.unwrap();
+    assert!(
+        std::fs::metadata(&log).unwrap().len() < data.len() as u64,
+        "the log should be compacted once it grows too large"
+    );
+    let after: Vec<_> = repo.read_tag_changes(0).try_collect().await.unwrap();
+    assert_eq!(after.last().unwrap().tag, Some(tag));
+    assert!(
+        after.len() < before.len(),
+        "the oldest changes should be dropped"
+    );
+    let kept = &after[..after.len() - 1];
+    assert_eq!(
+        kept,
+        &before[before.len() - kept.len()..],
+        "the remaining changes should keep their cursors"
+    );
+}
+
+#[rstest]
+#[case::fs(tmprepo("fs"))]
+#[cfg_attr(feature = "server", case::rpc(tmprepo("rpc")))]
+#[tokio::test]
+async fn test_watch_tag_changes(
+    #[case]
+    #[future]
+    tmprepo: TempRepo,
+) {
+    init_logging();
+    let tmprepo = tmprepo.await;
+    let timeout = std::time::Duration::from_secs(10);
+
+    let watched = tracking::TagSpec::parse("watched/tag").unwrap();
+    let ignored = tracking::TagSpec::parse("ignored/tag").unwrap();
+    tmprepo.push_tag(&ignored, &random_digest()).await.unwrap();
+    tmprepo.push_tag(&watched, &random_digest()).await.unwrap();
+
+    let mut changes = tmprepo.watch_tag_changes(RelativePath::new("watched"), 0);
+    let change = tokio::time::timeout(timeout, changes.next())
+        .await
+        .expect("existing changes should be returned right away")
+        .unwrap()
+        .unwrap();
+    assert_eq!(change.spec, watched);
+
+    // changes made after the watch has started should also be seen
+    let latest = tmprepo.push_tag(&ignored, &random_digest()).await.unwrap();
+    let latest_watched = tmprepo.push_tag(&watched, &random_digest()).await.unwrap();
+    let change = tokio::time::timeout(timeout, changes.next())
+        .await
+        .expect("new changes should be seen by the watch")
+        .unwrap()
+        .unwrap();
+    assert_eq!(change.tag, Some(latest_watched));
+    assert_ne!(change.tag, Some(latest));
+}
@@ -501,6 +501,16 @@ where
             _permit.is_ok(),
             "We never close the semaphore and so should never see errors"
         );
+        if self.dest.supports_chunked_payloads() {
+            if let Some(index) = self.src.read_chunk_index(digest).await? {
+                // Safety: we are simply deferring to the chunked variant
+                // which carries the same safety rules
+                let res = unsafe { self.sync_chunked_payload(index).await? };
+                self.reporter.synced_payload(&res);
+                return Ok(res);
+            }
+        }
+
         let (mut payload, _) = self.src.open_payload(digest).await?;
         if let Some(perms) = perms {
             payload = Box::pin(payload.with_permissions(perms));
@@ -520,6 +530,43 @@ where
         Ok(res)
     }
 
+    /// Sync a chunked payload by transferring only the chunks that
+    /// are missing from the destination repository.
+    ///
+    /// # Safety
+    /// This function may sync a payload without
+    /// syncing the blob, which is unsafe (see [`Self::sync_payload`])
+    async unsafe fn sync_chunked_payload(
+        &self,
+        index: storage::ChunkIndex,
+    ) -> Result<SyncPayloadResult> {
+        let mut transferred_size = 0;
+        for chunk in index.chunks() {
+            if self.policy.check_existing_payloads() && self.dest.has_payload(chunk.digest).await {
+                continue;
+            }
+            let (payload, _) = self.src.open_payload(chunk.digest).await?;
+            // Safety: chunks are only reachable through the index
+            // that is written once all of them exist
+            let (created_digest, size) = unsafe { self.dest.write_data(payload).await? };
+            if chunk.digest != created_digest {
+                return Err(Error::String(format!(
+                    "Source repository provided chunk that did not match the requested digest: wanted {}, got {created_digest}. wrote {size} bytes",
+                    chunk.digest
+                )));
+            }
+            transferred_size += size;
+        }
+
+        // Safety: all chunks now exist in the destination, and the
+        // caller is responsible for the related blob
+        unsafe { self.dest.write_chunk_index(&index).await? };
+        Ok(SyncPayloadResult::SyncedChunks {
+            size: index.size(),
+            transferred_size,
+        })
+    }
+
     async fn read_object_with_fallback(&self, digest: encoding::Digest) -> Result<graph::Object> {
         let res = self.src.read_object(digest).await;
         match res {
@@ -640,7 +687,9 @@ impl SyncReporter for ConsoleSyncReporter {
     fn synced_blob(&self, result: &SyncBlobResult) {
         let bars = self.get_bars();
         bars.payloads.inc(1);
-        bars.bytes.inc(result.summary().synced_payload_bytes);
+        let summary = result.summary();
+        bars.bytes
+            .inc(summary.synced_payload_bytes + summary.deduplicated_payload_bytes);
     }
 
     fn synced_env(&self, _result: &SyncEnvResult) {
@@ -686,6 +735,9 @@ pub struct SyncSummary {
     pub synced_payloads: usize,
     /// The total number of payload bytes synced
     pub synced_payload_bytes: u64,
+    /// The total number of payload bytes not transferred because
+    /// the chunks that hold them already existed
+    pub deduplicated_payload_bytes: u64,
 }
 
 impl SyncSummary {
@@ -716,6 +768,7 @@ impl std::ops::AddAssign for SyncSummary {
             skipped_payloads,
             synced_payloads,
             synced_payload_bytes,
+            deduplicated_payload_bytes,
         } = rhs;
         self.skipped_tags += skipped_tags;
         self.synced_tags += synced_tags;
@@ -724,6 +777,7 @@ impl std::ops::AddAssign for SyncSummary {
         self.skipped_payloads += skipped_payloads;
         self.synced_payloads += synced_payloads;
         self.synced_payload_bytes += synced_payload_bytes;
+        self.deduplicated_payload_bytes += deduplicated_payload_bytes;
     }
 }
 
@@ -984,6 +1038,9 @@ pub enum SyncPayloadResult {
     Duplicate,
     /// The payload was synced
     Synced { size: u64 },
+    /// The payload was synced as chunks, of which only
+    /// the missing ones were transferred
+    SyncedChunks { size: u64, transferred_size: u64 },
 }
 
 impl SyncPayloadResult {
@@ -998,6 +1055,15 @@ impl SyncPayloadResult {
                 synced_payload_bytes: *size,
                 ..Default::default()
             },
+            Self::SyncedChunks {
+                size,
+                transferred_size,
+            } => SyncSummary {
+                synced_payloads: 1,
+                synced_payload_bytes: *transferred_size,
+                deduplicated_payload_bytes: size.saturating_sub(*transferred_size),
+                ..Default::default()
+            },
         }
     }
 }
@@ -75,6 +75,7 @@ async fn test_push_ref(#[future] config: (tempfile::TempDir, Config)) {
 #[rstest]
 #[case::fs(tmprepo("fs"), tmprepo("fs"))]
 #[case::tar(tmprepo("tar"), tmprepo("tar"))]
+#[case::s3(tmprepo("s3"), tmprepo("s3"))]
 #[cfg_attr(feature = "server", case::rpc(tmprepo("rpc"), tmprepo("rpc")))]
 #[tokio::test]
 // This test just needs the config to not change while it is running.
@@ -136,6 +137,7 @@ async fn test_sync_ref(
 #[rstest]
 #[case::fs(tmprepo("fs"), tmprepo("fs"))]
 #[case::tar(tmprepo("tar"), tmprepo("tar"))]
+#[case::s3(tmprepo("s3"), tmprepo("s3"))]
 #[cfg_attr(feature = "server", case::rpc(tmprepo("rpc"), tmprepo("rpc")))]
 #[tokio::test]
 async fn test_sync_missing_from_source(
@@ -207,6 +209,7 @@ async fn test_sync_missing_from_source(
 #[rstest]
 #[case::fs(tmprepo("fs"), tmprepo("fs"))]
 #[case::tar(tmprepo("tar"), tmprepo("tar"))]
+#[case::s3(tmprepo("s3"), tmprepo("s3"))]
 #[cfg_attr(feature = "server", case::rpc(tmprepo("rpc"), tmprepo("rpc")))]
 #[tokio::test]
 // This test just needs the config to not change while it is running.
@@ -291,3 +294,66 @@ async fn config(tmpdir: tempfile::TempDir) -> (tempfile::TempDir, Config) {
     conf.storage.root = repo_path;
     (tmpdir, conf)
 }
+
+#[rstest]
+#[tokio::test]
+async fn test_sync_chunked_payload(
+    #[future]
+    #[with("fs")]
+    tmprepo: TempRepo,
+    tmpdir: tempfile::TempDir,
+) {
+    init_logging();
+    let repo_a = tmprepo.await;
+    let RepositoryHandle::FS(fs_repo) = &*repo_a else {
+        panic!("expected an fs repository");
+    };
+    let mut writer_a = (*fs_repo.opened().await.unwrap()).clone();
+    writer_a.set_payload_chunking_threshold(Some(0));
+    let mut writer_b = storage::fs::OpenFsRepository::create(tmpdir.path())
+        .await
+        .unwrap();
+    writer_b.set_payload_chunking_threshold(Some(0));
+
+    let size = 12 * 1024 * 1024;
+    let data = random_data(size);
+    let mut modified = data.clone();
+    modified.truncate(size / 2);
+    let digest = writer_a
+        .commit_blob(Box::pin(std::io::Cursor::new(data.clone())))
+        .await
+        .unwrap();
+    writer_b
+        .commit_blob(Box::pin(std::io::Cursor::new(modified.clone())))
+        .await
+        .unwrap();
+    let repo_b: RepositoryHandle = storage::fs::FsRepository::from(writer_b).into();
+
+    let summary = Syncer::new(&repo_a, &repo_b)
+        .sync_digest(digest)
+        .await
+        .expect("Failed to sync chunked blob")
+        .summary();
+    assert_eq!(summary.synced_payloads, 1);
+    assert!(
+        summary.deduplicated_payload_bytes > 0,
+        "chunks that already existed should not be transferred"
+    );
+    assert_eq!(
+        summary.synced_payload_bytes + summary.deduplicated_payload_bytes,
+        size as u64
+    );
+
+    let index = repo_b
+        .read_chunk_index(digest)
+        .await
+        .unwrap()
+        .expect("chunk index should be synced");
+    assert_eq!(index.size(), size as u64);
+    let (mut reader, _) = repo_b.open_payload(digest).await.unwrap();
+    let mut synced = Vec::new();
+    tokio::io::AsyncReadExt::read_to_end(&mut reader, &mut synced)
+        .await
+        .unwrap();
+    assert!(synced == data, "synced payload should match the original");
+}
@@ -2,7 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0
 // https://github.com/spkenv/spk
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 
 use relative_path::RelativePathBuf;
 
@@ -105,6 +105,9 @@ impl<U1, U2> Diff<U1, U2> {
             if a.object != b.object {
                 details = format!("{details} {{!content!}}");
             }
+            if a.xattrs != b.xattrs {
+                details = format!("{details} {{!xattrs!}}");
+            }
         }
         details
     }
@@ -135,6 +138,106 @@ pub fn compute_diff<U1: Clone>(a: &Manifest<U1>, b: &Manifest<U1>) -> Vec<Diff<U
     changes
 }
 
+/// Identifies how a path was changed by two different
+/// sets of changes made to the same base
+#[derive(Debug, Eq, PartialEq, Clone)]
+pub enum MergeMode<U = ()> {
+    /// Only our changes modified the path
+    Ours(DiffMode<U, U>),
+    /// Only their changes modified the path
+    Theirs(DiffMode<U, U>),
+    /// Both sets of changes made the same modification
+    Both(DiffMode<U, U>),
+    /// Each set of changes modified the path differently (ours, theirs)
+    Conflict(DiffMode<U, U>, DiffMode<U, U>),
+}
+
+impl<U> std::fmt::Display for MergeMode<U> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Ours(..) => f.write_str("ours"),
+            Self::Theirs(..) => f.write_str("theirs"),
+            Self::Both(..) => f.write_str("both"),
+            Self::Conflict(..) => f.write_str("conflict"),
+        }
+    }
+}
+
+impl<U> MergeMode<U> {
+    pub fn is_conflict(&self) -> bool {
+        matches!(self, Self::Conflict(..))
+    }
+}
+
+/// A single path that was modified in a three-way diff
+#[derive(Debug, Eq, PartialEq)]
+pub struct MergeDiff<U = ()> {
+    pub mode: MergeMode<U>,
+    pub path: RelativePathBuf,
+}
+
+impl<U> std::fmt::Display for MergeDiff<U> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_fmt(format_args!("{} {}", self.mode, self.path))
+    }
+}
+
+/// Compare two sets of changes (ours and theirs) made to the same base.
+///
+/// Only paths that were modified by at least one side are returned,
+/// ordered by path. Directories are only considered to be in conflict
+/// when their own metadata differs, since any conflicting changes to
+/// their contents are reported separately.
+pub fn compute_three_way_diff<U: Clone>(
+    base: &Manifest<U>,
+    ours: &Manifest<U>,
+    theirs: &Manifest<U>,
+) -> Vec<MergeDiff<U>> {
+    let mut their_changes = compute_diff(base, theirs)
+        .into_iter()
+        .filter(|d| !d.mode.is_unchanged())
+        .map(|d| (d.path, d.mode))
+        .collect::<HashMap<_, _>>();
+
+    let mut merged = Vec::new();
+    for diff in compute_diff(base, ours) {
+        if diff.mode.is_unchanged() {
+            continue;
+        }
+        let mode = match their_changes.remove(&diff.path) {
+            None => MergeMode::Ours(diff.mode),
+            Some(theirs) if is_same_change(&diff.mode, &theirs) => MergeMode::Both(diff.mode),
+            Some(theirs) => MergeMode::Conflict(diff.mode, theirs),
+        };
+        merged.push(MergeDiff {
+            mode,
+            path: diff.path,
+        });
+    }
+    merged.extend(their_changes.into_iter().map(|(path, mode)| MergeDiff {
+        mode: MergeMode::Theirs(mode),
+        path,
+    }));
+    merged.sort_by(|a, b| a.path.cmp(&b.path));
+    merged
+}
+
+/// True if the two changes leave the path in the same state
+fn is_same_change<U>(ours: &DiffMode<U, U>, theirs: &DiffMode<U, U>) -> bool {
+    fn result<U>(mode: &DiffMode<U, U>) -> Option<&Entry<U>> {
+        match mode {
+            DiffMode::Unchanged(e) | DiffMode::Changed(_, e) | DiffMode::Added(e) => Some(e),
+            DiffMode::Removed(_) => None,
+        }
+    }
+    match (result(ours), result(theirs)) {
+        (None, None) => true,
+        (Some(a), Some(b)) if a.is_dir() && b.is_dir() => a.mode == b.mode && a.xattrs == b.xattrs,
+        (Some(a), Some(b)) => a == b,
+        _ => false,
+    }
+}
+
 /// Compares the two entries, creating a diff to represent their delta.
 ///
 /// In the case of no change, the entry from `a` is returned.
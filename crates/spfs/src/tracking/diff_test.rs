@@ -5,7 +5,7 @@
 use relative_path::RelativePath;
 use rstest::rstest;
 
-use super::{compute_diff, Diff, DiffMode};
+use super::{compute_diff, compute_three_way_diff, Diff, DiffMode, MergeMode};
 use crate::fixtures::*;
 use crate::tracking::{compute_manifest, Entry, Manifest};
 
@@ -99,3 +99,41 @@ async fn test_compute_diff_removed(tmpdir: tempfile::TempDir) {
     assert!(matches!(third.mode, DiffMode::Removed(..)));
     assert_eq!(&third.path, &RelativePath::new("/dir/dir/file"));
 }
+
+#[rstest]
+#[tokio::test]
+async fn test_compute_three_way_diff(tmpdir: tempfile::TempDir) {
+    let dir = tmpdir.path();
+    let base_dir = dir.join("base");
+    std::fs::create_dir_all(base_dir.join("dir")).unwrap();
+    std::fs::write(base_dir.join("dir/same"), "same").unwrap();
+    std::fs::write(base_dir.join("dir/conflict"), "base").unwrap();
+    std::fs::write(base_dir.join("dir/agreed"), "base").unwrap();
+    let ours_dir = dir.join("ours");
+    let theirs_dir = dir.join("theirs");
+    for side in [&ours_dir, &theirs_dir] {
+        std::fs::create_dir_all(side.join("dir")).unwrap();
+        std::fs::write(side.join("dir/same"), "same").unwrap();
+        std::fs::write(side.join("dir/agreed"), "agreed").unwrap();
+    }
+    std::fs::write(ours_dir.join("dir/conflict"), "ours").unwrap();
+    std::fs::write(ours_dir.join("ours"), "ours").unwrap();
+    std::fs::write(theirs_dir.join("dir/conflict"), "theirs").unwrap();
+
+    let base = compute_manifest(base_dir).await.unwrap();
+    let ours = compute_manifest(ours_dir).await.unwrap();
+    let theirs = compute_manifest(theirs_dir).await.unwrap();
+    let actual = compute_three_way_diff(&base, &ours, &theirs)
+        .into_iter()
+        .map(|d| (d.path.to_string(), d.mode))
+        .collect::<Vec<_>>();
+
+    assert_eq!(actual.len(), 4, "{actual:#?}");
+    assert!(
+        matches!(actual[0], (ref p, MergeMode::Both(..)) if p == "/dir"),
+        "changes to the contents of a directory should not conflict"
+    );
+    assert!(matches!(actual[1], (ref p, MergeMode::Both(..)) if p == "/dir/agreed"));
+    assert!(matches!(actual[2], (ref p, MergeMode::Conflict(..)) if p == "/dir/conflict"));
+    assert!(matches!(actual[3], (ref p, MergeMode::Ours(DiffMode::Added(..))) if p == "/ours"));
+}
@@ -101,6 +101,13 @@ impl encoding::Decodable for EntryKind {
     }
 }
 
+/// The extended attributes of an entry, by name.
+///
+/// This includes posix acls (`system.posix_acl_*`) and file
+/// capabilities (`security.capability`), which are both stored
+/// as extended attributes on linux.
+pub type Xattrs = std::collections::BTreeMap<String, Vec<u8>>;
+
 /// An entry in the manifest identifies a directory or file in the tree
 ///
 /// Any associated user data is not considered for comparison, sorting, etc.
@@ -113,6 +120,8 @@ pub struct Entry<T = ()> {
     pub user_data: T,
     /// The size associated with non-blob entries.
     pub legacy_size: u64,
+    /// Any extended attributes that were captured for this entry.
+    pub xattrs: Xattrs,
 }
 
 impl<T> std::fmt::Debug for Entry<T>
@@ -129,14 +138,18 @@ where
             entries,
             user_data,
             legacy_size: _,
+            xattrs,
         } = self;
-        f.debug_struct("Entry")
-            .field("kind", kind)
+        let mut f = f.debug_struct("Entry");
+        f.field("kind", kind)
             .field("mode", &format!("{mode:#06o}"))
             .field("object", object)
             .field("entries", entries)
-            .field("user_data", user_data)
-            .finish()
+            .field("user_data", user_data);
+        if !xattrs.is_empty() {
+            f.field("xattrs", &xattrs.keys().collect::<Vec<_>>());
+        }
+        f.finish()
     }
 }
 
@@ -151,11 +164,13 @@ impl<T, T2> PartialEq<Entry<T2>> for Entry<T> {
             entries,
             user_data: _,
             legacy_size: _,
+            xattrs,
         } = other;
         if self.kind != *kind
             || self.mode != *mode
             || self.size() != other.size()
             || self.object != *object
+            || self.xattrs != *xattrs
         {
             return false;
         }
@@ -196,6 +211,7 @@ impl<T> Entry<T> {
             entries: Default::default(),
             user_data,
             legacy_size: 0,
+            xattrs: Default::default(),
         }
     }
 
@@ -208,6 +224,7 @@ impl<T> Entry<T> {
             entries: Default::default(),
             user_data,
             legacy_size: 0,
+            xattrs: Default::default(),
         }
     }
 
@@ -221,6 +238,7 @@ impl<T> Entry<T> {
             entries: Default::default(),
             user_data,
             legacy_size: 0,
+            xattrs: Default::default(),
         }
     }
 
@@ -234,6 +252,7 @@ impl<T> Entry<T> {
             entries: Default::default(),
             user_data,
             legacy_size: 0,
+            xattrs: Default::default(),
         }
     }
 
@@ -306,6 +325,17 @@ impl<T> Entry<T> {
             })
     }
 
+    /// Remove the extended attributes from this entry and all of
+    /// its children, returning the number of entries that had any
+    pub fn clear_xattrs(&mut self) -> usize {
+        let mut cleared = usize::from(!self.xattrs.is_empty());
+        self.xattrs.clear();
+        for entry in self.entries.values_mut() {
+            cleared += entry.clear_xattrs();
+        }
+        cleared
+    }
+
     pub fn strip_user_data(self) -> Entry<()> {
         Entry {
             kind: self.kind,
@@ -318,6 +348,7 @@ impl<T> Entry<T> {
                 .collect(),
             user_data: (),
             legacy_size: self.legacy_size,
+            xattrs: self.xattrs,
         }
     }
 
@@ -334,6 +365,7 @@ impl<T> Entry<T> {
                 .collect(),
             user_data,
             legacy_size: self.legacy_size,
+            xattrs: self.xattrs,
         }
     }
 
@@ -351,6 +383,7 @@ impl<T> Entry<T> {
                 .collect(),
             user_data,
             legacy_size: self.legacy_size,
+            xattrs: self.xattrs,
         }
     }
 }
@@ -363,6 +396,7 @@ where
         self.kind = other.kind;
         self.object = other.object;
         self.mode = other.mode;
+        self.xattrs.clone_from(&other.xattrs);
         if !self.kind.is_tree() {
             return;
         }
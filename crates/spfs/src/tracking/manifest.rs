@@ -34,6 +34,12 @@ pub const DEFAULT_MAX_CONCURRENT_BLOBS: usize = 1000;
 /// See: [`ManifestBuilder::with_max_concurrent_branches`]
 pub const DEFAULT_MAX_CONCURRENT_BRANCHES: usize = 5;
 
+/// Extended attributes that are never captured into a manifest
+/// because they describe the host or the overlay filesystem
+/// rather than the file itself.
+#[cfg(unix)]
+const IGNORED_XATTR_PREFIXES: &[&str] = &["trusted.overlay.", "user.overlay.", "security.selinux"];
+
 #[derive(Clone)]
 pub struct Manifest<T = ()> {
     /// retains the original header values/configuration
@@ -108,6 +114,12 @@ impl<T> Manifest<T> {
         self.root
     }
 
+    /// Remove all extended attributes from this manifest, returning
+    /// the number of entries that had any
+    pub fn clear_xattrs(&mut self) -> usize {
+        self.root.clear_xattrs()
+    }
+
     /// Return true if this manifest has no contents.
     pub fn is_empty(&self) -> bool {
         self.root.entries.len() == 0
@@ -447,6 +459,7 @@ where
     reporter: R,
     blob_semaphore: Arc<Semaphore>,
     max_concurrent_branches: usize,
+    capture_xattrs: bool,
 }
 
 impl ManifestBuilder<(), (), ()> {
@@ -463,6 +476,7 @@ impl Default for ManifestBuilder<(), (), ()> {
             reporter: (),
             blob_semaphore: Arc::new(Semaphore::new(DEFAULT_MAX_CONCURRENT_BLOBS)),
             max_concurrent_branches: DEFAULT_MAX_CONCURRENT_BRANCHES,
+            capture_xattrs: false,
         }
     }
 }
@@ -493,6 +507,17 @@ where
         self
     }
 
+    /// Set whether the extended attributes of files and directories
+    /// should be captured into the manifest.
+    ///
+    /// This includes posix acls and file capabilities. Symlinks and
+    /// masked entries never have their extended attributes captured.
+    /// Defaults to false.
+    pub fn with_xattrs(mut self, capture_xattrs: bool) -> Self {
+        self.capture_xattrs = capture_xattrs;
+        self
+    }
+
     /// Use the provided hasher when building the manifest.
     ///
     /// The hasher turns blob contents into a digest to be included
@@ -509,6 +534,7 @@ where
             reporter: self.reporter,
             blob_semaphore: self.blob_semaphore,
             max_concurrent_branches: self.max_concurrent_branches,
+            capture_xattrs: self.capture_xattrs,
         }
     }
 
@@ -528,6 +554,7 @@ where
             reporter: self.reporter,
             blob_semaphore: self.blob_semaphore,
             max_concurrent_branches: self.max_concurrent_branches,
+            capture_xattrs: self.capture_xattrs,
         }
     }
 
@@ -542,6 +569,7 @@ where
             reporter,
             blob_semaphore: self.blob_semaphore,
             max_concurrent_branches: self.max_concurrent_branches,
+            capture_xattrs: self.capture_xattrs,
         }
     }
 
@@ -720,11 +748,57 @@ where
             entry.mode = 0o644;
         }
 
+        #[cfg(unix)]
+        if self.capture_xattrs && !entry.kind.is_mask() && !file_type.is_symlink() {
+            let path = path.as_ref().to_owned();
+            entry.xattrs = tokio::task::spawn_blocking(move || read_xattrs(&path))
+                .await
+                .expect("syscall should not panic")?;
+        }
+
         self.reporter.computed_entry(&entry);
         Ok(entry)
     }
 }
 
+/// Read the extended attributes of the file at `path`, without
+/// following symlinks.
+#[cfg(unix)]
+fn read_xattrs(path: &std::path::Path) -> Result<super::Xattrs> {
+    let mut xattrs = super::Xattrs::new();
+    let names = match xattr::list(path) {
+        Ok(names) => names,
+        // the filesystem may not support extended attributes at all
+        Err(err) if err.raw_os_error() == Some(libc::ENOTSUP) => return Ok(xattrs),
+        Err(err) => {
+            return Err(Error::StorageReadError(
+                "list xattrs of node",
+                path.to_owned(),
+                err,
+            ))
+        }
+    };
+    for name in names {
+        let Some(name) = name.to_str() else {
+            tracing::warn!(?name, ?path, "skipping non-utf8 extended attribute");
+            continue;
+        };
+        if IGNORED_XATTR_PREFIXES
+            .iter()
+            .any(|prefix| name.starts_with(prefix))
+        {
+            continue;
+        }
+        let value = xattr::get(path, name)
+            .map_err(|err| Error::StorageReadError("get xattr of node", path.to_owned(), err))?;
+        // the attribute may have been removed since it was listed
+        if let Some(value) = value {
+            xattrs.insert(name.to_owned(), value);
+        }
+    }
+    Ok(xattrs)
+}
+
 #[derive(Debug)]
 pub struct ManifestNode<'a, T = ()> {
     pub path: RelativePathBuf,
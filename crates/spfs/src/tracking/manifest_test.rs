@@ -125,3 +125,39 @@ async fn test_layer_manifests_removal() {
 
     compute_manifest("./src").await.unwrap();
 }
+
+#[cfg(unix)]
+#[rstest]
+#[tokio::test]
+async fn test_compute_manifest_xattrs(tmpdir: tempfile::TempDir) {
+    let dir = tmpdir.path();
+    ensure(dir.join("bin/runme"), "somedata");
+    if let Err(err) = xattr::set(dir.join("bin/runme"), "user.spfs.test", b"value") {
+        tracing::warn!("skipping test, xattrs not supported in tmpdir: {err}");
+        return;
+    }
+
+    let manifest = compute_manifest(dir).await.unwrap();
+    assert!(
+        manifest.get_path("bin/runme").unwrap().xattrs.is_empty(),
+        "xattrs should not be captured by default"
+    );
+
+    let manifest = super::ManifestBuilder::new()
+        .with_xattrs(true)
+        .compute_manifest(dir)
+        .await
+        .unwrap();
+    let entry = manifest.get_path("bin/runme").unwrap();
+    assert_eq!(
+        entry.xattrs.get("user.spfs.test").map(Vec::as_slice),
+        Some(b"value".as_slice())
+    );
+
+    let graph_manifest = manifest.to_graph_manifest();
+    assert_eq!(
+        graph_manifest.to_tracking_manifest(),
+        manifest,
+        "xattrs should round-trip through the graph manifest"
+    );
+}
@@ -11,10 +11,11 @@ mod env;
 pub mod manifest;
 mod object;
 mod tag;
+mod text_diff;
 
 pub use blob_reader::{BlobRead, BlobReadExt};
-pub use diff::{compute_diff, Diff, DiffMode};
-pub use entry::{Entry, EntryKind};
+pub use diff::{compute_diff, compute_three_way_diff, Diff, DiffMode, MergeDiff, MergeMode};
+pub use entry::{Entry, EntryKind, Xattrs};
 pub use env::{EnvSpec, EnvSpecItem, ENV_SPEC_EMPTY, ENV_SPEC_SEPARATOR};
 pub use manifest::{
     compute_manifest,
@@ -30,5 +31,6 @@ pub use manifest::{
 };
 pub use object::Object;
 pub use tag::{build_tag_spec, split_tag_spec, Tag, TagSpec};
+pub use text_diff::{unified_diff, DEFAULT_CONTEXT_LINES};
 mod time_spec;
 pub use time_spec::{parse_duration, parse_time, TimeSpec};
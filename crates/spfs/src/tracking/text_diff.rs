@@ -0,0 +1,207 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+#[cfg(test)]
+#[path = "./text_diff_test.rs"]
+mod text_diff_test;
+
+/// The number of unchanged lines shown around each change in a unified diff
+pub const DEFAULT_CONTEXT_LINES: usize = 3;
+
+/// Beyond this many changed lines, the files are considered
+/// to be entirely different rather than searching further for
+/// a minimal diff, which would take too much time and memory
+const MAX_EDIT_DISTANCE: usize = 2000;
+
+/// A single step in transforming one sequence of lines into another
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Edit {
+    Equal,
+    Delete,
+    Insert,
+}
+
+/// Render the changes between two texts in the unified diff format.
+///
+/// An empty string is returned if the texts are the same.
+pub fn unified_diff(old: &str, new: &str, old_name: &str, new_name: &str) -> String {
+    let old_lines = old.split_inclusive('\n').collect::<Vec<_>>();
+    let new_lines = new.split_inclusive('\n').collect::<Vec<_>>();
+    let edits = diff_lines(&old_lines, &new_lines);
+    if edits.iter().all(|e| *e == Edit::Equal) {
+        return String::new();
+    }
+
+    // pair each edit with the position of the lines
+    // that it applies to in the old and new texts
+    let mut ops = Vec::with_capacity(edits.len());
+    let (mut old_pos, mut new_pos) = (0, 0);
+    for edit in edits {
+        ops.push((edit, old_pos, new_pos));
+        match edit {
+            Edit::Equal => {
+                old_pos += 1;
+                new_pos += 1;
+            }
+            Edit::Delete => old_pos += 1,
+            Edit::Insert => new_pos += 1,
+        }
+    }
+
+    let mut out = format!("--- {old_name}\n+++ {new_name}\n");
+    let changes = ops
+        .iter()
+        .enumerate()
+        .filter(|(_, (edit, ..))| *edit != Edit::Equal)
+        .map(|(i, _)| i)
+        .collect::<Vec<_>>();
+    let mut group_start = 0;
+    for i in 0..changes.len() {
+        let is_last = i + 1 == changes.len();
+        if !is_last && changes[i + 1] - changes[i] <= DEFAULT_CONTEXT_LINES * 2 + 1 {
+            // the context of the two changes would touch or overlap
+            continue;
+        }
+        let start = changes[group_start].saturating_sub(DEFAULT_CONTEXT_LINES);
+        let end = (changes[i] + DEFAULT_CONTEXT_LINES + 1).min(ops.len());
+        write_hunk(&mut out, &ops[start..end], &old_lines, &new_lines);
+        group_start = i + 1;
+    }
+    out
+}
+
+fn write_hunk(
+    out: &mut String,
+    ops: &[(Edit, usize, usize)],
+    old_lines: &[&str],
+    new_lines: &[&str],
+) {
+    let (_, old_start, new_start) = ops[0];
+    let old_count = ops.iter().filter(|(e, ..)| *e != Edit::Insert).count();
+    let new_count = ops.iter().filter(|(e, ..)| *e != Edit::Delete).count();
+    out.push_str(&format!(
+        "@@ -{} +{} @@\n",
+        hunk_range(old_start, old_count),
+        hunk_range(new_start, new_count)
+    ));
+    for (edit, old_pos, new_pos) in ops {
+        let (marker, line) = match edit {
+            Edit::Equal => (' ', old_lines[*old_pos]),
+            Edit::Delete => ('-', old_lines[*old_pos]),
+            Edit::Insert => ('+', new_lines[*new_pos]),
+        };
+        out.push(marker);
+        out.push_str(line);
+        if !line.ends_with('\n') {
+            out.push_str("\n\\ No newline at end of file\n");
+        }
+    }
+}
+
+fn hunk_range(start: usize, count: usize) -> String {
+    match count {
+        // an empty range refers to the line before it
+        0 => format!("{start},0"),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{count}", start + 1),
+    }
+}
+
+/// Find a minimal set of edits that turns `old` into `new`
+/// using the Myers difference algorithm
+fn diff_lines(old: &[&str], new: &[&str]) -> Vec<Edit> {
+    // the common prefix and suffix are trimmed first, which is much
+    // cheaper than including them in the search below
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let a = &old[prefix..old.len() - suffix];
+    let b = &new[prefix..new.len() - suffix];
+
+    let mut edits = vec![Edit::Equal; prefix];
+    match shortest_edit(a, b) {
+        Some(middle) => edits.extend(middle),
+        None => {
+            edits.extend(std::iter::repeat(Edit::Delete).take(a.len()));
+            edits.extend(std::iter::repeat(Edit::Insert).take(b.len()));
+        }
+    }
+    edits.extend(std::iter::repeat(Edit::Equal).take(suffix));
+    edits
+}
+
+/// The core of the Myers algorithm, returning None
+/// if no solution is found within [`MAX_EDIT_DISTANCE`]
+fn shortest_edit(a: &[&str], b: &[&str]) -> Option<Vec<Edit>> {
+    let (n, m) = (a.len() as isize, b.len() as isize);
+    let max = (a.len() + b.len()).min(MAX_EDIT_DISTANCE) as isize;
+    // the furthest x position reached on each diagonal k, indexed by k + offset
+    let offset = max + 1;
+    let mut v = vec![0isize; 2 * offset as usize + 1];
+    // the relevant part of v at the start of each round, for backtracking
+    let mut trace = Vec::new();
+
+    let mut found = None;
+    'search: for d in 0..=max {
+        trace.push(v[(offset - d) as usize..=(offset + d) as usize].to_vec());
+        for k in (-d..=d).step_by(2) {
+            let idx = (offset + k) as usize;
+            let mut x = if k == -d || (k != d && v[idx - 1] < v[idx + 1]) {
+                v[idx + 1]
+            } else {
+                v[idx - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[idx] = x;
+            if x >= n && y >= m {
+                found = Some(d);
+                break 'search;
+            }
+        }
+    }
+    let distance = found?;
+
+    let mut edits = Vec::with_capacity((n + m) as usize);
+    let (mut x, mut y) = (n, m);
+    for d in (0..=distance).rev() {
+        let k = x - y;
+        let (prev_x, prev_y) = if d == 0 {
+            (0, 0)
+        } else {
+            let v = &trace[d as usize];
+            let at = |k: isize| v[(k + d) as usize];
+            let prev_k = if k == -d || (k != d && at(k - 1) < at(k + 1)) {
+                k + 1
+            } else {
+                k - 1
+            };
+            let prev_x = at(prev_k);
+            (prev_x, prev_x - prev_k)
+        };
+        while x > prev_x && y > prev_y {
+            edits.push(Edit::Equal);
+            x -= 1;
+            y -= 1;
+        }
+        if d > 0 {
+            edits.push(if x == prev_x {
+                Edit::Insert
+            } else {
+                Edit::Delete
+            });
+        }
+        x = prev_x;
+        y = prev_y;
+    }
+    edits.reverse();
+    Some(edits)
+}
@@ -0,0 +1,78 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use rstest::rstest;
+
+use super::unified_diff;
+
+#[rstest]
+fn test_unified_diff_same() {
+    assert_eq!(unified_diff("a\nb\n", "a\nb\n", "a/file", "b/file"), "");
+}
+
+#[rstest]
+fn test_unified_diff_changed_line() {
+    let old = "1\n2\n3\n4\n5\n6\n7\n8\n9\n";
+    let new = "1\n2\n3\n4\nfive\n6\n7\n8\n9\n";
+    let expected = "\
+--- a/file
++++ b/file
+@@ -2,7 +2,7 @@
+ 2
+ 3
+ 4
+-5
++five
+ 6
+ 7
+ 8
+";
+    assert_eq!(unified_diff(old, new, "a/file", "b/file"), expected);
+}
+
+#[rstest]
+fn test_unified_diff_separate_hunks() {
+    let old = (1..=20).map(|i| format!("{i}\n")).collect::<String>();
+    let new = (1..=20)
+        .filter(|i| *i != 19)
+        .map(|i| match i {
+            2 => "two\n".to_string(),
+            _ => format!("{i}\n"),
+        })
+        .collect::<String>();
+    let expected = "\
+--- a
++++ b
+@@ -1,5 +1,5 @@
+ 1
+-2
++two
+ 3
+ 4
+ 5
+@@ -16,5 +16,4 @@
+ 16
+ 17
+ 18
+-19
+ 20
+";
+    assert_eq!(unified_diff(&old, &new, "a", "b"), expected);
+}
+
+#[rstest]
+fn test_unified_diff_added_file() {
+    let expected = "\
+--- /dev/null
++++ b/file
+@@ -0,0 +1,2 @@
++first
++second
+\\ No newline at end of file
+";
+    assert_eq!(
+        unified_diff("", "first\nsecond", "/dev/null", "b/file"),
+        expected
+    );
+}
@@ -36,6 +36,7 @@ fn test_split_manifest_permissions() {
                 entries: Default::default(),
                 user_data: (),
                 legacy_size: 0,
+                xattrs: Default::default(),
             },
         )
         .unwrap();
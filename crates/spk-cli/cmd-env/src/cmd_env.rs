@@ -2,10 +2,15 @@
 // SPDX-License-Identifier: Apache-2.0
 // https://github.com/spkenv/spk
 
+use std::path::PathBuf;
+use std::sync::Arc;
+
 use clap::Args;
 use miette::{Context, Result};
 use spk_cli_common::{build_required_packages, flags, CommandArgs, Run};
 use spk_exec::setup_runtime;
+use spk_solve::solution::Lockfile;
+use spk_solve::SolveCache;
 #[cfg(feature = "statsd")]
 use spk_solve::{get_metrics_client, SPK_RUN_TIME_METRIC};
 
@@ -31,6 +36,21 @@ pub struct Env {
     #[clap(flatten)]
     pub formatter_settings: flags::DecisionFormatterSettings,
 
+    /// Restore the environment from a lockfile created by `spk lock`
+    ///
+    /// The locked builds are used exactly as they were recorded,
+    /// without resolving any requests, and this fails if any of them
+    /// are no longer available or have been deprecated.
+    #[clap(long, value_name = "FILE", conflicts_with = "REQUESTS")]
+    pub locked: Option<PathBuf>,
+
+    /// Always solve the requests, instead of reusing a cached solution
+    ///
+    /// Solutions are only cached when a `solve_cache_dir` is set in
+    /// the solver section of the spk config.
+    #[clap(long, conflicts_with = "locked")]
+    pub no_solve_cache: bool,
+
     /// The requests to resolve and run
     #[clap(name = "REQUESTS")]
     pub requested: Vec<String>,
@@ -64,20 +84,39 @@ impl Run for Env {
             rt.config.live_layers = live_layers;
         }
 
-        let mut solver = self.solver.get_solver(&self.options).await?;
-
-        let requests = self
-            .requests
-            .parse_requests(&self.requested, &self.options, solver.repositories())
-            .await?;
-        for request in requests {
-            solver.add_request(request)
-        }
-
-        let formatter = self.formatter_settings.get_formatter(self.verbose)?;
-        let (solution, _) = formatter.run_and_print_resolve(&solver).await?;
-
-        let solution = build_required_packages(&solution).await?;
+        let solution = match &self.locked {
+            Some(lockfile) => {
+                let lockfile = Lockfile::load(lockfile)?;
+                let repos = self
+                    .solver
+                    .repos
+                    .get_repos_for_non_destructive_operation()
+                    .await?
+                    .into_iter()
+                    .map(|(_, repo)| Arc::new(repo))
+                    .collect::<Vec<_>>();
+                lockfile.to_solution(&repos).await?
+            }
+            None => {
+                let mut solver = self.solver.get_solver(&self.options).await?;
+                if !self.no_solve_cache {
+                    solver.set_solve_cache(SolveCache::from_config());
+                }
+
+                let requests = self
+                    .requests
+                    .parse_requests(&self.requested, &self.options, solver.repositories())
+                    .await?;
+                for request in requests {
+                    solver.add_request(request)
+                }
+
+                let formatter = self.formatter_settings.get_formatter(self.verbose)?;
+                let (solution, _) = formatter.run_and_print_resolve(&solver).await?;
+
+                build_required_packages(&solution).await?
+            }
+        };
 
         rt.status.editable =
             self.runtime.editable() || self.requests.any_build_stage_requests(&self.requested)?;
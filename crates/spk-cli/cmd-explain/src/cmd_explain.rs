@@ -2,10 +2,21 @@
 // SPDX-License-Identifier: Apache-2.0
 // https://github.com/spkenv/spk
 
-use clap::Args;
-use miette::Result;
+use clap::{Args, ValueEnum};
+use miette::{IntoDiagnostic, Result};
 use spk_cli_common::{flags, CommandArgs, Run};
 
+/// How to show the requests that conflict with each other
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum CoreFormat {
+    /// A tree explaining how the requests lead to the conflict
+    #[default]
+    Text,
+    /// The same explanation as structured json, without showing
+    /// the resolve process
+    Json,
+}
+
 /// Show the resolve process for a set of packages.
 #[derive(Args)]
 pub struct Explain {
@@ -22,6 +33,21 @@ pub struct Explain {
     #[clap(flatten)]
     pub formatter_settings: flags::DecisionFormatterSettings,
 
+    /// When the requests cannot be resolved, search for a minimal set
+    /// of them that still conflict and show why they cannot be used
+    /// together
+    ///
+    /// This runs one additional solve for each request so it can take
+    /// some time for large sets of requests. Only the requests given
+    /// here are reduced, the requirements of the packages involved are
+    /// shown as they are.
+    #[clap(long)]
+    pub core: bool,
+
+    /// How to show the conflicting requests found with --core
+    #[clap(long, value_enum, default_value_t, requires = "core")]
+    pub core_format: CoreFormat,
+
     /// The requests to resolve
     #[clap(name = "REQUESTS", required = true)]
     pub requested: Vec<String>,
@@ -78,13 +104,30 @@ impl Run for Explain {
             solver.add_request(request)
         }
 
+        if self.core_format == CoreFormat::Json {
+            // Only the json is printed so that it can be parsed
+            let core = solver.find_unsatisfiable_core().await?;
+            let data = core.as_ref().map(|core| core.to_json()).unwrap_or_default();
+            println!("{}", serde_json::to_string_pretty(&data).into_diagnostic()?);
+            return Ok(if core.is_some() { 1 } else { 0 });
+        }
+
         // Always show the solution packages for the solve
         let formatter = self
             .formatter_settings
             .get_formatter_builder(self.verbose + 1)?
             .with_solution(true)
             .build();
-        formatter.run_and_print_resolve(&solver).await?;
+        let result = formatter.run_and_print_resolve(&solver).await;
+        if let Err(err) = result {
+            // other errors would only happen again when searching for a core
+            if self.core && err.is_unsatisfiable() {
+                if let Some(core) = solver.find_unsatisfiable_core().await? {
+                    println!("{core}");
+                }
+            }
+            return Err(err.into());
+        }
 
         Ok(0)
     }
@@ -234,6 +234,11 @@ pub struct Solver {
     /// requests, build validation before a resolve, and for build keys
     #[clap(long, env = "SPK_SOLVER_CHECK_IMPOSSIBLE_ALL")]
     pub check_impossible_all: bool,
+
+    /// What the solver optimizes for when more than one candidate
+    /// satisfies a request, overriding the strategy in the spk config
+    #[clap(long, env = "SPK_SOLVER__SOLVE_STRATEGY", value_enum)]
+    pub solve_strategy: Option<SolveStrategy>,
 }
 
 impl Solver {
@@ -255,6 +260,11 @@ impl Solver {
         solver.set_build_key_impossible_checks(
             self.check_impossible_builds || self.check_impossible_all,
         );
+        let strategy = match self.solve_strategy {
+            Some(strategy) => strategy.into(),
+            None => solve::package_iterator::SolveStrategy::from_config(),
+        };
+        solver.set_solve_strategy(strategy);
 
         for r in options.get_var_requests()? {
             solver.add_request(r.into());
@@ -1016,11 +1026,40 @@ pub enum SolverToRun {
     Cli,
     /// Run and show output from the "impossible requests" checking solver
     Checks,
+    /// Run and show output from the conflict-driven solver, which
+    /// backjumps to the cause of each conflict and explains why a
+    /// set of requests cannot be satisfied
+    Conflict,
     /// Run both solvers, showing the output from the basic solver,
     /// unless overridden with --solver-to-show
     All,
 }
 
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
+pub enum SolveStrategy {
+    /// Try the highest versions of each package first
+    Newest,
+    /// Try the lowest versions of each package first, to check that
+    /// the lower bounds of requirements are still accurate
+    Lowest,
+    /// Try the packages that are already in the current runtime first
+    PreferRuntime,
+    /// Try the builds that need the fewest layers to be synced into the
+    /// local repository first
+    PreferLocal,
+}
+
+impl From<SolveStrategy> for solve::package_iterator::SolveStrategy {
+    fn from(item: SolveStrategy) -> solve::package_iterator::SolveStrategy {
+        match item {
+            SolveStrategy::Newest => solve::package_iterator::SolveStrategy::Newest,
+            SolveStrategy::Lowest => solve::package_iterator::SolveStrategy::Lowest,
+            SolveStrategy::PreferRuntime => solve::package_iterator::SolveStrategy::PreferRuntime,
+            SolveStrategy::PreferLocal => solve::package_iterator::SolveStrategy::PreferLocal,
+        }
+    }
+}
+
 #[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ValueEnum)]
 pub enum SolverToShow {
     /// Show output from the basic solver
@@ -1034,6 +1073,7 @@ impl From<SolverToRun> for MultiSolverKind {
         match item {
             SolverToRun::Cli => MultiSolverKind::Unchanged,
             SolverToRun::Checks => MultiSolverKind::AllImpossibleChecks,
+            SolverToRun::Conflict => MultiSolverKind::ConflictDriven,
             SolverToRun::All => MultiSolverKind::All,
         }
     }
@@ -1111,6 +1151,11 @@ pub struct DecisionFormatterSettings {
     /// the (cli) solver is displayed. even if the result ultimately
     /// comes from the (checks) solver. To run only one solver, use
     /// `--solver-to-run <cli|checks>`.
+    ///
+    /// The (conflict) solver is not run by default. It learns from
+    /// each conflict it finds and jumps straight back to the decision
+    /// that caused it, and when there is no solution it explains which
+    /// requests could not be satisfied together.
     #[clap(long, env = "SPK_SOLVER__SOLVER_TO_RUN", value_enum, default_value_t = SolverToRun::All)]
     pub solver_to_run: SolverToRun,
     /// Control what solver's output is shown when multiple solvers
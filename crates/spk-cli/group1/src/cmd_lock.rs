@@ -0,0 +1,75 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use std::path::PathBuf;
+
+use clap::Args;
+use miette::Result;
+use spk_cli_common::{flags, CommandArgs, Run};
+use spk_solve::solution::Lockfile;
+
+/// Resolve a set of requests and save the solution to a lockfile
+///
+/// The lockfile records every resolved build along with its components,
+/// source repository and layers, so that `spk env --locked` can restore
+/// exactly the same environment later on without solving again.
+#[derive(Args)]
+pub struct Lock {
+    #[clap(flatten)]
+    pub solver: flags::Solver,
+    #[clap(flatten)]
+    pub options: flags::Options,
+    #[clap(flatten)]
+    pub requests: flags::Requests,
+
+    /// Verbosity level, can be specified multiple times for more verbose output
+    #[clap(short, long, global = true, action = clap::ArgAction::Count)]
+    pub verbose: u8,
+
+    #[clap(flatten)]
+    pub formatter_settings: flags::DecisionFormatterSettings,
+
+    /// The file to write the lockfile to
+    #[clap(long, default_value = "spk.lock")]
+    pub output: PathBuf,
+
+    /// The requests to resolve and lock
+    #[clap(name = "REQUESTS", required = true)]
+    pub requested: Vec<String>,
+}
+
+#[async_trait::async_trait]
+impl Run for Lock {
+    type Output = i32;
+
+    async fn run(&mut self) -> Result<Self::Output> {
+        let mut solver = self.solver.get_solver(&self.options).await?;
+
+        let requests = self
+            .requests
+            .parse_requests(&self.requested, &self.options, solver.repositories())
+            .await?;
+        for request in requests {
+            solver.add_request(request)
+        }
+
+        let formatter = self.formatter_settings.get_formatter(self.verbose)?;
+        let (solution, _) = formatter.run_and_print_resolve(&solver).await?;
+
+        let lockfile = Lockfile::from_solution(&solution)?;
+        lockfile.save(&self.output)?;
+        tracing::info!(
+            "Locked {} packages to {}",
+            lockfile.packages().len(),
+            self.output.display()
+        );
+        Ok(0)
+    }
+}
+
+impl CommandArgs for Lock {
+    fn get_positional_args(&self) -> Vec<String> {
+        self.requested.clone()
+    }
+}
@@ -5,4 +5,5 @@
 pub mod cmd_bake;
 pub mod cmd_completion;
 pub mod cmd_deprecate;
+pub mod cmd_lock;
 pub mod cmd_undeprecate;
@@ -87,6 +87,16 @@ pub struct Solver {
 
     /// Name of the solver whose output to show  when multiple solvers are being run.
     pub solver_to_show: String,
+
+    /// What the solver optimizes for when more than one candidate
+    /// satisfies a request: newest (the default), lowest,
+    /// prefer-runtime, or prefer-local.
+    pub solve_strategy: String,
+
+    /// Directory for saving solve results, so that solving the same
+    /// requests again can reuse them while the repositories have not
+    /// changed. The cache is disabled when this is empty.
+    pub solve_cache_dir: String,
 }
 
 #[derive(Clone, Default, Debug, Deserialize, Serialize)]
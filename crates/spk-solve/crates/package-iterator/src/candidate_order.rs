@@ -0,0 +1,254 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use std::collections::{HashSet, VecDeque};
+use std::sync::Arc;
+
+use spfs::graph::DatabaseView;
+use spk_schema::foundation::name::PkgName;
+use spk_schema::foundation::version::Version;
+use spk_schema::ident::VersionIdent;
+use spk_schema::{BuildIdent, Package};
+use spk_solve_solution::PackageSource;
+use spk_storage::{Repository, RuntimeRepository};
+use tokio::sync::OnceCell;
+
+use crate::package_iterator::BuildWithRepos;
+use crate::{Error, Result};
+
+#[cfg(test)]
+#[path = "./candidate_order_test.rs"]
+mod candidate_order_test;
+
+pub const NEWEST_STRATEGY: &str = "newest";
+pub const LOWEST_STRATEGY: &str = "lowest";
+pub const PREFER_RUNTIME_STRATEGY: &str = "prefer-runtime";
+pub const PREFER_LOCAL_STRATEGY: &str = "prefer-local";
+
+/// What the solver optimizes for when choosing between the
+/// candidates that satisfy a request.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum SolveStrategy {
+    /// Try the highest versions first, and the builds of each version
+    /// in the order given by their build keys
+    #[default]
+    Newest,
+    /// Try the lowest versions first, which is useful for testing that
+    /// the lower bounds of requirements are still accurate
+    Lowest,
+    /// Try the versions and builds that are already installed in the
+    /// current runtime first, otherwise the same as newest
+    PreferRuntime,
+    /// Try the builds of each version that have the fewest layers
+    /// missing from the local repository first, otherwise the same
+    /// as newest
+    PreferLocal,
+}
+
+impl SolveStrategy {
+    /// The strategy that is set in the spk config, falling back
+    /// to the default strategy if it is not set or not valid.
+    pub fn from_config() -> Self {
+        let Ok(config) = spk_config::get_config() else {
+            return Self::default();
+        };
+        if config.solver.solve_strategy.is_empty() {
+            return Self::default();
+        }
+        match config.solver.solve_strategy.parse() {
+            Ok(strategy) => strategy,
+            Err(err) => {
+                tracing::warn!("{err}, using the {} strategy", Self::default());
+                Self::default()
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for SolveStrategy {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.to_lowercase().as_ref() {
+            NEWEST_STRATEGY => Ok(SolveStrategy::Newest),
+            LOWEST_STRATEGY => Ok(SolveStrategy::Lowest),
+            PREFER_RUNTIME_STRATEGY => Ok(SolveStrategy::PreferRuntime),
+            PREFER_LOCAL_STRATEGY => Ok(SolveStrategy::PreferLocal),
+            _ => Err(Error::String(format!(
+                "Unknown solve strategy '{value}', expected one of: {NEWEST_STRATEGY}, {LOWEST_STRATEGY}, {PREFER_RUNTIME_STRATEGY}, {PREFER_LOCAL_STRATEGY}"
+            ))),
+        }
+    }
+}
+
+impl std::fmt::Display for SolveStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            SolveStrategy::Newest => NEWEST_STRATEGY,
+            SolveStrategy::Lowest => LOWEST_STRATEGY,
+            SolveStrategy::PreferRuntime => PREFER_RUNTIME_STRATEGY,
+            SolveStrategy::PreferLocal => PREFER_LOCAL_STRATEGY,
+        };
+        f.write_str(name)
+    }
+}
+
+/// Orders the versions and builds of a package for the solver,
+/// based on a [`SolveStrategy`].
+///
+/// Anything that a strategy needs to know about the environment,
+/// such as the packages in the current runtime, is only loaded the
+/// first time that it is needed and is then shared by all the clones
+/// of the order.
+#[derive(Clone, Debug, Default)]
+pub struct CandidateOrder {
+    strategy: SolveStrategy,
+    runtime_builds: Arc<OnceCell<HashSet<BuildIdent>>>,
+    local_repo: Arc<OnceCell<Option<spfs::storage::RepositoryHandle>>>,
+}
+
+impl CandidateOrder {
+    pub fn new(strategy: SolveStrategy) -> Self {
+        Self {
+            strategy,
+            ..Default::default()
+        }
+    }
+
+    /// Create an order that treats the given builds as the ones
+    /// installed in the current runtime.
+    pub fn with_runtime_builds(strategy: SolveStrategy, builds: HashSet<BuildIdent>) -> Self {
+        Self {
+            strategy,
+            runtime_builds: Arc::new(OnceCell::new_with(Some(builds))),
+            ..Default::default()
+        }
+    }
+
+    pub fn strategy(&self) -> SolveStrategy {
+        self.strategy
+    }
+
+    /// Sort the versions of a package into the order that they should
+    /// be tried in
+    pub async fn sort_versions(&self, name: &PkgName, versions: &mut [Arc<Version>]) {
+        versions.sort();
+        match self.strategy {
+            SolveStrategy::Lowest => return,
+            SolveStrategy::Newest | SolveStrategy::PreferLocal => {}
+            SolveStrategy::PreferRuntime => {
+                let installed = self
+                    .runtime_builds()
+                    .await
+                    .iter()
+                    .filter(|build| build.name() == name)
+                    .map(|build| build.version().clone())
+                    .collect::<HashSet<_>>();
+                // Sorted (stable) so the installed versions come
+                // first, and the rest remain highest first
+                versions.reverse();
+                versions.sort_by_key(|version| !installed.contains(&**version));
+                return;
+            }
+        }
+        versions.reverse();
+    }
+
+    /// Sort builds that are already in build key order into the order
+    /// that they should be tried in. Source builds always stay last.
+    pub(crate) async fn sort_builds(&self, builds: &mut VecDeque<BuildWithRepos>) {
+        match self.strategy {
+            SolveStrategy::Newest | SolveStrategy::Lowest => {}
+            SolveStrategy::PreferRuntime => {
+                let installed = self.runtime_builds().await;
+                builds.make_contiguous().sort_by_cached_key(|hm| {
+                    let (spec, _) = hm.values().next().expect("non-empty hashmap");
+                    (spec.ident().is_source(), !installed.contains(spec.ident()))
+                });
+            }
+            SolveStrategy::PreferLocal => {
+                let Some(local) = self.local_repo().await else {
+                    return;
+                };
+                let mut missing = Vec::with_capacity(builds.len());
+                for hm in builds.iter() {
+                    missing.push(Self::count_missing_layers(local, hm).await);
+                }
+                let mut keyed = builds.drain(..).zip(missing).collect::<Vec<_>>();
+                keyed.sort_by_key(|(hm, missing)| {
+                    let (spec, _) = hm.values().next().expect("non-empty hashmap");
+                    (spec.ident().is_source(), *missing)
+                });
+                builds.extend(keyed.into_iter().map(|(hm, _)| hm));
+            }
+        }
+    }
+
+    /// Count the layers of a build's components that are not in the
+    /// local repository, using the repository that has the fewest
+    async fn count_missing_layers(
+        local: &spfs::storage::RepositoryHandle,
+        build: &BuildWithRepos,
+    ) -> usize {
+        let mut fewest = usize::MAX;
+        for (_, source) in build.values() {
+            let PackageSource::Repository { components, .. } = source else {
+                continue;
+            };
+            let mut missing = 0;
+            for digest in components.values() {
+                if !local.has_object(*digest).await {
+                    missing += 1;
+                }
+            }
+            fewest = fewest.min(missing);
+        }
+        fewest
+    }
+
+    async fn runtime_builds(&self) -> &HashSet<BuildIdent> {
+        self.runtime_builds
+            .get_or_init(|| async {
+                match Self::load_runtime_builds().await {
+                    Ok(builds) => builds,
+                    Err(err) => {
+                        tracing::debug!("No runtime packages to prefer: {err}");
+                        HashSet::new()
+                    }
+                }
+            })
+            .await
+    }
+
+    async fn load_runtime_builds() -> spk_storage::Result<HashSet<BuildIdent>> {
+        let repo = RuntimeRepository::default();
+        let mut builds = HashSet::new();
+        for name in repo.list_packages().await? {
+            for version in repo.list_package_versions(&name).await?.iter() {
+                let pkg = VersionIdent::new(name.clone(), (**version).clone());
+                builds.extend(repo.list_package_builds(&pkg).await?);
+            }
+        }
+        Ok(builds)
+    }
+
+    async fn local_repo(&self) -> Option<&spfs::storage::RepositoryHandle> {
+        self.local_repo
+            .get_or_init(|| async {
+                let repo = match spfs::get_config() {
+                    Ok(config) => config.get_local_repository_handle().await,
+                    Err(err) => Err(err),
+                };
+                match repo {
+                    Ok(repo) => Some(repo),
+                    Err(err) => {
+                        tracing::warn!("Cannot prefer local builds, failed to open the local repository: {err}");
+                        None
+                    }
+                }
+            })
+            .await
+            .as_ref()
+    }
+}
@@ -0,0 +1,95 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use rstest::rstest;
+use spk_schema::foundation::name::PkgName;
+use spk_schema::foundation::version::parse_version;
+use spk_schema::ident::build_ident;
+use spk_solve_macros::make_repo;
+
+use super::{CandidateOrder, SolveStrategy};
+use crate::{PackageIterator, RepositoryPackageIterator};
+
+fn versions(values: &[&str]) -> Vec<Arc<spk_schema::foundation::version::Version>> {
+    values
+        .iter()
+        .map(|v| Arc::new(parse_version(v).unwrap()))
+        .collect()
+}
+
+fn names(versions: &[Arc<spk_schema::foundation::version::Version>]) -> Vec<String> {
+    versions.iter().map(ToString::to_string).collect()
+}
+
+#[rstest]
+#[case(SolveStrategy::Newest)]
+#[case(SolveStrategy::Lowest)]
+#[case(SolveStrategy::PreferRuntime)]
+#[case(SolveStrategy::PreferLocal)]
+fn test_solve_strategy_round_trip(#[case] strategy: SolveStrategy) {
+    let parsed: SolveStrategy = strategy.to_string().parse().unwrap();
+    assert_eq!(parsed, strategy);
+}
+
+#[rstest]
+fn test_solve_strategy_unknown() {
+    assert!("fastest".parse::<SolveStrategy>().is_err());
+}
+
+#[rstest]
+#[case(SolveStrategy::Newest, &["2.0.0", "1.1.0", "1.0.0"])]
+#[case(SolveStrategy::Lowest, &["1.0.0", "1.1.0", "2.0.0"])]
+#[tokio::test]
+async fn test_candidate_order_sort_versions(
+    #[case] strategy: SolveStrategy,
+    #[case] expected: &[&str],
+) {
+    let order = CandidateOrder::new(strategy);
+    let mut values = versions(&["1.1.0", "2.0.0", "1.0.0"]);
+    order
+        .sort_versions(PkgName::new("my-pkg").unwrap(), &mut values)
+        .await;
+    assert_eq!(names(&values), expected);
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_candidate_order_prefers_runtime_versions() {
+    let order = CandidateOrder::with_runtime_builds(
+        SolveStrategy::PreferRuntime,
+        HashSet::from([
+            build_ident!("my-pkg/1.0.0/3I42H3S6"),
+            build_ident!("other/2.0.0/3I42H3S6"),
+        ]),
+    );
+    let mut values = versions(&["1.1.0", "2.0.0", "1.0.0"]);
+    order
+        .sort_versions(PkgName::new("my-pkg").unwrap(), &mut values)
+        .await;
+    assert_eq!(names(&values), vec!["1.0.0", "2.0.0", "1.1.0"]);
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_repository_package_iterator_lowest_versions() {
+    let repo = make_repo!([
+        {"pkg": "my-pkg/1.0.0"},
+        {"pkg": "my-pkg/3.0.0"},
+        {"pkg": "my-pkg/2.0.0"},
+    ]);
+    let mut iterator = RepositoryPackageIterator::new(
+        PkgName::new("my-pkg").unwrap().to_owned(),
+        vec![Arc::new(repo)],
+    )
+    .with_order(CandidateOrder::new(SolveStrategy::Lowest));
+
+    let mut found = Vec::new();
+    while let Some((pkg, _builds)) = iterator.next().await.unwrap() {
+        found.push(pkg.version().to_string());
+    }
+    assert_eq!(found, vec!["1.0.0", "2.0.0", "3.0.0"]);
+}
@@ -3,10 +3,19 @@
 // https://github.com/spkenv/spk
 
 mod build_key;
+mod candidate_order;
 mod error;
 mod package_iterator;
 mod promotion_patterns;
 
+pub use candidate_order::{
+    CandidateOrder,
+    SolveStrategy,
+    LOWEST_STRATEGY,
+    NEWEST_STRATEGY,
+    PREFER_LOCAL_STRATEGY,
+    PREFER_RUNTIME_STRATEGY,
+};
 pub use error::{Error, Result};
 pub use package_iterator::{
     BuildIterator,
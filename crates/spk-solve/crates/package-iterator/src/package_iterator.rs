@@ -19,7 +19,7 @@ use spk_solve_solution::PackageSource;
 use spk_storage::RepositoryHandle;
 
 use crate::build_key::BuildKey;
-use crate::{Error, PromotionPatterns, Result};
+use crate::{CandidateOrder, Error, PromotionPatterns, Result};
 
 #[cfg(test)]
 #[path = "./package_iterator_test.rs"]
@@ -44,7 +44,7 @@ static BUILD_KEY_NAME_ORDER: Lazy<PromotionPatterns> = Lazy::new(|| {
     )
 });
 
-type BuildWithRepos = HashMap<RepositoryNameBuf, (Arc<Spec>, PackageSource)>;
+pub(crate) type BuildWithRepos = HashMap<RepositoryNameBuf, (Arc<Spec>, PackageSource)>;
 
 #[async_trait::async_trait]
 pub trait BuildIterator: DynClone + Send + Sync + std::fmt::Debug {
@@ -114,6 +114,7 @@ pub struct RepositoryPackageIterator {
     builds_map: HashMap<Version, Arc<tokio::sync::Mutex<dyn BuildIterator + Send>>>,
     active_version: Option<Arc<Version>>,
     embedded_stubs: bool,
+    order: CandidateOrder,
 }
 
 #[async_trait::async_trait]
@@ -124,10 +125,13 @@ impl PackageIterator for RepositoryPackageIterator {
             match self.build_version_map().await {
                 Ok(version_map) => version_map,
                 Err(Error::SpkStorageError(spk_storage::Error::PackageNotFound(_))) => {
-                    return Box::new(RepositoryPackageIterator::new(
-                        self.package_name.clone(),
-                        self.repos.clone(),
-                    ))
+                    return Box::new(
+                        RepositoryPackageIterator::new(
+                            self.package_name.clone(),
+                            self.repos.clone(),
+                        )
+                        .with_order(self.order.clone()),
+                    )
                 }
                 Err(err) => {
                     // we wanted to save the clone from causing this
@@ -152,6 +156,7 @@ impl PackageIterator for RepositoryPackageIterator {
             builds_map: HashMap::default(),
             active_version: None,
             embedded_stubs: self.embedded_stubs,
+            order: self.order.clone(),
         })
     }
 
@@ -246,9 +251,16 @@ impl RepositoryPackageIterator {
             builds_map: HashMap::default(),
             active_version: None,
             embedded_stubs: false,
+            order: CandidateOrder::default(),
         }
     }
 
+    /// Use the given order for the versions and builds of the package
+    pub fn with_order(mut self, order: CandidateOrder) -> Self {
+        self.order = order;
+        self
+    }
+
     async fn build_version_map(&self) -> Result<RepositoryByNameByVersion> {
         let mut version_map: RepositoryByNameByVersion = HashMap::default();
         // Keep track of all the repos that possess this version so it is
@@ -289,8 +301,9 @@ impl RepositoryPackageIterator {
     /// The `version_map` must already be built.
     async fn restart_version_iterator(&mut self) -> Result<()> {
         let mut versions: Vec<Arc<Version>> = self.version_map.keys().cloned().collect();
-        versions.sort();
-        versions.reverse();
+        self.order
+            .sort_versions(&self.package_name, &mut versions)
+            .await;
         self.versions = Some(VersionIterator::new(versions.into()));
         Ok(())
     }
@@ -311,44 +324,48 @@ impl BuildIterator for RepositoryBuildIterator {
     }
 
     async fn next(&mut self) -> crate::Result<Option<BuildWithRepos>> {
-        let (build, repos) = if let Some(build) = self.builds.pop_front() {
-            build
-        } else {
-            return Ok(None);
-        };
+        'builds: while let Some((build, repos)) = self.builds.pop_front() {
+            let mut result = HashMap::new();
+
+            for (repo_name, repo) in repos.iter() {
+                let spec = match repo.read_package(&build).await {
+                    Ok(spec) => spec,
+                    Err(spk_storage::Error::PackageNotFound(_)) => {
+                        tracing::warn!(
+                            "Repository listed build with no spec: {build} from {repo:?}",
+                        );
+                        // Skip to next build
+                        continue 'builds;
+                    }
+                    Err(err) => return Err(err.into()),
+                };
 
-        let mut result = HashMap::new();
+                let components = match repo.read_components(&build).await {
+                    Ok(c) => c,
+                    Err(spk_storage::Error::PackageNotFound(_)) => Default::default(),
+                    Err(spk_storage::Error::UntrustedPackage { pkg, source }) => {
+                        tracing::warn!("Skipping untrusted build {pkg} from {repo:?}: {source}");
+                        // Skip to next build
+                        continue 'builds;
+                    }
+                    Err(err) => return Err(err.into()),
+                };
 
-        for (repo_name, repo) in repos.iter() {
-            let spec = match repo.read_package(&build).await {
-                Ok(spec) => spec,
-                Err(spk_storage::Error::PackageNotFound(_)) => {
-                    tracing::warn!("Repository listed build with no spec: {build} from {repo:?}",);
-                    // Skip to next build
-                    return self.next().await;
-                }
-                Err(err) => return Err(err.into()),
-            };
-
-            let components = match repo.read_components(&build).await {
-                Ok(c) => c,
-                Err(spk_storage::Error::PackageNotFound(_)) => Default::default(),
-                Err(err) => return Err(err.into()),
-            };
-
-            result.insert(
-                repo_name.clone(),
-                (
-                    spec,
-                    PackageSource::Repository {
-                        repo: Arc::clone(repo),
-                        components,
-                    },
-                ),
-            );
-        }
+                result.insert(
+                    repo_name.clone(),
+                    (
+                        spec,
+                        PackageSource::Repository {
+                            repo: Arc::clone(repo),
+                            components,
+                        },
+                    ),
+                );
+            }
 
-        Ok(Some(result))
+            return Ok(Some(result));
+        }
+        Ok(None)
     }
 
     fn len(&self) -> usize {
@@ -473,6 +490,7 @@ impl SortedBuildIterator {
         _options: OptionMap,
         source: Arc<tokio::sync::Mutex<dyn BuildIterator + Send>>,
         builds_with_impossible_requests: HashMap<BuildIdent, Compatibility>,
+        order: &CandidateOrder,
     ) -> Result<Self> {
         // Note: _options is unused in this implementation, it was used
         // in the by_distance sorting implementation
@@ -488,6 +506,8 @@ impl SortedBuildIterator {
 
         sbi.sort_by_build_option_values(builds_with_impossible_requests)
             .await;
+        // The strategy can then promote builds over the build key order
+        order.sort_builds(&mut sbi.builds).await;
         Ok(sbi)
     }
 
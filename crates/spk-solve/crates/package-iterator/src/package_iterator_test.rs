@@ -14,6 +14,7 @@ use spk_schema::{recipe, spec, BuildIdent, Package, Spec};
 use spk_solve_macros::{make_build, make_repo};
 
 use super::{BuildIterator, PackageIterator, RepositoryPackageIterator, SortedBuildIterator};
+use crate::CandidateOrder;
 
 #[rstest]
 #[tokio::test]
@@ -187,6 +188,7 @@ async fn test_solver_sorted_build_iterator_sort_by_option_values() {
             OptionMap::default(),
             builds,
             builds_with_impossible_requests.clone(),
+            &CandidateOrder::default(),
         )
         .await
         .unwrap();
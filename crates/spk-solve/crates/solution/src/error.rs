@@ -2,7 +2,12 @@
 // SPDX-License-Identifier: Apache-2.0
 // https://github.com/spkenv/spk
 
+use std::path::PathBuf;
+
 use miette::Diagnostic;
+use spk_schema::foundation::ident_component::Component;
+use spk_schema::name::RepositoryNameBuf;
+use spk_schema::BuildIdent;
 use thiserror::Error;
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -22,6 +27,24 @@ pub enum Error {
     EmbeddedHasNoComponentLayers,
     #[error("Spk internal test has no component layers")]
     SpkInternalTestHasNoComponentLayers,
+    #[error("Locked build {0} was not found in the {1} repository")]
+    #[diagnostic(help("the lockfile can be updated by running `spk lock` again"))]
+    LockedBuildNotFound(BuildIdent, RepositoryNameBuf),
+    #[error("Locked build {0} has been deprecated")]
+    #[diagnostic(help("the lockfile can be updated by running `spk lock` again"))]
+    LockedBuildDeprecated(BuildIdent),
+    #[error("Locked build {build} has a different layer for its {component} component than when it was locked")]
+    LockedLayerChanged {
+        build: BuildIdent,
+        component: Component,
+    },
+    #[error("Locked build {0} comes from the {1} repository, which is not enabled")]
+    #[diagnostic(help("enable the repository with --enable-repo {1}"))]
+    LockedRepositoryNotEnabled(BuildIdent, RepositoryNameBuf),
+    #[error("Error: Lockfile IO error: {1} - {0}")]
+    LockfileIOError(#[source] std::io::Error, PathBuf),
+    #[error("Error: Invalid lockfile: {1} - {0}")]
+    LockfileParseError(#[source] serde_yaml::Error, PathBuf),
     #[error("Error: {0}")]
     String(String),
 }
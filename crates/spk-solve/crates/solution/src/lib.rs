@@ -3,10 +3,12 @@
 // https://github.com/spkenv/spk
 
 mod error;
+mod lockfile;
 mod package_solve_data;
 mod solution;
 
 pub use error::{Error, Result};
+pub use lockfile::{LockedPackage, Lockfile, LOCKFILE_VERSION};
 pub use package_solve_data::{PackageSolveData, PackagesToSolveData, SPK_SOLVE_EXTRA_DATA_KEY};
 pub use solution::{
     find_highest_package_version,
@@ -0,0 +1,251 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::path::Path;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use spfs::Digest;
+use spk_schema::foundation::ident_component::Component;
+use spk_schema::foundation::option_map::OptionMap;
+use spk_schema::ident::{PkgRequest, PreReleasePolicy, RangeIdent, RequestedBy};
+use spk_schema::name::RepositoryNameBuf;
+use spk_schema::prelude::*;
+use spk_schema::BuildIdent;
+use spk_storage::RepositoryHandle;
+
+use crate::{Error, PackageSource, Result, Solution};
+
+#[cfg(test)]
+#[path = "./lockfile_test.rs"]
+mod lockfile_test;
+
+/// Current data structure version number for Lockfile
+pub const LOCKFILE_VERSION: u32 = 1;
+
+/// A resolved package build, as recorded in a lockfile
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LockedPackage {
+    /// The resolved package build
+    pub build: BuildIdent,
+    /// The components that were requested from the build
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub components: BTreeSet<Component>,
+    /// Name of the repository that the build was resolved from.
+    /// Optional because embedded packages do not have a source repo.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repository: Option<RepositoryNameBuf>,
+    /// The package that provided this one, if it was embedded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub embedded_in: Option<BuildIdent>,
+    /// The spfs layer of each component used from the build
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub layers: BTreeMap<Component, Digest>,
+    /// What the resolved package was requested by
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub requested_by: Vec<RequestedBy>,
+}
+
+/// A complete record of a solution, which can be used to restore the
+/// same environment later on without solving the requests again
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Lockfile {
+    /// For tracking data structure changes
+    #[serde(deserialize_with = "ensure_version")]
+    version: u32,
+    /// The options that the solution was resolved with
+    #[serde(default)]
+    options: OptionMap,
+    /// The resolved packages, in the order that they were resolved
+    #[serde(default)]
+    packages: Vec<LockedPackage>,
+}
+
+fn ensure_version<'de, D>(deserializer: D) -> std::result::Result<u32, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let version = u32::deserialize(deserializer)?;
+    if version != LOCKFILE_VERSION {
+        return Err(serde::de::Error::custom(format!("Lockfile version mismatch. Required version {LOCKFILE_VERSION} but data is version {version}")));
+    }
+    Ok(version)
+}
+
+impl Lockfile {
+    /// Record all of the resolved packages in a solution.
+    ///
+    /// Packages that need to be built from source cannot be locked.
+    pub fn from_solution(solution: &Solution) -> Result<Self> {
+        let mut packages = Vec::with_capacity(solution.len());
+        for resolved in solution.items() {
+            let (repository, embedded_in) = match &resolved.source {
+                PackageSource::Repository { repo, .. } => (Some(repo.name().to_owned()), None),
+                PackageSource::Embedded { parent } => (None, Some(parent.clone())),
+                PackageSource::BuildFromSource { .. } => {
+                    return Err(Error::String(format!(
+                        "Cannot lock {}, it needs to be built from source first",
+                        resolved.spec.ident()
+                    )));
+                }
+                PackageSource::SpkInternalTest => {
+                    return Err(Error::String(format!(
+                        "Cannot lock {}, it is an internal test package",
+                        resolved.spec.ident()
+                    )));
+                }
+            };
+            let layers = match resolved.component_layers() {
+                Ok(layers) => layers.into_iter().collect(),
+                Err(Error::EmbeddedHasNoComponentLayers) => BTreeMap::new(),
+                Err(err) => return Err(err),
+            };
+            packages.push(LockedPackage {
+                build: resolved.spec.ident().clone(),
+                components: resolved.request.pkg.components.clone(),
+                repository,
+                embedded_in,
+                layers,
+                requested_by: resolved.request.get_requesters(),
+            });
+        }
+        Ok(Self {
+            version: LOCKFILE_VERSION,
+            options: solution.options().clone(),
+            packages,
+        })
+    }
+
+    /// Read a lockfile from disk
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let reader = std::fs::File::open(path)
+            .map_err(|err| Error::LockfileIOError(err, path.to_owned()))?;
+        serde_yaml::from_reader(reader)
+            .map_err(|err| Error::LockfileParseError(err, path.to_owned()))
+    }
+
+    /// Write this lockfile to disk, replacing any existing file
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let data = serde_yaml::to_string(self)
+            .map_err(|err| Error::LockfileParseError(err, path.to_owned()))?;
+        std::fs::write(path, data).map_err(|err| Error::LockfileIOError(err, path.to_owned()))
+    }
+
+    /// The options that the locked solution was resolved with
+    pub fn options(&self) -> &OptionMap {
+        &self.options
+    }
+
+    /// The locked packages, in the order that they were resolved
+    pub fn packages(&self) -> &[LockedPackage] {
+        &self.packages
+    }
+
+    /// Restore the locked solution from the given repositories.
+    ///
+    /// Every locked build must still exist, unchanged and not deprecated,
+    /// in a repository with the same name as the one that it was
+    /// originally resolved from.
+    pub async fn to_solution(&self, repos: &[Arc<RepositoryHandle>]) -> Result<Solution> {
+        let mut specs = HashMap::with_capacity(self.packages.len());
+        let mut sources = HashMap::with_capacity(self.packages.len());
+        for locked in self.packages.iter() {
+            let Some(repo_name) = &locked.repository else {
+                continue;
+            };
+            let Some(repo) = repos.iter().find(|r| r.name() == repo_name) else {
+                return Err(Error::LockedRepositoryNotEnabled(
+                    locked.build.clone(),
+                    repo_name.clone(),
+                ));
+            };
+            let spec = match repo.read_package(&locked.build).await {
+                Ok(spec) => spec,
+                Err(err) if err.is_package_not_found() => {
+                    return Err(Error::LockedBuildNotFound(
+                        locked.build.clone(),
+                        repo_name.clone(),
+                    ))
+                }
+                Err(err) => return Err(err.into()),
+            };
+            if spec.is_deprecated() {
+                return Err(Error::LockedBuildDeprecated(locked.build.clone()));
+            }
+            let components = repo.read_components(&locked.build).await?;
+            for (component, digest) in locked.layers.iter() {
+                if components.get(component) != Some(digest) {
+                    return Err(Error::LockedLayerChanged {
+                        build: locked.build.clone(),
+                        component: component.clone(),
+                    });
+                }
+            }
+            specs.insert(locked.build.clone(), spec);
+            sources.insert(
+                locked.build.clone(),
+                PackageSource::Repository {
+                    repo: Arc::clone(repo),
+                    components,
+                },
+            );
+        }
+
+        let mut solution = Solution::new(self.options.clone());
+        for locked in self.packages.iter() {
+            let (spec, source) = match &locked.embedded_in {
+                None => match (specs.get(&locked.build), sources.remove(&locked.build)) {
+                    (Some(spec), Some(source)) => (Arc::clone(spec), source),
+                    _ => {
+                        return Err(Error::String(format!(
+                            "Locked build {} has no source repository or parent package",
+                            locked.build
+                        )))
+                    }
+                },
+                Some(parent) => {
+                    // embedded packages are provided by the spec of
+                    // their parent, which must also be in the lockfile
+                    let embedded = specs
+                        .get(parent)
+                        .and_then(|spec| {
+                            spec.embedded()
+                                .iter()
+                                .find(|embedded| embedded.ident() == &locked.build)
+                        })
+                        .ok_or_else(|| {
+                            Error::String(format!(
+                                "Locked build {} is no longer embedded in {parent}",
+                                locked.build
+                            ))
+                        })?;
+                    (
+                        Arc::new(embedded.clone()),
+                        PackageSource::Embedded {
+                            parent: parent.clone(),
+                        },
+                    )
+                }
+            };
+
+            let range_ident =
+                RangeIdent::equals(&locked.build.to_any(), locked.components.iter().cloned());
+            let mut requesters = locked.requested_by.iter().cloned();
+            let mut request = PkgRequest::new(
+                range_ident,
+                requesters.next().unwrap_or(RequestedBy::CurrentEnvironment),
+            );
+            for requester in requesters {
+                request.add_requester(requester);
+            }
+            request.prerelease_policy = Some(PreReleasePolicy::IncludeAll);
+
+            solution.add(request, spec, source);
+        }
+        Ok(solution)
+    }
+}
@@ -0,0 +1,130 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use rstest::rstest;
+use spfs::encoding::EMPTY_DIGEST;
+use spk_schema::foundation::ident_component::Component;
+use spk_schema::foundation::option_map;
+use spk_schema::ident::{PkgRequest, RequestedBy};
+use spk_schema::prelude::*;
+use spk_schema::{spec, Spec};
+use spk_storage::RepositoryHandle;
+
+use super::Lockfile;
+use crate::{Error, PackageSource, Solution};
+
+async fn publish(repo: &RepositoryHandle, spec: &Spec) -> HashMap<Component, spfs::Digest> {
+    let components = spec
+        .components()
+        .iter()
+        .map(|c| (c.name.clone(), EMPTY_DIGEST.into()))
+        .collect::<HashMap<_, _>>();
+    repo.publish_package(spec, &components).await.unwrap();
+    components
+}
+
+async fn make_solution(repo: &Arc<RepositoryHandle>) -> Solution {
+    let spec = Arc::new(spec!({
+        "pkg": "my-pkg/1.0.0/3I42H3S6",
+        "embedded": [{"pkg": "my-embedded/2.0.0"}],
+    }));
+    let components = publish(repo, &spec).await;
+    let mut solution = Solution::new(option_map! {"debug" => "off"});
+    let mut request = PkgRequest::from_ident(spec.ident().to_any(), RequestedBy::SpkInternalTest);
+    request.pkg.components.insert(Component::Run);
+    solution.add(
+        request,
+        Arc::clone(&spec),
+        PackageSource::Repository {
+            repo: Arc::clone(repo),
+            components,
+        },
+    );
+    for embedded in spec.embedded().iter() {
+        solution.add(
+            PkgRequest::from_ident(
+                embedded.ident().to_any(),
+                RequestedBy::Embedded(spec.ident().clone()),
+            ),
+            Arc::new(embedded.clone()),
+            PackageSource::Embedded {
+                parent: spec.ident().clone(),
+            },
+        );
+    }
+    solution
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_lockfile_round_trip() {
+    let repo = Arc::new(RepositoryHandle::new_mem());
+    let solution = make_solution(&repo).await;
+
+    let tmpdir = tempfile::tempdir().unwrap();
+    let path = tmpdir.path().join("spk.lock");
+    Lockfile::from_solution(&solution)
+        .unwrap()
+        .save(&path)
+        .unwrap();
+    let lockfile = Lockfile::load(&path).unwrap();
+    assert_eq!(lockfile.options(), solution.options());
+
+    let restored = lockfile.to_solution(&[Arc::clone(&repo)]).await.unwrap();
+    assert_eq!(restored.options(), solution.options());
+    assert_eq!(restored.len(), solution.len());
+    for (expected, actual) in solution.items().zip(restored.items()) {
+        assert_eq!(expected.spec.ident(), actual.spec.ident());
+        assert_eq!(expected.source, actual.source);
+        assert_eq!(expected.selected_components(), actual.selected_components());
+        assert_eq!(
+            expected.request.get_requesters(),
+            actual.request.get_requesters()
+        );
+    }
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_lockfile_missing_build() {
+    let repo = Arc::new(RepositoryHandle::new_mem());
+    let solution = make_solution(&repo).await;
+    let lockfile = Lockfile::from_solution(&solution).unwrap();
+
+    let other = Arc::new(RepositoryHandle::new_mem());
+    let res = lockfile.to_solution(&[other]).await;
+    assert!(
+        matches!(res, Err(Error::LockedRepositoryNotEnabled(..))),
+        "expected missing repository error, got {res:?}"
+    );
+
+    let build = solution.get("my-pkg").unwrap().spec.ident().clone();
+    repo.remove_package(&build).await.unwrap();
+    let res = lockfile.to_solution(&[repo]).await;
+    assert!(
+        matches!(res, Err(Error::LockedBuildNotFound(..))),
+        "expected missing build error, got {res:?}"
+    );
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_lockfile_deprecated_build() {
+    let repo = Arc::new(RepositoryHandle::new_mem());
+    let solution = make_solution(&repo).await;
+    let lockfile = Lockfile::from_solution(&solution).unwrap();
+
+    let mut deprecated = (*solution.get("my-pkg").unwrap().spec).clone();
+    deprecated.deprecate().unwrap();
+    repo.update_package(&deprecated).await.unwrap();
+
+    let res = lockfile.to_solution(&[repo]).await;
+    assert!(
+        matches!(res, Err(Error::LockedBuildDeprecated(..))),
+        "expected deprecated build error, got {res:?}"
+    );
+}
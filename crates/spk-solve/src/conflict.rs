@@ -0,0 +1,170 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use std::collections::HashSet;
+use std::fmt::Write;
+use std::sync::Arc;
+
+use miette::Diagnostic;
+use spk_schema::ident::{PkgRequest, RequestedBy};
+use spk_schema::AnyIdent;
+use thiserror::Error;
+
+#[cfg(test)]
+#[path = "./conflict_test.rs"]
+mod conflict_test;
+
+/// Why a package request could not be satisfied by any of the
+/// available packages.
+///
+/// Conflicts are produced by the conflict-driven solver. A candidate
+/// that was only rejected after trying to resolve its own
+/// dependencies refers to the conflict that it led to, so a conflict
+/// forms a tree that explains the failure from the original request
+/// down to the packages that could not be used together.
+#[derive(Clone, Debug)]
+pub struct Conflict {
+    /// The merged request that could not be satisfied
+    pub request: PkgRequest,
+    /// How the request came to be made. Each chain starts with one
+    /// of the requesters of the request and is followed by what
+    /// requested that package, and so on back to an initial request.
+    pub required_by: Vec<Vec<RequestedBy>>,
+    /// Why each of the candidates for the request was rejected
+    pub rejections: Vec<Rejection>,
+}
+
+/// A candidate that could not be used to satisfy a request
+#[derive(Clone, Debug)]
+pub struct Rejection {
+    /// The package version or build that was rejected
+    pub package: AnyIdent,
+    /// Why the package could not be used
+    pub reason: String,
+    /// The conflict that using this package was found to lead to,
+    /// if it was not rejected outright
+    pub cause: Option<Arc<Conflict>>,
+    /// The requirement of the package that could not be combined
+    /// with the requests already made, if that is why it was rejected
+    pub conflicting_requirement: Option<ConflictingRequirement>,
+}
+
+/// A requirement of a candidate package that conflicts with a
+/// request made earlier in the resolve
+#[derive(Clone, Debug)]
+pub struct ConflictingRequirement {
+    /// The requirement of the rejected package
+    pub requirement: PkgRequest,
+    /// The earlier request that the requirement conflicts with
+    pub conflicts_with: PkgRequest,
+}
+
+impl Rejection {
+    /// Return true if this rejection would be explained in the same
+    /// way as the other one, apart from the package rejected
+    fn same_explanation(&self, other: &Rejection) -> bool {
+        let clash = |r: &Rejection| {
+            r.conflicting_requirement
+                .as_ref()
+                .map(|c| (c.requirement.pkg.clone(), c.conflicts_with.pkg.clone()))
+        };
+        self.cause.is_none()
+            && other.cause.is_none()
+            && self.reason == other.reason
+            && clash(self) == clash(other)
+    }
+}
+
+impl Conflict {
+    /// A one line description of the request and what required it
+    pub fn summary(&self) -> String {
+        format!(
+            "could not satisfy '{}' as required by: {}",
+            self.request.pkg,
+            self.required_by
+                .iter()
+                .map(|chain| {
+                    chain
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect::<Vec<_>>()
+                        .join(" <- ")
+                })
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    }
+
+    /// The rejections of this conflict, with the candidates that were
+    /// rejected outright for the same reason listed together to keep
+    /// explanations short.
+    pub(crate) fn grouped_rejections(&self) -> Vec<(String, &Rejection)> {
+        let mut grouped: Vec<(Vec<&AnyIdent>, &Rejection)> = Vec::new();
+        for rejection in self.rejections.iter() {
+            if let Some((packages, _)) = grouped
+                .iter_mut()
+                .find(|(_, r)| r.same_explanation(rejection))
+            {
+                packages.push(&rejection.package);
+                continue;
+            }
+            grouped.push((vec![&rejection.package], rejection));
+        }
+        grouped
+            .into_iter()
+            .map(|(packages, rejection)| {
+                let packages = packages
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                (packages, rejection)
+            })
+            .collect()
+    }
+
+    fn format_tree(&self, out: &mut String, depth: usize, seen: &mut HashSet<*const Conflict>) {
+        let indent = "  ".repeat(depth);
+        let _ = write!(out, "{indent}{}", self.summary());
+        if self.rejections.is_empty() {
+            let _ = write!(
+                out,
+                "\n{indent}  no versions of {} were found in the enabled repositories",
+                self.request.pkg.name
+            );
+            return;
+        }
+
+        for (packages, rejection) in self.grouped_rejections() {
+            let _ = write!(out, "\n{indent}  {packages}: {}", rejection.reason);
+            if let Some(cause) = &rejection.cause {
+                if seen.insert(Arc::as_ptr(cause)) {
+                    out.push('\n');
+                    cause.format_tree(out, depth + 2, seen);
+                } else {
+                    let _ = write!(out, " ({}, see above)", cause.summary());
+                }
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Conflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut out = String::new();
+        self.format_tree(&mut out, 0, &mut HashSet::new());
+        f.write_str(&out)
+    }
+}
+
+/// The requests given to the solver cannot all be satisfied together
+#[derive(Diagnostic, Debug, Error)]
+#[error("there is no solution for these requests using the available packages:\n{conflict}")]
+#[diagnostic(
+    code("spk::solve::unsatisfiable"),
+    help("The conflict above follows from the initial requests alone, at least one of them needs to change")
+)]
+pub struct Unsatisfiable {
+    pub conflict: Arc<Conflict>,
+}
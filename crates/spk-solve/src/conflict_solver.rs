@@ -0,0 +1,1036 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::Arc;
+
+use spk_schema::foundation::ident_build::Build;
+use spk_schema::foundation::ident_component::Component;
+use spk_schema::foundation::name::{PkgName, PkgNameBuf};
+use spk_schema::foundation::version::Compatibility;
+use spk_schema::ident::{InclusionPolicy, PkgRequest, Request, RequestedBy};
+use spk_schema::ident_build::EmbeddedSource;
+use spk_schema::version::IncompatibleReason;
+use spk_schema::{AnyIdent, BuildIdent, Deprecate, Package, Spec};
+use spk_solve_graph::{Change, Decision, Graph, Node, Note, SkipPackageNote, State, StepBack};
+use spk_solve_package_iterator::{
+    BuildIterator,
+    CandidateOrder,
+    PackageIterator,
+    RepositoryPackageIterator,
+    SortedBuildIterator,
+};
+use spk_solve_solution::PackageSource;
+use spk_solve_validation::{ValidatorT, Validators};
+use spk_storage::RepositoryHandle;
+
+use crate::conflict::{Conflict, ConflictingRequirement, Rejection, Unsatisfiable};
+use crate::error::OutOfOptions;
+use crate::option_map::OptionMap;
+use crate::solver::{ErrorDetails, Solver};
+use crate::{Error, Result};
+
+#[cfg(test)]
+#[path = "./conflict_solver_test.rs"]
+mod conflict_solver_test;
+
+type SharedNode = Arc<tokio::sync::RwLock<Arc<Node>>>;
+
+/// The builds available for one version of a package, in the
+/// order that they should be tried
+type CandidateBuilds = Arc<Vec<(Arc<Spec>, PackageSource)>>;
+
+/// Either a decision to use a candidate, or why it was rejected and
+/// the levels responsible
+type Attempt = std::result::Result<Decision, (Rejection, BTreeSet<usize>)>;
+
+/// A search through the same graph of states as the [`Solver`], but
+/// which records why each candidate was rejected so that it can jump
+/// straight back to the decisions responsible for a conflict.
+///
+/// Each package decision opens a new level in the search. When every
+/// candidate for a level has been rejected, the levels that caused
+/// those rejections are combined into a learned incompatibility and
+/// the search backjumps to the most recent of them, skipping over
+/// any decisions that had nothing to do with the conflict. If none
+/// of the causes are package decisions then the initial requests
+/// cannot be satisfied and the conflict explains why.
+pub(crate) struct ConflictSearch {
+    graph: Arc<tokio::sync::RwLock<Graph>>,
+    repos: Vec<Arc<RepositoryHandle>>,
+    order: CandidateOrder,
+    initial_decision: Option<Arc<Decision>>,
+    current_node: Option<SharedNode>,
+    /// The requests made by the initial decision, which are treated
+    /// as level 0 in the search
+    initial_requests: Vec<PkgRequest>,
+    /// The open levels of the search, level n is stored at index n-1
+    levels: Vec<Level>,
+    /// Combinations of builds that are known to lead to a conflict
+    nogoods: Vec<Nogood>,
+    /// The versions and builds loaded so far for each package name,
+    /// shared by every level that requests the package
+    candidates: HashMap<PkgNameBuf, Candidates>,
+    finished: bool,
+}
+
+/// One package decision in the search
+struct Level {
+    request: PkgRequest,
+    /// The node that the decision is made from
+    node: SharedNode,
+    state: Arc<State>,
+    version_index: usize,
+    build_index: usize,
+    /// The builds of the current version, when their order depends
+    /// on the state of this level rather than being shared
+    sorted: Option<(usize, CandidateBuilds)>,
+    /// The lower levels that were responsible for rejecting
+    /// candidates at this level
+    causes: BTreeSet<usize>,
+    rejections: Vec<Rejection>,
+    decision: Option<LevelDecision>,
+}
+
+/// What was decided at a level, if anything
+struct LevelDecision {
+    build: BuildIdent,
+    components: BTreeSet<Component>,
+    /// The packages that the decision added to the resolve,
+    /// including any embedded packages
+    packages: Vec<PkgNameBuf>,
+    /// The package requests that the decision introduced
+    requests: Vec<PkgRequest>,
+}
+
+/// A set of builds that cannot all appear in the same solution
+struct Nogood {
+    builds: BTreeMap<PkgNameBuf, (BuildIdent, BTreeSet<Component>)>,
+    conflict: Arc<Conflict>,
+}
+
+struct Candidates {
+    iterator: Box<dyn PackageIterator + Send>,
+    versions: Vec<CandidateVersion>,
+    exhausted: bool,
+}
+
+struct CandidateVersion {
+    pkg: AnyIdent,
+    builds: Arc<tokio::sync::Mutex<dyn BuildIterator + Send>>,
+    /// The builds in their default order, once they have been read
+    sorted: Option<(SortedBuildIterator, CandidateBuilds)>,
+}
+
+enum Step {
+    Forward(Decision),
+    Back {
+        decision: Decision,
+        node: SharedNode,
+    },
+}
+
+impl Level {
+    fn new(request: PkgRequest, node: SharedNode, state: Arc<State>) -> Self {
+        Self {
+            request,
+            node,
+            state,
+            version_index: 0,
+            build_index: 0,
+            sorted: None,
+            causes: BTreeSet::new(),
+            rejections: Vec::new(),
+            decision: None,
+        }
+    }
+}
+
+impl ConflictSearch {
+    pub fn new(
+        graph: Arc<tokio::sync::RwLock<Graph>>,
+        initial_decision: Arc<Decision>,
+        repos: Vec<Arc<RepositoryHandle>>,
+        order: CandidateOrder,
+    ) -> Self {
+        Self {
+            graph,
+            repos,
+            order,
+            initial_decision: Some(initial_decision),
+            current_node: None,
+            initial_requests: Vec::new(),
+            levels: Vec::new(),
+            nogoods: Vec::new(),
+            candidates: HashMap::new(),
+            finished: false,
+        }
+    }
+
+    /// The node that the search is currently at, if it has started
+    pub fn current_node(&self) -> Option<SharedNode> {
+        self.current_node.clone()
+    }
+
+    /// Take the next step in the search.
+    ///
+    /// Returns the node that the step was taken from and the decision
+    /// that was applied to it, or None once a solution has been found.
+    pub async fn step(
+        &mut self,
+        solver: &mut Solver,
+    ) -> Result<Option<(Arc<Node>, Arc<Decision>)>> {
+        if self.finished {
+            return Ok(None);
+        }
+        if let Some(decision) = self.initial_decision.take() {
+            let root = self.graph.read().await.root.clone();
+            let before = root.read().await.clone();
+            let node = self
+                .graph
+                .write()
+                .await
+                .add_branch(before.id(), Arc::clone(&decision))
+                .await?;
+            self.initial_requests = decision
+                .changes
+                .iter()
+                .filter_map(|change| match change {
+                    Change::RequestPackage(rp) => Some(rp.request.clone()),
+                    _ => None,
+                })
+                .collect();
+            self.current_node = Some(node);
+            return Ok(Some((before, decision)));
+        }
+        let Some(node) = self.current_node.clone() else {
+            return Ok(None);
+        };
+        let before = node.read().await.clone();
+
+        loop {
+            match self.next_step(solver, &node, &before.state).await? {
+                None => {
+                    self.finished = true;
+                    return Ok(None);
+                }
+                Some(Step::Back { decision, node }) => {
+                    self.current_node = Some(node);
+                    return Ok(Some((before, Arc::new(decision))));
+                }
+                Some(Step::Forward(decision)) => {
+                    let decision = Arc::new(decision);
+                    let added = self
+                        .graph
+                        .write()
+                        .await
+                        .add_branch(before.id(), Arc::clone(&decision))
+                        .await;
+                    match added {
+                        Ok(node) => {
+                            solver.count_step();
+                            self.current_node = Some(node);
+                            return Ok(Some((before, decision)));
+                        }
+                        Err(err) => {
+                            // This exact branch has been visited before, which
+                            // depends on the whole path taken to get here.
+                            let causes = self.lower_levels();
+                            let level = self.open_level_mut()?;
+                            if let Some(made) = level.decision.take() {
+                                level.rejections.push(Rejection {
+                                    package: made.build.to_any(),
+                                    reason: err.to_string(),
+                                    cause: None,
+                                    conflicting_requirement: None,
+                                });
+                            }
+                            level.causes.extend(causes);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    async fn next_step(
+        &mut self,
+        solver: &mut Solver,
+        node: &SharedNode,
+        state: &Arc<State>,
+    ) -> Result<Option<Step>> {
+        let open = self
+            .levels
+            .last()
+            .map(|level| level.decision.is_none())
+            .unwrap_or(false);
+        if !open {
+            let Some(request) = state.get_next_request()? else {
+                return self.verify_embedded_providers(solver, state);
+            };
+            self.levels
+                .push(Level::new(request, Arc::clone(node), Arc::clone(state)));
+        }
+
+        match self.next_candidate(solver).await? {
+            Some(decision) => Ok(Some(Step::Forward(decision))),
+            None => self.backjump(solver).map(Some),
+        }
+    }
+
+    /// Check that every embedded package in a finished resolve also
+    /// has the package that provides it in the resolve.
+    fn verify_embedded_providers(
+        &mut self,
+        solver: &mut Solver,
+        state: &Arc<State>,
+    ) -> Result<Option<Step>> {
+        for (spec, _, _) in state.get_resolved_packages().values() {
+            let Build::Embedded(EmbeddedSource::Package(package)) = spec.ident().build() else {
+                continue;
+            };
+            let provider: BuildIdent = (&package.ident).try_into()?;
+            let provided = state
+                .get_current_resolve(provider.name())
+                .map(|(resolved, _, _)| resolved.ident() == &provider)
+                .unwrap_or(false);
+            if provided {
+                continue;
+            }
+
+            let message = format!(
+                "Embedded package {} missing its provider {provider}",
+                spec.ident()
+            );
+            let Some(level) = self.level_of(spec.ident().name()) else {
+                return Err(Error::OutOfOptions(OutOfOptions {
+                    request: PkgRequest::new(
+                        provider.clone().into(),
+                        RequestedBy::PackageBuild(spec.ident().clone()),
+                    ),
+                    notes: vec![Note::Other(message)],
+                }));
+            };
+            let rejection = Rejection {
+                package: spec.ident().to_any(),
+                reason: format!("its provider {provider} is not part of the resolve"),
+                cause: None,
+                conflicting_requirement: None,
+            };
+            let causes = (1..level).collect();
+            return Ok(Some(
+                self.retract(solver, level, rejection, causes, message)?,
+            ));
+        }
+        Ok(None)
+    }
+
+    /// Find the next usable candidate for the open level, recording
+    /// why each candidate before it was rejected.
+    async fn next_candidate(&mut self, solver: &mut Solver) -> Result<Option<Decision>> {
+        let mut notes = Vec::new();
+        loop {
+            let index = self.levels.len() - 1;
+            let name = self.levels[index].request.pkg.name.clone();
+            let version_index = self.levels[index].version_index;
+            let build_index = self.levels[index].build_index;
+
+            let Some(pkg) = self.version(solver, &name, version_index).await? else {
+                return Ok(None);
+            };
+
+            if build_index == 0 {
+                let compat = self.levels[index]
+                    .request
+                    .is_version_applicable(pkg.version());
+                if !&compat {
+                    let builds = self.build_count(&name, version_index).await;
+                    solver.count_incompatible_version(builds);
+                    let causes = self.blame_requests(&name, |request| {
+                        !request.is_version_applicable(pkg.version()).is_ok()
+                    });
+                    notes.push(Note::SkipPackageNote(SkipPackageNote::new(
+                        pkg.clone(),
+                        compat.clone(),
+                    )));
+                    self.reject(
+                        Rejection {
+                            package: pkg,
+                            reason: compat.to_string(),
+                            cause: None,
+                            conflicting_requirement: None,
+                        },
+                        causes,
+                    )?;
+                    self.levels[index].version_index += 1;
+                    continue;
+                }
+            }
+
+            let builds = self.builds(solver, index, &name, version_index).await?;
+            let Some((spec, source)) = builds.get(build_index).cloned() else {
+                let level = &mut self.levels[index];
+                level.version_index += 1;
+                level.build_index = 0;
+                continue;
+            };
+            self.levels[index].build_index += 1;
+            solver.count_build();
+
+            match self.try_build(solver, &spec, source).await? {
+                Ok(mut decision) => {
+                    decision.add_notes(notes);
+                    self.record_decision(&decision)?;
+                    return Ok(Some(decision));
+                }
+                Err((rejection, causes)) => {
+                    solver.count_skipped_build();
+                    notes.push(Note::SkipPackageNote(SkipPackageNote::new_from_message(
+                        rejection.package.clone(),
+                        &rejection.reason,
+                    )));
+                    self.reject(rejection, causes)?;
+                }
+            }
+        }
+    }
+
+    /// Try to use the given build to satisfy the request of the open
+    /// level, returning the decision to use it or why it was rejected
+    /// along with the levels responsible.
+    async fn try_build(
+        &self,
+        solver: &mut Solver,
+        spec: &Arc<Spec>,
+        source: PackageSource,
+    ) -> Result<Attempt> {
+        let level = self.open_level()?;
+        let state = Arc::clone(&level.state);
+        let request = &level.request;
+        let rejected = |reason: String, causes: BTreeSet<usize>| -> Result<Attempt> {
+            Ok(Err((
+                Rejection {
+                    package: spec.ident().to_any(),
+                    reason,
+                    cause: None,
+                    conflicting_requirement: None,
+                },
+                causes,
+            )))
+        };
+
+        if let Some((causes, conflict)) = self.check_nogoods(spec.ident(), &request.pkg.components)
+        {
+            return Ok(Err((
+                Rejection {
+                    package: spec.ident().to_any(),
+                    reason: "already known to conflict with the packages resolved so far".into(),
+                    cause: Some(conflict),
+                    conflicting_requirement: None,
+                },
+                causes,
+            )));
+        }
+
+        let build_from_source =
+            spec.ident().is_source() && request.pkg.build != Some(Build::Source);
+        if !build_from_source {
+            for validator in solver.validators().iter() {
+                let compat = validator.validate_package(&state, spec, &source)?;
+                if !&compat {
+                    let causes = self.blame_validator(validator, &compat, spec);
+                    let conflicting_requirement = match validator {
+                        Validators::PkgRequirements(_) => self.conflicting_requirement(spec),
+                        _ => None,
+                    };
+                    return Ok(Err((
+                        Rejection {
+                            package: spec.ident().to_any(),
+                            reason: compat.to_string(),
+                            cause: None,
+                            conflicting_requirement,
+                        },
+                        causes,
+                    )));
+                }
+            }
+            return Ok(Ok(Decision::builder(&state)
+                .with_components(&request.pkg.components)
+                .resolve_package(spec, source)));
+        }
+
+        if let PackageSource::Embedded { .. } = source {
+            return rejected(
+                "embedded packages cannot be built from source".into(),
+                BTreeSet::new(),
+            );
+        }
+        let recipe = match source.read_recipe(spec.ident().base()).await {
+            Ok(r) if r.is_deprecated() => {
+                return rejected(
+                    "cannot build from source, version is deprecated".into(),
+                    BTreeSet::new(),
+                )
+            }
+            Ok(r) => r,
+            Err(spk_solve_solution::Error::SpkStorageError(
+                spk_storage::Error::PackageNotFound(_),
+            )) => {
+                return rejected(
+                    "cannot build from source, recipe not available".into(),
+                    BTreeSet::new(),
+                )
+            }
+            Err(err) => return Err(err.into()),
+        };
+
+        // building from source depends on the options and requests
+        // of the whole resolve so far
+        let causes = self.lower_levels();
+        let compat = solver.validate_recipe(&state, &recipe)?;
+        if !&compat {
+            return rejected(
+                format!("building from source is not possible with this recipe: {compat}"),
+                causes,
+            );
+        }
+        let new_spec = match solver.resolve_new_build(&recipe, &state).await {
+            Ok(new_spec) => new_spec,
+            Err(err) => {
+                return rejected(
+                    format!("cannot resolve build env for source build: {err}"),
+                    causes,
+                )
+            }
+        };
+        let new_source = PackageSource::BuildFromSource {
+            recipe: Arc::clone(&recipe),
+        };
+        let compat = solver.validate_package(&state, &new_spec, &new_source)?;
+        if !&compat {
+            return rejected(
+                format!("building from source not possible: {compat}"),
+                causes,
+            );
+        }
+        match Decision::builder(&state)
+            .with_components(&request.pkg.components)
+            .build_package(&recipe, &new_spec)
+        {
+            Ok(decision) => Ok(Ok(decision)),
+            Err(err) => rejected(format!("cannot build package from source: {err}"), causes),
+        }
+    }
+
+    /// Give up on the open level, learning from the conflict and
+    /// jumping back to the most recent level that caused it.
+    fn backjump(&mut self, solver: &mut Solver) -> Result<Step> {
+        let depth = self.levels.len();
+        let level = self.open_level()?;
+        let conflict = Arc::new(Conflict {
+            request: level.request.clone(),
+            required_by: self.requester_chains(&level.request),
+            rejections: level.rejections.clone(),
+        });
+        let mut causes = level.causes.clone();
+        match self.existence_level(&level.request.pkg.name) {
+            Some(existence) => {
+                causes.insert(existence);
+            }
+            None => causes.extend(1..depth),
+        }
+        causes.remove(&0);
+
+        let requested_by = level.request.get_requesters();
+        for requester in requested_by.iter() {
+            if let RequestedBy::PackageBuild(problem_package) = requester {
+                solver.increment_problem_package_count(problem_package.name().to_string());
+            }
+        }
+        solver.increment_error_count(ErrorDetails::CouldNotSatisfy(
+            level.request.pkg.to_string(),
+            requested_by,
+        ));
+
+        let Some(&target) = causes.last() else {
+            return Err(Unsatisfiable { conflict }.into());
+        };
+
+        let builds = causes
+            .iter()
+            .filter_map(|cause| self.levels[cause - 1].decision.as_ref())
+            .map(|made| {
+                (
+                    made.build.name().to_owned(),
+                    (made.build.clone(), made.components.clone()),
+                )
+            })
+            .collect();
+        self.nogoods.push(Nogood {
+            builds,
+            conflict: Arc::clone(&conflict),
+        });
+
+        let package = self.levels[target - 1]
+            .decision
+            .as_ref()
+            .map(|made| made.build.to_any())
+            .ok_or_else(|| {
+                Error::String("backjump target has no decision [INTERNAL ERROR]".into())
+            })?;
+        let rejection = Rejection {
+            package,
+            reason: "leads to a conflict".into(),
+            cause: Some(Arc::clone(&conflict)),
+            conflicting_requirement: None,
+        };
+        self.retract(solver, target, rejection, causes, conflict.summary())
+    }
+
+    /// Undo the decision at the target level and everything above it,
+    /// returning to the state that the target decision was made from.
+    fn retract(
+        &mut self,
+        solver: &mut Solver,
+        target: usize,
+        rejection: Rejection,
+        causes: BTreeSet<usize>,
+        message: String,
+    ) -> Result<Step> {
+        self.levels.truncate(target);
+        let level = self.open_level_mut()?;
+        level.decision = None;
+        level.rejections.push(rejection);
+        level.causes.extend(
+            causes
+                .into_iter()
+                .filter(|cause| *cause > 0 && *cause < target),
+        );
+        solver.count_skipped_build();
+        let decision = Change::StepBack(StepBack::new(
+            message,
+            &level.state,
+            solver.steps_back_counter(),
+        ))
+        .as_decision();
+        Ok(Step::Back {
+            decision,
+            node: Arc::clone(&level.node),
+        })
+    }
+
+    /// Record a rejected candidate, and the levels that caused it,
+    /// against the open level.
+    fn reject(&mut self, rejection: Rejection, causes: BTreeSet<usize>) -> Result<()> {
+        let depth = self.levels.len();
+        let level = self.open_level_mut()?;
+        level.causes.extend(
+            causes
+                .into_iter()
+                .filter(|cause| *cause > 0 && *cause < depth),
+        );
+        level.rejections.push(rejection);
+        Ok(())
+    }
+
+    fn record_decision(&mut self, decision: &Decision) -> Result<()> {
+        let mut build = None;
+        let mut packages = Vec::new();
+        let mut requests = Vec::new();
+        for change in decision.changes.iter() {
+            let spec = match change {
+                Change::SetPackage(sp) => &sp.spec,
+                Change::SetPackageBuild(spb) => &spb.spec,
+                Change::RequestPackage(rp) => {
+                    requests.push(rp.request.clone());
+                    continue;
+                }
+                _ => continue,
+            };
+            build.get_or_insert_with(|| spec.ident().clone());
+            packages.push(spec.ident().name().to_owned());
+        }
+        let level = self.open_level_mut()?;
+        if let Some(build) = build {
+            level.decision = Some(LevelDecision {
+                build,
+                components: level.request.pkg.components.clone(),
+                packages,
+                requests,
+            });
+        }
+        Ok(())
+    }
+
+    /// Find the levels responsible for a validator rejecting a build
+    fn blame_validator(
+        &self,
+        validator: &Validators,
+        compat: &Compatibility,
+        spec: &Spec,
+    ) -> BTreeSet<usize> {
+        if let Compatibility::Incompatible(IncompatibleReason::ConflictingEmbeddedPackage(name)) =
+            compat
+        {
+            return self.level_of(name).into_iter().collect();
+        }
+        let name = spec.ident().name();
+        match validator {
+            Validators::BinaryOnly(_) => BTreeSet::new(),
+            Validators::PackageRequest(_) => self.blame_requests(name, |request| {
+                !request
+                    .is_version_applicable(spec.ident().version())
+                    .is_ok()
+                    || !request.is_satisfied_by(spec).is_ok()
+            }),
+            Validators::Components(_) | Validators::Deprecation(_) => {
+                self.blame_requests(name, |_| false)
+            }
+            Validators::PkgRequirements(_) => self.blame_requirements(spec),
+            _ => self.lower_levels(),
+        }
+    }
+
+    /// Find the levels responsible for the requests of a package
+    /// rejecting a candidate.
+    ///
+    /// If one of the requests rejects the candidate by itself then
+    /// only the earliest such request is blamed, otherwise all of
+    /// them are.
+    fn blame_requests<F>(&self, name: &PkgName, rejects: F) -> BTreeSet<usize>
+    where
+        F: Fn(&PkgRequest) -> bool,
+    {
+        let tracked = self.tracked_requests(name);
+        match tracked
+            .iter()
+            .filter(|(_, request)| rejects(request))
+            .map(|(level, _)| *level)
+            .min()
+        {
+            Some(level) => BTreeSet::from([level]),
+            None => tracked.iter().map(|(level, _)| *level).collect(),
+        }
+    }
+
+    /// Find the levels responsible for the requirements of a build
+    /// conflicting with the resolve so far
+    fn blame_requirements(&self, spec: &Spec) -> BTreeSet<usize> {
+        let mut causes = BTreeSet::new();
+        for requirement in spec.runtime_requirements().iter() {
+            let Request::Pkg(requirement) = requirement else {
+                continue;
+            };
+            let name = &requirement.pkg.name;
+            let tracked = self.tracked_requests(name);
+            let conflicting = tracked
+                .iter()
+                .filter(|(_, request)| (*request).clone().restrict(requirement).is_err())
+                .map(|(level, _)| *level)
+                .min();
+            if let Some(level) = conflicting {
+                return BTreeSet::from([level]);
+            }
+            match self.level_of(name) {
+                Some(level) => {
+                    causes.insert(level);
+                }
+                None => causes.extend(tracked.iter().map(|(level, _)| *level)),
+            }
+        }
+        if causes.is_empty() {
+            self.lower_levels()
+        } else {
+            causes
+        }
+    }
+
+    /// Find the requirement of a build that cannot be combined with
+    /// one of the requests made so far, if any
+    fn conflicting_requirement(&self, spec: &Spec) -> Option<ConflictingRequirement> {
+        for requirement in spec.runtime_requirements().iter() {
+            let Request::Pkg(requirement) = requirement else {
+                continue;
+            };
+            let conflicts_with = self
+                .tracked_requests(&requirement.pkg.name)
+                .into_iter()
+                .map(|(_, request)| request)
+                .find(|request| (*request).clone().restrict(requirement).is_err());
+            if let Some(conflicts_with) = conflicts_with {
+                let mut requirement = requirement.clone();
+                if requirement.requested_by.is_empty() {
+                    requirement.add_requester(RequestedBy::PackageBuild(spec.ident().clone()));
+                }
+                return Some(ConflictingRequirement {
+                    requirement,
+                    conflicts_with: conflicts_with.clone(),
+                });
+            }
+        }
+        None
+    }
+
+    /// Check the learned incompatibilities for one that would be
+    /// completed by the given build, returning the levels that
+    /// decided the rest of it.
+    fn check_nogoods(
+        &self,
+        build: &BuildIdent,
+        components: &BTreeSet<Component>,
+    ) -> Option<(BTreeSet<usize>, Arc<Conflict>)> {
+        'nogoods: for nogood in self.nogoods.iter() {
+            match nogood.builds.get(build.name()) {
+                Some((b, c)) if b == build && c == components => {}
+                _ => continue,
+            }
+            let mut causes = BTreeSet::new();
+            for (name, (other_build, other_components)) in nogood.builds.iter() {
+                if &**name == build.name() {
+                    continue;
+                }
+                let Some(index) = self.levels.iter().position(|level| {
+                    level
+                        .decision
+                        .as_ref()
+                        .map(|made| {
+                            &made.build == other_build && &made.components == other_components
+                        })
+                        .unwrap_or(false)
+                }) else {
+                    continue 'nogoods;
+                };
+                causes.insert(index + 1);
+            }
+            return Some((causes, Arc::clone(&nogood.conflict)));
+        }
+        None
+    }
+
+    /// The individual package requests made so far for a package,
+    /// along with the level that made each of them
+    fn tracked_requests(&self, name: &PkgName) -> Vec<(usize, &PkgRequest)> {
+        let initial = self.initial_requests.iter().map(|request| (0, request));
+        let decided = self.levels.iter().enumerate().flat_map(|(index, level)| {
+            level
+                .decision
+                .iter()
+                .flat_map(|made| made.requests.iter())
+                .map(move |request| (index + 1, request))
+        });
+        initial
+            .chain(decided)
+            .filter(|(_, request)| &*request.pkg.name == name)
+            .collect()
+    }
+
+    /// The earliest level that requires the package to be resolved
+    fn existence_level(&self, name: &PkgName) -> Option<usize> {
+        self.tracked_requests(name)
+            .into_iter()
+            .filter(|(_, request)| request.inclusion_policy == InclusionPolicy::Always)
+            .map(|(level, _)| level)
+            .min()
+    }
+
+    /// The level that added the package to the resolve, if any
+    fn level_of(&self, name: &PkgName) -> Option<usize> {
+        self.levels
+            .iter()
+            .position(|level| {
+                level
+                    .decision
+                    .as_ref()
+                    .map(|made| made.packages.iter().any(|p| &**p == name))
+                    .unwrap_or(false)
+            })
+            .map(|index| index + 1)
+    }
+
+    /// The innermost level of the search, which is being decided
+    fn open_level(&self) -> Result<&Level> {
+        self.levels.last().ok_or_else(|| {
+            Error::String("conflict search has no open level [INTERNAL ERROR]".into())
+        })
+    }
+
+    /// The innermost level of the search, which is being decided
+    fn open_level_mut(&mut self) -> Result<&mut Level> {
+        self.levels.last_mut().ok_or_else(|| {
+            Error::String("conflict search has no open level [INTERNAL ERROR]".into())
+        })
+    }
+
+    /// All of the levels below the open one
+    fn lower_levels(&self) -> BTreeSet<usize> {
+        (1..self.levels.len()).collect()
+    }
+
+    /// Follow each requester of the request back through the packages
+    /// that requested them, to explain how the request came to be made.
+    fn requester_chains(&self, request: &PkgRequest) -> Vec<Vec<RequestedBy>> {
+        request
+            .get_requesters()
+            .into_iter()
+            .map(|requester| {
+                let mut chain = vec![requester];
+                while chain.len() <= self.levels.len() {
+                    let parent = match chain.last() {
+                        Some(RequestedBy::PackageBuild(ident))
+                        | Some(RequestedBy::Embedded(ident)) => ident.name(),
+                        _ => break,
+                    };
+                    let Some(level) = self.level_of(parent) else {
+                        break;
+                    };
+                    match self.levels[level - 1]
+                        .request
+                        .get_requesters()
+                        .into_iter()
+                        .next()
+                    {
+                        Some(next) => chain.push(next),
+                        None => break,
+                    }
+                }
+                chain
+            })
+            .collect()
+    }
+
+    /// Load the version of a package at the given index, in the order
+    /// that the package iterator provides them.
+    async fn version(
+        &mut self,
+        solver: &Solver,
+        name: &PkgName,
+        index: usize,
+    ) -> Result<Option<AnyIdent>> {
+        let repos = &self.repos;
+        let order = &self.order;
+        let candidates = self.candidates.entry(name.to_owned()).or_insert_with(|| {
+            solver.record_searched_package(name);
+            Candidates {
+                iterator: Box::new(
+                    RepositoryPackageIterator::new(name.to_owned(), repos.clone())
+                        .with_order(order.clone()),
+                ),
+                versions: Vec::new(),
+                exhausted: false,
+            }
+        });
+        while candidates.versions.len() <= index && !candidates.exhausted {
+            match candidates.iterator.next().await {
+                Ok(Some((pkg, builds))) => candidates.versions.push(CandidateVersion {
+                    pkg,
+                    builds,
+                    sorted: None,
+                }),
+                Ok(None) => candidates.exhausted = true,
+                Err(spk_solve_package_iterator::Error::SpkStorageError(
+                    spk_storage::Error::PackageNotFound(_),
+                )) => {
+                    // A package with no versions at all is treated
+                    // like any other package that has run out of
+                    // candidates so that the conflict explains it.
+                    candidates.exhausted = true
+                }
+                Err(err) => return Err(err.into()),
+            }
+        }
+        Ok(candidates
+            .versions
+            .get(index)
+            .map(|version| version.pkg.clone()))
+    }
+
+    async fn build_count(&self, name: &PkgName, index: usize) -> usize {
+        match self
+            .candidates
+            .get(name)
+            .and_then(|candidates| candidates.versions.get(index))
+        {
+            Some(CandidateVersion {
+                sorted: Some((_, sorted)),
+                ..
+            }) => sorted.len(),
+            Some(version) => version.builds.lock().await.len(),
+            None => 0,
+        }
+    }
+
+    /// Load the sorted builds for an already loaded package version
+    /// that is being tried at the given level.
+    ///
+    /// The builds are sorted once and shared by every level, unless
+    /// impossible request checks are used in the build keys. Those
+    /// depend on the unresolved requests of the level, so the builds
+    /// are sorted again for each level, as the [`Solver`] does for
+    /// each of its states.
+    async fn builds(
+        &mut self,
+        solver: &Solver,
+        level: usize,
+        name: &PkgName,
+        index: usize,
+    ) -> Result<CandidateBuilds> {
+        let version = self
+            .candidates
+            .get_mut(name)
+            .and_then(|candidates| candidates.versions.get_mut(index))
+            .ok_or_else(|| Error::String("candidate version not loaded [INTERNAL ERROR]".into()))?;
+        if version.sorted.is_none() {
+            let iterator = SortedBuildIterator::new(
+                OptionMap::default(),
+                Arc::clone(&version.builds),
+                HashMap::new(),
+                &self.order,
+            )
+            .await?;
+            let builds = Self::collect_builds(iterator.clone()).await?;
+            version.sorted = Some((iterator, builds));
+        }
+        let Some((iterator, sorted)) = &version.sorted else {
+            return Err(Error::String(
+                "candidate builds not sorted [INTERNAL ERROR]".into(),
+            ));
+        };
+        if !solver.impossible_checks_in_build_keys() {
+            return Ok(Arc::clone(sorted));
+        }
+
+        let level = &mut self.levels[level];
+        if let Some((version_index, sorted)) = &level.sorted {
+            if *version_index == index {
+                return Ok(Arc::clone(sorted));
+            }
+        }
+        let source: Arc<tokio::sync::Mutex<dyn BuildIterator + Send>> =
+            Arc::new(tokio::sync::Mutex::new(iterator.clone()));
+        let builds_with_impossible_requests = solver
+            .check_builds_for_impossible_requests(
+                level.state.get_unresolved_requests(),
+                Arc::clone(&source),
+            )
+            .await?;
+        let iterator = SortedBuildIterator::new(
+            OptionMap::default(),
+            source,
+            builds_with_impossible_requests,
+            &self.order,
+        )
+        .await?;
+        let builds = Self::collect_builds(iterator).await?;
+        level.sorted = Some((index, Arc::clone(&builds)));
+        Ok(builds)
+    }
+
+    async fn collect_builds(mut iterator: SortedBuildIterator) -> Result<CandidateBuilds> {
+        let mut builds = Vec::new();
+        while let Some(hm) = iterator.next().await? {
+            builds.extend(hm.into_values());
+        }
+        Ok(Arc::new(builds))
+    }
+}
@@ -0,0 +1,246 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use std::sync::Arc;
+
+use rstest::{fixture, rstest};
+use spk_schema::prelude::*;
+use spk_schema::recipe;
+use spk_solve_macros::{make_build, make_repo, request};
+
+use crate::{option_map, spec, Error, Solver};
+
+#[fixture]
+fn solver() -> Solver {
+    let mut solver = Solver::default();
+    solver.set_conflict_driven(true);
+    solver
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_conflict_solver_simple_deps(mut solver: Solver) {
+    let repo = make_repo!(
+        [
+            {"pkg": "pkg-a/1.0.0"},
+            {"pkg": "pkg-a/1.2.1"},
+            {"pkg": "pkg-a/2.0.0"},
+            {"pkg": "pkg-b/1.0.0", "install": {"requirements": [{"pkg": "pkg-a/2.0"}]}},
+            {"pkg": "pkg-b/1.1.0", "install": {"requirements": [{"pkg": "pkg-a/1.2"}]}},
+        ]
+    );
+
+    solver.add_repository(Arc::new(repo));
+    solver.add_request(request!("pkg-b/1.1"));
+
+    let solution = solver.solve().await.unwrap();
+    assert_eq!(solution.len(), 2, "expected two resolved packages");
+    assert_eq!(
+        solution.get("pkg-a").unwrap().spec.version().to_string(),
+        "1.2.1"
+    );
+    assert_eq!(
+        solution.get("pkg-b").unwrap().spec.version().to_string(),
+        "1.1.0"
+    );
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_conflict_solver_skips_incompatible_version(mut solver: Solver) {
+    let repo = make_repo!(
+        [
+            {"pkg": "maya/2019"},
+            {"pkg": "maya/2020"},
+            {
+                "pkg": "my-plugin/1.1.0",
+                "install": {"requirements": [{"pkg": "maya/2020"}]},
+            },
+            {
+                "pkg": "my-plugin/1.0.0",
+                "install": {"requirements": [{"pkg": "maya/2019"}]},
+            },
+        ]
+    );
+
+    solver.add_repository(Arc::new(repo));
+    solver.add_request(request!("my-plugin/1"));
+    solver.add_request(request!("maya/2019"));
+
+    let solution = solver.solve().await.unwrap();
+    assert_eq!(
+        solution
+            .get("my-plugin")
+            .unwrap()
+            .spec
+            .version()
+            .to_string(),
+        "1.0.0"
+    );
+    assert_eq!(
+        solution.get("maya").unwrap().spec.version().to_string(),
+        "2019.0.0"
+    );
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_conflict_solver_backjumps_over_unrelated_decisions(mut solver: Solver) {
+    // The conflict between dep-1 and dep-2 is caused by which version
+    // of pkg-top was chosen, so the solver should go straight back to
+    // it without trying every version of the unrelated package.
+    let repo = make_repo!(
+        [
+            {
+                "pkg": "pkg-top/2.0.0",
+                "install": {"requirements": [
+                    {"pkg": "dep-1/1.1"},
+                    {"pkg": "unrelated/1"},
+                    {"pkg": "dep-2/1"},
+                ]},
+            },
+            {
+                "pkg": "pkg-top/1.0.0",
+                "install": {"requirements": [
+                    {"pkg": "dep-1/1.0"},
+                    {"pkg": "unrelated/1"},
+                    {"pkg": "dep-2/1"},
+                ]},
+            },
+            {"pkg": "dep-1/1.1.0"},
+            {"pkg": "dep-1/1.0.0"},
+            {"pkg": "unrelated/1.2.0"},
+            {"pkg": "unrelated/1.1.0"},
+            {"pkg": "unrelated/1.0.0"},
+            {
+                "pkg": "dep-2/1.0.0",
+                "install": {"requirements": [{"pkg": "dep-1/~1.0.0"}]},
+            },
+        ]
+    );
+
+    solver.add_repository(Arc::new(repo));
+    solver.add_request(request!("pkg-top"));
+
+    let solution = solver.solve().await.unwrap();
+    assert_eq!(
+        solution.get("pkg-top").unwrap().spec.version().to_string(),
+        "1.0.0"
+    );
+    assert_eq!(
+        solution.get("dep-1").unwrap().spec.version().to_string(),
+        "1.0.0"
+    );
+    assert_eq!(
+        solution
+            .get("unrelated")
+            .unwrap()
+            .spec
+            .version()
+            .to_string(),
+        "1.2.0",
+        "the latest version of an unrelated package should still be used"
+    );
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_conflict_solver_explains_unsatisfiable_requests(mut solver: Solver) {
+    let repo = make_repo!(
+        [
+            {
+                "pkg": "pkg-top/1.0.0",
+                "install": {"requirements": [{"pkg": "dep-1/1.1"}, {"pkg": "dep-2/1"}]},
+            },
+            {"pkg": "dep-1/1.1.0"},
+            {"pkg": "dep-1/1.0.0"},
+            {
+                "pkg": "dep-2/1.0.0",
+                "install": {"requirements": [{"pkg": "dep-1/~1.0.0"}]},
+            },
+        ]
+    );
+
+    solver.add_repository(Arc::new(repo));
+    solver.add_request(request!("pkg-top"));
+
+    let err = solver.solve().await.unwrap_err();
+    let Error::Unsatisfiable(err) = err else {
+        panic!("expected an unsatisfiable error, got: {err}");
+    };
+    assert_eq!(err.conflict.request.pkg.name.as_str(), "pkg-top");
+    let cause = err
+        .conflict
+        .rejections
+        .iter()
+        .find_map(|rejection| rejection.cause.as_ref())
+        .expect("expected the rejection of pkg-top to be explained by a conflict");
+    assert_eq!(cause.request.pkg.name.as_str(), "dep-2");
+
+    let message = err.to_string();
+    assert!(
+        message.contains("as required by: pkg-top/1.0.0/"),
+        "expected explanation to include the requester chain, got: {message}"
+    );
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_conflict_solver_missing_package(mut solver: Solver) {
+    let repo = make_repo!([{"pkg": "my-pkg/1.0.0"}]);
+
+    solver.add_repository(Arc::new(repo));
+    solver.add_request(request!("does-not-exist"));
+
+    let err = solver.solve().await.unwrap_err();
+    let Error::Unsatisfiable(err) = err else {
+        panic!("expected an unsatisfiable error, got: {err}");
+    };
+    assert!(err.conflict.rejections.is_empty());
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_conflict_solver_with_impossible_checks_in_build_keys(mut solver: Solver) {
+    let options1 = option_map! {"dep" => "1.0.0"};
+    let options2 = option_map! {"dep" => "2.0.0"};
+
+    let dep1 = spec!({"pkg": "dep/1.0.0/3I42H3S6"});
+    let dep2 = spec!({"pkg": "dep/2.0.0/3I42H3S6"});
+
+    let a_spec = recipe!({
+        "pkg": "pkg-a/1.0.0",
+        "build": {"options": [{"pkg": "dep/1.0.0"}],
+                  "variants": [{"pkg": "dep/=1.0.0"}, {"pkg": "dep/=2.0.0"}],
+        },
+        "install": {"requirements": [{"pkg": "dep", "fromBuildEnv": "x.x.x"}]},
+    });
+
+    let build1 = make_build!(a_spec, [dep1], options1);
+    let build2 = make_build!(a_spec, [dep2], options2);
+    // dep2 is deliberately not in the repo so that build2 makes an
+    // impossible request and is sorted after build1
+    let repo = make_repo!([{"pkg": "pkg-top/1.2.3",
+                            "install": { "requirements": [{"pkg": "pkg-a"}] }},
+                           {"pkg": "dep/1.0.0"},
+                           build1,
+                           build2]);
+    repo.publish_recipe(&a_spec).await.unwrap();
+
+    solver.add_repository(Arc::new(repo));
+    solver.add_request(request!("pkg-top"));
+    solver.set_build_key_impossible_checks(true);
+
+    let mut runtime = solver.run();
+    let solution = runtime.solution().await.unwrap();
+    assert_eq!(
+        solution.get("dep").unwrap().spec.version().to_string(),
+        "1.0.0"
+    );
+    assert_eq!(
+        runtime.solver.get_number_of_builds_skipped(),
+        0,
+        "the build that makes an impossible request should be tried last"
+    );
+}
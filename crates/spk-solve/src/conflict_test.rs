@@ -0,0 +1,114 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use std::sync::Arc;
+
+use rstest::rstest;
+use spk_schema::ident::{build_ident, parse_ident, parse_ident_range, PkgRequest, RequestedBy};
+
+use super::{Conflict, Rejection};
+
+fn reject(package: &str, reason: &str) -> Rejection {
+    Rejection {
+        package: parse_ident(package).unwrap(),
+        reason: reason.to_string(),
+        cause: None,
+        conflicting_requirement: None,
+    }
+}
+
+#[rstest]
+fn test_conflict_groups_rejections_with_same_reason() {
+    let conflict = Conflict {
+        request: PkgRequest::new(
+            parse_ident_range("maya/2020").unwrap(),
+            RequestedBy::CommandLine,
+        ),
+        required_by: vec![vec![RequestedBy::CommandLine]],
+        rejections: vec![
+            reject("maya/2019.2.0/3I42H3S6", "not in the requested range"),
+            reject("maya/2019.0.0/3I42H3S6", "not in the requested range"),
+            reject("maya/2020.0.0/3I42H3S6", "package is deprecated"),
+        ],
+    };
+
+    let lines = conflict.to_string();
+    let lines = lines.lines().collect::<Vec<_>>();
+    assert_eq!(
+        lines,
+        vec![
+            "could not satisfy 'maya/2020' as required by: command line",
+            "  maya/2019.2.0/3I42H3S6, maya/2019.0.0/3I42H3S6: not in the requested range",
+            "  maya/2020.0.0/3I42H3S6: package is deprecated",
+        ]
+    );
+}
+
+#[rstest]
+fn test_conflict_nests_causes_and_shows_them_once() {
+    let plugin = build_ident!("my-plugin/1.0.0/3I42H3S6");
+    let cause = Arc::new(Conflict {
+        request: PkgRequest::new(
+            parse_ident_range("maya/2020").unwrap(),
+            RequestedBy::PackageBuild(plugin.clone()),
+        ),
+        required_by: vec![vec![
+            RequestedBy::PackageBuild(plugin.clone()),
+            RequestedBy::CommandLine,
+        ]],
+        rejections: vec![reject(
+            "maya/2019.0.0/3I42H3S6",
+            "not in the requested range",
+        )],
+    });
+    let conflict = Conflict {
+        request: PkgRequest::new(
+            parse_ident_range("my-plugin/1").unwrap(),
+            RequestedBy::CommandLine,
+        ),
+        required_by: vec![vec![RequestedBy::CommandLine]],
+        rejections: vec![
+            Rejection {
+                package: plugin.to_any(),
+                reason: "leads to a conflict".to_string(),
+                cause: Some(Arc::clone(&cause)),
+                conflicting_requirement: None,
+            },
+            Rejection {
+                package: parse_ident("my-plugin/1.0.0/src").unwrap(),
+                reason: "leads to a conflict".to_string(),
+                cause: Some(cause),
+                conflicting_requirement: None,
+            },
+        ],
+    };
+
+    let message = conflict.to_string();
+    let lines = message.lines().collect::<Vec<_>>();
+    assert_eq!(
+        lines,
+        vec![
+            "could not satisfy 'my-plugin/1' as required by: command line",
+            "  my-plugin/1.0.0/3I42H3S6: leads to a conflict",
+            "    could not satisfy 'maya/2020' as required by: my-plugin/1.0.0/3I42H3S6 <- command line",
+            "      maya/2019.0.0/3I42H3S6: not in the requested range",
+            "  my-plugin/1.0.0/src: leads to a conflict (could not satisfy 'maya/2020' as required by: my-plugin/1.0.0/3I42H3S6 <- command line, see above)",
+        ]
+    );
+}
+
+#[rstest]
+fn test_conflict_without_candidates() {
+    let conflict = Conflict {
+        request: PkgRequest::new(parse_ident_range("maya").unwrap(), RequestedBy::CommandLine),
+        required_by: vec![vec![RequestedBy::CommandLine]],
+        rejections: Vec::new(),
+    };
+
+    let message = conflict.to_string();
+    assert!(
+        message.contains("no versions of maya were found in the enabled repositories"),
+        "expected missing package explanation, got: {message}"
+    );
+}
@@ -24,6 +24,9 @@ pub enum Error {
     #[error(transparent)]
     #[diagnostic(forward(0))]
     OutOfOptions(#[from] OutOfOptions),
+    #[error(transparent)]
+    #[diagnostic(forward(0))]
+    Unsatisfiable(#[from] crate::conflict::Unsatisfiable),
     #[error("Solver interrupted: {0}")]
     SolverInterrupted(String),
     #[error(transparent)]
@@ -65,6 +68,10 @@ pub enum Error {
     SolverLogFileIOError(#[source] std::io::Error, PathBuf),
     #[error("Error: Flushing solver log file: {0}")]
     SolverLogFileFlushError(#[source] std::io::Error),
+    #[error("Error: Solve cache IO error: {1} - {0}")]
+    SolveCacheIOError(#[source] std::io::Error, PathBuf),
+    #[error("Error: Invalid solve cache entry: {1} - {0}")]
+    SolveCacheParseError(#[source] serde_json::Error, PathBuf),
 }
 
 #[derive(Diagnostic, Debug, Error)]
@@ -74,6 +81,19 @@ pub struct OutOfOptions {
     pub notes: Vec<Note>,
 }
 
+impl Error {
+    /// True if the solve ran out of candidates for the requests,
+    /// rather than failing for some other reason
+    pub fn is_unsatisfiable(&self) -> bool {
+        matches!(
+            self,
+            Error::OutOfOptions(_)
+                | Error::Unsatisfiable(_)
+                | Error::GraphError(spk_solve_graph::Error::FailedToResolve(_))
+        )
+    }
+}
+
 impl FormatError for Error {
     fn format_error(&self, verbosity: u8) -> String {
         let mut msg = String::new();
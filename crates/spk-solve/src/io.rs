@@ -66,6 +66,7 @@ const BY_USER: &str = "by user";
 
 const CLI_SOLVER: &str = "cli";
 const IMPOSSIBLE_CHECKS_SOLVER: &str = "check";
+const CONFLICT_DRIVEN_SOLVER: &str = "conflict";
 const ALL_SOLVERS: &str = "all";
 
 const UNABLE_TO_GET_OUTPUT_FILE_LOCK: &str = "Unable to get lock to write solver output to file";
@@ -1040,6 +1041,7 @@ enum LoopOutcome {
 pub enum MultiSolverKind {
     Unchanged,
     AllImpossibleChecks,
+    ConflictDriven,
     // This isn't a solver on its own. It indicates: the run all the
     // solvers in parallel but show the output from the unchanged one.
     All,
@@ -1056,6 +1058,7 @@ impl MultiSolverKind {
         match self {
             MultiSolverKind::Unchanged => CLI_SOLVER,
             MultiSolverKind::AllImpossibleChecks => IMPOSSIBLE_CHECKS_SOLVER,
+            MultiSolverKind::ConflictDriven => CONFLICT_DRIVEN_SOLVER,
             MultiSolverKind::All => ALL_SOLVERS,
         }
     }
@@ -1067,6 +1070,7 @@ impl MultiSolverKind {
         match value.to_lowercase().as_ref() {
             CLI_SOLVER => MultiSolverKind::Unchanged,
             IMPOSSIBLE_CHECKS_SOLVER => MultiSolverKind::AllImpossibleChecks,
+            CONFLICT_DRIVEN_SOLVER => MultiSolverKind::ConflictDriven,
             ALL_SOLVERS => MultiSolverKind::All,
             _ => MultiSolverKind::Unchanged,
         }
@@ -1089,6 +1093,7 @@ impl Display for MultiSolverKind {
         let name = match self {
             MultiSolverKind::Unchanged => "Unchanged",
             MultiSolverKind::AllImpossibleChecks => "All Impossible Checks",
+            MultiSolverKind::ConflictDriven => "Conflict Driven",
             MultiSolverKind::All => "All",
         };
         write!(f, "{name}")
@@ -1155,13 +1160,21 @@ impl DecisionFormatter {
     /// Run the solver to completion, printing each step to stdout as
     /// appropriate. This runs two solvers in parallel (one based on
     /// the given solver, one with additional options) and takes the
-    /// result from the first to finish.
+    /// result from the first to finish. If the solver has a solve
+    /// cache with a valid solution, that is returned without solving.
     pub async fn run_and_print_resolve(
         &self,
         solver: &Solver,
     ) -> Result<(Solution, Arc<tokio::sync::RwLock<Graph>>)> {
+        if let Some(solution) = solver.cached_solution().await {
+            return Ok((solution, Arc::new(tokio::sync::RwLock::new(Graph::new()))));
+        }
         let solvers = self.setup_solvers(solver);
-        self.run_multi_solve(solvers, OutputKind::Println).await
+        let result = self.run_multi_solve(solvers, OutputKind::Println).await;
+        if let Ok((solution, _)) = &result {
+            solver.cache_solution(solution).await;
+        }
+        result
     }
 
     /// Run the solver runtime to completion, printing each step to
@@ -1188,13 +1201,22 @@ impl DecisionFormatter {
     /// Run the solver to completion, logging each step as a tracing
     /// info-level event as appropriate. This runs two solvers in
     /// parallel (one based on the given solver, one with additional
-    /// options) and takes the result from the first to finish.
+    /// options) and takes the result from the first to finish. If the
+    /// solver has a solve cache with a valid solution, that is returned
+    /// without solving.
     pub async fn run_and_log_resolve(
         &self,
         solver: &Solver,
     ) -> Result<(Solution, Arc<tokio::sync::RwLock<Graph>>)> {
+        if let Some(solution) = solver.cached_solution().await {
+            return Ok((solution, Arc::new(tokio::sync::RwLock::new(Graph::new()))));
+        }
         let solvers = self.setup_solvers(solver);
-        self.run_multi_solve(solvers, OutputKind::Tracing).await
+        let result = self.run_multi_solve(solvers, OutputKind::Tracing).await;
+        if let Ok((solution, _)) = &result {
+            solver.cache_solution(solution).await;
+        }
+        result
     }
 
     /// Run the solver runtime to completion, logging each step as a
@@ -1239,6 +1261,15 @@ impl DecisionFormatter {
                 solver_kind: MultiSolverKind::AllImpossibleChecks,
                 ignore_failure: false,
             }]),
+            MultiSolverKind::ConflictDriven => {
+                let mut conflict_driven_solver = base_solver.clone();
+                conflict_driven_solver.set_conflict_driven(true);
+                Vec::from([SolverTaskSettings {
+                    solver: conflict_driven_solver,
+                    solver_kind: MultiSolverKind::ConflictDriven,
+                    ignore_failure: false,
+                }])
+            }
             MultiSolverKind::All => Vec::from([
                 SolverTaskSettings {
                     solver: solver_with_no_change,
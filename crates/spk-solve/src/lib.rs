@@ -2,16 +2,21 @@
 // SPDX-License-Identifier: Apache-2.0
 // https://github.com/spkenv/spk
 
+mod conflict;
+mod conflict_solver;
 mod error;
 mod io;
 #[cfg(feature = "statsd")]
 mod metrics;
 mod search_space;
+mod solve_cache;
 mod solver;
 mod status_line;
+mod unsat_core;
 
 use std::sync::Arc;
 
+pub use conflict::{Conflict, ConflictingRequirement, Rejection, Unsatisfiable};
 pub use error::{Error, Result};
 use graph::Graph;
 pub use io::{
@@ -34,6 +39,7 @@ pub use metrics::{
     SPK_SOLVER_SOLUTION_SIZE_METRIC,
 };
 pub(crate) use search_space::show_search_space_stats;
+pub use solve_cache::{SolveCache, SOLVE_CACHE_VERSION};
 pub use solver::{Solver, SolverRuntime};
 pub use spk_schema::foundation::ident_build::Build;
 pub use spk_schema::foundation::ident_component::Component;
@@ -51,6 +57,7 @@ pub use spk_schema::{recipe, spec, v0, Package, Recipe, Spec, SpecRecipe};
 pub use spk_solve_solution::{PackageSource, Solution};
 pub use spk_storage::RepositoryHandle;
 pub(crate) use status_line::StatusLine;
+pub use unsat_core::{Requirement, UnsatisfiableCore};
 pub use {
     serde_json,
     spfs,
@@ -0,0 +1,276 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use futures::{Stream, StreamExt, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use spk_schema::foundation::name::{PkgName, PkgNameBuf};
+use spk_schema::ident::VersionIdent;
+use spk_schema::prelude::*;
+use spk_solve_solution::Lockfile;
+use spk_storage::RepositoryHandle;
+
+use crate::{Error, Result, Solution, Solver};
+
+#[cfg(test)]
+#[path = "./solve_cache_test.rs"]
+mod solve_cache_test;
+
+/// Current data structure version number for cached solves
+pub const SOLVE_CACHE_VERSION: u32 = 3;
+
+/// How many packages are fingerprinted at the same time
+const FINGERPRINT_CONCURRENCY: usize = 16;
+
+/// A persistent cache of solutions, stored as files in a directory.
+///
+/// Solutions are keyed by the solver's initial requests, options and
+/// settings, and by the repositories that it uses. Each entry also
+/// records a fingerprint of the published builds of every package
+/// that the search looked at, including the ones that it rejected.
+/// The entry is only used while all of those fingerprints still
+/// match, and while every build in the solution still exists
+/// unchanged and is not deprecated.
+///
+/// Checking the fingerprints lists the versions of each of those
+/// packages and the builds of each version in every repository, and
+/// resolves the spec tag of every build, so a cache hit still makes
+/// a request per version and per build of every package to remote
+/// repositories. This is usually much cheaper than solving again,
+/// which reads the full spec of each build that it considers, but
+/// is not free for packages with many builds.
+#[derive(Clone, Debug)]
+pub struct SolveCache {
+    root: PathBuf,
+}
+
+/// A solution as it is saved in the cache
+#[derive(Serialize, Deserialize)]
+struct CachedSolve {
+    /// For tracking data structure changes
+    version: u32,
+    /// The fingerprint of each package that the search looked at
+    fingerprints: BTreeMap<PkgNameBuf, String>,
+    /// The cached solution
+    solution: Lockfile,
+}
+
+impl SolveCache {
+    pub fn new<P: Into<PathBuf>>(root: P) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Create a cache in the directory set in the spk config, or
+    /// None if no directory is configured.
+    pub fn from_config() -> Option<Self> {
+        let config = spk_config::get_config().ok()?;
+        if config.solver.solve_cache_dir.is_empty() {
+            return None;
+        }
+        Some(Self::new(&config.solver.solve_cache_dir))
+    }
+
+    /// The directory that cached solutions are stored in
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Find a cached solution for the solver's requests.
+    ///
+    /// Returns None if there is no cached solution, or if the
+    /// repositories have changed in a way that could affect it.
+    pub async fn get(&self, solver: &Solver) -> Result<Option<Solution>> {
+        let path = self.entry_path(solver)?;
+        let data = match tokio::fs::read(&path).await {
+            Ok(data) => data,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(Error::SolveCacheIOError(err, path)),
+        };
+        let entry: CachedSolve = match serde_json::from_slice(&data) {
+            Ok(entry) => entry,
+            Err(err) => {
+                tracing::debug!("Ignoring unreadable cached solve {path:?}: {err}");
+                return Ok(None);
+            }
+        };
+        if entry.version != SOLVE_CACHE_VERSION {
+            return Ok(None);
+        }
+
+        let repos = solver.repositories();
+        let mut current = Self::fingerprints(repos, entry.fingerprints.keys());
+        while let Some((name, fingerprint)) = current.try_next().await? {
+            if entry.fingerprints.get(name) != Some(&fingerprint) {
+                tracing::debug!("Ignoring cached solve, {name} has changed since it was saved");
+                return Ok(None);
+            }
+        }
+        match entry.solution.to_solution(repos).await {
+            Ok(solution) => Ok(Some(solution)),
+            Err(err) => {
+                tracing::debug!("Ignoring cached solve, it is no longer valid: {err}");
+                Ok(None)
+            }
+        }
+    }
+
+    /// Save a solution to the solver's requests into the cache.
+    ///
+    /// Solutions that include packages which need to be built from
+    /// source are not cached.
+    pub async fn insert(&self, solver: &Solver, solution: &Solution) -> Result<()> {
+        let lockfile = match Lockfile::from_solution(solution) {
+            Ok(lockfile) => lockfile,
+            Err(err) => {
+                tracing::debug!("Not caching solve: {err}");
+                return Ok(());
+            }
+        };
+
+        // A new version or build of any package that the search
+        // looked at could change the solution, even if the search
+        // rejected that package, so all of them are fingerprinted
+        let mut names = solver.searched_packages();
+        names.extend(
+            solver
+                .initial_package_requests()
+                .into_iter()
+                .map(|request| request.pkg.name),
+        );
+        names.extend(
+            solution
+                .items()
+                .map(|resolved| resolved.spec.name().to_owned()),
+        );
+        let fingerprints = Self::fingerprints(solver.repositories(), names.iter())
+            .map_ok(|(name, fingerprint)| (name.to_owned(), fingerprint))
+            .try_collect::<BTreeMap<_, _>>()
+            .await?;
+
+        let entry = CachedSolve {
+            version: SOLVE_CACHE_VERSION,
+            fingerprints,
+            solution: lockfile,
+        };
+        let path = self.entry_path(solver)?;
+        let data = serde_json::to_vec(&entry)
+            .map_err(|err| Error::SolveCacheParseError(err, path.clone()))?;
+        tokio::fs::create_dir_all(&self.root)
+            .await
+            .map_err(|err| Error::SolveCacheIOError(err, self.root.clone()))?;
+        // Written to a separate file first so that other processes
+        // never read a partially written entry
+        let partial = path.with_extension(format!("{}.tmp", uuid::Uuid::new_v4()));
+        tokio::fs::write(&partial, data)
+            .await
+            .map_err(|err| Error::SolveCacheIOError(err, partial.clone()))?;
+        tokio::fs::rename(&partial, &path)
+            .await
+            .map_err(|err| Error::SolveCacheIOError(err, path))
+    }
+
+    /// The file that holds the cached solution for the solver's
+    /// current requests, options and repositories
+    fn entry_path(&self, solver: &Solver) -> Result<PathBuf> {
+        let state = solver.get_initial_state();
+        // Requests are normalized by sorting, so that the same set of
+        // requests finds the same entry no matter what order they
+        // were given in
+        let mut requests = Vec::new();
+        for request in state.get_pkg_requests() {
+            requests.push(key_part(&***request)?);
+        }
+        requests.sort();
+        let mut vars = Vec::new();
+        for request in state.get_var_requests() {
+            vars.push(key_part(request)?);
+        }
+        let repos = solver
+            .repositories()
+            .iter()
+            .map(|repo| format!("{}={}", repo.name(), repo.address()))
+            .collect::<Vec<_>>();
+        let key = serde_json::json!({
+            "version": SOLVE_CACHE_VERSION,
+            "requests": requests,
+            "vars": vars,
+            "options": state.get_option_map(),
+            "binary_only": solver.is_binary_only(),
+            "strategy": solver.solve_strategy().to_string(),
+            "repos": repos,
+        });
+
+        let mut hasher = spfs::encoding::Hasher::new_sync();
+        hasher
+            .write_all(key.to_string().as_bytes())
+            .map_err(|err| Error::String(format!("Failed to hash solve cache key: {err}")))?;
+        Ok(self.root.join(format!("{}.json", hasher.digest())))
+    }
+
+    /// The fingerprints of many packages, listed concurrently and
+    /// produced in no particular order
+    fn fingerprints<'a, I>(
+        repos: &'a [Arc<RepositoryHandle>],
+        names: I,
+    ) -> impl Stream<Item = Result<(&'a PkgName, String)>> + 'a
+    where
+        I: IntoIterator<Item = &'a PkgNameBuf>,
+        I::IntoIter: 'a,
+    {
+        futures::stream::iter(names)
+            .map(move |name| async move {
+                let fingerprint = Self::fingerprint(repos, name).await?;
+                Ok::<_, Error>((&**name, fingerprint))
+            })
+            .buffer_unordered(FINGERPRINT_CONCURRENCY)
+    }
+
+    /// A fingerprint of the builds of a package in each of the
+    /// repositories, which changes whenever any of them are
+    /// published, modified or removed
+    async fn fingerprint(repos: &[Arc<RepositoryHandle>], name: &PkgName) -> Result<String> {
+        let mut listing = String::new();
+        for repo in repos {
+            listing.push_str(&format!("{}\n", repo.name()));
+            let mut versions = repo.list_package_versions(name).await?.to_vec();
+            versions.sort();
+            // The builds of each version are listed at the same time,
+            // because each listing is a round trip to remote repositories
+            let builds = futures::future::try_join_all(versions.iter().map(|version| async move {
+                let pkg = VersionIdent::new(name.to_owned(), (**version).clone());
+                let builds = repo.list_package_builds(&pkg).await?;
+                // The state of each build is included so that builds
+                // that are republished or deprecated change it too,
+                // even when they were skipped by the original search
+                let mut states =
+                    futures::future::try_join_all(builds.iter().map(|build| async move {
+                        let fingerprint = repo.read_package_fingerprint(build).await?;
+                        Ok::<_, Error>(format!("{}={fingerprint}", build.build()))
+                    }))
+                    .await?;
+                states.sort();
+                Ok::<_, Error>(states.join(" "))
+            }))
+            .await?;
+            for (version, builds) in versions.iter().zip(builds) {
+                listing.push_str(&format!("{version} {builds}\n"));
+            }
+        }
+
+        let mut hasher = spfs::encoding::Hasher::new_sync();
+        hasher
+            .write_all(listing.as_bytes())
+            .map_err(|err| Error::String(format!("Failed to hash {name}: {err}")))?;
+        Ok(hasher.digest().to_string())
+    }
+}
+
+fn key_part<T: Serialize>(value: &T) -> Result<String> {
+    serde_json::to_string(value)
+        .map_err(|err| Error::String(format!("Failed to create solve cache key: {err}")))
+}
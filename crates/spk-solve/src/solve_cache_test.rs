@@ -0,0 +1,180 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use std::sync::Arc;
+
+use rstest::rstest;
+use spk_schema::prelude::*;
+use spk_solve_macros::{make_package, make_repo, request};
+use spk_storage::RepositoryHandle;
+
+use super::SolveCache;
+use crate::{option_map, Solution, Solver};
+
+async fn cached_repo() -> Arc<RepositoryHandle> {
+    Arc::new(make_repo!(
+        [
+            {"pkg": "dep/1.0.0"},
+            {"pkg": "other/1.0.0"},
+            {"pkg": "pkg-a/1.0.0", "install": {"requirements": [{"pkg": "dep/1"}]}},
+        ]
+    ))
+}
+
+fn solver(repo: &Arc<RepositoryHandle>, cache: &SolveCache, requests: &[&str]) -> Solver {
+    let mut solver = Solver::default();
+    solver.add_repository(Arc::clone(repo));
+    solver.set_solve_cache(Some(cache.clone()));
+    for request in requests {
+        solver.add_request(request!(request));
+    }
+    solver
+}
+
+fn resolved(solution: &Solution) -> Vec<String> {
+    let mut builds = solution
+        .items()
+        .map(|resolved| resolved.spec.ident().to_string())
+        .collect::<Vec<_>>();
+    builds.sort();
+    builds
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_solve_cache_reuses_solution() {
+    let tmpdir = tempfile::tempdir().unwrap();
+    let cache = SolveCache::new(tmpdir.path());
+    let repo = cached_repo().await;
+
+    let mut first = solver(&repo, &cache, &["pkg-a", "other"]);
+    assert!(cache.get(&first).await.unwrap().is_none());
+    let solution = first.solve().await.unwrap();
+    assert_eq!(std::fs::read_dir(tmpdir.path()).unwrap().count(), 1);
+
+    // the same requests in a different order find the same entry
+    let second = solver(&repo, &cache, &["other", "pkg-a"]);
+    let cached = cache
+        .get(&second)
+        .await
+        .unwrap()
+        .expect("expected a cached solution");
+    assert_eq!(resolved(&cached), resolved(&solution));
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_solve_cache_ignores_changed_packages() {
+    let tmpdir = tempfile::tempdir().unwrap();
+    let cache = SolveCache::new(tmpdir.path());
+    let repo = cached_repo().await;
+
+    let mut first = solver(&repo, &cache, &["pkg-a"]);
+    first.solve().await.unwrap();
+
+    let options = option_map! {};
+    let (spec, components) = make_package!(repo, {"pkg": "dep/1.1.0"}, &options);
+    repo.publish_package(&spec, &components).await.unwrap();
+
+    let mut second = solver(&repo, &cache, &["pkg-a"]);
+    assert!(
+        cache.get(&second).await.unwrap().is_none(),
+        "a new version of a resolved package should invalidate the cached solve"
+    );
+    let solution = second.solve().await.unwrap();
+    assert_eq!(
+        solution.get("dep").unwrap().spec.version().to_string(),
+        "1.1.0"
+    );
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_solve_cache_keyed_by_requests() {
+    let tmpdir = tempfile::tempdir().unwrap();
+    let cache = SolveCache::new(tmpdir.path());
+    let repo = cached_repo().await;
+
+    let mut first = solver(&repo, &cache, &["pkg-a"]);
+    first.solve().await.unwrap();
+
+    let other = solver(&repo, &cache, &["pkg-a", "other"]);
+    assert!(cache.get(&other).await.unwrap().is_none());
+
+    let mut uncached = solver(&repo, &cache, &["pkg-a"]);
+    uncached.set_solve_cache(None);
+    assert!(uncached.cached_solution().await.is_none());
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_solve_cache_ignores_changed_rejected_packages() {
+    let tmpdir = tempfile::tempdir().unwrap();
+    let cache = SolveCache::new(tmpdir.path());
+    let repo = Arc::new(make_repo!(
+        [
+            {"pkg": "pkg-a/1.0.0"},
+            {"pkg": "pkg-a/2.0.0", "install": {"requirements": [{"pkg": "missing/1"}]}},
+        ]
+    ));
+
+    let mut first = solver(&repo, &cache, &["pkg-a"]);
+    let solution = first.solve().await.unwrap();
+    assert_eq!(
+        solution.get("pkg-a").unwrap().spec.version().to_string(),
+        "1.0.0"
+    );
+
+    let options = option_map! {};
+    let (spec, components) = make_package!(repo, {"pkg": "missing/1.0.0"}, &options);
+    repo.publish_package(&spec, &components).await.unwrap();
+
+    let mut second = solver(&repo, &cache, &["pkg-a"]);
+    assert!(
+        cache.get(&second).await.unwrap().is_none(),
+        "a new version of a rejected package should invalidate the cached solve"
+    );
+    let solution = second.solve().await.unwrap();
+    assert_eq!(
+        solution.get("pkg-a").unwrap().spec.version().to_string(),
+        "2.0.0"
+    );
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_solve_cache_ignores_undeprecated_packages() {
+    let tmpdir = tempfile::tempdir().unwrap();
+    let cache = SolveCache::new(tmpdir.path());
+    let repo = Arc::new(make_repo!([{"pkg": "pkg-a/1.0.0"}]));
+    let options = option_map! {};
+    let (mut spec, components) = make_package!(
+        repo,
+        {"pkg": "pkg-a/2.0.0", "deprecated": true},
+        &options
+    );
+    repo.publish_package(&spec, &components).await.unwrap();
+
+    let mut first = solver(&repo, &cache, &["pkg-a"]);
+    let solution = first.solve().await.unwrap();
+    assert_eq!(
+        solution.get("pkg-a").unwrap().spec.version().to_string(),
+        "1.0.0"
+    );
+
+    // the versions and builds are unchanged, only the skipped build is
+    spec.undeprecate().unwrap();
+    repo.update_package(&spec).await.unwrap();
+
+    let mut second = solver(&repo, &cache, &["pkg-a"]);
+    assert!(
+        cache.get(&second).await.unwrap().is_none(),
+        "un-deprecating a skipped build should invalidate the cached solve"
+    );
+    let solution = second.solve().await.unwrap();
+    assert_eq!(
+        solution.get("pkg-a").unwrap().spec.version().to_string(),
+        "2.0.0"
+    );
+}
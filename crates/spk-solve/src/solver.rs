@@ -3,7 +3,7 @@
 // https://github.com/spkenv/spk
 
 use std::borrow::Cow;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::mem::take;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
@@ -37,9 +37,11 @@ use spk_solve_graph::{
 };
 use spk_solve_package_iterator::{
     BuildIterator,
+    CandidateOrder,
     EmptyBuildIterator,
     PackageIterator,
     RepositoryPackageIterator,
+    SolveStrategy,
     SortedBuildIterator,
 };
 use spk_solve_solution::{PackageSource, Solution};
@@ -54,8 +56,11 @@ use spk_solve_validation::{
 use spk_storage::RepositoryHandle;
 
 use super::error;
+use crate::conflict_solver::ConflictSearch;
 use crate::error::OutOfOptions;
 use crate::option_map::OptionMap;
+use crate::solve_cache::SolveCache;
+use crate::unsat_core::UnsatisfiableCore;
 use crate::{Error, Result};
 
 // Public to allow other tests to use its macros
@@ -101,6 +106,18 @@ pub struct Solver {
     request_validator: Arc<ImpossibleRequestsChecker>,
     // For holding the settings that say which impossible checks are enabled
     impossible_checks: ImpossibleChecksSettings,
+    // Whether to search using the conflict-driven backjumping search
+    // instead of stepping back through the history of decisions
+    conflict_driven: bool,
+    // The order to try the versions and builds of each package in
+    candidate_order: CandidateOrder,
+    // Where to look for a previous solution to the same requests
+    // before solving, and to save new solutions
+    solve_cache: Option<SolveCache>,
+    // The names of the packages that searches have listed the
+    // candidates of, shared with clones of this solver so that it
+    // knows about every search that was run on its behalf
+    searched_packages: Arc<std::sync::Mutex<BTreeSet<PkgNameBuf>>>,
     // For counting the number of steps (forward) taken in a solve
     number_of_steps: usize,
     // For counting number of builds skipped for some reason
@@ -133,6 +150,10 @@ impl Default for Solver {
             validators: Cow::from(default_validators()),
             request_validator: Arc::new(ImpossibleRequestsChecker::default()),
             impossible_checks: ImpossibleChecksSettings::default(),
+            conflict_driven: false,
+            candidate_order: CandidateOrder::default(),
+            solve_cache: None,
+            searched_packages: Arc::default(),
             number_of_steps: 0,
             number_builds_skipped: 0,
             number_incompat_versions: 0,
@@ -242,6 +263,81 @@ impl Solver {
         &self.repos
     }
 
+    /// The package requests that have been added to this solver
+    pub fn initial_package_requests(&self) -> Vec<PkgRequest> {
+        self.initial_state_builders
+            .iter()
+            .filter_map(|change| match change {
+                Change::RequestPackage(rp) => Some(rp.request.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The package and var requests that have been added to this solver
+    pub fn initial_requests(&self) -> Vec<Request> {
+        self.initial_state_builders
+            .iter()
+            .filter_map(|change| match change {
+                Change::RequestPackage(rp) => Some(Request::Pkg(rp.request.clone())),
+                Change::RequestVar(rv) => Some(Request::Var(rv.request.clone())),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Note that a search has listed the candidates of a package
+    pub(crate) fn record_searched_package(&self, name: &PkgName) {
+        let mut searched = self
+            .searched_packages
+            .lock()
+            .expect("searched packages lock should not be poisoned");
+        if !searched.contains(name) {
+            searched.insert(name.to_owned());
+        }
+    }
+
+    /// The names of every package that the searches for this solver
+    /// have looked at, whether or not they ended up in a solution.
+    ///
+    /// This includes the packages whose candidates were listed and
+    /// the packages that the impossible request checks looked for.
+    pub(crate) fn searched_packages(&self) -> BTreeSet<PkgNameBuf> {
+        let mut names = self
+            .searched_packages
+            .lock()
+            .expect("searched packages lock should not be poisoned")
+            .clone();
+        for requests in [
+            self.request_validator.impossible_requests(),
+            self.request_validator.possible_requests(),
+        ] {
+            names.extend(requests.iter().map(|entry| entry.key().name.clone()));
+        }
+        names
+    }
+
+    /// A copy of this solver with the same repositories and options,
+    /// but none of its package or var requests and none of the stats
+    /// gathered by previous solves.
+    pub(crate) fn without_initial_requests(&self) -> Self {
+        let mut solver = self.clone();
+        solver
+            .initial_state_builders
+            .retain(|change| !matches!(change, Change::RequestPackage(_) | Change::RequestVar(_)));
+        solver.number_of_steps = 0;
+        solver.number_builds_skipped = 0;
+        solver.number_incompat_versions = 0;
+        solver.number_incompat_builds = 0;
+        solver.number_total_builds = 0;
+        solver.number_of_steps_back = Arc::new(AtomicU64::new(0));
+        solver.error_frequency.clear();
+        solver.problem_packages.clear();
+        solver.solve_cache = None;
+        solver.searched_packages = Arc::default();
+        solver
+    }
+
     pub fn get_initial_state(&self) -> Arc<State> {
         let mut state = None;
         let base = State::default_state();
@@ -335,8 +431,10 @@ impl Solver {
         package_name: PkgNameBuf,
     ) -> Arc<tokio::sync::Mutex<Box<dyn PackageIterator + Send>>> {
         debug_assert!(!self.repos.is_empty());
+        self.record_searched_package(&package_name);
         Arc::new(tokio::sync::Mutex::new(Box::new(
-            RepositoryPackageIterator::new(package_name, self.repos.clone()),
+            RepositoryPackageIterator::new(package_name, self.repos.clone())
+                .with_order(self.candidate_order.clone()),
         )))
     }
 
@@ -349,7 +447,11 @@ impl Solver {
     /// validate that a build is possible and to generate the resulting
     /// spec.
     #[async_recursion::async_recursion]
-    async fn resolve_new_build(&self, recipe: &SpecRecipe, state: &State) -> Result<Arc<Spec>> {
+    pub(crate) async fn resolve_new_build(
+        &self,
+        recipe: &SpecRecipe,
+        state: &State,
+    ) -> Result<Arc<Spec>> {
         let mut opts = state.get_option_map().clone();
         for pkg_request in state.get_pkg_requests() {
             if !opts.contains_key(pkg_request.pkg.name.as_opt_name()) {
@@ -374,6 +476,8 @@ impl Solver {
 
         let mut solver = Solver {
             repos: self.repos.clone(),
+            conflict_driven: self.conflict_driven,
+            candidate_order: self.candidate_order.clone(),
             ..Default::default()
         };
         solver.update_options(opts.clone());
@@ -388,7 +492,7 @@ impl Solver {
     /// impossible request when combined with the unresolved
     /// requests. Returns a map of builds that do generate impossible
     /// requests and the reasons they are impossible.
-    async fn check_builds_for_impossible_requests(
+    pub(crate) async fn check_builds_for_impossible_requests(
         &self,
         unresolved: &HashMap<PkgNameBuf, PkgRequest>,
         builds: Arc<tokio::sync::Mutex<dyn BuildIterator + Send>>,
@@ -579,6 +683,7 @@ impl Solver {
                         node.state.get_option_map().clone(),
                         builds.clone(),
                         builds_with_impossible_requests,
+                        &self.candidate_order,
                     )
                     .await?,
                 ));
@@ -774,7 +879,11 @@ impl Solver {
         }))
     }
 
-    fn validate_recipe<R: Recipe>(&self, state: &State, recipe: &R) -> Result<Compatibility> {
+    pub(crate) fn validate_recipe<R: Recipe>(
+        &self,
+        state: &State,
+        recipe: &R,
+    ) -> Result<Compatibility> {
         for validator in self.validators.as_ref() {
             let compat = validator.validate_recipe(state, recipe)?;
             if !&compat {
@@ -784,7 +893,7 @@ impl Solver {
         Ok(Compatibility::Compatible)
     }
 
-    fn validate_package<P>(
+    pub(crate) fn validate_package<P>(
         &self,
         state: &State,
         spec: &P,
@@ -899,6 +1008,10 @@ impl Solver {
         self.initial_state_builders.truncate(0);
         self.validators = Cow::from(default_validators());
         (*self.request_validator).reset();
+        self.conflict_driven = false;
+        self.candidate_order = CandidateOrder::default();
+        self.solve_cache = None;
+        self.searched_packages = Arc::default();
 
         self.number_of_steps = 0;
         self.number_builds_skipped = 0;
@@ -950,6 +1063,13 @@ impl Solver {
         }
     }
 
+    /// Return true if this solver only solves pre-built binary packages
+    pub fn is_binary_only(&self) -> bool {
+        self.validators
+            .iter()
+            .any(|v| matches!(v, Validators::BinaryOnly(_)))
+    }
+
     /// Enable or disable running impossible checks on the initial requests
     /// before the solve starts
     pub fn set_initial_request_impossible_checks(&mut self, enabled: bool) {
@@ -968,6 +1088,105 @@ impl Solver {
         self.impossible_checks.use_in_build_keys = enabled;
     }
 
+    /// Enable or disable the conflict-driven search.
+    ///
+    /// Instead of stepping back to the oldest fork in the history of
+    /// decisions when it gets stuck, the conflict-driven search tracks
+    /// which earlier decisions caused each candidate to be rejected.
+    /// It jumps straight back to the most recent decision responsible
+    /// for a conflict, and remembers the combination of packages so
+    /// that it is not tried again. When no solution exists, the error
+    /// explains which requests could not be satisfied and why.
+    pub fn set_conflict_driven(&mut self, enabled: bool) {
+        self.conflict_driven = enabled;
+    }
+
+    /// Return true if this solver uses the conflict-driven search
+    pub fn is_conflict_driven(&self) -> bool {
+        self.conflict_driven
+    }
+
+    /// Set what the solver optimizes for when there is more than one
+    /// candidate that satisfies a request.
+    ///
+    /// The default is to prefer the newest versions.
+    pub fn set_solve_strategy(&mut self, strategy: SolveStrategy) {
+        self.candidate_order = CandidateOrder::new(strategy);
+    }
+
+    /// Get the strategy used to choose between candidates
+    pub fn solve_strategy(&self) -> SolveStrategy {
+        self.candidate_order.strategy()
+    }
+
+    /// Set the cache of solve results to use, or None to always solve.
+    ///
+    /// When set, a solve first looks for a cached solution to the same
+    /// requests, options and repositories, and reuses it if none of
+    /// the packages involved have changed since. New solutions are
+    /// saved into the cache. The cache is not used with the
+    /// prefer-runtime and prefer-local strategies, because their
+    /// results depend on more than the repositories.
+    pub fn set_solve_cache(&mut self, cache: Option<SolveCache>) {
+        self.solve_cache = cache;
+    }
+
+    /// Get the cache of solve results used by this solver, if any
+    pub fn solve_cache(&self) -> Option<&SolveCache> {
+        self.solve_cache.as_ref()
+    }
+
+    fn usable_solve_cache(&self) -> Option<&SolveCache> {
+        match self.solve_strategy() {
+            SolveStrategy::Newest | SolveStrategy::Lowest => self.solve_cache.as_ref(),
+            SolveStrategy::PreferRuntime | SolveStrategy::PreferLocal => None,
+        }
+    }
+
+    /// Find a cached solution to this solver's requests.
+    ///
+    /// Failures to read the cache are logged and treated as though
+    /// there was no cached solution.
+    pub async fn cached_solution(&self) -> Option<Solution> {
+        let cache = self.usable_solve_cache()?;
+        match cache.get(self).await {
+            Ok(solution) => {
+                if solution.is_some() {
+                    tracing::debug!("Using cached solve from {}", cache.root().display());
+                }
+                solution
+            }
+            Err(err) => {
+                tracing::warn!("Failed to read from the solve cache: {err}");
+                None
+            }
+        }
+    }
+
+    /// Save a solution to this solver's requests into its solve
+    /// cache, if it has one.
+    ///
+    /// Failures to write to the cache are logged and otherwise ignored.
+    pub async fn cache_solution(&self, solution: &Solution) {
+        let Some(cache) = self.usable_solve_cache() else {
+            return;
+        };
+        if let Err(err) = cache.insert(self, solution).await {
+            tracing::warn!("Failed to save to the solve cache: {err}");
+        }
+    }
+
+    /// Find a minimal set of this solver's package requests that
+    /// cannot be solved together, and explain why.
+    ///
+    /// Returns None if the requests can be solved. This runs a
+    /// conflict-driven solve for each request, so it is much slower
+    /// than a single solve and is meant to be used once a solve has
+    /// already failed.
+    pub async fn find_unsatisfiable_core(&self) -> Result<Option<UnsatisfiableCore>> {
+        UnsatisfiableCore::find(self).await
+    }
+
     /// Return true is any of the impossible request checks are
     /// enabled for this solver, otherwise false
     pub fn any_impossible_checks_enabled(&self) -> bool {
@@ -977,13 +1196,18 @@ impl Solver {
     }
 
     pub async fn solve(&mut self) -> Result<Solution> {
+        if let Some(solution) = self.cached_solution().await {
+            return Ok(solution);
+        }
         let mut runtime = self.run();
         {
             let iter = runtime.iter();
             tokio::pin!(iter);
             while let Some(_step) = iter.try_next().await? {}
         }
-        runtime.current_solution().await
+        let solution = runtime.current_solution().await?;
+        self.cache_solution(&solution).await;
+        Ok(solution)
     }
 
     /// Adds requests for all build requirements
@@ -1013,6 +1237,44 @@ impl Solver {
             .push(Change::SetOptions(SetOptions::new(options)))
     }
 
+    /// The validators used to check each candidate build
+    pub(crate) fn validators(&self) -> &[Validators] {
+        &self.validators
+    }
+
+    /// True if builds that would make impossible requests should be
+    /// sorted after the rest when ordering builds for selection
+    pub(crate) fn impossible_checks_in_build_keys(&self) -> bool {
+        self.impossible_checks.use_in_build_keys
+    }
+
+    /// Count a step forward taken during the solve
+    pub(crate) fn count_step(&mut self) {
+        self.number_of_steps += 1;
+    }
+
+    /// Count a build that was considered during the solve
+    pub(crate) fn count_build(&mut self) {
+        self.number_total_builds += 1;
+    }
+
+    /// Count a build that was skipped during the solve
+    pub(crate) fn count_skipped_build(&mut self) {
+        self.number_builds_skipped += 1;
+    }
+
+    /// Count a version that was incompatible with its request,
+    /// along with the given number of builds that it has
+    pub(crate) fn count_incompatible_version(&mut self, builds: usize) {
+        self.number_incompat_versions += 1;
+        self.number_incompat_builds += builds;
+    }
+
+    /// The shared count of steps back taken during the solve
+    pub(crate) fn steps_back_counter(&self) -> Arc<AtomicU64> {
+        Arc::clone(&self.number_of_steps_back)
+    }
+
     /// Get the number of steps (forward) taken in the solve
     pub fn get_number_of_steps(&self) -> usize {
         self.number_of_steps
@@ -1172,6 +1434,68 @@ impl SolverRuntime {
 
     /// Iterate through each step of this runtime, trying to converge on a solution
     pub fn iter(&mut self) -> impl Stream<Item = Result<(Arc<Node>, Arc<Decision>)>> + Send + '_ {
+        if self.solver.conflict_driven {
+            self.iter_conflict_driven().left_stream()
+        } else {
+            self.iter_steps().right_stream()
+        }
+    }
+
+    /// Iterate through the steps of the conflict-driven search
+    fn iter_conflict_driven(
+        &mut self,
+    ) -> impl Stream<Item = Result<(Arc<Node>, Arc<Decision>)>> + Send + '_ {
+        stream! {
+            if let Some(initial_decision) = self.decision.take() {
+                let mut search = ConflictSearch::new(
+                    self.graph.clone(),
+                    initial_decision,
+                    self.solver.repos.clone(),
+                    self.solver.candidate_order.clone(),
+                );
+                let mut first_iter = true;
+                loop {
+                    let step = search.step(&mut self.solver).await;
+                    self.current_node = search.current_node();
+                    match step {
+                        Ok(Some(to_yield)) => {
+                            if first_iter {
+                                // The initial requests are only known once
+                                // the first decision has been applied.
+                                first_iter = false;
+                                if self.solver.impossible_checks.check_initial_requests {
+                                    if let Some(node) = self.current_node.clone() {
+                                        let state = node.read().await.state.clone();
+                                        if let Err(err) = self.solver.check_initial_requests_for_impossible_requests(&state).await {
+                                            let cause = format!("{err}");
+                                            self.solver.increment_error_count(ErrorDetails::Message(cause));
+                                            yield Err(err);
+                                            break;
+                                        }
+                                    }
+                                }
+                            }
+                            yield Ok(to_yield);
+                        }
+                        Ok(None) => break,
+                        Err(err) => {
+                            // Unsatisfiable requests have already been
+                            // counted as they were discovered.
+                            if !matches!(err, Error::Unsatisfiable(_)) {
+                                let cause = format!("{err}");
+                                self.solver.increment_error_count(ErrorDetails::Message(cause));
+                            }
+                            yield Err(err);
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Iterate through the steps of the default search
+    fn iter_steps(&mut self) -> impl Stream<Item = Result<(Arc<Node>, Arc<Decision>)>> + Send + '_ {
         stream! {
             let mut first_iter = true;
             'outer: loop {
@@ -0,0 +1,301 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+use std::sync::Arc;
+
+use serde_json::json;
+use spk_schema::ident::{PkgRequest, Request, RequestedBy, VarRequest};
+use spk_schema::BuildIdent;
+
+use crate::conflict::{Conflict, Rejection};
+use crate::{Error, Result, Solver};
+
+#[cfg(test)]
+#[path = "./unsat_core_test.rs"]
+mod unsat_core_test;
+
+/// A minimal set of initial requests that cannot be solved
+/// together, along with the conflict that they lead to.
+///
+/// Removing any one of the requests in the core would allow the
+/// rest of them to be solved, so the core points straight at the
+/// requests that are incompatible with each other no matter how
+/// many other requests were made alongside them. This is not
+/// necessarily the smallest such set, since a different order of
+/// removal could find a different, smaller one.
+///
+/// Both the initial package requests and the initial var requests
+/// are reduced. The requirements of the packages that take part in
+/// the conflict are not: they cannot be removed from a solve without
+/// changing the packages themselves, and so are reported in full by
+/// [`Self::requirements`] instead.
+#[derive(Clone, Debug)]
+pub struct UnsatisfiableCore {
+    /// The initial package requests that conflict
+    pub requests: Vec<PkgRequest>,
+    /// The initial var requests that take part in the conflict
+    pub var_requests: Vec<VarRequest>,
+    /// Why the requests cannot be solved together
+    pub conflict: Arc<Conflict>,
+}
+
+/// A requirement of a package that takes part in a conflict
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Requirement {
+    /// The package build that has the requirement
+    pub package: BuildIdent,
+    /// The requirement, as it was made by the package
+    pub request: String,
+}
+
+impl UnsatisfiableCore {
+    /// Find a minimal set of the solver's package and var requests
+    /// that cannot be solved together.
+    ///
+    /// Each request is removed in turn and the rest are solved again
+    /// with the conflict-driven search. A request is only kept in the
+    /// core if the remaining requests can be solved without it.
+    /// Returns None if all of the requests can be solved together.
+    pub async fn find(solver: &Solver) -> Result<Option<Self>> {
+        let mut base = solver.without_initial_requests();
+        base.set_conflict_driven(true);
+        // The conflict explains impossible requests as well, and the
+        // check would fail the solve before the search could start
+        base.set_initial_request_impossible_checks(false);
+
+        let mut requests = solver.initial_requests();
+        let Some(mut conflict) = Self::conflict_of(&base, &requests).await? else {
+            return Ok(None);
+        };
+
+        let mut index = 0;
+        while index < requests.len() {
+            let mut remaining = requests.clone();
+            remaining.remove(index);
+            match Self::conflict_of(&base, &remaining).await? {
+                Some(smaller) => {
+                    requests = remaining;
+                    conflict = smaller;
+                }
+                None => index += 1,
+            }
+        }
+
+        let mut core = Self {
+            requests: Vec::new(),
+            var_requests: Vec::new(),
+            conflict,
+        };
+        for request in requests {
+            match request {
+                Request::Pkg(request) => core.requests.push(request),
+                Request::Var(request) => core.var_requests.push(request),
+            }
+        }
+        Ok(Some(core))
+    }
+
+    /// Solve the given requests, returning the conflict they lead to
+    /// or None if they can be solved.
+    async fn conflict_of(base: &Solver, requests: &[Request]) -> Result<Option<Arc<Conflict>>> {
+        let mut solver = base.clone();
+        for request in requests {
+            solver.add_request(request.clone());
+        }
+        match solver.solve().await {
+            Ok(_) => Ok(None),
+            Err(Error::Unsatisfiable(err)) => Ok(Some(err.conflict)),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Every conflict in the explanation, starting with the conflict
+    /// of the core itself and followed by the conflicts that it was
+    /// caused by, each listed once in the order they are first found.
+    pub fn conflicts(&self) -> Vec<Arc<Conflict>> {
+        let mut seen = HashSet::new();
+        let mut conflicts = Vec::new();
+        let mut pending = vec![Arc::clone(&self.conflict)];
+        while let Some(conflict) = pending.pop() {
+            if !seen.insert(Arc::as_ptr(&conflict)) {
+                continue;
+            }
+            // pushed in reverse so that causes are visited in order
+            for rejection in conflict.rejections.iter().rev() {
+                if let Some(cause) = &rejection.cause {
+                    pending.push(Arc::clone(cause));
+                }
+            }
+            conflicts.push(conflict);
+        }
+        conflicts
+    }
+
+    /// The requirements of packages that take part in the conflict
+    pub fn requirements(&self) -> Vec<Requirement> {
+        let mut requirements = Vec::new();
+        let mut add = |request: &PkgRequest| {
+            for (part, requesters) in request.requested_by.iter() {
+                for requester in requesters {
+                    let RequestedBy::PackageBuild(package) = requester else {
+                        continue;
+                    };
+                    let requirement = Requirement {
+                        package: package.clone(),
+                        request: part.clone(),
+                    };
+                    if !requirements.contains(&requirement) {
+                        requirements.push(requirement);
+                    }
+                }
+            }
+        };
+        for conflict in self.conflicts() {
+            add(&conflict.request);
+            for rejection in conflict.rejections.iter() {
+                if let Some(clash) = &rejection.conflicting_requirement {
+                    add(&clash.requirement);
+                    add(&clash.conflicts_with);
+                }
+            }
+        }
+        requirements
+    }
+
+    /// A structured form of the explanation.
+    ///
+    /// Conflicts are listed once each in the `conflicts` array, with
+    /// the first being the conflict of the core itself. Rejections
+    /// that were caused by another conflict refer to it by its index
+    /// in that array.
+    pub fn to_json(&self) -> serde_json::Value {
+        let conflicts = self.conflicts();
+        let ids = conflicts
+            .iter()
+            .enumerate()
+            .map(|(id, conflict)| (Arc::as_ptr(conflict), id))
+            .collect::<HashMap<_, _>>();
+        let required_by = |request: &PkgRequest| {
+            request
+                .requested_by
+                .iter()
+                .flat_map(|(part, requesters)| {
+                    requesters.iter().map(move |requester| {
+                        json!({"requester": requester.to_string(), "requires": part})
+                    })
+                })
+                .collect::<Vec<_>>()
+        };
+        let rejection = |rejection: &Rejection| {
+            json!({
+                "package": rejection.package.to_string(),
+                "reason": rejection.reason,
+                "cause": rejection.cause.as_ref().map(|cause| ids[&Arc::as_ptr(cause)]),
+                "conflicting_requirement": rejection.conflicting_requirement.as_ref().map(|clash| {
+                    json!({
+                        "requires": clash.requirement.pkg.to_string(),
+                        "conflicts_with": required_by(&clash.conflicts_with),
+                    })
+                }),
+            })
+        };
+        json!({
+            "requests": self.requests.iter().map(|r| r.pkg.to_string()).collect::<Vec<_>>(),
+            "var_requests": self.var_requests.iter().map(|r| r.to_string()).collect::<Vec<_>>(),
+            "requirements": self.requirements().iter().map(|requirement| {
+                json!({
+                    "package": requirement.package.to_string(),
+                    "requires": requirement.request,
+                })
+            }).collect::<Vec<_>>(),
+            "conflicts": conflicts.iter().map(|conflict| {
+                json!({
+                    "request": conflict.request.pkg.to_string(),
+                    "required_by": required_by(&conflict.request),
+                    "rejections": conflict.rejections.iter().map(&rejection).collect::<Vec<_>>(),
+                })
+            }).collect::<Vec<_>>(),
+        })
+    }
+
+    fn format_derivation(
+        conflict: &Arc<Conflict>,
+        out: &mut String,
+        depth: usize,
+        seen: &mut HashSet<*const Conflict>,
+    ) {
+        let indent = "  ".repeat(depth);
+        let _ = write!(
+            out,
+            "\n{indent}{} cannot be satisfied",
+            conflict.request.pkg
+        );
+        for line in Self::requirement_lines(&conflict.request) {
+            let _ = write!(out, "\n{indent}  {line}");
+        }
+        if conflict.rejections.is_empty() {
+            let _ = write!(
+                out,
+                "\n{indent}  no versions of {} were found in the enabled repositories",
+                conflict.request.pkg.name
+            );
+            return;
+        }
+
+        for (packages, rejection) in conflict.grouped_rejections() {
+            let _ = write!(out, "\n{indent}  {packages}: {}", rejection.reason);
+            if let Some(clash) = &rejection.conflicting_requirement {
+                let _ = write!(out, "\n{indent}    requires {}", clash.requirement.pkg);
+                for line in Self::requirement_lines(&clash.conflicts_with) {
+                    let _ = write!(out, "\n{indent}    but {line}");
+                }
+            }
+            let Some(cause) = &rejection.cause else {
+                continue;
+            };
+            if seen.insert(Arc::as_ptr(cause)) {
+                out.push_str(", because:");
+                Self::format_derivation(cause, out, depth + 2, seen);
+            } else {
+                let _ = write!(
+                    out,
+                    " (because {} cannot be satisfied, see above)",
+                    cause.request.pkg
+                );
+            }
+        }
+    }
+
+    /// Describe who made each part of a request
+    fn requirement_lines(request: &PkgRequest) -> Vec<String> {
+        let mut lines = Vec::new();
+        for (part, requesters) in request.requested_by.iter() {
+            for requester in requesters {
+                let line = format!("{requester} requires {part}");
+                if !lines.contains(&line) {
+                    lines.push(line);
+                }
+            }
+        }
+        lines
+    }
+}
+
+impl std::fmt::Display for UnsatisfiableCore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut out = String::from("These requests cannot be solved together:");
+        for request in self.requests.iter() {
+            let _ = write!(out, "\n  {}", request.pkg);
+        }
+        for request in self.var_requests.iter() {
+            let _ = write!(out, "\n  {request}");
+        }
+        out.push_str("\nBecause:");
+        let mut seen = HashSet::from([Arc::as_ptr(&self.conflict)]);
+        Self::format_derivation(&self.conflict, &mut out, 1, &mut seen);
+        f.write_str(&out)
+    }
+}
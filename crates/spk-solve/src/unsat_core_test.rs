@@ -0,0 +1,175 @@
+// Copyright (c) Contributors to the SPK project.
+// SPDX-License-Identifier: Apache-2.0
+// https://github.com/spkenv/spk
+
+use std::sync::Arc;
+
+use rstest::rstest;
+use spk_schema::prelude::*;
+use spk_solve_macros::{make_repo, request};
+
+use crate::Solver;
+
+async fn conflicting_repo() -> spk_storage::RepositoryHandle {
+    make_repo!(
+        [
+            {"pkg": "dep/1.0.0"},
+            {"pkg": "dep/2.0.0"},
+            {"pkg": "unrelated/1.0.0"},
+            {"pkg": "other/1.0.0", "install": {"requirements": [{"pkg": "unrelated/1"}]}},
+            {"pkg": "pkg-a/1.0.0", "install": {"requirements": [{"pkg": "dep/1"}]}},
+            {"pkg": "pkg-b/2.0.0", "install": {"requirements": [{"pkg": "dep/2"}]}},
+        ]
+    )
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_unsat_core_drops_unrelated_requests() {
+    let mut solver = Solver::default();
+    solver.add_repository(Arc::new(conflicting_repo().await));
+    solver.add_request(request!("unrelated"));
+    solver.add_request(request!("pkg-a/1"));
+    solver.add_request(request!("other"));
+    solver.add_request(request!("pkg-b/2"));
+
+    let core = solver
+        .find_unsatisfiable_core()
+        .await
+        .unwrap()
+        .expect("expected the requests to conflict");
+    let names = core
+        .requests
+        .iter()
+        .map(|request| request.pkg.name.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec!["pkg-a", "pkg-b"]);
+
+    let requirements = core
+        .requirements()
+        .into_iter()
+        .map(|requirement| format!("{} {}", requirement.package.name(), requirement.request))
+        .collect::<Vec<_>>();
+    assert!(
+        requirements
+            .iter()
+            .any(|r| r.starts_with("pkg-a dep") && r.ends_with("/1")),
+        "expected the requirement of pkg-a to be part of the explanation, got: {requirements:?}"
+    );
+    assert!(
+        requirements
+            .iter()
+            .any(|r| r.starts_with("pkg-b dep") && r.ends_with("/2")),
+        "expected the requirement of pkg-b to be part of the explanation, got: {requirements:?}"
+    );
+
+    let message = core.to_string();
+    assert!(
+        message.starts_with(
+            "These requests cannot be solved together:\n  pkg-a:run/1\n  pkg-b:run/2\n"
+        ),
+        "expected the core to be listed first, got: {message}"
+    );
+    assert!(
+        !message.contains("unrelated"),
+        "expected unrelated requests to be left out, got: {message}"
+    );
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_unsat_core_reduces_var_requests() {
+    let repo = make_repo!(
+        [
+            {"pkg": "unrelated/1.0.0"},
+            {
+                "pkg": "my-tool/1.2.0",
+                "build": {"options": [{"var": "debug"}], "script": "echo BUILD"},
+            },
+        ],
+        options={"debug" => "off"}
+    );
+    let mut solver = Solver::default();
+    solver.add_repository(Arc::new(repo));
+    solver.add_request(request!({"var": "color/red"}));
+    solver.add_request(request!({"var": "debug/on"}));
+    solver.add_request(request!("unrelated"));
+    solver.add_request(request!("my-tool"));
+
+    let core = solver
+        .find_unsatisfiable_core()
+        .await
+        .unwrap()
+        .expect("expected the requests to conflict");
+    let names = core
+        .requests
+        .iter()
+        .map(|request| request.pkg.name.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec!["my-tool"]);
+    let vars = core
+        .var_requests
+        .iter()
+        .map(|request| request.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(vars, vec!["var: debug/on"]);
+
+    let message = core.to_string();
+    assert!(
+        message.contains("\n  var: debug/on\n"),
+        "expected the var request to be listed in the core, got: {message}"
+    );
+    assert!(
+        !message.contains("color"),
+        "expected unrelated var requests to be left out, got: {message}"
+    );
+    assert_eq!(
+        core.to_json()["var_requests"],
+        serde_json::json!(["var: debug/on"])
+    );
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_unsat_core_of_solvable_requests() {
+    let mut solver = Solver::default();
+    solver.add_repository(Arc::new(conflicting_repo().await));
+    solver.add_request(request!("pkg-a/1"));
+    solver.add_request(request!("other"));
+
+    let core = solver.find_unsatisfiable_core().await.unwrap();
+    assert!(core.is_none(), "expected the requests to be solvable");
+}
+
+#[rstest]
+#[tokio::test]
+async fn test_unsat_core_json() {
+    let mut solver = Solver::default();
+    solver.add_repository(Arc::new(conflicting_repo().await));
+    solver.add_request(request!("pkg-a/1"));
+    solver.add_request(request!("pkg-b/2"));
+
+    let core = solver
+        .find_unsatisfiable_core()
+        .await
+        .unwrap()
+        .expect("expected the requests to conflict");
+    let data = core.to_json();
+    assert_eq!(
+        data["requests"],
+        serde_json::json!(["pkg-a:run/1", "pkg-b:run/2"])
+    );
+
+    let conflicts = data["conflicts"].as_array().unwrap();
+    assert_eq!(conflicts.len(), core.conflicts().len());
+    for conflict in conflicts {
+        for rejection in conflict["rejections"].as_array().unwrap() {
+            if let Some(cause) = rejection["cause"].as_u64() {
+                assert!(
+                    (cause as usize) < conflicts.len(),
+                    "expected causes to refer to a listed conflict"
+                );
+            }
+        }
+    }
+}
@@ -3,7 +3,7 @@
 // https://github.com/spkenv/spk
 
 use miette::Diagnostic;
-use spk_schema::{AnyIdent, VersionIdent};
+use spk_schema::{AnyIdent, BuildIdent, VersionIdent};
 use thiserror::Error;
 
 pub type Result<T> = std::result::Result<T, Error>;
@@ -36,6 +36,16 @@ pub enum Error {
     PackageNotFound(AnyIdent),
     #[error("Version exists: {0}")]
     VersionExists(VersionIdent),
+    #[error("Package {pkg} is not signed by a trusted key")]
+    #[diagnostic(
+        code("spk::untrusted_package"),
+        help("This repository requires that the layers of all packages are signed")
+    )]
+    UntrustedPackage {
+        pkg: BuildIdent,
+        #[source]
+        source: spfs::Error,
+    },
     #[error(transparent)]
     #[diagnostic(forward(0))]
     SPFS(#[from] spfs::Error),
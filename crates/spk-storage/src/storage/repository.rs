@@ -335,6 +335,24 @@ pub trait Repository: Storage + Sync {
         }
     }
 
+    /// Return a fingerprint of the published state of a package build.
+    ///
+    /// The fingerprint changes whenever the build is republished,
+    /// deprecated or otherwise modified, and is only meant to be
+    /// compared to other fingerprints of the same build in the
+    /// same repository.
+    ///
+    /// # Errors:
+    /// - PackageNotFound: If the package, version, or build does not exist
+    async fn read_package_fingerprint(&self, pkg: &BuildIdent) -> Result<String> {
+        use std::hash::{Hash, Hasher};
+
+        let package = self.read_package(pkg).await?;
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        package.hash(&mut hasher);
+        Ok(format!("{:016x}", hasher.finish()))
+    }
+
     /// Publish a package to this repository.
     ///
     /// The provided component digests are expected to each identify an spfs
@@ -249,6 +249,45 @@ async fn test_repo_publish_spec_updates_embed_stubs(#[case] repo: RepoKind) {
         .any(|pkg| pkg == "my-embedded-pkg2"));
 }
 
+#[rstest]
+#[case::mem(RepoKind::Mem)]
+#[case::spfs(RepoKind::Spfs)]
+#[tokio::test]
+async fn test_repo_package_fingerprint(#[case] repo: RepoKind) {
+    let repo = make_repo(repo).await;
+    let mut spec = spec!({"pkg": "my-pkg/1.0.0/3I42H3S6"});
+    let components = vec![(Component::Run, empty_layer_digest())]
+        .into_iter()
+        .collect();
+    repo.publish_package(&spec, &components).await.unwrap();
+
+    let original = repo.read_package_fingerprint(spec.ident()).await.unwrap();
+    assert_eq!(
+        repo.read_package_fingerprint(spec.ident()).await.unwrap(),
+        original,
+        "fingerprint should be stable while the package is unchanged"
+    );
+
+    spec.deprecate().unwrap();
+    repo.update_package(&spec).await.unwrap();
+    let deprecated = repo.read_package_fingerprint(spec.ident()).await.unwrap();
+    assert_ne!(
+        deprecated, original,
+        "deprecating should change the fingerprint"
+    );
+
+    let spec = spec!({
+        "pkg": "my-pkg/1.0.0/3I42H3S6",
+        "install": {"requirements": [{"pkg": "dep/1"}]}
+    });
+    repo.publish_package(&spec, &components).await.unwrap();
+    let republished = repo.read_package_fingerprint(spec.ident()).await.unwrap();
+    assert_ne!(
+        republished, original,
+        "republishing with new requirements should change the fingerprint"
+    );
+}
+
 #[rstest]
 #[case::mem(RepoKind::Mem)]
 #[case::spfs(RepoKind::Spfs)]
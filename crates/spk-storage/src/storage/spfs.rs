@@ -81,6 +81,9 @@ pub struct SpfsRepository<S> {
     caches: CachesForAddress,
     tag_strategy: PhantomData<S>,
     legacy_spk_version_tags: bool,
+    /// When set, the layers of packages read from this repository
+    /// must be signed by one of these keys
+    trusted_keys: Option<Arc<Vec<spfs::signing::TrustedKey>>>,
 }
 
 impl<S> std::hash::Hash for SpfsRepository<S> {
@@ -168,12 +171,16 @@ where
             cache_policy: Arc::new(ArcSwap::new(Arc::new(CachePolicy::CacheOk))),
             tag_strategy: PhantomData,
             legacy_spk_version_tags: cfg!(feature = "legacy-spk-version-tags"),
+            trusted_keys: None,
         })
     }
 }
 
 impl<S> SpfsRepository<S> {
     pub async fn new(name: &str, address: &str) -> Result<Self> {
+        let require_signatures = url::Url::parse(address)
+            .map(|url| spfs::config::address_requires_signatures(&url))
+            .unwrap_or_default();
         let inner = spfs::open_repository(address).await?;
         let address = inner.address();
         Ok(Self {
@@ -184,6 +191,7 @@ impl<S> SpfsRepository<S> {
             cache_policy: Arc::new(ArcSwap::new(Arc::new(CachePolicy::CacheOk))),
             tag_strategy: PhantomData,
             legacy_spk_version_tags: cfg!(feature = "legacy-spk-version-tags"),
+            trusted_keys: trusted_keys_if_required(require_signatures)?,
         })
     }
 
@@ -208,6 +216,24 @@ impl<S> SpfsRepository<S> {
     pub fn set_legacy_spk_version_tags(&mut self, enabled: bool) {
         self.legacy_spk_version_tags = enabled;
     }
+
+    /// Require that the layers of all packages read from this repository
+    /// are signed by one of the given keys, or remove that requirement
+    pub fn set_trusted_keys(&mut self, trusted_keys: Option<Vec<spfs::signing::TrustedKey>>) {
+        self.trusted_keys = trusted_keys.map(Arc::new);
+    }
+}
+
+/// Load the trusted keys from the spfs config, if signatures are required.
+fn trusted_keys_if_required(
+    require_signatures: bool,
+) -> Result<Option<Arc<Vec<spfs::signing::TrustedKey>>>> {
+    if !require_signatures {
+        return Ok(None);
+    }
+    let config = spfs::get_config()?;
+    let trusted_keys = spfs::signing::load_trusted_keys(&config.signing)?;
+    Ok(Some(Arc::new(trusted_keys)))
 }
 
 #[derive(Clone)]
@@ -523,6 +549,14 @@ where
         let mut components = HashMap::with_capacity(component_tags.len());
         for (name, tag_spec) in component_tags.into_iter() {
             let tag = self.resolve_tag(|| pkg.to_any(), &tag_spec).await?;
+            if let Some(trusted_keys) = &self.trusted_keys {
+                spfs::signing::ensure_trusted(&*self.inner, tag.target, trusted_keys)
+                    .await
+                    .map_err(|source| Error::UntrustedPackage {
+                        pkg: pkg.clone(),
+                        source,
+                    })?;
+            }
             components.insert(name, tag.target);
         }
         Ok(components)
@@ -771,6 +805,14 @@ where
         &self.name
     }
 
+    async fn read_package_fingerprint(&self, pkg: &BuildIdent) -> Result<String> {
+        // the spec tag points at a new blob whenever the spec of
+        // the build is changed, which includes deprecating it, so
+        // the spec itself does not need to be read
+        self.with_build_spec_tag_for_pkg(pkg, |_, _, tag| async move { Ok(tag.target.to_string()) })
+            .await
+    }
+
     async fn read_embed_stub(&self, pkg: &BuildIdent) -> Result<Arc<Self::Package>> {
         // This is similar to read_recipe but it returns a package and
         // uses the package cache.
@@ -1317,6 +1359,7 @@ pub async fn local_repository() -> Result<SpfsRepository<NormalizedTagStrategy>>
         cache_policy: Arc::new(ArcSwap::new(Arc::new(CachePolicy::CacheOk))),
         tag_strategy: PhantomData,
         legacy_spk_version_tags: cfg!(feature = "legacy-spk-version-tags"),
+        trusted_keys: None,
     })
 }
 
@@ -1337,5 +1380,6 @@ pub async fn remote_repository<S: AsRef<str>, TagStrategy>(
         cache_policy: Arc::new(ArcSwap::new(Arc::new(CachePolicy::CacheOk))),
         tag_strategy: PhantomData,
         legacy_spk_version_tags: cfg!(feature = "legacy-spk-version-tags"),
+        trusted_keys: trusted_keys_if_required(config.remote_requires_signatures(&name))?,
     })
 }
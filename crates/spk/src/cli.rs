@@ -12,7 +12,7 @@ use miette::{Context, Result};
 #[cfg(feature = "sentry")]
 use spk_cli_common::configure_sentry;
 use spk_cli_common::{configure_logging, CommandArgs, Error, Run};
-use spk_cli_group1::{cmd_bake, cmd_completion, cmd_deprecate, cmd_undeprecate};
+use spk_cli_group1::{cmd_bake, cmd_completion, cmd_deprecate, cmd_lock, cmd_undeprecate};
 use spk_cli_group2::{cmd_ls, cmd_new, cmd_num_variants, cmd_publish, cmd_remove};
 use spk_cli_group3::{cmd_export, cmd_import};
 use spk_cli_group4::{cmd_lint, cmd_search, cmd_version, cmd_view};
@@ -153,6 +153,7 @@ pub enum Command {
     Import(cmd_import::Import),
     Install(cmd_install::Install),
     Lint(cmd_lint::Lint),
+    Lock(cmd_lock::Lock),
     Ls(cmd_ls::Ls),
     MakeBinary(cmd_make_binary::MakeBinary),
     MakeSource(cmd_make_source::MakeSource),
@@ -193,6 +194,7 @@ impl Run for Command {
             Command::Import(cmd) => cmd.run().await,
             Command::Install(cmd) => cmd.run().await,
             Command::Lint(cmd) => cmd.run().await,
+            Command::Lock(cmd) => cmd.run().await,
             Command::Ls(cmd) => cmd.run().await,
             Command::MakeBinary(cmd) => cmd.run().await,
             Command::MakeSource(cmd) => cmd.run().await,
@@ -228,6 +230,7 @@ impl CommandArgs for Command {
             Command::Import(cmd) => cmd.get_positional_args(),
             Command::Install(cmd) => cmd.get_positional_args(),
             Command::Lint(cmd) => cmd.get_positional_args(),
+            Command::Lock(cmd) => cmd.get_positional_args(),
             Command::Ls(cmd) => cmd.get_positional_args(),
             Command::MakeBinary(cmd) => cmd.get_positional_args(),
             Command::MakeSource(cmd) => cmd.get_positional_args(),